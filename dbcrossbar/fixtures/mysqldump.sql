@@ -0,0 +1,38 @@
+-- MySQL dump 10.13  Distrib 8.0.32, for Linux (x86_64)
+--
+-- Host: localhost    Database: shop
+-- ------------------------------------------------------
+
+/*!40101 SET @OLD_CHARACTER_SET_CLIENT=@@CHARACTER_SET_CLIENT */;
+/*!40101 SET NAMES utf8mb4 */;
+
+--
+-- Table structure for table `customers`
+--
+
+DROP TABLE IF EXISTS `customers`;
+/*!40101 SET @saved_cs_client     = @@character_set_client */;
+/*!50503 SET character_set_client = utf8mb4 */;
+CREATE TABLE `customers` (
+  `id` int unsigned NOT NULL AUTO_INCREMENT,
+  `name` varchar(100) COLLATE utf8mb4_unicode_ci NOT NULL COMMENT 'Full name',
+  `is_active` tinyint(1) NOT NULL DEFAULT '1',
+  `tier` enum('free','pro') COLLATE utf8mb4_unicode_ci DEFAULT 'free',
+  `balance` decimal(10,2) DEFAULT NULL,
+  `signed_up_at` datetime DEFAULT NULL,
+  `avatar` blob,
+  PRIMARY KEY (`id`),
+  KEY `name_idx` (`name`)
+) ENGINE=InnoDB AUTO_INCREMENT=4 DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_unicode_ci;
+/*!40101 SET character_set_client = @saved_cs_client */;
+
+--
+-- Dumping data for table `customers`
+--
+
+LOCK TABLES `customers` WRITE;
+/*!40000 ALTER TABLE `customers` DISABLE KEYS */;
+INSERT INTO `customers` VALUES (1,'Ann O\'Neil',1,'pro',12.50,'2020-01-02 03:04:05',0x4869),(2,'Bob \"Bobby\" Smith',0,'free',NULL,'0000-00-00 00:00:00',NULL);
+INSERT INTO `customers` VALUES (3,'Line\nBreak',1,NULL,-1.00,NULL,'');
+/*!40000 ALTER TABLE `customers` ENABLE KEYS */;
+UNLOCK TABLES;
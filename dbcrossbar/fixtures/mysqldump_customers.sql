@@ -0,0 +1,12 @@
+CREATE TABLE "customers" (
+    "id" bigint NOT NULL,
+    "name" character varying(100) NOT NULL,
+    "is_active" boolean DEFAULT 't' NOT NULL,
+    "tier" text DEFAULT 'free' CHECK ("tier" IN ('free', 'pro')),
+    "balance" numeric(10,2),
+    "signed_up_at" timestamp without time zone,
+    "avatar" bytea,
+    PRIMARY KEY ("id")
+);
+COMMENT ON COLUMN "customers"."name" IS 'Full name';
+CREATE INDEX "customers_name_idx" ON "customers" ("name");
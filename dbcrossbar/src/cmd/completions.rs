@@ -0,0 +1,135 @@
+//! The `completions` subcommand.
+
+use clap::Shell;
+use common_failures::Result;
+use dbcrossbarlib::{drivers::all_drivers, Context, Error};
+use failure::format_err;
+use std::{io::Write, result, str::FromStr};
+use structopt::{self, StructOpt};
+
+/// Completion arguments.
+#[derive(Debug, StructOpt)]
+pub(crate) struct Opt {
+    /// The shell to generate completions for (bash, zsh, fish).
+    shell: CompletionShell,
+}
+
+/// Which shell should we generate completions for?
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum CompletionShell {
+    /// GNU bash.
+    Bash,
+    /// The Z shell.
+    Zsh,
+    /// The friendly interactive shell.
+    Fish,
+}
+
+impl FromStr for CompletionShell {
+    type Err = Error;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(CompletionShell::Bash),
+            "zsh" => Ok(CompletionShell::Zsh),
+            "fish" => Ok(CompletionShell::Fish),
+            _ => Err(format_err!("unknown shell: {}", s)),
+        }
+    }
+}
+
+/// Print a shell completion script.
+pub(crate) async fn run(_ctx: Context, opt: Opt) -> Result<()> {
+    let mut schemes = all_drivers().iter().map(|d| d.scheme()).collect::<Vec<_>>();
+    schemes.push("alias:");
+    let schemes = schemes.join(" ");
+    let script = completion_script(opt.shell, &schemes)?;
+    std::io::stdout().write_all(script.as_bytes())?;
+    Ok(())
+}
+
+/// Generate a completion script for `shell` using `clap`, and then teach it to
+/// complete the locator `schemes` (a space-separated list like `csv:
+/// postgres:`), because locators are easy to mistype.
+fn completion_script(shell: CompletionShell, schemes: &str) -> Result<String> {
+    let clap_shell = match shell {
+        CompletionShell::Bash => Shell::Bash,
+        CompletionShell::Zsh => Shell::Zsh,
+        CompletionShell::Fish => Shell::Fish,
+    };
+    let mut script = vec![];
+    super::Opt::clap().gen_completions_to("dbcrossbar", clap_shell, &mut script);
+    let script = String::from_utf8(script)?;
+
+    match shell {
+        // Wrap the generated function, and add schemes to any argument which
+        // isn't an option or an option's value.
+        CompletionShell::Bash => Ok(format!(
+            r#"{script}
+_dbcrossbar_with_locators() {{
+    _dbcrossbar "$@"
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    local prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    if [[ $COMP_CWORD -lt 2 || "$cur" == -* ]]; then
+        return
+    fi
+    case "$prev" in
+        --schema|--backup-to|--also-to|--state|-*=*) ;;
+        -*) return ;;
+    esac
+    local schemes
+    schemes=( $(compgen -W "{schemes}" -- "$cur") )
+    if [[ ${{#schemes[@]}} -gt 0 ]]; then
+        COMPREPLY+=( "${{schemes[@]}}" )
+        compopt -o nospace
+    fi
+}}
+
+complete -F _dbcrossbar_with_locators -o bashdefault -o default dbcrossbar
+"#,
+            script = script.trim_end(),
+            schemes = schemes,
+        )),
+
+        // Complete positional locator arguments using our own function.
+        CompletionShell::Zsh => {
+            let mut out = String::new();
+            for line in script.lines() {
+                let is_locator = (line.starts_with("':") || line.starts_with("'::"))
+                    && line
+                        .split(" -- ")
+                        .next()
+                        .map(|name| name.ends_with("locator") || name.ends_with("locators"))
+                        .unwrap_or(false);
+                if is_locator && line.ends_with(":_files' \\") {
+                    out.push_str(line.trim_end_matches(":_files' \\"));
+                    out.push_str(":_dbcrossbar_locators' \\");
+                } else if line == r#"_dbcrossbar "$@""# {
+                    out.push_str(&format!(
+                        r#"(( $+functions[_dbcrossbar_locators] )) ||
+_dbcrossbar_locators() {{
+    local schemes; schemes=({schemes})
+    compadd -S '' -a schemes
+    _files
+}}
+
+"#,
+                        schemes = schemes,
+                    ));
+                    out.push_str(line);
+                } else {
+                    out.push_str(line);
+                }
+                out.push('\n');
+            }
+            Ok(out)
+        }
+
+        // fish won't add a space after a completion ending in `:`.
+        CompletionShell::Fish => Ok(format!(
+            "{}\ncomplete -c dbcrossbar -n \"not __fish_use_subcommand\" -a \"{}\" -d 'Locator scheme'\n",
+            script.trim_end(),
+            schemes,
+        )),
+    }
+}
@@ -1,10 +1,16 @@
 //! The `conv` subcommand.
 
 use common_failures::Result;
-use dbcrossbarlib::{BoxLocator, Context, IfExists};
+use dbcrossbarlib::{
+    sample_data::sample_csv_stream, BoxLocator, Context, DestinationArguments,
+    DriverArguments, IfExists, SharedArguments, TemporaryStorage,
+};
 use failure::format_err;
+use futures::{future, stream, StreamExt, TryStreamExt};
 use structopt::{self, StructOpt};
 
+use crate::cmd::check_read_only_dest;
+
 /// Schema conversion arguments.
 #[derive(Debug, StructOpt)]
 pub(crate) struct Opt {
@@ -12,6 +18,14 @@ pub(crate) struct Opt {
     #[structopt(long = "if-exists", default_value = "error")]
     if_exists: IfExists,
 
+    /// Also generate this many rows of sample data matching the schema.
+    #[structopt(long = "with-sample-rows", requires = "sample-rows-to")]
+    with_sample_rows: Option<usize>,
+
+    /// Where to write sample data (for example, `csv:sample.csv`).
+    #[structopt(long = "sample-rows-to", requires = "with-sample-rows")]
+    sample_rows_to: Option<BoxLocator>,
+
     /// The input schema.
     from_locator: BoxLocator,
 
@@ -19,13 +33,54 @@ pub(crate) struct Opt {
     to_locator: BoxLocator,
 }
 
+impl Opt {
+    /// Fail if this conversion would write anywhere but standard output.
+    pub(crate) fn check_read_only(&self) -> Result<()> {
+        if let Some(sample_rows_to) = &self.sample_rows_to {
+            check_read_only_dest(sample_rows_to)?;
+        }
+        check_read_only_dest(&self.to_locator)
+    }
+}
+
 /// Perform our schema conversion.
 pub(crate) async fn run(ctx: Context, opt: Opt) -> Result<()> {
-    let schema = opt.from_locator.schema(ctx.clone()).await?.ok_or_else(|| {
-        format_err!("don't know how to read schema from {}", opt.from_locator)
-    })?;
+    let schemas = opt
+        .from_locator
+        .schemas(ctx.clone())
+        .await?
+        .ok_or_else(|| {
+            format_err!("don't know how to read schema from {}", opt.from_locator)
+        })?;
     opt.to_locator
-        .write_schema(ctx, schema, opt.if_exists)
+        .write_schemas(ctx.clone(), schemas.clone(), opt.if_exists.clone())
         .await?;
+
+    // Write sample data, if requested.
+    if let (Some(rows), Some(sample_rows_to)) =
+        (opt.with_sample_rows, opt.sample_rows_to)
+    {
+        let schema = match &schemas[..] {
+            [schema] => schema.to_owned(),
+            _ => {
+                return Err(format_err!(
+                    "cannot generate sample rows for {} tables",
+                    schemas.len(),
+                ))
+            }
+        };
+        let data =
+            stream::once(future::ready(sample_csv_stream(&schema, rows))).boxed();
+        let shared_args =
+            SharedArguments::new(schema, TemporaryStorage::new(vec![]), 1);
+        let dest_args =
+            DestinationArguments::new(DriverArguments::default(), opt.if_exists);
+        sample_rows_to
+            .write_local_data(ctx, data, shared_args, dest_args)
+            .await?
+            .try_buffer_unordered(1)
+            .try_collect::<Vec<_>>()
+            .await?;
+    }
     Ok(())
 }
@@ -28,6 +28,11 @@ pub(crate) struct Opt {
     #[structopt(long = "where")]
     where_clause: Option<String>,
 
+    /// When counting rows locally, how many data streams should we read in
+    /// parallel?
+    #[structopt(long = "max-streams", short = "J", default_value = "4")]
+    max_streams: usize,
+
     /// The locator specifying the records to count.
     locator: BoxLocator,
 }
@@ -46,10 +51,11 @@ pub(crate) async fn run(ctx: Context, opt: Opt) -> Result<()> {
             })
     }?;
 
-    // Build our shared arguments. Specify 1 for `max_streams` until we actually
-    // implement local counting.
+    // Build our shared arguments. `max_streams` is only used by drivers which
+    // count rows by reading them.
     let temporary_storage = TemporaryStorage::new(opt.temporaries.clone());
-    let shared_args = SharedArguments::new(schema, temporary_storage, 1);
+    let shared_args =
+        SharedArguments::new(schema, temporary_storage, opt.max_streams.max(1));
 
     // Build our source arguments.
     let from_args = DriverArguments::from_cli_args(&opt.from_args)?;
@@ -2,22 +2,55 @@
 
 use common_failures::Result;
 use dbcrossbarlib::{
-    rechunk::rechunk_csvs, tokio_glue::try_forward, BoxLocator, Context,
-    DestinationArguments, DisplayOutputLocators, DriverArguments, IfExists,
-    SharedArguments, SourceArguments, TemporaryStorage,
+    cast::ColumnCast,
+    check_headers::check_csv_headers,
+    column_transform::{transform_csv_columns, ColumnTransform},
+    dedupe::{dedupe_csv_streams, DedupeKeep, DedupeKeys},
+    drivers::{csv, find_driver},
+    empty_streams::fill_empty_csv_streams,
+    rechunk::rechunk_csvs,
+    rename::{rename_csv_headers, ColumnRenames},
+    schema::Table,
+    select::ColumnSelection,
+    tee::tee_csv_streams,
+    tokio_glue::{try_forward, BoxStream},
+    union::union_csv_streams,
+    usage::{count_csv_streams, driver_name, Direction},
+    BoxLocator, BufferWithParallelism, Context, CsvStream, DestinationArguments,
+    DisplayOutputLocators, DriverArguments, IfExists, MergeDelete, SharedArguments,
+    SourceArguments, TemporaryStorage, Unverified,
+};
+use failure::{format_err, Fail, ResultExt};
+use futures::{
+    future, lock::Mutex, pin_mut, stream, FutureExt, StreamExt, TryStreamExt,
 };
-use failure::{format_err, ResultExt};
-use futures::{pin_mut, stream, FutureExt, StreamExt, TryStreamExt};
 use humanize_rs::bytes::Bytes as HumanizedBytes;
-use slog::{debug, o};
+use slog::{debug, o, warn};
+use std::{
+    collections::HashSet,
+    fmt, fs,
+    io::{self as std_io, Write},
+    iter, mem,
+    path::PathBuf,
+    sync::Arc,
+    time::Instant,
+};
 use structopt::{self, StructOpt};
 use tokio::io;
 use tokio_util::codec::{FramedWrite, LinesCodec};
 
+use crate::cmd::check_read_only_dest;
+use crate::jobs::{BackupRecord, JobManifest};
+use crate::lineage::LineageEvent;
+use crate::regions::{regional_locator, regional_temporaries};
+use crate::resume::ResumeFile;
+use crate::table_filter::TableFilter;
+
 /// Schema conversion arguments.
 #[derive(Debug, StructOpt)]
 pub(crate) struct Opt {
-    /// One of `error`, `overwrite`, `append` or `upsert-on:COL`.
+    /// One of `error`, `overwrite`, `append`, `upsert-on:COL` or
+    /// `merge-on:COL`.
     #[structopt(long = "if-exists", default_value = "error")]
     if_exists: IfExists,
 
@@ -37,6 +70,11 @@ pub(crate) struct Opt {
     #[structopt(long = "stream-size")]
     stream_size: Option<HumanizedBytes>, // usize
 
+    /// Split the CSV streams manipulated by `dbcrossbar` so that each one
+    /// contains at most this many rows. May be combined with `--stream-size`.
+    #[structopt(long = "stream-rows")]
+    stream_rows: Option<usize>,
+
     /// Pass an extra argument of the form `key=value` to the source driver.
     #[structopt(long = "from-arg")]
     from_args: Vec<String>,
@@ -50,57 +88,1239 @@ pub(crate) struct Opt {
     #[structopt(long = "where")]
     where_clause: Option<String>,
 
+    /// Only copy these columns, using a list of the form `col1,col2`.
+    #[structopt(long = "select")]
+    select: Option<ColumnSelection>,
+
+    /// Only copy the first N rows.
+    #[structopt(long = "limit", value_name = "N")]
+    limit: Option<usize>,
+
+    /// SQL expression specifying the order in which to read rows, like
+    /// `created_at DESC, id` (database sources only).
+    #[structopt(long = "order-by")]
+    order_by: Option<String>,
+
     /// How many data streams should we attempt to copy in parallel?
     #[structopt(long = "max-streams", short = "J", default_value = "4")]
     max_streams: usize,
 
+    /// Start by copying one data stream at a time, and adjust the number of
+    /// streams based on throughput, never going above `--max-streams`.
+    #[structopt(long = "adaptive-streams")]
+    adaptive_streams: bool,
+
+    /// Write data streams one at a time, in the order they were read, so that
+    /// appending to a file or table produces the same row order every time.
+    /// This overrides `--max-streams`, and may be much slower.
+    #[structopt(long = "preserve-order")]
+    preserve_order: bool,
+
+    /// Change the type of a column, using `column:type`, where `type` is a
+    /// PostgreSQL type like `text` or `numeric(12,2)` (can be repeated).
+    #[structopt(long = "cast")]
+    casts: Vec<ColumnCast>,
+
+    /// Replace the value of a column while copying, using `column=expr`,
+    /// where `expr` may call functions like `lower`, `trim` or `replace`
+    /// (can be repeated).
+    #[structopt(long = "transform")]
+    column_transforms: Vec<ColumnTransform>,
+
+    /// Rename columns while copying, using a list of the form
+    /// `old:new,old2:new2`.
+    #[structopt(long = "rename")]
+    rename: Option<ColumnRenames>,
+
+    /// Rename columns while copying, using a JSON file containing an object
+    /// which maps old column names to new ones.
+    #[structopt(long = "rename-file", parse(from_os_str))]
+    rename_file: Option<PathBuf>,
+
+    /// Keep only one row for each value of these columns, using a list of the
+    /// form `col1,col2`. This combines all input streams into one.
+    #[structopt(long = "dedupe-on")]
+    dedupe_on: Option<DedupeKeys>,
+
+    /// When using `--dedupe-on`, keep the `first` or `last` row with each key.
+    #[structopt(long = "dedupe-keep", default_value = "last")]
+    dedupe_keep: DedupeKeep,
+
     /// Display where we wrote our output data.
     #[structopt(long = "display-output-locators")]
     display_output_locators: bool,
 
-    /// The input table.
+    /// Before overwriting or upserting into the output table, copy its
+    /// current contents to this locator. If the input is a dataset, this
+    /// should contain `{table}`.
+    #[structopt(long = "backup-to")]
+    backup_to: Option<BoxLocator>,
+
+    /// After each table is copied, append an OpenLineage event describing the
+    /// copy to this file.
+    #[structopt(long = "lineage-to", parse(from_os_str))]
+    lineage_to: Option<PathBuf>,
+
+    /// Record each stream we finish writing in this file, and skip any streams
+    /// it already lists. If a copy fails, re-running it with the same file
+    /// picks up where it left off.
+    #[structopt(long = "resume", parse(from_os_str))]
+    resume: Option<PathBuf>,
+
+    /// When appending or upserting, accept CSV input whose columns are in a
+    /// different order than the schema, and reorder them to match.
+    #[structopt(long = "allow-reordered-columns")]
+    allow_reordered_columns: bool,
+
+    /// If the input contains no data, create an empty output table using
+    /// `--schema`. If there is no `--schema`, skip the input with a warning,
+    /// and exit with status 3 once everything else has been copied.
+    #[structopt(long = "allow-empty")]
+    allow_empty: bool,
+
+    /// After copying to a BigQuery or Google Cloud Storage destination, also
+    /// copy the result to a sibling dataset or bucket in each of these
+    /// comma-separated regions.
+    #[structopt(long = "replicate-to-regions", use_delimiter = true)]
+    replicate_to_regions: Vec<String>,
+
+    /// Before copying, print a summary of what we're about to do, and ask for
+    /// confirmation.
+    #[structopt(long = "confirm")]
+    confirm: bool,
+
+    /// Answer "yes" to the --confirm prompt. This is required when standard
+    /// input isn't a terminal.
+    #[structopt(long = "yes", short = "y")]
+    yes: bool,
+
+    /// Print what we would do, including any SQL we would run, without copying
+    /// any data or changing anything.
+    #[structopt(long = "dry-run")]
+    dry_run: bool,
+
+    /// When copying a database or dataset, only copy tables whose names match
+    /// this glob pattern (can be repeated).
+    #[structopt(long = "include-table")]
+    include_tables: Vec<String>,
+
+    /// When copying a database or dataset, skip tables whose names match this
+    /// glob pattern (can be repeated).
+    #[structopt(long = "exclude-table")]
+    exclude_tables: Vec<String>,
+
+    /// When copying a database or dataset, how many tables should we copy at
+    /// once?
+    #[structopt(long = "max-parallel-tables", default_value = "1")]
+    max_parallel_tables: usize,
+
+    /// Also write the output to this table (can be repeated). The input will
+    /// only be read once, and the data will be written to every output at the
+    /// same time.
+    #[structopt(long = "also-to")]
+    also_to: Vec<BoxLocator>,
+
+    /// The input table, or a database or dataset containing multiple tables.
     from_locator: BoxLocator,
 
-    /// The output table.
+    /// The output table. If the input contains multiple tables, this should
+    /// either be a database or dataset, or contain `{table}`, which will be
+    /// replaced by the name of each input table.
     to_locator: BoxLocator,
+
+    // clap won't allow a variable number of positional arguments before a
+    // required one, so we parse the last locator here and then move it to
+    // `to_locator` using `arrange_locators`.
+    /// To append several input tables into one output, list the inputs
+    /// followed by the output, as in `cp IN1 IN2 IN3 OUT`. The inputs must all
+    /// have the same columns.
+    #[structopt(name = "more-locators")]
+    more_from_locators: Vec<BoxLocator>,
+}
+
+/// The exit status we use when `--allow-empty` skipped any inputs.
+pub(crate) const SKIPPED_EMPTY_EXIT_CODE: i32 = 3;
+
+/// Returned when `--allow-empty` skipped one or more inputs because they were
+/// empty and we had no schema for them.
+#[derive(Debug)]
+pub(crate) struct SkippedEmptyInputs {
+    /// The inputs we skipped.
+    pub(crate) inputs: Vec<String>,
+}
+
+impl fmt::Display for SkippedEmptyInputs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "skipped empty inputs with no schema: {}",
+            self.inputs.join(", "),
+        )
+    }
+}
+
+impl Fail for SkippedEmptyInputs {}
+
+impl Opt {
+    /// Turn `cp IN1 IN2 IN3 OUT` into a `from_locator` of `IN1`, a
+    /// `more_from_locators` of `IN2 IN3` and a `to_locator` of `OUT`. This must
+    /// be called exactly once after parsing our arguments.
+    pub(crate) fn arrange_locators(&mut self) {
+        if let Some(to_locator) = self.more_from_locators.pop() {
+            let from_locator = mem::replace(&mut self.to_locator, to_locator);
+            self.more_from_locators.insert(0, from_locator);
+        }
+    }
+
+    /// Fail if this copy would write anywhere but standard output.
+    pub(crate) fn check_read_only(&self) -> Result<()> {
+        // A dry run never changes anything.
+        if self.dry_run {
+            return Ok(());
+        }
+        if let Some(backup_to) = &self.backup_to {
+            return Err(format_err!(
+                "cannot use --backup-to {} in --read-only mode",
+                backup_to,
+            ));
+        }
+        check_read_only_dest(&self.to_locator)?;
+        for to_locator in &self.also_to {
+            check_read_only_dest(to_locator)?;
+        }
+        Ok(())
+    }
+
+    /// All our input tables.
+    fn input_locators(&self) -> impl Iterator<Item = &BoxLocator> {
+        iter::once(&self.from_locator).chain(&self.more_from_locators)
+    }
+
+    /// Fail if we were asked to copy from multiple sources or to multiple
+    /// destinations along with options that only make sense for a single
+    /// source and destination.
+    fn check_multiple_locators(&self) -> Result<()> {
+        if !self.more_from_locators.is_empty() && self.lineage_to.is_some() {
+            return Err(format_err!(
+                "cannot use --lineage-to when copying from multiple sources",
+            ));
+        }
+        if self.also_to.is_empty() {
+            return Ok(());
+        }
+        let conflicts = [
+            ("--backup-to", self.backup_to.is_some()),
+            ("--lineage-to", self.lineage_to.is_some()),
+            ("--resume", self.resume.is_some()),
+            (
+                "--replicate-to-regions",
+                !self.replicate_to_regions.is_empty(),
+            ),
+        ];
+        for (flag, is_set) in &conflicts {
+            if *is_set {
+                return Err(format_err!(
+                    "cannot use {} when copying to multiple destinations",
+                    flag,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Fail if `--resume` was combined with options that need to see all our
+    /// streams at once, or which would change the destination again each time
+    /// we resume.
+    fn check_resume(&self) -> Result<()> {
+        if self.resume.is_none() {
+            return Ok(());
+        }
+        if let IfExists::Merge(..) = &self.if_exists {
+            return Err(format_err!(
+                "cannot use --resume with --if-exists=merge-on, because merging needs every stream at once",
+            ));
+        }
+        if self.backup_to.is_some() {
+            return Err(format_err!("cannot use --resume with --backup-to"));
+        }
+        // PostgreSQL recalculates its key ranges every time we export, so the
+        // same stream name may contain different rows when we resume.
+        let splits_key_ranges = self.from_args.iter().any(|arg| {
+            let key = arg.split('=').next().unwrap_or_default().trim();
+            key == "ranges" || key == "partition_column"
+        });
+        if splits_key_ranges {
+            return Err(format_err!(
+                "cannot use --resume with --from-arg=ranges or --from-arg=partition_column, because key ranges may change between runs",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Build a copy of these options for copying a single table from a larger
+    /// dataset.
+    fn for_table(
+        &self,
+        from_locator: BoxLocator,
+        to_locator: BoxLocator,
+        backup_to: Option<BoxLocator>,
+    ) -> Opt {
+        Opt {
+            if_exists: self.if_exists.clone(),
+            schema: None,
+            temporaries: self.temporaries.clone(),
+            stream_size: self.stream_size,
+            stream_rows: self.stream_rows,
+            from_args: self.from_args.clone(),
+            to_args: self.to_args.clone(),
+            where_clause: self.where_clause.clone(),
+            select: self.select.clone(),
+            limit: self.limit,
+            order_by: self.order_by.clone(),
+            max_streams: self.max_streams,
+            adaptive_streams: self.adaptive_streams,
+            preserve_order: self.preserve_order,
+            casts: self.casts.clone(),
+            column_transforms: self.column_transforms.clone(),
+            dedupe_on: self.dedupe_on.clone(),
+            dedupe_keep: self.dedupe_keep,
+            rename: self.rename.clone(),
+            rename_file: self.rename_file.clone(),
+            display_output_locators: self.display_output_locators,
+            backup_to,
+            lineage_to: self.lineage_to.clone(),
+            resume: self.resume.clone(),
+            allow_reordered_columns: self.allow_reordered_columns,
+            allow_empty: self.allow_empty,
+            replicate_to_regions: self.replicate_to_regions.clone(),
+            confirm: false,
+            yes: self.yes,
+            dry_run: self.dry_run,
+            include_tables: vec![],
+            exclude_tables: vec![],
+            max_parallel_tables: 1,
+            also_to: vec![],
+            from_locator,
+            more_from_locators: vec![],
+            to_locator,
+        }
+    }
+
+    /// If we're about to overwrite or upsert into `to_locator`, and the user
+    /// passed `--backup-to`, build options for copying the current contents of
+    /// `to_locator` to the backup location.
+    fn for_backup(&self) -> Result<Option<Opt>> {
+        let backup_to = match (&self.backup_to, &self.if_exists) {
+            (Some(backup_to), IfExists::Overwrite)
+            | (Some(backup_to), IfExists::Upsert(..))
+            | (Some(backup_to), IfExists::Merge(..)) => backup_to,
+            _ => return Ok(None),
+        };
+        Ok(Some(Opt::for_overwrite(
+            self.to_locator.to_string_with_credentials().parse()?,
+            backup_to.to_string_with_credentials().parse()?,
+            self.temporaries.clone(),
+            self.max_streams,
+        )))
+    }
+
+    /// Build options for a plain copy which overwrites `to_locator` with the
+    /// contents of `from_locator`.
+    pub(crate) fn for_overwrite(
+        from_locator: BoxLocator,
+        to_locator: BoxLocator,
+        temporaries: Vec<String>,
+        max_streams: usize,
+    ) -> Opt {
+        Opt {
+            if_exists: IfExists::Overwrite,
+            schema: None,
+            temporaries,
+            stream_size: None,
+            stream_rows: None,
+            from_args: vec![],
+            to_args: vec![],
+            where_clause: None,
+            select: None,
+            limit: None,
+            order_by: None,
+            max_streams,
+            adaptive_streams: false,
+            preserve_order: false,
+            casts: vec![],
+            column_transforms: vec![],
+            dedupe_on: None,
+            dedupe_keep: DedupeKeep::default(),
+            rename: None,
+            rename_file: None,
+            display_output_locators: false,
+            backup_to: None,
+            lineage_to: None,
+            resume: None,
+            allow_reordered_columns: false,
+            allow_empty: false,
+            replicate_to_regions: vec![],
+            confirm: false,
+            yes: false,
+            dry_run: false,
+            include_tables: vec![],
+            exclude_tables: vec![],
+            max_parallel_tables: 1,
+            also_to: vec![],
+            from_locator,
+            more_from_locators: vec![],
+            to_locator,
+        }
+    }
+
+    /// Build options for copying our destination to each of the regions in
+    /// `--replicate-to-regions`.
+    fn for_replicas(&self) -> Result<Vec<(String, Opt)>> {
+        let to_locator = self.to_locator.to_string();
+        self.replicate_to_regions
+            .iter()
+            .map(|region| {
+                let replica = regional_locator(&to_locator, region)?;
+                let opt = Opt::for_overwrite(
+                    to_locator.parse()?,
+                    replica.parse()?,
+                    regional_temporaries(&self.temporaries, region)?,
+                    self.max_streams,
+                );
+                Ok((region.to_owned(), opt))
+            })
+            .collect()
+    }
+
+    /// Which tables should we copy from a database or dataset?
+    fn table_filter(&self) -> TableFilter {
+        TableFilter {
+            include: self.include_tables.clone(),
+            exclude: self.exclude_tables.clone(),
+        }
+    }
+
+    /// How many data streams should we copy in parallel?
+    fn parallelism(&self) -> usize {
+        if self.preserve_order {
+            1
+        } else {
+            self.max_streams
+        }
+    }
+
+    /// Can we copy directly from our source to our destination, without
+    /// passing the data through this machine?
+    fn uses_remote_transfer(&self, renames: &ColumnRenames) -> bool {
+        self.also_to.is_empty()
+            && self.more_from_locators.is_empty()
+            && self.resume.is_none()
+            && self.stream_size.is_none()
+            && self.stream_rows.is_none()
+            && renames.is_empty()
+            && self.column_transforms.is_empty()
+            && self.dedupe_on.is_none()
+            && !self.allow_empty
+            && ((self.where_clause.is_none()
+                && self.select.is_none()
+                && self.limit.is_none())
+                || !self.from_locator.rewrites_data_locally())
+            && self
+                .to_locator
+                .supports_write_remote_data(self.from_locator.as_ref())
+    }
+
+    /// Which columns should we rename while copying?
+    fn renames(&self) -> Result<ColumnRenames> {
+        let mut renames = self.rename.clone().unwrap_or_default();
+        if let Some(path) = &self.rename_file {
+            let json = fs::read_to_string(path)
+                .with_context(|_| format!("could not read {}", path.display()))?;
+            renames
+                .extend(ColumnRenames::from_json(&json).with_context(|_| {
+                    format!("could not parse {}", path.display())
+                })?);
+        }
+        Ok(renames)
+    }
+
+    /// Fail unless these options describe a copy that `sync` can run
+    /// incrementally: one source table, one destination, and new rows appended
+    /// or upserted into the destination.
+    pub(crate) fn check_sync(&self) -> Result<()> {
+        if !self.more_from_locators.is_empty() || !self.also_to.is_empty() {
+            return Err(format_err!(
+                "sync can only copy from one source to one destination"
+            ));
+        }
+        if !self.include_tables.is_empty() || !self.exclude_tables.is_empty() {
+            return Err(format_err!("sync can only copy a single table"));
+        }
+        if self.dry_run {
+            return Err(format_err!("sync does not support --dry-run"));
+        }
+        if self.limit.is_some() || self.order_by.is_some() {
+            return Err(format_err!("sync does not support --limit or --order-by"));
+        }
+        if self.resume.is_some() {
+            return Err(format_err!("sync does not support --resume"));
+        }
+        match &self.if_exists {
+            IfExists::Append | IfExists::Upsert(..) => Ok(()),
+            if_exists => Err(format_err!(
+                "sync needs --if-exists=append or --if-exists=upsert-on:COL, not --if-exists={}",
+                if_exists,
+            )),
+        }
+    }
+
+    /// The input table.
+    pub(crate) fn source_locator(&self) -> &BoxLocator {
+        &self.from_locator
+    }
+
+    /// The output table.
+    pub(crate) fn dest_locator(&self) -> &BoxLocator {
+        &self.to_locator
+    }
+
+    /// The `--where` clause specifying rows to copy.
+    pub(crate) fn where_clause(&self) -> Option<&str> {
+        self.where_clause.as_deref()
+    }
+
+    /// Replace our `--where` clause.
+    pub(crate) fn set_where_clause(&mut self, where_clause: Option<String>) {
+        self.where_clause = where_clause;
+    }
+
+    /// Build the arguments we'd pass to our source driver when reading data
+    /// using `schema`.
+    pub(crate) fn source_arguments(
+        &self,
+        schema: Table,
+    ) -> Result<(SharedArguments<Unverified>, SourceArguments<Unverified>)> {
+        let temporary_storage = TemporaryStorage::new(self.temporaries.clone());
+        let shared_args =
+            SharedArguments::new(schema, temporary_storage, self.parallelism())
+                .with_adaptive_streams(self.adaptive_streams);
+        let from_args = DriverArguments::from_cli_args(&self.from_args)?;
+        let source_args = SourceArguments::new(from_args, self.where_clause.clone())
+            .with_select_columns(self.select.is_some())
+            .with_limit(self.limit)
+            .with_order_by(self.order_by.clone());
+        Ok((shared_args, source_args))
+    }
+
+    /// Describe the ways in which we'll change the data while copying it,
+    /// for use in lineage events.
+    fn transforms(&self) -> Vec<String> {
+        let mut transforms = vec![];
+        if let Some(where_clause) = &self.where_clause {
+            transforms.push(format!("filtered using WHERE {}", where_clause));
+        }
+        if let Some(select) = &self.select {
+            transforms.push(format!("selected columns {}", select));
+        }
+        if let Some(order_by) = &self.order_by {
+            transforms.push(format!("ordered by {}", order_by));
+        }
+        if let Some(limit) = self.limit {
+            transforms.push(format!("limited to {} rows", limit));
+        }
+        if let Some(schema) = &self.schema {
+            transforms.push(format!("converted to schema {}", schema));
+        }
+        for cast in &self.casts {
+            transforms.push(format!("cast column {}", cast));
+        }
+        for column_transform in &self.column_transforms {
+            transforms.push(format!("transformed column {}", column_transform));
+        }
+        if self.rename.is_some() || self.rename_file.is_some() {
+            transforms.push("renamed columns".to_owned());
+        }
+        if let Some(dedupe_on) = &self.dedupe_on {
+            transforms.push(format!(
+                "kept the {} row for each {}",
+                self.dedupe_keep, dedupe_on,
+            ));
+        }
+        match &self.if_exists {
+            IfExists::Error => {}
+            if_exists => {
+                transforms.push(format!("wrote with --if-exists={}", if_exists))
+            }
+        }
+        transforms
+    }
 }
 
-/// Perform our schema conversion.
+/// Perform our copy, expanding datasets into one copy per table.
 pub(crate) async fn run(ctx: Context, opt: Opt) -> Result<()> {
-    // Figure out what table schema to use.
-    let schema = {
-        let schema_locator = opt.schema.as_ref().unwrap_or(&opt.from_locator);
-        schema_locator
+    // If we make any backups, we'll record them here so that they can be
+    // restored using `dbcrossbar rollback`.
+    let job = Mutex::new(None);
+    opt.check_multiple_locators()?;
+    opt.check_resume()?;
+
+    let tables = opt.from_locator.list_tables(ctx.clone()).await?;
+    if let Some(tables) = tables {
+        copy_tables(ctx, opt, tables, &job).await
+    } else {
+        if !opt.include_tables.is_empty() || !opt.exclude_tables.is_empty() {
+            return Err(format_err!(
+                "--include-table and --exclude-table can only be used when copying a database or dataset",
+            ));
+        }
+        if opt.dry_run {
+            return print_dry_run(&ctx, &opt).await;
+        }
+        if opt.confirm {
+            let schema = read_schema(&ctx, &opt).await?;
+            let plan = describe_copy(&ctx, &opt, &schema, true).await?;
+            confirm_plan(&opt, plan)?;
+        }
+        let from = opt.from_locator.to_string();
+        if backup_and_copy_table(ctx, opt, &job).await? {
+            Ok(())
+        } else {
+            check_skipped(vec![from])
+        }
+    }
+}
+
+/// A single table to copy from a database or dataset.
+struct TableCopy {
+    /// The bare name of the table.
+    table: String,
+    /// Options for copying just this table.
+    opt: Opt,
+}
+
+/// What happened when we copied a table from a database or dataset.
+enum TableCopyOutcome {
+    /// We copied the table.
+    Copied,
+    /// We skipped the table because of `--allow-empty`.
+    Skipped,
+    /// We couldn't copy the table.
+    Failed(failure::Error),
+}
+
+/// Copy every table in `tables` that matches our filters, and print a summary.
+async fn copy_tables(
+    ctx: Context,
+    opt: Opt,
+    tables: Vec<(String, BoxLocator)>,
+    job: &Mutex<Option<JobManifest>>,
+) -> Result<()> {
+    // We're copying every table in a dataset, so make sure our other
+    // arguments make sense.
+    if !opt.more_from_locators.is_empty() {
+        return Err(format_err!(
+            "cannot append {} to other inputs, because it contains multiple tables",
+            opt.from_locator,
+        ));
+    }
+    if !opt.also_to.is_empty() {
+        return Err(format_err!(
+            "cannot copy all the tables in {} to more than one destination",
+            opt.from_locator,
+        ));
+    }
+    if opt.schema.is_some() {
+        return Err(format_err!(
+            "cannot use --schema when copying all the tables in {}",
+            opt.from_locator,
+        ));
+    }
+    // Build our templates from locators which still contain any passwords.
+    let backup_template = opt
+        .backup_to
+        .as_ref()
+        .map(|b| b.to_string_with_credentials());
+    if let Some(backup_template) = &backup_template {
+        if !backup_template.contains("{table}") {
+            return Err(format_err!(
+                "{} contains multiple tables, so --backup-to must contain {{table}}",
+                opt.from_locator,
+            ));
+        }
+    }
+
+    // Decide where each table should go.
+    let filter = opt.table_filter();
+    let to_template = opt.to_locator.to_string_with_credentials();
+    let mut copies = vec![];
+    for (table, from_locator) in tables {
+        if !filter.matches(&table) {
+            debug!(ctx.log(), "skipping {} because of table filters", table);
+            continue;
+        }
+        let to_locator = if to_template.contains("{table}") {
+            to_template
+                .replace("{table}", &table)
+                .parse::<BoxLocator>()?
+        } else {
+            opt.to_locator.table_locator(&table)?.ok_or_else(|| {
+                format_err!(
+                    "{} contains multiple tables, so the destination must be a database or dataset, or contain {{table}}",
+                    opt.from_locator,
+                )
+            })?
+        };
+        let backup_to = backup_template
+            .as_ref()
+            .map(|t| t.replace("{table}", &table).parse::<BoxLocator>())
+            .transpose()?;
+        copies.push(TableCopy {
+            table,
+            opt: opt.for_table(from_locator, to_locator, backup_to),
+        });
+    }
+
+    if opt.dry_run {
+        for copy in &copies {
+            print_dry_run(&ctx, &copy.opt).await?;
+        }
+        return Ok(());
+    }
+    if opt.confirm {
+        let mut plan = vec![format!(
+            "copy {} tables from {}",
+            copies.len(),
+            opt.from_locator,
+        )];
+        for copy in &copies {
+            plan.push(format!("  {} -> {}", copy.table, copy.opt.to_locator));
+        }
+        confirm_plan(&opt, plan)?;
+    }
+
+    // Copy our tables, keeping going if any of them fail, so that we can
+    // report on all of them.
+    let table_count = copies.len();
+    let results = stream::iter(copies)
+        .map(|copy| {
+            let ctx = ctx.child(o!("table" => copy.table.clone()));
+            async move {
+                debug!(
+                    ctx.log(),
+                    "copying {} to {}", copy.opt.from_locator, copy.opt.to_locator,
+                );
+                let summary =
+                    format!("{} -> {}", copy.opt.from_locator, copy.opt.to_locator);
+                let start = Instant::now();
+                let outcome =
+                    match backup_and_copy_table(ctx.clone(), copy.opt, job).await {
+                        Ok(true) => TableCopyOutcome::Copied,
+                        Ok(false) => TableCopyOutcome::Skipped,
+                        Err(err) => {
+                            warn!(
+                                ctx.log(),
+                                "error copying table {}: {}", copy.table, err
+                            );
+                            TableCopyOutcome::Failed(err)
+                        }
+                    };
+                (summary, start.elapsed(), outcome)
+            }
+        })
+        .buffered(opt.max_parallel_tables.max(1))
+        .collect::<Vec<_>>()
+        .await;
+
+    // Print our summary.
+    let mut skipped = vec![];
+    let mut failed = 0;
+    eprintln!("dbcrossbar: copied tables from {}:", opt.from_locator);
+    for (summary, elapsed, outcome) in results {
+        match outcome {
+            TableCopyOutcome::Copied => {
+                eprintln!("  copied  {} ({:.1}s)", summary, elapsed.as_secs_f64(),)
+            }
+            TableCopyOutcome::Skipped => {
+                eprintln!("  skipped {} (empty, with no schema)", summary);
+                skipped.push(summary);
+            }
+            TableCopyOutcome::Failed(err) => {
+                let causes =
+                    err.iter_chain().map(|c| c.to_string()).collect::<Vec<_>>();
+                eprintln!("  FAILED  {}: {}", summary, causes.join(": "));
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        Err(format_err!(
+            "could not copy {} of {} tables",
+            failed,
+            table_count,
+        ))
+    } else {
+        check_skipped(skipped)
+    }
+}
+
+/// Return an error if we skipped any empty inputs.
+fn check_skipped(skipped: Vec<String>) -> Result<()> {
+    if skipped.is_empty() {
+        Ok(())
+    } else {
+        Err(SkippedEmptyInputs { inputs: skipped }.into())
+    }
+}
+
+/// Describe a single-table copy for `--confirm`, one line per item. If
+/// `count_rows` is true, try to count the rows we'll copy.
+async fn describe_copy(
+    ctx: &Context,
+    opt: &Opt,
+    schema: &Table,
+    count_rows: bool,
+) -> Result<Vec<String>> {
+    let schema_source = opt.schema.as_ref().unwrap_or(&opt.from_locator);
+
+    // Counting rows is just a courtesy, so ignore any errors. Drivers which
+    // read files would have to read all our data to count it, and they can't
+    // read standard input twice, so we only ask databases.
+    let temporary_storage = TemporaryStorage::new(opt.temporaries.clone());
+    let shared_args = SharedArguments::new(schema.clone(), temporary_storage, 1);
+    let from_args = DriverArguments::from_cli_args(&opt.from_args)?;
+    let source_args = SourceArguments::new(from_args, opt.where_clause.clone())
+        .with_select_columns(opt.select.is_some());
+    let count_all = async {
+        if !count_rows {
+            return Ok(None);
+        }
+        if opt.input_locators().any(|l| l.rewrites_data_locally()) {
+            return Ok(Some("not counted for file sources".to_owned()));
+        }
+        let mut total = 0;
+        for from_locator in opt.input_locators() {
+            let count = from_locator
+                .count(ctx.clone(), shared_args.clone(), source_args.clone())
+                .await?;
+            total += opt.limit.map_or(count, |limit| count.min(limit));
+        }
+        Ok::<_, failure::Error>(Some(total.to_string()))
+    };
+    let rows = match count_all.await {
+        Ok(count) => count,
+        Err(err) => {
+            debug!(ctx.log(), "could not count rows: {}", err);
+            Some("unknown".to_owned())
+        }
+    };
+
+    let if_exists = match &opt.if_exists {
+        IfExists::Error => "error (fail if the destination exists)".to_owned(),
+        IfExists::Append => "append (add rows to any existing data)".to_owned(),
+        IfExists::Overwrite => "overwrite (REPLACE any existing data)".to_owned(),
+        upsert @ IfExists::Upsert(..) => {
+            format!("{} (update or insert rows by key)", upsert)
+        }
+        merge @ IfExists::Merge(_, MergeDelete::Delete) => format!(
+            "{} (update or insert rows by key, DELETE rows not in the input)",
+            merge,
+        ),
+        merge @ IfExists::Merge(_, MergeDelete::SoftDelete(column)) => format!(
+            "{} (update or insert rows by key, mark rows not in the input as deleted in {})",
+            merge, column,
+        ),
+    };
+    let temporaries = if opt.temporaries.is_empty() {
+        "none".to_owned()
+    } else {
+        opt.temporaries.join(", ")
+    };
+
+    let mut plan = vec![format!("from:        {}", opt.from_locator)];
+    for from_locator in &opt.more_from_locators {
+        plan.push(format!("             {}", from_locator));
+    }
+    plan.push(format!("to:          {}", opt.to_locator));
+    for to_locator in &opt.also_to {
+        plan.push(format!("             {}", to_locator));
+    }
+    plan.extend(vec![
+        format!(
+            "schema:      {} columns, from {}",
+            schema.columns.len(),
+            schema_source,
+        ),
+        format!("if exists:   {}", if_exists),
+        format!("temporaries: {}", temporaries),
+    ]);
+    if let Some(rows) = rows {
+        plan.push(format!("rows:        {}", rows));
+    }
+    if let Some(where_clause) = &opt.where_clause {
+        plan.push(format!("where:       {}", where_clause));
+    }
+    if let Some(select) = &opt.select {
+        plan.push(format!("select:      {}", select));
+    }
+    for column_transform in &opt.column_transforms {
+        plan.push(format!("transform:   {}", column_transform));
+    }
+    if let Some(dedupe_on) = &opt.dedupe_on {
+        plan.push(format!(
+            "dedupe on:   {} (keep {})",
+            dedupe_on, opt.dedupe_keep,
+        ));
+    }
+    if let Some(order_by) = &opt.order_by {
+        plan.push(format!("order by:    {}", order_by));
+    }
+    if let Some(limit) = opt.limit {
+        plan.push(format!("limit:       {}", limit));
+    }
+    if let Some(backup_to) = &opt.backup_to {
+        plan.push(format!("backup to:   {}", backup_to));
+    }
+    Ok(plan)
+}
+
+/// Print what a single-table copy would do, including the steps each
+/// destination driver would take, without changing anything.
+async fn print_dry_run(ctx: &Context, opt: &Opt) -> Result<()> {
+    let schema = read_schema(ctx, opt).await?;
+    let mut plan = describe_copy(ctx, opt, &schema, false).await?;
+    let renames = opt.renames()?;
+    let remote = opt.uses_remote_transfer(&renames);
+    if remote {
+        plan.push(format!(
+            "transfer:    directly from {} to {}",
+            driver_name(opt.from_locator.as_ref()),
+            driver_name(opt.to_locator.as_ref()),
+        ));
+    } else {
+        plan.push(format!(
+            "transfer:    via this machine, up to {} streams at once",
+            opt.parallelism(),
+        ));
+    }
+    for (region, replica_opt) in opt.for_replicas()? {
+        plan.push(format!(
+            "replicate:   {} (region {})",
+            replica_opt.to_locator, region,
+        ));
+    }
+
+    // Ask each destination what it would do.
+    let temporary_storage = TemporaryStorage::new(opt.temporaries.clone());
+    let dest_schema = renames.rename_table(&schema)?;
+    let dest_shared_args =
+        SharedArguments::new(dest_schema, temporary_storage, opt.parallelism())
+            .with_adaptive_streams(opt.adaptive_streams);
+    let to_args = DriverArguments::from_cli_args(&opt.to_args)?;
+    let dest_args = DestinationArguments::new(to_args, opt.if_exists.clone());
+    let remote_source = if remote {
+        Some(opt.from_locator.to_string())
+    } else {
+        None
+    };
+    let mut steps = vec![];
+    for to_locator in iter::once(&opt.to_locator).chain(&opt.also_to) {
+        let to_steps = to_locator
+            .plan_write(
+                ctx.clone(),
+                dest_shared_args.clone(),
+                dest_args.clone(),
+                remote_source.clone(),
+            )
+            .await
+            .with_context(|_| format!("error planning write to {}", to_locator))?;
+        steps.push((to_locator, to_steps));
+    }
+
+    let stdout = std_io::stdout();
+    let mut out = stdout.lock();
+    writeln!(out, "dbcrossbar would copy:")?;
+    for line in &plan {
+        writeln!(out, "  {}", line)?;
+    }
+    for (to_locator, to_steps) in steps {
+        writeln!(out, "steps for {}:", to_locator)?;
+        match to_steps {
+            Some(to_steps) => {
+                for (idx, step) in to_steps.iter().enumerate() {
+                    // Indent multi-line SQL to line up with the first line.
+                    let prefix = format!("{}. ", idx + 1);
+                    let indent = " ".repeat(prefix.len());
+                    for (line_idx, line) in step.trim().lines().enumerate() {
+                        let prefix = if line_idx == 0 { &prefix } else { &indent };
+                        let line = format!("  {}{}", prefix, line);
+                        writeln!(out, "{}", line.trim_end())?;
+                    }
+                }
+            }
+            None => writeln!(out, "  (this driver can't describe its steps)")?,
+        }
+    }
+    Ok(())
+}
+
+/// Print `plan` to standard error, and ask the user whether to continue.
+fn confirm_plan(opt: &Opt, plan: Vec<String>) -> Result<()> {
+    eprintln!("dbcrossbar is about to:");
+    for line in &plan {
+        eprintln!("  {}", line);
+    }
+    if opt.yes {
+        return Ok(());
+    }
+    if !atty::is(atty::Stream::Stdin) {
+        return Err(format_err!(
+            "cannot ask for --confirm because standard input is not a terminal (pass --yes to continue anyway)"
+        ));
+    }
+    eprint!("Continue? [y/N] ");
+    std_io::stderr().flush()?;
+    let mut answer = String::new();
+    std_io::stdin().read_line(&mut answer)?;
+    match answer.trim().to_ascii_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => Err(format_err!("copy cancelled")),
+    }
+}
+
+/// Back up the destination table (if requested), and then copy a single table.
+/// Returns false if we skipped an empty input because of `--allow-empty`.
+///
+/// Any backups will be recorded in `job`, which will be created if needed.
+async fn backup_and_copy_table(
+    ctx: Context,
+    opt: Opt,
+    job: &Mutex<Option<JobManifest>>,
+) -> Result<bool> {
+    // An empty CSV file without a header has no columns, and we can't create
+    // a table with no columns.
+    let schema = read_schema(&ctx, &opt).await?;
+    check_more_from_schemas(&ctx, &opt, &schema).await?;
+    if opt.allow_empty && schema.columns.is_empty() {
+        warn!(
+            ctx.log(),
+            "skipping {} because it is empty and has no schema", opt.from_locator,
+        );
+        return Ok(false);
+    }
+
+    if let Some(backup_opt) = opt.for_backup()? {
+        let ctx = ctx.child(o!("backup_to" => backup_opt.to_locator.to_string()));
+        debug!(
+            ctx.log(),
+            "backing up {} to {}", backup_opt.from_locator, backup_opt.to_locator,
+        );
+        // Keep any passwords, so that `rollback` can reconnect.
+        let destination = backup_opt.from_locator.to_string_with_credentials();
+        let backup = backup_opt.to_locator.to_string_with_credentials();
+        let backup_result = async {
+            let schema = read_schema(&ctx, &backup_opt).await?;
+            copy_table_with_schema(ctx.clone(), backup_opt, schema.clone()).await?;
+            Ok::<_, failure::Error>(schema)
+        };
+        let schema = backup_result.await.with_context(|_| {
+            format!(
+                "could not back up {} before changing it (if it doesn't exist yet, omit --backup-to)",
+                opt.to_locator,
+            )
+        })?;
+
+        // Record our backup before we change anything.
+        let mut job = job.lock().await;
+        if job.is_none() {
+            *job = Some(JobManifest::new(opt.temporaries.clone())?);
+        }
+        let job = job.as_mut().expect("job should have been created");
+        job.backups.push(BackupRecord {
+            destination,
+            backup,
+            schema,
+        });
+        job.save()?;
+        if job.backups.len() == 1 {
+            eprintln!(
+                "dbcrossbar: to undo this copy, run `dbcrossbar rollback {}`",
+                job.id,
+            );
+        }
+    }
+    copy_table(ctx, opt, schema).await?;
+    Ok(true)
+}
+
+/// Figure out what table schema to use, including any `--cast` types and
+/// `--select` columns.
+pub(crate) async fn read_schema(ctx: &Context, opt: &Opt) -> Result<Table> {
+    if opt.schema.is_none()
+        && driver_name(opt.from_locator.as_ref()) == "csv"
+        && !csv::source_has_header(&opt.from_args)?
+    {
+        return Err(format_err!(
+            "--from-arg=has_header=false requires --schema, because the input has no column names"
+        ));
+    }
+    let schema_locator = opt.schema.as_ref().unwrap_or(&opt.from_locator);
+    let schema = schema_locator
+        .schema(ctx.clone())
+        .await
+        .with_context(|_| format!("error reading schema from {}", opt.from_locator))?
+        .ok_or_else(|| {
+            format_err!("don't know how to read schema from {}", opt.from_locator)
+        })?;
+    let schema = ColumnCast::apply_all(&opt.casts, &schema)?;
+    match &opt.select {
+        Some(select) => select.select_table(&schema),
+        None => Ok(schema),
+    }
+}
+
+/// If we're appending several inputs and we don't have a `--schema`, make sure
+/// each input has the same column names as `schema`, which came from our first
+/// input.
+async fn check_more_from_schemas(
+    ctx: &Context,
+    opt: &Opt,
+    schema: &Table,
+) -> Result<()> {
+    if opt.schema.is_some() {
+        return Ok(());
+    }
+    let column_names = |table: &Table| {
+        table
+            .columns
+            .iter()
+            .map(|c| c.name.clone())
+            .collect::<Vec<_>>()
+    };
+    let expected = column_names(schema);
+    for from_locator in &opt.more_from_locators {
+        let other_schema = from_locator
             .schema(ctx.clone())
             .await
-            .with_context(|_| {
-                format!("error reading schema from {}", opt.from_locator)
-            })?
+            .with_context(|_| format!("error reading schema from {}", from_locator))?
             .ok_or_else(|| {
-                format_err!("don't know how to read schema from {}", opt.from_locator)
+                format_err!("don't know how to read schema from {}", from_locator)
+            })?;
+        let other_schema = match &opt.select {
+            Some(select) => select.select_table(&other_schema)?,
+            None => other_schema,
+        };
+        let found = column_names(&other_schema);
+        if found != expected {
+            return Err(format_err!(
+                "cannot append {} to {}, because it has columns ({}) instead of ({})",
+                from_locator,
+                opt.from_locator,
+                found.join(", "),
+                expected.join(", "),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Copy a single table using `schema`.
+async fn copy_table(ctx: Context, opt: Opt, schema: Table) -> Result<()> {
+    let replicas = opt.for_replicas()?;
+    let renames = opt.renames()?;
+    let dest_schema = renames.rename_table(&schema)?;
+    let lineage = opt.lineage_to.as_ref().map(|path| {
+        LineageEvent::for_copy(
+            path,
+            &opt.from_locator,
+            &opt.to_locator,
+            &schema,
+            &dest_schema,
+            &opt.transforms(),
+        )
+    });
+    copy_table_with_schema(ctx.clone(), opt, schema).await?;
+
+    // Copy our finished destination to each regional replica in parallel.
+    // These already have our new column names.
+    let schema = dest_schema;
+    let replica_copies = replicas.into_iter().map(|(region, replica_opt)| {
+        let ctx = ctx.child(o!("region" => region.clone()));
+        let to_locator = replica_opt.to_locator.to_string();
+        debug!(ctx.log(), "replicating to {}", to_locator);
+        copy_table_with_schema(ctx, replica_opt, schema.clone()).map(move |result| {
+            result.with_context(|_| {
+                format!("error replicating to {} in region {}", to_locator, region)
             })
-    }?;
+        })
+    });
+    future::try_join_all(replica_copies).await?;
+
+    if let Some(lineage) = lineage {
+        lineage.write()?;
+    }
+    Ok(())
+}
 
-    // Build our shared arguments.
+/// Copy a single table, using `schema` instead of the schema specified by
+/// `opt`.
+pub(crate) async fn copy_table_with_schema(
+    ctx: Context,
+    opt: Opt,
+    schema: Table,
+) -> Result<()> {
+    // Build our shared arguments. If we're renaming columns, the source sees
+    // the original schema and the destination sees the renamed one.
+    let renames = opt.renames()?;
+    let dest_schema = renames.rename_table(&schema)?;
+    let source_schema = schema.clone();
+    for column_transform in &opt.column_transforms {
+        column_transform.check_table(&source_schema)?;
+    }
+    if let Some(dedupe_on) = &opt.dedupe_on {
+        dedupe_on.check_table(&dest_schema)?;
+    }
+    let columns = dest_schema.columns.clone();
+    let check_headers = matches!(
+        opt.if_exists,
+        IfExists::Append | IfExists::Upsert(..) | IfExists::Merge(..)
+    );
     let temporary_storage = TemporaryStorage::new(opt.temporaries.clone());
-    let shared_args = SharedArguments::new(schema, temporary_storage, opt.max_streams);
+    if opt.preserve_order && opt.max_streams > 1 {
+        debug!(
+            ctx.log(),
+            "ignoring --max-streams because of --preserve-order"
+        );
+    }
+    let shared_args =
+        SharedArguments::new(schema, temporary_storage.clone(), opt.parallelism())
+            .with_adaptive_streams(opt.adaptive_streams);
+    let dest_shared_args =
+        SharedArguments::new(dest_schema, temporary_storage, opt.parallelism())
+            .with_adaptive_streams(opt.adaptive_streams);
 
     // Build our source arguments.
     let from_args = DriverArguments::from_cli_args(&opt.from_args)?;
-    let source_args = SourceArguments::new(from_args, opt.where_clause.clone());
-
-    // Build our destination arguments.
-    let to_args = DriverArguments::from_cli_args(&opt.to_args)?;
-    let dest_args = DestinationArguments::new(to_args, opt.if_exists);
+    let source_args = SourceArguments::new(from_args, opt.where_clause.clone())
+        .with_select_columns(opt.select.is_some())
+        .with_limit(opt.limit)
+        .with_order_by(opt.order_by.clone());
 
     // Can we short-circuit this particular copy using special features of the
     // the source and destination, or do we need to pull the data down to the
     // local machine?
+    let should_use_remote = opt.uses_remote_transfer(&renames);
+
+    // Build our destination arguments.
+    let to_args = DriverArguments::from_cli_args(&opt.to_args)?;
+    let dest_args = DestinationArguments::new(to_args.clone(), opt.if_exists.clone());
+
     let to_locator = opt.to_locator;
     let from_locator = opt.from_locator;
-    let should_use_remote = opt.stream_size.is_none()
-        && to_locator.supports_write_remote_data(from_locator.as_ref());
+    let mut to_locators = vec![to_locator];
+    to_locators.extend(opt.also_to);
     let dests = if should_use_remote {
+        let to_locator = &to_locators[0];
+
         // Build a logging context.
         let ctx = ctx.child(o!(
             "from_locator" => from_locator.to_string(),
@@ -110,7 +1330,13 @@ pub(crate) async fn run(ctx: Context, opt: Opt) -> Result<()> {
         // Perform a remote transfer.
         debug!(ctx.log(), "performing remote data transfer");
         let dests = to_locator
-            .write_remote_data(ctx, from_locator, shared_args, source_args, dest_args)
+            .write_remote_data(
+                ctx,
+                from_locator,
+                dest_shared_args,
+                source_args,
+                dest_args,
+            )
             .await?;
 
         // Convert our list of output locators into a stream.
@@ -120,58 +1346,166 @@ pub(crate) async fn run(ctx: Context, opt: Opt) -> Result<()> {
         // input.
         debug!(ctx.log(), "performing local data transfer");
 
-        let input_ctx = ctx.child(o!("from_locator" => from_locator.to_string()));
-        let mut data = from_locator
-            .local_data(input_ctx, shared_args.clone(), source_args)
-            .await?
-            .ok_or_else(|| {
-                format_err!("don't know how to read data from {}", from_locator)
-            })?;
+        let mut from_locators = vec![from_locator];
+        from_locators.extend(opt.more_from_locators);
+        let mut sources = vec![];
+        for from_locator in &from_locators {
+            let input_ctx = ctx.child(o!("from_locator" => from_locator.to_string()));
+            let data = from_locator
+                .local_data(input_ctx, shared_args.clone(), source_args.clone())
+                .await?
+                .ok_or_else(|| {
+                    format_err!("don't know how to read data from {}", from_locator)
+                })?;
+            sources.push(count_csv_streams(
+                ctx.usage().clone(),
+                driver_name(from_locator.as_ref()),
+                Direction::Down,
+                data,
+            ));
+        }
+
+        // If we have more than one source, append them all together.
+        let mut data = if sources.len() == 1 {
+            sources.pop().expect("should have one source")
+        } else {
+            union_csv_streams(ctx.clone(), sources)
+        };
 
-        // Honor --stream-size if passed.
-        if let Some(stream_size) = opt.stream_size {
-            let stream_size = stream_size.size();
-            data = rechunk_csvs(ctx.clone(), stream_size, data)?;
+        // If our input contained no data, write just a header, so that the
+        // destination can still create the table.
+        if opt.allow_empty {
+            data = fill_empty_csv_streams(ctx.clone(), source_schema, data);
         }
 
-        // Write data to output.
-        let output_ctx = ctx.child(o!("to_locator" => to_locator.to_string()));
-        let result_stream = to_locator
-            .write_local_data(output_ctx, data, shared_args.clone(), dest_args)
-            .await?;
+        // Clean up column values, using the original column names.
+        if !opt.column_transforms.is_empty() {
+            data = transform_csv_columns(ctx.clone(), opt.column_transforms, data);
+        }
 
-        // Consume the stream of futures produced by `write_local_data`, allowing a
-        // certain degree of parallelism. This is where all the actual work happens,
-        // and this what controls how many "input driver" -> "output driver"
-        // connections are running at any given time.
-        result_stream
-            // Run up to `parallelism` futures in parallel.
-            .try_buffer_unordered(shared_args.max_streams())
-            .boxed()
-    };
+        // Give our columns their new names.
+        if !renames.is_empty() {
+            data = rename_csv_headers(ctx.clone(), renames, data);
+        }
 
-    // Optionally display `dests`, depending on a combination of
-    // `--display-output-locators` and the defaults for `to_locator`.
-    let display_output_locators = match (
-        opt.display_output_locators,
-        to_locator.display_output_locators(),
-    ) {
-        // The user passed `--display-output-locators`, but displaying them is
-        // forbidden (probably because we wrote actual data to standard output).
-        (true, DisplayOutputLocators::Never) => {
-            return Err(format_err!(
-                "cannot use --display-output-locators with {}",
-                to_locator
-            ))
+        // Remove rows with duplicate keys, using the new column names.
+        if let Some(dedupe_on) = opt.dedupe_on {
+            data = dedupe_csv_streams(ctx.clone(), dedupe_on, opt.dedupe_keep, data)?;
         }
 
-        // We want to display our actual output locators.
-        (true, _) | (false, DisplayOutputLocators::ByDefault) => true,
+        // When adding data to an existing table, make sure each stream's
+        // columns match the schema, so we don't load them into the wrong
+        // fields.
+        if check_headers {
+            data = check_csv_headers(
+                ctx.clone(),
+                columns,
+                opt.allow_reordered_columns,
+                data,
+            );
+        }
 
-        // We don't want to display our output locators.
-        (false, _) => false,
+        // Honor --stream-size and --stream-rows if passed.
+        if opt.stream_size.is_some() || opt.stream_rows.is_some() {
+            let stream_size = opt.stream_size.map(|size| size.size());
+            data = rechunk_csvs(ctx.clone(), stream_size, opt.stream_rows, data)?;
+        }
+
+        if let Some(resume) = &opt.resume {
+            // We're recording our progress, so write each stream separately.
+            let to_locator = &to_locators[0];
+            let resume_file = ResumeFile::load(&ctx, resume, to_locator)?;
+            let data = count_csv_streams(
+                ctx.usage().clone(),
+                driver_name(to_locator.as_ref()),
+                Direction::Up,
+                data,
+            );
+            write_resumable(
+                ctx.child(o!("to_locator" => to_locator.to_string())),
+                resume_file,
+                to_locator.to_string_with_credentials().parse()?,
+                data,
+                dest_shared_args,
+                to_args,
+                opt.if_exists,
+                opt.preserve_order,
+            )?
+        } else {
+            // If we have more than one destination, send each of them a copy of
+            // our data, so that we only read the source once.
+            let datas = if to_locators.len() == 1 {
+                vec![data]
+            } else {
+                tee_csv_streams(ctx.clone(), to_locators.len(), data)
+            };
+
+            // Start writing data to each output. We start all our outputs before
+            // waiting on any of them, because some drivers begin reading their
+            // data before `write_local_data` returns.
+            let result_stream_futs =
+                to_locators.iter().zip(datas).map(|(to_locator, data)| {
+                    let data = count_csv_streams(
+                        ctx.usage().clone(),
+                        driver_name(to_locator.as_ref()),
+                        Direction::Up,
+                        data,
+                    );
+                    let output_ctx =
+                        ctx.child(o!("to_locator" => to_locator.to_string()));
+                    to_locator.write_local_data(
+                        output_ctx,
+                        data,
+                        dest_shared_args.clone(),
+                        dest_args.clone(),
+                    )
+                });
+            let result_streams = future::try_join_all(result_stream_futs).await?;
+
+            // Consume the stream of futures produced by `write_local_data`, allowing a
+            // certain degree of parallelism. This is where all the actual work happens,
+            // and this what controls how many "input driver" -> "output driver"
+            // connections are running at any given time.
+            let preserve_order = opt.preserve_order;
+            let result_streams = result_streams.into_iter().map(|result_stream| {
+                if preserve_order {
+                    // Finish each stream before starting the next.
+                    result_stream.try_buffered(1).boxed()
+                } else {
+                    // Run up to `parallelism` futures in parallel.
+                    result_stream.buffer_with_parallelism(&ctx, &shared_args)
+                }
+            });
+            stream::select_all(result_streams).boxed()
+        }
     };
 
+    // Optionally display `dests`, depending on a combination of
+    // `--display-output-locators` and the defaults for each of `to_locators`.
+    let mut display_output_locators = opt.display_output_locators;
+    for to_locator in &to_locators {
+        match (
+            opt.display_output_locators,
+            to_locator.display_output_locators(),
+        ) {
+            // The user passed `--display-output-locators`, but displaying them
+            // is forbidden (probably because we wrote actual data to standard
+            // output).
+            (true, DisplayOutputLocators::Never) => {
+                return Err(format_err!(
+                    "cannot use --display-output-locators with {}",
+                    to_locator
+                ))
+            }
+
+            // We want to display our actual output locators.
+            (_, DisplayOutputLocators::ByDefault) => display_output_locators = true,
+
+            // We don't want to display our output locators.
+            (_, _) => {}
+        }
+    }
+
     // Print our destination
     if display_output_locators {
         // Display our output locators incrementally on standard output using
@@ -201,3 +1535,108 @@ pub(crate) async fn run(ctx: Context, opt: Opt) -> Result<()> {
     }
     Ok(())
 }
+
+/// Write each stream in `data` to `to_locator` using a separate call to
+/// `write_local_data`, so that we know when the destination has finished with
+/// it, and record it in `resume_file`. Streams which `resume_file` already
+/// lists are skipped.
+#[allow(clippy::too_many_arguments)]
+fn write_resumable(
+    ctx: Context,
+    resume_file: ResumeFile,
+    to_locator: BoxLocator,
+    data: BoxStream<CsvStream>,
+    shared_args: SharedArguments<Unverified>,
+    to_args: DriverArguments,
+    if_exists: IfExists,
+    preserve_order: bool,
+) -> Result<BoxStream<BoxLocator>> {
+    // Once the destination contains some of our data, we need to add to it
+    // instead of replacing it.
+    let resumed_if_exists = match &if_exists {
+        IfExists::Upsert(..) => if_exists.clone(),
+        _ => IfExists::Append,
+    };
+    let driver = find_driver(&format!("{}:", driver_name(to_locator.as_ref())))?;
+    DestinationArguments::new(to_args.clone(), resumed_if_exists.clone())
+        .verify(driver.features())
+        .with_context(|_| format!("cannot use --resume with {}", to_locator))?;
+
+    // Skip any streams we've already written. We identify streams by name, so
+    // each name must be unique.
+    let has_progress = resume_file.has_progress();
+    let resume_file = Arc::new(resume_file);
+    let skip_ctx = ctx.clone();
+    let skip_file = resume_file.clone();
+    let mut seen = HashSet::new();
+    let mut data = data
+        .try_filter_map(move |stream| {
+            let result = if !seen.insert(stream.name.clone()) {
+                Err(format_err!(
+                    "cannot use --resume because more than one stream is named {:?}",
+                    stream.name,
+                ))
+            } else if skip_file.is_finished(&stream.name) {
+                debug!(skip_ctx.log(), "skipping finished stream {}", stream.name);
+                Ok(None)
+            } else {
+                Ok(Some(stream))
+            };
+            future::ready(result)
+        })
+        .boxed();
+
+    // Write a single stream, wait for the destination to finish with it, and
+    // record it.
+    let to_locator = Arc::new(to_locator);
+    let parallel_ctx = ctx.clone();
+    let parallel_args = shared_args.clone();
+    let write_stream = move |stream: CsvStream, if_exists: IfExists| {
+        let ctx = ctx.child(o!("stream" => stream.name.clone()));
+        let to_locator = to_locator.clone();
+        let resume_file = resume_file.clone();
+        let shared_args = shared_args.clone();
+        let dest_args = DestinationArguments::new(to_args.clone(), if_exists);
+        async move {
+            let name = stream.name.clone();
+            let data = stream::iter(vec![Ok(stream)]).boxed();
+            let dests = to_locator
+                .write_local_data(ctx, data, shared_args, dest_args)
+                .await?
+                .try_buffered(1)
+                .try_collect::<Vec<_>>()
+                .await?;
+            resume_file.record(&name)?;
+            Ok(dests)
+        }
+        .boxed()
+    };
+
+    let dests_fut = async move {
+        // If this is our first attempt, write our first stream by itself, so
+        // that the destination is created or replaced before we add anything
+        // else to it.
+        let mut first_dests = vec![];
+        if !has_progress {
+            if let Some(stream) = data.try_next().await? {
+                first_dests = write_stream(stream, if_exists).await?;
+            }
+        }
+
+        // Write the rest of our streams in parallel.
+        let rest = data
+            .map_ok(move |stream| write_stream(stream, resumed_if_exists.clone()))
+            .boxed();
+        let rest = if preserve_order {
+            rest.try_buffered(1).boxed()
+        } else {
+            rest.buffer_with_parallelism(&parallel_ctx, &parallel_args)
+        };
+        let dests = stream::iter(first_dests).map(Ok).chain(
+            rest.map_ok(|dests| stream::iter(dests).map(Ok))
+                .try_flatten(),
+        );
+        Ok::<_, failure::Error>(dests.boxed())
+    };
+    Ok(stream::once(dests_fut).try_flatten().boxed())
+}
@@ -0,0 +1,98 @@
+//! The `head` subcommand.
+
+use common_failures::Result;
+use dbcrossbarlib::{
+    BoxLocator, Context, DriverArguments, Error, SharedArguments, SourceArguments,
+    TemporaryStorage,
+};
+use failure::{format_err, ResultExt};
+use std::{io, result, str::FromStr};
+use structopt::{self, StructOpt};
+
+/// Head arguments.
+#[derive(Debug, StructOpt)]
+pub(crate) struct Opt {
+    /// The schema to use (defaults to input table schema).
+    #[structopt(long = "schema")]
+    schema: Option<BoxLocator>,
+
+    /// How many rows should we print?
+    #[structopt(short = "n", long = "rows", default_value = "10")]
+    rows: usize,
+
+    /// How to print the rows (table, csv).
+    #[structopt(long = "format", default_value = "table")]
+    format: HeadFormat,
+
+    /// Temporary directories, cloud storage buckets, datasets to use during
+    /// transfer (can be repeated).
+    #[structopt(long = "temporary")]
+    temporaries: Vec<String>,
+
+    /// Pass an extra argument of the form `key=value` to the source driver.
+    #[structopt(long = "from-arg")]
+    from_args: Vec<String>,
+
+    /// SQL where clause specifying rows to use.
+    #[structopt(long = "where")]
+    where_clause: Option<String>,
+
+    /// The locator specifying the records to print.
+    locator: BoxLocator,
+}
+
+/// How should we print our rows?
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum HeadFormat {
+    /// A table with aligned columns.
+    Table,
+    /// CSV, with a header row.
+    Csv,
+}
+
+impl FromStr for HeadFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(HeadFormat::Table),
+            "csv" => Ok(HeadFormat::Csv),
+            _ => Err(format_err!("unknown head format: {}", s)),
+        }
+    }
+}
+
+/// Print the first few rows of a table.
+pub(crate) async fn run(ctx: Context, opt: Opt) -> Result<()> {
+    // Figure out what table schema to use.
+    let schema = {
+        let schema_locator = opt.schema.as_ref().unwrap_or(&opt.locator);
+        schema_locator
+            .schema(ctx.clone())
+            .await
+            .with_context(|_| format!("error reading schema from {}", opt.locator))?
+            .ok_or_else(|| {
+                format_err!("don't know how to read schema from {}", opt.locator)
+            })
+    }?;
+
+    // Build our shared and source arguments.
+    let temporary_storage = TemporaryStorage::new(opt.temporaries.clone());
+    let shared_args = SharedArguments::new(schema, temporary_storage, 1);
+    let from_args = DriverArguments::from_cli_args(&opt.from_args)?;
+    let source_args = SourceArguments::new(from_args, opt.where_clause.clone());
+
+    let preview = opt
+        .locator
+        .head(ctx.clone(), shared_args, source_args, opt.rows)
+        .await?
+        .ok_or_else(|| {
+            format_err!("don't know how to read data from {}", opt.locator)
+        })?;
+    let stdout = io::stdout();
+    match opt.format {
+        HeadFormat::Table => preview.write_table(stdout.lock())?,
+        HeadFormat::Csv => preview.write_csv(stdout.lock())?,
+    }
+    Ok(())
+}
@@ -0,0 +1,28 @@
+//! The `ls` subcommand.
+
+use common_failures::Result;
+use dbcrossbarlib::{BoxLocator, Context};
+use failure::format_err;
+use structopt::{self, StructOpt};
+
+/// List arguments.
+#[derive(Debug, StructOpt)]
+pub(crate) struct Opt {
+    /// The database, dataset or directory to list.
+    locator: BoxLocator,
+}
+
+/// Print a locator for each table or file contained in our locator.
+pub(crate) async fn run(ctx: Context, opt: Opt) -> Result<()> {
+    let locators = if let Some(tables) = opt.locator.list_tables(ctx.clone()).await? {
+        tables.into_iter().map(|(_, locator)| locator).collect()
+    } else if let Some(files) = opt.locator.list_files(ctx.clone()).await? {
+        files
+    } else {
+        return Err(format_err!("don't know how to list {}", opt.locator));
+    };
+    for locator in locators {
+        println!("{}", locator);
+    }
+    Ok(())
+}
@@ -1,16 +1,30 @@
 //! Command parsing.
 
-use dbcrossbarlib::{tokio_glue::BoxFuture, Context};
+use common_failures::Result;
+use dbcrossbarlib::{
+    tokio_glue::BoxFuture, usage::UsageReport, BoxLocator, Context, Error,
+};
+use failure::format_err;
 use futures::FutureExt;
+use std::{env, path::PathBuf, result, str::FromStr};
 //use structopt::StructOpt;
 use structopt_derive::StructOpt;
 
 use crate::logging::LogFormat;
 
+pub(crate) mod completions;
 pub(crate) mod conv;
 pub(crate) mod count;
 pub(crate) mod cp;
 pub(crate) mod features;
+pub(crate) mod head;
+pub(crate) mod ls;
+pub(crate) mod rm;
+pub(crate) mod rollback;
+pub(crate) mod run;
+pub(crate) mod schema;
+pub(crate) mod sync;
+pub(crate) mod validate;
 
 /// Command-line options, parsed using `structopt`.
 #[derive(Debug, StructOpt)]
@@ -27,14 +41,47 @@ pub(crate) struct Opt {
     #[structopt(long = "log-extra")]
     pub(crate) log_extra: Vec<String>,
 
+    /// Refuse to run any command that would create, modify or drop data
+    /// anywhere but standard output. May also be turned on by setting
+    /// `DBCROSSBAR_READ_ONLY=1`.
+    #[structopt(long = "read-only")]
+    pub(crate) read_only: bool,
+
+    /// When finished, print a report of the resources used to standard error
+    /// (human, json).
+    #[structopt(long = "usage-report")]
+    pub(crate) usage_report: Option<UsageReportFormat>,
+
+    /// A directory in which to put local temporary files, instead of the
+    /// system temporary directory. May also be set using
+    /// `DBCROSSBAR_SCRATCH_DIR`.
+    #[structopt(long = "scratch-dir", parse(from_os_str))]
+    pub(crate) scratch_dir: Option<PathBuf>,
+
     /// The command to run.
     #[structopt(subcommand)]
     pub(crate) cmd: Command,
 }
 
 /// The command to run.
+///
+/// We only ever create one of these, so we don't mind that `cp::Opt` is much
+/// larger than the other variants.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, StructOpt)]
 pub(crate) enum Command {
+    /// Print a shell completion script.
+    #[structopt(name = "completions")]
+    #[structopt(after_help = r#"EXAMPLES:
+    dbcrossbar completions bash > /etc/bash_completion.d/dbcrossbar
+    dbcrossbar completions zsh > ~/.zfunc/_dbcrossbar
+    dbcrossbar completions fish > ~/.config/fish/completions/dbcrossbar.fish
+"#)]
+    Completions {
+        #[structopt(flatten)]
+        command: completions::Opt,
+    },
+
     /// Convert table schemas from one format to another.
     #[structopt(name = "conv")]
     #[structopt(after_help = r#"EXAMPLE LOCATORS:
@@ -60,9 +107,13 @@ pub(crate) enum Command {
 
     /// Copy tables from one location to another.
     #[structopt(name = "cp")]
+    #[structopt(
+        usage = "dbcrossbar cp [FLAGS] [OPTIONS] <from-locator>... <to-locator>"
+    )]
     #[structopt(after_help = r#"EXAMPLE LOCATORS:
     postgres://localhost:5432/db#table
     bigquery:project:dataset.table
+    bigquery:project:dataset (source only, with destination db#{table})
 "#)]
     Cp {
         #[structopt(flatten)]
@@ -75,13 +126,213 @@ pub(crate) enum Command {
         #[structopt(flatten)]
         command: features::Opt,
     },
+
+    /// Print the first few rows of a table.
+    #[structopt(name = "head")]
+    #[structopt(after_help = r#"EXAMPLE LOCATORS:
+    postgres://localhost:5432/db#table
+    bigquery:project:dataset.table
+    csv:data.csv
+"#)]
+    Head {
+        #[structopt(flatten)]
+        command: head::Opt,
+    },
+
+    /// List the tables or files behind a locator.
+    #[structopt(name = "ls")]
+    #[structopt(after_help = r#"EXAMPLE LOCATORS:
+    postgres://localhost:5432/db
+    bigquery:project:dataset
+    gs://example/dir/
+    s3://example/dir/
+"#)]
+    Ls {
+        #[structopt(flatten)]
+        command: ls::Opt,
+    },
+
+    /// Undo a `cp --backup-to` by restoring the backed-up destinations.
+    #[structopt(name = "rollback")]
+    Rollback {
+        #[structopt(flatten)]
+        command: rollback::Opt,
+    },
+
+    /// Drop tables, or delete the files in cloud storage directories.
+    #[structopt(name = "rm")]
+    #[structopt(after_help = r#"EXAMPLE LOCATORS:
+    postgres://localhost:5432/db#table
+    bigquery:project:dataset.table
+    gs://example/dir/
+    s3://example/dir/
+"#)]
+    Rm {
+        #[structopt(flatten)]
+        command: rm::Opt,
+    },
+
+    /// Run a batch of copies described by a YAML file.
+    #[structopt(name = "run")]
+    Run {
+        #[structopt(flatten)]
+        command: run::Opt,
+    },
+
+    /// Work with table schemas.
+    #[structopt(name = "schema")]
+    Schema {
+        #[structopt(subcommand)]
+        command: schema::Opt,
+    },
+
+    /// Copy only the rows added since the last sync.
+    #[structopt(name = "sync")]
+    #[structopt(
+        usage = "dbcrossbar sync [FLAGS] [OPTIONS] --incremental-key <incremental-key> --state <state> <from-locator> <to-locator>"
+    )]
+    #[structopt(after_help = r#"EXAMPLE STATE LOCATIONS:
+    file:sync_state.json
+    gs://example/sync_state.json
+    postgres://localhost:5432/db#sync_state
+"#)]
+    Sync {
+        #[structopt(flatten)]
+        command: sync::Opt,
+    },
+
+    /// Check that data matches a schema, without copying it.
+    #[structopt(name = "validate")]
+    #[structopt(after_help = r#"EXAMPLE LOCATORS:
+    csv:data.csv
+    s3://example/vendor/
+    gs://example/vendor/
+"#)]
+    Validate {
+        #[structopt(flatten)]
+        command: validate::Opt,
+    },
 }
 
-pub(crate) fn run(ctx: Context, opt: Opt) -> BoxFuture<()> {
+/// How to print our resource usage report.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum UsageReportFormat {
+    /// A summary for humans.
+    Human,
+    /// A single line of JSON.
+    Json,
+}
+
+impl UsageReportFormat {
+    /// Print `report` to standard error.
+    pub(crate) fn print(self, report: &UsageReport) -> Result<()> {
+        match self {
+            Self::Human => eprint!("{}", report),
+            Self::Json => eprintln!("{}", serde_json::to_string(report)?),
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for UsageReportFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(UsageReportFormat::Human),
+            "json" => Ok(UsageReportFormat::Json),
+            _ => Err(format_err!("unknown usage report format: {}", s)),
+        }
+    }
+}
+
+impl Opt {
+    /// Where should we put local temporary files?
+    pub(crate) fn scratch_dir(&self) -> Option<PathBuf> {
+        self.scratch_dir.clone().or_else(|| {
+            env::var_os("DBCROSSBAR_SCRATCH_DIR")
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from)
+        })
+    }
+
+    /// Should we refuse to change any data?
+    fn is_read_only(&self) -> bool {
+        self.read_only
+            || env::var("DBCROSSBAR_READ_ONLY")
+                .map(|v| !matches!(v.as_str(), "" | "0" | "false"))
+                .unwrap_or(false)
+    }
+}
+
+impl Command {
+    /// Fail if this command might create, modify or drop any data.
+    fn check_read_only(&self) -> Result<()> {
+        match self {
+            Command::Conv { command } => command.check_read_only(),
+            Command::Completions { .. }
+            | Command::Count { .. }
+            | Command::Features { .. }
+            | Command::Head { .. }
+            | Command::Ls { .. }
+            | Command::Schema { .. }
+            | Command::Validate { .. } => Ok(()),
+            Command::Cp { command } => command.check_read_only(),
+            Command::Run { command } => command.check_read_only(),
+            Command::Rm { .. } => {
+                Err(format_err!("cannot run `rm` in --read-only mode"))
+            }
+            Command::Sync { .. } => {
+                Err(format_err!("cannot run `sync` in --read-only mode"))
+            }
+            Command::Rollback { .. } => {
+                Err(format_err!("cannot run `rollback` in --read-only mode"))
+            }
+        }
+    }
+}
+
+/// Fail unless `dest` is standard output, which is the only place we may write
+/// to in `--read-only` mode.
+pub(crate) fn check_read_only_dest(dest: &BoxLocator) -> Result<()> {
+    if dest.is_stdio() {
+        Ok(())
+    } else {
+        Err(format_err!(
+            "cannot write to {} in --read-only mode (only standard output is allowed)",
+            dest,
+        ))
+    }
+}
+
+pub(crate) fn run(ctx: Context, mut opt: Opt) -> BoxFuture<()> {
+    // Figure out which of our `cp` arguments are inputs and which is the output.
+    match &mut opt.cmd {
+        Command::Cp { command } => command.arrange_locators(),
+        Command::Sync { command } => command.arrange_locators(),
+        _ => {}
+    }
+
+    // Refuse to do anything before we touch any data.
+    if opt.is_read_only() {
+        if let Err(err) = opt.cmd.check_read_only() {
+            return async move { Err(err) }.boxed();
+        }
+    }
+
     match opt.cmd {
+        Command::Completions { command } => completions::run(ctx, command).boxed(),
         Command::Conv { command } => conv::run(ctx, command).boxed(),
         Command::Count { command } => count::run(ctx, command).boxed(),
         Command::Cp { command } => cp::run(ctx, command).boxed(),
         Command::Features { command } => features::run(ctx, command).boxed(),
+        Command::Head { command } => head::run(ctx, command).boxed(),
+        Command::Ls { command } => ls::run(ctx, command).boxed(),
+        Command::Rollback { command } => rollback::run(ctx, command).boxed(),
+        Command::Rm { command } => rm::run(ctx, command).boxed(),
+        Command::Run { command } => run::run(ctx, command).boxed(),
+        Command::Schema { command } => schema::run(ctx, command).boxed(),
+        Command::Sync { command } => sync::run(ctx, command).boxed(),
+        Command::Validate { command } => validate::run(ctx, command).boxed(),
     }
 }
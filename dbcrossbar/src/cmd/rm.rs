@@ -0,0 +1,28 @@
+//! The `rm` subcommand.
+
+use common_failures::Result;
+use dbcrossbarlib::{BoxLocator, Context};
+use failure::ResultExt;
+use slog::{debug, o};
+use structopt::{self, StructOpt};
+
+/// Remove arguments.
+#[derive(Debug, StructOpt)]
+pub(crate) struct Opt {
+    /// The tables or directories to delete.
+    #[structopt(required = true)]
+    locators: Vec<BoxLocator>,
+}
+
+/// Delete each of our locators, in order.
+pub(crate) async fn run(ctx: Context, opt: Opt) -> Result<()> {
+    for locator in &opt.locators {
+        let ctx = ctx.child(o!("locator" => locator.to_string()));
+        debug!(ctx.log(), "removing {}", locator);
+        locator
+            .remove(ctx.clone())
+            .await
+            .with_context(|_| format!("could not remove {}", locator))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,68 @@
+//! The `rollback` subcommand.
+
+use common_failures::Result;
+use dbcrossbarlib::{BoxLocator, Context};
+use failure::ResultExt;
+use slog::{debug, o};
+use structopt::{self, StructOpt};
+
+use crate::cmd::cp;
+use crate::jobs::JobManifest;
+
+/// Rollback arguments.
+#[derive(Debug, StructOpt)]
+pub(crate) struct Opt {
+    /// Temporary directories, cloud storage buckets, datasets to use during
+    /// transfer (can be repeated). Defaults to the ones used by the original
+    /// copy.
+    #[structopt(long = "temporary")]
+    temporaries: Vec<String>,
+
+    /// How many data streams should we attempt to copy in parallel?
+    #[structopt(long = "max-streams", short = "J", default_value = "4")]
+    max_streams: usize,
+
+    /// The job ID printed by `cp --backup-to`.
+    job_id: String,
+}
+
+/// Restore every destination backed up by a job.
+pub(crate) async fn run(ctx: Context, opt: Opt) -> Result<()> {
+    let job = JobManifest::load(&opt.job_id)?;
+    let temporaries = if opt.temporaries.is_empty() {
+        job.temporaries.clone()
+    } else {
+        opt.temporaries.clone()
+    };
+
+    // Restore in reverse order, in case the same destination was somehow
+    // backed up more than once.
+    for record in job.backups.iter().rev() {
+        // Our records include passwords, so only log the parsed locators,
+        // which hide them.
+        let backup = record.backup.parse::<BoxLocator>()?;
+        let destination = record.destination.parse::<BoxLocator>()?;
+        let destination_name = destination.to_string();
+        let backup_name = backup.to_string();
+        let ctx = ctx.child(o!("destination" => destination_name.clone()));
+        debug!(
+            ctx.log(),
+            "restoring {} from {}", destination_name, backup_name
+        );
+        let cp_opt = cp::Opt::for_overwrite(
+            backup,
+            destination,
+            temporaries.clone(),
+            opt.max_streams,
+        );
+        cp::copy_table_with_schema(ctx, cp_opt, record.schema.clone())
+            .await
+            .with_context(|_| {
+                format!(
+                    "could not restore {} from {}",
+                    destination_name, backup_name,
+                )
+            })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,256 @@
+//! The `run` subcommand, which runs a batch of copies from a YAML file.
+
+use common_failures::Result;
+use dbcrossbarlib::Context;
+use failure::{format_err, ResultExt};
+use futures::{stream, StreamExt};
+use serde_derive::Deserialize;
+use slog::{debug, o, warn};
+use std::{collections::BTreeMap, fs, path::PathBuf, time::Instant};
+use structopt::{self, StructOpt};
+
+use crate::cmd::cp::{self, SkippedEmptyInputs};
+
+/// Run arguments.
+#[derive(Debug, StructOpt)]
+pub(crate) struct Opt {
+    /// How many jobs should we run at once? Overrides `max_parallel_jobs` in
+    /// the jobs file.
+    #[structopt(long = "max-parallel-jobs")]
+    max_parallel_jobs: Option<usize>,
+
+    /// A YAML file describing the copies to run.
+    #[structopt(parse(from_os_str))]
+    jobs_file: PathBuf,
+}
+
+impl Opt {
+    /// Fail if any of our jobs would write anywhere but standard output.
+    pub(crate) fn check_read_only(&self) -> Result<()> {
+        for job in JobsFile::load(&self.jobs_file)?.cp_opts()? {
+            job.opt.check_read_only()?;
+        }
+        Ok(())
+    }
+}
+
+/// A YAML file describing a batch of copies.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct JobsFile {
+    /// Temporary storage shared by all jobs.
+    #[serde(default)]
+    temporaries: Vec<String>,
+
+    /// How many jobs should we run at once?
+    #[serde(default = "default_max_parallel_jobs")]
+    max_parallel_jobs: usize,
+
+    /// The copies to run.
+    jobs: Vec<Job>,
+}
+
+/// By default, run one job at a time.
+fn default_max_parallel_jobs() -> usize {
+    1
+}
+
+/// A single copy in a `JobsFile`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Job {
+    /// A name for this job, used in our summary. Defaults to the destination.
+    name: Option<String>,
+
+    /// The source locator.
+    from: String,
+
+    /// The destination locator.
+    to: String,
+
+    /// One of `error`, `overwrite`, `append`, `upsert-on:COL` or
+    /// `merge-on:COL`.
+    if_exists: Option<String>,
+
+    /// The schema to use (defaults to the source's schema).
+    schema: Option<String>,
+
+    /// A SQL `WHERE` clause specifying rows to copy.
+    #[serde(rename = "where")]
+    where_clause: Option<String>,
+
+    /// Extra arguments for the source driver.
+    #[serde(default)]
+    from_args: BTreeMap<String, serde_yaml::Value>,
+
+    /// Extra arguments for the destination driver.
+    #[serde(default)]
+    to_args: BTreeMap<String, serde_yaml::Value>,
+
+    /// Temporary storage for just this job, in addition to the shared
+    /// `temporaries`.
+    #[serde(default)]
+    temporaries: Vec<String>,
+
+    /// How many data streams should this job copy in parallel?
+    max_streams: Option<usize>,
+}
+
+/// A job, converted to `cp` options.
+struct CpJob {
+    /// The name to use in our summary.
+    name: String,
+    /// Options for copying.
+    opt: cp::Opt,
+}
+
+impl JobsFile {
+    /// Load a jobs file from `path`.
+    fn load(path: &PathBuf) -> Result<JobsFile> {
+        let yaml = fs::read_to_string(path)
+            .with_context(|_| format!("could not read {}", path.display()))?;
+        let jobs_file = serde_yaml::from_str::<JobsFile>(&yaml)
+            .with_context(|_| format!("could not parse {}", path.display()))?;
+        Ok(jobs_file)
+    }
+
+    /// Convert each of our jobs into `cp` options.
+    fn cp_opts(&self) -> Result<Vec<CpJob>> {
+        self.jobs
+            .iter()
+            .enumerate()
+            .map(|(idx, job)| {
+                let name = job.name.clone().unwrap_or_else(|| job.to.clone());
+                let opt = job.cp_opt(&self.temporaries).with_context(|_| {
+                    format!("error in job {} ({})", idx + 1, name)
+                })?;
+                Ok(CpJob { name, opt })
+            })
+            .collect()
+    }
+}
+
+impl Job {
+    /// Build `cp` options for this job, by converting it to command-line
+    /// arguments. This ensures that jobs are checked exactly like the
+    /// arguments to `dbcrossbar cp`.
+    fn cp_opt(&self, shared_temporaries: &[String]) -> Result<cp::Opt> {
+        let mut args = vec!["cp".to_owned()];
+        if let Some(if_exists) = &self.if_exists {
+            args.push(format!("--if-exists={}", if_exists));
+        }
+        if let Some(schema) = &self.schema {
+            args.push(format!("--schema={}", schema));
+        }
+        if let Some(where_clause) = &self.where_clause {
+            args.push(format!("--where={}", where_clause));
+        }
+        for (key, value) in &self.from_args {
+            args.push(format!("--from-arg={}={}", key, driver_arg_value(value)?));
+        }
+        for (key, value) in &self.to_args {
+            args.push(format!("--to-arg={}={}", key, driver_arg_value(value)?));
+        }
+        for temporary in shared_temporaries.iter().chain(&self.temporaries) {
+            args.push(format!("--temporary={}", temporary));
+        }
+        if let Some(max_streams) = self.max_streams {
+            args.push(format!("--max-streams={}", max_streams));
+        }
+        args.push(self.from.clone());
+        args.push(self.to.clone());
+        let mut opt = cp::Opt::from_iter_safe(&args).map_err(|err| {
+            format_err!("{}", err.message.trim_start_matches("error: "))
+        })?;
+        opt.arrange_locators();
+        Ok(opt)
+    }
+}
+
+/// Convert a YAML driver argument to the string we'd pass on the command line.
+fn driver_arg_value(value: &serde_yaml::Value) -> Result<String> {
+    match value {
+        serde_yaml::Value::String(s) => Ok(s.to_owned()),
+        serde_yaml::Value::Bool(b) => Ok(b.to_string()),
+        serde_yaml::Value::Number(n) => Ok(n.to_string()),
+        _ => Err(format_err!(
+            "driver arguments must be strings, numbers or booleans"
+        )),
+    }
+}
+
+/// What happened when we ran a job.
+enum JobOutcome {
+    /// The copy succeeded.
+    Succeeded,
+    /// The copy skipped empty inputs because of `--allow-empty`.
+    Skipped(Vec<String>),
+    /// The copy failed.
+    Failed(failure::Error),
+}
+
+/// Run every job in our jobs file, and print a summary.
+pub(crate) async fn run(ctx: Context, opt: Opt) -> Result<()> {
+    // Check all our jobs before we start copying anything.
+    let jobs_file = JobsFile::load(&opt.jobs_file)?;
+    let jobs = jobs_file.cp_opts()?;
+    let max_parallel_jobs = opt
+        .max_parallel_jobs
+        .unwrap_or(jobs_file.max_parallel_jobs)
+        .max(1);
+
+    // Run our jobs, keeping going if any of them fail, so that we can report
+    // on all of them.
+    let job_count = jobs.len();
+    let results = stream::iter(jobs)
+        .map(|job| {
+            let ctx = ctx.child(o!("job" => job.name.clone()));
+            async move {
+                debug!(ctx.log(), "running job {}", job.name);
+                let start = Instant::now();
+                let outcome = match cp::run(ctx.clone(), job.opt).await {
+                    Ok(()) => JobOutcome::Succeeded,
+                    Err(err) => match err.downcast::<SkippedEmptyInputs>() {
+                        Ok(skipped) => JobOutcome::Skipped(skipped.inputs),
+                        Err(err) => {
+                            warn!(ctx.log(), "job {} failed: {}", job.name, err);
+                            JobOutcome::Failed(err)
+                        }
+                    },
+                };
+                (job.name, start.elapsed(), outcome)
+            }
+        })
+        .buffered(max_parallel_jobs)
+        .collect::<Vec<_>>()
+        .await;
+
+    // Print our summary.
+    let mut skipped = vec![];
+    let mut failed = 0;
+    eprintln!("dbcrossbar: ran jobs from {}:", opt.jobs_file.display());
+    for (name, elapsed, outcome) in results {
+        match outcome {
+            JobOutcome::Succeeded => {
+                eprintln!("  ok      {} ({:.1}s)", name, elapsed.as_secs_f64())
+            }
+            JobOutcome::Skipped(inputs) => {
+                eprintln!("  skipped {} (empty inputs: {})", name, inputs.join(", "));
+                skipped.extend(inputs);
+            }
+            JobOutcome::Failed(err) => {
+                let causes =
+                    err.iter_chain().map(|c| c.to_string()).collect::<Vec<_>>();
+                eprintln!("  FAILED  {}: {}", name, causes.join(": "));
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        Err(format_err!("{} of {} jobs failed", failed, job_count))
+    } else if !skipped.is_empty() {
+        Err(SkippedEmptyInputs { inputs: skipped }.into())
+    } else {
+        Ok(())
+    }
+}
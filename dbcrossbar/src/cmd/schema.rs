@@ -0,0 +1,86 @@
+//! The `schema` subcommand.
+
+use common_failures::Result;
+use dbcrossbarlib::{schema::Table, schema_diff::SchemaDiff, BoxLocator, Context};
+use failure::{format_err, Fail, ResultExt};
+use std::fmt;
+use structopt::{self, StructOpt};
+
+use super::UsageReportFormat;
+
+/// The exit status we use when `schema diff` finds differences. Errors use 1.
+pub(crate) const SCHEMAS_DIFFER_EXIT_CODE: i32 = 2;
+
+/// Schema subcommands.
+#[derive(Debug, StructOpt)]
+pub(crate) enum Opt {
+    /// Compare two schemas, and exit with status 2 if they differ.
+    #[structopt(name = "diff")]
+    #[structopt(after_help = r#"EXAMPLE:
+    dbcrossbar schema diff postgres://localhost:5432/db#users \
+        bigquery:project:dataset.users
+"#)]
+    Diff {
+        #[structopt(flatten)]
+        command: DiffOpt,
+    },
+}
+
+/// Schema diff arguments.
+#[derive(Debug, StructOpt)]
+pub(crate) struct DiffOpt {
+    /// How to print the differences (human, json).
+    #[structopt(long = "format", default_value = "human")]
+    format: UsageReportFormat,
+
+    /// The old schema.
+    old_locator: BoxLocator,
+
+    /// The new schema.
+    new_locator: BoxLocator,
+}
+
+/// Returned when `schema diff` finds differences, so that we can exit with a
+/// distinct status.
+#[derive(Debug)]
+pub(crate) struct SchemasDiffer;
+
+impl fmt::Display for SchemasDiffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "schemas differ")
+    }
+}
+
+impl Fail for SchemasDiffer {}
+
+/// Run a schema subcommand.
+pub(crate) async fn run(ctx: Context, opt: Opt) -> Result<()> {
+    match opt {
+        Opt::Diff { command } => diff(ctx, command).await,
+    }
+}
+
+/// Print the differences between two schemas.
+async fn diff(ctx: Context, opt: DiffOpt) -> Result<()> {
+    let old = read_schema(&ctx, &opt.old_locator).await?;
+    let new = read_schema(&ctx, &opt.new_locator).await?;
+    let diff = SchemaDiff::new(&old, &new);
+    match opt.format {
+        UsageReportFormat::Human => print!("{}", diff),
+        UsageReportFormat::Json => println!("{}", serde_json::to_string(&diff)?),
+    }
+    if diff.is_empty() {
+        Ok(())
+    } else {
+        Err(SchemasDiffer.into())
+    }
+}
+
+/// Read the schema of the table at `locator`.
+async fn read_schema(ctx: &Context, locator: &BoxLocator) -> Result<Table> {
+    locator
+        .schema(ctx.clone())
+        .await
+        .with_context(|_| format!("error reading schema from {}", locator))?
+        .ok_or_else(|| format_err!("don't know how to read schema from {}", locator))
+}
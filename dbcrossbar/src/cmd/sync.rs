@@ -0,0 +1,208 @@
+//! The `sync` subcommand, which copies only the rows added since the last sync.
+
+use chrono::Utc;
+use common_failures::Result;
+use dbcrossbarlib::{
+    schema::DataType,
+    sync_state::{SyncState, SyncStateStore},
+    Context,
+};
+use failure::format_err;
+use slog::debug;
+use structopt::{self, StructOpt};
+
+use crate::cmd::cp;
+
+/// Sync arguments.
+#[derive(Debug, StructOpt)]
+pub(crate) struct Opt {
+    /// Only copy rows where this column is larger than the largest value we
+    /// copied last time. This should be a column like `updated_at` or `id`,
+    /// which only ever increases.
+    #[structopt(long = "incremental-key")]
+    incremental_key: String,
+
+    /// Where to remember how far we've copied: `file:PATH`,
+    /// `gs://BUCKET/PATH` or `postgres://HOST/DB#TABLE`.
+    #[structopt(long = "state")]
+    state: SyncStateStore,
+
+    /// The name under which to store our progress (defaults to the source and
+    /// destination locators).
+    #[structopt(long = "name")]
+    name: Option<String>,
+
+    #[structopt(flatten)]
+    cp: cp::Opt,
+}
+
+impl Opt {
+    /// Figure out which of our `cp` arguments are inputs and which is the
+    /// output. This must be called exactly once after parsing our arguments.
+    pub(crate) fn arrange_locators(&mut self) {
+        self.cp.arrange_locators();
+    }
+
+    /// The name of this sync.
+    fn name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            format!("{} -> {}", self.cp.source_locator(), self.cp.dest_locator())
+        })
+    }
+}
+
+/// Copy any rows that are newer than our high-water mark, and then record a new
+/// high-water mark.
+pub(crate) async fn run(ctx: Context, mut opt: Opt) -> Result<()> {
+    opt.cp.check_sync()?;
+    let name = opt.name();
+    let key = opt.incremental_key.clone();
+
+    // Find our incremental key, so that we know how to compare it.
+    let schema = cp::read_schema(&ctx, &opt.cp).await?;
+    let data_type = schema
+        .columns
+        .iter()
+        .find(|c| c.name == key)
+        .map(|c| c.data_type.clone())
+        .ok_or_else(|| {
+            format_err!("cannot find --incremental-key column {:?} in schema", key)
+        })?;
+    let column = sql_column(&key)?;
+
+    // Find the range of keys that we need to copy. We copy everything up to
+    // and including the current largest key, so that rows added while we copy
+    // will be picked up by the next sync.
+    let previous = opt.state.load(&ctx, &name).await?;
+    let mut conditions = opt
+        .cp
+        .where_clause()
+        .map(|w| vec![format!("({})", w)])
+        .unwrap_or_default();
+    if let Some(previous) = &previous {
+        conditions.push(format!(
+            "{} > {}",
+            column,
+            sql_literal(&previous.high_water_mark, &data_type)?,
+        ));
+    }
+    opt.cp.set_where_clause(where_clause(&conditions));
+    let (shared_args, source_args) = opt.cp.source_arguments(schema)?;
+    let high_water_mark = opt
+        .cp
+        .source_locator()
+        .max_value(ctx.clone(), shared_args, source_args, &key)
+        .await?;
+    let high_water_mark = match high_water_mark {
+        Some(high_water_mark) => high_water_mark,
+        None => {
+            eprintln!(
+                "dbcrossbar: no new rows in {} to sync",
+                opt.cp.source_locator()
+            );
+            return Ok(());
+        }
+    };
+    conditions.push(format!(
+        "{} <= {}",
+        column,
+        sql_literal(&high_water_mark, &data_type)?,
+    ));
+
+    // Copy our new rows.
+    let where_clause = where_clause(&conditions);
+    debug!(ctx.log(), "syncing rows where {:?}", where_clause);
+    opt.cp.set_where_clause(where_clause);
+    let from_locator = opt.cp.source_locator().to_string();
+    cp::run(ctx.clone(), opt.cp).await?;
+
+    // Record how far we got.
+    let state = SyncState {
+        high_water_mark,
+        synced_at: Utc::now(),
+    };
+    opt.state.save(&ctx, &name, &state).await?;
+    eprintln!(
+        "dbcrossbar: synced {} up to {} = {}",
+        from_locator, key, state.high_water_mark,
+    );
+    Ok(())
+}
+
+/// Combine `conditions` into a single `WHERE` clause.
+fn where_clause(conditions: &[String]) -> Option<String> {
+    if conditions.is_empty() {
+        None
+    } else {
+        Some(conditions.join(" AND "))
+    }
+}
+
+/// Return `name` for use in SQL. Different databases quote column names in
+/// different ways, so we only allow names which never need quoting.
+fn sql_column(name: &str) -> Result<&str> {
+    let mut chars = name.chars();
+    let is_simple = chars
+        .next()
+        .map(|c| c.is_ascii_lowercase() || c == '_')
+        .unwrap_or(false)
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if is_simple {
+        Ok(name)
+    } else {
+        Err(format_err!(
+            "--incremental-key {:?} must be a lowercase column name containing only letters, digits and `_`",
+            name,
+        ))
+    }
+}
+
+/// Return `value` as an SQL literal that can be compared to a column of type
+/// `data_type`.
+fn sql_literal(value: &str, data_type: &DataType) -> Result<String> {
+    match data_type {
+        DataType::Decimal
+        | DataType::DecimalWithPrecision(_)
+        | DataType::Float32
+        | DataType::Float64
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64 => {
+            // Make sure this really is a number before we put it in our SQL.
+            // We can't use `f64::from_str`, because it accepts values like
+            // `NaN` and `inf`, which aren't numeric literals.
+            if is_decimal_literal(value) {
+                Ok(value.to_owned())
+            } else {
+                Err(format_err!("expected a number, found {:?}", value))
+            }
+        }
+        _ => Ok(format!("'{}'", value.replace('\'', "''"))),
+    }
+}
+
+/// Is `value` a plain decimal number, like `-12`, `3.5` or `1.5e-3`?
+fn is_decimal_literal(value: &str) -> bool {
+    fn skip_digits(s: &str) -> (usize, &str) {
+        let len = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        (len, &s[len..])
+    }
+
+    let rest = value.strip_prefix('-').unwrap_or(value);
+    let (int_digits, rest) = skip_digits(rest);
+    let (frac_digits, rest) = match rest.strip_prefix('.') {
+        Some(rest) => skip_digits(rest),
+        None => (0, rest),
+    };
+    if int_digits + frac_digits == 0 {
+        return false;
+    }
+    match rest.strip_prefix(['e', 'E']) {
+        Some(exp) => {
+            let exp = exp.strip_prefix(['+', '-']).unwrap_or(exp);
+            let (exp_digits, rest) = skip_digits(exp);
+            exp_digits > 0 && rest.is_empty()
+        }
+        None => rest.is_empty(),
+    }
+}
@@ -0,0 +1,82 @@
+//! The `validate` subcommand.
+
+use common_failures::Result;
+use dbcrossbarlib::{
+    validate::validate_csv_streams, BoxLocator, Context, DriverArguments,
+    SharedArguments, SourceArguments, TemporaryStorage,
+};
+use failure::{format_err, ResultExt};
+use structopt::{self, StructOpt};
+
+use super::UsageReportFormat;
+
+/// Validation arguments.
+#[derive(Debug, StructOpt)]
+pub(crate) struct Opt {
+    /// The schema to check the data against.
+    #[structopt(long = "schema")]
+    schema: BoxLocator,
+
+    /// How many invalid cells should we describe?
+    #[structopt(long = "max-failures", default_value = "10")]
+    max_failures: usize,
+
+    /// How to print the results (human, json).
+    #[structopt(long = "format", default_value = "human")]
+    format: UsageReportFormat,
+
+    /// Temporary directories, cloud storage buckets, datasets to use during
+    /// transfer (can be repeated).
+    #[structopt(long = "temporary")]
+    temporaries: Vec<String>,
+
+    /// Pass an extra argument of the form `key=value` to the source driver.
+    #[structopt(long = "from-arg")]
+    from_args: Vec<String>,
+
+    /// The data to check.
+    locator: BoxLocator,
+}
+
+/// Check every cell of our input against the schema.
+pub(crate) async fn run(ctx: Context, opt: Opt) -> Result<()> {
+    let schema = opt
+        .schema
+        .schema(ctx.clone())
+        .await
+        .with_context(|_| format!("error reading schema from {}", opt.schema))?
+        .ok_or_else(|| {
+            format_err!("don't know how to read schema from {}", opt.schema)
+        })?;
+
+    let temporary_storage = TemporaryStorage::new(opt.temporaries.clone());
+    let shared_args = SharedArguments::new(schema.clone(), temporary_storage, 1);
+    let from_args = DriverArguments::from_cli_args(&opt.from_args)?;
+    let source_args = SourceArguments::new(from_args, None);
+    let data = opt
+        .locator
+        .local_data(ctx.clone(), shared_args, source_args)
+        .await?
+        .ok_or_else(|| {
+            format_err!("don't know how to read data from {}", opt.locator)
+        })?;
+
+    let report = validate_csv_streams(ctx, schema, data, opt.max_failures).await?;
+    match opt.format {
+        UsageReportFormat::Human => {
+            for failure in &report.failures {
+                println!("{}", failure);
+            }
+        }
+        UsageReportFormat::Json => println!("{}", serde_json::to_string(&report)?),
+    }
+    if report.is_valid() {
+        Ok(())
+    } else {
+        Err(format_err!(
+            "found {} invalid cells in {} rows",
+            report.failure_count,
+            report.row_count,
+        ))
+    }
+}
@@ -0,0 +1,173 @@
+//! Records of destructive copies, which allow us to roll them back later.
+//!
+//! When `cp --backup-to` backs up a destination before changing it, we write a
+//! small JSON "job manifest" describing what we backed up and where. The
+//! `rollback` subcommand reads this manifest and copies the backups back over
+//! the destinations.
+
+use common_failures::Result;
+use dbcrossbarlib::schema::Table;
+use failure::{format_err, ResultExt};
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A single destination which we backed up before changing it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct BackupRecord {
+    /// The destination which we changed, including any password. Don't log
+    /// this directly.
+    pub(crate) destination: String,
+    /// Where we put a copy of `destination` before changing it, including any
+    /// password.
+    pub(crate) backup: String,
+    /// The schema of `destination` before we changed it.
+    pub(crate) schema: Table,
+}
+
+/// Everything we need to know to roll back a `cp` command.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct JobManifest {
+    /// A unique ID for this job.
+    pub(crate) id: String,
+    /// Temporary storage used during the original copy.
+    pub(crate) temporaries: Vec<String>,
+    /// The destinations we backed up, in the order we backed them up.
+    pub(crate) backups: Vec<BackupRecord>,
+}
+
+impl JobManifest {
+    /// Create a new manifest with a fresh job ID.
+    pub(crate) fn new(temporaries: Vec<String>) -> Result<JobManifest> {
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .context("system clock is set before 1970")?;
+        Ok(JobManifest {
+            id: format!("{}-{}", since_epoch.as_secs(), process::id()),
+            temporaries,
+            backups: vec![],
+        })
+    }
+
+    /// Load the manifest for the job with the specified ID.
+    pub(crate) fn load(id: &str) -> Result<JobManifest> {
+        if id.is_empty() || id.contains(['/', '\\', '.']) {
+            return Err(format_err!("invalid job ID {:?}", id));
+        }
+        let path = jobs_dir()?.join(format!("{}.json", id));
+        let data = fs::read_to_string(&path)
+            .with_context(|_| format!("could not read job {}", path.display()))?;
+        let manifest = serde_json::from_str(&data)
+            .with_context(|_| format!("could not parse job {}", path.display()))?;
+        Ok(manifest)
+    }
+
+    /// Save this manifest, replacing any earlier version.
+    pub(crate) fn save(&self) -> Result<()> {
+        self.save_in(&jobs_dir()?)
+    }
+
+    /// Save this manifest in `dir`. Job manifests may contain database
+    /// passwords, so we only allow their owner to read them. We write to a
+    /// temporary file which is never readable by anybody else, and then rename
+    /// it into place, so that readers never see a partial manifest.
+    fn save_in(&self, dir: &Path) -> Result<()> {
+        create_private_dir(dir)
+            .with_context(|_| format!("could not create {}", dir.display()))?;
+        let path = dir.join(format!("{}.json", self.id));
+        let tmp_path = dir.join(format!(".{}.json.tmp", self.id));
+        let data = serde_json::to_string_pretty(self)?;
+        let write_tmp = || -> io::Result<()> {
+            // Remove anything left behind by a crash, because `create_new`
+            // will only set our permissions on a new file.
+            match fs::remove_file(&tmp_path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+            let mut file = private_file_options().open(&tmp_path)?;
+            file.write_all(data.as_bytes())?;
+            file.sync_all()
+        };
+        write_tmp()
+            .with_context(|_| format!("could not write job {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, &path)
+            .with_context(|_| format!("could not write job {}", path.display()))?;
+        Ok(())
+    }
+}
+
+/// Create `dir` and any missing parents, only allowing their owner to access
+/// them.
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)
+}
+
+/// Options for creating a new file which only its owner can access.
+#[cfg(unix)]
+fn private_file_options() -> fs::OpenOptions {
+    use std::os::unix::fs::OpenOptionsExt;
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true).mode(0o600);
+    options
+}
+
+#[cfg(not(unix))]
+fn private_file_options() -> fs::OpenOptions {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    options
+}
+
+/// The directory where we store job manifests. This can be overridden using
+/// `DBCROSSBAR_JOBS_DIR`.
+fn jobs_dir() -> Result<PathBuf> {
+    if let Some(dir) = env::var_os("DBCROSSBAR_JOBS_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    let home = env::var_os("HOME").ok_or_else(|| {
+        format_err!("cannot find home directory (try setting DBCROSSBAR_JOBS_DIR)")
+    })?;
+    Ok(PathBuf::from(home).join(".dbcrossbar").join("jobs"))
+}
+
+#[test]
+fn save_creates_private_files() {
+    let parent =
+        env::temp_dir().join(format!("dbcrossbar-jobs-{}", rand::random::<u64>()));
+    let dir = parent.join("jobs");
+    let mut manifest = JobManifest::new(vec![]).unwrap();
+    manifest.save_in(&dir).unwrap();
+    manifest.temporaries.push("s3://example/tmp/".to_owned());
+    manifest.save_in(&dir).unwrap();
+
+    let path = dir.join(format!("{}.json", manifest.id));
+    let saved: JobManifest =
+        serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(saved.temporaries, manifest.temporaries);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = |p: &Path| fs::metadata(p).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&dir), 0o700);
+        assert_eq!(mode(&path), 0o600);
+    }
+
+    fs::remove_dir_all(&parent).unwrap();
+}
@@ -0,0 +1,180 @@
+//! Lineage events describing completed copies.
+//!
+//! When `cp --lineage-to=PATH` finishes copying a table, we append an
+//! [OpenLineage](https://openlineage.io/) `RunEvent` to `PATH`, one JSON
+//! document per line. This describes the source and destination, the schema
+//! we wrote, which source column each destination column came from, and any
+//! transforms we applied along the way. Data catalogs can use these events to
+//! track where warehouse tables came from.
+
+use chrono::{SecondsFormat, Utc};
+use common_failures::Result;
+use dbcrossbarlib::{schema::Table, usage::driver_name, BoxLocator};
+use failure::ResultExt;
+use serde_json::{json, Map, Value};
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+};
+use url::Url;
+use uuid::{Builder, Uuid, Variant, Version};
+
+/// Identifies us as the producer of lineage events.
+const PRODUCER: &str = "https://github.com/faradayio/dbcrossbar";
+
+/// The version of the OpenLineage spec we follow.
+const SPEC_URL: &str = "https://openlineage.io/spec/2-0-2/OpenLineage.json";
+
+/// The base URL for the OpenLineage facet specs.
+const FACETS_URL: &str = "https://openlineage.io/spec/facets/1-0-1";
+
+/// A lineage event, and the file to which we'll write it.
+#[derive(Clone, Debug)]
+pub(crate) struct LineageEvent {
+    /// The file to which we'll append our event.
+    path: PathBuf,
+    /// The event itself.
+    event: Value,
+}
+
+impl LineageEvent {
+    /// Describe a copy from `from_locator` to `to_locator`, reading data using
+    /// `schema` and writing it using `dest_schema`, which must contain the same
+    /// columns in the same order, possibly renamed. `transforms` should contain a short description of each change we make
+    /// to the data along the way.
+    pub(crate) fn for_copy(
+        path: &Path,
+        from_locator: &BoxLocator,
+        to_locator: &BoxLocator,
+        schema: &Table,
+        dest_schema: &Table,
+        transforms: &[String],
+    ) -> LineageEvent {
+        let (input_namespace, input_name) = dataset_id(from_locator);
+        let (output_namespace, output_name) = dataset_id(to_locator);
+
+        // Each destination column comes from the source column in the same
+        // position.
+        let mut column_lineage = Map::new();
+        for (col, dest_col) in schema.columns.iter().zip(&dest_schema.columns) {
+            column_lineage.insert(
+                dest_col.name.clone(),
+                json!({
+                    "inputFields": [{
+                        "namespace": input_namespace,
+                        "name": input_name,
+                        "field": col.name,
+                    }],
+                    "transformationDescription": "copied unchanged",
+                    "transformationType": "IDENTITY",
+                }),
+            );
+        }
+
+        let event = json!({
+            "eventType": "COMPLETE",
+            "eventTime": Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            "producer": PRODUCER,
+            "schemaURL": format!("{}#/$defs/RunEvent", SPEC_URL),
+            "run": {
+                "runId": random_uuid().to_string(),
+                "facets": {
+                    "dbcrossbar": {
+                        "_producer": PRODUCER,
+                        "_schemaURL": format!("{}#/$defs/RunFacet", SPEC_URL),
+                        "transforms": transforms,
+                    },
+                },
+            },
+            "job": {
+                "namespace": "dbcrossbar",
+                "name": format!("cp {} {}", input_name, output_name),
+            },
+            "inputs": [{
+                "namespace": input_namespace,
+                "name": input_name,
+                "facets": { "schema": schema_facet(schema) },
+            }],
+            "outputs": [{
+                "namespace": output_namespace,
+                "name": output_name,
+                "facets": {
+                    "schema": schema_facet(dest_schema),
+                    "columnLineage": {
+                        "_producer": PRODUCER,
+                        "_schemaURL": format!(
+                            "{}/ColumnLineageDatasetFacet.json",
+                            FACETS_URL,
+                        ),
+                        "fields": column_lineage,
+                    },
+                },
+            }],
+        });
+        LineageEvent {
+            path: path.to_owned(),
+            event,
+        }
+    }
+
+    /// Append this event to our lineage file.
+    pub(crate) fn write(&self) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|_| format!("could not open {}", self.path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(&self.event)?)
+            .with_context(|_| format!("could not write {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+/// Build an OpenLineage dataset namespace and name for `locator`. We remove
+/// any passwords, because lineage events are typically shared widely.
+fn dataset_id(locator: &BoxLocator) -> (String, String) {
+    let namespace = driver_name(locator.as_ref());
+    let name = locator.to_string();
+    let name = match Url::parse(&name) {
+        Ok(mut url) if url.password().is_some() => {
+            url.set_password(None)
+                .expect("should be able to remove password");
+            url.to_string()
+        }
+        _ => name,
+    };
+    (namespace, name)
+}
+
+/// Build an OpenLineage schema facet for `schema`.
+fn schema_facet(schema: &Table) -> Value {
+    let fields = schema
+        .columns
+        .iter()
+        .map(|col| {
+            // OpenLineage wants a string, so use our serialized type name, or
+            // the JSON for more complicated types.
+            let data_type = match serde_json::to_value(&col.data_type)
+                .expect("data types should always serialize")
+            {
+                Value::String(s) => s,
+                other => other.to_string(),
+            };
+            json!({ "name": col.name, "type": data_type })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "_producer": PRODUCER,
+        "_schemaURL": format!("{}/SchemaDatasetFacet.json", FACETS_URL),
+        "fields": fields,
+    })
+}
+
+/// Generate a random (version 4) UUID for a run ID.
+fn random_uuid() -> Uuid {
+    Builder::from_bytes(rand::random())
+        .set_variant(Variant::RFC4122)
+        .set_version(Version::Random)
+        .build()
+}
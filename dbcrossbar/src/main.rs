@@ -6,22 +6,23 @@
 #[allow(unused_extern_crates)]
 extern crate openssl;
 
-// Pull in all of `tokio`'s experimental `async` and `await` support.
-#[macro_use]
-#[allow(unused_imports)]
-extern crate tokio;
-
 use common_failures::{quick_main, Result};
-use dbcrossbarlib::{run_futures_with_runtime, Context};
+use dbcrossbarlib::{run_futures_with_runtime, Context, ScratchDir};
 use env_logger;
 use openssl_probe;
 use slog::{debug, Drain};
 use slog_async::{self, OverflowStrategy};
 use slog_envlogger;
+use std::process;
 use structopt::{self, StructOpt};
 
 mod cmd;
+mod jobs;
+mod lineage;
 mod logging;
+mod regions;
+mod resume;
+mod table_filter;
 
 quick_main!(run);
 
@@ -35,6 +36,15 @@ fn run() -> Result<()> {
     // Parse our command-line arguments.
     let opt = cmd::Opt::from_args();
 
+    // If we were given a scratch directory, create a private directory inside
+    // it for our temporary files. This is removed when `scratch_dir` is
+    // dropped, even if the command fails. We do this before we start any
+    // threads, because it changes our environment variables.
+    let mut scratch_dir = match opt.scratch_dir() {
+        Some(parent) => Some(ScratchDir::new(&parent)?),
+        None => None,
+    };
+
     // Set up `slog`-based structured logging for our async code, because we
     // need to be able to untangle very complicated logs from many parallel
     // async tasks.
@@ -59,9 +69,43 @@ fn run() -> Result<()> {
     // Log our command-line options.
     debug!(ctx.log(), "{:?}", opt);
 
+    // Keep track of the resources we use, so that we can report them later.
+    let usage = ctx.usage().clone();
+    let usage_report = opt.usage_report;
+
+    // Measure our scratch directory, if we have one.
+    if let Some(scratch_dir) = &mut scratch_dir {
+        debug!(
+            ctx.log(),
+            "using scratch directory {}",
+            scratch_dir.path().display()
+        );
+        scratch_dir.record_usage(usage.clone())?;
+    }
+
     // Create a future to run our command.
     let cmd_fut = cmd::run(ctx, opt);
 
     // Run our futures.
-    run_futures_with_runtime(cmd_fut, worker_fut)
+    let result = run_futures_with_runtime(cmd_fut, worker_fut);
+    drop(scratch_dir);
+
+    // Report our resource usage, even if the command failed.
+    if let Some(format) = usage_report {
+        format.print(&usage.report())?;
+    }
+
+    // If `cp --allow-empty` skipped anything, report it as a warning and use a
+    // distinct exit status, so that scripts can tell this apart from failure.
+    if let Err(err) = &result {
+        if let Some(skipped) = err.downcast_ref::<cmd::cp::SkippedEmptyInputs>() {
+            eprintln!("dbcrossbar: warning: {}", skipped);
+            process::exit(cmd::cp::SKIPPED_EMPTY_EXIT_CODE);
+        }
+        // `schema diff` has already printed the differences.
+        if err.downcast_ref::<cmd::schema::SchemasDiffer>().is_some() {
+            process::exit(cmd::schema::SCHEMAS_DIFFER_EXIT_CODE);
+        }
+    }
+    result
 }
@@ -0,0 +1,138 @@
+//! Regional replicas of BigQuery and Google Cloud Storage locations.
+//!
+//! `cp --replicate-to-regions=eu,asia` copies a table once, and then copies
+//! the result to a sibling location in each region. For `bigquery:` locators,
+//! we append `_REGION` to the dataset name, and for `gs://` locators, we append
+//! `-REGION` to the bucket name. The sibling datasets and buckets must already
+//! exist in the appropriate regions.
+
+use common_failures::Result;
+use failure::format_err;
+
+/// Build the locator for the replica of `locator` in `region`.
+pub(crate) fn regional_locator(locator: &str, region: &str) -> Result<String> {
+    check_region(region)?;
+    if let Some(rest) = locator.strip_prefix("bigquery:") {
+        // This may be either `project:dataset.table` or just
+        // `project:dataset`, if we're looking at a temporary dataset.
+        let (project, dataset_and_table) = rest
+            .split_once(':')
+            .ok_or_else(|| format_err!("cannot parse {}", locator))?;
+        let suffix = region.replace('-', "_");
+        Ok(match dataset_and_table.split_once('.') {
+            Some((dataset, table)) => {
+                format!("bigquery:{}:{}_{}.{}", project, dataset, suffix, table)
+            }
+            None => format!("bigquery:{}:{}_{}", project, dataset_and_table, suffix),
+        })
+    } else if let Some(rest) = locator.strip_prefix("gs://") {
+        let (bucket, path) = match rest.split_once('/') {
+            Some((bucket, path)) => (bucket, Some(path)),
+            None => (rest, None),
+        };
+        if bucket.is_empty() {
+            return Err(format_err!("cannot parse {}", locator));
+        }
+        Ok(match path {
+            Some(path) => format!("gs://{}-{}/{}", bucket, region, path),
+            None => format!("gs://{}-{}", bucket, region),
+        })
+    } else {
+        Err(format_err!(
+            "--replicate-to-regions only supports bigquery: and gs:// destinations, not {}",
+            locator,
+        ))
+    }
+}
+
+/// Build the temporary locations to use when replicating to `region`. Our
+/// Google Cloud temporaries need to be in the same region as the replica, so
+/// we use their regional siblings. Other temporaries are used as-is.
+pub(crate) fn regional_temporaries(
+    temporaries: &[String],
+    region: &str,
+) -> Result<Vec<String>> {
+    temporaries
+        .iter()
+        .map(|temp| {
+            if temp.starts_with("bigquery:") || temp.starts_with("gs://") {
+                regional_locator(temp, region)
+            } else {
+                Ok(temp.to_owned())
+            }
+        })
+        .collect()
+}
+
+/// Make sure `region` looks like a Google Cloud region or multi-region name.
+fn check_region(region: &str) -> Result<()> {
+    let valid = !region.is_empty()
+        && region
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(format_err!("invalid region name {:?}", region))
+    }
+}
+
+#[test]
+fn regional_locator_rewrites_bigquery_and_gs() {
+    let examples = &[
+        (
+            "bigquery:my-project:my_dataset.my_table",
+            "europe-west2",
+            "bigquery:my-project:my_dataset_europe_west2.my_table",
+        ),
+        (
+            "bigquery:my-project:temp",
+            "eu",
+            "bigquery:my-project:temp_eu",
+        ),
+        (
+            "gs://my-bucket/path/to/data/",
+            "asia",
+            "gs://my-bucket-asia/path/to/data/",
+        ),
+        ("gs://my-bucket/", "eu", "gs://my-bucket-eu/"),
+        ("gs://my-bucket", "us-east1", "gs://my-bucket-us-east1"),
+    ];
+    for &(locator, region, expected) in examples {
+        assert_eq!(regional_locator(locator, region).unwrap(), expected);
+    }
+}
+
+#[test]
+fn regional_locator_rejects_bad_input() {
+    // Unsupported destinations.
+    assert!(regional_locator("s3://my-bucket/data/", "eu").is_err());
+    assert!(regional_locator("postgres://localhost/db#table", "eu").is_err());
+
+    // Unparseable locators.
+    assert!(regional_locator("bigquery:my_dataset", "eu").is_err());
+    assert!(regional_locator("gs:///path/", "eu").is_err());
+
+    // Invalid region names.
+    assert!(regional_locator("gs://my-bucket/", "").is_err());
+    assert!(regional_locator("gs://my-bucket/", "EU").is_err());
+    assert!(regional_locator("gs://my-bucket/", "eu/../x").is_err());
+}
+
+#[test]
+fn regional_temporaries_only_rewrites_google_cloud() {
+    let temporaries = vec![
+        "gs://temp-bucket/".to_owned(),
+        "bigquery:my-project:temp".to_owned(),
+        "s3://temp-bucket/".to_owned(),
+    ];
+    assert_eq!(
+        regional_temporaries(&temporaries, "eu").unwrap(),
+        vec![
+            "gs://temp-bucket-eu/",
+            "bigquery:my-project:temp_eu",
+            "s3://temp-bucket/",
+        ],
+    );
+    assert!(regional_temporaries(&temporaries, "Bad Region").is_err());
+}
@@ -0,0 +1,203 @@
+//! Records of finished streams, which allow us to resume failed copies.
+//!
+//! When `cp --resume=PATH` finishes writing a stream to its destination, we
+//! append a line to `PATH` naming the destination and the stream. If the copy
+//! fails, running it again with the same `--resume=PATH` skips every stream
+//! which is already listed, instead of starting over from the beginning.
+
+use common_failures::Result;
+use dbcrossbarlib::{BoxLocator, Context};
+use failure::{Fail, ResultExt};
+use serde_derive::{Deserialize, Serialize};
+use slog::warn;
+use std::{
+    collections::HashSet,
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+/// A single line in our resume file.
+#[derive(Debug, Deserialize, Serialize)]
+struct FinishedStream {
+    /// The destination we wrote to, without any password.
+    destination: String,
+    /// The name of the stream we finished writing.
+    stream: String,
+}
+
+/// The streams which we've already written to a single destination.
+#[derive(Debug)]
+pub(crate) struct ResumeFile {
+    /// The file which lists our finished streams.
+    path: PathBuf,
+    /// The destination we're writing to, without any password.
+    destination: String,
+    /// The streams we've already written to `destination`.
+    finished: HashSet<String>,
+}
+
+impl ResumeFile {
+    /// Load the streams we've already written to `destination` from `path`.
+    /// If `path` doesn't exist, we haven't written anything yet.
+    ///
+    /// If we crashed while recording a stream, the last line may be incomplete.
+    /// We ignore it (and remove it, so that we can append more lines), because
+    /// that stream was never recorded as finished.
+    pub(crate) fn load(
+        ctx: &Context,
+        path: &Path,
+        destination: &BoxLocator,
+    ) -> Result<ResumeFile> {
+        let destination = destination.to_string();
+        let data = match fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+            Err(err) => {
+                return Err(err
+                    .context(format!("could not read {}", path.display()))
+                    .into())
+            }
+        };
+        let mut finished = HashSet::new();
+        let mut offset = 0;
+        let mut lines = data.split_terminator('\n').enumerate().peekable();
+        while let Some((idx, line)) = lines.next() {
+            let is_last = lines.peek().is_none();
+            let has_newline = data[offset + line.len()..].starts_with('\n');
+            let record = if line.trim().is_empty() {
+                None
+            } else {
+                match serde_json::from_str::<FinishedStream>(line) {
+                    Ok(record) if has_newline => Some(record),
+                    Ok(_) | Err(_) if is_last => {
+                        warn!(
+                            ctx.log(),
+                            "ignoring incomplete last line of {}",
+                            path.display(),
+                        );
+                        truncate(path, offset)?;
+                        break;
+                    }
+                    Ok(_) => unreachable!("only the last line can lack a newline"),
+                    Err(err) => {
+                        return Err(err
+                            .context(format!(
+                                "could not parse line {} of {}",
+                                idx + 1,
+                                path.display(),
+                            ))
+                            .into())
+                    }
+                }
+            };
+            if let Some(record) = record {
+                if record.destination == destination {
+                    finished.insert(record.stream);
+                }
+            }
+            offset += line.len() + 1;
+        }
+        Ok(ResumeFile {
+            path: path.to_owned(),
+            destination,
+            finished,
+        })
+    }
+
+    /// Have we already written any streams to our destination?
+    pub(crate) fn has_progress(&self) -> bool {
+        !self.finished.is_empty()
+    }
+
+    /// Have we already written `stream` to our destination?
+    pub(crate) fn is_finished(&self, stream: &str) -> bool {
+        self.finished.contains(stream)
+    }
+
+    /// Record that we've finished writing `stream` to our destination.
+    pub(crate) fn record(&self, stream: &str) -> Result<()> {
+        let record = FinishedStream {
+            destination: self.destination.clone(),
+            stream: stream.to_owned(),
+        };
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|_| format!("could not open {}", self.path.display()))?;
+        // Write the whole line at once, and make sure it reaches the disk
+        // before we move on.
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+        file.write_all(line.as_bytes())
+            .and_then(|()| file.sync_data())
+            .with_context(|_| format!("could not write {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+/// Truncate the file at `path` to `len` bytes.
+fn truncate(path: &Path, len: usize) -> Result<()> {
+    OpenOptions::new()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_len(len as u64))
+        .with_context(|_| format!("could not truncate {}", path.display()))?;
+    Ok(())
+}
+
+#[test]
+fn load_ignores_incomplete_last_line() {
+    use slog::{o, Discard, Logger};
+
+    let (ctx, _worker_fut) = Context::create(Logger::root(Discard, o!()));
+    let dir = std::env::temp_dir()
+        .join(format!("dbcrossbar-resume-{}", rand::random::<u64>()));
+    fs::create_dir(&dir).unwrap();
+    let path = dir.join("resume.jsonl");
+    let dest = "csv:out/".parse::<BoxLocator>().unwrap();
+
+    let resume = ResumeFile::load(&ctx, &path, &dest).unwrap();
+    assert!(!resume.has_progress());
+    resume.record("a").unwrap();
+    resume.record("b").unwrap();
+
+    // Simulate a crash in the middle of recording stream `c`.
+    let complete = fs::read_to_string(&path).unwrap();
+    fs::write(
+        &path,
+        format!("{}{{\"destination\":\"csv:out/\",\"str", complete),
+    )
+    .unwrap();
+    let resume = ResumeFile::load(&ctx, &path, &dest).unwrap();
+    assert!(resume.is_finished("a"));
+    assert!(resume.is_finished("b"));
+    assert!(!resume.is_finished("c"));
+    assert_eq!(fs::read_to_string(&path).unwrap(), complete);
+
+    // We can keep appending after truncating.
+    resume.record("c").unwrap();
+    let resume = ResumeFile::load(&ctx, &path, &dest).unwrap();
+    assert!(resume.is_finished("c"));
+
+    // A complete line which is missing its newline is also ignored, because we
+    // can't tell whether it was finished.
+    let complete = fs::read_to_string(&path).unwrap();
+    fs::write(
+        &path,
+        format!(
+            "{}{{\"destination\":\"csv:out/\",\"stream\":\"d\"}}",
+            complete
+        ),
+    )
+    .unwrap();
+    let resume = ResumeFile::load(&ctx, &path, &dest).unwrap();
+    assert!(!resume.is_finished("d"));
+
+    // But a bad line in the middle of the file is still an error.
+    fs::write(&path, format!("garbage\n{}", complete)).unwrap();
+    assert!(ResumeFile::load(&ctx, &path, &dest).is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
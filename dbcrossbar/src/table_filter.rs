@@ -0,0 +1,56 @@
+//! Choosing which tables to copy from a database or dataset.
+//!
+//! `cp --include-table=GLOB --exclude-table=GLOB` selects tables by name,
+//! where `*` matches any sequence of characters and `?` matches any single
+//! character. A table is copied if it matches at least one include pattern
+//! (or if there are none), and no exclude patterns.
+
+/// Include and exclude patterns for table names.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TableFilter {
+    /// Tables which we should copy. If empty, we copy all tables.
+    pub(crate) include: Vec<String>,
+    /// Tables which we should never copy.
+    pub(crate) exclude: Vec<String>,
+}
+
+impl TableFilter {
+    /// Should we copy the table `name`?
+    pub(crate) fn matches(&self, name: &str) -> bool {
+        let included = self.include.is_empty()
+            || self.include.iter().any(|pat| glob_matches(pat, name));
+        included && !self.exclude.iter().any(|pat| glob_matches(pat, name))
+    }
+}
+
+/// Does `name` match the glob `pattern`?
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+
+    // Keep track of the most recent `*`, so that we can backtrack and let it
+    // match one more character when we get stuck.
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
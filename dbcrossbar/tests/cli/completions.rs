@@ -0,0 +1,23 @@
+//! Tests for the `completions` subcommand.
+
+use cli_test_dir::*;
+
+#[test]
+fn completions_include_locator_schemes() {
+    let testdir = TestDir::new("dbcrossbar", "completions_include_locator_schemes");
+    for shell in &["bash", "zsh", "fish"] {
+        let output = testdir.cmd().args(["completions", shell]).expect_success();
+        let script = output.stdout_str();
+        assert!(script.contains("postgres:"));
+        assert!(script.contains("bigquery:"));
+    }
+}
+
+#[test]
+fn completions_zsh_completes_locators() {
+    let testdir = TestDir::new("dbcrossbar", "completions_zsh_completes_locators");
+    let output = testdir.cmd().args(["completions", "zsh"]).expect_success();
+    assert!(output
+        .stdout_str()
+        .contains(":to-locator -- The output schema:_dbcrossbar_locators'"));
+}
@@ -56,6 +56,21 @@ fn conv_pg_sql_to_dbcrossbar_schema_to_pg_sql() {
     assert_eq!(output3.stdout_str(), output1.stdout_str());
 }
 
+#[test]
+fn conv_pg_sql_to_protobuf() {
+    let testdir = TestDir::new("dbcrossbar", "conv_pg_sql_to_protobuf");
+    let output = testdir
+        .cmd()
+        .args(["conv", "postgres-sql:-", "protobuf:-"])
+        .output_with_stdin(INPUT_SQL)
+        .expect_success();
+    let proto = output.stdout_str();
+    assert!(proto.starts_with("syntax = \"proto3\";"));
+    assert!(proto.contains("message Example {"));
+    assert!(proto.contains("  repeated int32 g = 7;"));
+    assert!(proto.contains("  google.protobuf.Timestamp k = 11;"));
+}
+
 #[test]
 fn conv_csv_to_pg_sql() {
     let testdir = TestDir::new("dbcrossbar", "conv_csv_to_pg_sql");
@@ -99,3 +114,96 @@ fn conv_bq_schema_to_pg_sql() {
     let expected = fs::read_to_string(&expected_sql).unwrap();
     testdir.expect_file_contents("output.sql", &expected);
 }
+
+#[test]
+fn conv_with_sample_rows() {
+    let testdir = TestDir::new("dbcrossbar", "conv_with_sample_rows");
+    let sample = testdir.path("sample.csv");
+    let output = testdir
+        .cmd()
+        .args([
+            "conv",
+            "--with-sample-rows=3",
+            &format!("--sample-rows-to=csv:{}", sample.display()),
+            "postgres-sql:-",
+            "postgres-sql:-",
+        ])
+        .output_with_stdin(INPUT_SQL)
+        .expect_success();
+    assert!(output.stdout_str().contains("CREATE TABLE"));
+    let sample_csv = fs::read_to_string(&sample).unwrap();
+    assert_eq!(sample_csv.lines().count(), 4);
+    assert!(sample_csv.starts_with("a,b,c,"));
+}
+
+#[test]
+fn conv_multiple_tables() {
+    let testdir = TestDir::new("dbcrossbar", "conv_multiple_tables");
+    let input = "CREATE TABLE users (id bigint PRIMARY KEY, name text);
+CREATE TABLE posts (id bigint NOT NULL, user_id bigint, body text);
+";
+    let output1 = testdir
+        .cmd()
+        .args(["conv", "postgres-sql:-", "dbcrossbar-schema:-"])
+        .output_with_stdin(input)
+        .expect_success();
+    assert!(output1.stdout_str().contains("\"tables\""));
+    let output2 = testdir
+        .cmd()
+        .args(["conv", "dbcrossbar-schema:-", "postgres-sql:-"])
+        .output_with_stdin(output1.stdout_str())
+        .expect_success();
+    assert_eq!(output2.stdout_str().matches("CREATE TABLE").count(), 2);
+
+    // Destinations which hold a single table should refuse multiple tables.
+    testdir
+        .cmd()
+        .args(["conv", "postgres-sql:-", "bigquery-schema:-"])
+        .output_with_stdin(input)
+        .expect_failure();
+}
+
+#[test]
+fn conv_selects_table_by_name() {
+    let testdir = TestDir::new("dbcrossbar", "conv_selects_table_by_name");
+    let input = "CREATE TABLE users (id bigint PRIMARY KEY, name text);
+CREATE TABLE posts (id bigint NOT NULL, user_id bigint, body text);
+";
+    testdir.create_file("schema.sql", input);
+    testdir
+        .cmd()
+        .args([
+            "conv",
+            "postgres-sql:schema.sql",
+            "dbcrossbar-schema:all.json",
+        ])
+        .expect_success();
+    let output = testdir
+        .cmd()
+        .args(["conv", "dbcrossbar-schema:all.json#posts", "postgres-sql:-"])
+        .expect_success();
+    assert_eq!(output.stdout_str().matches("CREATE TABLE").count(), 1);
+    assert!(output.stdout_str().contains("\"posts\""));
+
+    // A single table can be used as a `--schema`.
+    testdir.create_file("posts.csv", "id,user_id,body\n1,2,hello\n");
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--schema=dbcrossbar-schema:all.json#posts",
+            "csv:posts.csv",
+            "csv:out.csv",
+        ])
+        .expect_success();
+    testdir.expect_file_contents("out.csv", "id,user_id,body\n1,2,hello\n");
+
+    testdir
+        .cmd()
+        .args([
+            "conv",
+            "dbcrossbar-schema:all.json#missing",
+            "postgres-sql:-",
+        ])
+        .expect_failure();
+}
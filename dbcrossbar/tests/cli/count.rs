@@ -69,3 +69,48 @@ fn count_postgres() {
 
     assert_eq!(output.stdout_str().trim(), "2");
 }
+
+#[test]
+fn count_csv() {
+    let testdir = TestDir::new("dbcrossbar", "count_csv");
+    let src = testdir.src_path("fixtures/posts.csv");
+    let output = testdir
+        .cmd()
+        .args(["count", &format!("csv:{}", src.display())])
+        .tee_output()
+        .expect_success();
+    assert_eq!(output.stdout_str().trim(), "2");
+}
+
+#[test]
+fn count_csv_with_where() {
+    let testdir = TestDir::new("dbcrossbar", "count_csv_with_where");
+    let src = testdir.src_path("fixtures/posts.csv");
+    let schema = testdir.src_path("fixtures/posts.sql");
+    let output = testdir
+        .cmd()
+        .args([
+            "count",
+            &format!("--schema=postgres-sql:{}", schema.display()),
+            "--where=author_id > 1 AND title IS NOT NULL",
+            &format!("csv:{}", src.display()),
+        ])
+        .tee_output()
+        .expect_success();
+    assert_eq!(output.stdout_str().trim(), "1");
+}
+
+#[test]
+fn count_csv_with_unsupported_where() {
+    let testdir = TestDir::new("dbcrossbar", "count_csv_with_unsupported_where");
+    let src = testdir.src_path("fixtures/posts.csv");
+    let output = testdir
+        .cmd()
+        .args([
+            "count",
+            "--where=lower(title) = 'welcome'",
+            &format!("csv:{}", src.display()),
+        ])
+        .expect_failure();
+    assert!(output.stderr_str().contains("cannot evaluate"));
+}
@@ -268,3 +268,26 @@ fn bigquery_honors_not_null_for_complex_inserts() {
     // Make sure it contains REQUIRED columns.
     testdir.expect_contains("output.json", "REQUIRED");
 }
+
+#[test]
+fn cp_from_gs_to_bigquery_dry_run() {
+    let testdir = TestDir::new("dbcrossbar", "cp_from_gs_to_bigquery_dry_run");
+    let schema = testdir.src_path("fixtures/posts.sql");
+    let output = testdir
+        .cmd()
+        .args([
+            "cp",
+            "--dry-run",
+            "--if-exists=overwrite",
+            "--temporary=bigquery:example:temp",
+            &format!("--schema=postgres-sql:{}", schema.display()),
+            "gs://example/posts/",
+            "bigquery:example:data.posts",
+        ])
+        .tee_output()
+        .expect_success();
+    let stdout = output.stdout_str();
+    assert!(stdout.contains("directly from gs to bigquery"));
+    assert!(stdout.contains("bq load "));
+    assert!(stdout.contains("gs://example/posts/*.csv"));
+}
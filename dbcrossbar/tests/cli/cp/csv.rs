@@ -40,6 +40,92 @@ fn cp_csvs_to_csv() {
     testdir.expect_file_contents("out.csv", &expected);
 }
 
+#[test]
+fn cp_csv_to_csv_overwrite_truncates() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_csv_overwrite_truncates");
+    let src = testdir.src_path("fixtures/example.csv");
+    let expected = fs::read_to_string(&src).unwrap();
+    // Make the old file longer than the new one, so leftover bytes show up.
+    testdir.create_file("out.csv", expected.repeat(3));
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--if-exists=overwrite",
+            &format!("csv:{}", src.display()),
+            "csv:out.csv",
+        ])
+        .expect_success();
+    testdir.expect_file_contents("out.csv", &expected);
+}
+
+#[test]
+fn cp_csv_to_csv_with_backup() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_csv_with_backup");
+    let src = testdir.src_path("fixtures/example.csv");
+    let old = testdir.src_path("fixtures/posts.csv");
+    let old_contents = fs::read_to_string(&old).unwrap();
+    testdir.create_file("out.csv", &old_contents);
+    testdir
+        .cmd()
+        .env("DBCROSSBAR_JOBS_DIR", testdir.path("jobs"))
+        .args([
+            "cp",
+            "--if-exists=overwrite",
+            "--backup-to=csv:backup.csv",
+            &format!("csv:{}", src.display()),
+            "csv:out.csv",
+        ])
+        .expect_success();
+    testdir.expect_file_contents("backup.csv", &old_contents);
+    let expected = fs::read_to_string(&src).unwrap();
+    testdir.expect_file_contents("out.csv", &expected);
+}
+
+#[test]
+fn cp_csv_to_csv_with_backup_and_rollback() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_csv_with_backup_and_rollback");
+    let src = testdir.src_path("fixtures/example.csv");
+    let old = testdir.src_path("fixtures/posts.csv");
+    let old_contents = fs::read_to_string(&old).unwrap();
+    testdir.create_file("out.csv", &old_contents);
+    let jobs_dir = testdir.path("jobs");
+    testdir
+        .cmd()
+        .env("DBCROSSBAR_JOBS_DIR", &jobs_dir)
+        .args([
+            "cp",
+            "--if-exists=overwrite",
+            "--backup-to=csv:backup.csv",
+            &format!("csv:{}", src.display()),
+            "csv:out.csv",
+        ])
+        .expect_success();
+    let expected = fs::read_to_string(&src).unwrap();
+    testdir.expect_file_contents("out.csv", &expected);
+
+    // Find our job ID and roll back.
+    let job_files = fs::read_dir(&jobs_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    assert_eq!(job_files.len(), 1);
+    #[cfg(unix)]
+    {
+        // Job files may contain passwords.
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&job_files[0]).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+    let job_id = job_files[0].file_stem().unwrap().to_str().unwrap();
+    testdir
+        .cmd()
+        .env("DBCROSSBAR_JOBS_DIR", &jobs_dir)
+        .args(["rollback", job_id])
+        .expect_success();
+    testdir.expect_file_contents("out.csv", &old_contents);
+}
+
 #[test]
 fn cp_csv_to_csv_piped() {
     let testdir = TestDir::new("dbcrossbar", "cp_csv_to_csv");
@@ -56,3 +142,914 @@ fn cp_csv_to_csv_piped() {
         .expect_success();
     assert_eq!(output.stdout_str(), EXAMPLE_CSV);
 }
+
+#[test]
+fn cp_read_only_allows_only_stdout() {
+    let testdir = TestDir::new("dbcrossbar", "cp_read_only_allows_only_stdout");
+    let src = testdir.src_path("fixtures/example.csv");
+    let output = testdir
+        .cmd()
+        .args([
+            "--read-only",
+            "cp",
+            &format!("csv:{}", src.display()),
+            "csv:-",
+        ])
+        .expect_success();
+    assert_eq!(output.stdout_str(), EXAMPLE_CSV);
+
+    let output = testdir
+        .cmd()
+        .args([
+            "--read-only",
+            "cp",
+            &format!("csv:{}", src.display()),
+            "csv:out.csv",
+        ])
+        .expect_failure();
+    assert!(output.stderr_str().contains("--read-only"));
+    testdir.expect_no_such_path("out.csv");
+
+    testdir
+        .cmd()
+        .env("DBCROSSBAR_READ_ONLY", "1")
+        .args(["cp", &format!("csv:{}", src.display()), "csv:out.csv"])
+        .expect_failure();
+    testdir.expect_no_such_path("out.csv");
+}
+
+#[test]
+fn cp_confirm_requires_yes_without_terminal() {
+    let testdir =
+        TestDir::new("dbcrossbar", "cp_confirm_requires_yes_without_terminal");
+    let src = testdir.src_path("fixtures/example.csv");
+    let output = testdir
+        .cmd()
+        .args([
+            "cp",
+            "--confirm",
+            "--if-exists=overwrite",
+            &format!("csv:{}", src.display()),
+            "csv:out.csv",
+        ])
+        .expect_failure();
+    assert!(output.stderr_str().contains("overwrite (REPLACE"));
+    assert!(output.stderr_str().contains("--yes"));
+    testdir.expect_no_such_path("out.csv");
+
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--confirm",
+            "--yes",
+            "--if-exists=overwrite",
+            &format!("csv:{}", src.display()),
+            "csv:out.csv",
+        ])
+        .expect_success();
+    testdir.expect_file_contents("out.csv", EXAMPLE_CSV);
+}
+
+#[test]
+fn cp_confirm_does_not_read_stdin_twice() {
+    let testdir = TestDir::new("dbcrossbar", "cp_confirm_does_not_read_stdin_twice");
+    let src = testdir.src_path("fixtures/example.csv");
+    let output = testdir
+        .cmd()
+        .args([
+            "cp",
+            "--confirm",
+            "--yes",
+            &format!("--schema=csv:{}", src.display()),
+            "csv:-",
+            "csv:out.csv",
+        ])
+        .output_with_stdin(EXAMPLE_CSV)
+        .expect_success();
+    assert!(output.stderr_str().contains("not counted"));
+    testdir.expect_file_contents("out.csv", EXAMPLE_CSV);
+}
+
+#[test]
+fn cp_dry_run_does_not_write() {
+    let testdir = TestDir::new("dbcrossbar", "cp_dry_run_does_not_write");
+    let src = testdir.src_path("fixtures/example.csv");
+    let output = testdir
+        .cmd()
+        .args([
+            "--read-only",
+            "cp",
+            "--dry-run",
+            "--if-exists=overwrite",
+            &format!("csv:{}", src.display()),
+            "csv:out.csv",
+        ])
+        .tee_output()
+        .expect_success();
+    assert!(output.stdout_str().contains("overwrite (REPLACE"));
+    assert!(output.stdout_str().contains("via this machine"));
+    testdir.expect_no_such_path("out.csv");
+}
+
+#[test]
+fn cp_csv_using_aliases() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_using_aliases");
+    let fixtures = testdir.src_path("fixtures");
+    testdir.create_file(
+        "config.toml",
+        "[aliases]\nfixtures = \"csv:${FIXTURES_DIR}\"\nout = \"csv:out\"\n",
+    );
+    testdir
+        .cmd()
+        .env("DBCROSSBAR_CONFIG", testdir.path("config.toml"))
+        .env("FIXTURES_DIR", &fixtures)
+        .args(["cp", "alias:fixtures/example.csv", "alias:out/example.csv"])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("out/example.csv", EXAMPLE_CSV);
+
+    let output = testdir
+        .cmd()
+        .env("DBCROSSBAR_CONFIG", testdir.path("config.toml"))
+        .args(["cp", "alias:missing/example.csv", "csv:-"])
+        .expect_failure();
+    assert!(output.stderr_str().contains("unknown alias \"missing\""));
+}
+
+#[test]
+fn cp_replicate_to_regions_rejects_unsupported_destinations() {
+    let testdir = TestDir::new(
+        "dbcrossbar",
+        "cp_replicate_to_regions_rejects_unsupported_destinations",
+    );
+    let src = testdir.src_path("fixtures/example.csv");
+    let output = testdir
+        .cmd()
+        .args([
+            "cp",
+            "--replicate-to-regions=eu,asia",
+            &format!("csv:{}", src.display()),
+            "csv:out.csv",
+        ])
+        .expect_failure();
+    assert!(output.stderr_str().contains("--replicate-to-regions"));
+    testdir.expect_no_such_path("out.csv");
+}
+
+#[test]
+fn cp_csv_to_csv_with_usage_report() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_csv_with_usage_report");
+    let src = testdir.src_path("fixtures/example.csv");
+    let output = testdir
+        .cmd()
+        .args([
+            "--usage-report=json",
+            "cp",
+            &format!("csv:{}", src.display()),
+            "csv:out.csv",
+        ])
+        .expect_success();
+    let report_line = output.stderr_str().lines().last().unwrap();
+    let report = serde_json::from_str::<serde_json::Value>(report_line).unwrap();
+    let size = EXAMPLE_CSV.len() as u64;
+    assert_eq!(report["bytes"]["csv"]["down"], size);
+    assert_eq!(report["bytes"]["csv"]["up"], size);
+}
+
+#[test]
+fn cp_csv_to_csv_with_scratch_dir() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_csv_with_scratch_dir");
+    let src = testdir.src_path("fixtures/example.csv");
+    let output = testdir
+        .cmd()
+        .args([
+            "--usage-report=json",
+            "--scratch-dir=scratch",
+            "cp",
+            &format!("csv:{}", src.display()),
+            "csv:out.csv",
+        ])
+        .expect_success();
+    let report_line = output.stderr_str().lines().last().unwrap();
+    let report = serde_json::from_str::<serde_json::Value>(report_line).unwrap();
+    assert!(report["peak_scratch_bytes"].is_u64());
+
+    // Our private scratch directory should have been cleaned up.
+    let leftovers = fs::read_dir(testdir.path("scratch")).unwrap().count();
+    assert_eq!(leftovers, 0);
+    testdir.expect_file_contents("out.csv", EXAMPLE_CSV);
+}
+
+#[test]
+fn cp_csv_to_csv_with_bytes_encoding() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_csv_with_bytes_encoding");
+    testdir.create_file(
+        "schema.sql",
+        "CREATE TABLE blobs (id int, data bytea, chunks bytea[]);\n",
+    );
+    testdir.create_file(
+        "in.csv",
+        "id,data,chunks\n1,\\xdeadbeef,\"[\"\"0001\"\"]\"\n2,,\n",
+    );
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--schema=postgres-sql:schema.sql",
+            "--from-arg=bytes_encoding=hex",
+            "csv:in.csv",
+            "csv:base64.csv",
+        ])
+        .expect_success();
+    testdir.expect_file_contents(
+        "base64.csv",
+        "id,data,chunks\n1,3q2+7w==,\"[\"\"AAE=\"\"]\"\n2,,\n",
+    );
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--schema=postgres-sql:schema.sql",
+            "--to-arg=bytes_encoding=hex",
+            "csv:base64.csv",
+            "csv:hex.csv",
+        ])
+        .expect_success();
+    testdir.expect_file_contents(
+        "hex.csv",
+        "id,data,chunks\n1,deadbeef,\"[\"\"0001\"\"]\"\n2,,\n",
+    );
+}
+
+#[test]
+fn cp_csv_to_csv_with_lineage() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_csv_with_lineage");
+    let src = testdir.src_path("fixtures/example.csv");
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--lineage-to=lineage.jsonl",
+            &format!("csv:{}", src.display()),
+            "csv:out.csv",
+        ])
+        .expect_success();
+    let lineage = fs::read_to_string(testdir.path("lineage.jsonl")).unwrap();
+    assert_eq!(lineage.lines().count(), 1);
+    let event = serde_json::from_str::<serde_json::Value>(&lineage).unwrap();
+    assert_eq!(event["eventType"], "COMPLETE");
+    assert_eq!(event["inputs"][0]["namespace"], "csv");
+    assert_eq!(event["outputs"][0]["name"], "csv:out.csv");
+    let id_lineage = &event["outputs"][0]["facets"]["columnLineage"]["fields"]["id"];
+    assert_eq!(id_lineage["inputFields"][0]["field"], "id");
+}
+
+#[test]
+fn cp_resume_rejects_destinations_without_append() {
+    let testdir = TestDir::new(
+        "dbcrossbar",
+        "cp_resume_rejects_destinations_without_append",
+    );
+    let src = testdir.src_path("fixtures/example.csv");
+    let output = testdir
+        .cmd()
+        .args([
+            "cp",
+            "--resume=resume.jsonl",
+            &format!("csv:{}", src.display()),
+            "csv:out.csv",
+        ])
+        .expect_failure();
+    assert!(output
+        .stderr_str()
+        .contains("cannot use --resume with csv:out.csv"));
+    testdir.expect_no_such_path("out.csv");
+    testdir.expect_no_such_path("resume.jsonl");
+}
+
+#[test]
+fn cp_resume_rejects_postgres_key_ranges() {
+    let testdir = TestDir::new("dbcrossbar", "cp_resume_rejects_postgres_key_ranges");
+    let output = testdir
+        .cmd()
+        .args([
+            "cp",
+            "--resume=resume.jsonl",
+            "--from-arg=ranges=4",
+            "postgres://localhost:5432/dbcrossbar_test#example",
+            "csv:out/",
+        ])
+        .expect_failure();
+    assert!(output
+        .stderr_str()
+        .contains("cannot use --resume with --from-arg=ranges"));
+    testdir.expect_no_such_path("resume.jsonl");
+}
+
+#[test]
+fn cp_csv_to_encrypted_csvs() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_encrypted_csvs");
+    let src = testdir.src_path("fixtures/example.csv");
+
+    // Create a throwaway PGP key to encrypt for.
+    let gnupg_home = testdir.path("gnupg");
+    fs::create_dir_all(&gnupg_home).unwrap();
+    let gpg = || {
+        let mut cmd = std::process::Command::new("gpg");
+        cmd.env("GNUPGHOME", &gnupg_home).args([
+            "--batch",
+            "--quiet",
+            "--no-permission-warning",
+        ]);
+        cmd
+    };
+    let status = gpg()
+        .args([
+            "--passphrase",
+            "",
+            "--quick-gen-key",
+            "partner@example.com",
+            "default",
+            "default",
+            "never",
+        ])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    testdir
+        .cmd()
+        .env("GNUPGHOME", &gnupg_home)
+        .args([
+            "cp",
+            "--to-arg=encrypt_recipient=partner@example.com",
+            &format!("csv:{}", src.display()),
+            "csv:out/",
+        ])
+        .expect_success();
+    let output = gpg()
+        .arg("--decrypt")
+        .arg(testdir.path("out/example.csv.gpg"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let expected = fs::read_to_string(&src).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+}
+
+#[test]
+fn cp_csv_to_csv_following_appended_rows() {
+    use std::{
+        fs::OpenOptions,
+        io::{BufRead, BufReader, Write},
+        process::Stdio,
+    };
+
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_csv_following_appended_rows");
+    testdir.create_file("in.csv", "id,name\n1,a\n");
+    let mut child = testdir
+        .cmd()
+        .args([
+            "cp",
+            "--from-arg=follow=true",
+            "--from-arg=follow_interval_ms=50",
+            "csv:in.csv",
+            "csv:-",
+        ])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    assert_eq!(lines.next().unwrap().unwrap(), "id,name");
+    assert_eq!(lines.next().unwrap().unwrap(), "1,a");
+
+    // Append a row in two pieces, and make sure it arrives in one piece.
+    let mut file = OpenOptions::new()
+        .append(true)
+        .open(testdir.path("in.csv"))
+        .unwrap();
+    file.write_all(b"2,").unwrap();
+    file.flush().unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    file.write_all(b"b\n").unwrap();
+    file.flush().unwrap();
+    assert_eq!(lines.next().unwrap().unwrap(), "2,b");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+}
+
+#[test]
+fn cp_csv_to_csv_with_where() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_csv_with_where");
+    let schema = testdir.src_path("fixtures/posts.sql");
+    let src = testdir.src_path("fixtures/posts.csv");
+    let filtered = testdir.src_path("fixtures/posts_where_author_id_1.csv");
+    testdir
+        .cmd()
+        .arg("cp")
+        .arg(format!("--schema=postgres-sql:{}", schema.display()))
+        .arg("--where=author_id < 2 OR title IS NULL")
+        .arg(format!("csv:{}", src.display()))
+        .arg("csv:out.csv")
+        .tee_output()
+        .expect_success();
+    let expected = fs::read_to_string(&filtered).unwrap();
+    testdir.expect_file_contents("out.csv", &expected);
+
+    // We can only evaluate simple expressions ourselves.
+    let output = testdir
+        .cmd()
+        .arg("cp")
+        .arg("--where=lower(title) = 'welcome'")
+        .arg(format!("csv:{}", src.display()))
+        .arg("csv:out2.csv")
+        .expect_failure();
+    assert!(output.stderr_str().contains("cannot evaluate"));
+}
+
+#[test]
+fn cp_csv_to_csv_with_select() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_csv_with_select");
+    testdir.create_file("in.csv", "id,name,email\n1,Ann,ann@example.com\n");
+    testdir
+        .cmd()
+        .args(["cp", "--select=email,id", "csv:in.csv", "csv:out.csv"])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("out.csv", "email,id\nann@example.com,1\n");
+
+    let output = testdir
+        .cmd()
+        .args(["cp", "--select=id,missing", "csv:in.csv", "csv:out2.csv"])
+        .expect_failure();
+    assert!(output.stderr_str().contains("isn't in the schema"));
+}
+
+#[test]
+fn cp_csv_to_csv_with_select_and_empty_input() {
+    let testdir =
+        TestDir::new("dbcrossbar", "cp_csv_to_csv_with_select_and_empty_input");
+    testdir.create_file("schema.sql", "CREATE TABLE t (id int, name text);\n");
+    testdir.create_file("empty.csv", "");
+    let output = testdir
+        .cmd()
+        .args([
+            "cp",
+            "--schema=postgres-sql:schema.sql",
+            "--select=id",
+            "csv:empty.csv",
+            "csv:out.csv",
+        ])
+        .tee_output()
+        .expect_success();
+    assert!(!output.stderr_str().contains("panicked"));
+}
+
+#[test]
+fn cp_csv_to_csv_without_select_keeps_extra_columns() {
+    let testdir = TestDir::new(
+        "dbcrossbar",
+        "cp_csv_to_csv_without_select_keeps_extra_columns",
+    );
+    testdir.create_file("schema.sql", "CREATE TABLE t (id int, name text);\n");
+    testdir.create_file("in.csv", "id,name,secret\n1,Ann,x\n");
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--schema=postgres-sql:schema.sql",
+            "csv:in.csv",
+            "csv:out.csv",
+        ])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("out.csv", "id,name,secret\n1,Ann,x\n");
+}
+
+#[test]
+fn cp_csv_to_csv_with_limit() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_csv_with_limit");
+    testdir.create_file("schema.sql", "CREATE TABLE t (id int);\n");
+    testdir.create_file("in/a.csv", "id\n1\n2\n3\n");
+    testdir.create_file("in/b.csv", "id\n4\n5\n");
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--schema=postgres-sql:schema.sql",
+            "--limit=4",
+            "--where=id <> 2",
+            "-J1",
+            "csv:in/",
+            "csv:out.csv",
+        ])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("out.csv", "id\n1\n3\n4\n5\n");
+
+    let output = testdir
+        .cmd()
+        .args([
+            "cp",
+            "--schema=postgres-sql:schema.sql",
+            "--order-by=id",
+            "csv:in/",
+            "csv:out2.csv",
+        ])
+        .expect_failure();
+    assert!(output.stderr_str().contains("does not support --order-by"));
+}
+
+#[test]
+fn cp_csv_to_csv_with_transforms() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_csv_with_transforms");
+    testdir.create_file("in.csv", "Email,name\nAnn@X.COM,  Ann \n,Bob\n");
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--transform=Email=lower(Email)",
+            "--transform=name=trim(name)",
+            "--rename=Email:email",
+            "csv:in.csv",
+            "csv:out.csv",
+        ])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("out.csv", "email,name\nann@x.com,Ann\n,Bob\n");
+
+    let output = testdir
+        .cmd()
+        .args([
+            "cp",
+            "--transform=missing=lower(name)",
+            "csv:in.csv",
+            "csv:out2.csv",
+        ])
+        .expect_failure();
+    assert!(output.stderr_str().contains("isn't in the schema"));
+}
+
+#[test]
+fn cp_csv_to_csv_with_dedupe() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_csv_with_dedupe");
+    testdir.create_file("day1.csv", "id,status\n1,new\n2,new\n");
+    testdir.create_file("day2.csv", "id,status\n1,paid\n3,new\n");
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--dedupe-on=id",
+            "csv:day1.csv",
+            "csv:day2.csv",
+            "csv:last.csv",
+        ])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("last.csv", "id,status\n2,new\n1,paid\n3,new\n");
+
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--dedupe-on=id",
+            "--dedupe-keep=first",
+            "csv:day1.csv",
+            "csv:day2.csv",
+            "csv:first.csv",
+        ])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("first.csv", "id,status\n1,new\n2,new\n3,new\n");
+
+    let output = testdir
+        .cmd()
+        .args(["cp", "--dedupe-on=missing", "csv:day1.csv", "csv:out.csv"])
+        .expect_failure();
+    assert!(output.stderr_str().contains("isn't in the schema"));
+}
+
+#[test]
+fn cp_csv_to_csv_dir_with_stream_rows_and_name_template() {
+    let testdir = TestDir::new(
+        "dbcrossbar",
+        "cp_csv_to_csv_dir_with_stream_rows_and_name_template",
+    );
+    testdir.create_file("in.csv", "id\n1\n2\n3\n");
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--stream-rows=2",
+            "--to-arg=name_template=part-{shard:03}.csv",
+            "csv:in.csv",
+            "csv:out/",
+        ])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("out/part-000.csv", "id\n1\n2\n");
+    testdir.expect_file_contents("out/part-001.csv", "id\n3\n");
+
+    let output = testdir
+        .cmd()
+        .args([
+            "cp",
+            "--to-arg=name_template=part.csv",
+            "csv:in.csv",
+            "csv:bad/",
+        ])
+        .expect_failure();
+    assert!(output
+        .stderr_str()
+        .contains("must contain {name} or {shard}"));
+}
+
+#[test]
+fn cp_csv_to_csv_dir_with_partition_by() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_csv_dir_with_partition_by");
+    testdir.create_file(
+        "in.csv",
+        "id,day\n1,2023-01-01\n2,2023-01-02\n3,2023-01-01\n4,\n",
+    );
+    testdir
+        .cmd()
+        .args(["cp", "--to-arg=partition_by=day", "csv:in.csv", "csv:out/"])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("out/day=2023-01-01/part-000.csv", "id\n1\n3\n");
+    testdir.expect_file_contents("out/day=2023-01-02/part-000.csv", "id\n2\n");
+    testdir.expect_file_contents(
+        "out/day=__HIVE_DEFAULT_PARTITION__/part-000.csv",
+        "id\n4\n",
+    );
+
+    let output = testdir
+        .cmd()
+        .args([
+            "cp",
+            "--to-arg=partition_by=day",
+            "csv:in.csv",
+            "csv:out.csv",
+        ])
+        .expect_failure();
+    assert!(output.stderr_str().contains("writing to a directory"));
+}
+
+#[test]
+fn cp_csv_to_csv_without_headers() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_csv_without_headers");
+    testdir.create_file(
+        "schema.sql",
+        "CREATE TABLE t (id int NOT NULL, name text);\n",
+    );
+    testdir.create_file("in.csv", "1,a\n2,b\n");
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--schema=postgres-sql:schema.sql",
+            "--from-arg=has_header=false",
+            "csv:in.csv",
+            "csv:with_header.csv",
+        ])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("with_header.csv", "id,name\n1,a\n2,b\n");
+
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--to-arg=header=false",
+            "csv:with_header.csv",
+            "csv:out.csv",
+        ])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("out.csv", "1,a\n2,b\n");
+
+    let output = testdir
+        .cmd()
+        .args(["cp", "--from-arg=has_header=false", "csv:in.csv", "csv:-"])
+        .expect_failure();
+    assert!(output.stderr_str().contains("requires --schema"));
+}
+
+#[test]
+fn cp_csv_to_csv_with_null_tokens() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_csv_with_null_tokens");
+    testdir.create_file("schema.sql", "CREATE TABLE t (id int, name text);\n");
+    testdir.create_file("in.csv", "id,name\n1,\\N\n\\N,Ann\n");
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--schema=postgres-sql:schema.sql",
+            "--from-arg=null=\\N",
+            "--to-arg=null=NULL",
+            "--where=id IS NULL",
+            "csv:in.csv",
+            "csv:out.csv",
+        ])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("out.csv", "id,name\nNULL,Ann\n");
+}
+
+#[test]
+fn cp_csv_to_csv_with_date_formats() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_csv_with_date_formats");
+    testdir.create_file(
+        "schema.sql",
+        "CREATE TABLE t (born date, seen timestamp, shipped date);\n",
+    );
+    testdir.create_file(
+        "in.csv",
+        "born,seen,shipped\n20/07/1969,20/07/1969 20:17,19690721\n",
+    );
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--schema=postgres-sql:schema.sql",
+            "--from-arg=date_format=%d/%m/%Y",
+            "--from-arg=timestamp_format=%d/%m/%Y %H:%M",
+            "--from-arg=column_formats.shipped=%Y%m%d",
+            "--where=born < '1970-01-01'",
+            "csv:in.csv",
+            "csv:out.csv",
+        ])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents(
+        "out.csv",
+        "born,seen,shipped\n1969-07-20,1969-07-20T20:17:00,1969-07-21\n",
+    );
+}
+
+#[test]
+fn cp_csv_to_csv_renaming_columns() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_csv_renaming_columns");
+    testdir.create_file("in.csv", "UserId,Full Name,score\n1,Ann,2\n");
+    testdir.create_file("renames.json", r#"{"Full Name": "full_name"}"#);
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--rename=UserId:user_id",
+            "--rename-file=renames.json",
+            "csv:in.csv",
+            "csv:out.csv",
+        ])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("out.csv", "user_id,full_name,score\n1,Ann,2\n");
+
+    let output = testdir
+        .cmd()
+        .args(["cp", "--rename=Missing:x", "csv:in.csv", "csv:out2.csv"])
+        .expect_failure();
+    assert!(output.stderr_str().contains("isn't in the schema"));
+}
+
+#[test]
+fn cp_empty_csv_with_allow_empty() {
+    let testdir = TestDir::new("dbcrossbar", "cp_empty_csv_with_allow_empty");
+    testdir.create_file("empty.csv", "");
+    testdir.create_file("schema.sql", "CREATE TABLE t (id int, name text);\n");
+
+    // With a schema, we write an empty table.
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--allow-empty",
+            "--schema=postgres-sql:schema.sql",
+            "csv:empty.csv",
+            "csv:out.csv",
+        ])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("out.csv", "id,name\n");
+
+    // Without a schema, we skip the input and exit with a special status.
+    let output = testdir
+        .cmd()
+        .args(["cp", "--allow-empty", "csv:empty.csv", "csv:skipped.csv"])
+        .expect_failure();
+    assert_eq!(output.status.code(), Some(3));
+    assert!(output.stderr_str().contains("skipped empty inputs"));
+    assert!(!testdir.path("skipped.csv").exists());
+}
+
+#[test]
+fn cp_table_filters_require_multiple_tables() {
+    let testdir =
+        TestDir::new("dbcrossbar", "cp_table_filters_require_multiple_tables");
+    let src = testdir.src_path("fixtures/example.csv");
+    let output = testdir
+        .cmd()
+        .args([
+            "cp",
+            "--include-table=users_*",
+            &format!("csv:{}", src.display()),
+            "csv:out.csv",
+        ])
+        .expect_failure();
+    assert!(output
+        .stderr_str()
+        .contains("can only be used when copying a database or dataset"));
+    testdir.expect_no_such_path("out.csv");
+}
+
+#[test]
+fn cp_csv_to_multiple_destinations() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_multiple_destinations");
+    let src = testdir.src_path("fixtures/example.csv");
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--also-to=csv:out2/",
+            &format!("csv:{}", src.display()),
+            "csv:out1.csv",
+        ])
+        .expect_success();
+    let expected = fs::read_to_string(&src).unwrap();
+    testdir.expect_file_contents("out1.csv", &expected);
+    testdir.expect_file_contents("out2/example.csv", &expected);
+}
+
+#[test]
+fn cp_multiple_destinations_refuses_backup() {
+    let testdir =
+        TestDir::new("dbcrossbar", "cp_multiple_destinations_refuses_backup");
+    let src = testdir.src_path("fixtures/example.csv");
+    let output = testdir
+        .cmd()
+        .args([
+            "cp",
+            "--backup-to=csv:backup.csv",
+            "--also-to=csv:out2.csv",
+            &format!("csv:{}", src.display()),
+            "csv:out1.csv",
+        ])
+        .expect_failure();
+    assert!(output
+        .stderr_str()
+        .contains("cannot use --backup-to when copying to multiple destinations"));
+    testdir.expect_no_such_path("out1.csv");
+}
+
+#[test]
+fn cp_multiple_csvs_to_csv() {
+    let testdir = TestDir::new("dbcrossbar", "cp_multiple_csvs_to_csv");
+    testdir.create_file("jan/data.csv", "id,name\n1,a\n");
+    testdir.create_file("feb/data.csv", "id,name\n2,b\n");
+    testdir.create_file("mar/data.csv", "id,name\n3,c\n");
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "csv:jan/data.csv",
+            "csv:feb/data.csv",
+            "csv:mar/data.csv",
+            "csv:out.csv",
+        ])
+        .expect_success();
+    testdir.expect_file_contents("out.csv", "id,name\n1,a\n2,b\n3,c\n");
+}
+
+#[test]
+fn cp_multiple_csvs_to_directory_keeps_every_stream() {
+    let testdir = TestDir::new(
+        "dbcrossbar",
+        "cp_multiple_csvs_to_directory_keeps_every_stream",
+    );
+    testdir.create_file("jan/data.csv", "id,name\n1,a\n");
+    testdir.create_file("feb/data.csv", "id,name\n2,b\n");
+    testdir
+        .cmd()
+        .args(["cp", "csv:jan/data.csv", "csv:feb/data.csv", "csv:out/"])
+        .expect_success();
+    testdir.expect_file_contents("out/data.csv", "id,name\n1,a\n");
+    testdir.expect_file_contents("out/data_2.csv", "id,name\n2,b\n");
+}
+
+#[test]
+fn cp_multiple_csvs_with_different_columns() {
+    let testdir =
+        TestDir::new("dbcrossbar", "cp_multiple_csvs_with_different_columns");
+    testdir.create_file("a.csv", "id,name\n1,a\n");
+    testdir.create_file("b.csv", "id,title\n2,b\n");
+    let output = testdir
+        .cmd()
+        .args(["cp", "csv:a.csv", "csv:b.csv", "csv:out.csv"])
+        .expect_failure();
+    assert!(output
+        .stderr_str()
+        .contains("has columns (id, title) instead of (id, name)"));
+    testdir.expect_no_such_path("out.csv");
+}
@@ -9,6 +9,8 @@ mod bigquery;
 mod combined;
 mod csv;
 mod gs;
+mod msgpack;
+mod mysqldump;
 mod postgres;
 mod redshift;
 mod s3;
@@ -0,0 +1,31 @@
+//! Tests specific to the MessagePack driver.
+
+use cli_test_dir::*;
+use std::fs;
+
+#[test]
+fn cp_csv_to_msgpack_to_csv() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_msgpack_to_csv");
+    let src = testdir.src_path("fixtures/example.csv");
+    let schema = testdir.src_path("fixtures/example.sql");
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            &format!("--schema=postgres-sql:{}", schema.display()),
+            &format!("csv:{}", src.display()),
+            "msgpack:example.msgpack",
+        ])
+        .expect_success();
+    let output = testdir
+        .cmd()
+        .args([
+            "cp",
+            &format!("--schema=postgres-sql:{}", schema.display()),
+            "msgpack:example.msgpack",
+            "csv:-",
+        ])
+        .expect_success();
+    let expected = fs::read_to_string(&src).unwrap();
+    assert_eq!(output.stdout_str(), expected);
+}
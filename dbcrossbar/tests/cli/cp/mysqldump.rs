@@ -0,0 +1,38 @@
+//! Tests specific to the `mysqldump` driver.
+
+use cli_test_dir::*;
+use std::fs;
+
+#[test]
+fn cp_mysqldump_to_csv() {
+    let testdir = TestDir::new("dbcrossbar", "cp_mysqldump_to_csv");
+    let src = testdir.src_path("fixtures/mysqldump.sql");
+    let expected = testdir.src_path("fixtures/mysqldump_customers.csv");
+    let output = testdir
+        .cmd()
+        .args([
+            "cp",
+            &format!("mysqldump:{}#customers", src.display()),
+            "csv:-",
+        ])
+        .expect_success();
+    let expected = fs::read_to_string(&expected).unwrap();
+    assert_eq!(output.stdout_str(), expected);
+}
+
+#[test]
+fn conv_mysqldump_to_postgres_sql() {
+    let testdir = TestDir::new("dbcrossbar", "conv_mysqldump_to_postgres_sql");
+    let src = testdir.src_path("fixtures/mysqldump.sql");
+    let output = testdir
+        .cmd()
+        .args([
+            "conv",
+            &format!("mysqldump:{}#customers", src.display()),
+            "postgres-sql:-",
+        ])
+        .expect_success();
+    let expected = testdir.src_path("fixtures/mysqldump_customers.sql");
+    let expected = fs::read_to_string(&expected).unwrap();
+    assert_eq!(output.stdout_str(), expected);
+}
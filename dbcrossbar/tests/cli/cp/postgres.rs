@@ -91,18 +91,269 @@ fn cp_from_postgres_with_where() {
     assert_diff!(&expected, &actual, ",", 0);
 }
 
+#[test]
+#[ignore]
+fn cp_from_postgres_with_select() {
+    let testdir = TestDir::new("dbcrossbar", "cp_from_postgres_with_select");
+    let src = testdir.src_path("fixtures/posts.csv");
+    let schema = testdir.src_path("fixtures/posts.sql");
+    let pg_table = post_test_table_url("cp_from_postgres_with_select");
+
+    // CSV to Postgres.
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--if-exists=overwrite",
+            &format!("--schema=postgres-sql:{}", schema.display()),
+            &format!("csv:{}", src.display()),
+            &pg_table,
+        ])
+        .tee_output()
+        .expect_success();
+
+    // PostgreSQL back to CSV using --select, filtering on a column we don't
+    // copy.
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--select=title",
+            "--where=author_id = 2",
+            &pg_table,
+            "csv:out.csv",
+        ])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("out.csv", "title\nMy weekend\n");
+}
+
 #[test]
 #[ignore]
 fn postgres_upsert() {
+    check_postgres_upsert("postgres_upsert", &[]);
+}
+
+#[test]
+#[ignore]
+fn postgres_upsert_only_some_columns() {
+    for (table_name, to_args) in &[
+        ("postgres_upsert_only_some_columns", &[][..]),
+        (
+            "postgres_upsert_only_some_columns_with_insert",
+            &["--to-arg=write_method=insert"][..],
+        ),
+    ] {
+        check_postgres_upsert_with(
+            table_name,
+            "--if-exists=upsert-on:key1,key2:update:value",
+            to_args,
+            "fixtures/upsert_update_value_result.csv",
+        );
+    }
+}
+
+#[test]
+#[ignore]
+fn postgres_merge() {
+    check_postgres_upsert_with(
+        "postgres_merge",
+        "--if-exists=merge-on:key1,key2",
+        &[],
+        "fixtures/merge_result.csv",
+    );
+}
+
+#[test]
+#[ignore]
+fn postgres_merge_with_soft_delete() {
+    let table_name = "postgres_merge_with_soft_delete";
+    check_postgres_upsert_with(
+        table_name,
+        "--if-exists=merge-on:key1,key2:soft-delete:deleted_at",
+        &[],
+        "fixtures/upsert_result.csv",
+    );
+
+    // Only the row missing from our second input should be marked as deleted.
+    let output = Command::new("psql")
+        .arg(postgres_test_url())
+        .args([
+            "--tuples-only",
+            "--no-align",
+            "--command",
+            &format!(
+                "SELECT key1, key2 FROM {} WHERE deleted_at IS NOT NULL",
+                table_name,
+            ),
+        ])
+        .expect_success();
+    assert_eq!(output.stdout_str().trim(), "1|1");
+}
+
+#[test]
+#[ignore]
+fn postgres_upsert_with_insert() {
+    check_postgres_upsert(
+        "postgres_upsert_with_insert",
+        &[
+            "--to-arg=write_method=insert",
+            "--to-arg=batch_size=1",
+            "--to-arg=concurrency=2",
+            "--to-arg=max_rows_per_second=100",
+        ],
+    );
+}
+
+#[test]
+#[ignore]
+fn cp_csv_to_postgres_with_insert_quotes_values() {
+    let testdir =
+        TestDir::new("dbcrossbar", "cp_csv_to_postgres_with_insert_quotes_values");
+    testdir.create_file(
+        "schema.sql",
+        "CREATE TABLE t (id int, name text, tags text[]);\n",
+    );
+    let data = "id,name,tags\n1,O'Brien,\"[\"\"it's\"\"]\"\n2,C:\\temp\\$1,\n3,,[]\n";
+    testdir.create_file("in.csv", data);
+    let pg_table = post_test_table_url("cp_csv_to_postgres_with_insert_quotes_values");
+
+    // We insert using the simple query protocol, so this must quote every
+    // value correctly.
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--if-exists=overwrite",
+            "--to-arg=write_method=insert",
+            "--schema=postgres-sql:schema.sql",
+            "csv:in.csv",
+            &pg_table,
+        ])
+        .tee_output()
+        .expect_success();
+    testdir
+        .cmd()
+        .args(["cp", &pg_table, "csv:out.csv"])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("out.csv", data);
+}
+
+#[test]
+#[ignore]
+fn cp_postgres_to_csv_with_limit_and_order_by() {
+    let testdir =
+        TestDir::new("dbcrossbar", "cp_postgres_to_csv_with_limit_and_order_by");
+    testdir.create_file("schema.sql", "CREATE TABLE t (id int, name text);\n");
+    testdir.create_file("in.csv", "id,name\n1,a\n3,c\n2,b\n");
+    let pg_table = post_test_table_url("cp_postgres_to_csv_with_limit_and_order_by");
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--if-exists=overwrite",
+            "--schema=postgres-sql:schema.sql",
+            "csv:in.csv",
+            &pg_table,
+        ])
+        .tee_output()
+        .expect_success();
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--limit=2",
+            "--order-by=id DESC",
+            &pg_table,
+            "csv:out.csv",
+        ])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("out.csv", "id,name\n3,c\n2,b\n");
+}
+
+#[test]
+#[ignore]
+fn cp_postgres_to_csv_with_key_ranges() {
+    let testdir = TestDir::new("dbcrossbar", "cp_postgres_to_csv_with_key_ranges");
+    testdir.create_file(
+        "schema.sql",
+        "CREATE TABLE t (id int PRIMARY KEY, group_id int, name text);\n",
+    );
+    testdir.create_file("in.csv", "id,group_id,name\n1,10,a\n2,,b\n3,30,c\n4,40,d\n");
+    let pg_table = post_test_table_url("cp_postgres_to_csv_with_key_ranges");
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--if-exists=overwrite",
+            "--schema=postgres-sql:schema.sql",
+            "csv:in.csv",
+            &pg_table,
+        ])
+        .tee_output()
+        .expect_success();
+
+    // Export using the primary key, and then a nullable column.
+    for (out, from_arg) in &[
+        ("by_id", "--from-arg=ranges=3"),
+        ("by_group", "--from-arg=partition_column=group_id"),
+    ] {
+        testdir
+            .cmd()
+            .args(["cp", from_arg, &pg_table, &format!("csv:{}/", out)])
+            .tee_output()
+            .expect_success();
+        let mut rows = fs::read_dir(testdir.path(out))
+            .unwrap()
+            .flat_map(|entry| {
+                let text = fs::read_to_string(entry.unwrap().path()).unwrap();
+                text.lines()
+                    .skip(1)
+                    .map(|l| l.to_owned())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        rows.sort();
+        assert_eq!(rows, vec!["1,10,a", "2,,b", "3,30,c", "4,40,d"]);
+    }
+    assert!(fs::read_dir(testdir.path("by_id")).unwrap().count() > 1);
+
+    let output = testdir
+        .cmd()
+        .args(["cp", "--from-arg=partition_column=name", &pg_table, "csv:-"])
+        .expect_failure();
+    assert!(output.stderr_str().contains("integer columns"));
+}
+
+/// Upsert into a PostgreSQL table, passing `to_args` to the driver.
+fn check_postgres_upsert(table_name: &str, to_args: &[&str]) {
+    check_postgres_upsert_with(
+        table_name,
+        "--if-exists=upsert-on:key1,key2",
+        to_args,
+        "fixtures/upsert_result.csv",
+    );
+}
+
+/// Upsert into a PostgreSQL table using `upsert`, and compare the result to
+/// `expected`.
+fn check_postgres_upsert_with(
+    table_name: &str,
+    upsert: &str,
+    to_args: &[&str],
+    expected: &str,
+) {
     let _ = env_logger::try_init();
-    let testdir = TestDir::new("dbcrossbar", "postgres_upsert");
+    let testdir = TestDir::new("dbcrossbar", table_name);
     let srcs = &[
         testdir.src_path("fixtures/upsert_1.csv"),
         testdir.src_path("fixtures/upsert_2.csv"),
     ];
-    let expected = testdir.src_path("fixtures/upsert_result.csv");
+    let expected = testdir.src_path(expected);
     let schema = testdir.src_path("fixtures/upsert.sql");
-    let pg_table = post_test_table_url("postgres_upsert");
+    let pg_table = post_test_table_url(table_name);
 
     // CSVes to Postgres.
     let mut first = true;
@@ -116,18 +367,18 @@ fn postgres_upsert() {
                 .arg(postgres_test_url())
                 .args(&[
                     "--command",
-                    "CREATE UNIQUE INDEX ON postgres_upsert (key1, key2)",
+                    &format!("CREATE UNIQUE INDEX ON {} (key1, key2)", table_name),
                 ])
                 .expect_success();
 
             // Our `--if-exists` argument.
-            "--if-exists=upsert-on:key1,key2"
+            upsert
         };
         testdir
             .cmd()
+            .args(["cp", if_exists])
+            .args(to_args)
             .args(&[
-                "cp",
-                if_exists,
                 &format!("--schema=postgres-sql:{}", schema.display()),
                 &format!("csv:{}", src.display()),
                 &pg_table,
@@ -136,10 +387,17 @@ fn postgres_upsert() {
             .expect_success();
     }
 
-    // Postgres to CSV.
+    // Postgres to CSV. We pass our schema so we don't export any columns
+    // added by the upsert, like soft-delete timestamps.
     testdir
         .cmd()
-        .args(&["cp", "--if-exists=overwrite", &pg_table, "csv:out.csv"])
+        .args([
+            "cp",
+            "--if-exists=overwrite",
+            &format!("--schema=postgres-sql:{}", schema.display()),
+            &pg_table,
+            "csv:out.csv",
+        ])
         .tee_output()
         .expect_success();
 
@@ -223,6 +481,216 @@ fn cp_pg_append_upsert_legacy_json() {
     assert_diff!(&expected, &actual, ",", 0);
 }
 
+#[test]
+#[ignore]
+fn cp_csvs_to_postgres_preserving_order() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csvs_to_postgres_preserving_order");
+    let pg_table = post_test_table_url("cp_csvs_to_postgres_preserving_order");
+    testdir.create_file("schema.sql", "CREATE TABLE t (id int, name text);\n");
+    let mut expected = "id,name\n".to_owned();
+    for file in 0..5 {
+        let mut csv = "id,name\n".to_owned();
+        for row in 0..200 {
+            let line = format!("{},row {}\n", file * 200 + row, row);
+            csv.push_str(&line);
+            expected.push_str(&line);
+        }
+        testdir.create_file(format!("in/{:02}.csv", file), &csv);
+    }
+
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--if-exists=overwrite",
+            "--preserve-order",
+            "--max-streams=4",
+            "--schema=postgres-sql:schema.sql",
+            "csv:in/",
+            &pg_table,
+        ])
+        .tee_output()
+        .expect_success();
+    testdir
+        .cmd()
+        .args(["cp", &pg_table, "csv:out.csv"])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("out.csv", &expected);
+}
+
+#[test]
+#[ignore]
+fn cp_csvs_to_postgres_in_parallel() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csvs_to_postgres_in_parallel");
+    let pg_table = post_test_table_url("cp_csvs_to_postgres_in_parallel");
+    testdir.create_file("schema.sql", "CREATE TABLE t (id int, name text);\n");
+    let mut expected = "id,name\n".to_owned();
+    let mut csv = "id,name\n".to_owned();
+    for row in 0..1000 {
+        let line = format!("{},row {}\n", row, row);
+        csv.push_str(&line);
+        expected.push_str(&line);
+    }
+    testdir.create_file("in.csv", &csv);
+
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--if-exists=overwrite",
+            "--stream-rows=100",
+            "--max-streams=4",
+            "--schema=postgres-sql:schema.sql",
+            "csv:in.csv",
+            &pg_table,
+        ])
+        .tee_output()
+        .expect_success();
+    testdir
+        .cmd()
+        .args(["cp", "--order-by=id", &pg_table, "csv:out.csv"])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("out.csv", &expected);
+}
+
+#[test]
+#[ignore]
+fn cp_csvs_to_postgres_with_resume() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csvs_to_postgres_with_resume");
+    let pg_table = post_test_table_url("cp_csvs_to_postgres_with_resume");
+    testdir.create_file("schema.sql", "CREATE TABLE t (id int, name text);\n");
+    testdir.create_file("in/a.csv", "id,name\n1,a\n");
+    testdir.create_file("in/b.csv", "id,name\n2,b\n");
+    let cp = || {
+        testdir
+            .cmd()
+            .args([
+                "cp",
+                "--if-exists=overwrite",
+                "--resume=resume.jsonl",
+                "--schema=postgres-sql:schema.sql",
+                "csv:in/",
+                &pg_table,
+            ])
+            .tee_output()
+            .expect_success();
+    };
+
+    // Copy everything, and then resume without anything left to do. This
+    // shouldn't overwrite the table or copy anything twice.
+    cp();
+    let resume = fs::read_to_string(testdir.path("resume.jsonl")).unwrap();
+    assert_eq!(resume.lines().count(), 2);
+    cp();
+
+    // Add a new file, which should be appended.
+    testdir.create_file("in/c.csv", "id,name\n3,c\n");
+    cp();
+    let resume = fs::read_to_string(testdir.path("resume.jsonl")).unwrap();
+    assert_eq!(resume.lines().count(), 3);
+
+    testdir
+        .cmd()
+        .args(["cp", "--order-by=id", &pg_table, "csv:out.csv"])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("out.csv", "id,name\n1,a\n2,b\n3,c\n");
+}
+
+#[test]
+#[ignore]
+fn cp_csv_to_postgres_append_adding_columns() {
+    let testdir =
+        TestDir::new("dbcrossbar", "cp_csv_to_postgres_append_adding_columns");
+    let pg_table = post_test_table_url("cp_csv_to_postgres_append_adding_columns");
+    testdir.create_file("old.sql", "CREATE TABLE t (id int);\n");
+    testdir.create_file("old.csv", "id\n1\n");
+    testdir.create_file("new.sql", "CREATE TABLE t (id int, name text);\n");
+    testdir.create_file("new.csv", "id,name\n2,two\n");
+
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--if-exists=overwrite",
+            "--schema=postgres-sql:old.sql",
+            "csv:old.csv",
+            &pg_table,
+        ])
+        .tee_output()
+        .expect_success();
+
+    // Without `evolve_schema`, appending fails.
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--if-exists=append",
+            "--schema=postgres-sql:new.sql",
+            "csv:new.csv",
+            &pg_table,
+        ])
+        .tee_output()
+        .expect_failure();
+
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--if-exists=append",
+            "--to-arg=evolve_schema=add_columns",
+            "--schema=postgres-sql:new.sql",
+            "csv:new.csv",
+            &pg_table,
+        ])
+        .tee_output()
+        .expect_success();
+    testdir
+        .cmd()
+        .args(["cp", &pg_table, "csv:out.csv"])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("out.csv", "id,name\n1,\n2,two\n");
+}
+
+#[test]
+#[ignore]
+fn cp_csv_to_postgres_with_casts() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_postgres_with_casts");
+    let pg_table = post_test_table_url("cp_csv_to_postgres_with_casts");
+    testdir.create_file("schema.sql", "CREATE TABLE t (zip_code int, id text);\n");
+    testdir.create_file("in.csv", "zip_code,id\n02134,7\n");
+
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--if-exists=overwrite",
+            "--schema=postgres-sql:schema.sql",
+            "--cast=zip_code:text",
+            "--cast=id:bigint",
+            "csv:in.csv",
+            &pg_table,
+        ])
+        .tee_output()
+        .expect_success();
+    testdir
+        .cmd()
+        .args(["cp", &pg_table, "csv:out.csv"])
+        .tee_output()
+        .expect_success();
+    testdir.expect_file_contents("out.csv", "zip_code,id\n02134,7\n");
+    let output = testdir
+        .cmd()
+        .args(["conv", &pg_table, "postgres-sql:-"])
+        .tee_output()
+        .expect_success();
+    assert!(output.stdout_str().contains("\"zip_code\" text"));
+    assert!(output.stdout_str().contains("\"id\" bigint"));
+}
+
 #[test]
 #[ignore]
 fn cp_pg_tricky_column_types() {
@@ -275,3 +743,304 @@ fn cp_pg_tricky_column_types() {
     let actual = fs::read_to_string(testdir.path("out.csv")).unwrap();
     assert_diff!(&expected, &actual, ",", 0);
 }
+
+#[test]
+#[ignore]
+fn cp_pg_composite_types() {
+    let testdir = TestDir::new("dbcrossbar", "cp_pg_composite_types");
+    let pg_table = post_test_table_url("pg_composite_types");
+
+    // Create a table using nested composite types.
+    Command::new("psql")
+        .arg(postgres_test_url())
+        .args([
+            "--command",
+            r#"
+DROP TABLE IF EXISTS pg_composite_types;
+DROP TYPE IF EXISTS test_composite_outer;
+DROP TYPE IF EXISTS test_composite_inner;
+CREATE TYPE test_composite_inner AS (x float8, label text);
+CREATE TYPE test_composite_outer AS (id bigint, inner_value test_composite_inner);
+CREATE TABLE pg_composite_types (
+    id int NOT NULL,
+    outer_value test_composite_outer,
+    inner_values test_composite_inner[]
+);
+INSERT INTO pg_composite_types VALUES
+    (1, ROW(10, ROW(1.5, 'a')), ARRAY[ROW(2.5, 'b')::test_composite_inner]),
+    (2, NULL, NULL);
+"#,
+        ])
+        .expect_success();
+
+    // PostgreSQL to BigQuery schema.
+    testdir
+        .cmd()
+        .args(["conv", &pg_table, "bigquery-schema:schema.json"])
+        .tee_output()
+        .expect_success();
+    let schema = fs::read_to_string(testdir.path("schema.json")).unwrap();
+    assert!(schema.contains(r#""type": "RECORD""#));
+    assert!(schema.contains(r#""name": "inner_value""#));
+
+    // PostgreSQL to CSV.
+    testdir
+        .cmd()
+        .args(["cp", &pg_table, "csv:out.csv"])
+        .tee_output()
+        .expect_success();
+    let expected = r#"id,outer_value,inner_values
+1,"{""id"":10,""inner_value"":{""x"":1.5,""label"":""a""}}","[{""x"":2.5,""label"":""b""}]"
+2,,
+"#;
+    let actual = fs::read_to_string(testdir.path("out.csv")).unwrap();
+    assert_diff!(expected, &actual, ",", 0);
+}
+
+#[test]
+#[ignore]
+fn cp_pg_range_types() {
+    let testdir = TestDir::new("dbcrossbar", "cp_pg_range_types");
+    let pg_table = post_test_table_url("pg_range_types");
+
+    // Create a table using range types.
+    Command::new("psql")
+        .arg(postgres_test_url())
+        .args([
+            "--command",
+            r#"
+DROP TABLE IF EXISTS pg_range_types;
+CREATE TABLE pg_range_types (
+    id int NOT NULL,
+    quantity int4range,
+    billed tstzrange
+);
+INSERT INTO pg_range_types VALUES
+    (1, '[1,5)', '[2020-01-01 00:00:00+00,)'),
+    (2, 'empty', NULL);
+"#,
+        ])
+        .expect_success();
+
+    // PostgreSQL to CSV.
+    testdir
+        .cmd()
+        .args(["cp", &pg_table, "csv:out.csv"])
+        .tee_output()
+        .expect_success();
+    let expected = r#"id,quantity,billed
+1,"{""lower"" : 1, ""upper"" : 5, ""lower_inc"" : true, ""upper_inc"" : false, ""empty"" : false}","{""lower"" : ""2020-01-01T00:00:00Z"", ""upper"" : null, ""lower_inc"" : true, ""upper_inc"" : false, ""empty"" : false}"
+2,"{""lower"" : null, ""upper"" : null, ""lower_inc"" : false, ""upper_inc"" : false, ""empty"" : true}",
+"#;
+    let actual = fs::read_to_string(testdir.path("out.csv")).unwrap();
+    assert_diff!(expected, &actual, ",", 0);
+}
+
+#[test]
+#[ignore]
+fn cp_pg_network_types() {
+    let testdir = TestDir::new("dbcrossbar", "cp_pg_network_types");
+    let pg_table = post_test_table_url("pg_network_types");
+    testdir.create_file(
+        "schema.sql",
+        "CREATE TABLE pg_network_types (id int NOT NULL, host inet, net cidr, mac macaddr, hosts inet[]);",
+    );
+    let input = r#"id,host,net,mac,hosts
+1,192.168.0.1/24,10.0.0.0/8,08-00-2B-01-02-03,"[""::1"",""10.1.2.3""]"
+2,2001:db8::1,2001:db8::/32,0800.2b01.0204,
+3,,,,
+"#;
+    testdir.create_file("in.csv", input);
+
+    // CSV to PostgreSQL.
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--if-exists=overwrite",
+            "--schema=postgres-sql:schema.sql",
+            "csv:in.csv",
+            &pg_table,
+        ])
+        .tee_output()
+        .expect_success();
+
+    // PostgreSQL to CSV.
+    testdir
+        .cmd()
+        .args(["cp", &pg_table, "csv:out.csv"])
+        .tee_output()
+        .expect_success();
+    let expected = r#"id,host,net,mac,hosts
+1,192.168.0.1/24,10.0.0.0/8,08:00:2b:01:02:03,"[""::1"",""10.1.2.3""]"
+2,2001:db8::1,2001:db8::/32,08:00:2b:01:02:04,
+3,,,,
+"#;
+    let actual = fs::read_to_string(testdir.path("out.csv")).unwrap();
+    assert_diff!(expected, &actual, ",", 0);
+
+    // Invalid addresses are rejected.
+    testdir.create_file(
+        "bad.csv",
+        "id,host,net,mac,hosts\n4,192.168.0.1,10.0.0.1/8,,\n",
+    );
+    let output = testdir
+        .cmd()
+        .args([
+            "cp",
+            "--if-exists=append",
+            "--schema=postgres-sql:schema.sql",
+            "csv:bad.csv",
+            &pg_table,
+        ])
+        .expect_failure();
+    assert!(output.stderr_str().contains("10.0.0.1/8"));
+}
+
+#[test]
+#[ignore]
+fn cp_pg_create_indexes() {
+    let testdir = TestDir::new("dbcrossbar", "cp_pg_create_indexes");
+    let src_table = post_test_table_url("pg_with_indexes");
+    let dest_table = post_test_table_url("pg_with_indexes_copy");
+    testdir.create_file(
+        "schema.sql",
+        "CREATE TABLE pg_with_indexes (id int PRIMARY KEY, name text, email text);
+CREATE INDEX pg_with_indexes_name_idx ON pg_with_indexes (name);
+CREATE UNIQUE INDEX pg_with_indexes_email_name_idx ON pg_with_indexes (email, name);
+",
+    );
+    testdir.create_file("in.csv", "id,name,email\n1,a,a@example.com\n");
+
+    // CSV to PostgreSQL, creating the indexes in our schema.
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--if-exists=overwrite",
+            "--schema=postgres-sql:schema.sql",
+            "--to-arg=create_indexes=true",
+            "csv:in.csv",
+            &src_table,
+        ])
+        .tee_output()
+        .expect_success();
+
+    // PostgreSQL to PostgreSQL, copying the indexes from our source table.
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--if-exists=overwrite",
+            "--to-arg=create_indexes=true",
+            &src_table,
+            &dest_table,
+        ])
+        .tee_output()
+        .expect_success();
+
+    let output = testdir
+        .cmd()
+        .args(["conv", &dest_table, "postgres-sql:-"])
+        .tee_output()
+        .expect_success();
+    let sql = output.stdout_str();
+    assert!(sql.contains("PRIMARY KEY (\"id\")"));
+    assert!(sql.contains(
+        "CREATE INDEX \"pg_with_indexes_copy_name_idx\" ON \"pg_with_indexes_copy\" (\"name\");"
+    ));
+    assert!(sql.contains(
+        "CREATE UNIQUE INDEX \"pg_with_indexes_copy_email_name_idx\" ON \"pg_with_indexes_copy\" (\"email\", \"name\");"
+    ));
+}
+
+#[test]
+#[ignore]
+fn cp_csv_to_partitioned_postgres() {
+    let testdir = TestDir::new("dbcrossbar", "cp_csv_to_partitioned_postgres");
+    let pg_table = post_test_table_url("pg_partitioned_events");
+    testdir.create_file(
+        "schema.sql",
+        "CREATE TABLE events (id int, created_at timestamp);\n",
+    );
+    testdir.create_file(
+        "in.csv",
+        "id,created_at\n1,2020-01-15T10:00:00\n2,2020-02-01T00:00:00\n3,\n",
+    );
+    for if_exists in &["--if-exists=overwrite", "--if-exists=append"] {
+        testdir
+            .cmd()
+            .args([
+                "cp",
+                if_exists,
+                "--schema=postgres-sql:schema.sql",
+                "--to-arg=partition_by=range(created_at)",
+                "--to-arg=partition_interval=month",
+                "csv:in.csv",
+                &pg_table,
+            ])
+            .tee_output()
+            .expect_success();
+    }
+
+    // Each row should have landed in the right partition.
+    let output = Command::new("psql")
+        .arg(postgres_test_url())
+        .args([
+            "--tuples-only",
+            "--no-align",
+            "--command",
+            "SELECT tableoid::regclass::text AS partition, count(*) FROM pg_partitioned_events GROUP BY 1 ORDER BY 1",
+        ])
+        .expect_success();
+    assert_eq!(
+        output.stdout_str(),
+        "pg_partitioned_events_2020_01|2\npg_partitioned_events_2020_02|2\npg_partitioned_events_default|2\n",
+    );
+}
+
+#[test]
+#[ignore]
+fn cp_every_table_in_postgres_database() {
+    let testdir = TestDir::new("dbcrossbar", "cp_every_table_in_postgres_database");
+    let src = testdir.src_path("fixtures/posts.csv");
+    let schema = testdir.src_path("fixtures/posts.sql");
+
+    // Create some tables to copy, and one to leave out.
+    for table in &[
+        "cp_every_table_a",
+        "cp_every_table_b",
+        "cp_every_table_skip",
+    ] {
+        testdir
+            .cmd()
+            .args([
+                "cp",
+                "--if-exists=overwrite",
+                &format!("--schema=postgres-sql:{}", schema.display()),
+                &format!("csv:{}", src.display()),
+                &post_test_table_url(table),
+            ])
+            .tee_output()
+            .expect_success();
+    }
+
+    // Copy the whole database, filtering by table name.
+    let output = testdir
+        .cmd()
+        .args([
+            "cp",
+            "--include-table=cp_every_table_*",
+            "--exclude-table=*_skip",
+            "--max-parallel-tables=2",
+            &postgres_test_url(),
+            "csv:out/{table}.csv",
+        ])
+        .tee_output()
+        .expect_success();
+    testdir.expect_path("out/cp_every_table_a.csv");
+    testdir.expect_path("out/cp_every_table_b.csv");
+    testdir.expect_no_such_path("out/cp_every_table_skip.csv");
+    assert!(output.stderr_str().contains("copied "));
+    assert!(!output.stderr_str().contains("FAILED"));
+}
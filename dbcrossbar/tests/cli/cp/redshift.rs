@@ -73,3 +73,80 @@ fn cp_csv_to_redshift_to_csv() {
     let actual = fs::read_to_string(testdir.path("out.csv")).unwrap();
     assert_diff!(&expected, &actual, ",", 0);
 }
+
+#[test]
+#[ignore]
+fn redshift_upsert_only_some_columns() {
+    let _ = env_logger::try_init();
+    let testdir = TestDir::new("dbcrossbar", "redshift_upsert_only_some_columns");
+    // Our usual upsert fixtures contain arrays, which Redshift doesn't support.
+    testdir.create_file(
+        "upsert.sql",
+        "CREATE TABLE upsert_test (key1 INT NOT NULL, key2 INT NOT NULL, value TEXT, more TEXT);\n",
+    );
+    testdir.create_file("upsert_1.csv", "key1,key2,value,more\n1,1,a,x\n1,2,b,x\n");
+    testdir.create_file("upsert_2.csv", "key1,key2,value,more\n1,2,c,y\n1,3,d,y\n");
+    let srcs = &[testdir.path("upsert_1.csv"), testdir.path("upsert_2.csv")];
+    let expected = "key1,key2,value,more\n1,1,a,x\n1,2,c,x\n1,3,d,y\n".to_owned();
+    let schema = testdir.path("upsert.sql");
+    let s3_dir = s3_test_dir_url("redshift_upsert_only_some_columns");
+    let redshift_table =
+        match redshift_test_table_url("public.redshift_upsert_only_some_columns") {
+            Some(redshift_table) => redshift_table,
+            None => {
+                eprintln!("SKIPPING REDSHIFT TEST - PLEASE SET `REDSHIFT_TEST_URL`!");
+                return;
+            }
+        };
+    let iam_role =
+        env::var("REDSHIFT_TEST_IAM_ROLE").expect("Please set REDSHIFT_TEST_IAM_ROLE");
+    let region =
+        env::var("REDSHIFT_TEST_REGION").expect("Please set REDSHIFT_TEST_REGION");
+
+    // CSVes to Redshift, overwriting and then upserting.
+    for (src, if_exists) in srcs.iter().zip(&[
+        "--if-exists=overwrite",
+        "--if-exists=upsert-on:key1,key2:update:value",
+    ]) {
+        testdir
+            .cmd()
+            .args([
+                "cp",
+                if_exists,
+                &format!("--temporary={}", s3_dir),
+                &format!("--schema=postgres-sql:{}", schema.display()),
+                &format!("--to-arg=iam_role={}", iam_role),
+                &format!("--to-arg=region={}", region),
+                &format!("csv:{}", src.display()),
+                &redshift_table,
+            ])
+            .tee_output()
+            .expect_success();
+    }
+
+    // Redshift to CSV.
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--if-exists=overwrite",
+            &format!("--temporary={}", s3_dir),
+            &format!("--schema=postgres-sql:{}", schema.display()),
+            &format!("--from-arg=iam_role={}", iam_role),
+            &format!("--from-arg=region={}", region),
+            &redshift_table,
+            "csv:out.csv",
+        ])
+        .tee_output()
+        .expect_success();
+
+    // Redshift may output rows in any order, so sort them.
+    let normalize_csv = |text: String| -> String {
+        let mut lines = text.lines().map(|l| l.to_owned()).collect::<Vec<_>>();
+        lines.sort();
+        lines.join("\n")
+    };
+    let expected = normalize_csv(expected);
+    let actual = normalize_csv(fs::read_to_string(testdir.path("out.csv")).unwrap());
+    assert_diff!(&expected, &actual, ",", 0);
+}
@@ -0,0 +1,71 @@
+//! Tests for the `head` subcommand.
+
+use cli_test_dir::*;
+
+use super::cp::*;
+
+#[test]
+fn head_csv() {
+    let testdir = TestDir::new("dbcrossbar", "head_csv");
+    let src = testdir.src_path("fixtures/posts.csv");
+    let output = testdir
+        .cmd()
+        .args(["head", "-n", "1", &format!("csv:{}", src.display())])
+        .tee_output()
+        .expect_success();
+    assert_eq!(
+        output.stdout_str(),
+        "author_id  title\n---------  -------\n1          Welcome\n",
+    );
+}
+
+#[test]
+fn head_csv_as_csv() {
+    let testdir = TestDir::new("dbcrossbar", "head_csv_as_csv");
+    let src = testdir.src_path("fixtures/posts.csv");
+    let output = testdir
+        .cmd()
+        .args([
+            "head",
+            "--format=csv",
+            "-n",
+            "5",
+            &format!("csv:{}", src.display()),
+        ])
+        .tee_output()
+        .expect_success();
+    assert_eq!(
+        output.stdout_str(),
+        "author_id,title\n1,Welcome\n2,My weekend\n",
+    );
+}
+
+#[test]
+#[ignore]
+fn head_postgres() {
+    let testdir = TestDir::new("dbcrossbar", "head_postgres");
+    let src = testdir.src_path("fixtures/posts.csv");
+    let schema = testdir.src_path("fixtures/posts.sql");
+    let pg_table = post_test_table_url("head_postgres");
+
+    // CSV to PostgreSQL.
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--if-exists=overwrite",
+            &format!("--schema=postgres-sql:{}", schema.display()),
+            &format!("csv:{}", src.display()),
+            &pg_table,
+        ])
+        .tee_output()
+        .expect_success();
+
+    // Preview PostgreSQL.
+    let output = testdir
+        .cmd()
+        .args(["head", "--format=csv", "--where=author_id > 1", &pg_table])
+        .tee_output()
+        .expect_success();
+    assert_eq!(output.stdout_str(), "author_id,title\n2,My weekend\n");
+}
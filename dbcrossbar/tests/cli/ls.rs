@@ -0,0 +1,48 @@
+//! Tests for the `ls` subcommand.
+
+use cli_test_dir::*;
+
+use super::cp::*;
+
+#[test]
+fn ls_refuses_single_files() {
+    let testdir = TestDir::new("dbcrossbar", "ls_refuses_single_files");
+    testdir.create_file("data.csv", "id\n1\n");
+    let output = testdir.cmd().args(["ls", "csv:data.csv"]).expect_failure();
+    assert!(output
+        .stderr_str()
+        .contains("don't know how to list csv:data.csv"));
+}
+
+#[test]
+#[ignore]
+fn ls_postgres() {
+    let testdir = TestDir::new("dbcrossbar", "ls_postgres");
+    let src = testdir.src_path("fixtures/posts.csv");
+    let schema = testdir.src_path("fixtures/posts.sql");
+    let pg_table = post_test_table_url("ls_postgres");
+
+    // CSV to PostgreSQL.
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--if-exists=overwrite",
+            &format!("--schema=postgres-sql:{}", schema.display()),
+            &format!("csv:{}", src.display()),
+            &pg_table,
+        ])
+        .tee_output()
+        .expect_success();
+
+    // List the tables in our database.
+    let output = testdir
+        .cmd()
+        .args(["ls", &postgres_test_url()])
+        .tee_output()
+        .expect_success();
+    assert!(output
+        .stdout_str()
+        .lines()
+        .any(|l| l.ends_with("#ls_postgres")));
+}
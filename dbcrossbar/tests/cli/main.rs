@@ -3,6 +3,14 @@
 //! This is the top-level file for a single CLI integration test binary.
 
 pub(crate) mod about;
+pub(crate) mod completions;
 pub(crate) mod conv;
 pub(crate) mod count;
 pub(crate) mod cp;
+pub(crate) mod head;
+pub(crate) mod ls;
+pub(crate) mod rm;
+pub(crate) mod run;
+pub(crate) mod schema;
+pub(crate) mod sync;
+pub(crate) mod validate;
@@ -0,0 +1,77 @@
+//! Tests for the `rm` subcommand.
+
+use cli_test_dir::*;
+
+use super::cp::*;
+
+#[test]
+fn rm_refuses_unsupported_locators() {
+    let testdir = TestDir::new("dbcrossbar", "rm_refuses_unsupported_locators");
+    testdir.create_file("data.csv", "id\n1\n");
+    let output = testdir.cmd().args(["rm", "csv:data.csv"]).expect_failure();
+    assert!(output.stderr_str().contains("cannot remove csv:data.csv"));
+
+    let output = testdir
+        .cmd()
+        .args(["--read-only", "rm", "csv:data.csv"])
+        .expect_failure();
+    assert!(output.stderr_str().contains("--read-only"));
+}
+
+#[test]
+#[cfg(unix)]
+fn rm_s3_fails_if_aws_fails() {
+    use std::{env, fs, os::unix::fs::PermissionsExt};
+
+    let testdir = TestDir::new("dbcrossbar", "rm_s3_fails_if_aws_fails");
+
+    // Put a fake `aws` command which always fails at the front of our `PATH`.
+    testdir.create_file("bin/aws", "#!/bin/sh\nexit 1\n");
+    let aws = testdir.path("bin/aws");
+    fs::set_permissions(&aws, fs::Permissions::from_mode(0o755)).unwrap();
+    let mut path = testdir.path("bin").into_os_string();
+    path.push(":");
+    path.push(env::var_os("PATH").unwrap_or_default());
+    let output = testdir
+        .cmd()
+        .env("PATH", path)
+        .args(["rm", "s3://example-bucket/dir/"])
+        .expect_failure();
+    assert!(output.stderr_str().contains("could not delete contents"));
+}
+
+#[test]
+#[ignore]
+fn rm_postgres() {
+    let testdir = TestDir::new("dbcrossbar", "rm_postgres");
+    let src = testdir.src_path("fixtures/posts.csv");
+    let schema = testdir.src_path("fixtures/posts.sql");
+    let pg_table = post_test_table_url("rm_postgres");
+
+    // CSV to PostgreSQL.
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--if-exists=overwrite",
+            &format!("--schema=postgres-sql:{}", schema.display()),
+            &format!("csv:{}", src.display()),
+            &pg_table,
+        ])
+        .tee_output()
+        .expect_success();
+
+    // Drop the table, twice, because missing tables are fine.
+    for _ in 0..2 {
+        testdir
+            .cmd()
+            .args(["rm", &pg_table])
+            .tee_output()
+            .expect_success();
+    }
+    testdir
+        .cmd()
+        .args(["count", &pg_table])
+        .tee_output()
+        .expect_failure();
+}
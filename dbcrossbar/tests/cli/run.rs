@@ -0,0 +1,70 @@
+//! Tests for the `run` subcommand.
+
+use cli_test_dir::*;
+
+#[test]
+fn run_jobs_file_and_report_each_job() {
+    let testdir = TestDir::new("dbcrossbar", "run_jobs_file_and_report_each_job");
+    testdir.create_file("a.csv", "id,name\n1,a\n");
+    testdir.create_file("b.csv", "id,name\n2,b\n");
+    testdir.create_file(
+        "jobs.yaml",
+        r#"
+max_parallel_jobs: 2
+jobs:
+  - name: copy_a
+    from: csv:a.csv
+    to: csv:out/a.csv
+    if_exists: overwrite
+  - from: csv:b.csv
+    to: csv:out/b.csv
+    if_exists: overwrite
+  - name: missing
+    from: csv:missing.csv
+    to: csv:out/missing.csv
+"#,
+    );
+
+    let output = testdir
+        .cmd()
+        .args(["run", "jobs.yaml"])
+        .tee_output()
+        .expect_failure();
+    testdir.expect_file_contents("out/a.csv", "id,name\n1,a\n");
+    testdir.expect_file_contents("out/b.csv", "id,name\n2,b\n");
+    let stderr = output.stderr_str();
+    assert!(stderr.contains("ok      copy_a"));
+    assert!(stderr.contains("ok      csv:out/b.csv"));
+    assert!(stderr.contains("FAILED  missing"));
+    assert!(stderr.contains("1 of 3 jobs failed"));
+}
+
+#[test]
+fn run_checks_jobs_before_copying() {
+    let testdir = TestDir::new("dbcrossbar", "run_checks_jobs_before_copying");
+    testdir.create_file("a.csv", "id,name\n1,a\n");
+    testdir.create_file(
+        "jobs.yaml",
+        r#"
+jobs:
+  - from: csv:a.csv
+    to: csv:out/a.csv
+  - from: csv:a.csv
+    to: csv:out/b.csv
+    if_exists: sometimes
+"#,
+    );
+    let output = testdir.cmd().args(["run", "jobs.yaml"]).expect_failure();
+    assert!(output.stderr_str().contains("error in job 2"));
+    testdir.expect_no_such_path("out/a.csv");
+
+    testdir.create_file(
+        "valid_jobs.yaml",
+        "jobs:\n  - from: csv:a.csv\n    to: csv:out/a.csv\n",
+    );
+    let output = testdir
+        .cmd()
+        .args(["--read-only", "run", "valid_jobs.yaml"])
+        .expect_failure();
+    assert!(output.stderr_str().contains("--read-only"));
+}
@@ -0,0 +1,40 @@
+//! Tests for the `schema` subcommand.
+
+use cli_test_dir::*;
+
+#[test]
+fn schema_diff_pg_sql() {
+    let testdir = TestDir::new("dbcrossbar", "schema_diff_pg_sql");
+    testdir.create_file("old.sql", "CREATE TABLE t (id int NOT NULL, name text);");
+    testdir.create_file(
+        "new.sql",
+        "CREATE TABLE t (id bigint NOT NULL, name text NOT NULL, email text);",
+    );
+
+    // Identical schemas.
+    testdir
+        .cmd()
+        .args([
+            "schema",
+            "diff",
+            "postgres-sql:old.sql",
+            "postgres-sql:old.sql",
+        ])
+        .expect_success();
+
+    // Different schemas.
+    let output = testdir
+        .cmd()
+        .args([
+            "schema",
+            "diff",
+            "postgres-sql:old.sql",
+            "postgres-sql:new.sql",
+        ])
+        .expect_failure();
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        output.stdout_str(),
+        "~ id: int32 -> int64\n~ name: NULL -> NOT NULL\n+ email: text\n",
+    );
+}
@@ -0,0 +1,127 @@
+//! Tests for the `sync` subcommand.
+
+use cli_test_dir::*;
+
+use super::cp::*;
+
+#[test]
+#[ignore]
+fn sync_postgres_incrementally() {
+    let testdir = TestDir::new("dbcrossbar", "sync_postgres_incrementally");
+    let src = testdir.src_path("fixtures/posts.csv");
+    let schema = testdir.src_path("fixtures/posts.sql");
+    let pg_src = post_test_table_url("sync_postgres_incrementally_src");
+    let pg_dest = post_test_table_url("sync_postgres_incrementally_dest");
+    let state = format!("--state=file:{}", testdir.path("state.json").display());
+
+    // CSV to PostgreSQL.
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--if-exists=overwrite",
+            &format!("--schema=postgres-sql:{}", schema.display()),
+            &format!("csv:{}", src.display()),
+            &pg_src,
+        ])
+        .tee_output()
+        .expect_success();
+    testdir
+        .cmd()
+        .args([
+            "cp",
+            "--if-exists=overwrite",
+            "--where=author_id < 0",
+            &pg_src,
+            &pg_dest,
+        ])
+        .tee_output()
+        .expect_success();
+
+    // Sync only the rows with `author_id = 1`.
+    testdir
+        .cmd()
+        .args([
+            "sync",
+            "--if-exists=append",
+            "--incremental-key=author_id",
+            &state,
+            "--where=author_id = 1",
+            &pg_src,
+            &pg_dest,
+        ])
+        .tee_output()
+        .expect_success();
+
+    // Now sync the rest, twice.
+    for _ in 0..2 {
+        testdir
+            .cmd()
+            .args([
+                "sync",
+                "--if-exists=append",
+                "--incremental-key=author_id",
+                &state,
+                &pg_src,
+                &pg_dest,
+            ])
+            .tee_output()
+            .expect_success();
+    }
+    let output = testdir
+        .cmd()
+        .args(["head", "--format=csv", &pg_dest])
+        .tee_output()
+        .expect_success();
+    assert_eq!(
+        output.stdout_str(),
+        "author_id,title\n1,Welcome\n2,My weekend\n",
+    );
+}
+
+#[test]
+fn sync_rejects_non_numeric_high_water_marks() {
+    let testdir =
+        TestDir::new("dbcrossbar", "sync_rejects_non_numeric_high_water_marks");
+    testdir.create_file("schema.sql", "CREATE TABLE t (id int);\n");
+    testdir.create_file("in.csv", "id\n1\n");
+    for (value, ok) in &[
+        ("12", true),
+        ("-1.5e-3", true),
+        ("NaN", false),
+        ("inf", false),
+        ("infinity", false),
+        ("1 OR 1=1", false),
+        ("1e", false),
+        (".", false),
+    ] {
+        testdir.create_file(
+            "state.json",
+            format!(
+                r#"{{"sync": {{"high_water_mark": {:?}, "synced_at": "2020-01-01T00:00:00Z"}}}}"#,
+                value,
+            ),
+        );
+        let output = testdir
+            .cmd()
+            .args([
+                "sync",
+                "--if-exists=append",
+                "--incremental-key=id",
+                "--state=file:state.json",
+                "--name=sync",
+                "--schema=postgres-sql:schema.sql",
+                "csv:in.csv",
+                "csv:out.csv",
+            ])
+            .expect_failure();
+        // Valid numbers get as far as asking `csv:` for its largest key.
+        assert_eq!(
+            !output.stderr_str().contains("expected a number"),
+            *ok,
+            "{}: {}",
+            value,
+            output.stderr_str(),
+        );
+    }
+}
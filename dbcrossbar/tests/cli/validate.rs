@@ -0,0 +1,42 @@
+//! Tests for the `validate` subcommand.
+
+use cli_test_dir::*;
+
+#[test]
+fn validate_csv_against_schema() {
+    let testdir = TestDir::new("dbcrossbar", "validate_csv_against_schema");
+    testdir.create_file(
+        "schema.sql",
+        "CREATE TABLE t (id int NOT NULL, code varchar(2));",
+    );
+    testdir.create_file("good.csv", "id,code\n1,ab\n2,\n");
+    testdir.create_file("bad.csv", "id,code\n1,abc\n,ab\nx,a\n");
+
+    testdir
+        .cmd()
+        .args([
+            "validate",
+            "--schema=postgres-sql:schema.sql",
+            "csv:good.csv",
+        ])
+        .expect_success();
+
+    let output = testdir
+        .cmd()
+        .args([
+            "validate",
+            "--schema=postgres-sql:schema.sql",
+            "--max-failures=2",
+            "csv:bad.csv",
+        ])
+        .expect_failure();
+    assert_eq!(
+        output.stdout_str(),
+        "bad: row 1, column code: value is 3 characters long, but the limit is 2
+bad: row 2, column id: NULL value in NOT NULL column
+",
+    );
+    assert!(output
+        .stderr_str()
+        .contains("found 3 invalid cells in 3 rows"));
+}
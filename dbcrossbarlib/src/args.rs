@@ -63,6 +63,10 @@ pub struct SharedArguments<S: ArgumentState> {
     /// How many streams should we process at once?
     max_streams: usize,
 
+    /// Should we adjust the number of streams we process at once based on our
+    /// throughput, using `max_streams` as an upper limit?
+    adaptive_streams: bool,
+
     /// We need to include a reference to `ArgumentState` somewhere, so use a
     /// 0-byte phantom value.
     _phantom: PhantomData<S>,
@@ -76,6 +80,12 @@ impl<S: ArgumentState> SharedArguments<S> {
     pub fn max_streams(&self) -> usize {
         self.max_streams
     }
+
+    /// Should we adjust how many streams we process at once based on our
+    /// throughput? See `BufferWithParallelism`.
+    pub fn adaptive_streams(&self) -> bool {
+        self.adaptive_streams
+    }
 }
 
 // These methods are only available in the `Unverified` state.
@@ -90,10 +100,18 @@ impl SharedArguments<Unverified> {
             schema,
             temporary_storage,
             max_streams,
+            adaptive_streams: false,
             _phantom: PhantomData,
         }
     }
 
+    /// Adjust the number of streams we process at once based on our
+    /// throughput, never going above `max_streams`.
+    pub fn with_adaptive_streams(mut self, adaptive_streams: bool) -> Self {
+        self.adaptive_streams = adaptive_streams;
+        self
+    }
+
     /// Verify that this structure only contains supported arguments. This uses
     /// the [type state][] pattern to keep track of whether our arguments have
     /// been verified to be supported.
@@ -105,6 +123,7 @@ impl SharedArguments<Unverified> {
             schema: self.schema,
             temporary_storage: self.temporary_storage,
             max_streams: self.max_streams,
+            adaptive_streams: self.adaptive_streams,
             _phantom: PhantomData,
         })
     }
@@ -128,6 +147,8 @@ impl SharedArguments<Verified> {
 pub enum SourceArgumentsFeatures {
     DriverArgs,
     WhereClause,
+    Limit,
+    OrderBy,
 }
 
 impl fmt::Display for DisplayEnumSet<SourceArgumentsFeatures> {
@@ -139,6 +160,12 @@ impl fmt::Display for DisplayEnumSet<SourceArgumentsFeatures> {
         if self.0.contains(SourceArgumentsFeatures::WhereClause) {
             write!(f, "{}--where=$SQL_EXPR", sep.display())?;
         }
+        if self.0.contains(SourceArgumentsFeatures::Limit) {
+            write!(f, "{}--limit=$N", sep.display())?;
+        }
+        if self.0.contains(SourceArgumentsFeatures::OrderBy) {
+            write!(f, "{}--order-by=$SQL_EXPR", sep.display())?;
+        }
         Ok(())
     }
 }
@@ -152,6 +179,16 @@ pub struct SourceArguments<ArgumentState> {
     /// A `WHERE` clause for this query.
     where_clause: Option<String>,
 
+    /// Did the user pass `--select`? If so, drivers which read CSV files
+    /// should drop any columns which aren't in the schema.
+    select_columns: bool,
+
+    /// The maximum number of rows to read.
+    limit: Option<usize>,
+
+    /// An `ORDER BY` clause for this query.
+    order_by: Option<String>,
+
     /// We need to include a reference to `ArgumentState` somewhere, so use a
     /// 0-byte phantom value.
     _phantom: PhantomData<ArgumentState>,
@@ -164,16 +201,50 @@ impl SourceArguments<Unverified> {
         Self {
             driver_args,
             where_clause,
+            select_columns: false,
+            limit: None,
+            order_by: None,
             _phantom: PhantomData,
         }
     }
 
+    /// Ask drivers which read CSV files to drop any columns which aren't in
+    /// the schema. We only do this for `--select`, so that a schema which
+    /// doesn't match the data is still an error.
+    pub fn with_select_columns(mut self, select_columns: bool) -> Self {
+        self.select_columns = select_columns;
+        self
+    }
+
+    /// Only read the first `limit` rows.
+    pub fn with_limit(mut self, limit: Option<usize>) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Read rows in the order specified by the SQL expression `order_by`.
+    pub fn with_order_by(mut self, order_by: Option<String>) -> Self {
+        self.order_by = order_by;
+        self
+    }
+
     /// Construct a new `SourceArguments` with typical values for a temporary
     /// storage location.
     pub fn for_temporary() -> Self {
         Self::new(DriverArguments::default(), None)
     }
 
+    /// Remove our `WHERE` clause and return it separately, so that we can
+    /// filter rows ourselves instead of asking the driver to do it.
+    pub(crate) fn take_where_clause(self) -> (Self, Option<String>) {
+        let where_clause = self.where_clause;
+        let args = Self::new(self.driver_args, None)
+            .with_select_columns(self.select_columns)
+            .with_limit(self.limit)
+            .with_order_by(self.order_by);
+        (args, where_clause)
+    }
+
     /// Verify that this structure only contains supported arguments. This uses
     /// the [type state][] pattern to keep track of whether our arguments have
     /// been verified to be supported.
@@ -194,9 +265,26 @@ impl SourceArguments<Unverified> {
         {
             return Err(format_err!("this data source does not support --where"));
         }
+        if !features
+            .source_args
+            .contains(SourceArgumentsFeatures::Limit)
+            && self.limit.is_some()
+        {
+            return Err(format_err!("this data source does not support --limit"));
+        }
+        if !features
+            .source_args
+            .contains(SourceArgumentsFeatures::OrderBy)
+            && self.order_by.is_some()
+        {
+            return Err(format_err!("this data source does not support --order-by"));
+        }
         Ok(SourceArguments {
             driver_args: self.driver_args,
             where_clause: self.where_clause,
+            select_columns: self.select_columns,
+            limit: self.limit,
+            order_by: self.order_by,
             _phantom: PhantomData,
         })
     }
@@ -213,6 +301,22 @@ impl SourceArguments<Verified> {
     pub fn where_clause(&self) -> Option<&str> {
         self.where_clause.as_ref().map(|s| &s[..])
     }
+
+    /// Should drivers which read CSV files drop columns which aren't in the
+    /// schema?
+    pub fn select_columns(&self) -> bool {
+        self.select_columns
+    }
+
+    /// The maximum number of rows to read.
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// An `ORDER BY` clause for this query.
+    pub fn order_by(&self) -> Option<&str> {
+        self.order_by.as_deref()
+    }
 }
 
 /// What `DestinationArguments` features are supported by a given driver?
@@ -239,6 +343,10 @@ pub struct DestinationArguments<ArgumentState> {
     /// What to do it the destination already exists.
     if_exists: IfExists,
 
+    /// Is this a staging location that we created inside our temporary
+    /// storage, and not a destination chosen by the user?
+    temporary: bool,
+
     /// We need to include a reference to `ArgumentState` somewhere, so use a
     /// 0-byte phantom value.
     _phantom: PhantomData<ArgumentState>,
@@ -251,6 +359,7 @@ impl DestinationArguments<Unverified> {
         DestinationArguments {
             driver_args,
             if_exists,
+            temporary: false,
             _phantom: PhantomData,
         }
     }
@@ -258,7 +367,18 @@ impl DestinationArguments<Unverified> {
     /// Construct a new `DestinationArguments` with typical values for a
     /// temporary storage location.
     pub fn for_temporary() -> Self {
-        Self::new(DriverArguments::default(), IfExists::Overwrite)
+        Self::for_temporary_with_driver_args(DriverArguments::default())
+    }
+
+    /// Construct a new `DestinationArguments` for a temporary storage
+    /// location, using the specified `driver_args`.
+    pub(crate) fn for_temporary_with_driver_args(
+        driver_args: DriverArguments,
+    ) -> Self {
+        DestinationArguments {
+            temporary: true,
+            ..Self::new(driver_args, IfExists::Overwrite)
+        }
     }
 
     /// Verify that this structure only contains supported arguments. This uses
@@ -280,6 +400,7 @@ impl DestinationArguments<Unverified> {
         Ok(DestinationArguments {
             driver_args: self.driver_args,
             if_exists: self.if_exists,
+            temporary: self.temporary,
             _phantom: PhantomData,
         })
     }
@@ -296,4 +417,10 @@ impl DestinationArguments<Verified> {
     pub fn if_exists(&self) -> &IfExists {
         &self.if_exists
     }
+
+    /// Is this a staging location that we created inside our temporary
+    /// storage?
+    pub(crate) fn is_temporary(&self) -> bool {
+        self.temporary
+    }
 }
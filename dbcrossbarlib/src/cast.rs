@@ -0,0 +1,123 @@
+//! Overriding the types of specific columns.
+//!
+//! CSV type inference and legacy DDL often get column types wrong, like
+//! treating ZIP codes as integers. Rather than editing a schema file by hand,
+//! the user can pass `--cast zip_code:text` to change the portable schema
+//! before copying.
+
+use std::{fmt, str::FromStr};
+
+use crate::common::*;
+use crate::drivers::postgres_shared::parse_column_type;
+use crate::schema::{Collation, DataType};
+
+/// A new type for a single column, parsed from `column:type`. The type uses
+/// PostgreSQL syntax, like `text`, `numeric(12,2)` or `citext`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ColumnCast {
+    /// The column to change.
+    column: String,
+    /// The original type, as specified by the user.
+    type_name: String,
+    /// The new data type.
+    data_type: DataType,
+    /// The new collation, if any.
+    collation: Option<Collation>,
+}
+
+impl ColumnCast {
+    /// Apply `casts` to the columns of `table`. Fails if we try to change a
+    /// column that doesn't exist.
+    pub fn apply_all(casts: &[ColumnCast], table: &Table) -> Result<Table> {
+        let mut table = table.clone();
+        for cast in casts {
+            let col = table
+                .columns
+                .iter_mut()
+                .find(|c| c.name == cast.column)
+                .ok_or_else(|| {
+                    format_err!(
+                        "cannot cast column {:?} because it isn't in the schema",
+                        cast.column,
+                    )
+                })?;
+            col.data_type = cast.data_type.clone();
+            col.collation = cast.collation.clone();
+        }
+        Ok(table)
+    }
+}
+
+impl fmt::Display for ColumnCast {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.column, self.type_name)
+    }
+}
+
+impl FromStr for ColumnCast {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some(column), Some(type_name)) if !column.is_empty() => {
+                let (data_type, collation) = parse_column_type(type_name)?;
+                Ok(ColumnCast {
+                    column: column.to_owned(),
+                    type_name: type_name.to_owned(),
+                    data_type,
+                    collation,
+                })
+            }
+            _ => Err(format_err!(
+                "expected a cast of the form column:type, found {:?}",
+                s,
+            )),
+        }
+    }
+}
+
+#[test]
+fn casts_are_parsed_and_applied() {
+    use crate::schema::{Column, DecimalPrecision};
+
+    let table = Table {
+        name: "t".to_owned(),
+        columns: ["zip_code", "amount", "email"]
+            .iter()
+            .map(|&name| Column {
+                name: name.to_owned(),
+                is_nullable: true,
+                data_type: DataType::Int64,
+                comment: None,
+                default: None,
+                collation: None,
+            })
+            .collect(),
+        primary_key: vec![],
+        indexes: vec![],
+    };
+    let casts = ["zip_code:text", "amount:numeric(12, 2)", "email:citext"]
+        .iter()
+        .map(|s| s.parse::<ColumnCast>())
+        .collect::<Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(casts[1].to_string(), "amount:numeric(12, 2)");
+
+    let cast = ColumnCast::apply_all(&casts, &table).unwrap();
+    assert_eq!(cast.columns[0].data_type, DataType::Text);
+    assert_eq!(
+        cast.columns[1].data_type,
+        DataType::DecimalWithPrecision(DecimalPrecision::new(12, 2).unwrap()),
+    );
+    assert_eq!(cast.columns[2].data_type, DataType::Text);
+    assert_eq!(cast.columns[2].collation, Some(Collation::CaseInsensitive));
+
+    assert!("zip_code".parse::<ColumnCast>().is_err());
+    assert!("zip_code:nonsense".parse::<ColumnCast>().is_err());
+    let missing = vec!["missing:text".parse::<ColumnCast>().unwrap()];
+    let err = ColumnCast::apply_all(&missing, &table)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("isn't in the schema"), "{}", err);
+}
@@ -0,0 +1,266 @@
+//! Checking CSV headers against the schema before appending data.
+//!
+//! Most of our destination drivers load CSV columns by position. If a file's
+//! columns don't match the schema, we would quietly load data into the wrong
+//! fields. So when appending to an existing table, we check each stream's
+//! header first.
+
+use csv;
+use itertools::Itertools;
+use std::{collections::HashSet, convert::TryFrom, io};
+
+use crate::common::*;
+use crate::schema::Column;
+use crate::transform::spawn_sync_transform;
+
+/// Check the header of each stream in `streams` against `columns`, failing if
+/// any columns are missing or unexpected. If `allow_reordering` is true, we
+/// accept columns in any order, and rewrite them to match `columns`.
+///
+/// Streams whose headers already match are passed through without parsing or
+/// rewriting their rows, which is much faster for large files. We only do this
+/// for plain `\n`-terminated headers without a UTF-8 byte order mark, so that
+/// streams using `\r\n` or starting with a BOM are still normalized.
+pub fn check_csv_headers(
+    ctx: Context,
+    columns: Vec<Column>,
+    allow_reordering: bool,
+    streams: BoxStream<CsvStream>,
+) -> BoxStream<CsvStream> {
+    streams
+        .and_then(move |stream| {
+            let ctx = ctx.clone();
+            let columns = columns.clone();
+            async move {
+                let name = stream.name.clone();
+                let data = spawn_sync_transform(
+                    ctx,
+                    "check_csv_headers".to_owned(),
+                    stream.data,
+                    move |_ctx, rdr, wtr| {
+                        check_and_reorder(&columns, allow_reordering, rdr, wtr)
+                            .with_context(|_| {
+                                format!("bad CSV header in stream {}", name)
+                            })?;
+                        Ok(())
+                    },
+                )?;
+                Ok(CsvStream {
+                    name: stream.name,
+                    data,
+                })
+            }
+        })
+        .boxed()
+}
+
+/// Copy CSV data from `rdr` to `wtr`, making sure that the header matches
+/// `columns`, and putting columns in schema order if `allow_reordering` is
+/// true.
+///
+/// This is synchronous, and should be run using `spawn_sync_transform`.
+fn check_and_reorder<R: Read, W: Write>(
+    columns: &[Column],
+    allow_reordering: bool,
+    mut rdr: R,
+    wtr: W,
+) -> Result<()> {
+    let mut csv_rdr = csv::Reader::from_reader(RecordingReader {
+        inner: &mut rdr,
+        recorded: vec![],
+    });
+    let headers = csv_rdr.headers()?.clone();
+    let header_len = usize::try_from(csv_rdr.position().byte())
+        .expect("CSV header should fit in memory");
+    let order = column_order(columns, &headers, allow_reordering)?;
+
+    // If our header isn't plain, `\n`-terminated UTF-8, rewrite everything,
+    // even if our columns are already in the right order.
+    let recorded = &csv_rdr.get_ref().recorded;
+    let header_is_plain = !recorded.starts_with(b"\xEF\xBB\xBF")
+        && recorded[..header_len].ends_with(b"\n")
+        && !recorded[..header_len].ends_with(b"\r\n");
+    let order = match order {
+        None if !header_is_plain => Some((0..columns.len()).collect()),
+        order => order,
+    };
+
+    match order {
+        // Our columns are already in the right order, so copy our data without
+        // parsing it, starting with the bytes the CSV reader has already seen.
+        None => {
+            let recorded = csv_rdr.into_inner().recorded;
+            copy_with_final_newline(
+                Read::chain(&recorded[..], rdr),
+                io::BufWriter::with_capacity(BUFFER_SIZE, wtr),
+            )?;
+        }
+        // Rearrange each row to match our schema.
+        Some(order) => {
+            let mut rdr = csv_rdr;
+            let mut wtr = csv::Writer::from_writer(io::BufWriter::with_capacity(
+                BUFFER_SIZE,
+                wtr,
+            ));
+            wtr.write_record(columns.iter().map(|c| &c.name))?;
+            let mut out = csv::ByteRecord::new();
+            for row in rdr.byte_records() {
+                let row = row?;
+                out.clear();
+                for &idx in &order {
+                    out.push_field(&row[idx]);
+                }
+                wtr.write_byte_record(&out)?;
+            }
+            wtr.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// A reader which remembers everything read through it, so that we can parse
+/// a CSV header and still copy the original bytes.
+struct RecordingReader<R> {
+    inner: R,
+    recorded: Vec<u8>,
+}
+
+impl<R: Read> Read for RecordingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        self.recorded.extend_from_slice(&buf[..count]);
+        Ok(count)
+    }
+}
+
+/// Copy `rdr` to `wtr` unchanged, except that we add a newline if the last row
+/// doesn't end with one, so that our output can safely be concatenated with
+/// other CSV data.
+fn copy_with_final_newline<R: Read, W: Write>(mut rdr: R, mut wtr: W) -> Result<()> {
+    let mut buffer = vec![0; BUFFER_SIZE];
+    let mut last_byte = None;
+    loop {
+        let count = match rdr.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(count) => count,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        wtr.write_all(&buffer[..count])?;
+        last_byte = Some(buffer[count - 1]);
+    }
+    if matches!(last_byte, Some(b) if b != b'\n') {
+        wtr.write_all(b"\n")?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Compare `headers` to `columns`. Returns `None` if they already match, or
+/// the header index to use for each column if they only differ in order and
+/// `allow_reordering` is true.
+pub(crate) fn column_order(
+    columns: &[Column],
+    headers: &csv::StringRecord,
+    allow_reordering: bool,
+) -> Result<Option<Vec<usize>>> {
+    if headers.iter().eq(columns.iter().map(|c| &c.name[..])) {
+        return Ok(None);
+    }
+
+    // Look for missing and unexpected columns.
+    let expected = columns.iter().map(|c| &c.name[..]).collect::<HashSet<_>>();
+    let found = headers.iter().collect::<HashSet<_>>();
+    let missing = columns
+        .iter()
+        .map(|c| &c.name[..])
+        .filter(|name| !found.contains(name))
+        .collect::<Vec<_>>();
+    let unexpected = headers
+        .iter()
+        .filter(|name| !expected.contains(name))
+        .collect::<Vec<_>>();
+    let mut problems = vec![];
+    if !missing.is_empty() {
+        problems.push(format!("missing columns: {}", missing.join(", ")));
+    }
+    if !unexpected.is_empty() {
+        problems.push(format!("unexpected columns: {}", unexpected.join(", ")));
+    }
+    if headers.len() != found.len() {
+        problems.push("duplicate column names".to_owned());
+    }
+    if !problems.is_empty() {
+        return Err(format_err!("{}", problems.join("; ")));
+    }
+
+    // We have the right columns, but in the wrong order.
+    if !allow_reordering {
+        return Err(format_err!(
+            "columns are in a different order than the schema (expected {}, found {}; pass --allow-reordered-columns to accept this)",
+            columns.iter().map(|c| &c.name[..]).join(", "),
+            headers.iter().join(", "),
+        ));
+    }
+    let order = columns
+        .iter()
+        .map(|c| {
+            headers
+                .iter()
+                .position(|h| h == c.name)
+                .expect("column should be present")
+        })
+        .collect();
+    Ok(Some(order))
+}
+
+#[test]
+fn headers_are_checked_and_reordered() {
+    use crate::schema::DataType;
+
+    let columns = ["a", "b", "c"]
+        .iter()
+        .map(|&name| Column {
+            name: name.to_owned(),
+            is_nullable: true,
+            data_type: DataType::Text,
+            comment: None,
+            default: None,
+            collation: None,
+        })
+        .collect::<Vec<_>>();
+    let run = |input: &str, allow_reordering| -> Result<String> {
+        let mut out = vec![];
+        check_and_reorder(&columns, allow_reordering, input.as_bytes(), &mut out)?;
+        Ok(String::from_utf8(out)?)
+    };
+
+    assert_eq!(run("a,b,c\n1,2,3\n", false).unwrap(), "a,b,c\n1,2,3\n");
+    assert_eq!(run("c,a,b\n3,1,2\n", true).unwrap(), "a,b,c\n1,2,3\n");
+
+    // Matching headers are copied without rewriting the data.
+    assert_eq!(
+        run("a,b,c\n\"1\",2,3\n4,5,6", false).unwrap(),
+        "a,b,c\n\"1\",2,3\n4,5,6\n",
+    );
+
+    // But CRLF line endings and byte order marks are normalized, and ragged
+    // rows are rejected.
+    assert_eq!(
+        run("a,b,c\r\n\"1\",2,3\r\n4,5,6", false).unwrap(),
+        "a,b,c\n1,2,3\n4,5,6\n",
+    );
+    assert_eq!(
+        run("\u{FEFF}a,b,c\n1,2,3\n", false).unwrap(),
+        "a,b,c\n1,2,3\n",
+    );
+    assert!(run("a,b,c\r\n1,2\r\n", false).is_err());
+    let long_input = format!("a,b,c\n{}", "1,2,3\n".repeat(10_000));
+    assert_eq!(run(&long_input, false).unwrap(), long_input);
+
+    let err = run("c,a,b\n3,1,2\n", false).unwrap_err().to_string();
+    assert!(err.contains("different order"), "{}", err);
+    let err = run("a,c,d\n1,3,4\n", true).unwrap_err().to_string();
+    assert!(err.contains("missing columns: b"), "{}", err);
+    assert!(err.contains("unexpected columns: d"), "{}", err);
+}
@@ -1,13 +1,14 @@
 //! Interfaces to BigQuery.
 
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Deserialize};
 use std::{fs::File, process::Stdio};
 use tempdir::TempDir;
 use tokio::process::Command;
 
+use super::load_quota::{reserve_load_job, LoadQuota};
 use crate::common::*;
 use crate::drivers::bigquery_shared::{
-    if_exists_to_bq_load_arg, BqColumn, BqTable, TableName,
+    if_exists_to_bq_load_arg, BqColumn, BqTable, DatasetName, TableName,
 };
 use crate::tokio_glue::write_to_stdin;
 
@@ -16,6 +17,7 @@ use crate::tokio_glue::write_to_stdin;
 async fn query_all_json(ctx: &Context, project: &str, sql: &str) -> Result<String> {
     // Run our query.
     debug!(ctx.log(), "running `bq query`");
+    ctx.usage().record_api_call("bigquery");
     let mut query_child = Command::new("bq")
         // We'll pass the query on `stdin`.
         .stdin(Stdio::piped())
@@ -85,6 +87,7 @@ pub(crate) async fn query_to_table(
 ) -> Result<()> {
     // Run our query.
     debug!(ctx.log(), "running `bq query`");
+    ctx.usage().record_api_call("bigquery");
     let mut query_child = Command::new("bq")
         // We'll pass the query on `stdin`.
         .stdin(Stdio::piped())
@@ -119,6 +122,7 @@ pub(crate) async fn execute_sql(
 ) -> Result<()> {
     // Run our SQL.
     debug!(ctx.log(), "running `bq query`");
+    ctx.usage().record_api_call("bigquery");
     let mut query_child = Command::new("bq")
         // We'll pass the SQL on `stdin`.
         .stdin(Stdio::piped())
@@ -143,12 +147,36 @@ pub(crate) async fn execute_sql(
     }
 }
 
-/// Load data from `gs_url` into `dest_table`.
+/// The format of the files we ask `bq load` to read.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum LoadFormat {
+    /// CSV files with a single header row.
+    Csv,
+    /// Newline-delimited JSON files, which may contain nested data.
+    NewlineDelimitedJson,
+}
+
+impl LoadFormat {
+    /// The `bq load` argument used to specify this format.
+    fn to_bq_load_arg(self) -> &'static str {
+        match self {
+            LoadFormat::Csv => "--skip_leading_rows=1",
+            LoadFormat::NewlineDelimitedJson => {
+                "--source_format=NEWLINE_DELIMITED_JSON"
+            }
+        }
+    }
+}
+
+/// Load data from `gs_url` into `dest_table`, which must be in `format`. If
+/// submitting another load job would exceed `quota`, wait until it won't.
 pub(crate) async fn load(
     ctx: &Context,
     gs_url: &Url,
     dest_table: &BqTable,
+    format: LoadFormat,
     if_exists: &IfExists,
+    quota: &LoadQuota,
 ) -> Result<()> {
     // Write our schema to a temp file. This actually needs to be somewhere on
     // disk, and `bq` uses various hueristics to detect that it's a file
@@ -163,18 +191,12 @@ pub(crate) async fn load(
     dest_table.write_json_schema(&mut initial_schema_file)?;
 
     // Build and run a `bq load` command.
+    reserve_load_job(ctx, dest_table.name(), quota).await?;
     debug!(ctx.log(), "running `bq load`");
+    ctx.usage().record_api_call("bigquery");
     let load_child = Command::new("bq")
         // These arguments can all be represented as UTF-8 `&str`.
-        .args(&[
-            "load",
-            "--headless",
-            "--skip_leading_rows=1",
-            &format!("--project_id={}", dest_table.name().project()),
-            if_exists_to_bq_load_arg(&if_exists)?,
-            &dest_table.name().to_string(),
-            gs_url.as_str(),
-        ])
+        .args(load_args(gs_url, dest_table, format, if_exists)?)
         // Throw away stdout so it doesn't corrupt our output.
         .stdout(Stdio::null())
         // This argument is a path, and so it might contain non-UTF-8
@@ -191,6 +213,39 @@ pub(crate) async fn load(
     }
 }
 
+/// The arguments we pass to `bq load`, except for the path to our schema.
+fn load_args(
+    gs_url: &Url,
+    dest_table: &BqTable,
+    format: LoadFormat,
+    if_exists: &IfExists,
+) -> Result<Vec<String>> {
+    let mut args = vec![
+        "load".to_owned(),
+        "--headless".to_owned(),
+        format.to_bq_load_arg().to_owned(),
+        format!("--project_id={}", dest_table.name().project()),
+        if_exists_to_bq_load_arg(if_exists)?.to_owned(),
+    ];
+    // Options like partitioning and clustering, which only matter if we
+    // create the table.
+    args.extend(dest_table.bq_load_args());
+    args.push(dest_table.name().to_string());
+    args.push(gs_url.as_str().to_owned());
+    Ok(args)
+}
+
+/// Describe the `bq load` command that `load` would run, for `cp --dry-run`.
+pub(crate) fn load_command(
+    gs_url: &Url,
+    dest_table: &BqTable,
+    format: LoadFormat,
+    if_exists: &IfExists,
+) -> Result<String> {
+    let args = load_args(gs_url, dest_table, format, if_exists)?;
+    Ok(format!("bq {} schema.json", args.join(" ")))
+}
+
 /// Drop a table from BigQuery.
 pub(crate) async fn drop_table(ctx: &Context, table_name: &TableName) -> Result<()> {
     // Delete temp table.
@@ -199,11 +254,33 @@ pub(crate) async fn drop_table(ctx: &Context, table_name: &TableName) -> Result<
     execute_sql(ctx, table_name.project(), &sql).await
 }
 
+/// Drop a table from BigQuery, if it exists.
+pub(crate) async fn drop_table_if_exists(
+    ctx: &Context,
+    table_name: &TableName,
+) -> Result<()> {
+    debug!(ctx.log(), "deleting table if exists: {}", table_name);
+    let sql = format!("DROP TABLE IF EXISTS {};\n", table_name.dotted_and_quoted());
+    execute_sql(ctx, table_name.project(), &sql).await
+}
+
 /// Look up the schema of the specified table.
 pub(crate) async fn schema(ctx: &Context, name: &TableName) -> Result<BqTable> {
+    schema_if_exists(ctx, name)
+        .await?
+        .ok_or_else(|| format_err!("BigQuery table {} does not exist", name))
+}
+
+/// Look up the schema of the specified table, or return `None` if the table
+/// doesn't exist. Any other error is returned normally.
+pub(crate) async fn schema_if_exists(
+    ctx: &Context,
+    name: &TableName,
+) -> Result<Option<BqTable>> {
     let project_id = format!("--project_id={}", name.project());
+    ctx.usage().record_api_call("bigquery");
     let output = Command::new("bq")
-        .args(&[
+        .args([
             "show",
             "--headless",
             "--schema",
@@ -211,16 +288,28 @@ pub(crate) async fn schema(ctx: &Context, name: &TableName) -> Result<BqTable> {
             &project_id,
             &name.to_string(),
         ])
-        .stderr(Stdio::inherit())
+        .stderr(Stdio::piped())
         .output()
         .await
         .context("error running `bq show --schema`")?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
     if !output.status.success() {
+        // `bq` may report errors on either stdout or stderr.
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if is_not_found_error(&stdout) || is_not_found_error(&stderr) {
+            debug!(ctx.log(), "BigQuery table {} does not exist", name);
+            return Ok(None);
+        }
         return Err(format_err!(
-            "`bq show --schema` failed with {}",
+            "`bq show --schema` failed with {}: {} {}",
             output.status,
+            stdout.trim(),
+            stderr.trim(),
         ));
     }
+    if !stderr.trim().is_empty() {
+        debug!(ctx.log(), "`bq show --schema` output: {}", stderr.trim());
+    }
     debug!(
         ctx.log(),
         "BigQuery schema: {}",
@@ -228,10 +317,100 @@ pub(crate) async fn schema(ctx: &Context, name: &TableName) -> Result<BqTable> {
     );
     let columns: Vec<BqColumn> = serde_json::from_slice(&output.stdout)
         .context("error parsing BigQuery schema")?;
-    Ok(BqTable {
+    Ok(Some(BqTable {
         name: name.to_owned(),
         columns,
-    })
+        time_partitioning: None,
+        clustering: None,
+        ttl: None,
+    }))
+}
+
+/// Does this `bq` output say that a table (or its dataset) doesn't exist?
+fn is_not_found_error(output: &str) -> bool {
+    output.contains("Not found: Table") || output.contains("Not found: Dataset")
+}
+
+/// Fetch up to `limit` rows from the start of a table, and deserialize them.
+/// This uses `bq head`, which reads rows directly from the table without
+/// running a query, so it's fast and free.
+pub(crate) async fn head<T>(
+    ctx: &Context,
+    name: &TableName,
+    limit: usize,
+) -> Result<Vec<T>>
+where
+    T: DeserializeOwned,
+{
+    debug!(ctx.log(), "running `bq head`");
+    ctx.usage().record_api_call("bigquery");
+    let output = Command::new("bq")
+        .args(["head", "--headless", "--format=json"])
+        .arg(format!("--max_rows={}", limit))
+        .arg(format!("--project_id={}", name.project()))
+        .arg(name.to_string())
+        .stderr(Stdio::inherit())
+        .output()
+        .await
+        .context("error running `bq head`")?;
+    if !output.status.success() {
+        return Err(format_err!("`bq head` failed with {}", output.status));
+    }
+    Ok(serde_json::from_slice::<Vec<T>>(&output.stdout)
+        .context("could not parse `bq head` output")?)
+}
+
+/// List the tables, views and table snapshots in a BigQuery dataset.
+pub(crate) async fn list_tables(
+    ctx: &Context,
+    dataset_name: &DatasetName,
+) -> Result<Vec<TableName>> {
+    /// The parts of `bq ls` output that we care about.
+    #[derive(Deserialize)]
+    struct TableInfo {
+        #[serde(rename = "tableReference")]
+        table_reference: TableReference,
+        #[serde(rename = "type")]
+        ty: String,
+    }
+
+    /// A reference to a specific table.
+    #[derive(Deserialize)]
+    struct TableReference {
+        #[serde(rename = "tableId")]
+        table_id: String,
+    }
+
+    debug!(ctx.log(), "listing tables in {}", dataset_name);
+    ctx.usage().record_api_call("bigquery");
+    let output = Command::new("bq")
+        .args([
+            "ls",
+            "--headless",
+            "--format=json",
+            "--max_results=100000",
+            &format!("--project_id={}", dataset_name.project()),
+            &dataset_name.to_string(),
+        ])
+        .stderr(Stdio::inherit())
+        .output()
+        .await
+        .context("error running `bq ls`")?;
+    if !output.status.success() {
+        return Err(format_err!("`bq ls` failed with {}", output.status));
+    }
+
+    // `bq ls` prints nothing at all for an empty dataset.
+    if output.stdout.iter().all(|b| b.is_ascii_whitespace()) {
+        return Ok(vec![]);
+    }
+    let infos: Vec<TableInfo> = serde_json::from_slice(&output.stdout)
+        .context("error parsing `bq ls` output")?;
+    Ok(infos
+        .into_iter()
+        .filter(|info| matches!(&info.ty[..], "TABLE" | "VIEW" | "SNAPSHOT"))
+        .map(|info| dataset_name.table_name(&info.table_reference.table_id))
+        .collect())
 }
 
 /// Extract a table from BigQuery to Google Cloud Storage.
@@ -242,6 +421,7 @@ pub(crate) async fn extract(
 ) -> Result<()> {
     // Build and run a `bq extract` command.
     debug!(ctx.log(), "running `bq extract`");
+    ctx.usage().record_api_call("bigquery");
     let extract_child = Command::new("bq")
         // These arguments can all be represented as UTF-8 `&str`.
         .args(&[
@@ -263,3 +443,16 @@ pub(crate) async fn extract(
         Err(format_err!("`bq extract` failed with {}", status))
     }
 }
+
+#[test]
+fn recognizes_not_found_errors() {
+    assert!(is_not_found_error(
+        "BigQuery error in show operation: Not found: Table p:d.t"
+    ));
+    assert!(is_not_found_error(
+        "BigQuery error in show operation: Not found: Dataset p:d"
+    ));
+    assert!(!is_not_found_error(
+        "BigQuery error in show operation: Access Denied: Table p:d.t"
+    ));
+}
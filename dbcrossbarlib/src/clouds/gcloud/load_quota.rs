@@ -0,0 +1,413 @@
+//! Keeping track of BigQuery load jobs, so that we don't exceed BigQuery's
+//! daily load job quotas.
+//!
+//! BigQuery limits how many load jobs may be run against each table and each
+//! project in a 24-hour period, and failed jobs count against these limits.
+//! If a large backfill exceeds one of these limits halfway through, the
+//! remaining chunks can't be loaded until the next day. To prevent this, we
+//! record every load job we submit in a small JSON "ledger" file, and we wait
+//! before submitting new jobs if we're about to go over quota.
+//!
+//! The ledger only knows about load jobs submitted by `dbcrossbar` on this
+//! machine, so it will not prevent quota problems caused by other tools.
+//! Several `dbcrossbar` processes may share the ledger, so we lock it while
+//! updating it. If we can't use the ledger at all, we don't limit load jobs.
+
+use chrono::{Duration, TimeZone, Utc};
+use fs2::FileExt;
+use lazy_static::lazy_static;
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tokio::time::delay_for;
+
+use crate::common::*;
+use crate::drivers::bigquery_shared::TableName;
+use crate::tokio_glue::run_sync_fn_in_background;
+
+/// BigQuery's documented limit on load jobs per table per day.
+const DEFAULT_MAX_LOAD_JOBS_PER_TABLE: u32 = 1_500;
+
+/// BigQuery's documented limit on load jobs per project per day.
+const DEFAULT_MAX_LOAD_JOBS_PER_PROJECT: u32 = 100_000;
+
+/// The length of a quota window, in seconds.
+const WINDOW_SECONDS: i64 = 24 * 60 * 60;
+
+/// Warn once we've used this fraction of a quota.
+const WARNING_FRACTION: f64 = 0.9;
+
+lazy_static! {
+    /// Prevent multiple tasks in this process from updating the ledger at the
+    /// same time. We also lock the ledger file itself, to protect it from
+    /// other processes.
+    static ref LEDGER_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// How many load jobs are we allowed to submit in a 24-hour period?
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct LoadQuota {
+    /// The maximum number of load jobs per destination table.
+    pub(crate) max_per_table: u32,
+    /// The maximum number of load jobs per project.
+    pub(crate) max_per_project: u32,
+}
+
+impl Default for LoadQuota {
+    fn default() -> Self {
+        LoadQuota {
+            max_per_table: DEFAULT_MAX_LOAD_JOBS_PER_TABLE,
+            max_per_project: DEFAULT_MAX_LOAD_JOBS_PER_PROJECT,
+        }
+    }
+}
+
+/// A load job that we submitted.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+struct LoadJobRecord {
+    /// The project which ran the load job.
+    project: String,
+    /// The table we loaded, in `project:dataset.table` form.
+    table: String,
+    /// When we submitted the job, in seconds since the Unix epoch.
+    submitted_at: i64,
+}
+
+/// The load jobs submitted during the last 24 hours.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct LoadJobLedger {
+    jobs: Vec<LoadJobRecord>,
+}
+
+/// How much of our quota has been used?
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct QuotaUsage {
+    /// Jobs submitted for this table.
+    table: u32,
+    /// Jobs submitted for this project.
+    project: u32,
+}
+
+impl LoadJobLedger {
+    /// Forget about any jobs which are no longer in the quota window.
+    fn prune(&mut self, now: i64) {
+        self.jobs.retain(|j| j.submitted_at > now - WINDOW_SECONDS);
+    }
+
+    /// Count the jobs in the current window for `table`.
+    fn usage(&self, table: &TableName) -> QuotaUsage {
+        let table_str = table.to_string();
+        let mut usage = QuotaUsage {
+            table: 0,
+            project: 0,
+        };
+        for job in &self.jobs {
+            if job.project == table.project() {
+                usage.project += 1;
+                if job.table == table_str {
+                    usage.table += 1;
+                }
+            }
+        }
+        usage
+    }
+
+    /// If we can't submit a job for `table` right now, return the time at
+    /// which enough older jobs will have left the quota window.
+    fn available_at(&self, table: &TableName, quota: &LoadQuota) -> Option<i64> {
+        let table_str = table.to_string();
+        let table_available_at =
+            self.expiry_time(quota.max_per_table, |j| j.table == table_str);
+        let project_available_at =
+            self.expiry_time(quota.max_per_project, |j| j.project == table.project());
+        table_available_at.max(project_available_at)
+    }
+
+    /// If at least `max` jobs match `pred`, return the time at which enough of
+    /// them will have expired to allow one more.
+    fn expiry_time<P>(&self, max: u32, pred: P) -> Option<i64>
+    where
+        P: Fn(&LoadJobRecord) -> bool,
+    {
+        let mut times = self
+            .jobs
+            .iter()
+            .filter(|j| pred(j))
+            .map(|j| j.submitted_at)
+            .collect::<Vec<_>>();
+        if times.len() < max as usize {
+            return None;
+        }
+        times.sort_unstable();
+        Some(times[times.len() - max as usize] + WINDOW_SECONDS)
+    }
+
+    /// Record a new job for `table`.
+    fn record(&mut self, table: &TableName, now: i64) {
+        self.jobs.push(LoadJobRecord {
+            project: table.project().to_owned(),
+            table: table.to_string(),
+            submitted_at: now,
+        });
+    }
+
+    /// Load our ledger, or create an empty one.
+    fn load(path: &Path) -> Result<LoadJobLedger> {
+        if !path.exists() {
+            return Ok(LoadJobLedger::default());
+        }
+        let data = fs::read_to_string(path)
+            .with_context(|_| format!("could not read {}", path.display()))?;
+        Ok(serde_json::from_str(&data)
+            .with_context(|_| format!("could not parse {}", path.display()))?)
+    }
+
+    /// Save our ledger. We write a temporary file and rename it over the old
+    /// ledger, so that readers never see a partly-written file.
+    fn save(&self, path: &Path) -> Result<()> {
+        let data = serde_json::to_string(self)?;
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, data)
+            .with_context(|_| format!("could not write {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, path)
+            .with_context(|_| format!("could not replace {}", path.display()))?;
+        Ok(())
+    }
+}
+
+/// Take an exclusive lock on the ledger at `path`, waiting for any other
+/// process which holds it. The lock is released when the returned file is
+/// closed.
+fn lock_ledger(path: &Path) -> Result<File> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|_| format!("could not create {}", dir.display()))?;
+    }
+    let lock_path = path.with_extension("json.lock");
+    let lock_file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&lock_path)
+        .with_context(|_| format!("could not open {}", lock_path.display()))?;
+    lock_file
+        .lock_exclusive()
+        .with_context(|_| format!("could not lock {}", lock_path.display()))?;
+    Ok(lock_file)
+}
+
+/// Where do we keep our ledger? This can be overridden using
+/// `DBCROSSBAR_QUOTA_DIR`. Returns `None` if we have nowhere to put it.
+fn ledger_path() -> Option<PathBuf> {
+    let dir = match env::var_os("DBCROSSBAR_QUOTA_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?)
+            .join(".dbcrossbar")
+            .join("quota"),
+    };
+    Some(dir.join("bigquery_load_jobs.json"))
+}
+
+/// Try to record a load job for `table` in the ledger at `path`. If we can't
+/// do that without exceeding `quota`, return the time at which we should try
+/// again. Otherwise, return how much quota we've used, including the new job.
+///
+/// This blocks while waiting for other processes to unlock the ledger, so it
+/// must be run using `run_sync_fn_in_background`.
+fn try_reserve_load_job(
+    path: &Path,
+    table: &TableName,
+    quota: &LoadQuota,
+) -> Result<std::result::Result<QuotaUsage, i64>> {
+    let _guard = LEDGER_LOCK.lock().expect("lock poisoned");
+    let _lock_file = lock_ledger(path)?;
+    let now = Utc::now().timestamp();
+    let mut ledger = LoadJobLedger::load(path)?;
+    ledger.prune(now);
+    match ledger.available_at(table, quota) {
+        Some(available_at) => Ok(Err(available_at)),
+        None => {
+            ledger.record(table, now);
+            ledger.save(path)?;
+            Ok(Ok(ledger.usage(table)))
+        }
+    }
+}
+
+/// Format a Unix timestamp for a log message.
+fn format_time(timestamp: i64) -> String {
+    match Utc.timestamp_opt(timestamp, 0).single() {
+        Some(time) => time.to_rfc3339(),
+        None => timestamp.to_string(),
+    }
+}
+
+/// Wait until we can submit a load job for `table` without exceeding `quota`,
+/// and then record the job in our ledger.
+///
+/// If we can't use our ledger (for example, because there's no home
+/// directory, or it's read-only), we warn and submit the job anyway, because
+/// failing every load would be worse than risking the quota.
+pub(crate) async fn reserve_load_job(
+    ctx: &Context,
+    table: &TableName,
+    quota: &LoadQuota,
+) -> Result<()> {
+    let path = match ledger_path() {
+        Some(path) => path,
+        None => {
+            warn!(
+                ctx.log(),
+                "cannot find home directory for BigQuery load job ledger (try setting DBCROSSBAR_QUOTA_DIR); not limiting load jobs",
+            );
+            return Ok(());
+        }
+    };
+    loop {
+        let reserved = {
+            let path = path.clone();
+            let table = table.to_owned();
+            let quota = *quota;
+            run_sync_fn_in_background("load_quota".to_owned(), move || {
+                try_reserve_load_job(&path, &table, &quota)
+            })
+            .await
+        };
+        let wait_until = match reserved {
+            Ok(Ok(usage)) => {
+                warn_if_nearly_exhausted(ctx, table, quota, usage);
+                return Ok(());
+            }
+            Ok(Err(wait_until)) => wait_until,
+            Err(err) => {
+                warn!(
+                    ctx.log(),
+                    "cannot update BigQuery load job ledger {}; not limiting load jobs: {}",
+                    path.display(),
+                    err,
+                );
+                return Ok(());
+            }
+        };
+        warn!(
+            ctx.log(),
+            "BigQuery load job quota reached for {} (limits: {} per table, {} per project per day); waiting until {} to submit the next load job",
+            table,
+            quota.max_per_table,
+            quota.max_per_project,
+            format_time(wait_until),
+        );
+        let wait = Duration::seconds(wait_until - Utc::now().timestamp() + 1);
+        delay_for(wait.to_std().unwrap_or_default()).await;
+    }
+}
+
+/// Warn the user if they're about to run out of quota.
+fn warn_if_nearly_exhausted(
+    ctx: &Context,
+    table: &TableName,
+    quota: &LoadQuota,
+    usage: QuotaUsage,
+) {
+    if f64::from(usage.table) >= f64::from(quota.max_per_table) * WARNING_FRACTION {
+        warn!(
+            ctx.log(),
+            "{} of {} daily BigQuery load jobs used for table {}",
+            usage.table,
+            quota.max_per_table,
+            table,
+        );
+    }
+    if f64::from(usage.project) >= f64::from(quota.max_per_project) * WARNING_FRACTION
+    {
+        warn!(
+            ctx.log(),
+            "{} of {} daily BigQuery load jobs used for project {}",
+            usage.project,
+            quota.max_per_project,
+            table.project(),
+        );
+    }
+}
+
+#[test]
+fn ledger_is_saved_atomically() {
+    use std::{sync::Arc, thread};
+
+    let dir = tempdir::TempDir::new("load_quota").unwrap();
+    let path = Arc::new(dir.path().join("bigquery_load_jobs.json"));
+    let table = "p:d.t".parse::<TableName>().unwrap();
+
+    // Record jobs from several threads, each with its own lock file handle,
+    // the way separate processes would.
+    let threads = (0..4)
+        .map(|_| {
+            let path = path.clone();
+            let table = table.clone();
+            thread::spawn(move || {
+                for now in 0..25 {
+                    let _lock_file = lock_ledger(&path).unwrap();
+                    let mut ledger = LoadJobLedger::load(&path).unwrap();
+                    ledger.record(&table, now);
+                    ledger.save(&path).unwrap();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let ledger = LoadJobLedger::load(&path).unwrap();
+    assert_eq!(ledger.jobs.len(), 100);
+    assert!(!path.with_extension("json.tmp").exists());
+}
+
+#[test]
+fn ledger_enforces_quota() {
+    let table = "p:d.t".parse::<TableName>().unwrap();
+    let other_table = "p:d.u".parse::<TableName>().unwrap();
+    let quota = LoadQuota {
+        max_per_table: 2,
+        max_per_project: 3,
+    };
+    let mut ledger = LoadJobLedger::default();
+    assert_eq!(ledger.available_at(&table, &quota), None);
+    ledger.record(&table, 100);
+    ledger.record(&table, 200);
+    assert_eq!(
+        ledger.available_at(&table, &quota),
+        Some(100 + WINDOW_SECONDS)
+    );
+    assert_eq!(ledger.available_at(&other_table, &quota), None);
+    ledger.record(&other_table, 300);
+    assert_eq!(
+        ledger.available_at(&other_table, &quota),
+        Some(100 + WINDOW_SECONDS)
+    );
+    assert_eq!(
+        ledger.usage(&table),
+        QuotaUsage {
+            table: 2,
+            project: 3
+        }
+    );
+
+    // Once the window has passed, old jobs no longer count.
+    ledger.prune(150 + WINDOW_SECONDS);
+    assert_eq!(ledger.available_at(&table, &quota), None);
+    assert_eq!(ledger.jobs.len(), 2);
+}
+
+#[test]
+fn try_reserve_load_job_fails_without_writable_ledger() {
+    let dir = tempdir::TempDir::new("load_quota").unwrap();
+    let not_a_dir = dir.path().join("file");
+    fs::write(&not_a_dir, "").unwrap();
+    let path = not_a_dir.join("bigquery_load_jobs.json");
+    let table = "p:d.t".parse::<TableName>().unwrap();
+    assert!(try_reserve_load_job(&path, &table, &LoadQuota::default()).is_err());
+}
@@ -1,4 +1,5 @@
 //! Interfaces to Google Cloud.
 
 pub(crate) mod bigquery;
+pub(crate) mod load_quota;
 pub(crate) mod storage;
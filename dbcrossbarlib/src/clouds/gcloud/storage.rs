@@ -3,6 +3,7 @@
 use std::process::Stdio;
 use tokio::{io::BufReader, process::Command};
 
+use crate::clouds::object_tags::ObjectTags;
 use crate::common::*;
 use crate::tokio_glue::{copy_reader_to_stream, copy_stream_to_writer};
 
@@ -22,6 +23,7 @@ pub(crate) async fn ls(
     //
     // XXX - Shouldn't we be using `ls_url` below?
     debug!(ctx.log(), "listing {}", ls_url);
+    ctx.usage().record_api_call("gcs");
     let mut child = Command::new("gsutil")
         .args(&["ls", url.as_str()])
         .stdout(Stdio::piped())
@@ -51,6 +53,7 @@ pub(crate) async fn rmdir(ctx: &Context, url: &Url) -> Result<()> {
         ));
     }
     let delete_url = url.join("**")?;
+    ctx.usage().record_api_call("gcs");
     let status = Command::new("gsutil")
         .args(&["rm", "-f", delete_url.as_str()])
         // Throw away stdout so it doesn't corrupt our output.
@@ -74,6 +77,7 @@ pub(crate) async fn download_file(
 ) -> Result<BoxStream<BytesMut>> {
     // Stream the file from the cloud.
     debug!(ctx.log(), "streaming from {} using `gsutil cp`", file_url);
+    ctx.usage().record_api_call("gcs");
     let mut child = Command::new("gsutil")
         .args(&["cp", file_url.as_str(), "-"])
         .stdout(Stdio::piped())
@@ -86,15 +90,55 @@ pub(crate) async fn download_file(
     Ok(data.boxed())
 }
 
-/// Upload `data` as a file at `url`.
+/// Read a small file into memory, returning `None` if it doesn't exist.
+pub(crate) async fn read_small_file(
+    ctx: &Context,
+    file_url: &Url,
+) -> Result<Option<Vec<u8>>> {
+    // Check whether the file exists, so that we don't mistake a missing file
+    // for some other error.
+    debug!(ctx.log(), "checking for {} using `gsutil stat`", file_url);
+    ctx.usage().record_api_call("gcs");
+    let status = Command::new("gsutil")
+        .args(["-q", "stat", file_url.as_str()])
+        .status()
+        .await
+        .context("error running gsutil")?;
+    if !status.success() {
+        return Ok(None);
+    }
+
+    debug!(ctx.log(), "reading {} using `gsutil cat`", file_url);
+    ctx.usage().record_api_call("gcs");
+    let output = Command::new("gsutil")
+        .args(["cat", file_url.as_str()])
+        .stderr(Stdio::inherit())
+        .output()
+        .await
+        .context("error running gsutil")?;
+    if output.status.success() {
+        Ok(Some(output.stdout))
+    } else {
+        Err(format_err!(
+            "could not read {}: gsutil returned error: {}",
+            file_url,
+            output.status,
+        ))
+    }
+}
+
+/// Upload `data` as a file at `url`, with the specified `object_tags`.
 pub(crate) async fn upload_file(
     ctx: Context,
     data: BoxStream<BytesMut>,
     url: &Url,
+    object_tags: &ObjectTags,
 ) -> Result<()> {
     // Run `gsutil cp - $URL` as a background process.
     debug!(ctx.log(), "uploading stream to gsutil");
+    ctx.usage().record_api_call("gcs");
     let mut child = Command::new("gsutil")
+        .args(object_tags.gsutil_header_args())
         .args(&["cp", "-", url.as_str()])
         .stdin(Stdio::piped())
         // Throw away stdout so it doesn't corrupt our output.
@@ -118,3 +162,31 @@ pub(crate) async fn upload_file(
         Err(format_err!("gsutil returned error: {}", status))
     }
 }
+
+/// Apply `object_tags` to every file under the `gs://` directory `url`.
+pub(crate) async fn set_object_tags(
+    ctx: &Context,
+    url: &Url,
+    object_tags: &ObjectTags,
+) -> Result<()> {
+    if object_tags.is_empty() {
+        return Ok(());
+    }
+    let tag_url = url.join("**")?;
+    debug!(ctx.log(), "setting object tags on {}", tag_url);
+    ctx.usage().record_api_call("gcs");
+    let status = Command::new("gsutil")
+        .args(["-m", "setmeta"])
+        .args(object_tags.gsutil_header_args())
+        .arg(tag_url.as_str())
+        // Throw away stdout so it doesn't corrupt our output.
+        .stdout(Stdio::null())
+        .status()
+        .await
+        .context("error running gsutil")?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format_err!("gsutil returned error: {}", status))
+    }
+}
@@ -1,3 +1,4 @@
 //! Interfaces to various clouds.
 
 pub(crate) mod gcloud;
+pub(crate) mod object_tags;
@@ -0,0 +1,242 @@
+//! Tags applied to the objects we write to cloud storage.
+//!
+//! These are specified using `--to-arg=object_tags=key1=value1,key2=value2`.
+//! On S3 they become object tags, and on Google Cloud Storage (which doesn't
+//! have object tags) they become custom metadata.
+//!
+//! We also use them to mark the staging objects we write inside our
+//! `--temporary` storage, so that a bucket lifecycle rule can delete them even
+//! if `dbcrossbar` crashes before cleaning up. We never mark objects written
+//! to a destination chosen by the user, even if it's under a `--temporary`
+//! prefix. On S3, we add a `dbcrossbar-temporary=true` tag, and on
+//! Google Cloud Storage, we set the object's `Custom-Time` to the time it was
+//! written.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::{fmt, iter::FromIterator, str::FromStr};
+
+use crate::common::*;
+use crate::compression::Compression;
+
+/// S3 allows at most this many tags per object.
+const MAX_TAGS: usize = 10;
+
+/// The S3 tag we use to mark temporary objects.
+const TEMPORARY_TAG: &str = "dbcrossbar-temporary";
+
+/// A list of `key=value` tags.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct ObjectTags {
+    tags: Vec<(String, String)>,
+    /// If these objects are temporary, when were they written?
+    temporary_since: Option<DateTime<Utc>>,
+}
+
+impl ObjectTags {
+    /// Do we have any tags?
+    pub(crate) fn is_empty(&self) -> bool {
+        self.tags.is_empty() && self.temporary_since.is_none()
+    }
+
+    /// If `dest_args` describe one of our own staging locations, mark objects
+    /// written there as temporary.
+    pub(crate) fn mark_if_temporary(
+        &mut self,
+        dest_args: &DestinationArguments<Verified>,
+    ) -> Result<()> {
+        if dest_args.is_temporary() {
+            self.mark_temporary(Utc::now())?;
+        }
+        Ok(())
+    }
+
+    /// Mark objects as temporary, written at `now`.
+    fn mark_temporary(&mut self, now: DateTime<Utc>) -> Result<()> {
+        if self.tags.len() >= MAX_TAGS {
+            return Err(format_err!(
+                "cannot apply more than {} object tags to temporary files",
+                MAX_TAGS - 1,
+            ));
+        }
+        self.temporary_since = Some(now);
+        Ok(())
+    }
+
+    /// Are these objects temporary?
+    pub(crate) fn is_temporary(&self) -> bool {
+        self.temporary_since.is_some()
+    }
+
+    /// Arguments which tell `gsutil` to set our tags as custom metadata. These
+    /// must be passed before the `gsutil` subcommand.
+    pub(crate) fn gsutil_header_args(&self) -> Vec<String> {
+        let mut args = Vec::with_capacity(2 * self.tags.len());
+        for (k, v) in &self.tags {
+            args.push("-h".to_owned());
+            args.push(format!("x-goog-meta-{}:{}", k, v));
+        }
+        if let Some(since) = self.temporary_since {
+            args.push("-h".to_owned());
+            args.push(format!(
+                "Custom-Time:{}",
+                since.to_rfc3339_opts(SecondsFormat::Secs, true),
+            ));
+        }
+        args
+    }
+
+    /// A value for the `--tagging` argument to `aws s3api put-object-tagging`.
+    pub(crate) fn s3_tagging_json(&self) -> String {
+        let temporary_tag = self.temporary_since.map(|_| (TEMPORARY_TAG, "true"));
+        let tag_set = self
+            .tags
+            .iter()
+            .map(|(k, v)| (&k[..], &v[..]))
+            .chain(temporary_tag)
+            .map(|(k, v)| serde_json::json!({ "Key": k, "Value": v }))
+            .collect::<Vec<_>>();
+        serde_json::json!({ "TagSet": tag_set }).to_string()
+    }
+
+    /// Destination arguments for a temporary storage location, which will
+    /// apply these tags and compress each file using `compression`.
+    pub(crate) fn temporary_dest_args(
+        tags: Option<&ObjectTags>,
+        compression: Option<Compression>,
+    ) -> DestinationArguments<Unverified> {
+        let mut driver_args = vec![];
+        if let Some(tags) = tags.filter(|tags| !tags.is_empty()) {
+            driver_args.push(("object_tags", tags.to_string()));
+        }
+        if let Some(compression) = compression {
+            driver_args.push(("compression", compression.to_string()));
+        }
+        if driver_args.is_empty() {
+            DestinationArguments::for_temporary()
+        } else {
+            DestinationArguments::for_temporary_with_driver_args(
+                DriverArguments::from_iter(driver_args),
+            )
+        }
+    }
+}
+
+impl fmt::Display for ObjectTags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, (k, v)) in self.tags.iter().enumerate() {
+            if idx > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}={}", k, v)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ObjectTags {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut tags = vec![];
+        for tag in s.split(',').filter(|t| !t.is_empty()) {
+            let mut split = tag.splitn(2, '=');
+            let key = split.next().unwrap_or_default();
+            let value = split.next().ok_or_else(|| {
+                format_err!("expected key=value tag, found {:?}", tag)
+            })?;
+            let key_is_valid = !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c));
+            if !key_is_valid {
+                return Err(format_err!("invalid object tag name {:?}", key));
+            }
+            if value.chars().any(|c| c.is_control()) {
+                return Err(format_err!("invalid object tag value {:?}", value));
+            }
+            tags.push((key.to_owned(), value.to_owned()));
+        }
+        if tags.len() > MAX_TAGS {
+            return Err(format_err!(
+                "cannot apply more than {} object tags",
+                MAX_TAGS
+            ));
+        }
+        Ok(ObjectTags {
+            tags,
+            temporary_since: None,
+        })
+    }
+}
+
+#[test]
+fn parse_object_tags() {
+    let tags = "team=data,classification=internal"
+        .parse::<ObjectTags>()
+        .unwrap();
+    assert_eq!(tags.to_string(), "team=data,classification=internal");
+    assert_eq!(
+        tags.gsutil_header_args(),
+        vec![
+            "-h",
+            "x-goog-meta-team:data",
+            "-h",
+            "x-goog-meta-classification:internal",
+        ],
+    );
+    assert_eq!(
+        tags.s3_tagging_json(),
+        r#"{"TagSet":[{"Key":"team","Value":"data"},{"Key":"classification","Value":"internal"}]}"#,
+    );
+    assert!("team".parse::<ObjectTags>().is_err());
+    assert!("=data".parse::<ObjectTags>().is_err());
+    assert!("a b=c".parse::<ObjectTags>().is_err());
+}
+
+#[test]
+fn mark_temporary_objects() {
+    // Destinations chosen by the user are never temporary.
+    let features = crate::drivers::s3::S3Locator::features();
+    let dest_args =
+        DestinationArguments::new(DriverArguments::default(), IfExists::Overwrite)
+            .verify(features)
+            .unwrap();
+    let mut tags = ObjectTags::default();
+    tags.mark_if_temporary(&dest_args).unwrap();
+    assert!(tags.is_empty());
+
+    // But our own staging locations are.
+    let dest_args = ObjectTags::temporary_dest_args(None, None)
+        .verify(features)
+        .unwrap();
+    let mut tags = ObjectTags::default();
+    tags.mark_if_temporary(&dest_args).unwrap();
+    assert!(tags.is_temporary());
+
+    let mut tags = "team=data".parse::<ObjectTags>().unwrap();
+    let now = "2020-01-02T03:04:05.678Z".parse::<DateTime<Utc>>().unwrap();
+    tags.mark_temporary(now).unwrap();
+    assert!(tags.is_temporary());
+    assert_eq!(tags.to_string(), "team=data");
+    assert_eq!(
+        tags.gsutil_header_args(),
+        vec![
+            "-h",
+            "x-goog-meta-team:data",
+            "-h",
+            "Custom-Time:2020-01-02T03:04:05Z",
+        ],
+    );
+    assert_eq!(
+        tags.s3_tagging_json(),
+        r#"{"TagSet":[{"Key":"team","Value":"data"},{"Key":"dbcrossbar-temporary","Value":"true"}]}"#,
+    );
+
+    let mut tags = (0..MAX_TAGS)
+        .map(|i| format!("k{}=v", i))
+        .collect::<Vec<_>>()
+        .join(",")
+        .parse::<ObjectTags>()
+        .unwrap();
+    assert!(tags.mark_temporary(now).is_err());
+}
@@ -0,0 +1,391 @@
+//! Cleaning up column values while copying.
+//!
+//! `cp --transform` replaces the value of a column using a small expression
+//! language, so that trivial cleanups don't require a staging table. We
+//! support:
+//!
+//! - Column names (optionally in `"double quotes"`), `'strings'`, numbers and
+//!   `NULL`.
+//! - `lower(x)`, `upper(x)`, `trim(x)`, `ltrim(x)` and `rtrim(x)`.
+//! - `replace(x, 'from', 'to')` and `substr(x, start)` or
+//!   `substr(x, start, length)`, counting characters from 1.
+//! - `nullif(x, y)`, `coalesce(x, y, ...)` and `concat(x, y, ...)`.
+//!
+//! Empty CSV cells are `NULL`, and most functions return `NULL` if any of
+//! their arguments are `NULL`. Like PostgreSQL, `concat` ignores `NULL`.
+
+use std::{fmt, io, str::FromStr};
+
+use crate::common::*;
+use crate::schema::Table;
+use crate::transform::spawn_sync_transform;
+
+/// Include our `rust-peg` grammar.
+///
+/// We disable lots of clippy warnings because this is machine-generated code.
+#[allow(clippy::all, rust_2018_idioms, elided_lifetimes_in_paths)]
+mod grammar {
+    include!(concat!(env!("OUT_DIR"), "/column_transform.rs"));
+}
+
+/// A parsed transform expression.
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Null,
+    Literal(String),
+    Column(String),
+    Call(String, Vec<Expr>),
+}
+
+impl Expr {
+    /// Make sure that we only call functions that we know about, with the
+    /// right number of arguments.
+    fn check(&self) -> Result<()> {
+        if let Expr::Call(name, args) = self {
+            let (min, max) = match Function::from_name(name)? {
+                Function::Lower
+                | Function::Upper
+                | Function::Trim
+                | Function::Ltrim
+                | Function::Rtrim => (1, Some(1)),
+                Function::NullIf => (2, Some(2)),
+                Function::Replace => (3, Some(3)),
+                Function::Substr => (2, Some(3)),
+                Function::Coalesce | Function::Concat => (1, None),
+            };
+            if args.len() < min || max.is_some_and(|max| args.len() > max) {
+                return Err(format_err!(
+                    "wrong number of arguments to {}: {}",
+                    name,
+                    args.len(),
+                ));
+            }
+            for arg in args {
+                arg.check()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Look up the columns we use in `headers`.
+    fn bind(&self, headers: &csv::StringRecord) -> Result<BoundExpr> {
+        Ok(match self {
+            Expr::Null => BoundExpr::Null,
+            Expr::Literal(value) => BoundExpr::Literal(value.to_owned()),
+            Expr::Column(name) => BoundExpr::Column(column_index(headers, name)?),
+            Expr::Call(name, args) => BoundExpr::Call(
+                Function::from_name(name)?,
+                args.iter()
+                    .map(|arg| arg.bind(headers))
+                    .collect::<Result<Vec<_>>>()?,
+            ),
+        })
+    }
+}
+
+/// A function we can call in a transform expression.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Function {
+    Lower,
+    Upper,
+    Trim,
+    Ltrim,
+    Rtrim,
+    NullIf,
+    Coalesce,
+    Replace,
+    Concat,
+    Substr,
+}
+
+impl Function {
+    /// Look up a function by its lowercase name.
+    fn from_name(name: &str) -> Result<Function> {
+        match name {
+            "lower" => Ok(Function::Lower),
+            "upper" => Ok(Function::Upper),
+            "trim" => Ok(Function::Trim),
+            "ltrim" => Ok(Function::Ltrim),
+            "rtrim" => Ok(Function::Rtrim),
+            "nullif" => Ok(Function::NullIf),
+            "coalesce" => Ok(Function::Coalesce),
+            "replace" => Ok(Function::Replace),
+            "concat" => Ok(Function::Concat),
+            "substr" => Ok(Function::Substr),
+            _ => Err(format_err!("unknown function {:?}", name)),
+        }
+    }
+}
+
+/// Replace the value of a column with the result of an expression, using
+/// the syntax `column=expr`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColumnTransform {
+    column: String,
+    expr: Expr,
+    /// The text we were parsed from, for display.
+    source: String,
+}
+
+impl ColumnTransform {
+    /// Fail if we try to transform a column that isn't in `table`.
+    pub fn check_table(&self, table: &Table) -> Result<()> {
+        if table.columns.iter().any(|c| c.name == self.column) {
+            Ok(())
+        } else {
+            Err(format_err!(
+                "cannot transform column {:?} because it isn't in the schema",
+                self.column,
+            ))
+        }
+    }
+}
+
+impl fmt::Display for ColumnTransform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl FromStr for ColumnTransform {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (column, expr) = grammar::transform(s).map_err(|err| {
+            format_err!("expected a transform of the form column=expr: {}", err)
+        })?;
+        expr.check()
+            .with_context(|_| format!("cannot parse transform {:?}", s))?;
+        Ok(ColumnTransform {
+            column,
+            expr,
+            source: s.to_owned(),
+        })
+    }
+}
+
+/// An `Expr` which refers to columns by index.
+#[derive(Clone, Debug)]
+enum BoundExpr {
+    Null,
+    Literal(String),
+    Column(usize),
+    Call(Function, Vec<BoundExpr>),
+}
+
+impl BoundExpr {
+    /// Evaluate this expression against `row`, returning `None` for `NULL`.
+    fn eval(&self, row: &csv::StringRecord) -> Result<Option<String>> {
+        let (function, args) = match self {
+            BoundExpr::Null => return Ok(None),
+            BoundExpr::Literal(value) => return Ok(Some(value.to_owned())),
+            BoundExpr::Column(idx) => {
+                return Ok(row
+                    .get(*idx)
+                    .filter(|cell| !cell.is_empty())
+                    .map(|cell| cell.to_owned()))
+            }
+            BoundExpr::Call(function, args) => (function, args),
+        };
+        let args = args
+            .iter()
+            .map(|arg| arg.eval(row))
+            .collect::<Result<Vec<_>>>()?;
+        match function {
+            Function::Coalesce => return Ok(args.into_iter().flatten().next()),
+            Function::Concat => return Ok(Some(args.into_iter().flatten().collect())),
+            Function::NullIf => {
+                return Ok(if args[0] == args[1] {
+                    None
+                } else {
+                    args[0].clone()
+                })
+            }
+            _ => {}
+        }
+        let args = match args.into_iter().collect::<Option<Vec<_>>>() {
+            Some(args) => args,
+            None => return Ok(None),
+        };
+        let value = match function {
+            Function::Lower => args[0].to_lowercase(),
+            Function::Upper => args[0].to_uppercase(),
+            Function::Trim => args[0].trim().to_owned(),
+            Function::Ltrim => args[0].trim_start().to_owned(),
+            Function::Rtrim => args[0].trim_end().to_owned(),
+            Function::Replace => args[0].replace(&args[1][..], &args[2]),
+            Function::Substr => {
+                let start = parse_integer(&args[1])?;
+                let len = match args.get(2) {
+                    Some(len) => Some(parse_integer(len)?),
+                    None => None,
+                };
+                substr(&args[0], start, len)?
+            }
+            Function::Coalesce | Function::Concat | Function::NullIf => {
+                unreachable!("handled above")
+            }
+        };
+        Ok(Some(value))
+    }
+}
+
+/// Parse an argument which should be an integer.
+fn parse_integer(s: &str) -> Result<i64> {
+    s.parse::<i64>()
+        .map_err(|_| format_err!("expected an integer, found {:?}", s))
+}
+
+/// Return `len` characters of `s` starting at the 1-based position `start`,
+/// following the rules used by PostgreSQL's `substr`.
+fn substr(s: &str, start: i64, len: Option<i64>) -> Result<String> {
+    let end = match len {
+        Some(len) if len < 0 => {
+            return Err(format_err!("negative substr length not allowed"))
+        }
+        Some(len) => Some(start.saturating_add(len)),
+        None => None,
+    };
+    Ok(s.chars()
+        .enumerate()
+        .filter(|&(i, _)| {
+            let pos = i as i64 + 1;
+            pos >= start && end.is_none_or(|end| pos < end)
+        })
+        .map(|(_, c)| c)
+        .collect())
+}
+
+/// Find the column `name` in `headers`.
+fn column_index(headers: &csv::StringRecord, name: &str) -> Result<usize> {
+    headers
+        .iter()
+        .position(|h| h == name)
+        .ok_or_else(|| format_err!("cannot find column {:?} in CSV header", name))
+}
+
+/// Apply `transforms` to each stream in `streams`, in order.
+pub fn transform_csv_columns(
+    ctx: Context,
+    transforms: Vec<ColumnTransform>,
+    streams: BoxStream<CsvStream>,
+) -> BoxStream<CsvStream> {
+    streams
+        .and_then(move |stream| {
+            let ctx = ctx.clone();
+            let transforms = transforms.clone();
+            async move {
+                let name = stream.name.clone();
+                let data = spawn_sync_transform(
+                    ctx,
+                    "transform_csv_columns".to_owned(),
+                    stream.data,
+                    move |_ctx, rdr, wtr| {
+                        transform_columns(&transforms, rdr, wtr).with_context(
+                            |_| format!("cannot transform columns in stream {}", name),
+                        )?;
+                        Ok(())
+                    },
+                )?;
+                Ok(CsvStream {
+                    name: stream.name,
+                    data,
+                })
+            }
+        })
+        .boxed()
+}
+
+/// Copy CSV data from `rdr` to `wtr`, applying `transforms` to each row. Each
+/// transform sees the output of the transforms before it.
+///
+/// This is synchronous, and should be run using `spawn_sync_transform`.
+fn transform_columns<R: Read, W: Write>(
+    transforms: &[ColumnTransform],
+    rdr: R,
+    wtr: W,
+) -> Result<()> {
+    let mut rdr = csv::Reader::from_reader(rdr);
+    let mut wtr =
+        csv::Writer::from_writer(io::BufWriter::with_capacity(BUFFER_SIZE, wtr));
+
+    let headers = rdr.headers()?.clone();
+    if headers.is_empty() {
+        // Empty streams have no rows to transform.
+        return Ok(());
+    }
+    let bound = transforms
+        .iter()
+        .map(|t| Ok((column_index(&headers, &t.column)?, t.expr.bind(&headers)?)))
+        .collect::<Result<Vec<_>>>()?;
+    wtr.write_record(&headers)?;
+    let mut row = csv::StringRecord::new();
+    let mut cells = vec![];
+    while rdr.read_record(&mut row)? {
+        for (idx, expr) in &bound {
+            let value = expr.eval(&row)?.unwrap_or_default();
+            cells.clear();
+            cells.extend(row.iter().map(|cell| cell.to_owned()));
+            cells[*idx] = value;
+            row = csv::StringRecord::from(&cells[..]);
+        }
+        wtr.write_record(&row)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[test]
+fn transforms_are_parsed_and_applied() {
+    assert!("email".parse::<ColumnTransform>().is_err());
+    assert!("email=lower(".parse::<ColumnTransform>().is_err());
+    assert!("email=shout(email)".parse::<ColumnTransform>().is_err());
+    assert!("email=lower(email, name)"
+        .parse::<ColumnTransform>()
+        .is_err());
+
+    let run = |transforms: &[&str], input: &str| -> Result<String> {
+        let transforms = transforms
+            .iter()
+            .map(|t| t.parse::<ColumnTransform>())
+            .collect::<Result<Vec<_>>>()?;
+        let mut out = vec![];
+        transform_columns(&transforms, input.as_bytes(), &mut out)?;
+        Ok(String::from_utf8(out)?)
+    };
+    assert_eq!(
+        run(
+            &["email=LOWER(email)", "name = trim(name)"],
+            "id,email,name\n1,Ann@X.COM,  Ann \n2,,\n",
+        )
+        .unwrap(),
+        "id,email,name\n1,ann@x.com,Ann\n2,,\n",
+    );
+    assert_eq!(
+        run(
+            &["name=concat(upper(substr(name, 1, 1)), substr(name, 2))"],
+            "name\nann\né\n",
+        )
+        .unwrap(),
+        "name\nAnn\nÉ\n",
+    );
+    assert_eq!(
+        run(
+            &[
+                "code=nullif(code, 'N/A')",
+                "code=coalesce(code, \"fallback\", 'none')",
+            ],
+            "code,fallback\nN/A,x\nN/A,\nab,x\n",
+        )
+        .unwrap(),
+        "code,fallback\nx,x\nnone,\nab,x\n",
+    );
+    assert_eq!(
+        run(&["phone=replace(phone, '-', '')"], "phone\n555-1212\n").unwrap(),
+        "phone\n5551212\n",
+    );
+    assert_eq!(run(&["x=NULL"], "x\n1\n").unwrap(), "x\n\"\"\n");
+    assert_eq!(run(&["x=lower(x)"], "").unwrap(), "");
+    assert!(run(&["missing=lower(x)"], "x\n1\n").is_err());
+    assert!(run(&["x=substr(x, 'a')"], "x\n1\n").is_err());
+}
@@ -0,0 +1,150 @@
+//! Compressing the files we write to cloud storage.
+//!
+//! These are specified using `--to-arg=compression=gzip` for `s3://` and
+//! `gs://`, and using `--to-arg=temporary_compression=gzip` for drivers like
+//! Redshift and BigQuery, which stage data in cloud storage before loading it.
+//! When reading from `s3://` and `gs://`, we decompress any files whose names
+//! end in `.gz`.
+
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Compression as GzLevel};
+use std::{fmt, io, str::FromStr};
+
+use crate::common::*;
+use crate::transform::spawn_sync_transform;
+
+/// How should we compress the files we write?
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Compression {
+    /// Compress using `gzip`, which is supported by both Redshift and BigQuery.
+    Gzip,
+}
+
+impl Compression {
+    /// The extension to add to the names of compressed files, including the
+    /// leading `.`.
+    pub(crate) fn file_extension(self) -> &'static str {
+        match self {
+            Compression::Gzip => ".gz",
+        }
+    }
+
+    /// Guess how a file was compressed from its name. Returns `None` for
+    /// uncompressed files.
+    pub(crate) fn for_file_name(name: &str) -> Option<Compression> {
+        if name.ends_with(Compression::Gzip.file_extension()) {
+            Some(Compression::Gzip)
+        } else {
+            None
+        }
+    }
+
+    /// Add our file extension to `name`.
+    pub(crate) fn compressed_file_name(self, name: &str) -> String {
+        format!("{}{}", name, self.file_extension())
+    }
+
+    /// The option that tells Redshift's `COPY` or `UNLOAD` how our data is
+    /// compressed.
+    pub(crate) fn redshift_copy_option(self) -> &'static str {
+        match self {
+            Compression::Gzip => "GZIP",
+        }
+    }
+
+    /// Compress `data` in a background thread.
+    pub(crate) fn compress_stream(
+        self,
+        ctx: &Context,
+        data: BoxStream<BytesMut>,
+    ) -> Result<BoxStream<BytesMut>> {
+        debug!(ctx.log(), "compressing stream with {}", self);
+        spawn_sync_transform(
+            ctx.clone(),
+            format!("compress_{}", self),
+            data,
+            move |_ctx, rdr, wtr| match self {
+                Compression::Gzip => gzip(rdr, wtr),
+            },
+        )
+    }
+
+    /// Decompress `data` in a background thread.
+    pub(crate) fn decompress_stream(
+        self,
+        ctx: &Context,
+        data: BoxStream<BytesMut>,
+    ) -> Result<BoxStream<BytesMut>> {
+        debug!(ctx.log(), "decompressing stream with {}", self);
+        spawn_sync_transform(
+            ctx.clone(),
+            format!("decompress_{}", self),
+            data,
+            move |_ctx, rdr, wtr| match self {
+                Compression::Gzip => gunzip(rdr, wtr),
+            },
+        )
+    }
+}
+
+/// Copy `rdr` to `wtr`, compressing it with `gzip`.
+///
+/// This is synchronous, and should be run using `spawn_sync_transform`.
+fn gzip<R: Read, W: Write>(mut rdr: R, wtr: W) -> Result<()> {
+    let mut encoder = GzEncoder::new(wtr, GzLevel::default());
+    io::copy(&mut rdr, &mut encoder).context("error compressing data")?;
+    encoder.finish()?.flush()?;
+    Ok(())
+}
+
+/// Copy `rdr` to `wtr`, decompressing it with `gzip`. We accept files
+/// containing several concatenated `gzip` members, as allowed by the format.
+///
+/// This is synchronous, and should be run using `spawn_sync_transform`.
+fn gunzip<R: Read, W: Write>(rdr: R, mut wtr: W) -> Result<()> {
+    let mut decoder = MultiGzDecoder::new(rdr);
+    io::copy(&mut decoder, &mut wtr).context("error decompressing data")?;
+    wtr.flush()?;
+    Ok(())
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compression::Gzip => "gzip".fmt(f),
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "gzip" => Ok(Compression::Gzip),
+            _ => Err(format_err!("expected compression `gzip`, found {:?}", s)),
+        }
+    }
+}
+
+#[test]
+fn gzip_round_trips() {
+    let input = "id,name\n1,a\n2,b\n".repeat(100);
+    let mut compressed = vec![];
+    gzip(input.as_bytes(), &mut compressed).unwrap();
+    assert!(compressed.len() < input.len());
+
+    let mut output = vec![];
+    gunzip(&compressed[..], &mut output).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), input);
+    assert!(gunzip(input.as_bytes(), &mut vec![]).is_err());
+
+    let gzip = "gzip".parse::<Compression>().unwrap();
+    assert_eq!(gzip.compressed_file_name("data.csv"), "data.csv.gz");
+    assert_eq!(gzip.to_string(), "gzip");
+    assert!("zstd".parse::<Compression>().is_err());
+    assert_eq!(
+        Compression::for_file_name("s3://bucket/0000_part_00.gz"),
+        Some(Compression::Gzip),
+    );
+    assert_eq!(Compression::for_file_name("data.csv"), None);
+}
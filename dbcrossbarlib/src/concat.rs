@@ -103,7 +103,7 @@ fn concatenate_csv_streams_strips_all_but_first_header() {
 
 /// Remove the CSV header from a CSV stream, passing everything else through
 /// untouched.
-fn strip_csv_header(
+pub(crate) fn strip_csv_header(
     ctx: Context,
     mut stream: BoxStream<BytesMut>,
 ) -> Result<BoxStream<BytesMut>> {
@@ -1,9 +1,12 @@
 //! Logging and error-handling context.
 
 use slog::{OwnedKV, SendSyncRefUnwindSafeKV};
+use std::sync::Arc;
 use tokio::process::Child;
 
 use crate::common::*;
+use crate::drivers::postgres::ClientPool;
+use crate::usage::UsageTracker;
 
 /// Context shared by our various asynchronous operations.
 #[derive(Debug, Clone)]
@@ -13,6 +16,10 @@ pub struct Context {
     /// To report asynchronous errors anywhere in the application, send them to
     /// this channel.
     error_sender: mpsc::Sender<Error>,
+    /// Resources used by this job.
+    usage: Arc<UsageTracker>,
+    /// Idle PostgreSQL connections which we can reuse.
+    postgres_pool: Arc<ClientPool>,
 }
 
 impl Context {
@@ -21,7 +28,12 @@ impl Context {
     /// fails.
     pub fn create(log: Logger) -> (Self, BoxFuture<()>) {
         let (error_sender, mut receiver) = mpsc::channel(1);
-        let context = Context { log, error_sender };
+        let context = Context {
+            log,
+            error_sender,
+            usage: Arc::new(UsageTracker::default()),
+            postgres_pool: Arc::new(ClientPool::default()),
+        };
         let worker_future = async move {
             match receiver.next().await {
                 // All senders have shut down correctly.
@@ -67,9 +79,21 @@ impl Context {
         Context {
             log: self.log.new(log_kv),
             error_sender: self.error_sender.clone(),
+            usage: self.usage.clone(),
+            postgres_pool: self.postgres_pool.clone(),
         }
     }
 
+    /// Get the resource usage tracker shared by every context for this job.
+    pub fn usage(&self) -> &Arc<UsageTracker> {
+        &self.usage
+    }
+
+    /// Idle PostgreSQL connections shared by everything using this context.
+    pub(crate) fn postgres_pool(&self) -> &Arc<ClientPool> {
+        &self.postgres_pool
+    }
+
     /// Spawn an async worker in this context, and report any errors to the
     /// future returned by `create`.
     pub fn spawn_worker<W>(&self, worker: W)
@@ -0,0 +1,136 @@
+//! Counting rows by reading them, for drivers which can't count rows for us.
+
+use crate::common::*;
+use crate::row_filter::RowFilter;
+use crate::tokio_glue::{run_sync_fn_in_background, SyncStreamReader};
+
+/// Implementation of `count` for drivers which can read data but can't count
+/// it. We download all the data and count it locally, evaluating any `--where`
+/// clause ourselves using `RowFilter`.
+pub(crate) async fn count_local_data_helper<L: LocatorStatic>(
+    ctx: Context,
+    locator: L,
+    shared_args: SharedArguments<Unverified>,
+    source_args: SourceArguments<Unverified>,
+) -> Result<usize> {
+    let (source_args, where_clause) = source_args.take_where_clause();
+    let filter = where_clause
+        .map(|where_clause| where_clause.parse::<RowFilter>())
+        .transpose()?;
+    let table = shared_args.clone().verify(L::features())?.schema().clone();
+    let max_streams = shared_args.max_streams();
+
+    let streams = locator
+        .local_data(ctx.clone(), shared_args, source_args)
+        .await?
+        .ok_or_else(|| format_err!("don't know how to read data from {}", locator))?;
+    count_csv_rows(ctx, table, filter, streams, max_streams).await
+}
+
+/// Count the rows in `streams` which match `filter`, reading up to
+/// `max_streams` streams at once.
+async fn count_csv_rows(
+    ctx: Context,
+    table: Table,
+    filter: Option<RowFilter>,
+    streams: BoxStream<CsvStream>,
+    max_streams: usize,
+) -> Result<usize> {
+    streams
+        .map_ok(move |stream| {
+            let ctx = ctx.clone();
+            let table = table.clone();
+            let filter = filter.clone();
+            async move {
+                let name = stream.name.clone();
+                debug!(ctx.log(), "counting rows in stream {}", name);
+                let rdr = SyncStreamReader::new(ctx.clone(), stream.data);
+                run_sync_fn_in_background("count".to_owned(), move || {
+                    count_csv(&name, &table, filter.as_ref(), rdr)
+                })
+                .await
+            }
+            .boxed()
+        })
+        .try_buffer_unordered(max_streams)
+        .try_fold(0, |total, count| async move { Ok(total + count) })
+        .await
+}
+
+/// Count the rows in the CSV data in `rdr` which match `filter`.
+///
+/// This is synchronous, and should be run in a background thread.
+fn count_csv<R: Read>(
+    stream: &str,
+    table: &Table,
+    filter: Option<&RowFilter>,
+    rdr: R,
+) -> Result<usize> {
+    let mut rdr = csv::Reader::from_reader(rdr);
+    let read_err = || format!("cannot read CSV from stream {}", stream);
+    let mut count = 0;
+    if let Some(filter) = filter {
+        let headers = rdr.headers().with_context(|_| read_err())?;
+        let filter = filter
+            .bind(table, headers)
+            .with_context(|_| format!("cannot filter stream {}", stream))?;
+        let mut row = csv::StringRecord::new();
+        while rdr.read_record(&mut row).with_context(|_| read_err())? {
+            if filter.matches(&row)? {
+                count += 1;
+            }
+        }
+    } else {
+        // We don't need to look at the data, so avoid checking it's UTF-8.
+        let mut row = csv::ByteRecord::new();
+        while rdr
+            .read_byte_record(&mut row)
+            .with_context(|_| read_err())?
+        {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+#[test]
+fn count_csv_rows_applies_filter() {
+    use crate::schema::{Column, DataType};
+    use crate::tokio_glue::run_futures_with_runtime;
+
+    let table = Table {
+        name: "t".to_owned(),
+        columns: vec![Column {
+            name: "id".to_owned(),
+            is_nullable: true,
+            data_type: DataType::Int32,
+            comment: None,
+            default: None,
+            collation: None,
+        }],
+        primary_key: vec![],
+        indexes: vec![],
+    };
+
+    let (ctx, worker_fut) = Context::create_for_test("count_csv_rows");
+    let cmd_fut = async move {
+        let streams = || async {
+            let streams = vec![
+                Ok(CsvStream::from_bytes(&b"id\n1\n2\n"[..]).await),
+                Ok(CsvStream::from_bytes(&b"id\n3\n\n"[..]).await),
+            ];
+            stream::iter(streams).boxed()
+        };
+        let count =
+            count_csv_rows(ctx.clone(), table.clone(), None, streams().await, 2)
+                .await?;
+        assert_eq!(count, 3);
+
+        let filter = Some("id >= 2".parse::<RowFilter>()?);
+        let count =
+            count_csv_rows(ctx.clone(), table, filter, streams().await, 2).await?;
+        assert_eq!(count, 2);
+        Ok(())
+    };
+    run_futures_with_runtime(cmd_fut.boxed(), worker_fut).unwrap();
+}
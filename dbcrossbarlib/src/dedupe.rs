@@ -0,0 +1,236 @@
+//! Removing rows with duplicate keys while copying.
+//!
+//! Many sources contain several versions of the same row, like event logs or
+//! daily snapshots which overlap. We combine all our input streams into one,
+//! and keep only a single row for each key.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    fs::File,
+    io,
+    str::FromStr,
+};
+
+use crate::common::*;
+use crate::concat::concatenate_csv_streams;
+use crate::schema::Table;
+use crate::transform::spawn_sync_transform;
+
+/// The columns which identify a row when removing duplicates.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DedupeKeys {
+    columns: Vec<String>,
+}
+
+impl DedupeKeys {
+    /// Fail if any of our key columns aren't in `table`.
+    pub fn check_table(&self, table: &Table) -> Result<()> {
+        for name in &self.columns {
+            if !table.columns.iter().any(|c| &c.name == name) {
+                return Err(format_err!(
+                    "cannot dedupe on column {:?} because it isn't in the schema",
+                    name,
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DedupeKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.columns.join(","))
+    }
+}
+
+impl FromStr for DedupeKeys {
+    type Err = Error;
+
+    /// Parse a list of the form `col1,col2`.
+    fn from_str(s: &str) -> Result<Self> {
+        let columns = s
+            .split(',')
+            .filter(|name| !name.trim().is_empty())
+            .map(|name| name.to_owned())
+            .collect::<Vec<_>>();
+        if columns.is_empty() {
+            return Err(format_err!("must dedupe on at least one column"));
+        }
+        let mut seen = HashSet::new();
+        for name in &columns {
+            if !seen.insert(name) {
+                return Err(format_err!("column {:?} is listed twice", name));
+            }
+        }
+        Ok(DedupeKeys { columns })
+    }
+}
+
+/// Which row should we keep when several rows have the same key?
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum DedupeKeep {
+    /// Keep the first row we see.
+    First,
+    /// Keep the last row we see.
+    #[default]
+    Last,
+}
+
+impl fmt::Display for DedupeKeep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DedupeKeep::First => "first".fmt(f),
+            DedupeKeep::Last => "last".fmt(f),
+        }
+    }
+}
+
+impl FromStr for DedupeKeep {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "first" => Ok(DedupeKeep::First),
+            "last" => Ok(DedupeKeep::Last),
+            _ => Err(format_err!("expected `first` or `last`, found {:?}", s)),
+        }
+    }
+}
+
+/// Combine `streams` into a single stream, keeping only one row for each value
+/// of `keys`.
+///
+/// We need to see every row before we know which ones are duplicates, so this
+/// produces a single output stream. Use `rechunk_csvs` to split it up again.
+pub fn dedupe_csv_streams(
+    ctx: Context,
+    keys: DedupeKeys,
+    keep: DedupeKeep,
+    streams: BoxStream<CsvStream>,
+) -> Result<BoxStream<CsvStream>> {
+    let ctx = ctx.child(o!("streams_transform" => "dedupe_csv_streams"));
+    let combined = concatenate_csv_streams(ctx.clone(), streams)?;
+    let data = spawn_sync_transform(
+        ctx,
+        "dedupe_csv".to_owned(),
+        combined.data,
+        move |_ctx, rdr, wtr| {
+            dedupe_csv(&keys, keep, rdr, wtr)
+                .context("cannot remove duplicate rows")?;
+            Ok(())
+        },
+    )?;
+    Ok(box_stream_once(Ok(CsvStream {
+        name: "deduped".to_owned(),
+        data,
+    })))
+}
+
+/// Copy rows from `rdr` to `wtr`, keeping only one row for each value of
+/// `keys`.
+///
+/// When keeping the last row, we spool our input to a temporary file, and only
+/// keep the keys in memory. Each row we keep is output in the position where
+/// we last saw its key.
+///
+/// This is synchronous, and should be run using `spawn_sync_transform`.
+fn dedupe_csv<R: Read, W: Write>(
+    keys: &DedupeKeys,
+    keep: DedupeKeep,
+    rdr: R,
+    wtr: W,
+) -> Result<()> {
+    let mut rdr = csv::Reader::from_reader(rdr);
+    let mut wtr =
+        csv::Writer::from_writer(io::BufWriter::with_capacity(BUFFER_SIZE, wtr));
+
+    let headers = rdr.byte_headers()?.clone();
+    if headers.is_empty() {
+        // Empty input has no rows to dedupe.
+        return Ok(());
+    }
+    let key_indices = keys
+        .columns
+        .iter()
+        .map(|name| {
+            headers
+                .iter()
+                .position(|h| h == name.as_bytes())
+                .ok_or_else(|| format_err!("cannot find dedupe column {:?}", name))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let key_for = |row: &csv::ByteRecord| -> Vec<Vec<u8>> {
+        key_indices
+            .iter()
+            .map(|&idx| row.get(idx).unwrap_or_default().to_owned())
+            .collect()
+    };
+    wtr.write_byte_record(&headers)?;
+
+    let mut row = csv::ByteRecord::new();
+    match keep {
+        DedupeKeep::First => {
+            let mut seen = HashSet::new();
+            while rdr.read_byte_record(&mut row)? {
+                if seen.insert(key_for(&row)) {
+                    wtr.write_byte_record(&row)?;
+                }
+            }
+        }
+        DedupeKeep::Last => {
+            // Spool our rows, and remember where we last saw each key.
+            let tmp_dir = tempdir::TempDir::new("dedupe")?;
+            let spool_path = tmp_dir.path().join("rows.csv");
+            let mut spool = csv::Writer::from_writer(io::BufWriter::new(
+                File::create(&spool_path)?,
+            ));
+            let mut last_seen = HashMap::new();
+            let mut row_idx = 0usize;
+            while rdr.read_byte_record(&mut row)? {
+                last_seen.insert(key_for(&row), row_idx);
+                spool.write_byte_record(&row)?;
+                row_idx += 1;
+            }
+            spool.flush()?;
+            drop(spool);
+
+            // Copy each row that we last saw at its current position.
+            let mut spool = csv::ReaderBuilder::new()
+                .has_headers(false)
+                .from_path(&spool_path)?;
+            let mut row_idx = 0usize;
+            while spool.read_byte_record(&mut row)? {
+                if last_seen.get(&key_for(&row)) == Some(&row_idx) {
+                    wtr.write_byte_record(&row)?;
+                }
+                row_idx += 1;
+            }
+        }
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[test]
+fn dedupe_keeps_first_or_last_row() {
+    let keys = "id,region".parse::<DedupeKeys>().unwrap();
+    let run = |keep: DedupeKeep, input: &str| -> Result<String> {
+        let mut out = vec![];
+        dedupe_csv(&keys, keep, input.as_bytes(), &mut out)?;
+        Ok(String::from_utf8(out)?)
+    };
+    let input = "id,region,name\n1,us,a\n2,us,b\n1,eu,c\n1,us,d\n";
+    assert_eq!(
+        run(DedupeKeep::First, input).unwrap(),
+        "id,region,name\n1,us,a\n2,us,b\n1,eu,c\n",
+    );
+    assert_eq!(
+        run(DedupeKeep::Last, input).unwrap(),
+        "id,region,name\n2,us,b\n1,eu,c\n1,us,d\n",
+    );
+    assert_eq!(run(DedupeKeep::Last, "").unwrap(), "");
+    assert!(run(DedupeKeep::First, "name\na\n").is_err());
+    assert!("".parse::<DedupeKeys>().is_err());
+    assert!("id,id".parse::<DedupeKeys>().is_err());
+}
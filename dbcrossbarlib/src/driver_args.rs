@@ -1,6 +1,6 @@
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer};
 use serde_json::{map::Entry, Map, Value};
-use std::iter::FromIterator;
+use std::{fmt::Display, iter::FromIterator, str::FromStr};
 
 use crate::common::*;
 
@@ -123,6 +123,47 @@ fn to_json_handles_nested_keys() {
     assert!(conflicting_args_2.to_json().is_err());
 }
 
+/// Driver arguments are always passed as strings. Use this with
+/// `#[serde(deserialize_with = "deserialize_from_str")]` to parse a driver
+/// argument using `FromStr`, for types like `bool` and `usize`.
+pub(crate) fn deserialize_from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let s = String::deserialize(deserializer)?;
+    s.parse::<T>().map_err(serde::de::Error::custom)
+}
+
+/// Like `deserialize_from_str`, but for optional arguments. Use this with
+/// `#[serde(default, deserialize_with = "deserialize_optional_from_str")]`.
+pub(crate) fn deserialize_optional_from_str<'de, D, T>(
+    deserializer: D,
+) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    deserialize_from_str(deserializer).map(Some)
+}
+
+#[test]
+fn deserialize_from_str_parses_bools() {
+    #[derive(Deserialize)]
+    struct Args {
+        #[serde(default, deserialize_with = "deserialize_from_str")]
+        flag: bool,
+    }
+    let args = DriverArguments::from_iter(vec![("flag", "true")]);
+    assert!(args.deserialize::<Args>().unwrap().flag);
+    let args = DriverArguments::default();
+    assert!(!args.deserialize::<Args>().unwrap().flag);
+    let args = DriverArguments::from_iter(vec![("flag", "maybe")]);
+    assert!(args.deserialize::<Args>().is_err());
+}
+
 impl<K, V> FromIterator<(K, V)> for DriverArguments
 where
     K: Into<String>,
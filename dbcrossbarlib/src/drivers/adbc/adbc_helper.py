@@ -0,0 +1,119 @@
+# Helper script for the `adbc:` driver. We run this using `python3`, because
+# the ADBC driver manager and most ADBC drivers are distributed as Python
+# packages.
+#
+# Usage:
+#
+#     python3 -c "$SCRIPT" schema DRIVER URI TABLE
+#     python3 -c "$SCRIPT" data DRIVER URI TABLE [WHERE]
+#
+# `schema` prints the table's Arrow schema as JSON. `data` prints the table's
+# rows as CSV, using dbcrossbar's interchange format.
+
+import base64
+import csv
+import datetime
+import decimal
+import importlib
+import json
+import sys
+import uuid
+
+import adbc_driver_manager.dbapi
+
+
+def driver_path(driver):
+    """Find the shared library for `driver`, which may be the name of a Python
+    package like `adbc_driver_sqlite` or a path to a shared library."""
+    try:
+        module = importlib.import_module(driver)
+    except ImportError:
+        return driver
+    return module._driver_path()
+
+
+def describe_field(field):
+    return {
+        "name": field.name,
+        "type": describe_type(field.type),
+        "nullable": field.nullable,
+    }
+
+
+def describe_type(ty):
+    import pyarrow.types as t
+
+    if t.is_list(ty) or t.is_large_list(ty) or t.is_fixed_size_list(ty):
+        return {"list": describe_field(ty.value_field)}
+    if t.is_struct(ty):
+        return {"struct": [describe_field(ty.field(i)) for i in range(ty.num_fields)]}
+    if t.is_dictionary(ty):
+        return describe_type(ty.value_type)
+    return str(ty)
+
+
+def json_default(value):
+    if isinstance(value, (bytes, bytearray)):
+        return base64.b64encode(value).decode("ascii")
+    if isinstance(value, decimal.Decimal):
+        return format(value, "f")
+    if isinstance(value, (datetime.date, datetime.time, uuid.UUID)):
+        return csv_cell(value)
+    raise TypeError("cannot convert %r to JSON" % (value,))
+
+
+def csv_cell(value):
+    if value is None:
+        return ""
+    if isinstance(value, bool):
+        return "t" if value else "f"
+    if isinstance(value, (bytes, bytearray)):
+        return base64.b64encode(value).decode("ascii")
+    if isinstance(value, decimal.Decimal):
+        return format(value, "f")
+    if isinstance(value, datetime.datetime):
+        if value.tzinfo is not None:
+            value = value.astimezone(datetime.timezone.utc).replace(tzinfo=None)
+            return value.isoformat() + "Z"
+        return value.isoformat()
+    if isinstance(value, (datetime.date, datetime.time)):
+        return value.isoformat()
+    if isinstance(value, (list, dict)):
+        return json.dumps(value, default=json_default, separators=(",", ":"))
+    return str(value)
+
+
+def quote_table_name(table):
+    return ".".join('"' + part.replace('"', '""') + '"' for part in table.split("."))
+
+
+def main(command, driver, uri, table, where=None):
+    conn = adbc_driver_manager.dbapi.connect(
+        driver=driver_path(driver), db_kwargs={"uri": uri}
+    )
+    try:
+        if command == "schema":
+            parts = table.split(".")
+            schema = conn.adbc_get_table_schema(
+                parts[-1], db_schema_filter=".".join(parts[:-1]) or None
+            )
+            json.dump([describe_field(f) for f in schema], sys.stdout)
+        elif command == "data":
+            sql = "SELECT * FROM " + quote_table_name(table)
+            if where:
+                sql += " WHERE " + where
+            cursor = conn.cursor()
+            cursor.execute(sql)
+            reader = cursor.fetch_record_batch()
+            wtr = csv.writer(sys.stdout, lineterminator="\n")
+            wtr.writerow(reader.schema.names)
+            for batch in reader:
+                for row in batch.to_pylist():
+                    wtr.writerow([csv_cell(v) for v in row.values()])
+        else:
+            raise ValueError("unknown command %r" % (command,))
+    finally:
+        conn.close()
+
+
+main(*sys.argv[1:])
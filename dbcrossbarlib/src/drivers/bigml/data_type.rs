@@ -19,17 +19,27 @@ impl OptypeExt for Optype {
         match data_type {
             DataType::Array(_) => Ok(Optype::Text),
             DataType::Bool => Ok(Optype::Categorical),
+            DataType::Bytes => Ok(Optype::Text),
+            DataType::Cidr => Ok(Optype::Text),
             DataType::Date => Ok(Optype::DateTime),
-            DataType::Decimal => Ok(Optype::Numeric),
+            DataType::Decimal | DataType::DecimalWithPrecision(_) => {
+                Ok(Optype::Numeric)
+            }
             DataType::Float32 => Ok(Optype::Numeric),
             DataType::Float64 => Ok(Optype::Numeric),
             DataType::GeoJson(_) => Ok(Optype::Text),
+            DataType::Inet => Ok(Optype::Text),
             DataType::Int16 => Ok(Optype::Numeric),
             DataType::Int32 => Ok(Optype::Numeric),
             DataType::Int64 => Ok(Optype::Numeric),
+            DataType::Interval => Ok(Optype::Text),
             DataType::Json => Ok(Optype::Text),
+            DataType::MacAddr => Ok(Optype::Text),
+            DataType::OneOf(_) => Ok(Optype::Categorical),
             DataType::Other(_) => Ok(Optype::Text),
-            DataType::Text => Ok(optype_for_text),
+            DataType::Struct(_) => Ok(Optype::Text),
+            DataType::Text | DataType::TextWithMaxLength(_) => Ok(optype_for_text),
+            DataType::Time => Ok(Optype::Text),
             DataType::TimestampWithoutTimeZone => Ok(Optype::DateTime),
             DataType::TimestampWithTimeZone => Ok(Optype::DateTime),
             DataType::Uuid => Ok(Optype::Text),
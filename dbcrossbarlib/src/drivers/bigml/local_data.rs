@@ -23,6 +23,7 @@ pub(crate) async fn local_data_helper(
 
     let creds = BigMlCredentials::try_default()?;
     let client = creds.client()?;
+    ctx.usage().record_api_call("bigml");
     let response = client.download(&id).await?;
     let csv_stream =
         CsvStream::from_http_response(strip_id_prefix(&id).to_owned(), response)?;
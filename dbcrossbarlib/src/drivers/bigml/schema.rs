@@ -6,12 +6,13 @@ use crate::schema::{Column, Table};
 
 /// Implementation of `schema`, but as a real `async` function.
 pub(crate) async fn schema_helper(
-    _ctx: Context,
+    ctx: Context,
     source: BigMlLocator,
 ) -> Result<Option<Table>> {
     let creds = BigMlCredentials::try_default()?;
     let client = creds.client()?;
     if let BigMlAction::ReadDataset(id) = &source.action {
+        ctx.usage().record_api_call("bigml");
         let dataset = client.fetch(id).await?;
         let fields = &dataset.fields;
         if fields.is_empty() {
@@ -31,12 +32,16 @@ pub(crate) async fn schema_helper(
                 is_nullable: true,
                 data_type: field.optype.to_data_type()?,
                 comment: None,
+                default: None,
+                collation: None,
             });
         }
 
         Ok(Some(Table {
             name: "dataset".to_owned(),
             columns,
+            primary_key: vec![],
+            indexes: vec![],
         }))
     } else {
         Err(format_err!("cannot read schema from {}", source))
@@ -8,8 +8,10 @@ use chrono::{Duration, Utc};
 use serde::Deserialize;
 
 use super::{source::SourceExt, BigMlCredentials, BigMlLocator, CreateOptions};
+use crate::clouds::object_tags::ObjectTags;
 use crate::common::*;
 use crate::concat::concatenate_csv_streams;
+use crate::driver_args::deserialize_optional_from_str;
 use crate::drivers::s3::{find_s3_temp_dir, sign_s3_url, AwsCredentials};
 
 /// Parsed version of `--to-arg` values.
@@ -25,6 +27,10 @@ struct BigMlDestinationArguments {
     /// Tags to apply to the resources we create.
     #[serde(default)]
     tags: Vec<String>,
+
+    /// Tags to set on the temporary files we upload to S3.
+    #[serde(default, deserialize_with = "deserialize_optional_from_str")]
+    object_tags: Option<ObjectTags>,
 }
 
 /// Implementation of `write_local_data`, but as a real `async` function.
@@ -76,7 +82,10 @@ pub(crate) async fn write_local_data_helper(
             // write them to S3 and return a `BoxStream<BoxFuture<BoxLocator>>>`,
             // that is, a stream a futures yielding the S3 locators where we put
             // our data on S3.
-            let s3_dest_args = DestinationArguments::for_temporary();
+            let s3_dest_args = ObjectTags::temporary_dest_args(
+                bigml_dest_args.object_tags.as_ref(),
+                None,
+            );
             let s3_locator_stream: BoxStream<BoxFuture<BoxLocator>> = s3_temp
                 .write_local_data(ctx.clone(), data, shared_args, s3_dest_args)
                 .await?;
@@ -124,6 +133,7 @@ pub(crate) async fn write_local_data_helper(
                     }
                     args.tags = bigml_dest_args.tags.clone();
                     let client = creds.client()?;
+                    ctx.usage().record_api_call("bigml");
                     let source = client.create(&args).await?;
 
                     let ctx = ctx.child(o!("bigml_source" => source.id().to_string()));
@@ -177,6 +187,7 @@ pub(crate) async fn write_local_data_helper(
             // Wait for our `source` to finish being created.
             trace!(ctx.log(), "waiting for source to be ready");
             let client = creds.client()?;
+            ctx.usage().record_api_call("bigml");
             source = client.wait(source.id()).await?;
 
             // Fix data types.
@@ -184,8 +195,10 @@ pub(crate) async fn write_local_data_helper(
                 bigml_dest_args.optype_for_text.unwrap_or(Optype::Text);
             let update = source.calculate_column_type_fix(&schema, optype_for_text)?;
             trace!(ctx.log(), "updating source with {:?}", update);
+            ctx.usage().record_api_call("bigml");
             client.update(&source.id(), &update).await?;
             trace!(ctx.log(), "waiting for source to be ready (again)");
+            ctx.usage().record_api_call("bigml");
             source = client.wait(source.id()).await?;
 
             // Optionally convert our source to a dataset.
@@ -200,6 +213,7 @@ pub(crate) async fn write_local_data_helper(
                     args.name = Some(name.to_owned());
                 }
                 args.tags = bigml_dest_args.tags.clone();
+                ctx.usage().record_api_call("bigml");
                 let dataset = client.create_and_wait(&args).await?;
                 debug!(ctx.log(), "converted to {}", dataset.id().to_owned());
                 Ok(BigMlLocator::read_dataset(dataset.id().to_owned()).boxed())
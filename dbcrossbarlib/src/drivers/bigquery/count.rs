@@ -23,7 +23,7 @@ pub(crate) async fn count_helper(
     let schema = shared_args.schema();
 
     // Construct a `BqTable` describing our source table.
-    let table_name = locator.as_table_name().to_owned();
+    let table_name = locator.as_table_name()?.to_owned();
     let table = BqTable::for_table_name_and_columns(
         table_name,
         &schema.columns,
@@ -32,7 +32,7 @@ pub(crate) async fn count_helper(
 
     // Generate our count SQL.
     let mut count_sql_data = vec![];
-    table.write_count_sql(&source_args, &mut count_sql_data)?;
+    table.write_count_sql(&source_args, locator.as_of(), &mut count_sql_data)?;
     let count_sql = String::from_utf8(count_sql_data).expect("should always be UTF-8");
     debug!(ctx.log(), "count SQL: {}", count_sql);
 
@@ -0,0 +1,207 @@
+//! Convert CSV data to newline-delimited JSON that BigQuery can load.
+//!
+//! BigQuery can't load `ARRAY` or `STRUCT` columns from CSV files, so normally
+//! we load them as `STRING` into a temporary table and fix them up using SQL.
+//! But BigQuery _can_ load them directly from newline-delimited JSON, which
+//! allows us to skip the temporary table entirely.
+
+use csv;
+use serde_json::{Map, Value};
+use std::{convert::TryFrom, io};
+
+use crate::common::*;
+use crate::drivers::bigquery_shared::{
+    BqColumn, BqDataType, BqNonArrayDataType, ColumnName,
+};
+use crate::from_csv_cell::FromCsvCell;
+
+/// How should we represent a CSV cell in our JSON output?
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum JsonCellType {
+    /// Parse the cell as a JSON array.
+    Array,
+    /// Parse the cell as a boolean.
+    Bool,
+    /// Parse the cell as a JSON object.
+    Object,
+    /// Pass the cell through as a string, and let BigQuery parse it.
+    String,
+}
+
+impl JsonCellType {
+    /// Choose how to represent `col` as JSON.
+    fn for_column(col: &BqColumn) -> Result<JsonCellType> {
+        match col.bq_data_type()? {
+            BqDataType::Array(ty @ BqNonArrayDataType::Struct(_))
+                if ty.is_json_loadable_struct() =>
+            {
+                Ok(JsonCellType::Array)
+            }
+            BqDataType::NonArray(ty @ BqNonArrayDataType::Struct(_))
+                if ty.is_json_loadable_struct() =>
+            {
+                Ok(JsonCellType::Object)
+            }
+            BqDataType::Array(BqNonArrayDataType::Struct(_))
+            | BqDataType::NonArray(BqNonArrayDataType::Struct(_)) => Err(format_err!(
+                "cannot load column {} into BigQuery as JSON yet",
+                col.name,
+            )),
+            BqDataType::Array(_) => Ok(JsonCellType::Array),
+            BqDataType::NonArray(BqNonArrayDataType::Bool) => Ok(JsonCellType::Bool),
+            BqDataType::NonArray(_) => Ok(JsonCellType::String),
+        }
+    }
+
+    /// Convert `cell` into a JSON value.
+    fn to_json(self, cell: &str) -> Result<Value> {
+        match self {
+            JsonCellType::Array => {
+                let value = serde_json::from_str::<Value>(cell)?;
+                if value.is_array() {
+                    Ok(value)
+                } else {
+                    Err(format_err!("expected JSON array, found {:?}", cell))
+                }
+            }
+            JsonCellType::Bool => Ok(Value::Bool(bool::from_csv_cell(cell)?)),
+            JsonCellType::Object => {
+                let value = serde_json::from_str::<Value>(cell)?;
+                if value.is_object() {
+                    Ok(value)
+                } else {
+                    Err(format_err!("expected JSON object, found {:?}", cell))
+                }
+            }
+            JsonCellType::String => Ok(Value::String(cell.to_owned())),
+        }
+    }
+}
+
+/// Read CSV data, and write newline-delimited JSON data, using `columns` to
+/// figure out how to interpret the CSV data. These columns should have been
+/// created with `Usage::FinalTable`.
+///
+/// Like `copy_csv_to_pg_binary`, this is synchronous and should be run in its
+/// own thread.
+pub(crate) fn copy_csv_to_bigquery_json(
+    columns: &[BqColumn],
+    rdr: Box<dyn Read>,
+    wtr: Box<dyn Write>,
+) -> Result<()> {
+    // Set up wrappers for `rdr` and `wtr`, handling CSV parsing and buffering.
+    let mut rdr = csv::Reader::from_reader(rdr);
+    let mut wtr = io::BufWriter::with_capacity(BUFFER_SIZE, wtr);
+
+    // Check to make sure our CSV headers and table column names match.
+    let headers = rdr.headers()?;
+    if headers.len() != columns.len() {
+        return Err(format_err!(
+            "CSV file has {} columns, but schema has {}",
+            headers.len(),
+            columns.len(),
+        ));
+    }
+    for (idx, (hdr, col)) in headers.iter().zip(columns.iter()).enumerate() {
+        if ColumnName::try_from(hdr)? != col.name {
+            return Err(format_err!(
+                "CSV file has column {} at position {}, but schema has {}",
+                hdr,
+                idx,
+                col.name,
+            ));
+        }
+    }
+    let cell_types = columns
+        .iter()
+        .map(JsonCellType::for_column)
+        .collect::<Result<Vec<_>>>()?;
+
+    // Convert each row. Empty cells are `NULL`, so we leave them out.
+    for row in rdr.records() {
+        let row = row?;
+        let mut obj = Map::with_capacity(columns.len());
+        for ((cell, col), cell_type) in
+            row.iter().zip(columns.iter()).zip(cell_types.iter())
+        {
+            if cell.is_empty() {
+                continue;
+            }
+            let value = cell_type
+                .to_json(cell)
+                .with_context(|_| format!("could not convert column {}", col.name))?;
+            obj.insert(col.name.as_str().to_owned(), value);
+        }
+        serde_json::to_writer(&mut wtr, &Value::Object(obj))?;
+        wtr.write_all(b"\n")?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[test]
+fn copy_csv_to_bigquery_json_converts_cells() {
+    use crate::drivers::bigquery_shared::{BqTable, TableName, Usage};
+    use crate::schema::{Column, DataType, StructField};
+
+    let columns = vec![
+        Column {
+            name: "id".to_owned(),
+            is_nullable: false,
+            data_type: DataType::Int64,
+            comment: None,
+            default: None,
+            collation: None,
+        },
+        Column {
+            name: "ok".to_owned(),
+            is_nullable: true,
+            data_type: DataType::Bool,
+            comment: None,
+            default: None,
+            collation: None,
+        },
+        Column {
+            name: "tags".to_owned(),
+            is_nullable: true,
+            data_type: DataType::Array(Box::new(DataType::Text)),
+            comment: None,
+            default: None,
+            collation: None,
+        },
+        Column {
+            name: "point".to_owned(),
+            is_nullable: true,
+            data_type: DataType::Struct(vec![StructField {
+                name: "x".to_owned(),
+                is_nullable: true,
+                data_type: DataType::Float64,
+            }]),
+            comment: None,
+            default: None,
+            collation: None,
+        },
+    ];
+    let table = BqTable::for_table_name_and_columns(
+        "project:dataset.table".parse::<TableName>().unwrap(),
+        &columns,
+        Usage::FinalTable,
+    )
+    .unwrap();
+
+    let input =
+        "id,ok,tags,point\n1,t,\"[\"\"a\"\",\"\"b\"\"]\",\"{\"\"x\"\":1.5}\"\n2,,,\n";
+    let tmp_dir = tempdir::TempDir::new("csv_to_json").unwrap();
+    let output_path = tmp_dir.path().join("output.json");
+    let output = std::fs::File::create(&output_path).unwrap();
+    copy_csv_to_bigquery_json(
+        &table.columns,
+        Box::new(input.as_bytes()),
+        Box::new(output),
+    )
+    .unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&output_path).unwrap(),
+        "{\"id\":\"1\",\"ok\":true,\"point\":{\"x\":1.5},\"tags\":[\"a\",\"b\"]}\n{\"id\":\"2\"}\n",
+    );
+}
@@ -0,0 +1,71 @@
+//! Implementation of `head` for BigQuery.
+
+use serde_json::{Map, Value};
+
+use crate::clouds::gcloud::bigquery;
+use crate::common::*;
+use crate::drivers::{
+    bigquery::BigQueryLocator,
+    bigquery_shared::{BqTable, Usage},
+};
+
+/// Implementation of `head`, but as a real `async` function.
+pub(crate) async fn head_helper(
+    ctx: Context,
+    locator: BigQueryLocator,
+    shared_args: SharedArguments<Unverified>,
+    source_args: SourceArguments<Unverified>,
+    limit: usize,
+) -> Result<Option<Preview>> {
+    let shared_args = shared_args.verify(BigQueryLocator::features())?;
+    let source_args = source_args.verify(BigQueryLocator::features())?;
+
+    // Construct a `BqTable` describing our source table.
+    let table_name = locator.as_table_name()?.to_owned();
+    let table = BqTable::for_table_name_and_columns(
+        table_name.clone(),
+        &shared_args.schema().columns,
+        Usage::FinalTable,
+    )?;
+
+    // If we can, read the rows directly from the table. Otherwise, we need to
+    // run a query, which BigQuery will charge us for.
+    let rows = if source_args.where_clause().is_none() && locator.as_of().is_none() {
+        bigquery::head::<Map<String, Value>>(&ctx, &table_name, limit).await?
+    } else {
+        let mut sql_data = vec![];
+        table.write_export_sql(&source_args, locator.as_of(), &mut sql_data)?;
+        write!(&mut sql_data, " LIMIT {}", limit)?;
+        let sql = String::from_utf8(sql_data).expect("should always be UTF-8");
+        debug!(ctx.log(), "head SQL: {}", sql);
+        bigquery::query_all::<Map<String, Value>>(&ctx, locator.project(), &sql)
+            .await?
+    };
+
+    // Arrange our cells in the same order as our columns.
+    let columns = table
+        .columns
+        .iter()
+        .map(|c| c.name.as_str().to_owned())
+        .collect::<Vec<_>>();
+    let rows = rows
+        .iter()
+        .map(|row| {
+            columns
+                .iter()
+                .map(|name| json_to_cell(row.get(name).unwrap_or(&Value::Null)))
+                .collect::<Result<Vec<_>>>()
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Some(Preview { columns, rows }))
+}
+
+/// Convert a JSON value returned by `bq` into a cell. `bq` returns most scalar
+/// values as strings, but structs and arrays are returned as JSON.
+fn json_to_cell(value: &Value) -> Result<String> {
+    match value {
+        Value::Null => Ok(String::new()),
+        Value::String(s) => Ok(s.to_owned()),
+        other => Ok(serde_json::to_string(other)?),
+    }
+}
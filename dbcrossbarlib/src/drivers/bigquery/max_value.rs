@@ -0,0 +1,47 @@
+//! Implementation of `max_value`, but as a real `async` function.
+
+use serde::Deserialize;
+use std::convert::TryFrom;
+
+use crate::clouds::gcloud::bigquery;
+use crate::common::*;
+use crate::drivers::{
+    bigquery::BigQueryLocator,
+    bigquery_shared::{BqTable, ColumnName, Usage},
+};
+
+/// Implementation of `max_value`, but as a real `async` function.
+pub(crate) async fn max_value_helper(
+    ctx: Context,
+    locator: BigQueryLocator,
+    shared_args: SharedArguments<Unverified>,
+    source_args: SourceArguments<Unverified>,
+    column: String,
+) -> Result<Option<String>> {
+    let shared_args = shared_args.verify(BigQueryLocator::features())?;
+    let source_args = source_args.verify(BigQueryLocator::features())?;
+
+    // Construct a `BqTable` describing our source table.
+    let table_name = locator.as_table_name()?.to_owned();
+    let table = BqTable::for_table_name_and_columns(
+        table_name,
+        &shared_args.schema().columns,
+        Usage::FinalTable,
+    )?;
+
+    // Generate our max SQL.
+    let column = ColumnName::try_from(&column)?;
+    let mut sql_data = vec![];
+    table.write_max_sql(&source_args, locator.as_of(), &column, &mut sql_data)?;
+    let sql = String::from_utf8(sql_data).expect("should always be UTF-8");
+    debug!(ctx.log(), "max SQL: {}", sql);
+
+    // Run our query.
+    #[derive(Deserialize)]
+    struct MaxRow {
+        max_value: Option<String>,
+    }
+    Ok(bigquery::query_one::<MaxRow>(&ctx, locator.project(), &sql)
+        .await?
+        .max_value)
+}
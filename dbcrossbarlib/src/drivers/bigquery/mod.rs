@@ -1,44 +1,98 @@
 //! Driver for working with BigQuery.
 
+use chrono::{DateTime, SecondsFormat, Utc};
 use std::{fmt, str::FromStr};
 
+use crate::clouds::gcloud::bigquery;
 use crate::common::*;
-use crate::drivers::{bigquery_shared::TableName, gs::GsLocator};
+use crate::drivers::{
+    bigquery_shared::{DatasetName, TableName},
+    gs::GsLocator,
+};
 
 mod count;
+mod csv_to_json;
+mod head;
 mod local_data;
+mod max_value;
 mod schema;
 mod write_local_data;
 mod write_remote_data;
 
 use self::count::count_helper;
+use self::head::head_helper;
 use self::local_data::local_data_helper;
+use self::max_value::max_value_helper;
 use self::schema::schema_helper;
 use self::write_local_data::write_local_data_helper;
-use self::write_remote_data::write_remote_data_helper;
+use self::write_remote_data::{plan_write_helper, write_remote_data_helper};
 
-/// A locator for a BigQuery table.
+/// What does a `BigQueryLocator` point to?
+#[derive(Debug, Clone)]
+enum Target {
+    /// A single table.
+    Table(TableName),
+    /// Every table in a dataset.
+    Dataset(DatasetName),
+}
+
+/// A locator for a BigQuery table, or for all the tables in a dataset.
 #[derive(Debug, Clone)]
 pub struct BigQueryLocator {
-    /// The table pointed to by this locator.
-    table_name: TableName,
+    /// The table or dataset pointed to by this locator.
+    target: Target,
+    /// If present, read the table as it existed at the specified time, using
+    /// BigQuery's "time travel" support. Specified as `table@TIMESTAMP`.
+    as_of: Option<DateTime<Utc>>,
 }
 
 impl BigQueryLocator {
-    /// The table name for this locator.
-    pub(crate) fn as_table_name(&self) -> &TableName {
-        &self.table_name
+    /// The table name for this locator. Fails if this locator points to an
+    /// entire dataset.
+    pub(crate) fn as_table_name(&self) -> Result<&TableName> {
+        match &self.target {
+            Target::Table(table_name) => Ok(table_name),
+            Target::Dataset(_) => Err(format_err!(
+                "{} is a dataset, but we expected a table",
+                self,
+            )),
+        }
+    }
+
+    /// If this locator points to a historical snapshot of a table, return the
+    /// time of that snapshot.
+    pub(crate) fn as_of(&self) -> Option<&DateTime<Utc>> {
+        self.as_of.as_ref()
+    }
+
+    /// Fail if this locator can't be used as a destination.
+    pub(crate) fn check_writable(&self) -> Result<()> {
+        if self.as_of.is_some() {
+            Err(format_err!("cannot write to historical snapshot {}", self))
+        } else {
+            self.as_table_name().map(|_| ())
+        }
     }
 
     /// This locator's BigQuery project.
     pub(crate) fn project(&self) -> &str {
-        self.table_name.project()
+        match &self.target {
+            Target::Table(table_name) => table_name.project(),
+            Target::Dataset(dataset_name) => dataset_name.project(),
+        }
     }
 }
 
 impl fmt::Display for BigQueryLocator {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "bigquery:{}", self.table_name)
+        match &self.target {
+            Target::Table(table_name) => write!(f, "bigquery:{}", table_name)?,
+            Target::Dataset(dataset_name) => write!(f, "bigquery:{}", dataset_name)?,
+        }
+        if let Some(as_of) = &self.as_of {
+            write!(f, "@{}", as_of.to_rfc3339_opts(SecondsFormat::AutoSi, true))?;
+        }
+        Ok(())
     }
 }
 
@@ -49,8 +103,48 @@ impl FromStr for BigQueryLocator {
         if !s.starts_with(Self::scheme()) {
             return Err(format_err!("expected a bigquery: locator, found {}", s));
         }
-        let table_name = s[Self::scheme().len()..].parse()?;
-        Ok(BigQueryLocator { table_name })
+        let mut name = &s[Self::scheme().len()..];
+        let mut as_of = None;
+        if let Some(idx) = name.find('@') {
+            let timestamp = DateTime::parse_from_rfc3339(&name[idx + 1..])
+                .with_context(|_| format!("could not parse timestamp in {}", s))?;
+            as_of = Some(timestamp.with_timezone(&Utc));
+            name = &name[..idx];
+        }
+        let target = if name.contains('.') {
+            Target::Table(name.parse()?)
+        } else if as_of.is_some() {
+            return Err(format_err!("cannot use @TIMESTAMP with a dataset: {}", s));
+        } else {
+            Target::Dataset(name.parse()?)
+        };
+        Ok(BigQueryLocator { target, as_of })
+    }
+}
+
+/// Implementation of `list_tables`, but as a real `async` function.
+async fn list_tables_helper(
+    ctx: Context,
+    locator: BigQueryLocator,
+) -> Result<Option<Vec<(String, BoxLocator)>>> {
+    match &locator.target {
+        Target::Table(_) => Ok(None),
+        Target::Dataset(dataset_name) => {
+            let table_names = bigquery::list_tables(&ctx, dataset_name).await?;
+            Ok(Some(
+                table_names
+                    .into_iter()
+                    .map(|table_name| {
+                        let name = table_name.table().to_owned();
+                        let locator = BigQueryLocator {
+                            target: Target::Table(table_name),
+                            as_of: None,
+                        };
+                        (name, locator.boxed())
+                    })
+                    .collect(),
+            ))
+        }
     }
 }
 
@@ -72,6 +166,65 @@ impl Locator for BigQueryLocator {
         count_helper(ctx, self.to_owned(), shared_args, source_args).boxed()
     }
 
+    fn max_value(
+        &self,
+        ctx: Context,
+        shared_args: SharedArguments<Unverified>,
+        source_args: SourceArguments<Unverified>,
+        column: &str,
+    ) -> BoxFuture<Option<String>> {
+        max_value_helper(
+            ctx,
+            self.to_owned(),
+            shared_args,
+            source_args,
+            column.to_owned(),
+        )
+        .boxed()
+    }
+
+    fn head(
+        &self,
+        ctx: Context,
+        shared_args: SharedArguments<Unverified>,
+        source_args: SourceArguments<Unverified>,
+        limit: usize,
+    ) -> BoxFuture<Option<Preview>> {
+        head_helper(ctx, self.to_owned(), shared_args, source_args, limit).boxed()
+    }
+
+    fn remove(&self, ctx: Context) -> BoxFuture<()> {
+        let source = self.to_owned();
+        async move {
+            source.check_writable()?;
+            bigquery::drop_table_if_exists(&ctx, source.as_table_name()?).await
+        }
+        .boxed()
+    }
+
+    fn table_locator(&self, name: &str) -> Result<Option<BoxLocator>> {
+        match &self.target {
+            Target::Table(_) => Ok(None),
+            Target::Dataset(dataset_name) => {
+                // BigQuery table names can't contain `.`, so turn PostgreSQL
+                // names like `schema.table` into `schema_table`.
+                let table_name = dataset_name.table_name(&name.replace('.', "_"));
+                let locator = BigQueryLocator {
+                    target: Target::Table(table_name),
+                    as_of: None,
+                };
+                Ok(Some(locator.boxed()))
+            }
+        }
+    }
+
+    fn list_tables(
+        &self,
+        ctx: Context,
+    ) -> BoxFuture<Option<Vec<(String, BoxLocator)>>> {
+        list_tables_helper(ctx, self.to_owned()).boxed()
+    }
+
     fn local_data(
         &self,
         ctx: Context,
@@ -116,6 +269,17 @@ impl Locator for BigQueryLocator {
         )
         .boxed()
     }
+
+    fn plan_write(
+        &self,
+        _ctx: Context,
+        shared_args: SharedArguments<Unverified>,
+        dest_args: DestinationArguments<Unverified>,
+        remote_source: Option<String>,
+    ) -> BoxFuture<Option<Vec<String>>> {
+        plan_write_helper(self.to_owned(), shared_args, dest_args, remote_source)
+            .boxed()
+    }
 }
 
 impl LocatorStatic for BigQueryLocator {
@@ -128,13 +292,17 @@ impl LocatorStatic for BigQueryLocator {
             locator: LocatorFeatures::Schema
                 | LocatorFeatures::LocalData
                 | LocatorFeatures::WriteLocalData
-                | LocatorFeatures::Count,
+                | LocatorFeatures::Count
+                | LocatorFeatures::Remove,
             write_schema_if_exists: EnumSet::empty(),
-            source_args: SourceArgumentsFeatures::WhereClause.into(),
-            dest_args: EnumSet::empty(),
+            source_args: SourceArgumentsFeatures::WhereClause
+                | SourceArgumentsFeatures::Limit
+                | SourceArgumentsFeatures::OrderBy,
+            dest_args: DestinationArgumentsFeatures::DriverArgs.into(),
             dest_if_exists: IfExistsFeatures::Overwrite
                 | IfExistsFeatures::Append
-                | IfExistsFeatures::Upsert,
+                | IfExistsFeatures::Upsert
+                | IfExistsFeatures::Merge,
             _placeholder: (),
         }
     }
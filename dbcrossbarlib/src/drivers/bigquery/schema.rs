@@ -10,6 +10,6 @@ pub(crate) async fn schema_helper(
     ctx: Context,
     source: BigQueryLocator,
 ) -> Result<Option<Table>> {
-    let bq_table = BqTable::read_from_table(&ctx, &source.table_name).await?;
+    let bq_table = BqTable::read_from_table(&ctx, source.as_table_name()?).await?;
     Ok(Some(bq_table.to_table()?))
 }
@@ -1,8 +1,27 @@
 //! Implementation of `write_local_data` for BigQuery.
 
+use super::{
+    csv_to_json::copy_csv_to_bigquery_json,
+    write_remote_data::{
+        add_missing_columns, warn_about_projected_geometry,
+        BigQueryDestinationArguments,
+    },
+};
+use crate::clouds::{
+    gcloud::{
+        bigquery::{self, LoadFormat},
+        storage,
+    },
+    object_tags::ObjectTags,
+};
 use crate::common::*;
-use crate::drivers::{bigquery::BigQueryLocator, gs::find_gs_temp_dir};
-use crate::tokio_glue::ConsumeWithParallelism;
+use crate::drivers::{
+    bigquery::BigQueryLocator,
+    bigquery_shared::{BqTable, Usage},
+    gs::{find_gs_temp_dir, prepare_as_destination_helper, GsLocator},
+};
+use crate::parallelism::BufferWithParallelism;
+use crate::transform::spawn_sync_transform;
 
 /// Implementation of `write_local_data`, but as a real `async` function.
 pub(crate) async fn write_local_data_helper(
@@ -12,25 +31,66 @@ pub(crate) async fn write_local_data_helper(
     shared_args: SharedArguments<Unverified>,
     dest_args: DestinationArguments<Unverified>,
 ) -> Result<BoxStream<BoxFuture<BoxLocator>>> {
+    // Make sure we can actually write to `dest` before we upload anything.
+    dest.check_writable()?;
+
     // Build a temporary location.
     let shared_args_v = shared_args.clone().verify(BigQueryLocator::features())?;
     let gs_temp = find_gs_temp_dir(shared_args_v.temporary_storage())?;
-    let gs_dest_args = DestinationArguments::for_temporary();
+    let dest_args_v = dest_args.clone().verify(BigQueryLocator::features())?;
+    let bq_dest_args = dest_args_v
+        .driver_args()
+        .deserialize::<BigQueryDestinationArguments>()
+        .context("could not parse --to-arg")?;
+    let gs_dest_args = ObjectTags::temporary_dest_args(
+        bq_dest_args.object_tags.as_ref(),
+        bq_dest_args.temporary_compression,
+    );
     let gs_source_args = SourceArguments::for_temporary();
 
+    // If our data contains arrays, try to load it as newline-delimited JSON,
+    // which preserves nested types without needing a temporary table.
+    let dest_table = BqTable::for_table_name_and_columns(
+        dest.as_table_name()?.to_owned(),
+        &shared_args_v.schema().columns,
+        Usage::FinalTable,
+    )?
+    .with_json_type(&shared_args_v.schema().columns, bq_dest_args.json_type)
+    .with_time_partitioning(bq_dest_args.time_partitioning()?)?
+    .with_clustering(bq_dest_args.clustering()?)?
+    .with_ttl(bq_dest_args.ttl()?)?;
+    //
+    // Upserts, merges and TTL columns still need a temporary table, so they fall
+    // through and use CSV as usual.
+    if dest_table.prefers_json_load()?
+        && !dest_args_v.if_exists().changes_existing_rows()
+        && dest_table.ttl.is_none()
+    {
+        warn_about_projected_geometry(&ctx, shared_args_v.schema());
+        write_local_data_as_json(
+            ctx.clone(),
+            dest_table,
+            gs_temp,
+            data,
+            &shared_args_v,
+            dest_args_v,
+        )
+        .await?;
+        let fut = async { Ok(dest.boxed()) }.boxed();
+        return Ok(box_stream_once(Ok(fut)));
+    }
+
     // Copy to a temporary gs:// location.
     let to_temp_ctx = ctx.child(o!("to_temp" => gs_temp.to_string()));
     let result_stream = gs_temp
         .write_local_data(to_temp_ctx, data, shared_args.clone(), gs_dest_args)
         .await?;
 
-    // Wait for all gs:// uploads to finish with controllable parallelism.
-    //
-    // TODO: This duplicates our top-level `cp` code and we need to implement
-    // the same rules for picking a good argument to `consume_with_parallelism`
-    // and not just hard code our parallelism.
+    // Wait for all gs:// uploads to finish, using the same parallelism rules
+    // as our top-level `cp` code.
     result_stream
-        .consume_with_parallelism(shared_args_v.max_streams())
+        .buffer_with_parallelism(&ctx, &shared_args_v)
+        .try_collect::<Vec<_>>()
         .await?;
 
     // Load from gs:// to BigQuery.
@@ -49,3 +109,97 @@ pub(crate) async fn write_local_data_helper(
     let fut = async { Ok(dest.boxed()) }.boxed();
     Ok(box_stream_once(Ok(fut)))
 }
+
+/// Upload `data` to `gs_temp` as newline-delimited JSON, and load it directly
+/// into `dest_table`.
+async fn write_local_data_as_json(
+    ctx: Context,
+    dest_table: BqTable,
+    gs_temp: GsLocator,
+    data: BoxStream<CsvStream>,
+    shared_args: &SharedArguments<Verified>,
+    dest_args: DestinationArguments<Verified>,
+) -> Result<()> {
+    let if_exists = dest_args.if_exists();
+    let bq_dest_args = dest_args
+        .driver_args()
+        .deserialize::<BigQueryDestinationArguments>()
+        .context("could not parse --to-arg")?;
+    let load_quota = bq_dest_args.load_quota()?;
+    let object_tags = bq_dest_args.object_tags.clone().unwrap_or_default();
+    let compression = bq_dest_args.temporary_compression;
+
+    // Clear out our temporary directory.
+    let gs_temp_url = gs_temp.as_url().to_owned();
+    prepare_as_destination_helper(
+        ctx.clone(),
+        gs_temp_url.clone(),
+        IfExists::Overwrite,
+    )
+    .await?;
+
+    // Convert each stream to JSON and upload it.
+    let columns = dest_table.columns.clone();
+    let to_temp_ctx = ctx.child(o!("to_temp" => gs_temp_url.to_string()));
+    data.map_ok(move |stream| {
+        let ctx = to_temp_ctx.clone();
+        let columns = columns.clone();
+        let gs_temp_url = gs_temp_url.clone();
+        let object_tags = object_tags.clone();
+        async move {
+            let mut file_name = format!("{}.json", stream.name);
+            if let Some(compression) = compression {
+                file_name = compression.compressed_file_name(&file_name);
+            }
+            let url = gs_temp_url.join(&file_name)?;
+            let ctx = ctx
+                .child(o!("stream" => stream.name.clone(), "url" => url.to_string()));
+            let mut json_data = spawn_sync_transform(
+                ctx.clone(),
+                "copy_csv_to_bigquery_json".to_owned(),
+                stream.data,
+                move |_ctx, rdr, wtr| copy_csv_to_bigquery_json(&columns, rdr, wtr),
+            )?;
+            if let Some(compression) = compression {
+                json_data = compression.compress_stream(&ctx, json_data)?;
+            }
+            storage::upload_file(ctx, json_data, &url, &object_tags).await
+        }
+        .boxed()
+    })
+    .boxed()
+    .buffer_with_parallelism(&ctx, shared_args)
+    .try_collect::<Vec<_>>()
+    .await?;
+
+    // If requested, snapshot our destination before we change it.
+    bq_dest_args
+        .snapshot_if_requested(&ctx, dest_table.name(), if_exists)
+        .await?;
+
+    // If requested, add any missing columns to our destination. Unlike the CSV
+    // path, we load straight into `dest_table`, so this must happen first.
+    if bq_dest_args.adds_columns(if_exists) {
+        add_missing_columns(&ctx, &dest_table).await?;
+    }
+
+    // Load our JSON files directly into our destination table.
+    let source_url = gs_temp
+        .as_url()
+        .join(&bq_dest_args.temporary_file_name("*.json"))?;
+    debug!(
+        ctx.log(),
+        "loading JSON from {} directly into {}",
+        source_url,
+        dest_table.name(),
+    );
+    bigquery::load(
+        &ctx,
+        &source_url,
+        &dest_table,
+        LoadFormat::NewlineDelimitedJson,
+        if_exists,
+        &load_quota,
+    )
+    .await
+}
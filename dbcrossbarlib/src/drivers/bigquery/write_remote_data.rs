@@ -1,15 +1,176 @@
 //! Implementation of `BigQueryLocator::write_remote_data`.
 
+use chrono::Utc;
+use serde::Deserialize;
 use std::fs::File;
 use tempdir::TempDir;
 
 use super::BigQueryLocator;
-use crate::clouds::gcloud::bigquery;
+use crate::clouds::gcloud::{
+    bigquery::{self, LoadFormat},
+    load_quota::LoadQuota,
+};
+use crate::clouds::object_tags::ObjectTags;
 use crate::common::*;
+use crate::compression::Compression;
+use crate::driver_args::{deserialize_from_str, deserialize_optional_from_str};
 use crate::drivers::{
-    bigquery_shared::{BqTable, TableBigQueryExt, Usage},
-    gs::GsLocator,
+    bigquery_shared::{
+        BqClustering, BqPartitionType, BqTable, BqTimePartitioning, BqTtl, JsonType,
+        TableBigQueryExt, TableName, Usage,
+    },
+    gs::{find_gs_temp_dir, GsLocator},
 };
+use crate::evolve_schema::EvolveSchema;
+use crate::schema::{DataType, Srid, Table};
+
+/// Parsed version of `--to-arg` values.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct BigQueryDestinationArguments {
+    /// Take a snapshot of the destination table before overwriting or
+    /// upserting into it, so that we can roll back if something goes wrong.
+    #[serde(default, deserialize_with = "deserialize_from_str")]
+    create_snapshot: bool,
+
+    /// The maximum number of load jobs to submit for a single table in a
+    /// 24-hour period.
+    #[serde(default, deserialize_with = "deserialize_optional_from_str")]
+    max_load_jobs_per_table: Option<u32>,
+
+    /// The maximum number of load jobs to submit for a single project in a
+    /// 24-hour period.
+    #[serde(default, deserialize_with = "deserialize_optional_from_str")]
+    max_load_jobs_per_project: Option<u32>,
+
+    /// Tags to set on the temporary files we upload to Google Cloud Storage.
+    #[serde(default, deserialize_with = "deserialize_optional_from_str")]
+    pub(super) object_tags: Option<ObjectTags>,
+
+    /// Compress the temporary files we upload to Google Cloud Storage.
+    #[serde(default, deserialize_with = "deserialize_optional_from_str")]
+    pub(super) temporary_compression: Option<Compression>,
+
+    /// How should we store portable `json` columns?
+    #[serde(default)]
+    pub(super) json_type: JsonType,
+
+    /// Partition newly created tables on this `DATE`, `DATETIME` or
+    /// `TIMESTAMP` column.
+    #[serde(default)]
+    partition_field: Option<String>,
+
+    /// How finely to partition newly created tables.
+    #[serde(default)]
+    partition_type: Option<BqPartitionType>,
+
+    /// Require queries against newly created tables to filter on the
+    /// partitioning column.
+    #[serde(default, deserialize_with = "deserialize_from_str")]
+    require_partition_filter: bool,
+
+    /// A comma-separated list of up to 4 columns to cluster newly created
+    /// tables on.
+    #[serde(default)]
+    cluster_by: Option<String>,
+
+    /// Add a column recording when each row expires, and expire partitions
+    /// after this many days.
+    #[serde(default, deserialize_with = "deserialize_optional_from_str")]
+    ttl_days: Option<u32>,
+
+    /// The name of the expiration column to add.
+    #[serde(default)]
+    ttl_column: Option<String>,
+
+    /// How should we change an existing table to match our schema?
+    #[serde(default, deserialize_with = "deserialize_optional_from_str")]
+    evolve_schema: Option<EvolveSchema>,
+}
+
+impl BigQueryDestinationArguments {
+    /// How many load jobs may we submit?
+    pub(super) fn load_quota(&self) -> Result<LoadQuota> {
+        let default = LoadQuota::default();
+        let quota = LoadQuota {
+            max_per_table: self
+                .max_load_jobs_per_table
+                .unwrap_or(default.max_per_table),
+            max_per_project: self
+                .max_load_jobs_per_project
+                .unwrap_or(default.max_per_project),
+        };
+        if quota.max_per_table == 0 || quota.max_per_project == 0 {
+            return Err(format_err!(
+                "max_load_jobs_per_table and max_load_jobs_per_project must be greater than 0"
+            ));
+        }
+        Ok(quota)
+    }
+
+    /// How should we partition the tables we create?
+    pub(super) fn time_partitioning(&self) -> Result<Option<BqTimePartitioning>> {
+        BqTimePartitioning::from_dest_args(
+            self.partition_field.as_deref(),
+            self.partition_type,
+            self.require_partition_filter,
+        )
+    }
+
+    /// How should we cluster the tables we create?
+    pub(super) fn clustering(&self) -> Result<Option<BqClustering>> {
+        BqClustering::from_dest_arg(self.cluster_by.as_deref())
+    }
+
+    /// How long should rows in the tables we create be kept?
+    pub(super) fn ttl(&self) -> Result<Option<BqTtl>> {
+        BqTtl::from_dest_args(self.ttl_column.as_deref(), self.ttl_days)
+    }
+
+    /// The name of a temporary file, or a pattern matching several, with an
+    /// extension added if we compress them.
+    pub(super) fn temporary_file_name(&self, name: &str) -> String {
+        match self.temporary_compression {
+            Some(compression) => compression.compressed_file_name(name),
+            None => name.to_owned(),
+        }
+    }
+
+    /// Will we need to parse any columns in `schema` into native `JSON`
+    /// values?
+    fn needs_json_parsing(&self, schema: &Table) -> bool {
+        self.json_type == JsonType::Json
+            && schema
+                .columns
+                .iter()
+                .any(|c| JsonType::applies_to(&c.data_type))
+    }
+
+    /// Should we add missing columns to an existing table before loading into
+    /// it with `if_exists`?
+    pub(super) fn adds_columns(&self, if_exists: &IfExists) -> bool {
+        EvolveSchema::adds_columns(self.evolve_schema, if_exists)
+    }
+
+    /// If `create_snapshot` was requested, and `if_exists` would modify an
+    /// existing table, snapshot `table_name` before we change it.
+    pub(super) async fn snapshot_if_requested(
+        &self,
+        ctx: &Context,
+        table_name: &TableName,
+        if_exists: &IfExists,
+    ) -> Result<()> {
+        if self.create_snapshot {
+            match if_exists {
+                IfExists::Overwrite | IfExists::Upsert(..) | IfExists::Merge(..) => {
+                    create_snapshot(ctx, table_name).await?;
+                }
+                IfExists::Append | IfExists::Error => {}
+            }
+        }
+        Ok(())
+    }
+}
 
 /// Copy `source` to `dest` using `schema`.
 ///
@@ -35,6 +196,7 @@ pub(crate) async fn write_remote_data_helper(
         .to_owned();
 
     // Verify our arguments.
+    dest.check_writable()?;
     let shared_args = shared_args.verify(BigQueryLocator::features())?;
     let _source_args = source_args.verify(Features::empty())?;
     let dest_args = dest_args.verify(BigQueryLocator::features())?;
@@ -43,6 +205,15 @@ pub(crate) async fn write_remote_data_helper(
     let schema = shared_args.schema();
     let temporary_storage = shared_args.temporary_storage();
     let if_exists = dest_args.if_exists();
+    let bq_dest_args = dest_args
+        .driver_args()
+        .deserialize::<BigQueryDestinationArguments>()
+        .context("could not parse --to-arg")?;
+    let load_quota = bq_dest_args.load_quota()?;
+    let time_partitioning = bq_dest_args.time_partitioning()?;
+    let clustering = bq_dest_args.clustering()?;
+    let ttl = bq_dest_args.ttl()?;
+    warn_about_projected_geometry(&ctx, schema);
 
     // If our URL looks like a directory, add a glob.
     //
@@ -51,22 +222,43 @@ pub(crate) async fn write_remote_data_helper(
     // `dbcrossbar` property. Elsewhere, we're trying to default to adding
     // `**/*.csv`, but that's not supported by BigQuery.
     if source_url.as_str().ends_with('/') {
-        source_url = source_url.join("*.csv")?;
+        source_url = source_url.join(&bq_dest_args.temporary_file_name("*.csv"))?;
     }
     let ctx = ctx.child(o!("source_url" => source_url.as_str().to_owned()));
 
-    // Decide if we need to use a temp table.
-    let use_temp = !schema.bigquery_can_import_from_csv()? || if_exists.is_upsert();
+    // If requested, snapshot our destination before we change it.
+    bq_dest_args
+        .snapshot_if_requested(&ctx, dest.as_table_name()?, if_exists)
+        .await?;
+
+    // If requested, add any missing columns to our destination.
+    if bq_dest_args.adds_columns(if_exists) {
+        let wanted = BqTable::for_table_name_and_columns(
+            dest.as_table_name()?.clone(),
+            &schema.columns,
+            Usage::FinalTable,
+        )?
+        .with_json_type(&schema.columns, bq_dest_args.json_type);
+        add_missing_columns(&ctx, &wanted).await?;
+    }
+
+    // Decide if we need to use a temp table. We need one to fill in our TTL
+    // column, too.
+    let use_temp = !schema.bigquery_can_import_from_csv()?
+        || if_exists.changes_existing_rows()
+        || bq_dest_args.needs_json_parsing(schema)
+        || ttl.is_some();
     let initial_table_name = if use_temp {
-        let initial_table_name =
-            dest.table_name.temporary_table_name(temporary_storage)?;
+        let initial_table_name = dest
+            .as_table_name()?
+            .temporary_table_name(temporary_storage)?;
         debug!(
             ctx.log(),
             "loading into temporary table {}", initial_table_name
         );
         initial_table_name
     } else {
-        let initial_table_name = dest.table_name.clone();
+        let initial_table_name = dest.as_table_name()?.clone();
         debug!(
             ctx.log(),
             "loading directly into final table {}", initial_table_name,
@@ -74,16 +266,24 @@ pub(crate) async fn write_remote_data_helper(
         initial_table_name
     };
 
-    // Build the information we'll need about our initial table.
-    let initial_table = BqTable::for_table_name_and_columns(
-        initial_table_name,
-        &schema.columns,
-        if use_temp {
-            Usage::CsvLoad
-        } else {
-            Usage::FinalTable
-        },
-    )?;
+    // Build the information we'll need about our initial table. If we're
+    // loading directly into the final table, `bq load` will create it, so it
+    // needs to know about partitioning and clustering.
+    let initial_table = if use_temp {
+        BqTable::for_table_name_and_columns(
+            initial_table_name,
+            &schema.columns,
+            Usage::CsvLoad,
+        )?
+    } else {
+        BqTable::for_table_name_and_columns(
+            initial_table_name,
+            &schema.columns,
+            Usage::FinalTable,
+        )?
+        .with_time_partitioning(time_partitioning.clone())?
+        .with_clustering(clustering.clone())?
+    };
 
     // Write our schema to a temp file. This actually needs to be somewhere on
     // disk, and `bq` uses various hueristics to detect that it's a file
@@ -105,17 +305,29 @@ pub(crate) async fn write_remote_data_helper(
     };
 
     // Load our data.
-    bigquery::load(&ctx, &source_url, &initial_table, if_initial_table_exists).await?;
+    bigquery::load(
+        &ctx,
+        &source_url,
+        &initial_table,
+        LoadFormat::Csv,
+        if_initial_table_exists,
+        &load_quota,
+    )
+    .await?;
 
     // If `use_temp` is false, then we're done. Otherwise, run the update SQL to
     // build the final table (if needed).
     if use_temp {
         // Build a `BqTable` for our final table.
         let dest_table = BqTable::for_table_name_and_columns(
-            dest.table_name.clone(),
+            dest.as_table_name()?.clone(),
             &schema.columns,
             Usage::FinalTable,
-        )?;
+        )?
+        .with_json_type(&schema.columns, bq_dest_args.json_type)
+        .with_time_partitioning(time_partitioning)?
+        .with_clustering(clustering)?
+        .with_ttl(ttl)?;
         debug!(
             ctx.log(),
             "transforming data into final table {}",
@@ -136,3 +348,228 @@ pub(crate) async fn write_remote_data_helper(
 
     Ok(vec![dest.boxed()])
 }
+
+/// Describe what `write_remote_data_helper` would do, or, if `remote_source`
+/// is `None`, what `write_local_data_helper` would do.
+pub(crate) async fn plan_write_helper(
+    dest: BigQueryLocator,
+    shared_args: SharedArguments<Unverified>,
+    dest_args: DestinationArguments<Unverified>,
+    remote_source: Option<String>,
+) -> Result<Option<Vec<String>>> {
+    // Verify our arguments.
+    dest.check_writable()?;
+    let shared_args = shared_args.verify(BigQueryLocator::features())?;
+    let dest_args = dest_args.verify(BigQueryLocator::features())?;
+
+    // Get the arguments we care about.
+    let schema = shared_args.schema();
+    let temporary_storage = shared_args.temporary_storage();
+    let if_exists = dest_args.if_exists();
+    let bq_dest_args = dest_args
+        .driver_args()
+        .deserialize::<BigQueryDestinationArguments>()
+        .context("could not parse --to-arg")?;
+    let time_partitioning = bq_dest_args.time_partitioning()?;
+    let clustering = bq_dest_args.clustering()?;
+    let ttl = bq_dest_args.ttl()?;
+    let dest_table = BqTable::for_table_name_and_columns(
+        dest.as_table_name()?.clone(),
+        &schema.columns,
+        Usage::FinalTable,
+    )?
+    .with_json_type(&schema.columns, bq_dest_args.json_type)
+    .with_time_partitioning(time_partitioning.clone())?
+    .with_clustering(clustering.clone())?
+    .with_ttl(ttl.clone())?;
+
+    let mut plan = vec![];
+    let snapshot = if bq_dest_args.create_snapshot
+        && matches!(
+            if_exists,
+            IfExists::Overwrite | IfExists::Upsert(..) | IfExists::Merge(..)
+        ) {
+        Some(format!("snapshot {} before changing it", dest_table.name()))
+    } else {
+        None
+    };
+
+    // Figure out where we'll load our data from.
+    let source_url = match remote_source {
+        Some(remote_source) => remote_source
+            .parse::<Url>()
+            .with_context(|_| format!("cannot parse {}", remote_source))?,
+        None => {
+            let gs_temp = find_gs_temp_dir(temporary_storage)?;
+            let gs_temp_url = gs_temp.as_url();
+
+            // This mirrors the JSON fast path in `write_local_data_helper`.
+            if dest_table.prefers_json_load()?
+                && !if_exists.changes_existing_rows()
+                && dest_table.ttl.is_none()
+            {
+                plan.push(format!(
+                    "upload each stream as newline-delimited JSON to {}",
+                    gs_temp_url,
+                ));
+                plan.extend(snapshot);
+                plan.push(bigquery::load_command(
+                    &gs_temp_url.join(&bq_dest_args.temporary_file_name("*.json"))?,
+                    &dest_table,
+                    LoadFormat::NewlineDelimitedJson,
+                    if_exists,
+                )?);
+                return Ok(Some(plan));
+            }
+
+            plan.push(format!("upload each stream as CSV to {}", gs_temp_url));
+            gs_temp_url.to_owned()
+        }
+    };
+    let source_url = if source_url.as_str().ends_with('/') {
+        source_url.join(&bq_dest_args.temporary_file_name("*.csv"))?
+    } else {
+        source_url
+    };
+    plan.extend(snapshot);
+    if bq_dest_args.adds_columns(if_exists) {
+        plan.push(format!("add any missing columns to {}", dest_table.name()));
+    }
+
+    // Describe our load, using the same rules as `write_remote_data_helper`.
+    let use_temp = !schema.bigquery_can_import_from_csv()?
+        || if_exists.changes_existing_rows()
+        || bq_dest_args.needs_json_parsing(schema)
+        || ttl.is_some();
+    if use_temp {
+        let initial_table = BqTable::for_table_name_and_columns(
+            dest.as_table_name()?
+                .temporary_table_name(temporary_storage)?,
+            &schema.columns,
+            Usage::CsvLoad,
+        )?;
+        plan.push(bigquery::load_command(
+            &source_url,
+            &initial_table,
+            LoadFormat::Csv,
+            &IfExists::Overwrite,
+        )?);
+        let mut query = Vec::new();
+        dest_table.write_import_sql(initial_table.name(), if_exists, &mut query)?;
+        plan.push(
+            String::from_utf8(query).expect("generated SQL should always be UTF-8"),
+        );
+        plan.push(format!(
+            "DROP TABLE {};",
+            initial_table.name().dotted_and_quoted(),
+        ));
+    } else {
+        let initial_table = BqTable::for_table_name_and_columns(
+            dest.as_table_name()?.clone(),
+            &schema.columns,
+            Usage::FinalTable,
+        )?
+        .with_time_partitioning(time_partitioning)?
+        .with_clustering(clustering)?;
+        plan.push(bigquery::load_command(
+            &source_url,
+            &initial_table,
+            LoadFormat::Csv,
+            if_exists,
+        )?);
+    }
+    Ok(Some(plan))
+}
+
+/// If the table named by `wanted` exists, add any of the columns in `wanted`
+/// which it lacks.
+pub(super) async fn add_missing_columns(
+    ctx: &Context,
+    wanted: &BqTable,
+) -> Result<()> {
+    let existing = match bigquery::schema_if_exists(ctx, wanted.name()).await? {
+        Some(existing) => existing,
+        None => {
+            // We'll create the table with all our columns.
+            debug!(
+                ctx.log(),
+                "not adding columns to {}, which does not exist",
+                wanted.name(),
+            );
+            return Ok(());
+        }
+    };
+    let mut sql = vec![];
+    if wanted.write_add_missing_columns_sql(&existing, &mut sql)? {
+        let sql =
+            String::from_utf8(sql).expect("generated SQL should always be UTF-8");
+        info!(ctx.log(), "adding columns to {}", wanted.name());
+        debug!(ctx.log(), "ALTER TABLE SQL: {}", sql);
+        bigquery::execute_sql(ctx, wanted.name().project(), &sql).await?;
+    }
+    Ok(())
+}
+
+/// Take a snapshot of `table_name`, if it exists.
+async fn create_snapshot(ctx: &Context, table_name: &TableName) -> Result<()> {
+    let snapshot_name = TableName::new(
+        table_name.project(),
+        table_name.dataset(),
+        &format!(
+            "{}_snapshot_{}",
+            table_name.table(),
+            Utc::now().format("%Y%m%d_%H%M%S"),
+        ),
+    );
+    let table = BqTable {
+        name: table_name.to_owned(),
+        columns: vec![],
+        time_partitioning: None,
+        clustering: None,
+        ttl: None,
+    };
+    let mut sql = vec![];
+    table.write_create_snapshot_sql(&snapshot_name, &mut sql)?;
+    let sql = String::from_utf8(sql).expect("generated SQL should always be UTF-8");
+    debug!(ctx.log(), "snapshot SQL: {}", sql);
+    bigquery::execute_sql(ctx, table_name.project(), &sql).await?;
+    info!(
+        ctx.log(),
+        "snapshotted {} as {} (if it existed)", table_name, snapshot_name,
+    );
+    Ok(())
+}
+
+/// BigQuery `GEOGRAPHY` columns only support WGS84, so we load geometry using
+/// other SRIDs as `STRING` columns. Warn the user about this, because it's
+/// easy to fix if the source can reproject the data for us.
+pub(crate) fn warn_about_projected_geometry(ctx: &Context, schema: &Table) {
+    for col in &schema.columns {
+        if let DataType::GeoJson(srid) = &col.data_type {
+            if *srid != Srid::wgs84() {
+                warn!(
+                    ctx.log(),
+                    "column {} uses SRID {}, so BigQuery will store it as a STRING; to load it as a GEOGRAPHY, pass a --schema with SRID {} (PostgreSQL sources will reproject it)",
+                    col.name,
+                    srid,
+                    Srid::wgs84(),
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn adds_columns_only_when_requested() {
+    let parse = |args: &[&str]| {
+        let args = args.iter().map(|&a| a.to_owned()).collect::<Vec<_>>();
+        DriverArguments::from_cli_args(&args)
+            .unwrap()
+            .deserialize::<BigQueryDestinationArguments>()
+            .unwrap()
+    };
+    let evolve = parse(&["evolve_schema=add_columns"]);
+    assert!(evolve.adds_columns(&IfExists::Append));
+    assert!(!evolve.adds_columns(&IfExists::Overwrite));
+    assert!(!parse(&[]).adds_columns(&IfExists::Append));
+}
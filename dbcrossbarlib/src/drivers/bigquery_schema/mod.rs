@@ -31,6 +31,10 @@ impl Locator for BigQuerySchemaLocator {
         self
     }
 
+    fn is_stdio(&self) -> bool {
+        matches!(self.path, PathOrStdio::Stdio)
+    }
+
     fn schema(&self, ctx: Context) -> BoxFuture<Option<Table>> {
         schema_helper(ctx, self.to_owned()).boxed()
     }
@@ -72,20 +76,28 @@ async fn schema_helper(
     let data = async_read_to_end(input)
         .await
         .with_context(|_| format!("error reading {}", source.path))?;
+    let table = parse_table(&data)
+        .with_context(|_| format!("error parsing {}", source.path))?;
+    Ok(Some(table))
+}
 
+/// Parse a BigQuery JSON schema.
+pub(crate) fn parse_table(data: &[u8]) -> Result<Table> {
     // Parse our input as a list of columns.
-    let columns: Vec<BqColumn> = serde_json::from_slice(&data)
-        .with_context(|_| format!("error parsing {}", source.path))?;
+    let columns: Vec<BqColumn> = serde_json::from_slice(data)?;
 
     // Build a `BqTable`, convert it, and set a placeholder name.
     let arbitrary_name = TableName::from_str(&"unused:unused.unused")?;
     let bq_table = BqTable {
         name: arbitrary_name,
         columns,
+        time_partitioning: None,
+        clustering: None,
+        ttl: None,
     };
     let mut table = bq_table.to_table()?;
     table.name = "unnamed".to_owned();
-    Ok(Some(table))
+    Ok(table)
 }
 
 /// Implementation of `write_schema`, but as a real `async` function.
@@ -95,6 +107,17 @@ async fn write_schema_helper(
     table: Table,
     if_exists: IfExists,
 ) -> Result<()> {
+    // Output our schema to our destination.
+    let mut f = dest.path.create_async(ctx, if_exists).await?;
+    buffer_sync_write_and_copy_to_async(&mut f, |buff| write_table(buff, &table))
+        .await
+        .with_context(|_| format!("error writing to {}", dest.path))?;
+    f.flush().await?;
+    Ok(())
+}
+
+/// Write `table` as a BigQuery JSON schema.
+pub(crate) fn write_table(f: &mut dyn Write, table: &Table) -> Result<()> {
     // The BigQuery table name doesn't matter here, because our BigQuery schema
     // won't use it. We could convert `table.name` into a valid BigQuery table
     // name, but because BigQuery table names obey fairly strict restrictions,
@@ -107,14 +130,5 @@ async fn write_schema_helper(
         &table.columns,
         Usage::FinalTable,
     )?;
-
-    // Output our schema to our destination.
-    let mut f = dest.path.create_async(ctx, if_exists).await?;
-    buffer_sync_write_and_copy_to_async(&mut f, |buff| {
-        bq_table.write_json_schema(buff)
-    })
-    .await
-    .with_context(|_| format!("error writing to {}", dest.path))?;
-    f.flush().await?;
-    Ok(())
+    bq_table.write_json_schema(f)
 }
@@ -0,0 +1,143 @@
+//! BigQuery clustered tables.
+
+use itertools::Itertools;
+use std::convert::TryFrom;
+
+use super::{BqDataType, BqNonArrayDataType, BqTable, ColumnName};
+use crate::common::*;
+
+/// BigQuery allows at most this many clustering columns.
+const MAX_CLUSTERING_COLUMNS: usize = 4;
+
+/// The columns used to cluster a BigQuery table, in order.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct BqClustering {
+    /// The columns to cluster on.
+    pub(crate) columns: Vec<ColumnName>,
+}
+
+impl BqClustering {
+    /// Parse a comma-separated list of column names, as passed to
+    /// `--to-arg=cluster_by=...`.
+    pub(crate) fn from_dest_arg(
+        cluster_by: Option<&str>,
+    ) -> Result<Option<BqClustering>> {
+        let cluster_by = match cluster_by {
+            Some(cluster_by) => cluster_by,
+            None => return Ok(None),
+        };
+        let columns = cluster_by
+            .split(',')
+            .map(|name| ColumnName::try_from(name.trim()))
+            .collect::<Result<Vec<_>>>()?;
+        if columns.len() > MAX_CLUSTERING_COLUMNS {
+            return Err(format_err!(
+                "BigQuery can only cluster on {} columns, but cluster_by contains {}",
+                MAX_CLUSTERING_COLUMNS,
+                columns.len(),
+            ));
+        }
+        Ok(Some(BqClustering { columns }))
+    }
+
+    /// Make sure that `table` can be clustered this way.
+    pub(crate) fn validate(&self, table: &BqTable) -> Result<()> {
+        for name in &self.columns {
+            let col =
+                table
+                    .columns
+                    .iter()
+                    .find(|c| &c.name == name)
+                    .ok_or_else(|| {
+                        format_err!("cannot cluster on unknown column {}", name)
+                    })?;
+            match col.bq_data_type()? {
+                BqDataType::NonArray(BqNonArrayDataType::BigNumeric)
+                | BqDataType::NonArray(BqNonArrayDataType::Bool)
+                | BqDataType::NonArray(BqNonArrayDataType::Date)
+                | BqDataType::NonArray(BqNonArrayDataType::Datetime)
+                | BqDataType::NonArray(BqNonArrayDataType::Geography)
+                | BqDataType::NonArray(BqNonArrayDataType::Int64)
+                | BqDataType::NonArray(BqNonArrayDataType::Numeric)
+                | BqDataType::NonArray(BqNonArrayDataType::String)
+                | BqDataType::NonArray(BqNonArrayDataType::Timestamp) => {}
+                other => {
+                    return Err(format_err!(
+                        "cannot cluster on column {} of type {}",
+                        name,
+                        other,
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Arguments to pass to `bq load` when it creates a table.
+    pub(crate) fn bq_load_args(&self) -> Vec<String> {
+        vec![format!(
+            "--clustering_fields={}",
+            self.columns.iter().map(|c| c.as_str()).join(","),
+        )]
+    }
+
+    /// Write a `CLUSTER BY` clause.
+    pub(crate) fn write_cluster_by_sql(&self, f: &mut dyn Write) -> Result<()> {
+        write!(f, "CLUSTER BY {}", self.columns.iter().join(", "))?;
+        Ok(())
+    }
+}
+
+#[test]
+fn clustered_tables_are_created_with_cluster_by() {
+    use super::Usage;
+    use crate::schema::{Column, DataType};
+
+    let column = |name: &str, data_type: DataType| Column {
+        name: name.to_owned(),
+        is_nullable: true,
+        data_type,
+        comment: None,
+        default: None,
+        collation: None,
+    };
+    let columns = vec![
+        column("user_id", DataType::Int64),
+        column("event_type", DataType::Text),
+        column("score", DataType::Float64),
+    ];
+    let table = BqTable::for_table_name_and_columns(
+        "project:dataset.table".parse().unwrap(),
+        &columns,
+        Usage::FinalTable,
+    )
+    .unwrap();
+
+    let clustering = BqClustering::from_dest_arg(Some("user_id, event_type"))
+        .unwrap()
+        .unwrap();
+    clustering.validate(&table).unwrap();
+    assert_eq!(
+        clustering.bq_load_args(),
+        vec!["--clustering_fields=user_id,event_type".to_owned()],
+    );
+
+    let table = table.with_clustering(Some(clustering)).unwrap();
+    let mut sql = vec![];
+    table
+        .write_import_sql(
+            &"project:dataset.temp".parse().unwrap(),
+            &IfExists::Overwrite,
+            &mut sql,
+        )
+        .unwrap();
+    let sql = String::from_utf8(sql).unwrap();
+    assert!(sql.contains("\n)\nCLUSTER BY user_id, event_type;\n"));
+
+    let bad = BqClustering::from_dest_arg(Some("score")).unwrap().unwrap();
+    assert!(bad.validate(&table).is_err());
+    let missing = BqClustering::from_dest_arg(Some("nope")).unwrap().unwrap();
+    assert!(missing.validate(&table).is_err());
+    assert!(BqClustering::from_dest_arg(Some("a,b,c,d,e")).is_err());
+    assert_eq!(BqClustering::from_dest_arg(None).unwrap(), None);
+}
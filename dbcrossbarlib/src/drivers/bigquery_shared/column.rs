@@ -3,11 +3,14 @@
 use serde_derive::{Deserialize, Serialize};
 
 use super::{
-    BqDataType, BqNonArrayDataType, BqRecordOrNonArrayDataType, BqStructField,
-    ColumnName, DataTypeBigQueryExt, Usage,
+    BqDataType, BqNonArrayDataType, BqRecordOrNonArrayDataType, BqStr, BqStructField,
+    ColumnName, DataTypeBigQueryExt, JsonType, Usage,
 };
 use crate::common::*;
-use crate::schema::Column;
+use crate::schema::{Collation, Column, ColumnDefault, DataType};
+
+/// BigQuery's name for case-insensitive collation.
+const CASE_INSENSITIVE: &str = "und:ci";
 
 /// Extensions to `Column` (the portable version) to handle BigQuery-query
 /// specific stuff.
@@ -51,6 +54,20 @@ pub(crate) struct BqColumn {
     /// can't be exported as valid JSON in any case.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     fields: Vec<BqColumn>,
+
+    /// A SQL expression used to fill in this column when no value is supplied.
+    #[serde(
+        rename = "defaultValueExpression",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    default_value_expression: Option<String>,
+
+    /// How values in this column are compared. BigQuery only supports
+    /// `und:ci` (case-insensitive) and the default binary collation, and only
+    /// on `STRING` columns.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    collation: Option<String>,
 }
 
 impl BqColumn {
@@ -71,13 +88,89 @@ impl BqColumn {
             }
             BqDataType::NonArray(ty) => (ty, Mode::Required),
         };
-        Ok(BqColumn {
-            name,
-            description: None,
-            ty: BqRecordOrNonArrayDataType::DataType(ty),
-            mode,
-            fields: vec![],
-        })
+        let mut bq_col = BqColumn::for_non_array_data_type(name, ty, mode)?;
+        // Temporary tables used for loading data never need defaults or
+        // descriptions.
+        if usage == Usage::FinalTable {
+            bq_col.description = col.comment.clone();
+            bq_col.default_value_expression = col
+                .default
+                .as_ref()
+                .and_then(|default| default_value_sql(&col.data_type, default));
+            bq_col.collation = bq_col.collation_for(col.collation.as_ref());
+        }
+        Ok(bq_col)
+    }
+
+    /// Build a `BqColumn` with the specified type and mode. Structs with named
+    /// fields are declared as `RECORD` columns, which is what BigQuery's JSON
+    /// schemas expect.
+    fn for_non_array_data_type(
+        name: ColumnName,
+        ty: BqNonArrayDataType,
+        mode: Mode,
+    ) -> Result<BqColumn> {
+        match ty {
+            BqNonArrayDataType::Struct(struct_fields)
+                if struct_fields.iter().all(|f| f.name.is_some()) =>
+            {
+                let fields = struct_fields
+                    .into_iter()
+                    .map(|f| {
+                        let name = f.name.expect("checked above");
+                        let (ty, mode) = match f.ty {
+                            BqDataType::Array(ty) => (ty, Mode::Repeated),
+                            BqDataType::NonArray(ty) => (ty, Mode::Nullable),
+                        };
+                        BqColumn::for_non_array_data_type(name, ty, mode)
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(BqColumn {
+                    name,
+                    description: None,
+                    ty: BqRecordOrNonArrayDataType::Record,
+                    mode,
+                    fields,
+                    default_value_expression: None,
+                    collation: None,
+                })
+            }
+            ty => Ok(BqColumn {
+                name,
+                description: None,
+                ty: BqRecordOrNonArrayDataType::DataType(ty),
+                mode,
+                fields: vec![],
+                default_value_expression: None,
+                collation: None,
+            }),
+        }
+    }
+
+    /// Choose a BigQuery collation for this column, given a portable
+    /// `collation`. BigQuery can't use other databases' named collations, so
+    /// we only keep case-insensitivity.
+    fn collation_for(&self, collation: Option<&Collation>) -> Option<String> {
+        match (collation, &self.ty, self.mode) {
+            (
+                Some(Collation::CaseInsensitive),
+                BqRecordOrNonArrayDataType::DataType(BqNonArrayDataType::String),
+                Mode::Nullable | Mode::Required,
+            ) => Some(CASE_INSENSITIVE.to_owned()),
+            _ => None,
+        }
+    }
+
+    /// If `col` is a portable `json` column (or an array of `json`), and
+    /// `json_type` asks for BigQuery's native `JSON` type, use `JSON` instead
+    /// of `STRING`.
+    ///
+    /// This column should have been created from `col` with
+    /// `Usage::FinalTable`.
+    pub(crate) fn use_json_type(&mut self, col: &Column, json_type: JsonType) {
+        if json_type == JsonType::Json && JsonType::applies_to(&col.data_type) {
+            self.ty = BqRecordOrNonArrayDataType::DataType(BqNonArrayDataType::Json);
+        }
     }
 
     /// Given a `BqColumn`, construct a portable `Column`.
@@ -92,6 +185,15 @@ impl BqColumn {
                 Mode::Required => false,
             },
             comment: self.description.clone(),
+            default: self
+                .default_value_expression
+                .as_deref()
+                .and_then(parse_default_value_sql),
+            collation: match self.collation.as_deref() {
+                None | Some("") => None,
+                Some(CASE_INSENSITIVE) => Some(Collation::CaseInsensitive),
+                Some(other) => Some(Collation::Named(other.to_owned())),
+            },
         })
     }
 
@@ -126,6 +228,21 @@ impl BqColumn {
         })
     }
 
+    /// The description of this column, if any.
+    pub(crate) fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// The BigQuery collation of this column, if any.
+    pub(crate) fn collation(&self) -> Option<&str> {
+        self.collation.as_deref()
+    }
+
+    /// The SQL expression used as a default value for this column, if any.
+    pub(crate) fn default_value_expression(&self) -> Option<&str> {
+        self.default_value_expression.as_deref()
+    }
+
     /// Output JavaScript UDF for importing a column (if necessary). This can be
     /// used to patch up types that can't be loaded directly from a CSV.
     pub(crate) fn write_import_udf(
@@ -148,17 +265,80 @@ return JSON.parse(input);
 CREATE TEMP FUNCTION ImportJson_{idx}(input STRING)
 RETURNS ARRAY<{bq_type}>
 AS ((
-    SELECT ARRAY_AGG(
-        COALESCE(
-            SAFE.PARSE_DATETIME('%Y-%m-%d %H:%M:%E*S', e),
-            PARSE_DATETIME('%Y-%m-%dT%H:%M:%E*S', e)
-        )
-    )
+    SELECT ARRAY_AGG({parse})
     FROM UNNEST(ImportJsonHelper_{idx}(input)) AS e
 ));
 "#,
                     idx = idx,
                     bq_type = elem_ty,
+                    parse = parse_datetime_sql("e"),
+                )?;
+            }
+
+            // Like `DATETIME`, we parse `TIME` values using SQL.
+            BqDataType::Array(elem_ty @ BqNonArrayDataType::Time) => {
+                writeln!(
+                    f,
+                    r#"CREATE TEMP FUNCTION ImportJsonHelper_{idx}(input STRING)
+RETURNS ARRAY<STRING>
+LANGUAGE js AS """
+return JSON.parse(input);
+""";
+
+CREATE TEMP FUNCTION ImportJson_{idx}(input STRING)
+RETURNS ARRAY<{bq_type}>
+AS ((
+    SELECT ARRAY_AGG({parse})
+    FROM UNNEST(ImportJsonHelper_{idx}(input)) AS e
+));
+"#,
+                    idx = idx,
+                    bq_type = elem_ty,
+                    parse = parse_time_sql("e"),
+                )?;
+            }
+
+            // `INTERVAL` values are stored as ISO 8601 strings, which `CAST`
+            // understands.
+            BqDataType::Array(elem_ty @ BqNonArrayDataType::Interval) => {
+                writeln!(
+                    f,
+                    r#"CREATE TEMP FUNCTION ImportJsonHelper_{idx}(input STRING)
+RETURNS ARRAY<STRING>
+LANGUAGE js AS """
+return JSON.parse(input);
+""";
+
+CREATE TEMP FUNCTION ImportJson_{idx}(input STRING)
+RETURNS ARRAY<{bq_type}>
+AS ((
+    SELECT ARRAY_AGG(CAST(e AS INTERVAL))
+    FROM UNNEST(ImportJsonHelper_{idx}(input)) AS e
+));
+"#,
+                    idx = idx,
+                    bq_type = elem_ty,
+                )?;
+            }
+
+            // Arrays of structs with named fields are JSON arrays of objects.
+            // (Structs without field names are used to represent nested
+            // arrays, which we can't import yet.)
+            BqDataType::Array(BqNonArrayDataType::Struct(ref fields))
+                if fields.iter().all(|f| f.name.is_some()) =>
+            {
+                writeln!(
+                    f,
+                    r#"CREATE TEMP FUNCTION ImportJson_{idx}(input STRING)
+RETURNS {bq_type}
+AS ((
+    {import}
+));
+"#,
+                    idx = idx,
+                    bq_type = self.bq_data_type()?,
+                    import =
+                        array_of_structs_from_json_sql("input", "'$'", fields, 0)?,
                 )?;
             }
 
@@ -183,6 +363,24 @@ return "#,
                 )?;
             }
 
+            // Structs are loaded as JSON objects, which we take apart using
+            // SQL. This works for any field types that we can parse from
+            // strings.
+            BqDataType::NonArray(BqNonArrayDataType::Struct(ref fields)) => {
+                writeln!(
+                    f,
+                    r#"CREATE TEMP FUNCTION ImportJson_{idx}(input STRING)
+RETURNS {bq_type}
+AS ((
+    {import}
+));
+"#,
+                    idx = idx,
+                    bq_type = self.bq_data_type()?,
+                    import = struct_from_json_sql("input", fields, 0)?,
+                )?;
+            }
+
             // No special import required for any of these types yet.
             BqDataType::NonArray(_) => {}
         }
@@ -196,8 +394,10 @@ return "#,
         elem_ty: &BqNonArrayDataType,
     ) -> Result<()> {
         match elem_ty {
-            // These types can be converted directly from JSON.
+            // These types can be converted directly from JSON. JavaScript UDFs
+            // represent `BYTES` as base64 strings, just like our CSV format.
             BqNonArrayDataType::Bool
+            | BqNonArrayDataType::Bytes
             | BqNonArrayDataType::Float64
             | BqNonArrayDataType::String => {
                 write!(f, "JSON.parse(input)")?;
@@ -225,8 +425,10 @@ return "#,
             // Unsupported types. Some of these aren't actually supported by our
             // portable schema, so we should never see them. Others can occur in
             // real data.
-            BqNonArrayDataType::Bytes
+            BqNonArrayDataType::BigNumeric
             | BqNonArrayDataType::Geography
+            | BqNonArrayDataType::Interval
+            | BqNonArrayDataType::Json
             | BqNonArrayDataType::Numeric
             | BqNonArrayDataType::Time
             | BqNonArrayDataType::Struct(_) => {
@@ -254,21 +456,27 @@ return "#,
     ) -> Result<()> {
         let table_prefix = table_prefix.unwrap_or("");
         assert!(table_prefix == "" || table_prefix.ends_with('.'));
-        if self.mode == Mode::Repeated {
-            write!(
-                f,
-                "ImportJson_{idx}({table_prefix}{name})",
-                idx = idx,
-                table_prefix = table_prefix,
-                name = self.name,
-            )?;
-        } else {
-            write!(
-                f,
-                "{table_prefix}{name}",
-                table_prefix = table_prefix,
-                name = self.name,
-            )?;
+        let expr = format!("{}{}", table_prefix, self.name);
+        match self.bq_data_type()? {
+            BqDataType::Array(_)
+            | BqDataType::NonArray(BqNonArrayDataType::Struct(_)) => {
+                write!(f, "ImportJson_{idx}({expr})", idx = idx, expr = expr)?;
+            }
+            BqDataType::NonArray(BqNonArrayDataType::Datetime) => {
+                write!(f, "{}", parse_datetime_sql(&expr))?;
+            }
+            BqDataType::NonArray(BqNonArrayDataType::Interval) => {
+                write!(f, "CAST({} AS INTERVAL)", expr)?;
+            }
+            BqDataType::NonArray(BqNonArrayDataType::Json) => {
+                write!(f, "PARSE_JSON({})", expr)?;
+            }
+            BqDataType::NonArray(BqNonArrayDataType::Time) => {
+                write!(f, "{}", parse_time_sql(&expr))?;
+            }
+            BqDataType::NonArray(_) => {
+                write!(f, "{}", expr)?;
+            }
         }
         Ok(())
     }
@@ -307,12 +515,17 @@ return "#,
         write!(f, "NULLIF(TO_JSON_STRING(")?;
         match data_type {
             // We can safely convert arrays of these types directly to JSON.
-            BqNonArrayDataType::Bool
+            // `BYTES` values are converted to base64.
+            BqNonArrayDataType::BigNumeric
+            | BqNonArrayDataType::Bool
+            | BqNonArrayDataType::Bytes
             | BqNonArrayDataType::Date
             | BqNonArrayDataType::Float64
             | BqNonArrayDataType::Int64
+            | BqNonArrayDataType::Json
             | BqNonArrayDataType::Numeric
-            | BqNonArrayDataType::String => {
+            | BqNonArrayDataType::String
+            | BqNonArrayDataType::Time => {
                 write!(f, "{}", self.name)?;
             }
 
@@ -324,15 +537,22 @@ return "#,
                 write!(f, "(SELECT ARRAY_AGG(ST_ASGEOJSON({name})) FROM UNNEST({name}) AS {name})", name = self.name)?;
             }
 
+            BqNonArrayDataType::Interval => {
+                write!(
+                    f,
+                    "(SELECT ARRAY_AGG({iso}) FROM UNNEST({name}) AS {name})",
+                    iso = interval_to_iso8601_sql(&self.name.to_string()),
+                    name = self.name,
+                )?;
+            }
+
             BqNonArrayDataType::Timestamp => {
                 write!(f, "(SELECT ARRAY_AGG(FORMAT_TIMESTAMP(\"%Y-%m-%dT%H:%M:%E*SZ\", {name}, \"+0\")) FROM UNNEST({name}) AS {name})", name = self.name)?;
             }
 
             // These we don't know how to output at all. (We don't have a
             // portable type for most of these.)
-            BqNonArrayDataType::Bytes
-            | BqNonArrayDataType::Struct(_)
-            | BqNonArrayDataType::Time => {
+            BqNonArrayDataType::Struct(_) => {
                 return Err(format_err!(
                     "can't output {} columns yet",
                     self.bq_data_type()?,
@@ -350,12 +570,16 @@ return "#,
         f: &mut dyn Write,
     ) -> Result<()> {
         match data_type {
-            // We trust BigQuery to output these directly.
-            BqNonArrayDataType::Date
+            // We trust BigQuery to output these directly. `BYTES` values are
+            // written as base64.
+            BqNonArrayDataType::BigNumeric
+            | BqNonArrayDataType::Bytes
+            | BqNonArrayDataType::Date
             | BqNonArrayDataType::Float64
             | BqNonArrayDataType::Int64
             | BqNonArrayDataType::Numeric
-            | BqNonArrayDataType::String => {
+            | BqNonArrayDataType::String
+            | BqNonArrayDataType::Time => {
                 write!(f, "{}", self.name)?;
             }
 
@@ -384,6 +608,19 @@ return "#,
                 write!(f, "ST_ASGEOJSON({name}) AS {name}", name = self.name)?;
             }
 
+            BqNonArrayDataType::Json => {
+                write!(f, "TO_JSON_STRING({name}) AS {name}", name = self.name)?;
+            }
+
+            BqNonArrayDataType::Interval => {
+                write!(
+                    f,
+                    "IF({name} IS NULL, NULL, {iso}) AS {name}",
+                    iso = interval_to_iso8601_sql(&self.name.to_string()),
+                    name = self.name,
+                )?;
+            }
+
             struct_ty @ BqNonArrayDataType::Struct(_) => {
                 if struct_ty.is_json_safe() {
                     write!(f, "TO_JSON_STRING({name}) AS {name}", name = self.name)?;
@@ -399,18 +636,187 @@ return "#,
                     name = self.name
                 )?;
             }
+        }
+        Ok(())
+    }
+}
 
-            // These we don't know how to output at all. (We don't have a
-            // portable type for most of these.)
-            BqNonArrayDataType::Bytes | BqNonArrayDataType::Time => {
-                return Err(format_err!(
-                    "can't output {} columns yet",
-                    self.bq_data_type()?,
-                ));
+/// Formats we accept for civil `DATETIME` values. `%E*S` accepts optional
+/// fractional seconds.
+const DATETIME_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%E*S",
+    "%Y-%m-%d %H:%M:%E*S",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Formats we accept for `TIME` values.
+const TIME_FORMATS: &[&str] = &["%H:%M:%E*S", "%H:%M"];
+
+/// Generate SQL which parses the `STRING` expression `expr` using `function`,
+/// trying each of `formats` in turn. If none of them match, we try the first
+/// format again without `SAFE.`, so that BigQuery reports the bad value.
+fn parse_with_formats_sql(function: &str, formats: &[&str], expr: &str) -> String {
+    let mut sql = "COALESCE(".to_owned();
+    for format in formats {
+        sql.push_str(&format!("SAFE.{}('{}', {}), ", function, format, expr));
+    }
+    sql.push_str(&format!("{}('{}', {}))", function, formats[0], expr));
+    sql
+}
+
+/// Generate SQL which parses the `STRING` expression `expr` as a civil
+/// `DATETIME`, with either a `T` or a space before the time.
+fn parse_datetime_sql(expr: &str) -> String {
+    parse_with_formats_sql("PARSE_DATETIME", DATETIME_FORMATS, expr)
+}
+
+/// Generate SQL which parses the `STRING` expression `expr` as a `TIME`.
+fn parse_time_sql(expr: &str) -> String {
+    parse_with_formats_sql("PARSE_TIME", TIME_FORMATS, expr)
+}
+
+/// Generate SQL which formats the BigQuery `INTERVAL` expression `expr` as an
+/// ISO 8601 duration. `EXTRACT` returns a separately-signed value for each
+/// component, so we only need to be careful about negative fractional seconds.
+fn interval_to_iso8601_sql(expr: &str) -> String {
+    format!(
+        "FORMAT('P%dY%dM%dDT%dH%dM%s%d.%06dS', EXTRACT(YEAR FROM {e}), EXTRACT(MONTH FROM {e}), EXTRACT(DAY FROM {e}), EXTRACT(HOUR FROM {e}), EXTRACT(MINUTE FROM {e}), IF(EXTRACT(SECOND FROM {e}) = 0 AND EXTRACT(MICROSECOND FROM {e}) < 0, '-', ''), EXTRACT(SECOND FROM {e}), ABS(EXTRACT(MICROSECOND FROM {e})))",
+        e = expr,
+    )
+}
+
+/// Generate a BigQuery expression for `default` in a column of type
+/// `data_type`, or `None` if we don't know how to represent it.
+fn default_value_sql(data_type: &DataType, default: &ColumnDefault) -> Option<String> {
+    match (default, data_type) {
+        (ColumnDefault::CurrentTimestamp, DataType::Date) => {
+            Some("CURRENT_DATE()".to_owned())
+        }
+        (ColumnDefault::CurrentTimestamp, DataType::TimestampWithoutTimeZone) => {
+            Some("CURRENT_DATETIME()".to_owned())
+        }
+        (ColumnDefault::CurrentTimestamp, DataType::TimestampWithTimeZone) => {
+            Some("CURRENT_TIMESTAMP()".to_owned())
+        }
+        (ColumnDefault::CurrentTimestamp, _) => None,
+        (ColumnDefault::Literal(value), DataType::Bool) => {
+            match value.to_ascii_lowercase().as_str() {
+                "true" | "t" | "yes" | "y" | "1" => Some("TRUE".to_owned()),
+                "false" | "f" | "no" | "n" | "0" => Some("FALSE".to_owned()),
+                _ => None,
             }
         }
-        Ok(())
+        (
+            ColumnDefault::Literal(value),
+            DataType::Decimal
+            | DataType::DecimalWithPrecision(_)
+            | DataType::Float32
+            | DataType::Float64
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64,
+        ) if value.parse::<f64>().is_ok() => Some(value.to_owned()),
+        (
+            ColumnDefault::Literal(value),
+            DataType::Text | DataType::TextWithMaxLength(_) | DataType::OneOf(_),
+        ) => Some(BqStr(value).to_string()),
+        (ColumnDefault::Literal(value), DataType::Date) => {
+            Some(format!("DATE {}", BqStr(value)))
+        }
+        (ColumnDefault::Literal(value), DataType::Time) => {
+            Some(format!("TIME {}", BqStr(value)))
+        }
+        (ColumnDefault::Literal(value), DataType::TimestampWithoutTimeZone) => {
+            Some(format!("DATETIME {}", BqStr(value)))
+        }
+        (ColumnDefault::Literal(value), DataType::TimestampWithTimeZone) => {
+            Some(format!("TIMESTAMP {}", BqStr(value)))
+        }
+        (ColumnDefault::Literal(_), _) => None,
+    }
+}
+
+/// Parse a BigQuery default value expression, returning `None` if it's not
+/// something we'd generate using `default_value_sql`.
+fn parse_default_value_sql(expr: &str) -> Option<ColumnDefault> {
+    let expr = expr.trim();
+    let upper = expr.to_ascii_uppercase();
+    match upper.trim_end_matches("()") {
+        "CURRENT_DATE" | "CURRENT_DATETIME" | "CURRENT_TIMESTAMP" => {
+            return Some(ColumnDefault::CurrentTimestamp);
+        }
+        "TRUE" | "FALSE" => {
+            return Some(ColumnDefault::Literal(upper.to_ascii_lowercase()));
+        }
+        _ => {}
+    }
+    if expr.parse::<f64>().is_ok() {
+        return Some(ColumnDefault::Literal(expr.to_owned()));
+    }
+
+    // Strip any type prefix, and parse the remaining string literal.
+    let literal = ["DATE ", "DATETIME ", "TIME ", "TIMESTAMP "]
+        .iter()
+        .find(|prefix| upper.starts_with(*prefix))
+        .map(|prefix| expr[prefix.len()..].trim_start())
+        .unwrap_or(expr);
+    let quote = literal.chars().next().filter(|c| *c == '\'' || *c == '"')?;
+    let body = literal.strip_prefix(quote)?.strip_suffix(quote)?;
+    let mut value = String::with_capacity(body.len());
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                'r' => value.push('\r'),
+                't' => value.push('\t'),
+                escaped => value.push(escaped),
+            },
+            c => value.push(c),
+        }
+    }
+    Some(ColumnDefault::Literal(value))
+}
+
+#[test]
+fn default_values_round_trip() {
+    let examples = &[
+        (
+            DataType::Int64,
+            ColumnDefault::Literal("-12".to_owned()),
+            "-12",
+        ),
+        (
+            DataType::Bool,
+            ColumnDefault::Literal("true".to_owned()),
+            "TRUE",
+        ),
+        (
+            DataType::Text,
+            ColumnDefault::Literal("it's".to_owned()),
+            "'it\\'s'",
+        ),
+        (
+            DataType::Date,
+            ColumnDefault::Literal("2020-01-02".to_owned()),
+            "DATE '2020-01-02'",
+        ),
+        (
+            DataType::TimestampWithTimeZone,
+            ColumnDefault::CurrentTimestamp,
+            "CURRENT_TIMESTAMP()",
+        ),
+    ];
+    for (data_type, default, expected) in examples {
+        let sql = default_value_sql(data_type, default).unwrap();
+        assert_eq!(sql, *expected);
+        assert_eq!(parse_default_value_sql(&sql).as_ref(), Some(default));
     }
+    assert!(
+        default_value_sql(&DataType::Json, &ColumnDefault::CurrentTimestamp).is_none()
+    );
+    assert!(parse_default_value_sql("GENERATE_UUID()").is_none());
 }
 
 #[test]
@@ -420,6 +826,125 @@ fn column_without_mode() {
     assert_eq!(col.mode, Mode::Nullable);
 }
 
+#[test]
+fn case_insensitive_columns_use_und_ci() {
+    use std::convert::TryFrom;
+
+    let col = Column {
+        name: "email".to_owned(),
+        data_type: DataType::Text,
+        is_nullable: true,
+        comment: None,
+        default: None,
+        collation: Some(Collation::CaseInsensitive),
+    };
+    let name = ColumnName::try_from(&col.name).unwrap();
+    let bq_col = BqColumn::for_column(name.clone(), &col, Usage::FinalTable).unwrap();
+    assert_eq!(bq_col.collation(), Some("und:ci"));
+    assert_eq!(bq_col.to_column().unwrap(), col);
+
+    // Temporary tables and non-`STRING` columns don't get collations.
+    let temp_col = BqColumn::for_column(name.clone(), &col, Usage::CsvLoad).unwrap();
+    assert_eq!(temp_col.collation(), None);
+    let array_col = Column {
+        data_type: DataType::Array(Box::new(DataType::Text)),
+        ..col
+    };
+    let bq_col = BqColumn::for_column(name, &array_col, Usage::FinalTable).unwrap();
+    assert_eq!(bq_col.collation(), None);
+}
+
+/// Generate SQL which converts `json`, a `STRING` expression containing a JSON
+/// object (or `null`), into a `STRUCT` with `fields`. `depth` is used to
+/// generate unique names for nested subqueries.
+fn struct_from_json_sql(
+    json: &str,
+    fields: &[BqStructField],
+    depth: usize,
+) -> Result<String> {
+    let mut field_exprs = Vec::with_capacity(fields.len());
+    for field in fields {
+        let name = field.name.as_ref().ok_or_else(|| {
+            format_err!("cannot import STRUCT with unnamed fields into BigQuery yet")
+        })?;
+        let path = format!("'$.{}'", name);
+        let expr = match &field.ty {
+            BqDataType::NonArray(BqNonArrayDataType::Struct(fields)) => {
+                let nested_json = format!("JSON_QUERY({}, {})", json, path);
+                struct_from_json_sql(&nested_json, fields, depth + 1)?
+            }
+            BqDataType::NonArray(ty) => {
+                let value = format!("JSON_VALUE({}, {})", json, path);
+                scalar_from_json_value_sql(&value, ty)?
+            }
+            BqDataType::Array(BqNonArrayDataType::Struct(fields)) => {
+                array_of_structs_from_json_sql(json, &path, fields, depth + 1)?
+            }
+            BqDataType::Array(ty) => {
+                let elem = format!("e{}", depth);
+                format!(
+                    "ARRAY(SELECT {value} FROM UNNEST(JSON_VALUE_ARRAY({json}, {path})) AS {elem} WITH OFFSET AS i{depth} ORDER BY i{depth})",
+                    value = scalar_from_json_value_sql(&elem, ty)?,
+                    json = json,
+                    path = path,
+                    elem = elem,
+                    depth = depth,
+                )
+            }
+        };
+        field_exprs.push(format!("{} AS {}", expr, name));
+    }
+    Ok(format!(
+        "IF(COALESCE({json}, 'null') = 'null', NULL, STRUCT({fields}))",
+        json = json,
+        fields = field_exprs.join(", "),
+    ))
+}
+
+/// Generate SQL which converts the JSON array at `path` in `json` into an
+/// `ARRAY<STRUCT<...>>` with `fields`.
+fn array_of_structs_from_json_sql(
+    json: &str,
+    path: &str,
+    fields: &[BqStructField],
+    depth: usize,
+) -> Result<String> {
+    let elem = format!("e{}", depth);
+    Ok(format!(
+        "ARRAY(SELECT {value} FROM UNNEST(JSON_QUERY_ARRAY({json}, {path})) AS {elem} WITH OFFSET AS i{depth} ORDER BY i{depth})",
+        value = struct_from_json_sql(&elem, fields, depth + 1)?,
+        json = json,
+        path = path,
+        elem = elem,
+        depth = depth,
+    ))
+}
+
+/// Generate SQL which converts `value`, a `STRING` expression containing a
+/// scalar extracted from JSON, into `ty`.
+fn scalar_from_json_value_sql(value: &str, ty: &BqNonArrayDataType) -> Result<String> {
+    match ty {
+        BqNonArrayDataType::BigNumeric
+        | BqNonArrayDataType::Bool
+        | BqNonArrayDataType::Date
+        | BqNonArrayDataType::Float64
+        | BqNonArrayDataType::Int64
+        | BqNonArrayDataType::Interval
+        | BqNonArrayDataType::Numeric
+        | BqNonArrayDataType::Timestamp => Ok(format!("CAST({} AS {})", value, ty)),
+        BqNonArrayDataType::Bytes => Ok(format!("FROM_BASE64({})", value)),
+        BqNonArrayDataType::Datetime => Ok(parse_datetime_sql(value)),
+        BqNonArrayDataType::String => Ok(value.to_owned()),
+        BqNonArrayDataType::Time => Ok(parse_time_sql(value)),
+        BqNonArrayDataType::Geography
+        | BqNonArrayDataType::Json
+        | BqNonArrayDataType::Struct(_) => Err(format_err!(
+            "cannot import STRUCT fields of type {} into BigQuery yet",
+            ty,
+        )),
+    }
+}
+
 /// A column mode.
 #[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
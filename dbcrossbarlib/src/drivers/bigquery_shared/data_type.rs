@@ -1,7 +1,7 @@
 //! Data types supported BigQuery.
 
 use serde::{de::Error as DeError, Deserialize, Deserializer, Serialize, Serializer};
-use std::{borrow::Cow, collections::HashSet, fmt, result};
+use std::{borrow::Cow, collections::HashSet, convert::TryFrom, fmt, result};
 
 use super::{
     column::{BqColumn, Mode},
@@ -45,6 +45,30 @@ pub(crate) enum Usage {
     FinalTable,
 }
 
+/// How should we represent portable `json` columns in BigQuery?
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum JsonType {
+    /// Use a `STRING` containing serialized JSON. This works everywhere.
+    #[default]
+    String,
+    /// Use BigQuery's native `JSON` type.
+    Json,
+}
+
+impl JsonType {
+    /// Should a column of type `data_type` be stored using this `JsonType`?
+    /// This includes arrays of JSON values, which we store as a single JSON
+    /// array.
+    pub(crate) fn applies_to(data_type: &DataType) -> bool {
+        match data_type {
+            DataType::Json => true,
+            DataType::Array(elem) => elem.as_ref() == &DataType::Json,
+            _ => false,
+        }
+    }
+}
+
 /// A BigQuery data type.
 ///
 /// This is marked `pub` instead of `pub(crate)` because of limitations in
@@ -73,12 +97,13 @@ impl BqDataType {
             (DataType::Array(_), Usage::CsvLoad) => {
                 Ok(BqDataType::NonArray(BqNonArrayDataType::String))
             }
+            // BigQuery can't store `JSON` values in an `ARRAY`, so store a
+            // `STRING` containing a serialized JSON array instead. This is what
+            // `to_data_type` would produce for an array of structs, anyway.
+            (DataType::Array(nested), _) if nested.as_ref() == &DataType::Json => {
+                Ok(BqDataType::NonArray(BqNonArrayDataType::String))
+            }
             (DataType::Array(nested), _) => {
-                if let DataType::Json = nested.as_ref() {
-                    return Err(format_err!(
-                        "cannot represent arrays of JSON in BigQuery yet"
-                    ));
-                }
                 let bq_nested = BqNonArrayDataType::for_data_type(nested, usage)?;
                 Ok(BqDataType::Array(bq_nested))
             }
@@ -245,6 +270,7 @@ impl Serialize for BqRecordOrNonArrayDataType {
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[allow(dead_code)]
 pub enum BqNonArrayDataType {
+    BigNumeric,
     Bool,
     Bytes,
     Date,
@@ -252,6 +278,8 @@ pub enum BqNonArrayDataType {
     Float64,
     Geography,
     Int64,
+    Interval,
+    Json,
     Numeric,
     String,
     Struct(Vec<BqStructField>),
@@ -294,8 +322,27 @@ impl BqNonArrayDataType {
                 Ok(BqNonArrayDataType::Struct(vec![field]))
             }
             DataType::Bool => Ok(BqNonArrayDataType::Bool),
+            DataType::Bytes => Ok(BqNonArrayDataType::Bytes),
             DataType::Date => Ok(BqNonArrayDataType::Date),
             DataType::Decimal => Ok(BqNonArrayDataType::Numeric),
+            // BigQuery's `NUMERIC` has 38 digits of precision, 9 of which are
+            // after the decimal point.
+            DataType::DecimalWithPrecision(p)
+                if p.scale <= 9 && p.integer_digits() <= 29 =>
+            {
+                Ok(BqNonArrayDataType::Numeric)
+            }
+            // Larger decimals need `BIGNUMERIC`, which supports 38 digits on
+            // either side of the decimal point.
+            DataType::DecimalWithPrecision(p)
+                if p.scale <= 38 && p.integer_digits() <= 38 =>
+            {
+                Ok(BqNonArrayDataType::BigNumeric)
+            }
+            DataType::DecimalWithPrecision(p) => Err(format_err!(
+                "BigQuery BIGNUMERIC cannot hold NUMERIC{} values (it supports at most 38 digits before the decimal point and 38 after)",
+                p,
+            )),
             DataType::Float32 => Ok(BqNonArrayDataType::Float64),
             DataType::Float64 => Ok(BqNonArrayDataType::Float64),
             DataType::GeoJson(srid) if *srid == Srid::wgs84() => {
@@ -305,15 +352,55 @@ impl BqNonArrayDataType {
             DataType::Int16 => Ok(BqNonArrayDataType::Int64),
             DataType::Int32 => Ok(BqNonArrayDataType::Int64),
             DataType::Int64 => Ok(BqNonArrayDataType::Int64),
+            // We can't load `INTERVAL` values from a CSV file, so we load them
+            // as ISO 8601 strings and `CAST` them later.
+            DataType::Interval if usage == Usage::CsvLoad => {
+                Ok(BqNonArrayDataType::String)
+            }
+            DataType::Interval => Ok(BqNonArrayDataType::Interval),
             DataType::Json => Ok(BqNonArrayDataType::String),
+            // BigQuery has no enumerated types, so we just store the label.
+            DataType::OneOf(_) => Ok(BqNonArrayDataType::String),
             // Unknown types will become strings.
             DataType::Other(_unknown_type) => Ok(BqNonArrayDataType::String),
-            DataType::Text => Ok(BqNonArrayDataType::String),
+            // Like arrays, structs can't be loaded from a CSV file, so we load
+            // them as JSON strings and convert them later.
+            DataType::Struct(_) if usage == Usage::CsvLoad => {
+                Ok(BqNonArrayDataType::String)
+            }
+            DataType::Struct(fields) => {
+                let bq_fields = fields
+                    .iter()
+                    .map(|field| {
+                        Ok(BqStructField {
+                            name: Some(ColumnName::try_from(&field.name)?),
+                            ty: BqDataType::for_data_type(&field.data_type, usage)?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(BqNonArrayDataType::Struct(bq_fields))
+            }
+            DataType::Text | DataType::TextWithMaxLength(_) => {
+                Ok(BqNonArrayDataType::String)
+            }
+            // BigQuery is picky about the format of civil times in CSV files,
+            // so we load them as strings and parse them using SQL.
+            DataType::Time | DataType::TimestampWithoutTimeZone
+                if usage == Usage::CsvLoad =>
+            {
+                Ok(BqNonArrayDataType::String)
+            }
+            DataType::Time => Ok(BqNonArrayDataType::Time),
             // Timestamps without timezones will be mapped to `DATETIME`.
             DataType::TimestampWithoutTimeZone => Ok(BqNonArrayDataType::Datetime),
             // As far as I can tell, BigQuery will convert timestamps with timezones
             // to UTC.
             DataType::TimestampWithTimeZone => Ok(BqNonArrayDataType::Timestamp),
+            // BigQuery has no network address types, so we store them as
+            // strings.
+            DataType::Cidr | DataType::Inet | DataType::MacAddr => {
+                Ok(BqNonArrayDataType::String)
+            }
             DataType::Uuid => Ok(BqNonArrayDataType::String),
         }
     }
@@ -321,20 +408,21 @@ impl BqNonArrayDataType {
     /// Convert this `BqNonArrayDataType` to a portable `DataType`.
     pub(crate) fn to_data_type(&self) -> Result<DataType> {
         match self {
+            BqNonArrayDataType::BigNumeric => Ok(DataType::Decimal),
             BqNonArrayDataType::Bool => Ok(DataType::Bool),
+            BqNonArrayDataType::Bytes => Ok(DataType::Bytes),
             BqNonArrayDataType::Date => Ok(DataType::Date),
             BqNonArrayDataType::Numeric => Ok(DataType::Decimal),
             BqNonArrayDataType::Float64 => Ok(DataType::Float64),
             BqNonArrayDataType::Geography => Ok(DataType::GeoJson(Srid::wgs84())),
             BqNonArrayDataType::Int64 => Ok(DataType::Int64),
+            BqNonArrayDataType::Interval => Ok(DataType::Interval),
+            BqNonArrayDataType::Json => Ok(DataType::Json),
             BqNonArrayDataType::String => Ok(DataType::Text),
             BqNonArrayDataType::Datetime => Ok(DataType::TimestampWithoutTimeZone),
             BqNonArrayDataType::Struct(_) => Ok(DataType::Json),
+            BqNonArrayDataType::Time => Ok(DataType::Time),
             BqNonArrayDataType::Timestamp => Ok(DataType::TimestampWithTimeZone),
-            BqNonArrayDataType::Bytes | BqNonArrayDataType::Time => Err(format_err!(
-                "cannot convert {} to portable type (yet)",
-                self,
-            )),
         }
     }
 
@@ -362,6 +450,36 @@ impl BqNonArrayDataType {
             _ => true,
         }
     }
+
+    /// Is this a `STRUCT` that BigQuery can load directly from the JSON object
+    /// in one of our CSV cells? This requires named fields, and field types
+    /// that BigQuery can parse from plain JSON values.
+    pub(crate) fn is_json_loadable_struct(&self) -> bool {
+        match self {
+            BqNonArrayDataType::Struct(fields) => fields.iter().all(|field| {
+                field.name.is_some()
+                    && match &field.ty {
+                        BqDataType::Array(ty) | BqDataType::NonArray(ty) => {
+                            ty.is_json_loadable_field()
+                        }
+                    }
+            }),
+            _ => false,
+        }
+    }
+
+    /// Can BigQuery load a `STRUCT` field of this type from JSON? `GEOGRAPHY`
+    /// fields would be nested GeoJSON objects, and `INTERVAL` and `JSON`
+    /// fields need SQL to parse them.
+    fn is_json_loadable_field(&self) -> bool {
+        match self {
+            BqNonArrayDataType::Struct(_) => self.is_json_loadable_struct(),
+            BqNonArrayDataType::Geography
+            | BqNonArrayDataType::Interval
+            | BqNonArrayDataType::Json => false,
+            _ => true,
+        }
+    }
 }
 
 impl<'de> Deserialize<'de> for BqNonArrayDataType {
@@ -383,6 +501,7 @@ impl<'de> Deserialize<'de> for BqNonArrayDataType {
 impl fmt::Display for BqNonArrayDataType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            BqNonArrayDataType::BigNumeric => write!(f, "BIGNUMERIC"),
             BqNonArrayDataType::Bool => write!(f, "BOOL"),
             BqNonArrayDataType::Bytes => write!(f, "BYTES"),
             BqNonArrayDataType::Date => write!(f, "DATE"),
@@ -390,6 +509,8 @@ impl fmt::Display for BqNonArrayDataType {
             BqNonArrayDataType::Float64 => write!(f, "FLOAT64"),
             BqNonArrayDataType::Geography => write!(f, "GEOGRAPHY"),
             BqNonArrayDataType::Int64 => write!(f, "INT64"),
+            BqNonArrayDataType::Interval => write!(f, "INTERVAL"),
+            BqNonArrayDataType::Json => write!(f, "JSON"),
             BqNonArrayDataType::Numeric => write!(f, "NUMERIC"),
             BqNonArrayDataType::String => write!(f, "STRING"),
             BqNonArrayDataType::Struct(fields) => {
@@ -461,12 +582,44 @@ fn nested_arrays() {
     );
 }
 
+#[test]
+fn arrays_of_json_are_stored_as_strings() {
+    let input = DataType::Array(Box::new(DataType::Json));
+    for &usage in &[Usage::CsvLoad, Usage::FinalTable] {
+        assert_eq!(
+            BqDataType::for_data_type(&input, usage).unwrap(),
+            BqDataType::NonArray(BqNonArrayDataType::String),
+        );
+    }
+}
+
+#[test]
+fn decimal_precision_must_fit_numeric() {
+    use crate::schema::DecimalPrecision;
+
+    let decimal =
+        |p, s| DataType::DecimalWithPrecision(DecimalPrecision::new(p, s).unwrap());
+    let bq = BqDataType::for_data_type(&decimal(38, 9), Usage::FinalTable).unwrap();
+    assert_eq!(format!("{}", bq), "NUMERIC");
+    assert!(BqDataType::for_data_type(&decimal(10, 2), Usage::FinalTable).is_ok());
+    for (p, s) in &[(38, 10), (30, 0), (76, 38), (40, 38)] {
+        let bq =
+            BqDataType::for_data_type(&decimal(*p, *s), Usage::FinalTable).unwrap();
+        assert_eq!(format!("{}", bq), "BIGNUMERIC");
+    }
+    assert!(BqDataType::for_data_type(&decimal(40, 39), Usage::FinalTable).is_err());
+    assert!(BqDataType::for_data_type(&decimal(39, 0), Usage::FinalTable).is_err());
+}
+
 #[test]
 fn parsing() {
     use std::convert::TryFrom;
     use BqDataType as DT;
     use BqNonArrayDataType as NADT;
     let examples = [
+        ("BIGNUMERIC", DT::NonArray(NADT::BigNumeric)),
+        // An alias for `BIGNUMERIC`.
+        ("BIGDECIMAL", DT::NonArray(NADT::BigNumeric)),
         ("BOOL", DT::NonArray(NADT::Bool)),
         // Not documented, but it exists.
         ("BOOLEAN", DT::NonArray(NADT::Bool)),
@@ -476,6 +629,8 @@ fn parsing() {
         ("FLOAT64", DT::NonArray(NADT::Float64)),
         ("GEOGRAPHY", DT::NonArray(NADT::Geography)),
         ("INT64", DT::NonArray(NADT::Int64)),
+        ("INTERVAL", DT::NonArray(NADT::Interval)),
+        ("JSON", DT::NonArray(NADT::Json)),
         ("NUMERIC", DT::NonArray(NADT::Numeric)),
         ("STRING", DT::NonArray(NADT::String)),
         ("TIME", DT::NonArray(NADT::Time)),
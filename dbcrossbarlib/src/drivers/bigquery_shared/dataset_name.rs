@@ -0,0 +1,71 @@
+//! BigQuery dataset names.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::{fmt, str::FromStr};
+
+use super::TableName;
+use crate::common::*;
+
+/// A BigQuery dataset name of the form `"project:dataset"`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct DatasetName {
+    /// The name of the Google Cloud project.
+    project: String,
+    /// The BigQuery dataset.
+    dataset: String,
+}
+
+impl DatasetName {
+    /// Return the name of this project.
+    pub(crate) fn project(&self) -> &str {
+        &self.project
+    }
+
+    /// Return the name of this dataset, without the project.
+    pub(crate) fn dataset(&self) -> &str {
+        &self.dataset
+    }
+
+    /// Return the name of the table `table` in this dataset.
+    pub(crate) fn table_name(&self, table: &str) -> TableName {
+        TableName::new(&self.project, &self.dataset, table)
+    }
+}
+
+impl fmt::Display for DatasetName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.project, self.dataset)
+    }
+}
+
+impl FromStr for DatasetName {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        lazy_static! {
+            static ref RE: Regex =
+                Regex::new("^([^:.]+):([^:.]+)$").expect("invalid regex in source");
+        }
+        let cap = RE.captures(s).ok_or_else(|| {
+            format_err!("could not parse BigQuery dataset name: {:?}", s)
+        })?;
+        Ok(DatasetName {
+            project: cap[1].to_owned(),
+            dataset: cap[2].to_owned(),
+        })
+    }
+}
+
+#[test]
+fn dataset_name_round_trip() {
+    let dataset_name = "project:dataset".parse::<DatasetName>().unwrap();
+    assert_eq!(dataset_name.project(), "project");
+    assert_eq!(dataset_name.dataset(), "dataset");
+    assert_eq!(dataset_name.to_string(), "project:dataset");
+    assert_eq!(
+        dataset_name.table_name("table").to_string(),
+        "project:dataset.table",
+    );
+    assert!("project:dataset.table".parse::<DatasetName>().is_err());
+}
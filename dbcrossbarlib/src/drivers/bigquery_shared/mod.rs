@@ -12,17 +12,25 @@
 
 use crate::common::*;
 
+mod clustering;
 mod column;
 mod column_name;
 mod data_type;
+mod dataset_name;
+mod partitioning;
 mod table;
 mod table_name;
+mod ttl;
 
+pub(crate) use self::clustering::*;
 pub(crate) use self::column::*;
 pub(crate) use self::column_name::*;
 pub(crate) use self::data_type::*;
+pub(crate) use self::dataset_name::*;
+pub(crate) use self::partitioning::*;
 pub(crate) use self::table::*;
 pub(crate) use self::table_name::*;
+pub(crate) use self::ttl::*;
 
 /// Convert an `IfExists` value to the corresponding `bq load` argument, or
 /// return an error if we can't.
@@ -34,11 +42,11 @@ pub(crate) fn if_exists_to_bq_load_arg(if_exists: &IfExists) -> Result<&'static
         IfExists::Append => Ok("--append_table"),
         // Since we specify our own upsert SQL, be sure bq doesn't helpfully
         // clear the table first.
-        IfExists::Upsert(_) => Ok("--noreplace"),
+        IfExists::Upsert(..) | IfExists::Merge(..) => Ok("--noreplace"),
         // We need to be careful about race conditions--we don't want to try to
         // emulate this if we can't do it natively.
         IfExists::Error => Err(format_err!(
-            "BigQuery only supports --if-exists={{overwrite,append,upsert-on:X}}"
+            "BigQuery only supports --if-exists={{overwrite,append,upsert-on:X,merge-on:X}}"
         )),
     }
 }
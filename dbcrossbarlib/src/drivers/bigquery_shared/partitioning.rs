@@ -0,0 +1,273 @@
+//! BigQuery time-partitioned tables.
+
+use serde::Deserialize;
+use std::{convert::TryFrom, fmt};
+
+use super::{BqDataType, BqNonArrayDataType, BqTable, ColumnName};
+use crate::common::*;
+
+/// How finely should we partition a table?
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub(crate) enum BqPartitionType {
+    /// One partition per hour.
+    Hour,
+    /// One partition per day.
+    #[default]
+    Day,
+    /// One partition per month.
+    Month,
+    /// One partition per year.
+    Year,
+}
+
+impl fmt::Display for BqPartitionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BqPartitionType::Hour => write!(f, "HOUR"),
+            BqPartitionType::Day => write!(f, "DAY"),
+            BqPartitionType::Month => write!(f, "MONTH"),
+            BqPartitionType::Year => write!(f, "YEAR"),
+        }
+    }
+}
+
+/// How to partition a BigQuery table by time.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct BqTimePartitioning {
+    /// The `DATE`, `DATETIME` or `TIMESTAMP` column to partition on. If this
+    /// is `None`, partition on ingestion time.
+    pub(crate) field: Option<ColumnName>,
+    /// How finely to partition the table.
+    pub(crate) partition_type: BqPartitionType,
+    /// Should queries against this table be required to filter on the
+    /// partitioning column?
+    pub(crate) require_partition_filter: bool,
+}
+
+impl BqTimePartitioning {
+    /// Build a `BqTimePartitioning` from `--to-arg` values, or return `None` if
+    /// no partitioning was requested.
+    pub(crate) fn from_dest_args(
+        field: Option<&str>,
+        partition_type: Option<BqPartitionType>,
+        require_partition_filter: bool,
+    ) -> Result<Option<BqTimePartitioning>> {
+        if field.is_none() && partition_type.is_none() {
+            if require_partition_filter {
+                return Err(format_err!(
+                    "require_partition_filter needs partition_field or partition_type"
+                ));
+            }
+            return Ok(None);
+        }
+        Ok(Some(BqTimePartitioning {
+            field: field.map(ColumnName::try_from).transpose()?,
+            partition_type: partition_type.unwrap_or_default(),
+            require_partition_filter,
+        }))
+    }
+
+    /// Make sure that `table` can be partitioned this way.
+    pub(crate) fn validate(&self, table: &BqTable) -> Result<()> {
+        let field = match &self.field {
+            Some(field) => field,
+            None => return Ok(()),
+        };
+        let col =
+            table
+                .columns
+                .iter()
+                .find(|c| &c.name == field)
+                .ok_or_else(|| {
+                    format_err!("cannot partition on unknown column {}", field)
+                })?;
+        match (col.bq_data_type()?, self.partition_type) {
+            (
+                BqDataType::NonArray(BqNonArrayDataType::Date),
+                BqPartitionType::Hour,
+            ) => Err(format_err!(
+                "cannot partition DATE column {} by HOUR",
+                field
+            )),
+            (BqDataType::NonArray(BqNonArrayDataType::Date), _)
+            | (BqDataType::NonArray(BqNonArrayDataType::Datetime), _)
+            | (BqDataType::NonArray(BqNonArrayDataType::Timestamp), _) => Ok(()),
+            (other, _) => Err(format_err!(
+                "cannot partition on column {} of type {}",
+                field,
+                other,
+            )),
+        }
+    }
+
+    /// Arguments to pass to `bq load` when it creates a table.
+    pub(crate) fn bq_load_args(&self) -> Vec<String> {
+        let mut args =
+            vec![format!("--time_partitioning_type={}", self.partition_type)];
+        if let Some(field) = &self.field {
+            args.push(format!("--time_partitioning_field={}", field.as_str()));
+        }
+        if self.require_partition_filter {
+            args.push("--require_partition_filter".to_owned());
+        }
+        args
+    }
+
+    /// Write a `PARTITION BY` clause for `table`.
+    pub(crate) fn write_partition_by_sql(
+        &self,
+        table: &BqTable,
+        f: &mut dyn Write,
+    ) -> Result<()> {
+        let field = match &self.field {
+            Some(field) => field,
+            None if self.partition_type == BqPartitionType::Day => {
+                write!(f, "PARTITION BY _PARTITIONDATE")?;
+                return Ok(());
+            }
+            None => {
+                write!(
+                    f,
+                    "PARTITION BY TIMESTAMP_TRUNC(_PARTITIONTIME, {})",
+                    self.partition_type,
+                )?;
+                return Ok(());
+            }
+        };
+        let col =
+            table
+                .columns
+                .iter()
+                .find(|c| &c.name == field)
+                .ok_or_else(|| {
+                    format_err!("cannot partition on unknown column {}", field)
+                })?;
+        match col.bq_data_type()? {
+            BqDataType::NonArray(BqNonArrayDataType::Date)
+                if self.partition_type == BqPartitionType::Day =>
+            {
+                write!(f, "PARTITION BY {}", col.name)?
+            }
+            BqDataType::NonArray(BqNonArrayDataType::Date) => write!(
+                f,
+                "PARTITION BY DATE_TRUNC({}, {})",
+                col.name, self.partition_type,
+            )?,
+            BqDataType::NonArray(BqNonArrayDataType::Datetime) => write!(
+                f,
+                "PARTITION BY DATETIME_TRUNC({}, {})",
+                col.name, self.partition_type,
+            )?,
+            _ => write!(
+                f,
+                "PARTITION BY TIMESTAMP_TRUNC({}, {})",
+                col.name, self.partition_type,
+            )?,
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn partitioned_tables_are_created_with_partition_by() {
+    use super::Usage;
+    use crate::schema::{Column, DataType};
+
+    let column = |name: &str, data_type: DataType| Column {
+        name: name.to_owned(),
+        is_nullable: true,
+        data_type,
+        comment: None,
+        default: None,
+        collation: None,
+    };
+    let columns = vec![
+        column("id", DataType::Int64),
+        column("day", DataType::Date),
+        column("created_at", DataType::TimestampWithTimeZone),
+        column("seen_at", DataType::TimestampWithoutTimeZone),
+    ];
+    let table = BqTable::for_table_name_and_columns(
+        "project:dataset.table".parse().unwrap(),
+        &columns,
+        Usage::FinalTable,
+    )
+    .unwrap();
+    let partition_by = |field: Option<&str>,
+                        partition_type,
+                        require_partition_filter| {
+        let partitioning = BqTimePartitioning::from_dest_args(
+            field,
+            partition_type,
+            require_partition_filter,
+        )
+        .unwrap()
+        .unwrap();
+        partitioning.validate(&table)?;
+        let mut sql = vec![];
+        partitioning.write_partition_by_sql(&table, &mut sql)?;
+        Ok::<_, Error>((String::from_utf8(sql).unwrap(), partitioning.bq_load_args()))
+    };
+
+    assert_eq!(
+        partition_by(Some("created_at"), None, true).unwrap(),
+        (
+            "PARTITION BY TIMESTAMP_TRUNC(created_at, DAY)".to_owned(),
+            vec![
+                "--time_partitioning_type=DAY".to_owned(),
+                "--time_partitioning_field=created_at".to_owned(),
+                "--require_partition_filter".to_owned(),
+            ],
+        ),
+    );
+    assert_eq!(
+        partition_by(Some("day"), None, false).unwrap().0,
+        "PARTITION BY day",
+    );
+    assert_eq!(
+        partition_by(Some("day"), Some(BqPartitionType::Month), false)
+            .unwrap()
+            .0,
+        "PARTITION BY DATE_TRUNC(day, MONTH)",
+    );
+    assert_eq!(
+        partition_by(Some("seen_at"), Some(BqPartitionType::Hour), false)
+            .unwrap()
+            .0,
+        "PARTITION BY DATETIME_TRUNC(seen_at, HOUR)",
+    );
+    assert_eq!(
+        partition_by(None, Some(BqPartitionType::Day), false)
+            .unwrap()
+            .0,
+        "PARTITION BY _PARTITIONDATE",
+    );
+    assert!(partition_by(Some("id"), None, false).is_err());
+    assert!(partition_by(Some("missing"), None, false).is_err());
+    assert!(partition_by(Some("day"), Some(BqPartitionType::Hour), false).is_err());
+    assert!(BqTimePartitioning::from_dest_args(None, None, true).is_err());
+    assert_eq!(
+        BqTimePartitioning::from_dest_args(None, None, false).unwrap(),
+        None,
+    );
+
+    let table = table
+        .with_time_partitioning(
+            BqTimePartitioning::from_dest_args(Some("created_at"), None, true)
+                .unwrap(),
+        )
+        .unwrap();
+    let mut sql = vec![];
+    table
+        .write_import_sql(
+            &"project:dataset.temp".parse().unwrap(),
+            &IfExists::Overwrite,
+            &mut sql,
+        )
+        .unwrap();
+    let sql = String::from_utf8(sql).unwrap();
+    assert!(sql.contains(
+        "\n)\nPARTITION BY TIMESTAMP_TRUNC(created_at, DAY)\nOPTIONS(require_partition_filter=true);\n",
+    ));
+}
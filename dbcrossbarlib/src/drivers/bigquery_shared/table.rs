@@ -1,5 +1,6 @@
 //! Table-related support for BigQuery.
 
+use chrono::{DateTime, SecondsFormat, Utc};
 use itertools::Itertools;
 use serde_json;
 use std::{
@@ -9,7 +10,10 @@ use std::{
     iter::FromIterator,
 };
 
-use super::{BqColumn, ColumnBigQueryExt, ColumnName, TableName, Usage};
+use super::{
+    BqClustering, BqColumn, BqDataType, BqNonArrayDataType, BqStr, BqTimePartitioning,
+    BqTtl, ColumnBigQueryExt, ColumnName, Ident, JsonType, TableName, Usage,
+};
 use crate::clouds::gcloud::bigquery;
 use crate::common::*;
 use crate::schema::{Column, Table};
@@ -59,6 +63,13 @@ pub(crate) struct BqTable {
     pub(crate) name: TableName,
     /// The columns of this table.
     pub(crate) columns: Vec<BqColumn>,
+    /// How this table should be partitioned, if at all.
+    pub(crate) time_partitioning: Option<BqTimePartitioning>,
+    /// How this table should be clustered, if at all.
+    pub(crate) clustering: Option<BqClustering>,
+    /// How long rows in this table should be kept, if we add an expiration
+    /// column.
+    pub(crate) ttl: Option<BqTtl>,
 }
 
 impl BqTable {
@@ -93,7 +104,111 @@ impl BqTable {
                 }
             })
             .collect::<Result<Vec<BqColumn>>>()?;
-        Ok(BqTable { name, columns })
+        Ok(BqTable {
+            name,
+            columns,
+            time_partitioning: None,
+            clustering: None,
+            ttl: None,
+        })
+    }
+
+    /// Store any portable `json` columns in `columns` using `json_type`.
+    /// `columns` must be the same columns we used to create this table.
+    pub(crate) fn with_json_type(
+        mut self,
+        columns: &[Column],
+        json_type: JsonType,
+    ) -> BqTable {
+        for (bq_col, col) in self.columns.iter_mut().zip(columns) {
+            bq_col.use_json_type(col, json_type);
+        }
+        self
+    }
+
+    /// Partition this table using `time_partitioning`, if specified.
+    pub(crate) fn with_time_partitioning(
+        mut self,
+        time_partitioning: Option<BqTimePartitioning>,
+    ) -> Result<BqTable> {
+        if let Some(time_partitioning) = &time_partitioning {
+            time_partitioning.validate(&self)?;
+        }
+        self.time_partitioning = time_partitioning;
+        Ok(self)
+    }
+
+    /// Cluster this table using `clustering`, if specified.
+    pub(crate) fn with_clustering(
+        mut self,
+        clustering: Option<BqClustering>,
+    ) -> Result<BqTable> {
+        if let Some(clustering) = &clustering {
+            clustering.validate(&self)?;
+        }
+        self.clustering = clustering;
+        Ok(self)
+    }
+
+    /// Add an expiration column using `ttl`, if specified, and expire
+    /// partitions after the same amount of time. If this table isn't already
+    /// partitioned, partition it by ingestion time.
+    pub(crate) fn with_ttl(mut self, ttl: Option<BqTtl>) -> Result<BqTable> {
+        if let Some(ttl) = &ttl {
+            ttl.validate(&self)?;
+            if self.time_partitioning.is_none() {
+                self.time_partitioning = Some(ttl.default_time_partitioning());
+            }
+        }
+        self.ttl = ttl;
+        Ok(self)
+    }
+
+    /// Extra arguments to pass to `bq load`, describing how to create this
+    /// table.
+    pub(crate) fn bq_load_args(&self) -> Vec<String> {
+        let mut args = vec![];
+        if let Some(time_partitioning) = &self.time_partitioning {
+            args.extend(time_partitioning.bq_load_args());
+        }
+        if let Some(clustering) = &self.clustering {
+            args.extend(clustering.bq_load_args());
+        }
+        if let Some(ttl) = &self.ttl {
+            args.extend(ttl.bq_load_args());
+        }
+        args
+    }
+
+    /// Should we load this table from newline-delimited JSON instead of CSV?
+    ///
+    /// We do this when the table contains `ARRAY` or `STRUCT` columns, which
+    /// BigQuery can load directly from JSON but not from CSV. We can't handle
+    /// structs with unnamed fields (including the wrappers around nested
+    /// arrays) this way yet, `INTERVAL` columns, which we load as ISO 8601
+    /// strings, or native `JSON` columns, which we load as strings and parse
+    /// using SQL.
+    ///
+    /// This `BqTable` should have been created with `Usage::FinalTable`.
+    pub(crate) fn prefers_json_load(&self) -> Result<bool> {
+        let mut has_nested = false;
+        for col in &self.columns {
+            match col.bq_data_type()? {
+                BqDataType::Array(ty @ BqNonArrayDataType::Struct(_))
+                | BqDataType::NonArray(ty @ BqNonArrayDataType::Struct(_)) => {
+                    if !ty.is_json_loadable_struct() {
+                        return Ok(false);
+                    }
+                    has_nested = true;
+                }
+                BqDataType::Array(BqNonArrayDataType::Interval)
+                | BqDataType::NonArray(BqNonArrayDataType::Interval)
+                | BqDataType::NonArray(BqNonArrayDataType::Json) => return Ok(false),
+                BqDataType::Array(_) => has_nested = true,
+                BqDataType::NonArray(_) => {}
+            }
+        }
+        Ok(has_nested)
     }
 
     /// Given a table name, look up the schema and return a `BqTable`.
@@ -133,6 +248,9 @@ impl BqTable {
                     }
                 })
                 .collect::<Result<Vec<_>>>()?,
+            time_partitioning: self.time_partitioning.clone(),
+            clustering: self.clustering.clone(),
+            ttl: self.ttl.clone(),
         })
     }
 
@@ -146,6 +264,8 @@ impl BqTable {
         Ok(Table {
             name: self.name.to_string(),
             columns,
+            primary_key: vec![],
+            indexes: vec![],
         })
     }
 
@@ -177,19 +297,54 @@ impl BqTable {
         // Create the table with appropriate options. We do this explicitly so
         // that we preserve the NULLABLE property of each column.
         let create_table_type = match if_exists {
-            IfExists::Append | IfExists::Upsert(_) => CreateTableType::IfNotExists,
+            IfExists::Append | IfExists::Upsert(..) | IfExists::Merge(..) => {
+                CreateTableType::IfNotExists
+            }
             IfExists::Error => CreateTableType::Plain,
             IfExists::Overwrite => CreateTableType::OrReplace,
         };
         self.write_create_table_sql(create_table_type, f)?;
         writeln!(f)?;
 
+        // Make sure we have a column to record soft deletes in.
+        if let IfExists::Merge(_, MergeDelete::SoftDelete(column)) = if_exists {
+            let column = ColumnName::try_from(column)?;
+            if self.columns.iter().any(|c| c.name == column) {
+                return Err(format_err!(
+                    "soft-delete column {} must not be in the input data",
+                    column,
+                ));
+            }
+            writeln!(
+                f,
+                "ALTER TABLE {} ADD COLUMN IF NOT EXISTS {} TIMESTAMP;",
+                self.name.dotted_and_quoted(),
+                column,
+            )?;
+            writeln!(f)?;
+        }
+
         match if_exists {
             IfExists::Append | IfExists::Error | IfExists::Overwrite => {
                 self.write_insert_sql(source_table_name, f)?;
             }
-            IfExists::Upsert(merge_keys) => {
-                self.write_merge_sql(source_table_name, merge_keys, f)?;
+            IfExists::Upsert(merge_keys, update_columns) => {
+                self.write_merge_sql(
+                    source_table_name,
+                    merge_keys,
+                    update_columns.as_deref(),
+                    None,
+                    f,
+                )?;
+            }
+            IfExists::Merge(merge_keys, delete) => {
+                self.write_merge_sql(
+                    source_table_name,
+                    merge_keys,
+                    None,
+                    Some(delete),
+                    f,
+                )?;
             }
         }
 
@@ -215,14 +370,42 @@ impl BqTable {
             if i > 0 {
                 writeln!(f, ",")?;
             }
-            write!(f, "    {} {}", col.name, col.bq_data_type()?)?;
-            if col.is_not_null() {
-                write!(f, " NOT NULL")?;
+            write!(f, "    ")?;
+            write_column_sql(col, col.is_not_null(), f)?;
+        }
+        if let Some(ttl) = &self.ttl {
+            if !self.columns.is_empty() {
+                writeln!(f, ",")?;
             }
+            ttl.write_column_sql(f)?;
         }
 
-        // Write the footer.
-        writeln!(f, "\n);")?;
+        // Write the footer, including any table options.
+        write!(f, "\n)")?;
+        if let Some(time_partitioning) = &self.time_partitioning {
+            writeln!(f)?;
+            time_partitioning.write_partition_by_sql(self, f)?;
+        }
+        if let Some(clustering) = &self.clustering {
+            writeln!(f)?;
+            clustering.write_cluster_by_sql(f)?;
+        }
+        let require_partition_filter = self
+            .time_partitioning
+            .as_ref()
+            .map(|p| p.require_partition_filter)
+            .unwrap_or(false);
+        let mut options = vec![];
+        if require_partition_filter {
+            options.push("require_partition_filter=true".to_owned());
+        }
+        if let Some(ttl) = &self.ttl {
+            options.push(format!("partition_expiration_days={}", ttl.days));
+        }
+        if !options.is_empty() {
+            write!(f, "\nOPTIONS({})", options.join(", "))?;
+        }
+        writeln!(f, ";")?;
         Ok(())
     }
 
@@ -253,11 +436,14 @@ impl BqTable {
         Ok(())
     }
 
-    /// Generate a `MERGE INTO` statement using the specified columns.
+    /// Generate a `MERGE INTO` statement using the specified columns. If
+    /// `delete` is specified, also remove rows which aren't in the source.
     fn write_merge_sql(
         &self,
         source_table_name: &TableName,
         merge_keys: &[String],
+        update_columns: Option<&[String]>,
+        delete: Option<&MergeDelete>,
         f: &mut dyn Write,
     ) -> Result<()> {
         // Convert `merge_keys` into actual column values for consistency.
@@ -291,6 +477,37 @@ impl BqTable {
         let merge_key_table =
             merge_keys.iter().map(|c| &c.name).collect::<HashSet<_>>();
 
+        // If we were asked to only update some columns, look them up, too.
+        let update_table = update_columns
+            .map(|update_columns| {
+                update_columns
+                    .iter()
+                    .map(|name| -> Result<ColumnName> {
+                        let col_name = ColumnName::try_from(name)?;
+                        if !column_map.contains_key(&col_name) {
+                            return Err(format_err!(
+                                "cannot update column {} because it is not in table",
+                                name,
+                            ));
+                        }
+                        Ok(col_name)
+                    })
+                    .collect::<Result<HashSet<ColumnName>>>()
+            })
+            .transpose()?;
+        let should_update = |name: &ColumnName| match &update_table {
+            Some(update_table) => update_table.contains(name),
+            None => !merge_key_table.contains(name),
+        };
+
+        // If we soft-delete rows, look up the column we use.
+        let soft_delete_column = match delete {
+            Some(MergeDelete::SoftDelete(column)) => {
+                Some(ColumnName::try_from(column)?)
+            }
+            _ => None,
+        };
+
         // A helper function to generate import SQL for a column.
         let col_import_expr = |c: &BqColumn, idx: usize| -> String {
             let mut buf = vec![];
@@ -312,7 +529,7 @@ WHEN NOT MATCHED THEN INSERT (
     {columns}
 ) VALUES (
     {values}
-);"#,
+){delete};"#,
             dest_table = self.name().dotted_and_quoted(),
             temp_table = source_table_name.dotted_and_quoted(),
             key_comparisons = merge_keys
@@ -328,15 +545,23 @@ WHEN NOT MATCHED THEN INSERT (
                 .columns
                 .iter()
                 .enumerate()
-                .filter_map(|(idx, c)| if merge_key_table.contains(&c.name) {
-                    None
-                } else {
+                .filter_map(|(idx, c)| if should_update(&c.name) {
                     Some(format!(
                         "{col} = {expr}",
                         col = c.name,
                         expr = col_import_expr(c, idx),
                     ))
+                } else {
+                    None
                 })
+                // Updated rows were loaded again, so they expire later.
+                .chain(
+                    self.ttl
+                        .iter()
+                        .map(|ttl| format!("{} = DEFAULT", ttl.column))
+                )
+                // Rows which reappear are no longer deleted.
+                .chain(soft_delete_column.iter().map(|c| format!("{} = NULL", c)))
                 .join(",\n    "),
             columns = self.columns.iter().map(|c| &c.name).join(",\n    "),
             values = self
@@ -345,15 +570,25 @@ WHEN NOT MATCHED THEN INSERT (
                 .enumerate()
                 .map(|(idx, c)| col_import_expr(c, idx))
                 .join(",\n    "),
+            delete = match (delete, &soft_delete_column) {
+                (None, _) => "".to_owned(),
+                (Some(_), None) => "\nWHEN NOT MATCHED BY SOURCE THEN DELETE".to_owned(),
+                (Some(_), Some(c)) => format!(
+                    "\nWHEN NOT MATCHED BY SOURCE AND dest.{col} IS NULL THEN UPDATE SET\n    {col} = CURRENT_TIMESTAMP()",
+                    col = c,
+                ),
+            },
         )?;
         Ok(())
     }
 
     /// Generate SQL which `SELECT`s from a table, producing something we can
-    /// export to CSV.
+    /// export to CSV. If `as_of` is specified, read the table as it existed at
+    /// that time.
     pub(crate) fn write_export_sql(
         &self,
         source_args: &SourceArguments<Verified>,
+        as_of: Option<&DateTime<Utc>>,
         f: &mut dyn Write,
     ) -> Result<()> {
         write!(f, "SELECT ")?;
@@ -363,9 +598,12 @@ WHEN NOT MATCHED THEN INSERT (
             }
             col.write_export_select_expr(f)?;
         }
-        write!(f, " FROM {}", self.name.dotted_and_quoted())?;
-        if let Some(where_clause) = source_args.where_clause() {
-            write!(f, " WHERE ({})", where_clause)?;
+        self.write_from_and_where_sql(source_args, as_of, f)?;
+        if let Some(order_by) = source_args.order_by() {
+            write!(f, " ORDER BY {}", order_by)?;
+        }
+        if let Some(limit) = source_args.limit() {
+            write!(f, " LIMIT {}", limit)?;
         }
         Ok(())
     }
@@ -373,14 +611,545 @@ WHEN NOT MATCHED THEN INSERT (
     pub(crate) fn write_count_sql(
         &self,
         source_args: &SourceArguments<Verified>,
+        as_of: Option<&DateTime<Utc>>,
         f: &mut dyn Write,
     ) -> Result<()> {
         write!(f, "SELECT COUNT(*) AS `count`")?;
+        self.write_from_and_where_sql(source_args, as_of, f)
+    }
+
+    /// Write a `SELECT MAX(column) ...` statement for this table, returning
+    /// the largest value as a string.
+    pub(crate) fn write_max_sql(
+        &self,
+        source_args: &SourceArguments<Verified>,
+        as_of: Option<&DateTime<Utc>>,
+        column: &ColumnName,
+        f: &mut dyn Write,
+    ) -> Result<()> {
+        write!(f, "SELECT CAST(MAX(`{}`) AS STRING) AS `max_value`", column)?;
+        self.write_from_and_where_sql(source_args, as_of, f)
+    }
+
+    /// Write the `FROM` and `WHERE` clauses used to read from this table.
+    fn write_from_and_where_sql(
+        &self,
+        source_args: &SourceArguments<Verified>,
+        as_of: Option<&DateTime<Utc>>,
+        f: &mut dyn Write,
+    ) -> Result<()> {
         write!(f, " FROM {}", self.name.dotted_and_quoted())?;
+        if let Some(as_of) = as_of {
+            write!(
+                f,
+                " FOR SYSTEM_TIME AS OF TIMESTAMP '{}'",
+                as_of.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            )?;
+        }
         if let Some(where_clause) = source_args.where_clause() {
             write!(f, " WHERE ({})", where_clause)?;
         }
+        Ok(())
+    }
+
+    /// Write an `ALTER TABLE` statement adding any columns in this table which
+    /// are missing from `existing`, which should be the current schema of this
+    /// table. The new columns are always nullable, because the table may
+    /// already contain rows. Returns `false` if no columns are missing.
+    pub(crate) fn write_add_missing_columns_sql(
+        &self,
+        existing: &BqTable,
+        f: &mut dyn Write,
+    ) -> Result<bool> {
+        let existing_names = HashSet::<&ColumnName>::from_iter(
+            existing.columns.iter().map(|c| &c.name),
+        );
+        let missing = self
+            .columns
+            .iter()
+            .filter(|c| !existing_names.contains(&c.name))
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            return Ok(false);
+        }
+        write!(f, "ALTER TABLE {}", self.name.dotted_and_quoted())?;
+        for (i, col) in missing.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "\nADD COLUMN IF NOT EXISTS ")?;
+            write_column_sql(col, false, f)?;
+        }
+        writeln!(f, ";")?;
+        Ok(true)
+    }
 
+    /// Generate SQL which takes a snapshot of this table, if it exists, and
+    /// saves it as `snapshot_name`.
+    pub(crate) fn write_create_snapshot_sql(
+        &self,
+        snapshot_name: &TableName,
+        f: &mut dyn Write,
+    ) -> Result<()> {
+        writeln!(
+            f,
+            r#"IF EXISTS (
+    SELECT 1 FROM {project}.{dataset}.INFORMATION_SCHEMA.TABLES
+    WHERE table_name = {table}
+) THEN
+    CREATE SNAPSHOT TABLE {snapshot} CLONE {source};
+END IF;"#,
+            project = Ident(self.name.project()),
+            dataset = Ident(self.name.dataset()),
+            table = BqStr(self.name.table()),
+            snapshot = snapshot_name.dotted_and_quoted(),
+            source = self.name.dotted_and_quoted(),
+        )?;
         Ok(())
     }
 }
+
+/// Write the declaration of `col` for `CREATE TABLE` or `ALTER TABLE`,
+/// including `NOT NULL` only if `not_null` is true.
+fn write_column_sql(col: &BqColumn, not_null: bool, f: &mut dyn Write) -> Result<()> {
+    write!(f, "{} {}", col.name, col.bq_data_type()?)?;
+    if let Some(collation) = col.collation() {
+        write!(f, " COLLATE {}", BqStr(collation))?;
+    }
+    if let Some(default) = col.default_value_expression() {
+        write!(f, " DEFAULT {}", default)?;
+    }
+    if not_null {
+        write!(f, " NOT NULL")?;
+    }
+    if let Some(description) = col.description() {
+        write!(f, " OPTIONS(description={})", BqStr(description))?;
+    }
+    Ok(())
+}
+
+#[test]
+fn export_sql_supports_time_travel() {
+    let table = BqTable {
+        name: "project:dataset.table".parse().unwrap(),
+        columns: vec![],
+        time_partitioning: None,
+        clustering: None,
+        ttl: None,
+    };
+    let source_args = SourceArguments::for_temporary()
+        .verify(Features::empty())
+        .unwrap();
+    let as_of = "2020-01-02T03:04:05Z".parse::<DateTime<Utc>>().unwrap();
+    let mut sql = vec![];
+    table
+        .write_count_sql(&source_args, Some(&as_of), &mut sql)
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(sql).unwrap(),
+        "SELECT COUNT(*) AS `count` FROM `project`.`dataset`.`table` FOR SYSTEM_TIME AS OF TIMESTAMP '2020-01-02T03:04:05Z'",
+    );
+}
+
+#[test]
+fn prefers_json_load_only_for_loadable_nested_types() {
+    use crate::schema::{DataType, StructField};
+
+    let table_with = |data_type: DataType| {
+        let columns = vec![Column {
+            name: "col".to_owned(),
+            is_nullable: true,
+            data_type,
+            comment: None,
+            default: None,
+            collation: None,
+        }];
+        BqTable::for_table_name_and_columns(
+            "project:dataset.table".parse().unwrap(),
+            &columns,
+            Usage::FinalTable,
+        )
+        .unwrap()
+    };
+    let array_of = |data_type: DataType| DataType::Array(Box::new(data_type));
+
+    assert!(!table_with(DataType::Text).prefers_json_load().unwrap());
+    assert!(table_with(array_of(DataType::Int64))
+        .prefers_json_load()
+        .unwrap());
+    assert!(!table_with(array_of(array_of(DataType::Int64)))
+        .prefers_json_load()
+        .unwrap());
+    assert!(!table_with(array_of(DataType::Interval))
+        .prefers_json_load()
+        .unwrap());
+
+    let struct_of = |data_type: DataType| {
+        DataType::Struct(vec![StructField {
+            name: "x".to_owned(),
+            is_nullable: true,
+            data_type,
+        }])
+    };
+    assert!(table_with(struct_of(DataType::Float64))
+        .prefers_json_load()
+        .unwrap());
+    assert!(table_with(array_of(struct_of(array_of(DataType::Text))))
+        .prefers_json_load()
+        .unwrap());
+    assert!(!table_with(struct_of(DataType::Interval))
+        .prefers_json_load()
+        .unwrap());
+}
+
+#[test]
+fn interval_columns_are_cast_on_import() {
+    use crate::schema::DataType;
+
+    let columns = vec![Column {
+        name: "duration".to_owned(),
+        is_nullable: true,
+        data_type: DataType::Interval,
+        comment: None,
+        default: None,
+        collation: None,
+    }];
+    let name = "project:dataset.table".parse::<TableName>().unwrap();
+    let csv_table =
+        BqTable::for_table_name_and_columns(name.clone(), &columns, Usage::CsvLoad)
+            .unwrap();
+    assert_eq!(
+        csv_table.columns[0].bq_data_type().unwrap(),
+        BqDataType::NonArray(BqNonArrayDataType::String),
+    );
+    let final_table =
+        BqTable::for_table_name_and_columns(name, &columns, Usage::FinalTable)
+            .unwrap();
+    let mut sql = vec![];
+    final_table.columns[0]
+        .write_import_select_expr(&mut sql, 0)
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(sql).unwrap(),
+        "CAST(duration AS INTERVAL) AS duration",
+    );
+}
+
+#[test]
+fn column_descriptions_are_included_in_final_tables() {
+    use crate::schema::DataType;
+
+    let columns = vec![Column {
+        name: "id".to_owned(),
+        is_nullable: false,
+        data_type: DataType::Int64,
+        comment: Some("The user's ID.".to_owned()),
+        default: None,
+        collation: None,
+    }];
+    let name = "project:dataset.table".parse::<TableName>().unwrap();
+    let table =
+        BqTable::for_table_name_and_columns(name.clone(), &columns, Usage::FinalTable)
+            .unwrap();
+    assert_eq!(table.to_table().unwrap().columns, columns);
+    let mut sql = vec![];
+    table
+        .write_create_table_sql(CreateTableType::Plain, &mut sql)
+        .unwrap();
+    let sql = String::from_utf8(sql).unwrap();
+    assert!(
+        sql.contains(r#"id INT64 NOT NULL OPTIONS(description='The user\'s ID.')"#)
+    );
+
+    let temp_table =
+        BqTable::for_table_name_and_columns(name, &columns, Usage::CsvLoad).unwrap();
+    assert_eq!(temp_table.columns[0].description(), None);
+}
+
+#[test]
+fn missing_columns_are_added_as_nullable() {
+    use crate::schema::DataType;
+
+    let column = |name: &str, is_nullable| Column {
+        name: name.to_owned(),
+        is_nullable,
+        data_type: DataType::Text,
+        comment: None,
+        default: None,
+        collation: None,
+    };
+    let name = "project:dataset.table".parse::<TableName>().unwrap();
+    let existing = BqTable::for_table_name_and_columns(
+        name.clone(),
+        &[column("ID", false)],
+        Usage::FinalTable,
+    )
+    .unwrap();
+    let wanted = BqTable::for_table_name_and_columns(
+        name,
+        &[column("id", false), column("a", false), column("b", true)],
+        Usage::FinalTable,
+    )
+    .unwrap();
+
+    let mut sql = vec![];
+    assert!(wanted
+        .write_add_missing_columns_sql(&existing, &mut sql)
+        .unwrap());
+    assert_eq!(
+        String::from_utf8(sql).unwrap(),
+        "ALTER TABLE `project`.`dataset`.`table`
+ADD COLUMN IF NOT EXISTS a STRING,
+ADD COLUMN IF NOT EXISTS b STRING;
+",
+    );
+    assert!(!existing
+        .write_add_missing_columns_sql(&wanted, &mut vec![])
+        .unwrap());
+}
+
+#[test]
+fn json_columns_can_use_native_json_type() {
+    use crate::schema::DataType;
+
+    let columns = vec![
+        Column {
+            name: "doc".to_owned(),
+            is_nullable: true,
+            data_type: DataType::Json,
+            comment: None,
+            default: None,
+            collation: None,
+        },
+        Column {
+            name: "label".to_owned(),
+            is_nullable: true,
+            data_type: DataType::Text,
+            comment: None,
+            default: None,
+            collation: None,
+        },
+        Column {
+            name: "docs".to_owned(),
+            is_nullable: true,
+            data_type: DataType::Array(Box::new(DataType::Json)),
+            comment: None,
+            default: None,
+            collation: None,
+        },
+    ];
+    let name = "project:dataset.table".parse::<TableName>().unwrap();
+    let string_table =
+        BqTable::for_table_name_and_columns(name.clone(), &columns, Usage::FinalTable)
+            .unwrap()
+            .with_json_type(&columns, JsonType::String);
+    assert_eq!(
+        string_table.columns[0].bq_data_type().unwrap(),
+        BqDataType::NonArray(BqNonArrayDataType::String),
+    );
+
+    let json_table =
+        BqTable::for_table_name_and_columns(name, &columns, Usage::FinalTable)
+            .unwrap()
+            .with_json_type(&columns, JsonType::Json);
+    assert_eq!(
+        json_table.columns[0].bq_data_type().unwrap(),
+        BqDataType::NonArray(BqNonArrayDataType::Json),
+    );
+    assert_eq!(
+        json_table.columns[1].bq_data_type().unwrap(),
+        BqDataType::NonArray(BqNonArrayDataType::String),
+    );
+    assert_eq!(
+        string_table.columns[2].bq_data_type().unwrap(),
+        BqDataType::NonArray(BqNonArrayDataType::String),
+    );
+    assert_eq!(
+        json_table.columns[2].bq_data_type().unwrap(),
+        BqDataType::NonArray(BqNonArrayDataType::Json),
+    );
+    assert!(!json_table.prefers_json_load().unwrap());
+
+    let temp_name = "project:dataset.temp".parse::<TableName>().unwrap();
+    let mut sql = vec![];
+    json_table
+        .write_import_sql(&temp_name, &IfExists::Overwrite, &mut sql)
+        .unwrap();
+    let sql = String::from_utf8(sql).unwrap();
+    assert!(sql.contains("doc JSON"));
+    assert!(sql.contains("PARSE_JSON(doc) AS doc"));
+
+    let source_args = SourceArguments::for_temporary()
+        .verify(Features::empty())
+        .unwrap();
+    let mut sql = vec![];
+    json_table
+        .write_export_sql(&source_args, None, &mut sql)
+        .unwrap();
+    let sql = String::from_utf8(sql).unwrap();
+    assert!(sql.contains("TO_JSON_STRING(doc) AS doc"));
+}
+
+#[test]
+fn struct_columns_are_imported_from_json() {
+    use crate::schema::{DataType, StructField};
+
+    let columns = vec![Column {
+        name: "point".to_owned(),
+        is_nullable: true,
+        data_type: DataType::Struct(vec![
+            StructField {
+                name: "x".to_owned(),
+                is_nullable: true,
+                data_type: DataType::Float64,
+            },
+            StructField {
+                name: "tags".to_owned(),
+                is_nullable: true,
+                data_type: DataType::Array(Box::new(DataType::Text)),
+            },
+        ]),
+        comment: None,
+        default: None,
+        collation: None,
+    }];
+    let name = "project:dataset.table".parse::<TableName>().unwrap();
+    let csv_table =
+        BqTable::for_table_name_and_columns(name.clone(), &columns, Usage::CsvLoad)
+            .unwrap();
+    assert_eq!(
+        csv_table.columns[0].bq_data_type().unwrap(),
+        BqDataType::NonArray(BqNonArrayDataType::String),
+    );
+
+    let final_table =
+        BqTable::for_table_name_and_columns(name, &columns, Usage::FinalTable)
+            .unwrap();
+    assert_eq!(
+        final_table.columns[0].bq_data_type().unwrap().to_string(),
+        "STRUCT<x FLOAT64,tags ARRAY<STRING>>",
+    );
+    let mut schema = vec![];
+    final_table.write_json_schema(&mut schema).unwrap();
+    let schema = String::from_utf8(schema).unwrap();
+    assert!(schema.contains(r#""type": "RECORD""#));
+    assert!(schema.contains(r#""mode": "REPEATED""#));
+
+    let temp_name = "project:dataset.temp".parse::<TableName>().unwrap();
+    let mut sql = vec![];
+    final_table
+        .write_import_sql(&temp_name, &IfExists::Overwrite, &mut sql)
+        .unwrap();
+    let sql = String::from_utf8(sql).unwrap();
+    assert!(sql.contains(
+        "CREATE TEMP FUNCTION ImportJson_0(input STRING)\nRETURNS STRUCT<x FLOAT64,tags ARRAY<STRING>>",
+    ));
+    assert!(sql.contains(
+        "IF(COALESCE(input, 'null') = 'null', NULL, STRUCT(CAST(JSON_VALUE(input, '$.x') AS FLOAT64) AS x, ARRAY(SELECT e0 FROM UNNEST(JSON_VALUE_ARRAY(input, '$.tags')) AS e0 WITH OFFSET AS i0 ORDER BY i0) AS tags))",
+    ));
+    assert!(sql.contains("ImportJson_0(point) AS point"));
+}
+
+#[test]
+fn civil_times_are_parsed_on_import() {
+    use crate::schema::DataType;
+
+    let columns = vec![
+        Column {
+            name: "seen_at".to_owned(),
+            is_nullable: true,
+            data_type: DataType::TimestampWithoutTimeZone,
+            comment: None,
+            default: None,
+            collation: None,
+        },
+        Column {
+            name: "opens_at".to_owned(),
+            is_nullable: true,
+            data_type: DataType::Time,
+            comment: None,
+            default: None,
+            collation: None,
+        },
+    ];
+    let name = "project:dataset.table".parse::<TableName>().unwrap();
+    let csv_table =
+        BqTable::for_table_name_and_columns(name.clone(), &columns, Usage::CsvLoad)
+            .unwrap();
+    for col in &csv_table.columns {
+        assert_eq!(
+            col.bq_data_type().unwrap(),
+            BqDataType::NonArray(BqNonArrayDataType::String),
+        );
+    }
+    let final_table =
+        BqTable::for_table_name_and_columns(name, &columns, Usage::FinalTable)
+            .unwrap();
+    let import_expr = |idx: usize| {
+        let mut sql = vec![];
+        final_table.columns[idx]
+            .write_import_select_expr(&mut sql, idx)
+            .unwrap();
+        String::from_utf8(sql).unwrap()
+    };
+    assert_eq!(
+        import_expr(0),
+        "COALESCE(SAFE.PARSE_DATETIME('%Y-%m-%dT%H:%M:%E*S', seen_at), SAFE.PARSE_DATETIME('%Y-%m-%d %H:%M:%E*S', seen_at), SAFE.PARSE_DATETIME('%Y-%m-%dT%H:%M', seen_at), SAFE.PARSE_DATETIME('%Y-%m-%d %H:%M', seen_at), PARSE_DATETIME('%Y-%m-%dT%H:%M:%E*S', seen_at)) AS seen_at",
+    );
+    assert_eq!(
+        import_expr(1),
+        "COALESCE(SAFE.PARSE_TIME('%H:%M:%E*S', opens_at), SAFE.PARSE_TIME('%H:%M', opens_at), PARSE_TIME('%H:%M:%E*S', opens_at)) AS opens_at",
+    );
+}
+
+#[test]
+fn merge_sql_respects_upsert_and_merge_options() {
+    use crate::schema::DataType;
+
+    let column = |name: &str, is_nullable: bool| Column {
+        name: name.to_owned(),
+        is_nullable,
+        data_type: DataType::Text,
+        comment: None,
+        default: None,
+        collation: None,
+    };
+    let columns = vec![
+        column("id", false),
+        column("name", true),
+        column("email", true),
+    ];
+    let table = BqTable::for_table_name_and_columns(
+        "project:dataset.table".parse().unwrap(),
+        &columns,
+        Usage::FinalTable,
+    )
+    .unwrap();
+    let temp_name = "project:dataset.temp".parse::<TableName>().unwrap();
+    let import_sql = |if_exists: &IfExists| -> Result<String> {
+        let mut sql = vec![];
+        table.write_import_sql(&temp_name, if_exists, &mut sql)?;
+        Ok(String::from_utf8(sql)?)
+    };
+
+    let sql = import_sql(&"upsert-on:id:update:email".parse().unwrap()).unwrap();
+    assert!(sql.contains("WHEN MATCHED THEN UPDATE SET\n    email = temp.email\n"));
+    let sql = import_sql(&"upsert-on:id".parse().unwrap()).unwrap();
+    assert!(sql.contains(
+        "WHEN MATCHED THEN UPDATE SET\n    name = temp.name,\n    email = temp.email\n"
+    ));
+    assert!(import_sql(&"upsert-on:id:update:missing".parse().unwrap()).is_err());
+
+    let sql = import_sql(&"merge-on:id".parse().unwrap()).unwrap();
+    assert!(sql.ends_with("\nWHEN NOT MATCHED BY SOURCE THEN DELETE;\n"));
+    let sql =
+        import_sql(&"merge-on:id:soft-delete:deleted_at".parse().unwrap()).unwrap();
+    assert!(sql.contains(
+        "ALTER TABLE `project`.`dataset`.`table` ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMP;\n"
+    ));
+    assert!(sql.contains("    email = temp.email,\n    deleted_at = NULL\n"));
+    assert!(sql.ends_with(
+        "\nWHEN NOT MATCHED BY SOURCE AND dest.deleted_at IS NULL THEN UPDATE SET\n    deleted_at = CURRENT_TIMESTAMP();\n"
+    ));
+    assert!(import_sql(&"merge-on:id:soft-delete:name".parse().unwrap()).is_err());
+}
@@ -4,6 +4,7 @@ use lazy_static::lazy_static;
 use regex::Regex;
 use std::{fmt, str::FromStr};
 
+use super::DatasetName;
 use crate::common::*;
 use crate::drivers::bigquery::BigQueryLocator;
 
@@ -19,11 +20,30 @@ pub(crate) struct TableName {
 }
 
 impl TableName {
+    /// Construct a table name from its components.
+    pub(crate) fn new(project: &str, dataset: &str, table: &str) -> TableName {
+        TableName {
+            project: project.to_owned(),
+            dataset: dataset.to_owned(),
+            table: table.to_owned(),
+        }
+    }
+
     /// Return the name of this project.
     pub(crate) fn project(&self) -> &str {
         &self.project
     }
 
+    /// Return the name of this table's dataset, without the project.
+    pub(crate) fn dataset(&self) -> &str {
+        &self.dataset
+    }
+
+    /// Return the bare name of this table, without the project or dataset.
+    pub(crate) fn table(&self) -> &str {
+        &self.table
+    }
+
     /// Return a value which will be formatted as
     /// `"\`project\`.\`dataset\`.\`table\`"`, with "backtick" quoting.
     ///
@@ -37,22 +57,17 @@ impl TableName {
         &self,
         temporary_storage: &TemporaryStorage,
     ) -> Result<TableName> {
-        lazy_static! {
-            static ref DATASET_RE: Regex =
-                Regex::new("^([^:.]+):([^:.]+)$").expect("invalid regex in source");
-        }
-
         // Decide on what project and dataset to use.
         let temp = temporary_storage.find_scheme(BigQueryLocator::scheme());
         let (project, dataset) = if let Some(temp) = temp {
             // We have a `--temporary=bigquery:...` argument, so extract a project
             // and dataset name.
-            let cap = DATASET_RE
-                .captures(&temp[BigQueryLocator::scheme().len()..])
-                .ok_or_else(|| {
-                    format_err!("could not parse BigQuery dataset name: {:?}", temp)
-                })?;
-            (cap[1].to_owned(), cap[2].to_owned())
+            let dataset_name =
+                temp[BigQueryLocator::scheme().len()..].parse::<DatasetName>()?;
+            (
+                dataset_name.project().to_owned(),
+                dataset_name.dataset().to_owned(),
+            )
         } else {
             // We don't have a `--temporary=bigquery:...` argument, so just pick
             // something.
@@ -147,3 +162,27 @@ impl<'a> fmt::Display for Ident<'a> {
         }
     }
 }
+
+/// A BigQuery string literal, for formatting purposes.
+pub(crate) struct BqStr<'a>(pub(crate) &'a str);
+
+impl<'a> fmt::Display for BqStr<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'")?;
+        for c in self.0.chars() {
+            match c {
+                '\\' => write!(f, "\\\\")?,
+                '\'' => write!(f, "\\'")?,
+                '\n' => write!(f, "\\n")?,
+                '\r' => write!(f, "\\r")?,
+                _ => write!(f, "{}", c)?,
+            }
+        }
+        write!(f, "'")
+    }
+}
+
+#[test]
+fn bq_str_escapes_special_characters() {
+    assert_eq!(BqStr("it's a\\b\n").to_string(), "'it\\'s a\\\\b\\n'");
+}
@@ -0,0 +1,161 @@
+//! Row-level expiration for BigQuery tables.
+
+use std::convert::TryFrom;
+
+use super::{BqPartitionType, BqTable, BqTimePartitioning, ColumnName};
+use crate::common::*;
+
+/// The default name for our expiration column.
+const DEFAULT_TTL_COLUMN: &str = "expires_at";
+
+/// How long should loaded rows be kept?
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct BqTtl {
+    /// A `TIMESTAMP` column we add to the table, which records when each row
+    /// expires.
+    pub(crate) column: ColumnName,
+    /// How many days to keep each row after it's loaded.
+    pub(crate) days: u32,
+}
+
+impl BqTtl {
+    /// Build a `BqTtl` from `--to-arg` values, or return `None` if no TTL was
+    /// requested.
+    pub(crate) fn from_dest_args(
+        column: Option<&str>,
+        days: Option<u32>,
+    ) -> Result<Option<BqTtl>> {
+        match (column, days) {
+            (None, None) => Ok(None),
+            (Some(_), None) => Err(format_err!("ttl_column requires ttl_days")),
+            (_, Some(0)) => Err(format_err!("ttl_days must be greater than 0")),
+            (column, Some(days)) => Ok(Some(BqTtl {
+                column: ColumnName::try_from(column.unwrap_or(DEFAULT_TTL_COLUMN))?,
+                days,
+            })),
+        }
+    }
+
+    /// Make sure we can add our column to `table`.
+    pub(crate) fn validate(&self, table: &BqTable) -> Result<()> {
+        if table.columns.iter().any(|c| c.name == self.column) {
+            return Err(format_err!(
+                "cannot add TTL column {} because the table already has a column with that name",
+                self.column,
+            ));
+        }
+        Ok(())
+    }
+
+    /// The partitioning to use if the user doesn't ask for any. We partition on
+    /// ingestion time, so that each partition expires `days` after it was
+    /// loaded.
+    pub(crate) fn default_time_partitioning(&self) -> BqTimePartitioning {
+        BqTimePartitioning {
+            field: None,
+            partition_type: BqPartitionType::Day,
+            require_partition_filter: false,
+        }
+    }
+
+    /// An SQL expression which computes the expiration time of a row loaded
+    /// now.
+    pub(crate) fn expires_at_sql(&self) -> String {
+        format!(
+            "TIMESTAMP_ADD(CURRENT_TIMESTAMP(), INTERVAL {} DAY)",
+            self.days
+        )
+    }
+
+    /// Write the column declaration for our TTL column.
+    pub(crate) fn write_column_sql(&self, f: &mut dyn Write) -> Result<()> {
+        write!(
+            f,
+            "    {} TIMESTAMP DEFAULT {}",
+            self.column,
+            self.expires_at_sql(),
+        )?;
+        Ok(())
+    }
+
+    /// Arguments to pass to `bq load` when it creates a table.
+    pub(crate) fn bq_load_args(&self) -> Vec<String> {
+        vec![format!(
+            "--time_partitioning_expiration={}",
+            u64::from(self.days) * 24 * 60 * 60,
+        )]
+    }
+}
+
+#[test]
+fn ttl_tables_expire_partitions() {
+    use super::Usage;
+    use crate::schema::{Column, DataType};
+
+    let columns = vec![Column {
+        name: "id".to_owned(),
+        is_nullable: false,
+        data_type: DataType::Int64,
+        comment: None,
+        default: None,
+        collation: None,
+    }];
+    let ttl = BqTtl::from_dest_args(None, Some(30)).unwrap().unwrap();
+    let table = BqTable::for_table_name_and_columns(
+        "project:dataset.table".parse().unwrap(),
+        &columns,
+        Usage::FinalTable,
+    )
+    .unwrap()
+    .with_ttl(Some(ttl.clone()))
+    .unwrap();
+    assert_eq!(
+        table.bq_load_args(),
+        vec![
+            "--time_partitioning_type=DAY".to_owned(),
+            "--time_partitioning_expiration=2592000".to_owned(),
+        ],
+    );
+
+    let mut sql = vec![];
+    table
+        .write_import_sql(
+            &"project:dataset.temp".parse().unwrap(),
+            &IfExists::Overwrite,
+            &mut sql,
+        )
+        .unwrap();
+    let sql = String::from_utf8(sql).unwrap();
+    assert!(sql.contains(
+        "    id INT64 NOT NULL,
+    expires_at TIMESTAMP DEFAULT TIMESTAMP_ADD(CURRENT_TIMESTAMP(), INTERVAL 30 DAY)
+)
+PARTITION BY _PARTITIONDATE
+OPTIONS(partition_expiration_days=30);
+",
+    ));
+    assert!(sql.contains("INSERT INTO `project`.`dataset`.`table` (id)\n"));
+
+    let mut sql = vec![];
+    table
+        .write_import_sql(
+            &"project:dataset.temp".parse().unwrap(),
+            &IfExists::Upsert(vec!["id".to_owned()], None),
+            &mut sql,
+        )
+        .unwrap();
+    let sql = String::from_utf8(sql).unwrap();
+    assert!(sql.contains("WHEN MATCHED THEN UPDATE SET\n    expires_at = DEFAULT\n"));
+
+    assert!(BqTtl::from_dest_args(Some("expires_at"), None).is_err());
+    assert!(BqTtl::from_dest_args(None, Some(0)).is_err());
+    let clashing = BqTtl::from_dest_args(Some("id"), Some(1)).unwrap().unwrap();
+    assert!(BqTable::for_table_name_and_columns(
+        "project:dataset.table".parse().unwrap(),
+        &columns,
+        Usage::FinalTable,
+    )
+    .unwrap()
+    .with_ttl(Some(clashing))
+    .is_err());
+}
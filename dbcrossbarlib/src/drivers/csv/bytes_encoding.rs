@@ -0,0 +1,184 @@
+//! Alternate encodings for `bytes` columns in CSV files.
+//!
+//! Our CSV interchange format always represents binary data using base64. But
+//! some tools prefer hexadecimal, so we allow the user to choose a different
+//! encoding for CSV files, and convert to and from base64 as needed.
+
+use serde::Deserialize;
+use std::io;
+
+use crate::common::*;
+use crate::schema::{Column, DataType};
+
+/// How should we represent `bytes` columns in a CSV file?
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum BytesEncoding {
+    /// Standard base64, as used by our interchange format.
+    #[default]
+    Base64,
+    /// Lowercase hexadecimal digits. When reading, we also accept uppercase
+    /// digits and a PostgreSQL-style `\x` prefix.
+    Hex,
+}
+
+impl BytesEncoding {
+    /// Decode a single value.
+    fn decode(self, value: &str) -> Result<Vec<u8>> {
+        match self {
+            BytesEncoding::Base64 => Ok(base64::decode(value)
+                .with_context(|_| format!("cannot parse {:?} as base64", value))?),
+            BytesEncoding::Hex => {
+                let digits = value.strip_prefix("\\x").unwrap_or(value);
+                Ok(hex::decode(digits)
+                    .with_context(|_| format!("cannot parse {:?} as hex", value))?)
+            }
+        }
+    }
+
+    /// Encode a single value.
+    fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            BytesEncoding::Base64 => base64::encode(bytes),
+            BytesEncoding::Hex => hex::encode(bytes),
+        }
+    }
+
+    /// Convert a single value from this encoding to `to`.
+    fn convert(self, to: BytesEncoding, value: &str) -> Result<String> {
+        Ok(to.encode(&self.decode(value)?))
+    }
+}
+
+/// Copy CSV data from `rdr` to `wtr`, converting any `bytes` columns from
+/// the encoding `from` to the encoding `to`.
+///
+/// This is synchronous, and should be run using `spawn_sync_transform`.
+pub(crate) fn convert_bytes_encoding(
+    columns: &[Column],
+    from: BytesEncoding,
+    to: BytesEncoding,
+    rdr: Box<dyn Read>,
+    wtr: Box<dyn Write>,
+) -> Result<()> {
+    let mut rdr = csv::Reader::from_reader(rdr);
+    let mut wtr =
+        csv::Writer::from_writer(io::BufWriter::with_capacity(BUFFER_SIZE, wtr));
+
+    // Check to make sure our CSV headers and table column names match.
+    let headers = rdr.headers()?;
+    if headers.len() != columns.len() {
+        return Err(format_err!(
+            "CSV file has {} columns, but schema has {}",
+            headers.len(),
+            columns.len(),
+        ));
+    }
+    for (idx, (hdr, col)) in headers.iter().zip(columns.iter()).enumerate() {
+        if hdr != col.name {
+            return Err(format_err!(
+                "CSV file has column {} at position {}, but schema has {}",
+                hdr,
+                idx,
+                col.name,
+            ));
+        }
+    }
+    wtr.write_record(headers)?;
+
+    let mut cells = vec![];
+    for row in rdr.records() {
+        let row = row?;
+        cells.clear();
+        for (cell, col) in row.iter().zip(columns.iter()) {
+            let cell = convert_cell(cell, &col.data_type, from, to)
+                .with_context(|_| format!("could not convert column {}", col.name))?;
+            cells.push(cell);
+        }
+        wtr.write_record(&cells)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Convert a single CSV cell, leaving everything except `bytes` values alone.
+fn convert_cell(
+    cell: &str,
+    data_type: &DataType,
+    from: BytesEncoding,
+    to: BytesEncoding,
+) -> Result<String> {
+    if cell.is_empty() {
+        return Ok(String::new());
+    }
+    match data_type {
+        DataType::Bytes => from.convert(to, cell),
+        DataType::Array(elem) if **elem == DataType::Bytes => {
+            let elems = serde_json::from_str::<Vec<Option<String>>>(cell)?;
+            let elems = elems
+                .into_iter()
+                .map(|e| e.map(|e| from.convert(to, &e)).transpose())
+                .collect::<Result<Vec<_>>>()?;
+            Ok(serde_json::to_string(&elems)?)
+        }
+        _ => Ok(cell.to_owned()),
+    }
+}
+
+#[test]
+fn convert_between_hex_and_base64() {
+    let columns = vec![
+        Column {
+            name: "id".to_owned(),
+            is_nullable: false,
+            data_type: DataType::Int64,
+            comment: None,
+            default: None,
+            collation: None,
+        },
+        Column {
+            name: "data".to_owned(),
+            is_nullable: true,
+            data_type: DataType::Bytes,
+            comment: None,
+            default: None,
+            collation: None,
+        },
+        Column {
+            name: "chunks".to_owned(),
+            is_nullable: true,
+            data_type: DataType::Array(Box::new(DataType::Bytes)),
+            comment: None,
+            default: None,
+            collation: None,
+        },
+    ];
+    let hex = "id,data,chunks\n1,\\xDEADbeef,\"[\"\"0001\"\",null]\"\n2,,\n";
+    let base64 = "id,data,chunks\n1,3q2+7w==,\"[\"\"AAE=\"\",null]\"\n2,,\n";
+
+    let tmp_dir = tempdir::TempDir::new("bytes_encoding").unwrap();
+    let out_path = tmp_dir.path().join("out.csv");
+
+    convert_bytes_encoding(
+        &columns,
+        BytesEncoding::Hex,
+        BytesEncoding::Base64,
+        Box::new(hex.as_bytes()),
+        Box::new(std::fs::File::create(&out_path).unwrap()),
+    )
+    .unwrap();
+    assert_eq!(std::fs::read_to_string(&out_path).unwrap(), base64);
+
+    convert_bytes_encoding(
+        &columns,
+        BytesEncoding::Base64,
+        BytesEncoding::Hex,
+        Box::new(base64.as_bytes()),
+        Box::new(std::fs::File::create(&out_path).unwrap()),
+    )
+    .unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&out_path).unwrap(),
+        "id,data,chunks\n1,deadbeef,\"[\"\"0001\"\",null]\"\n2,,\n",
+    );
+}
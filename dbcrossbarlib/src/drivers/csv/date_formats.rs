@@ -0,0 +1,229 @@
+//! Custom formats for `date` and `timestamp` columns in CSV files.
+//!
+//! Our CSV interchange format uses ISO 8601 dates and timestamps. But vendor
+//! files often use formats like `20/07/1969 20:17`, so we allow the user to
+//! specify a [`chrono` format][fmt] for all `date` or `timestamp` columns, or
+//! for individual columns, and convert to our standard formats as we read.
+//!
+//! [fmt]: https://docs.rs/chrono/0.4/chrono/format/strftime/index.html
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use std::{collections::HashMap, io};
+
+use crate::common::*;
+use crate::schema::{Column, DataType};
+
+/// How are `date` and `timestamp` columns formatted in our input?
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DateFormats {
+    /// The format of all `date` columns.
+    pub(crate) date_format: Option<String>,
+
+    /// The format of all `timestamp` columns, with or without a time zone.
+    pub(crate) timestamp_format: Option<String>,
+
+    /// Formats for specific columns, overriding the formats above.
+    pub(crate) column_formats: HashMap<String, String>,
+}
+
+impl DateFormats {
+    /// Do we need to convert anything?
+    pub(crate) fn is_empty(&self) -> bool {
+        self.date_format.is_none()
+            && self.timestamp_format.is_none()
+            && self.column_formats.is_empty()
+    }
+
+    /// How should we parse `column`? Returns `None` if we can leave the
+    /// column alone.
+    fn format_for<'a>(&'a self, column: &Column) -> Option<&'a str> {
+        let default = match column.data_type {
+            DataType::Date => self.date_format.as_deref(),
+            DataType::TimestampWithoutTimeZone | DataType::TimestampWithTimeZone => {
+                self.timestamp_format.as_deref()
+            }
+            _ => None,
+        };
+        self.column_formats
+            .get(&column.name)
+            .map(|f| &f[..])
+            .or(default)
+    }
+
+    /// Make sure we only have custom formats for columns that exist and which
+    /// contain dates or timestamps.
+    pub(crate) fn check_columns(&self, columns: &[Column]) -> Result<()> {
+        for name in self.column_formats.keys() {
+            match columns.iter().find(|c| &c.name == name) {
+                Some(Column {
+                    data_type:
+                        DataType::Date
+                        | DataType::TimestampWithoutTimeZone
+                        | DataType::TimestampWithTimeZone,
+                    ..
+                }) => {}
+                Some(_) => {
+                    return Err(format_err!(
+                        "column_formats.{} must be a date or timestamp column",
+                        name,
+                    ))
+                }
+                None => {
+                    return Err(format_err!(
+                        "column_formats.{} isn't in the schema",
+                        name,
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Copy CSV data from `rdr` to `wtr`, converting any `date` or `timestamp`
+/// columns with custom formats to our interchange format. We find columns by
+/// name, and leave any columns which aren't in the schema alone.
+///
+/// This is synchronous, and should be run using `spawn_sync_transform`.
+pub(crate) fn convert_date_formats(
+    columns: &[Column],
+    formats: &DateFormats,
+    rdr: Box<dyn Read>,
+    wtr: Box<dyn Write>,
+) -> Result<()> {
+    let mut rdr = csv::Reader::from_reader(rdr);
+    let mut wtr =
+        csv::Writer::from_writer(io::BufWriter::with_capacity(BUFFER_SIZE, wtr));
+
+    let headers = rdr.headers()?.clone();
+    if headers.is_empty() {
+        // Empty streams have no cells to convert.
+        return Ok(());
+    }
+    let conversions = headers
+        .iter()
+        .map(|hdr| {
+            columns
+                .iter()
+                .find(|c| c.name == hdr)
+                .and_then(|c| Some((c, formats.format_for(c)?)))
+        })
+        .collect::<Vec<_>>();
+    wtr.write_record(&headers)?;
+
+    let mut row = csv::StringRecord::new();
+    let mut cells = vec![];
+    while rdr.read_record(&mut row)? {
+        cells.clear();
+        for (cell, conversion) in row.iter().zip(&conversions) {
+            match conversion {
+                Some((col, format)) if !cell.is_empty() => {
+                    let cell = convert_cell(cell, &col.data_type, format)
+                        .with_context(|_| {
+                            format!("could not convert column {}", col.name)
+                        })?;
+                    cells.push(cell);
+                }
+                _ => cells.push(cell.to_owned()),
+            }
+        }
+        wtr.write_record(&cells)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Parse `cell` using `format`, and return it in our interchange format.
+/// Timestamps without a time zone are assumed to be UTC when loaded into a
+/// column with a time zone.
+fn convert_cell(cell: &str, data_type: &DataType, format: &str) -> Result<String> {
+    let parse_err = || format!("cannot parse {:?} using format {:?}", cell, format);
+    Ok(match data_type {
+        DataType::Date => NaiveDate::parse_from_str(cell, format)
+            .with_context(|_| parse_err())?
+            .format("%Y-%m-%d")
+            .to_string(),
+        DataType::TimestampWithoutTimeZone => {
+            NaiveDateTime::parse_from_str(cell, format)
+                .with_context(|_| parse_err())?
+                .format("%Y-%m-%dT%H:%M:%S%.f")
+                .to_string()
+        }
+        DataType::TimestampWithTimeZone => {
+            let timestamp = match DateTime::parse_from_str(cell, format) {
+                Ok(timestamp) => timestamp.with_timezone(&Utc),
+                Err(_) => NaiveDateTime::parse_from_str(cell, format)
+                    .with_context(|_| parse_err())?
+                    .and_utc(),
+            };
+            timestamp.format("%Y-%m-%dT%H:%M:%S%.fZ").to_string()
+        }
+        _ => cell.to_owned(),
+    })
+}
+
+#[test]
+fn convert_custom_date_formats() {
+    let column = |name: &str, data_type: DataType| Column {
+        name: name.to_owned(),
+        is_nullable: true,
+        data_type,
+        comment: None,
+        default: None,
+        collation: None,
+    };
+    let columns = vec![
+        column("id", DataType::Int32),
+        column("born", DataType::Date),
+        column("seen", DataType::TimestampWithoutTimeZone),
+        column("seen_tz", DataType::TimestampWithTimeZone),
+        column("iso", DataType::Date),
+    ];
+    let mut formats = DateFormats {
+        date_format: Some("%d/%m/%Y".to_owned()),
+        timestamp_format: Some("%d/%m/%Y %H:%M".to_owned()),
+        column_formats: HashMap::new(),
+    };
+    formats
+        .column_formats
+        .insert("iso".to_owned(), "%Y-%m-%d".to_owned());
+    formats.check_columns(&columns).unwrap();
+
+    let run = |formats: &DateFormats, input: &'static str| -> Result<String> {
+        let tmp_dir = tempdir::TempDir::new("date_formats").unwrap();
+        let out_path = tmp_dir.path().join("out.csv");
+        convert_date_formats(
+            &columns,
+            formats,
+            Box::new(input.as_bytes()),
+            Box::new(std::fs::File::create(&out_path).unwrap()),
+        )?;
+        Ok(std::fs::read_to_string(&out_path).unwrap())
+    };
+    assert_eq!(
+        run(
+            &formats,
+            "id,born,seen,seen_tz,iso,extra\n1,20/07/1969,20/07/1969 20:17,20/07/1969 20:17,1969-07-20,x\n2,,,,,\n",
+        )
+        .unwrap(),
+        "id,born,seen,seen_tz,iso,extra\n1,1969-07-20,1969-07-20T20:17:00,1969-07-20T20:17:00Z,1969-07-20,x\n2,,,,,\n",
+    );
+    assert!(run(&formats, "born\n1969-07-20\n").is_err());
+
+    let mut with_zone = DateFormats::default();
+    with_zone
+        .column_formats
+        .insert("seen_tz".to_owned(), "%d/%m/%Y %H:%M %z".to_owned());
+    assert_eq!(
+        run(&with_zone, "seen_tz\n20/07/1969 21:17 +0100\n").unwrap(),
+        "seen_tz\n1969-07-20T20:17:00Z\n",
+    );
+
+    let mut bad = DateFormats::default();
+    bad.column_formats.insert("id".to_owned(), "%Y".to_owned());
+    assert!(bad.check_columns(&columns).is_err());
+    bad.column_formats.clear();
+    bad.column_formats
+        .insert("missing".to_owned(), "%Y".to_owned());
+    assert!(bad.check_columns(&columns).is_err());
+}
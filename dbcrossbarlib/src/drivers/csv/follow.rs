@@ -0,0 +1,98 @@
+//! Reading a CSV file as it grows, like `tail -f`.
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::{fs, io::AsyncReadExt, sync::mpsc, time::delay_for};
+
+use crate::common::*;
+use crate::tokio_glue::bytes_channel;
+
+/// Read `path` from the beginning, and then keep reading any rows appended to
+/// it, checking for new data every `interval`. Each chunk we return ends with
+/// a complete line. This stream never ends on its own.
+///
+/// If the file is truncated, we start again from the beginning, skipping the
+/// new header line.
+pub(crate) fn follow_file(
+    ctx: Context,
+    path: PathBuf,
+    interval: Duration,
+) -> BoxStream<BytesMut> {
+    let (sender, receiver) = bytes_channel(1);
+    let worker_ctx = ctx.clone();
+    ctx.spawn_worker(
+        async move { follow_file_helper(worker_ctx, path, interval, sender).await }
+            .boxed(),
+    );
+    receiver.boxed()
+}
+
+/// Implementation of `follow_file`, which runs as a background worker.
+async fn follow_file_helper(
+    ctx: Context,
+    path: PathBuf,
+    interval: Duration,
+    mut sender: mpsc::Sender<Result<BytesMut>>,
+) -> Result<()> {
+    debug!(ctx.log(), "following {}", path.display());
+    let mut file = open_file(&path).await?;
+    let mut pos: u64 = 0;
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut pending = BytesMut::new();
+    let mut skip_header = false;
+    loop {
+        let count = file
+            .read(&mut buffer)
+            .await
+            .with_context(|_| format!("cannot read {}", path.display()))?;
+        if count == 0 {
+            // We've caught up. If the file has shrunk, it was truncated (by
+            // `logrotate`'s `copytruncate`, for example), so start over.
+            // Otherwise, wait for more data.
+            if let Ok(metadata) = fs::metadata(&path).await {
+                if metadata.len() < pos {
+                    debug!(ctx.log(), "{} was truncated, reopening", path.display());
+                    file = open_file(&path).await?;
+                    pos = 0;
+                    pending.clear();
+                    skip_header = true;
+                    continue;
+                }
+            }
+            delay_for(interval).await;
+            continue;
+        }
+        pos += count as u64;
+        pending.extend_from_slice(&buffer[..count]);
+
+        if skip_header {
+            match pending.iter().position(|&b| b == b'\n') {
+                Some(idx) => {
+                    let _ = pending.split_to(idx + 1);
+                    skip_header = false;
+                }
+                None => continue,
+            }
+        }
+
+        // Only pass along complete lines, so that each chunk ends on a row
+        // boundary if the writer is in the middle of appending a row.
+        if let Some(idx) = pending.iter().rposition(|&b| b == b'\n') {
+            let chunk = pending.split_to(idx + 1);
+            trace!(ctx.log(), "sending {} appended bytes", chunk.len());
+            if sender.send(Ok(chunk)).await.is_err() {
+                debug!(ctx.log(), "stopped following {}", path.display());
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Open the file at `path`.
+async fn open_file(path: &Path) -> Result<fs::File> {
+    Ok(fs::File::open(path)
+        .await
+        .with_context(|_| format!("cannot open {}", path.display()))?)
+}
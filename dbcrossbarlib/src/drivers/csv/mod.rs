@@ -1,7 +1,10 @@
 //! Driver for working with CSV files.
 
 use csv;
-use std::{ffi::OsStr, fmt, path::PathBuf, str::FromStr};
+use serde::Deserialize;
+use std::{
+    collections::HashMap, ffi::OsStr, fmt, path::PathBuf, str::FromStr, time::Duration,
+};
 use tokio::{
     fs,
     io::{self, BufReader},
@@ -9,10 +12,125 @@ use tokio::{
 use walkdir::WalkDir;
 
 use crate::common::*;
-use crate::concat::concatenate_csv_streams;
+use crate::concat::{concatenate_csv_streams, strip_csv_header};
+use crate::count_rows::count_local_data_helper;
 use crate::csv_stream::csv_stream_name;
+use crate::driver_args::{deserialize_from_str, deserialize_optional_from_str};
+use crate::encryption::EncryptRecipient;
+use crate::limit::limit_csv_streams;
+use crate::name_template::{output_file_name, NameTemplate};
+use crate::partition::partition_csv_streams;
+use crate::row_filter::filter_csv_streams;
 use crate::schema::{Column, DataType, Table};
+use crate::select::select_csv_columns;
 use crate::tokio_glue::{copy_reader_to_stream, copy_stream_to_writer};
+use crate::transform::spawn_sync_transform;
+
+mod bytes_encoding;
+mod date_formats;
+mod follow;
+mod null_token;
+
+use self::bytes_encoding::{convert_bytes_encoding, BytesEncoding};
+use self::date_formats::{convert_date_formats, DateFormats};
+use self::follow::follow_file;
+use self::null_token::convert_null_token;
+
+/// Parsed version of `--from-arg` values.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CsvSourceArguments {
+    /// How are `bytes` columns encoded in our input?
+    #[serde(default)]
+    bytes_encoding: BytesEncoding,
+
+    /// Keep reading our input file as it grows, like `tail -f`.
+    #[serde(default, deserialize_with = "deserialize_from_str")]
+    follow: bool,
+
+    /// When following a file, how often should we check for new data?
+    #[serde(
+        default = "CsvSourceArguments::default_follow_interval_ms",
+        deserialize_with = "deserialize_from_str"
+    )]
+    follow_interval_ms: u64,
+
+    /// Does our input start with a header row? If not, we use the column
+    /// names from the schema.
+    #[serde(default = "default_true", deserialize_with = "deserialize_from_str")]
+    has_header: bool,
+
+    /// How is `NULL` represented in our input?
+    #[serde(default)]
+    null: String,
+
+    /// The `chrono` format of all `date` columns.
+    date_format: Option<String>,
+
+    /// The `chrono` format of all `timestamp` columns.
+    timestamp_format: Option<String>,
+
+    /// `chrono` formats for specific `date` or `timestamp` columns.
+    #[serde(default)]
+    column_formats: HashMap<String, String>,
+}
+
+impl CsvSourceArguments {
+    /// How are `date` and `timestamp` columns formatted in our input?
+    fn date_formats(&self) -> DateFormats {
+        DateFormats {
+            date_format: self.date_format.clone(),
+            timestamp_format: self.timestamp_format.clone(),
+            column_formats: self.column_formats.clone(),
+        }
+    }
+
+    /// Default value for `follow_interval_ms`.
+    fn default_follow_interval_ms() -> u64 {
+        1000
+    }
+}
+
+/// Parsed version of `--to-arg` values.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CsvDestinationArguments {
+    /// How should we encode `bytes` columns in our output?
+    #[serde(default)]
+    bytes_encoding: BytesEncoding,
+
+    /// Encrypt the files we write for this `age` or PGP recipient.
+    #[serde(default, deserialize_with = "deserialize_optional_from_str")]
+    encrypt_recipient: Option<EncryptRecipient>,
+
+    /// How should we represent `NULL` in our output?
+    #[serde(default)]
+    null: String,
+
+    /// A template for the names of the files we write to a directory.
+    #[serde(default, deserialize_with = "deserialize_optional_from_str")]
+    name_template: Option<NameTemplate>,
+
+    /// Write a Hive-style subdirectory for each value of this column.
+    #[serde(default)]
+    partition_by: Option<String>,
+
+    /// Should we write a header row?
+    #[serde(default = "default_true", deserialize_with = "deserialize_from_str")]
+    header: bool,
+}
+
+/// Default value for boolean arguments which are normally on.
+fn default_true() -> bool {
+    true
+}
+
+/// Does a `csv:` input read using `from_args` start with a header row? We
+/// parse `from_args` exactly the way we do when reading the input.
+pub fn source_has_header(from_args: &[String]) -> Result<bool> {
+    let args = DriverArguments::from_cli_args(from_args)?;
+    Ok(args.deserialize::<CsvSourceArguments>()?.has_header)
+}
 
 /// (Incomplete.) A CSV file containing data, or a directory containing CSV
 /// files.
@@ -53,6 +171,10 @@ impl Locator for CsvLocator {
         self
     }
 
+    fn is_stdio(&self) -> bool {
+        matches!(self.path, PathOrStdio::Stdio)
+    }
+
     fn schema(&self, _ctx: Context) -> BoxFuture<Option<Table>> {
         // We're going to use a helper thread to do this, because `csv` is a
         // purely synchrnous library.
@@ -80,6 +202,8 @@ impl Locator for CsvLocator {
                             is_nullable: true,
                             data_type: DataType::Text,
                             comment: None,
+                            default: None,
+                            collation: None,
                         })
                     }
 
@@ -89,13 +213,31 @@ impl Locator for CsvLocator {
                         .unwrap_or_else(|| OsStr::new("data"))
                         .to_string_lossy()
                         .into_owned();
-                    Ok(Some(Table { name, columns }))
+                    Ok(Some(Table {
+                        name,
+                        columns,
+                        primary_key: vec![],
+                        indexes: vec![],
+                    }))
                 }
             }
         })
         .boxed()
     }
 
+    fn count(
+        &self,
+        ctx: Context,
+        shared_args: SharedArguments<Unverified>,
+        source_args: SourceArguments<Unverified>,
+    ) -> BoxFuture<usize> {
+        count_local_data_helper(ctx, self.clone(), shared_args, source_args).boxed()
+    }
+
+    fn rewrites_data_locally(&self) -> bool {
+        true
+    }
+
     fn local_data(
         &self,
         ctx: Context,
@@ -132,8 +274,59 @@ async fn local_data_helper(
     shared_args: SharedArguments<Unverified>,
     source_args: SourceArguments<Unverified>,
 ) -> Result<Option<BoxStream<CsvStream>>> {
-    let _shared_args = shared_args.verify(CsvLocator::features())?;
-    let _source_args = source_args.verify(CsvLocator::features())?;
+    let shared_args = shared_args.verify(CsvLocator::features())?;
+    let source_args = source_args.verify(CsvLocator::features())?;
+    let csv_source_args = source_args
+        .driver_args()
+        .deserialize::<CsvSourceArguments>()
+        .context("could not parse --from-arg")?;
+    let data = if csv_source_args.follow {
+        if !csv_source_args.has_header {
+            return Err(format_err!(
+                "follow=true cannot be used with has_header=false"
+            ));
+        }
+        follow_local_data(ctx.clone(), path, csv_source_args.follow_interval_ms)?
+    } else {
+        local_data_unconverted(ctx.clone(), path).await?
+    };
+    let schema = shared_args.schema();
+    let data = if csv_source_args.has_header {
+        data
+    } else {
+        add_csv_headers(&schema.columns, data)?
+    };
+    let data = convert_null_streams(ctx.clone(), data, &csv_source_args.null, "");
+    let date_formats = csv_source_args.date_formats();
+    date_formats.check_columns(&schema.columns)?;
+    let data =
+        convert_date_streams(ctx.clone(), data, schema.columns.clone(), date_formats);
+
+    // We have no database to filter rows or select columns for us, so do it
+    // ourselves.
+    let where_clause = source_args.where_clause().map(|w| w.to_owned());
+    let data = filter_csv_streams(ctx.clone(), schema.clone(), where_clause, data)?;
+    let data = limit_csv_streams(ctx.clone(), source_args.limit(), data);
+    let data = if source_args.select_columns() {
+        select_csv_columns(ctx.clone(), schema.columns.clone(), data)
+    } else {
+        data
+    };
+
+    Ok(Some(convert_csv_streams(
+        ctx,
+        data,
+        schema.columns.clone(),
+        csv_source_args.bytes_encoding,
+        BytesEncoding::Base64,
+    )))
+}
+
+/// Read our CSV files, without converting them to our interchange format.
+async fn local_data_unconverted(
+    ctx: Context,
+    path: PathOrStdio,
+) -> Result<BoxStream<CsvStream>> {
     match path {
         PathOrStdio::Stdio => {
             let data = BufReader::with_capacity(BUFFER_SIZE, io::stdin());
@@ -144,15 +337,18 @@ async fn local_data_helper(
                     .map_err(move |e| format_err!("cannot read stdin: {}", e))
                     .boxed(),
             };
-            Ok(Some(box_stream_once(Ok(csv_stream))))
+            Ok(box_stream_once(Ok(csv_stream)))
         }
         PathOrStdio::Path(base_path) => {
             // Recursively look at our paths, picking out the ones that look
             // like CSVs. We do this synchronously because it's reasonably
-            // fast and we'd like to catch errors up front.
+            // fast and we'd like to catch errors up front. We sort by name so
+            // that we always read the files in the same order.
             let mut paths = vec![];
             debug!(ctx.log(), "walking {}", base_path.display());
-            let walker = WalkDir::new(&base_path).follow_links(true);
+            let walker = WalkDir::new(&base_path)
+                .follow_links(true)
+                .sort_by_file_name();
             for dirent in walker.into_iter() {
                 let dirent = dirent.with_context(|_| {
                     format!("error listing files in {}", base_path.display())
@@ -214,9 +410,166 @@ async fn local_data_helper(
                 .boxed()
             });
 
-            Ok(Some(csv_streams.boxed()))
+            Ok(csv_streams.boxed())
+        }
+    }
+}
+
+/// Read a single CSV file as it grows, without converting it.
+fn follow_local_data(
+    ctx: Context,
+    path: PathOrStdio,
+    follow_interval_ms: u64,
+) -> Result<BoxStream<CsvStream>> {
+    let path = match path {
+        PathOrStdio::Path(path) if path.is_file() => path,
+        PathOrStdio::Path(path) => {
+            return Err(format_err!(
+                "follow=true requires a single CSV file, not {}",
+                path.display(),
+            ));
+        }
+        PathOrStdio::Stdio => {
+            return Err(format_err!(
+                "follow=true cannot be used with csv:-, which is already read as it arrives"
+            ));
+        }
+    };
+    if follow_interval_ms == 0 {
+        return Err(format_err!("follow_interval_ms must be greater than 0"));
+    }
+    let name = path
+        .file_stem()
+        .unwrap_or_else(|| OsStr::new("data"))
+        .to_string_lossy()
+        .into_owned();
+    let ctx = ctx.child(o!(
+        "stream" => name.clone(),
+        "path" => format!("{}", path.display()),
+    ));
+    let data = follow_file(ctx, path, Duration::from_millis(follow_interval_ms));
+    Ok(box_stream_once(Ok(CsvStream { name, data })))
+}
+
+/// Add a header row containing the names of `columns` to each stream in `data`,
+/// for input files which don't have one.
+fn add_csv_headers(
+    columns: &[Column],
+    data: BoxStream<CsvStream>,
+) -> Result<BoxStream<CsvStream>> {
+    let mut wtr = csv::Writer::from_writer(vec![]);
+    wtr.write_record(columns.iter().map(|c| &c.name))?;
+    let header = BytesMut::from(&wtr.into_inner()?[..]);
+    Ok(data
+        .map_ok(move |stream| {
+            let header = header.clone();
+            CsvStream {
+                name: stream.name,
+                data: stream::once(async move { Ok(header) })
+                    .chain(stream.data)
+                    .boxed(),
+            }
+        })
+        .boxed())
+}
+
+/// If `from` and `to` differ, convert the `bytes` columns of each stream in
+/// `data` between them.
+fn convert_csv_streams(
+    ctx: Context,
+    data: BoxStream<CsvStream>,
+    columns: Vec<Column>,
+    from: BytesEncoding,
+    to: BytesEncoding,
+) -> BoxStream<CsvStream> {
+    if from == to {
+        return data;
+    }
+    data.and_then(move |stream| {
+        let ctx = ctx.clone();
+        let columns = columns.clone();
+        async move {
+            let converted = spawn_sync_transform(
+                ctx,
+                "convert_bytes_encoding".to_owned(),
+                stream.data,
+                move |_ctx, rdr, wtr| {
+                    convert_bytes_encoding(&columns, from, to, rdr, wtr)
+                },
+            )?;
+            Ok(CsvStream {
+                name: stream.name,
+                data: converted,
+            })
+        }
+    })
+    .boxed()
+}
+
+/// Convert any `date` or `timestamp` columns in `data` with custom `formats`
+/// to our interchange format.
+fn convert_date_streams(
+    ctx: Context,
+    data: BoxStream<CsvStream>,
+    columns: Vec<Column>,
+    formats: DateFormats,
+) -> BoxStream<CsvStream> {
+    if formats.is_empty() {
+        return data;
+    }
+    data.and_then(move |stream| {
+        let ctx = ctx.clone();
+        let columns = columns.clone();
+        let formats = formats.clone();
+        async move {
+            let converted = spawn_sync_transform(
+                ctx,
+                "convert_date_formats".to_owned(),
+                stream.data,
+                move |_ctx, rdr, wtr| {
+                    convert_date_formats(&columns, &formats, rdr, wtr)
+                },
+            )?;
+            Ok(CsvStream {
+                name: stream.name,
+                data: converted,
+            })
         }
+    })
+    .boxed()
+}
+
+/// If `from` and `to` differ, replace any cells in `data` which are exactly
+/// `from` with `to`. We use this to convert `NULL` tokens to and from the empty
+/// cells used by our interchange format.
+fn convert_null_streams(
+    ctx: Context,
+    data: BoxStream<CsvStream>,
+    from: impl Into<String>,
+    to: impl Into<String>,
+) -> BoxStream<CsvStream> {
+    let (from, to) = (from.into(), to.into());
+    if from == to {
+        return data;
     }
+    data.and_then(move |stream| {
+        let ctx = ctx.clone();
+        let from = from.clone();
+        let to = to.clone();
+        async move {
+            let converted = spawn_sync_transform(
+                ctx,
+                "convert_null_token".to_owned(),
+                stream.data,
+                move |_ctx, rdr, wtr| convert_null_token(&from, &to, rdr, wtr),
+            )?;
+            Ok(CsvStream {
+                name: stream.name,
+                data: converted,
+            })
+        }
+    })
+    .boxed()
 }
 
 async fn write_local_data_helper(
@@ -226,13 +579,49 @@ async fn write_local_data_helper(
     shared_args: SharedArguments<Unverified>,
     dest_args: DestinationArguments<Unverified>,
 ) -> Result<BoxStream<BoxFuture<BoxLocator>>> {
-    let _shared_args = shared_args.verify(CsvLocator::features())?;
+    let shared_args = shared_args.verify(CsvLocator::features())?;
     let dest_args = dest_args.verify(CsvLocator::features())?;
     let if_exists = dest_args.if_exists().to_owned();
+    let csv_dest_args = dest_args
+        .driver_args()
+        .deserialize::<CsvDestinationArguments>()
+        .context("could not parse --to-arg")?;
+    let data = convert_csv_streams(
+        ctx.clone(),
+        data,
+        shared_args.schema().columns.clone(),
+        BytesEncoding::Base64,
+        csv_dest_args.bytes_encoding,
+    );
+    let data = convert_null_streams(ctx.clone(), data, "", csv_dest_args.null);
+    let header = csv_dest_args.header;
+    let encrypt_recipient = csv_dest_args.encrypt_recipient;
+    let name_template = csv_dest_args.name_template;
+    let is_dir = match &path {
+        PathOrStdio::Path(path) => path.to_string_lossy().ends_with('/'),
+        PathOrStdio::Stdio => false,
+    };
+    let data = match csv_dest_args.partition_by {
+        Some(column) if is_dir => {
+            partition_csv_streams(ctx.clone(), shared_args.schema(), column, data)?
+        }
+        Some(_) => {
+            return Err(format_err!(
+                "partition_by can only be used when writing to a directory"
+            ));
+        }
+        None => data,
+    };
     match path {
         PathOrStdio::Stdio => {
             if_exists.warn_if_not_default_for_stdout(&ctx);
-            let stream = concatenate_csv_streams(ctx.clone(), data)?;
+            let mut stream = concatenate_csv_streams(ctx.clone(), data)?;
+            if !header {
+                stream.data = strip_csv_header(ctx.clone(), stream.data)?;
+            }
+            if let Some(recipient) = &encrypt_recipient {
+                stream.data = recipient.encrypt_stream(&ctx, stream.data)?;
+            }
             let fut = async move {
                 copy_stream_to_writer(ctx.clone(), stream.data, io::stdout())
                     .await
@@ -245,36 +634,54 @@ async fn write_local_data_helper(
             Ok(box_stream_once(Ok(fut.boxed())))
         }
         PathOrStdio::Path(path) => {
-            if path.to_string_lossy().ends_with('/') {
+            if is_dir {
                 // Write streams to our directory as multiple files.
+                let mut shard = 0;
                 let result_stream = data.map_ok(move |stream| {
+                    let file_name =
+                        output_file_name(name_template.as_ref(), &stream.name, shard);
+                    shard += 1;
                     let path = path.clone();
                     let ctx = ctx.clone();
                     let if_exists = if_exists.clone();
+                    let encrypt_recipient = encrypt_recipient.clone();
 
                     async move {
                         // TODO: This join does not handle `..` or nested `/` in
                         // a particularly safe fashion.
-                        let csv_path = path.join(&format!("{}.csv", stream.name));
+                        let mut file_name = file_name;
+                        if let Some(recipient) = &encrypt_recipient {
+                            file_name = recipient.encrypted_file_name(&file_name);
+                        }
+                        let csv_path = path.join(&file_name);
                         let ctx = ctx.child(o!(
                             "stream" => stream.name.clone(),
                             "path" => format!("{}", csv_path.display()),
                         ));
-                        write_stream_to_file(
-                            ctx,
-                            stream.data,
-                            csv_path.clone(),
-                            if_exists,
-                        )
-                        .await?;
+                        let mut data = stream.data;
+                        if !header {
+                            data = strip_csv_header(ctx.clone(), data)?;
+                        }
+                        if let Some(recipient) = &encrypt_recipient {
+                            data = recipient.encrypt_stream(&ctx, data)?;
+                        }
+                        write_stream_to_file(ctx, data, csv_path.clone(), if_exists)
+                            .await?;
                         Ok(CsvLocator::from_path(csv_path).boxed())
                     }
                     .boxed()
                 });
                 Ok(result_stream.boxed())
             } else {
-                // Write all our streams as a single file.
-                let stream = concatenate_csv_streams(ctx.clone(), data)?;
+                // Write all our streams as a single file. We use the path
+                // exactly as given, even if we're encrypting it.
+                let mut stream = concatenate_csv_streams(ctx.clone(), data)?;
+                if !header {
+                    stream.data = strip_csv_header(ctx.clone(), stream.data)?;
+                }
+                if let Some(recipient) = &encrypt_recipient {
+                    stream.data = recipient.encrypt_stream(&ctx, stream.data)?;
+                }
                 let fut = async move {
                     let ctx = ctx.child(o!(
                         "stream" => stream.name.clone(),
@@ -327,12 +734,24 @@ impl LocatorStatic for CsvLocator {
         Features {
             locator: LocatorFeatures::Schema
                 | LocatorFeatures::LocalData
-                | LocatorFeatures::WriteLocalData,
+                | LocatorFeatures::WriteLocalData
+                | LocatorFeatures::Count,
             write_schema_if_exists: EnumSet::empty(),
-            source_args: EnumSet::empty(),
-            dest_args: EnumSet::empty(),
+            source_args: SourceArgumentsFeatures::DriverArgs
+                | SourceArgumentsFeatures::WhereClause
+                | SourceArgumentsFeatures::Limit,
+            dest_args: DestinationArgumentsFeatures::DriverArgs.into(),
             dest_if_exists: IfExistsFeatures::no_append(),
             _placeholder: (),
         }
     }
 }
+
+#[test]
+fn source_has_header_parses_driver_args() {
+    let args = |args: &[&str]| args.iter().map(|&a| a.to_owned()).collect::<Vec<_>>();
+    assert!(source_has_header(&[]).unwrap());
+    assert!(source_has_header(&args(&["has_header=true"])).unwrap());
+    assert!(!source_has_header(&args(&["null=NA", "has_header=false"])).unwrap());
+    assert!(source_has_header(&args(&["has_header=0"])).is_err());
+}
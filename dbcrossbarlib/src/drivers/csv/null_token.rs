@@ -0,0 +1,73 @@
+//! Alternate representations of `NULL` in CSV files.
+//!
+//! Our CSV interchange format always represents `NULL` using an empty cell.
+//! But some tools write `\N` or `NULL` instead, and some destinations need a
+//! specific token, so we allow the user to choose a different representation,
+//! and convert to and from empty cells as needed.
+
+use std::io;
+
+use crate::common::*;
+
+/// Copy CSV data from `rdr` to `wtr`, replacing any cells which are exactly
+/// equal to `from` with `to`. We never change the header row.
+///
+/// This is synchronous, and should be run using `spawn_sync_transform`.
+pub(crate) fn convert_null_token(
+    from: &str,
+    to: &str,
+    rdr: Box<dyn Read>,
+    wtr: Box<dyn Write>,
+) -> Result<()> {
+    let mut rdr = csv::Reader::from_reader(rdr);
+    let mut wtr =
+        csv::Writer::from_writer(io::BufWriter::with_capacity(BUFFER_SIZE, wtr));
+
+    let headers = rdr.byte_headers()?;
+    if headers.is_empty() {
+        // Empty streams have no cells to convert.
+        return Ok(());
+    }
+    wtr.write_byte_record(headers)?;
+
+    let mut row = csv::ByteRecord::new();
+    let mut out = csv::ByteRecord::new();
+    while rdr.read_byte_record(&mut row)? {
+        out.clear();
+        for cell in &row {
+            if cell == from.as_bytes() {
+                out.push_field(to.as_bytes());
+            } else {
+                out.push_field(cell);
+            }
+        }
+        wtr.write_byte_record(&out)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[test]
+fn convert_to_and_from_null_tokens() {
+    let run = |from: &str, to: &str, input: &'static str| -> String {
+        let tmp_dir = tempdir::TempDir::new("null_token").unwrap();
+        let out_path = tmp_dir.path().join("out.csv");
+        convert_null_token(
+            from,
+            to,
+            Box::new(input.as_bytes()),
+            Box::new(std::fs::File::create(&out_path).unwrap()),
+        )
+        .unwrap();
+        std::fs::read_to_string(&out_path).unwrap()
+    };
+    assert_eq!(
+        run("\\N", "", "id,\\N\n1,\\N\n\\N,x\\N\n"),
+        "id,\\N\n1,\n,x\\N\n",
+    );
+    assert_eq!(
+        run("", "NULL", "id,name\n1,\n,\"\"\n"),
+        "id,name\n1,NULL\nNULL,NULL\n"
+    );
+    assert_eq!(run("\\N", "", ""), "");
+}
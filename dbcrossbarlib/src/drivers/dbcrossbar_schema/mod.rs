@@ -1,18 +1,27 @@
 //! Support for `dbcrossbar-schema` locators.
 
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
 use std::{fmt, str::FromStr};
 
 use crate::common::*;
+use crate::schema::NamedTypes;
 
-/// A JSON file containing BigQuery table schema.
+/// A JSON file containing one or more portable table schemas, optionally
+/// followed by `#table` to select a single table from the file.
 #[derive(Clone, Debug)]
 pub struct DbcrossbarSchemaLocator {
     path: PathOrStdio,
+    table_name: Option<String>,
 }
 
 impl fmt::Display for DbcrossbarSchemaLocator {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.path.fmt_locator_helper(Self::scheme(), f)
+        self.path.fmt_locator_helper(Self::scheme(), f)?;
+        if let Some(table_name) = &self.table_name {
+            write!(f, "#{}", table_name)?;
+        }
+        Ok(())
     }
 }
 
@@ -20,8 +29,15 @@ impl FromStr for DbcrossbarSchemaLocator {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
+        let (s, table_name) = match s.rsplit_once('#') {
+            Some((_, "")) => {
+                return Err(format_err!("missing table name after `#` in {}", s))
+            }
+            Some((s, table_name)) => (s, Some(table_name.to_owned())),
+            None => (s, None),
+        };
         let path = PathOrStdio::from_str_locator_helper(Self::scheme(), s)?;
-        Ok(DbcrossbarSchemaLocator { path })
+        Ok(DbcrossbarSchemaLocator { path, table_name })
     }
 }
 
@@ -30,6 +46,10 @@ impl Locator for DbcrossbarSchemaLocator {
         self
     }
 
+    fn is_stdio(&self) -> bool {
+        matches!(self.path, PathOrStdio::Stdio)
+    }
+
     fn schema(&self, ctx: Context) -> BoxFuture<Option<Table>> {
         schema_helper(ctx, self.to_owned()).boxed()
     }
@@ -40,7 +60,20 @@ impl Locator for DbcrossbarSchemaLocator {
         table: Table,
         if_exists: IfExists,
     ) -> BoxFuture<()> {
-        write_schema_helper(ctx, self.to_owned(), table, if_exists).boxed()
+        write_schemas_helper(ctx, self.to_owned(), vec![table], if_exists).boxed()
+    }
+
+    fn schemas(&self, ctx: Context) -> BoxFuture<Option<Vec<Table>>> {
+        schemas_helper(ctx, self.to_owned()).boxed()
+    }
+
+    fn write_schemas(
+        &self,
+        ctx: Context,
+        tables: Vec<Table>,
+        if_exists: IfExists,
+    ) -> BoxFuture<()> {
+        write_schemas_helper(ctx, self.to_owned(), tables, if_exists).boxed()
     }
 }
 
@@ -61,11 +94,46 @@ impl LocatorStatic for DbcrossbarSchemaLocator {
     }
 }
 
+/// A schema file describing several tables. Files describing a single table
+/// contain just that table's schema, for backwards compatibility.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct MultiTableSchema {
+    /// The tables in this schema.
+    tables: Vec<Table>,
+}
+
 /// Implementation of `schema`, but as a real `async` function.
 async fn schema_helper(
-    _ctx: Context,
+    ctx: Context,
     source: DbcrossbarSchemaLocator,
 ) -> Result<Option<Table>> {
+    let mut tables = read_tables(ctx, &source).await?;
+    if source.table_name.is_some() || tables.len() == 1 {
+        Ok(Some(tables.remove(0)))
+    } else {
+        Err(format_err!(
+            "expected 1 table in {}, found {}",
+            source.path,
+            tables.len(),
+        ))
+    }
+}
+
+/// Implementation of `schemas`, but as a real `async` function.
+async fn schemas_helper(
+    ctx: Context,
+    source: DbcrossbarSchemaLocator,
+) -> Result<Option<Vec<Table>>> {
+    Ok(Some(read_tables(ctx, &source).await?))
+}
+
+/// Read all the tables in `source`, or just the table selected by our `#table`
+/// fragment.
+async fn read_tables(
+    _ctx: Context,
+    source: &DbcrossbarSchemaLocator,
+) -> Result<Vec<Table>> {
     // Read our input.
     let input = source.path.open_async().await?;
     let data = async_read_to_end(input)
@@ -73,25 +141,133 @@ async fn schema_helper(
         .with_context(|_| format!("error reading {}", source.path))?;
 
     // Parse our input as table JSON.
-    let table: Table = serde_json::from_slice(&data)
+    let tables = parse_tables(&data)
         .with_context(|_| format!("error parsing {}", source.path))?;
-    Ok(Some(table))
+    match &source.table_name {
+        None => Ok(tables),
+        Some(table_name) => {
+            let table = tables
+                .into_iter()
+                .find(|t| &t.name == table_name)
+                .ok_or_else(|| {
+                    format_err!("cannot find table {} in {}", table_name, source.path)
+                })?;
+            Ok(vec![table])
+        }
+    }
+}
+
+/// Parse either a single table schema, or a `MultiTableSchema`. Either may
+/// contain `named_types`, which are shared by all tables in the file.
+pub(crate) fn parse_tables(data: &[u8]) -> Result<Vec<Table>> {
+    let mut value: Value = serde_json::from_slice(data)?;
+    let named_types = NamedTypes::take_from_schema(&mut value)?;
+    if let Some(Value::Array(tables)) = value.get_mut("tables") {
+        for table in tables {
+            named_types.resolve_table(table)?;
+        }
+    } else {
+        named_types.resolve_table(&mut value)?;
+    }
+    if value.get("tables").is_some() {
+        let schema: MultiTableSchema = serde_json::from_value(value)?;
+        Ok(schema.tables)
+    } else {
+        let table: Table = serde_json::from_value(value)?;
+        Ok(vec![table])
+    }
 }
 
-/// Implementation of `write_schema`, but as a real `async` function.
-async fn write_schema_helper(
+/// Implementation of `write_schemas`, but as a real `async` function.
+async fn write_schemas_helper(
     ctx: Context,
     dest: DbcrossbarSchemaLocator,
-    table: Table,
+    tables: Vec<Table>,
     if_exists: IfExists,
 ) -> Result<()> {
+    if dest.table_name.is_some() {
+        return Err(format_err!(
+            "cannot write to {}, because `#table` may only be used when reading schemas",
+            dest,
+        ));
+    }
+
     // Generate our JSON.
     let mut f = dest.path.create_async(ctx, if_exists).await?;
-    buffer_sync_write_and_copy_to_async(&mut f, |buff| {
-        serde_json::to_writer_pretty(buff, &table)
-    })
-    .await
-    .with_context(|_| format!("error writing to {}", dest.path))?;
+    buffer_sync_write_and_copy_to_async(&mut f, |buff| write_tables(buff, tables))
+        .await
+        .with_context(|_| format!("error writing to {}", dest.path))?;
     f.flush().await?;
     Ok(())
 }
+
+/// Write `tables` as JSON. We write a single table by itself, and several
+/// tables as a `MultiTableSchema`.
+pub(crate) fn write_tables(f: &mut dyn Write, mut tables: Vec<Table>) -> Result<()> {
+    if tables.len() == 1 {
+        serde_json::to_writer_pretty(f, &tables.remove(0))?;
+    } else {
+        serde_json::to_writer_pretty(f, &MultiTableSchema { tables })?;
+    }
+    Ok(())
+}
+
+#[test]
+fn parse_single_and_multiple_tables() {
+    let single = br#"{"name": "a", "columns": []}"#;
+    let tables = parse_tables(single).unwrap();
+    assert_eq!(tables.len(), 1);
+    assert_eq!(tables[0].name, "a");
+
+    let multiple = br#"{
+        "tables": [
+            {"name": "a", "columns": []},
+            {"name": "b", "columns": []}
+        ]
+    }"#;
+    let tables = parse_tables(multiple).unwrap();
+    let names = tables.iter().map(|t| &t.name[..]).collect::<Vec<_>>();
+    assert_eq!(names, &["a", "b"]);
+}
+
+#[test]
+fn parse_tables_with_shared_named_types() {
+    let data = br#"{
+        "named_types": {
+            "status": { "one_of": ["active", "inactive"] }
+        },
+        "tables": [
+            {"name": "a", "columns": [
+                {"name": "status", "is_nullable": true, "data_type": {"named": "status"}}
+            ]},
+            {"name": "b", "columns": [
+                {"name": "status", "is_nullable": false, "data_type": {"named": "status"}}
+            ]}
+        ]
+    }"#;
+    let tables = parse_tables(data).unwrap();
+    use crate::schema::DataType;
+
+    let status = DataType::OneOf(vec!["active".to_owned(), "inactive".to_owned()]);
+    assert_eq!(tables[0].columns[0].data_type, status);
+    assert_eq!(tables[1].columns[0].data_type, status);
+}
+
+#[test]
+fn parse_locator_with_table_name() {
+    let locator = "dbcrossbar-schema:schema.json#orders"
+        .parse::<DbcrossbarSchemaLocator>()
+        .unwrap();
+    assert_eq!(locator.table_name.as_deref(), Some("orders"));
+    assert_eq!(locator.to_string(), "dbcrossbar-schema:schema.json#orders");
+
+    let locator = "dbcrossbar-schema:-"
+        .parse::<DbcrossbarSchemaLocator>()
+        .unwrap();
+    assert_eq!(locator.table_name, None);
+    assert_eq!(locator.to_string(), "dbcrossbar-schema:-");
+
+    assert!("dbcrossbar-schema:schema.json#"
+        .parse::<DbcrossbarSchemaLocator>()
+        .is_err());
+}
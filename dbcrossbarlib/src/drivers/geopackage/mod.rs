@@ -0,0 +1,112 @@
+//! Driver for reading GeoPackage files.
+
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use crate::common::*;
+use crate::drivers::ogr_shared;
+
+/// A layer in a GeoPackage file, with an optional `#layer` name. If no layer
+/// is specified, the file must contain exactly one layer.
+#[derive(Clone, Debug)]
+pub struct GeoPackageLocator {
+    path: PathBuf,
+    layer: Option<String>,
+}
+
+impl fmt::Display for GeoPackageLocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", Self::scheme(), self.path.display())?;
+        if let Some(layer) = &self.layer {
+            write!(f, "#{}", layer)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for GeoPackageLocator {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if !s.starts_with(Self::scheme()) {
+            return Err(format_err!(
+                "expected {} locator, found {}",
+                Self::scheme(),
+                s
+            ));
+        }
+        let rest = &s[Self::scheme().len()..];
+        let (path, layer) = match rest.find('#') {
+            Some(idx) => (&rest[..idx], Some(rest[idx + 1..].to_owned())),
+            None => (rest, None),
+        };
+        if path.is_empty() || path == "-" {
+            return Err(format_err!("{} requires a file path", Self::scheme()));
+        }
+        Ok(GeoPackageLocator {
+            path: PathBuf::from(path),
+            layer,
+        })
+    }
+}
+
+impl Locator for GeoPackageLocator {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self, ctx: Context) -> BoxFuture<Option<Table>> {
+        let source = self.to_owned();
+        async move {
+            let table =
+                ogr_shared::schema(&ctx, &source.path, source.layer.as_deref())
+                    .await?;
+            Ok(Some(table))
+        }
+        .boxed()
+    }
+
+    fn local_data(
+        &self,
+        ctx: Context,
+        shared_args: SharedArguments<Unverified>,
+        source_args: SourceArguments<Unverified>,
+    ) -> BoxFuture<Option<BoxStream<CsvStream>>> {
+        local_data_helper(ctx, self.to_owned(), shared_args, source_args).boxed()
+    }
+}
+
+impl LocatorStatic for GeoPackageLocator {
+    fn scheme() -> &'static str {
+        "geopackage:"
+    }
+
+    fn features() -> Features {
+        Features {
+            locator: LocatorFeatures::Schema | LocatorFeatures::LocalData,
+            write_schema_if_exists: EnumSet::empty(),
+            source_args: SourceArgumentsFeatures::WhereClause.into(),
+            dest_args: EnumSet::empty(),
+            dest_if_exists: EnumSet::empty(),
+            _placeholder: (),
+        }
+    }
+}
+
+/// Implementation of `local_data`, but as a real `async` function.
+async fn local_data_helper(
+    ctx: Context,
+    source: GeoPackageLocator,
+    shared_args: SharedArguments<Unverified>,
+    source_args: SourceArguments<Unverified>,
+) -> Result<Option<BoxStream<CsvStream>>> {
+    let _shared_args = shared_args.verify(GeoPackageLocator::features())?;
+    let source_args = source_args.verify(GeoPackageLocator::features())?;
+    let data = ogr_shared::local_data(
+        ctx,
+        &source.path,
+        source.layer.as_deref(),
+        source_args.where_clause(),
+    )
+    .await?;
+    Ok(Some(data))
+}
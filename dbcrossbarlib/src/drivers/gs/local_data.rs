@@ -3,7 +3,31 @@
 use super::GsLocator;
 use crate::clouds::gcloud::storage;
 use crate::common::*;
+use crate::compression::Compression;
 use crate::csv_stream::csv_stream_name;
+use crate::limit::limit_csv_streams;
+use crate::row_filter::filter_csv_streams;
+use crate::select::select_csv_columns;
+
+/// Implementation of `list_files`, but as a real `async` function.
+pub(crate) async fn list_files_helper(
+    ctx: Context,
+    url: Url,
+) -> Result<Option<Vec<BoxLocator>>> {
+    // Only directories contain more than one file.
+    if !url.path().ends_with('/') {
+        return Ok(None);
+    }
+    let file_urls = storage::ls(&ctx, &url)
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?;
+    let locators = file_urls
+        .into_iter()
+        .map(|file_url| Ok(file_url.parse::<GsLocator>()?.boxed()))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Some(locators))
+}
 
 /// Implementation of `local_data`, but as a real `async` function.
 pub(crate) async fn local_data_helper(
@@ -12,12 +36,17 @@ pub(crate) async fn local_data_helper(
     shared_args: SharedArguments<Unverified>,
     source_args: SourceArguments<Unverified>,
 ) -> Result<Option<BoxStream<CsvStream>>> {
-    let _shared_args = shared_args.verify(GsLocator::features())?;
-    let _source_args = source_args.verify(GsLocator::features())?;
+    let shared_args = shared_args.verify(GsLocator::features())?;
+    let source_args = source_args.verify(GsLocator::features())?;
+    let table = shared_args.schema().clone();
+    let where_clause = source_args.where_clause().map(|w| w.to_owned());
+    let limit = source_args.limit();
+    let select_columns = source_args.select_columns();
     debug!(ctx.log(), "getting CSV files from {}", url);
 
     let file_urls = storage::ls(&ctx, &url).await?;
 
+    let filter_ctx = ctx.clone();
     let csv_streams = file_urls.and_then(move |file_url| {
         let ctx = ctx.clone();
         let url = url.clone();
@@ -27,7 +56,10 @@ pub(crate) async fn local_data_helper(
             let ctx =
                 ctx.child(o!("stream" => name.to_owned(), "url" => file_url.clone()));
             let file_url = Url::parse(&file_url)?;
-            let data = storage::download_file(&ctx, &file_url).await?;
+            let mut data = storage::download_file(&ctx, &file_url).await?;
+            if let Some(compression) = Compression::for_file_name(file_url.path()) {
+                data = compression.decompress_stream(&ctx, data)?;
+            }
 
             // Assemble everything into a CSV stream.
             Ok(CsvStream {
@@ -38,5 +70,19 @@ pub(crate) async fn local_data_helper(
         .boxed()
     });
 
-    Ok(Some(csv_streams.boxed()))
+    // We have no database to filter rows or select columns for us, so do it
+    // ourselves.
+    let columns = table.columns.clone();
+    let csv_streams = filter_csv_streams(
+        filter_ctx.clone(),
+        table,
+        where_clause,
+        csv_streams.boxed(),
+    )?;
+    let csv_streams = limit_csv_streams(filter_ctx.clone(), limit, csv_streams);
+    if select_columns {
+        Ok(Some(select_csv_columns(filter_ctx, columns, csv_streams)))
+    } else {
+        Ok(Some(csv_streams))
+    }
 }
@@ -1,20 +1,67 @@
 //! Support for Google Cloud Storage.
 
+use serde::Deserialize;
 use std::{fmt, str::FromStr};
 
+use crate::clouds::{gcloud::storage, object_tags::ObjectTags};
 use crate::common::*;
+use crate::compression::Compression;
+use crate::count_rows::count_local_data_helper;
+use crate::driver_args::deserialize_optional_from_str;
 use crate::drivers::bigquery::BigQueryLocator;
+use crate::encryption::EncryptRecipient;
+use crate::name_template::NameTemplate;
 
 mod local_data;
 mod prepare_as_destination;
 mod write_local_data;
 mod write_remote_data;
 
-use local_data::local_data_helper;
+use local_data::{list_files_helper, local_data_helper};
 pub(crate) use prepare_as_destination::prepare_as_destination_helper;
 use write_local_data::write_local_data_helper;
 use write_remote_data::write_remote_data_helper;
 
+/// Parsed version of `--to-arg` values.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GsDestinationArguments {
+    /// Custom metadata to set on each file we write.
+    #[serde(default, deserialize_with = "deserialize_optional_from_str")]
+    object_tags: Option<ObjectTags>,
+
+    /// Compress each file we write.
+    #[serde(default, deserialize_with = "deserialize_optional_from_str")]
+    compression: Option<Compression>,
+
+    /// Encrypt each file we write for this `age` or PGP recipient.
+    #[serde(default, deserialize_with = "deserialize_optional_from_str")]
+    encrypt_recipient: Option<EncryptRecipient>,
+
+    /// A template for the names of the files we write.
+    #[serde(default, deserialize_with = "deserialize_optional_from_str")]
+    name_template: Option<NameTemplate>,
+
+    /// Write a Hive-style directory for each value of this column.
+    #[serde(default)]
+    partition_by: Option<String>,
+}
+
+impl GsDestinationArguments {
+    /// Parse our destination arguments.
+    fn from_dest_args(dest_args: &DestinationArguments<Verified>) -> Result<Self> {
+        Ok(dest_args
+            .driver_args()
+            .deserialize::<GsDestinationArguments>()
+            .context("could not parse --to-arg")?)
+    }
+
+    /// The tags to apply to each file we write.
+    fn object_tags(&self) -> ObjectTags {
+        self.object_tags.clone().unwrap_or_default()
+    }
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct GsLocator {
     url: Url,
@@ -59,6 +106,15 @@ impl Locator for GsLocator {
         self
     }
 
+    fn count(
+        &self,
+        ctx: Context,
+        shared_args: SharedArguments<Unverified>,
+        source_args: SourceArguments<Unverified>,
+    ) -> BoxFuture<usize> {
+        count_local_data_helper(ctx, self.clone(), shared_args, source_args).boxed()
+    }
+
     fn local_data(
         &self,
         ctx: Context,
@@ -68,6 +124,19 @@ impl Locator for GsLocator {
         local_data_helper(ctx, self.url.clone(), shared_args, source_args).boxed()
     }
 
+    fn rewrites_data_locally(&self) -> bool {
+        true
+    }
+
+    fn list_files(&self, ctx: Context) -> BoxFuture<Option<Vec<BoxLocator>>> {
+        list_files_helper(ctx, self.url.clone()).boxed()
+    }
+
+    fn remove(&self, ctx: Context) -> BoxFuture<()> {
+        let url = self.url.clone();
+        async move { storage::rmdir(&ctx, &url).await }.boxed()
+    }
+
     fn write_local_data(
         &self,
         ctx: Context,
@@ -112,10 +181,14 @@ impl LocatorStatic for GsLocator {
 
     fn features() -> Features {
         Features {
-            locator: LocatorFeatures::LocalData | LocatorFeatures::WriteLocalData,
+            locator: LocatorFeatures::LocalData
+                | LocatorFeatures::WriteLocalData
+                | LocatorFeatures::Count
+                | LocatorFeatures::Remove,
             write_schema_if_exists: EnumSet::empty(),
-            source_args: EnumSet::empty(),
-            dest_args: EnumSet::empty(),
+            source_args: SourceArgumentsFeatures::WhereClause
+                | SourceArgumentsFeatures::Limit,
+            dest_args: DestinationArgumentsFeatures::DriverArgs.into(),
             dest_if_exists: IfExistsFeatures::Overwrite.into(),
             _placeholder: (),
         }
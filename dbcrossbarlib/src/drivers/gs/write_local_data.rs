@@ -1,8 +1,10 @@
 //! Writing data to Google Cloud Storage.
 
-use super::{prepare_as_destination_helper, GsLocator};
+use super::{prepare_as_destination_helper, GsDestinationArguments, GsLocator};
 use crate::clouds::gcloud::storage;
 use crate::common::*;
+use crate::name_template::output_file_name;
+use crate::partition::partition_csv_streams;
 
 /// Implementation of `write_local_data`, but as a real `async` function.
 pub(crate) async fn write_local_data_helper(
@@ -12,23 +14,61 @@ pub(crate) async fn write_local_data_helper(
     shared_args: SharedArguments<Unverified>,
     dest_args: DestinationArguments<Unverified>,
 ) -> Result<BoxStream<BoxFuture<BoxLocator>>> {
-    let _shared_args = shared_args.verify(GsLocator::features())?;
+    let shared_args = shared_args.verify(GsLocator::features())?;
     let dest_args = dest_args.verify(GsLocator::features())?;
+    let gs_dest_args = GsDestinationArguments::from_dest_args(&dest_args)?;
+    let compression = gs_dest_args.compression;
+    let encrypt_recipient = gs_dest_args.encrypt_recipient.clone();
+    let name_template = gs_dest_args.name_template.clone();
+    let mut object_tags = gs_dest_args.object_tags();
+    object_tags.mark_if_temporary(&dest_args)?;
+
+    // Split our data into partitions, if asked to.
+    let data = match &gs_dest_args.partition_by {
+        Some(column) => partition_csv_streams(
+            ctx.clone(),
+            shared_args.schema(),
+            column.to_owned(),
+            data,
+        )?,
+        None => data,
+    };
 
     // Delete the existing output, if it exists.
     let if_exists = dest_args.if_exists().to_owned();
     prepare_as_destination_helper(ctx.clone(), url.clone(), if_exists).await?;
 
     // Spawn our uploader processes.
+    let mut shard = 0;
     let written = data.map_ok(move |stream| {
+        let file_name = output_file_name(name_template.as_ref(), &stream.name, shard);
+        shard += 1;
         let url = url.clone();
         let ctx = ctx.clone();
+        let object_tags = object_tags.clone();
+        let encrypt_recipient = encrypt_recipient.clone();
         async move {
-            let url = url.join(&format!("{}.csv", stream.name))?;
+            let mut file_name = file_name;
+            let mut data = stream.data;
+            if let Some(compression) = compression {
+                file_name = compression.compressed_file_name(&file_name);
+            }
+            if let Some(recipient) = &encrypt_recipient {
+                file_name = recipient.encrypted_file_name(&file_name);
+            }
+            let url = url.join(&file_name)?;
             let ctx = ctx
                 .child(o!("stream" => stream.name.clone(), "url" => url.to_string()));
+            // Compress before we encrypt, because encrypted data won't
+            // compress.
+            if let Some(compression) = compression {
+                data = compression.compress_stream(&ctx, data)?;
+            }
+            if let Some(recipient) = &encrypt_recipient {
+                data = recipient.encrypt_stream(&ctx, data)?;
+            }
 
-            storage::upload_file(ctx.clone(), stream.data, &url).await?;
+            storage::upload_file(ctx.clone(), data, &url, &object_tags).await?;
             Ok(GsLocator { url }.boxed())
         }
         .boxed()
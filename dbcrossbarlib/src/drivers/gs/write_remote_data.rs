@@ -1,7 +1,7 @@
 //! Implementation of `GsLocator::write_remote_data`.
 
-use super::{prepare_as_destination_helper, GsLocator};
-use crate::clouds::gcloud::bigquery;
+use super::{prepare_as_destination_helper, GsDestinationArguments, GsLocator};
+use crate::clouds::gcloud::{bigquery, storage};
 use crate::common::*;
 use crate::drivers::{
     bigquery::BigQueryLocator,
@@ -28,7 +28,7 @@ pub(crate) async fn write_remote_data_helper(
         .as_any()
         .downcast_ref::<BigQueryLocator>()
         .ok_or_else(|| format_err!("not a bigquery locator: {}", source))?;
-    let source_table_name = source.as_table_name().to_owned();
+    let source_table_name = source.as_table_name()?.to_owned();
 
     // Verify our arguments.
     let shared_args = shared_args.verify(GsLocator::features())?;
@@ -39,6 +39,29 @@ pub(crate) async fn write_remote_data_helper(
     let schema = shared_args.schema();
     let temporary_storage = shared_args.temporary_storage();
     let if_exists = dest_args.if_exists().to_owned();
+    let gs_dest_args = GsDestinationArguments::from_dest_args(&dest_args)?;
+    if gs_dest_args.compression.is_some() {
+        return Err(format_err!(
+            "cannot set compression on files exported by BigQuery"
+        ));
+    }
+    if gs_dest_args.encrypt_recipient.is_some() {
+        return Err(format_err!(
+            "cannot set encrypt_recipient on files exported by BigQuery"
+        ));
+    }
+    if gs_dest_args.name_template.is_some() {
+        return Err(format_err!(
+            "cannot set name_template on files exported by BigQuery"
+        ));
+    }
+    if gs_dest_args.partition_by.is_some() {
+        return Err(format_err!(
+            "cannot set partition_by on files exported by BigQuery"
+        ));
+    }
+    let mut object_tags = gs_dest_args.object_tags();
+    object_tags.mark_if_temporary(&dest_args)?;
 
     // Construct a `BqTable` describing our source table.
     let source_table = BqTable::for_table_name_and_columns(
@@ -59,7 +82,11 @@ pub(crate) async fn write_remote_data_helper(
         .name()
         .temporary_table_name(&temporary_storage)?;
     let mut export_sql_data = vec![];
-    real_source_table.write_export_sql(&source_args, &mut export_sql_data)?;
+    real_source_table.write_export_sql(
+        &source_args,
+        source.as_of(),
+        &mut export_sql_data,
+    )?;
     let export_sql =
         String::from_utf8(export_sql_data).expect("should always be UTF-8");
     debug!(ctx.log(), "export SQL: {}", export_sql);
@@ -80,6 +107,7 @@ pub(crate) async fn write_remote_data_helper(
 
     // Build and run a `bq extract` command.
     bigquery::extract(&ctx, &temp_table_name, dest.as_url()).await?;
+    storage::set_object_tags(&ctx, dest.as_url(), &object_tags).await?;
 
     // Delete temp table.
     bigquery::drop_table(&ctx, &temp_table_name).await?;
@@ -8,18 +8,26 @@ use std::collections::HashMap;
 use crate::common::*;
 use crate::locator::{LocatorDriver, LocatorDriverWrapper};
 
+pub mod adbc;
 pub mod bigml;
 pub mod bigquery;
 pub mod bigquery_schema;
 pub mod bigquery_shared;
 pub mod csv;
 pub mod dbcrossbar_schema;
+pub mod geopackage;
 pub mod gs;
+pub mod msgpack;
+pub mod mysqldump;
+pub mod ogr_shared;
+pub mod pg_dump;
 pub mod postgres;
 pub mod postgres_shared;
 pub mod postgres_sql;
+pub mod protobuf;
 pub mod redshift;
 pub mod s3;
+pub mod shapefile;
 
 /// A helper which builds a `Box<dyn LocatorDriver>` for a type implementating
 /// `LocatorStatic`.
@@ -30,16 +38,23 @@ fn driver<L: LocatorStatic>() -> Box<dyn LocatorDriver> {
 lazy_static! {
     /// A list of known drivers, computed the first time we use it and cached.
     static ref KNOWN_DRIVERS: Vec<Box<dyn LocatorDriver>> = vec![
+        driver::<adbc::AdbcLocator>(),
         driver::<bigml::BigMlLocator>(),
         driver::<bigquery::BigQueryLocator>(),
         driver::<bigquery_schema::BigQuerySchemaLocator>(),
         driver::<csv::CsvLocator>(),
         driver::<dbcrossbar_schema::DbcrossbarSchemaLocator>(),
+        driver::<geopackage::GeoPackageLocator>(),
         driver::<gs::GsLocator>(),
+        driver::<msgpack::MsgpackLocator>(),
+        driver::<mysqldump::MySqlDumpLocator>(),
+        driver::<pg_dump::PgDumpLocator>(),
         driver::<postgres::PostgresLocator>(),
         driver::<postgres_sql::PostgresSqlLocator>(),
+        driver::<protobuf::ProtobufLocator>(),
         driver::<redshift::RedshiftLocator>(),
         driver::<s3::S3Locator>(),
+        driver::<shapefile::ShapefileLocator>(),
     ];
 
     /// A hash table of all known drivers, indexed by scheme and computed the
@@ -0,0 +1,158 @@
+//! Driver for reading and writing length-prefixed MessagePack records.
+
+use std::{ffi::OsStr, fmt, str::FromStr};
+
+use crate::common::*;
+use crate::concat::concatenate_csv_streams;
+use crate::tokio_glue::{copy_reader_to_stream, copy_stream_to_writer};
+use crate::transform::spawn_sync_transform;
+
+mod records;
+
+use self::records::{copy_csv_to_msgpack, copy_msgpack_to_csv};
+
+/// A file (or standard input/output) containing length-prefixed MessagePack
+/// records, one per row.
+#[derive(Clone, Debug)]
+pub struct MsgpackLocator {
+    path: PathOrStdio,
+}
+
+impl fmt::Display for MsgpackLocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.path.fmt_locator_helper(Self::scheme(), f)
+    }
+}
+
+impl FromStr for MsgpackLocator {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let path = PathOrStdio::from_str_locator_helper(Self::scheme(), s)?;
+        Ok(MsgpackLocator { path })
+    }
+}
+
+impl Locator for MsgpackLocator {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn is_stdio(&self) -> bool {
+        matches!(self.path, PathOrStdio::Stdio)
+    }
+
+    fn local_data(
+        &self,
+        ctx: Context,
+        shared_args: SharedArguments<Unverified>,
+        source_args: SourceArguments<Unverified>,
+    ) -> BoxFuture<Option<BoxStream<CsvStream>>> {
+        local_data_helper(ctx, self.path.clone(), shared_args, source_args).boxed()
+    }
+
+    fn display_output_locators(&self) -> DisplayOutputLocators {
+        match &self.path {
+            // If we write our data to standard output, we don't also want to
+            // print out "msgpack:-" to the same standard output.
+            PathOrStdio::Stdio => DisplayOutputLocators::Never,
+            _ => DisplayOutputLocators::IfRequested,
+        }
+    }
+
+    fn write_local_data(
+        &self,
+        ctx: Context,
+        data: BoxStream<CsvStream>,
+        shared_args: SharedArguments<Unverified>,
+        dest_args: DestinationArguments<Unverified>,
+    ) -> BoxFuture<BoxStream<BoxFuture<BoxLocator>>> {
+        write_local_data_helper(ctx, self.to_owned(), data, shared_args, dest_args)
+            .boxed()
+    }
+}
+
+impl LocatorStatic for MsgpackLocator {
+    fn scheme() -> &'static str {
+        "msgpack:"
+    }
+
+    fn features() -> Features {
+        Features {
+            locator: LocatorFeatures::LocalData | LocatorFeatures::WriteLocalData,
+            write_schema_if_exists: EnumSet::empty(),
+            source_args: EnumSet::empty(),
+            dest_args: EnumSet::empty(),
+            dest_if_exists: IfExistsFeatures::no_append(),
+            _placeholder: (),
+        }
+    }
+}
+
+/// Implementation of `local_data`, but as a real `async` function.
+async fn local_data_helper(
+    ctx: Context,
+    path: PathOrStdio,
+    shared_args: SharedArguments<Unverified>,
+    source_args: SourceArguments<Unverified>,
+) -> Result<Option<BoxStream<CsvStream>>> {
+    let shared_args = shared_args.verify(MsgpackLocator::features())?;
+    let _source_args = source_args.verify(MsgpackLocator::features())?;
+
+    // MessagePack records don't have a fixed schema, so we rely on the one
+    // passed by the user.
+    let columns = shared_args.schema().columns.clone();
+
+    let name = match &path {
+        PathOrStdio::Stdio => "data".to_owned(),
+        PathOrStdio::Path(p) => p
+            .file_stem()
+            .unwrap_or_else(|| OsStr::new("data"))
+            .to_string_lossy()
+            .into_owned(),
+    };
+    let input = path.open_async().await?;
+    let msgpack_data = copy_reader_to_stream(ctx.clone(), input)?;
+    let csv_data = spawn_sync_transform(
+        ctx,
+        "copy_msgpack_to_csv".to_owned(),
+        msgpack_data.boxed(),
+        move |_ctx, rdr, wtr| copy_msgpack_to_csv(&columns, rdr, wtr),
+    )?;
+    let csv_stream = CsvStream {
+        name,
+        data: csv_data,
+    };
+    Ok(Some(box_stream_once(Ok(csv_stream))))
+}
+
+/// Implementation of `write_local_data`, but as a real `async` function.
+async fn write_local_data_helper(
+    ctx: Context,
+    dest: MsgpackLocator,
+    data: BoxStream<CsvStream>,
+    shared_args: SharedArguments<Unverified>,
+    dest_args: DestinationArguments<Unverified>,
+) -> Result<BoxStream<BoxFuture<BoxLocator>>> {
+    let shared_args = shared_args.verify(MsgpackLocator::features())?;
+    let dest_args = dest_args.verify(MsgpackLocator::features())?;
+    let if_exists = dest_args.if_exists().to_owned();
+    let columns = shared_args.schema().columns.clone();
+
+    // Write all our streams to a single output, one record per row.
+    let stream = concatenate_csv_streams(ctx.clone(), data)?;
+    let fut = async move {
+        let msgpack_data = spawn_sync_transform(
+            ctx.clone(),
+            "copy_csv_to_msgpack".to_owned(),
+            stream.data,
+            move |_ctx, rdr, wtr| copy_csv_to_msgpack(&columns, rdr, wtr),
+        )?;
+        let wtr = dest.path.create_async(ctx.clone(), if_exists).await?;
+        copy_stream_to_writer(ctx, msgpack_data, wtr)
+            .await
+            .with_context(|_| format!("error writing {}", dest.path))?;
+        Ok(dest.boxed())
+    };
+    Ok(box_stream_once(Ok(fut.boxed())))
+}
@@ -0,0 +1,351 @@
+//! Converting between CSV data and length-prefixed MessagePack records.
+//!
+//! Each record is a 4-byte, big-endian length, followed by a MessagePack map
+//! from column names to values. This makes it easy for other tools to split
+//! a stream into records without needing a full MessagePack parser.
+
+use byteorder::{NetworkEndian as NE, ReadBytesExt, WriteBytesExt};
+use rmpv::Value;
+use std::{
+    convert::TryFrom,
+    io::{self, ErrorKind},
+};
+
+use crate::common::*;
+use crate::from_csv_cell::FromCsvCell;
+use crate::schema::{Column, DataType};
+
+/// The largest record we'll read or write, in bytes. Record lengths come from
+/// untrusted input, so we check them before allocating a buffer, instead of
+/// letting a corrupt length prefix allocate gigabytes of memory.
+const MAX_RECORD_SIZE: usize = 64 * 1024 * 1024;
+
+/// Read CSV data and write length-prefixed MessagePack records, using
+/// `columns` to decide how to represent each cell.
+///
+/// This is synchronous, and should be run using `spawn_sync_transform`.
+pub(crate) fn copy_csv_to_msgpack(
+    columns: &[Column],
+    rdr: Box<dyn Read>,
+    wtr: Box<dyn Write>,
+) -> Result<()> {
+    let mut rdr = csv::Reader::from_reader(rdr);
+    let mut wtr = io::BufWriter::with_capacity(BUFFER_SIZE, wtr);
+
+    // Check to make sure our CSV headers and table column names match.
+    let headers = rdr.headers()?;
+    if headers.len() != columns.len() {
+        return Err(format_err!(
+            "CSV file has {} columns, but schema has {}",
+            headers.len(),
+            columns.len(),
+        ));
+    }
+    for (idx, (hdr, col)) in headers.iter().zip(columns.iter()).enumerate() {
+        if hdr != col.name {
+            return Err(format_err!(
+                "CSV file has column {} at position {}, but schema has {}",
+                hdr,
+                idx,
+                col.name,
+            ));
+        }
+    }
+
+    let mut buf = vec![];
+    for row in rdr.records() {
+        let row = row?;
+        let mut entries = Vec::with_capacity(columns.len());
+        for (cell, col) in row.iter().zip(columns.iter()) {
+            let value = csv_cell_to_value(cell, &col.data_type)
+                .with_context(|_| format!("could not convert column {}", col.name))?;
+            entries.push((Value::from(&col.name[..]), value));
+        }
+        buf.clear();
+        rmpv::encode::write_value(&mut buf, &Value::Map(entries))?;
+        if buf.len() > MAX_RECORD_SIZE {
+            return Err(format_err!(
+                "MessagePack record is {} bytes, but the maximum is {}",
+                buf.len(),
+                MAX_RECORD_SIZE,
+            ));
+        }
+        let len = u32::try_from(buf.len()).expect("MAX_RECORD_SIZE should fit in u32");
+        wtr.write_u32::<NE>(len)?;
+        wtr.write_all(&buf)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Convert a CSV cell into a MessagePack value.
+fn csv_cell_to_value(cell: &str, data_type: &DataType) -> Result<Value> {
+    if cell.is_empty() {
+        return Ok(Value::Nil);
+    }
+    match data_type {
+        DataType::Bool => Ok(Value::Boolean(bool::from_csv_cell(cell)?)),
+        DataType::Bytes => Ok(Value::Binary(Vec::<u8>::from_csv_cell(cell)?)),
+        DataType::Int16 | DataType::Int32 | DataType::Int64 => {
+            Ok(Value::from(cell.parse::<i64>()?))
+        }
+        DataType::Float32 | DataType::Float64 => Ok(Value::F64(cell.parse::<f64>()?)),
+        DataType::Array(_)
+        | DataType::GeoJson(_)
+        | DataType::Json
+        | DataType::Struct(_) => {
+            let json = serde_json::from_str::<serde_json::Value>(cell)?;
+            Ok(json_to_value(json))
+        }
+        DataType::Cidr
+        | DataType::Date
+        | DataType::Decimal
+        | DataType::DecimalWithPrecision(_)
+        | DataType::Inet
+        | DataType::Interval
+        | DataType::MacAddr
+        | DataType::OneOf(_)
+        | DataType::Other(_)
+        | DataType::Text
+        | DataType::TextWithMaxLength(_)
+        | DataType::Time
+        | DataType::TimestampWithoutTimeZone
+        | DataType::TimestampWithTimeZone
+        | DataType::Uuid => Ok(Value::from(cell)),
+    }
+}
+
+/// Convert a JSON value into a MessagePack value.
+fn json_to_value(json: serde_json::Value) -> Value {
+    use serde_json::Value as J;
+    match json {
+        J::Null => Value::Nil,
+        J::Bool(b) => Value::Boolean(b),
+        J::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Value::from(i)
+            } else if let Some(u) = n.as_u64() {
+                Value::from(u)
+            } else {
+                Value::F64(n.as_f64().unwrap_or(f64::NAN))
+            }
+        }
+        J::String(s) => Value::from(s),
+        J::Array(elems) => {
+            Value::Array(elems.into_iter().map(json_to_value).collect())
+        }
+        J::Object(obj) => Value::Map(
+            obj.into_iter()
+                .map(|(k, v)| (Value::from(k), json_to_value(v)))
+                .collect(),
+        ),
+    }
+}
+
+/// Read length-prefixed MessagePack records and write CSV data, including only
+/// the fields named in `columns`. Missing fields are treated as `NULL`.
+///
+/// This is synchronous, and should be run using `spawn_sync_transform`.
+pub(crate) fn copy_msgpack_to_csv(
+    columns: &[Column],
+    rdr: Box<dyn Read>,
+    wtr: Box<dyn Write>,
+) -> Result<()> {
+    let mut rdr = io::BufReader::with_capacity(BUFFER_SIZE, rdr);
+    let mut wtr =
+        csv::Writer::from_writer(io::BufWriter::with_capacity(BUFFER_SIZE, wtr));
+    wtr.write_record(columns.iter().map(|c| &c.name))?;
+
+    let mut buf = vec![];
+    let mut cells = vec![String::new(); columns.len()];
+    loop {
+        let len = match rdr.read_u32::<NE>() {
+            Ok(len) => len,
+            Err(ref err) if err.kind() == ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        };
+        let len = len as usize;
+        if len > MAX_RECORD_SIZE {
+            return Err(format_err!(
+                "MessagePack record is {} bytes, but the maximum is {}",
+                len,
+                MAX_RECORD_SIZE,
+            ));
+        }
+        buf.resize(len, 0);
+        rdr.read_exact(&mut buf)
+            .context("MessagePack record was truncated")?;
+        let record = rmpv::decode::read_value(&mut &buf[..])?;
+        let entries = match record {
+            Value::Map(entries) => entries,
+            other => {
+                return Err(format_err!("expected MessagePack map, found {}", other))
+            }
+        };
+        for (cell, col) in cells.iter_mut().zip(columns.iter()) {
+            let value = entries
+                .iter()
+                .find(|(k, _)| k.as_str() == Some(&col.name[..]))
+                .map(|(_, v)| v);
+            *cell = match value {
+                Some(value) => value_to_csv_cell(value).with_context(|_| {
+                    format!("could not convert column {}", col.name)
+                })?,
+                None => String::new(),
+            };
+        }
+        wtr.write_record(&cells)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Convert a MessagePack value into a CSV cell in our interchange format.
+fn value_to_csv_cell(value: &Value) -> Result<String> {
+    match value {
+        Value::Nil => Ok(String::new()),
+        Value::Boolean(true) => Ok("t".to_owned()),
+        Value::Boolean(false) => Ok("f".to_owned()),
+        Value::Integer(i) => Ok(i.to_string()),
+        Value::F32(f) => Ok(f.to_string()),
+        Value::F64(f) => Ok(f.to_string()),
+        Value::String(s) => s
+            .as_str()
+            .map(|s| s.to_owned())
+            .ok_or_else(|| format_err!("MessagePack string was not UTF-8")),
+        Value::Binary(bytes) => Ok(base64::encode(bytes)),
+        Value::Array(_) | Value::Map(_) => {
+            Ok(serde_json::to_string(&value_to_json(value)?)?)
+        }
+        Value::Ext(_, _) => Err(format_err!(
+            "cannot convert MessagePack value {} to CSV",
+            value
+        )),
+    }
+}
+
+/// Convert a MessagePack value into a JSON value.
+fn value_to_json(value: &Value) -> Result<serde_json::Value> {
+    use serde_json::Value as J;
+    match value {
+        Value::Nil => Ok(J::Null),
+        Value::Boolean(b) => Ok(J::Bool(*b)),
+        Value::Integer(i) => {
+            if let Some(i) = i.as_i64() {
+                Ok(J::from(i))
+            } else {
+                Ok(J::from(i.as_u64().expect("integer should be u64")))
+            }
+        }
+        Value::F32(f) => Ok(J::from(f64::from(*f))),
+        Value::F64(f) => Ok(J::from(*f)),
+        Value::String(_) | Value::Binary(_) => {
+            Ok(J::String(value_to_csv_cell(value)?))
+        }
+        Value::Array(elems) => Ok(J::Array(
+            elems.iter().map(value_to_json).collect::<Result<_>>()?,
+        )),
+        Value::Map(entries) => {
+            let mut obj = serde_json::Map::with_capacity(entries.len());
+            for (k, v) in entries {
+                let k = k.as_str().ok_or_else(|| {
+                    format_err!("cannot convert MessagePack key {} to JSON", k)
+                })?;
+                obj.insert(k.to_owned(), value_to_json(v)?);
+            }
+            Ok(J::Object(obj))
+        }
+        Value::Ext(_, _) => Err(format_err!(
+            "cannot convert MessagePack value {} to JSON",
+            value
+        )),
+    }
+}
+
+#[test]
+fn msgpack_round_trip() {
+    let columns = vec![
+        Column {
+            name: "id".to_owned(),
+            is_nullable: false,
+            data_type: DataType::Int64,
+            comment: None,
+            default: None,
+            collation: None,
+        },
+        Column {
+            name: "ok".to_owned(),
+            is_nullable: true,
+            data_type: DataType::Bool,
+            comment: None,
+            default: None,
+            collation: None,
+        },
+        Column {
+            name: "name".to_owned(),
+            is_nullable: true,
+            data_type: DataType::Text,
+            comment: None,
+            default: None,
+            collation: None,
+        },
+        Column {
+            name: "tags".to_owned(),
+            is_nullable: true,
+            data_type: DataType::Array(Box::new(DataType::Text)),
+            comment: None,
+            default: None,
+            collation: None,
+        },
+    ];
+    let csv = "id,ok,name,tags\n1,t,\"a,b\",\"[\"\"x\"\"]\"\n2,f,,\n";
+    let tmp_dir = tempdir::TempDir::new("msgpack").unwrap();
+
+    let msgpack_path = tmp_dir.path().join("data.msgpack");
+    let msgpack_file = std::fs::File::create(&msgpack_path).unwrap();
+    copy_csv_to_msgpack(&columns, Box::new(csv.as_bytes()), Box::new(msgpack_file))
+        .unwrap();
+
+    let msgpack = std::fs::read(&msgpack_path).unwrap();
+    let first_len =
+        u32::from_be_bytes([msgpack[0], msgpack[1], msgpack[2], msgpack[3]]);
+    let first =
+        rmpv::decode::read_value(&mut &msgpack[4..4 + first_len as usize]).unwrap();
+    assert_eq!(first["id"], Value::from(1));
+    assert_eq!(first["ok"], Value::Boolean(true));
+
+    let csv_path = tmp_dir.path().join("data.csv");
+    let csv_file = std::fs::File::create(&csv_path).unwrap();
+    copy_msgpack_to_csv(
+        &columns,
+        Box::new(std::fs::File::open(&msgpack_path).unwrap()),
+        Box::new(csv_file),
+    )
+    .unwrap();
+    assert_eq!(std::fs::read_to_string(&csv_path).unwrap(), csv);
+}
+
+#[test]
+fn msgpack_rejects_oversized_records() {
+    let columns = vec![Column {
+        name: "id".to_owned(),
+        is_nullable: false,
+        data_type: DataType::Int64,
+        comment: None,
+        default: None,
+        collation: None,
+    }];
+    let tmp_dir = tempdir::TempDir::new("msgpack").unwrap();
+    let csv_path = tmp_dir.path().join("data.csv");
+    let csv_file = std::fs::File::create(&csv_path).unwrap();
+
+    // A corrupt length prefix claiming a 4 GB record, with no data.
+    let input = vec![0xff, 0xff, 0xff, 0xff];
+    let err = copy_msgpack_to_csv(
+        &columns,
+        Box::new(io::Cursor::new(input)),
+        Box::new(csv_file),
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("maximum"), "{}", err);
+}
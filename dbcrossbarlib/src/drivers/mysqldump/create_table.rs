@@ -0,0 +1,420 @@
+//! Reading `CREATE TABLE` statements from `mysqldump` output.
+
+use std::io::BufRead;
+
+use super::parser::Parser;
+use crate::common::*;
+use crate::schema::{Column, ColumnDefault, DataType, DecimalPrecision, Index};
+
+/// Find the `CREATE TABLE` statement for `table_name` in `rdr`, and convert it
+/// to a portable `Table`.
+///
+/// `mysqldump` always writes one column or key per line, with the closing `)`
+/// at the start of its own line, so we work a line at a time.
+pub(crate) fn read_create_table(rdr: impl BufRead, table_name: &str) -> Result<Table> {
+    let mut body: Option<Vec<Vec<u8>>> = None;
+    for line in rdr.split(b'\n') {
+        let line = line.context("error reading MySQL dump")?;
+        match &mut body {
+            None => {
+                if create_table_name(&line)?.as_deref() == Some(table_name) {
+                    body = Some(vec![]);
+                }
+            }
+            Some(body_lines) => {
+                if line.starts_with(b")") {
+                    return parse_create_table_body(table_name, body_lines);
+                }
+                body_lines.push(line);
+            }
+        }
+    }
+    Err(format_err!(
+        "could not find CREATE TABLE for {} in MySQL dump",
+        table_name,
+    ))
+}
+
+/// If `line` starts a `CREATE TABLE` statement, return the table name.
+fn create_table_name(line: &[u8]) -> Result<Option<String>> {
+    let mut p = Parser::new(line);
+    if !(p.try_keyword("CREATE") && p.try_keyword("TABLE")) {
+        return Ok(None);
+    }
+    if p.try_keyword("IF") {
+        p.keyword("NOT")?;
+        p.keyword("EXISTS")?;
+    }
+    Ok(Some(p.ident()?))
+}
+
+/// Parse the lines between `CREATE TABLE ... (` and `)`.
+fn parse_create_table_body(table_name: &str, lines: &[Vec<u8>]) -> Result<Table> {
+    let mut columns = vec![];
+    let mut primary_key = vec![];
+    let mut indexes = vec![];
+    for line in lines {
+        // Strip the trailing comma separating this item from the next.
+        let mut line = line.trim_end_with(|b| b.is_ascii_whitespace());
+        if line.ends_with(b",") {
+            line = &line[..line.len() - 1];
+        }
+
+        let mut p = Parser::new(line);
+        if p.peek() == Some(b'`') {
+            columns.push(parse_column(&mut p).with_context(|_| {
+                format!(
+                    "error parsing MySQL column {:?}",
+                    String::from_utf8_lossy(line),
+                )
+            })?);
+        } else if p.try_keyword("PRIMARY") {
+            p.keyword("KEY")?;
+            primary_key = key_columns(&mut p)?.ok_or_else(|| {
+                format_err!("cannot use a MySQL PRIMARY KEY on column prefixes")
+            })?;
+        } else {
+            let is_unique = p.try_keyword("UNIQUE");
+            if (p.try_keyword("KEY") || p.try_keyword("INDEX"))
+                && p.peek() == Some(b'`')
+            {
+                let name = p.ident()?;
+                // Indexes on column prefixes or expressions aren't portable.
+                if let Some(columns) = key_columns(&mut p)? {
+                    indexes.push(Index {
+                        name: index_name(table_name, &name),
+                        columns,
+                        is_unique,
+                    });
+                }
+            }
+            // We ignore `FULLTEXT` and `SPATIAL` keys, and `CONSTRAINT`s.
+        }
+    }
+
+    // Primary key columns are always `NOT NULL`.
+    for key in &primary_key {
+        let col = columns
+            .iter_mut()
+            .find(|c: &&mut Column| &c.name == key)
+            .ok_or_else(|| format_err!("unknown PRIMARY KEY column {}", key))?;
+        col.is_nullable = false;
+    }
+
+    Ok(Table {
+        name: table_name.to_owned(),
+        columns,
+        primary_key,
+        indexes,
+    })
+}
+
+/// MySQL index names only need to be unique within a table, but most other
+/// databases require them to be unique within a namespace. So make sure they
+/// start with the table name.
+fn index_name(table_name: &str, name: &str) -> String {
+    if name.starts_with(table_name) {
+        name.to_owned()
+    } else {
+        format!("{}_{}", table_name, name)
+    }
+}
+
+/// Parse a list of key columns, like `(`a`,`b`)`. Returns `None` if any
+/// part of the key is a column prefix or an expression.
+fn key_columns(p: &mut Parser<'_>) -> Result<Option<Vec<String>>> {
+    p.punct(b'(')?;
+    let mut columns = vec![];
+    let mut portable = true;
+    loop {
+        if p.peek() == Some(b'`') {
+            columns.push(p.ident()?);
+        } else {
+            portable = false;
+        }
+        // Skip prefix lengths, expressions and `ASC`/`DESC`.
+        while !matches!(p.peek(), Some(b',') | Some(b')') | None) {
+            if p.peek() == Some(b'(') {
+                portable = false;
+                p.skip_parens()?;
+            } else {
+                p.word()?;
+            }
+        }
+        if !p.try_punct(b',') {
+            break;
+        }
+    }
+    p.punct(b')')?;
+    Ok(if portable { Some(columns) } else { None })
+}
+
+/// Parse a column definition.
+fn parse_column(p: &mut Parser<'_>) -> Result<Column> {
+    let name = p.ident()?;
+    let type_name = p.word()?.to_ascii_lowercase();
+    let mut type_args = vec![];
+    if p.try_punct(b'(') {
+        loop {
+            if matches!(p.peek(), Some(b'\'') | Some(b'"')) {
+                type_args.push(String::from_utf8(p.string()?)?);
+            } else {
+                type_args.push(p.word()?);
+            }
+            if !p.try_punct(b',') {
+                break;
+            }
+        }
+        p.punct(b')')?;
+    }
+
+    let mut is_unsigned = false;
+    let mut is_nullable = true;
+    let mut default = None;
+    let mut comment = None;
+    while !p.is_at_end() {
+        if p.try_keyword("UNSIGNED") {
+            is_unsigned = true;
+        } else if p.try_keyword("NOT") {
+            p.keyword("NULL")?;
+            is_nullable = false;
+        } else if p.try_keyword("DEFAULT") {
+            default = Some(parse_default(p)?);
+        } else if p.try_keyword("COMMENT") {
+            comment = Some(String::from_utf8(p.string()?)?);
+        } else if p.try_keyword("GENERATED") || p.try_keyword("AS") {
+            // Generated columns are never included in `INSERT` statements,
+            // and we don't care about the rest of the definition.
+            break;
+        } else if p.peek() == Some(b'(') {
+            p.skip_parens()?;
+        } else if matches!(p.peek(), Some(b'\'') | Some(b'"')) {
+            p.string()?;
+        } else {
+            // `CHARACTER SET`, `COLLATE`, `AUTO_INCREMENT`, `ON UPDATE`, etc.
+            p.word()?;
+        }
+    }
+
+    let data_type = data_type(&type_name, &type_args, is_unsigned)?;
+    let default = match default {
+        Some(MySqlDefault::CurrentTimestamp) => Some(ColumnDefault::CurrentTimestamp),
+        Some(MySqlDefault::Value(value)) => {
+            value.to_csv_cell(&data_type)?.map(ColumnDefault::Literal)
+        }
+        Some(MySqlDefault::Expression) | None => None,
+    };
+    Ok(Column {
+        name,
+        is_nullable,
+        data_type,
+        comment,
+        default,
+        collation: None,
+    })
+}
+
+/// A MySQL `DEFAULT` clause.
+enum MySqlDefault {
+    /// The current time.
+    CurrentTimestamp,
+    /// A literal value.
+    Value(super::value::MySqlValue),
+    /// Some other expression, which we'll ignore.
+    Expression,
+}
+
+/// Parse the value after `DEFAULT`.
+fn parse_default(p: &mut Parser<'_>) -> Result<MySqlDefault> {
+    if p.try_keyword("CURRENT_TIMESTAMP") || p.try_keyword("NOW") {
+        // This may have a precision, like `CURRENT_TIMESTAMP(6)`.
+        if p.peek() == Some(b'(') {
+            p.skip_parens()?;
+        }
+        Ok(MySqlDefault::CurrentTimestamp)
+    } else if p.peek() == Some(b'(') {
+        p.skip_parens()?;
+        Ok(MySqlDefault::Expression)
+    } else {
+        Ok(MySqlDefault::Value(p.value()?))
+    }
+}
+
+/// Convert a MySQL type to a portable `DataType`.
+fn data_type(type_name: &str, args: &[String], is_unsigned: bool) -> Result<DataType> {
+    let precision_arg = |idx: usize| -> Result<Option<u32>> {
+        args.get(idx).map(|a| Ok(a.parse::<u32>()?)).transpose()
+    };
+    match type_name {
+        // `BOOLEAN` is an alias for `TINYINT(1)`, which is how `mysqldump`
+        // writes it.
+        "bool" | "boolean" => Ok(DataType::Bool),
+        "tinyint" if args == ["1"] => Ok(DataType::Bool),
+        "bit" if args.is_empty() || args == ["1"] => Ok(DataType::Bool),
+        "bit" => Ok(DataType::Int64),
+        "tinyint" => Ok(DataType::Int16),
+        "smallint" if is_unsigned => Ok(DataType::Int32),
+        "smallint" => Ok(DataType::Int16),
+        "mediumint" => Ok(DataType::Int32),
+        "int" | "integer" if is_unsigned => Ok(DataType::Int64),
+        "int" | "integer" => Ok(DataType::Int32),
+        // Unsigned `BIGINT` values don't fit in 64 bits.
+        "bigint" if is_unsigned => Ok(DataType::DecimalWithPrecision(
+            DecimalPrecision::new(20, 0)?,
+        )),
+        "bigint" => Ok(DataType::Int64),
+        "decimal" | "numeric" | "dec" | "fixed" => {
+            // MySQL defaults to `DECIMAL(10,0)`.
+            let precision = precision_arg(0)?.unwrap_or(10);
+            let scale = precision_arg(1)?.unwrap_or(0);
+            Ok(DataType::DecimalWithPrecision(DecimalPrecision::new(
+                precision, scale,
+            )?))
+        }
+        "float" => Ok(DataType::Float32),
+        "double" | "real" => Ok(DataType::Float64),
+        // MySQL measures these lengths in characters, as do we.
+        "char" | "varchar" => match precision_arg(0)? {
+            Some(len) => Ok(DataType::TextWithMaxLength(len)),
+            None => Ok(DataType::Text),
+        },
+        "tinytext" | "text" | "mediumtext" | "longtext" | "set" => Ok(DataType::Text),
+        "enum" => Ok(DataType::OneOf(args.to_owned())),
+        "binary" | "varbinary" | "tinyblob" | "blob" | "mediumblob" | "longblob" => {
+            Ok(DataType::Bytes)
+        }
+        "date" => Ok(DataType::Date),
+        "datetime" => Ok(DataType::TimestampWithoutTimeZone),
+        "timestamp" => Ok(DataType::TimestampWithTimeZone),
+        "time" => Ok(DataType::Time),
+        "year" => Ok(DataType::Int16),
+        "json" => Ok(DataType::Json),
+        _ => Err(format_err!("cannot read MySQL type {} yet", type_name)),
+    }
+}
+
+/// Helper trait for trimming byte slices.
+trait TrimEndWith {
+    /// Remove any trailing bytes matching `pred`.
+    fn trim_end_with(&self, pred: impl Fn(u8) -> bool) -> &Self;
+}
+
+impl TrimEndWith for [u8] {
+    fn trim_end_with(&self, pred: impl Fn(u8) -> bool) -> &Self {
+        let mut end = self.len();
+        while end > 0 && pred(self[end - 1]) {
+            end -= 1;
+        }
+        &self[..end]
+    }
+}
+
+#[test]
+fn reads_create_table() {
+    let dump = br#"
+-- MySQL dump 10.13
+DROP TABLE IF EXISTS `other`;
+CREATE TABLE `other` (
+  `x` int NOT NULL
+) ENGINE=InnoDB;
+CREATE TABLE `users` (
+  `id` int unsigned NOT NULL AUTO_INCREMENT,
+  `email` varchar(255) CHARACTER SET utf8mb4 COLLATE utf8mb4_unicode_ci NOT NULL COMMENT 'Login, lowercased',
+  `is_admin` tinyint(1) NOT NULL DEFAULT '0',
+  `status` enum('new','active','it''s closed') DEFAULT 'new',
+  `balance` decimal(10,2) DEFAULT NULL,
+  `created_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+  `avatar` blob,
+  PRIMARY KEY (`id`),
+  UNIQUE KEY `email` (`email`),
+  KEY `users_status_idx` (`status`,`created_at`),
+  KEY `email_prefix` (`email`(10)),
+  FULLTEXT KEY `email_text` (`email`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+"#;
+    let table = read_create_table(&dump[..], "users").unwrap();
+    let columns = table
+        .columns
+        .iter()
+        .map(|c| {
+            (
+                &c.name[..],
+                c.data_type.clone(),
+                c.is_nullable,
+                c.default.clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+    let literal = |s: &str| Some(ColumnDefault::Literal(s.to_owned()));
+    assert_eq!(
+        columns,
+        vec![
+            ("id", DataType::Int64, false, None),
+            ("email", DataType::TextWithMaxLength(255), false, None),
+            ("is_admin", DataType::Bool, false, literal("f")),
+            (
+                "status",
+                DataType::OneOf(vec![
+                    "new".to_owned(),
+                    "active".to_owned(),
+                    "it's closed".to_owned(),
+                ]),
+                true,
+                literal("new"),
+            ),
+            (
+                "balance",
+                DataType::DecimalWithPrecision(DecimalPrecision::new(10, 2).unwrap()),
+                true,
+                None,
+            ),
+            (
+                "created_at",
+                DataType::TimestampWithTimeZone,
+                false,
+                Some(ColumnDefault::CurrentTimestamp),
+            ),
+            ("avatar", DataType::Bytes, true, None),
+        ],
+    );
+    assert_eq!(
+        table.columns[1].comment.as_deref(),
+        Some("Login, lowercased")
+    );
+    assert_eq!(table.primary_key, vec!["id".to_owned()]);
+    assert_eq!(
+        table.indexes,
+        vec![
+            Index {
+                name: "users_email".to_owned(),
+                columns: vec!["email".to_owned()],
+                is_unique: true,
+            },
+            Index {
+                name: "users_status_idx".to_owned(),
+                columns: vec!["status".to_owned(), "created_at".to_owned()],
+                is_unique: false,
+            },
+        ],
+    );
+
+    assert!(read_create_table(&dump[..], "missing").is_err());
+}
+
+#[test]
+fn unsigned_integers_are_widened() {
+    // Each type must be able to hold the largest unsigned value.
+    let examples = &[
+        ("tinyint", DataType::Int16),
+        ("smallint", DataType::Int32),
+        ("mediumint", DataType::Int32),
+        ("int", DataType::Int64),
+        (
+            "bigint",
+            DataType::DecimalWithPrecision(DecimalPrecision::new(20, 0).unwrap()),
+        ),
+    ];
+    for (type_name, expected) in examples {
+        assert_eq!(&data_type(type_name, &[], true).unwrap(), expected);
+    }
+}
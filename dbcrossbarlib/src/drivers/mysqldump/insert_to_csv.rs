@@ -0,0 +1,211 @@
+//! Convert the `INSERT` statements in `mysqldump` output into our CSV
+//! interchange format.
+
+use std::io::{self, BufRead};
+
+use super::{parser::Parser, value::check_integer_range};
+use crate::common::*;
+use crate::schema::{Column, DataType};
+
+/// Read a `mysqldump` SQL file, and write the rows inserted into `table_name`
+/// as CSV. All other statements are ignored.
+///
+/// We decode values using the MySQL types in `columns`, and then make sure
+/// that any integers fit in the corresponding `output_types`, which may come
+/// from a different schema.
+///
+/// This is synchronous, and should be run using `spawn_sync_transform`.
+pub(crate) fn insert_sql_to_csv(
+    table_name: &str,
+    columns: &[Column],
+    output_types: &[DataType],
+    rdr: Box<dyn Read>,
+    wtr: Box<dyn Write>,
+) -> Result<()> {
+    let mut rdr = io::BufReader::with_capacity(BUFFER_SIZE, rdr);
+    let mut wtr =
+        csv::Writer::from_writer(io::BufWriter::with_capacity(BUFFER_SIZE, wtr));
+    wtr.write_record(columns.iter().map(|c| &c.name))?;
+
+    // `INSERT` lines may contain binary data, so read bytes, not `String`s.
+    let mut line = vec![];
+    let mut row = vec![None; columns.len()];
+    loop {
+        line.clear();
+        if rdr
+            .read_until(b'\n', &mut line)
+            .context("error reading MySQL dump")?
+            == 0
+        {
+            break;
+        }
+        while line
+            .last()
+            .map(|&b| b == b'\n' || b == b'\r')
+            .unwrap_or(false)
+        {
+            line.pop();
+        }
+
+        let mut p = Parser::new(&line);
+        if !is_insert_into(&mut p, table_name)? {
+            continue;
+        }
+        let column_indices = insert_column_indices(&mut p, columns)?;
+        p.keyword("VALUES")?;
+        loop {
+            p.punct(b'(')?;
+            let mut count = 0;
+            loop {
+                let value = p.value()?;
+                let idx = match &column_indices {
+                    Some(indices) => indices.get(count).copied().flatten(),
+                    None => Some(count),
+                };
+                if let Some(idx) = idx.filter(|&idx| idx < columns.len()) {
+                    let col = &columns[idx];
+                    let cell = value
+                        .to_csv_cell(&col.data_type)
+                        .and_then(|cell| {
+                            if let Some(cell) = &cell {
+                                check_integer_range(cell, &output_types[idx])?;
+                            }
+                            Ok(cell)
+                        })
+                        .with_context(|_| {
+                            format!("error reading MySQL column {}", col.name)
+                        })?;
+                    row[idx] = cell;
+                }
+                count += 1;
+                if !p.try_punct(b',') {
+                    break;
+                }
+            }
+            p.punct(b')')?;
+            let expected = column_indices
+                .as_ref()
+                .map(|indices| indices.len())
+                .unwrap_or_else(|| columns.len());
+            if count != expected {
+                return Err(format_err!(
+                    "expected {} columns in MySQL INSERT, found {}",
+                    expected,
+                    count,
+                ));
+            }
+            wtr.write_record(
+                row.iter_mut().map(|cell| cell.take().unwrap_or_default()),
+            )?;
+            if !p.try_punct(b',') {
+                break;
+            }
+        }
+        p.try_punct(b';');
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Does `p` start with `INSERT INTO` or `REPLACE INTO` for `table_name`?
+fn is_insert_into(p: &mut Parser<'_>, table_name: &str) -> Result<bool> {
+    if p.try_keyword("INSERT") {
+        p.try_keyword("IGNORE");
+    } else if !p.try_keyword("REPLACE") {
+        return Ok(false);
+    }
+    if !p.try_keyword("INTO") {
+        return Ok(false);
+    }
+    Ok(p.ident()? == table_name)
+}
+
+/// Parse an optional column list, like the one written by `mysqldump
+/// --complete-insert`, and map each column to its index in `columns`.
+///
+/// Columns which aren't in our schema map to `None`, so we can skip them.
+fn insert_column_indices(
+    p: &mut Parser<'_>,
+    columns: &[Column],
+) -> Result<Option<Vec<Option<usize>>>> {
+    if p.peek() != Some(b'(') {
+        return Ok(None);
+    }
+    p.punct(b'(')?;
+    let mut indices = vec![];
+    loop {
+        let name = p.ident()?;
+        indices.push(columns.iter().position(|c| c.name == name));
+        if !p.try_punct(b',') {
+            break;
+        }
+    }
+    p.punct(b')')?;
+    Ok(Some(indices))
+}
+
+#[test]
+fn converts_inserts_to_csv() {
+    use crate::schema::DataType;
+
+    let column = |name: &str, data_type: DataType| Column {
+        name: name.to_owned(),
+        is_nullable: true,
+        data_type,
+        comment: None,
+        default: None,
+        collation: None,
+    };
+    let columns = vec![
+        column("id", DataType::Int32),
+        column("name", DataType::Text),
+        column("active", DataType::Bool),
+    ];
+    let dump = b"
+INSERT INTO `other` VALUES (1);
+INSERT INTO `users` VALUES (1,'Ann',1),(2,'Bob, \\\"Jr.\\\"',0);
+INSERT INTO `users` (`active`, `id`) VALUES (1,3);
+REPLACE INTO `users` VALUES (4,NULL,NULL);
+";
+    let types = columns
+        .iter()
+        .map(|c| c.data_type.clone())
+        .collect::<Vec<_>>();
+    let tmp_dir = tempdir::TempDir::new("insert_to_csv").unwrap();
+    let output_path = tmp_dir.path().join("output.csv");
+    let output = std::fs::File::create(&output_path).unwrap();
+    insert_sql_to_csv(
+        "users",
+        &columns,
+        &types,
+        Box::new(&dump[..]),
+        Box::new(output),
+    )
+    .unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&output_path).unwrap(),
+        "id,name,active\n1,Ann,t\n2,\"Bob, \"\"Jr.\"\"\",f\n3,,t\n4,,\n",
+    );
+
+    let bad = b"INSERT INTO `users` VALUES (1,'Ann');\n";
+    assert!(insert_sql_to_csv(
+        "users",
+        &columns,
+        &types,
+        Box::new(&bad[..]),
+        Box::new(io::sink()),
+    )
+    .is_err());
+
+    // A value that doesn't fit in the output type is an error.
+    let big = b"INSERT INTO `users` VALUES (2147483648,'Ann',1);\n";
+    let err = insert_sql_to_csv(
+        "users",
+        &columns,
+        &types,
+        Box::new(&big[..]),
+        Box::new(io::sink()),
+    )
+    .unwrap_err();
+    assert!(format!("{:?}", err).contains("out of range"), "{:?}", err);
+}
@@ -0,0 +1,170 @@
+//! Driver for reading tables from `mysqldump` output.
+//!
+//! We parse the `CREATE TABLE` and `INSERT` statements directly, which means
+//! we can read archived MySQL and MariaDB dumps without a running server.
+
+use std::{fmt, fs::File, io::BufReader, path::PathBuf, str::FromStr};
+
+use crate::common::*;
+use crate::tokio_glue::{copy_reader_to_stream, run_sync_fn_in_background};
+use crate::transform::spawn_sync_transform;
+
+mod create_table;
+mod insert_to_csv;
+mod parser;
+mod value;
+
+use self::create_table::read_create_table;
+use self::insert_to_csv::insert_sql_to_csv;
+
+/// A table in a plain SQL file written by `mysqldump`.
+#[derive(Clone, Debug)]
+pub struct MySqlDumpLocator {
+    path: PathBuf,
+    table_name: String,
+}
+
+impl MySqlDumpLocator {
+    /// Read the `CREATE TABLE` statement for our table.
+    async fn table(&self, ctx: &Context) -> Result<Table> {
+        debug!(ctx.log(), "reading CREATE TABLE from {}", self);
+        let source = self.to_owned();
+        run_sync_fn_in_background("mysqldump schema".to_owned(), move || {
+            let file = File::open(&source.path).with_context(|_| {
+                format!("error opening {}", source.path.display())
+            })?;
+            let table = read_create_table(BufReader::new(file), &source.table_name)
+                .with_context(|_| format!("error reading schema from {}", source))?;
+            Ok(table)
+        })
+        .await
+    }
+}
+
+impl fmt::Display for MySqlDumpLocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}#{}",
+            Self::scheme(),
+            self.path.display(),
+            self.table_name,
+        )
+    }
+}
+
+impl FromStr for MySqlDumpLocator {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if !s.starts_with(Self::scheme()) {
+            return Err(format_err!(
+                "expected {} locator, found {}",
+                Self::scheme(),
+                s
+            ));
+        }
+        let rest = &s[Self::scheme().len()..];
+        let idx = rest.find('#').ok_or_else(|| {
+            format_err!("{} locator must end with #table: {}", Self::scheme(), s)
+        })?;
+        let (path, table_name) = (&rest[..idx], &rest[idx + 1..]);
+        if path.is_empty() || path == "-" {
+            return Err(format_err!("{} requires a file path", Self::scheme()));
+        }
+        if table_name.is_empty() {
+            return Err(format_err!("{} requires a table name", Self::scheme()));
+        }
+        Ok(MySqlDumpLocator {
+            path: PathBuf::from(path),
+            table_name: table_name.to_owned(),
+        })
+    }
+}
+
+impl Locator for MySqlDumpLocator {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self, ctx: Context) -> BoxFuture<Option<Table>> {
+        let source = self.to_owned();
+        async move { Ok(Some(source.table(&ctx).await?)) }.boxed()
+    }
+
+    fn local_data(
+        &self,
+        ctx: Context,
+        shared_args: SharedArguments<Unverified>,
+        source_args: SourceArguments<Unverified>,
+    ) -> BoxFuture<Option<BoxStream<CsvStream>>> {
+        local_data_helper(ctx, self.to_owned(), shared_args, source_args).boxed()
+    }
+}
+
+impl LocatorStatic for MySqlDumpLocator {
+    fn scheme() -> &'static str {
+        "mysqldump:"
+    }
+
+    fn features() -> Features {
+        Features {
+            locator: LocatorFeatures::Schema | LocatorFeatures::LocalData,
+            write_schema_if_exists: EnumSet::empty(),
+            source_args: EnumSet::empty(),
+            dest_args: EnumSet::empty(),
+            dest_if_exists: EnumSet::empty(),
+            _placeholder: (),
+        }
+    }
+}
+
+/// Implementation of `local_data`, but as a real `async` function.
+async fn local_data_helper(
+    ctx: Context,
+    source: MySqlDumpLocator,
+    shared_args: SharedArguments<Unverified>,
+    source_args: SourceArguments<Unverified>,
+) -> Result<Option<BoxStream<CsvStream>>> {
+    let shared_args = shared_args.verify(MySqlDumpLocator::features())?;
+    let _source_args = source_args.verify(MySqlDumpLocator::features())?;
+
+    // Use the schema stored in the dump to decode the data, not the portable
+    // schema, because we need to know which columns were `TINYINT(1)`, etc.
+    // But check integers against the portable schema, which may be narrower.
+    let table = source.table(&ctx).await?;
+    let columns = table.columns.clone();
+    let output_types = columns
+        .iter()
+        .map(|col| {
+            shared_args
+                .schema()
+                .columns
+                .iter()
+                .find(|c| c.name == col.name)
+                .map(|c| c.data_type.clone())
+                .unwrap_or_else(|| col.data_type.clone())
+        })
+        .collect::<Vec<_>>();
+
+    debug!(ctx.log(), "reading INSERT statements from {}", source);
+    let file = tokio::fs::File::open(&source.path)
+        .await
+        .with_context(|_| format!("error opening {}", source.path.display()))?;
+    let sql_data = copy_reader_to_stream(ctx.clone(), file)?;
+    let table_name = source.table_name.clone();
+    let csv_data = spawn_sync_transform(
+        ctx,
+        "insert_sql_to_csv".to_owned(),
+        sql_data.boxed(),
+        move |_ctx, rdr, wtr| {
+            insert_sql_to_csv(&table_name, &columns, &output_types, rdr, wtr)
+        },
+    )?;
+
+    let csv_stream = CsvStream {
+        name: table.name,
+        data: csv_data,
+    };
+    Ok(Some(box_stream_once(Ok(csv_stream))))
+}
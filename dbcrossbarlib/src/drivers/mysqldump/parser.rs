@@ -0,0 +1,290 @@
+//! A small hand-written parser for the SQL written by `mysqldump`.
+//!
+//! `mysqldump` writes each statement on a single line (or, for `CREATE TABLE`,
+//! one column per line), and `INSERT` lines can be very long. So instead of a
+//! full SQL grammar, we provide a few simple token-level helpers that work
+//! directly on the bytes of a line. We use bytes instead of `str`, because
+//! binary columns may contain invalid UTF-8.
+
+use super::value::MySqlValue;
+use crate::common::*;
+
+/// A parser for a single line of `mysqldump` output.
+pub(crate) struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Create a new parser for `input`.
+    pub(crate) fn new(input: &'a [u8]) -> Self {
+        Parser { input, pos: 0 }
+    }
+
+    /// Skip any whitespace.
+    fn skip_ws(&mut self) {
+        while self
+            .input
+            .get(self.pos)
+            .map(|b| b.is_ascii_whitespace())
+            .unwrap_or(false)
+        {
+            self.pos += 1;
+        }
+    }
+
+    /// Have we consumed all our input, ignoring whitespace?
+    pub(crate) fn is_at_end(&mut self) -> bool {
+        self.peek().is_none()
+    }
+
+    /// Look at the next non-whitespace byte, without consuming it.
+    pub(crate) fn peek(&mut self) -> Option<u8> {
+        self.skip_ws();
+        self.input.get(self.pos).copied()
+    }
+
+    /// Consume `c` if it's the next non-whitespace byte.
+    pub(crate) fn try_punct(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consume `c`, or return an error.
+    pub(crate) fn punct(&mut self, c: u8) -> Result<()> {
+        if self.try_punct(c) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("{:?}", c as char)))
+        }
+    }
+
+    /// Consume the keyword `kw` (case-insensitive) if it comes next.
+    pub(crate) fn try_keyword(&mut self, kw: &str) -> bool {
+        self.skip_ws();
+        let end = self.pos + kw.len();
+        let matches = self
+            .input
+            .get(self.pos..end)
+            .map(|found| found.eq_ignore_ascii_case(kw.as_bytes()))
+            .unwrap_or(false)
+            && !self
+                .input
+                .get(end)
+                .copied()
+                .map(is_word_byte)
+                .unwrap_or(false);
+        if matches {
+            self.pos = end;
+        }
+        matches
+    }
+
+    /// Consume the keyword `kw` (case-insensitive), or return an error.
+    pub(crate) fn keyword(&mut self, kw: &str) -> Result<()> {
+        if self.try_keyword(kw) {
+            Ok(())
+        } else {
+            Err(self.error(kw))
+        }
+    }
+
+    /// Consume a bare word, such as a type name or a number.
+    pub(crate) fn word(&mut self) -> Result<String> {
+        self.skip_ws();
+        let start = self.pos;
+        while self
+            .input
+            .get(self.pos)
+            .copied()
+            .map(|b| is_word_byte(b) || b == b'.' || b == b'-' || b == b'+')
+            .unwrap_or(false)
+        {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(self.error("word"));
+        }
+        Ok(String::from_utf8_lossy(&self.input[start..self.pos]).into_owned())
+    }
+
+    /// Consume a backtick-quoted identifier.
+    pub(crate) fn ident(&mut self) -> Result<String> {
+        let bytes = self.quoted(b'`', false)?;
+        Ok(String::from_utf8(bytes).context("MySQL identifier was not UTF-8")?)
+    }
+
+    /// Consume a single- or double-quoted string, returning the raw bytes.
+    pub(crate) fn string(&mut self) -> Result<Vec<u8>> {
+        match self.peek() {
+            Some(quote @ b'\'') | Some(quote @ b'"') => self.quoted(quote, true),
+            _ => Err(self.error("string")),
+        }
+    }
+
+    /// Consume a string delimited by `quote`. A doubled `quote` stands for a
+    /// single `quote`. If `backslash_escapes` is true, also handle MySQL's
+    /// backslash escapes.
+    fn quoted(&mut self, quote: u8, backslash_escapes: bool) -> Result<Vec<u8>> {
+        self.punct(quote)?;
+        let mut bytes = vec![];
+        loop {
+            let b = self
+                .input
+                .get(self.pos)
+                .copied()
+                .ok_or_else(|| self.error("end of string"))?;
+            self.pos += 1;
+            if b == quote {
+                if self.input.get(self.pos) == Some(&quote) {
+                    bytes.push(quote);
+                    self.pos += 1;
+                } else {
+                    return Ok(bytes);
+                }
+            } else if b == b'\\' && backslash_escapes {
+                let escaped = self
+                    .input
+                    .get(self.pos)
+                    .copied()
+                    .ok_or_else(|| self.error("escaped character"))?;
+                self.pos += 1;
+                match escaped {
+                    b'0' => bytes.push(b'\0'),
+                    b'b' => bytes.push(b'\x08'),
+                    b'n' => bytes.push(b'\n'),
+                    b'r' => bytes.push(b'\r'),
+                    b't' => bytes.push(b'\t'),
+                    b'Z' => bytes.push(b'\x1a'),
+                    // These are only escaped in `LIKE` patterns, so MySQL
+                    // keeps the backslash.
+                    b'%' | b'_' => bytes.extend_from_slice(&[b'\\', escaped]),
+                    other => bytes.push(other),
+                }
+            } else {
+                bytes.push(b);
+            }
+        }
+    }
+
+    /// Skip over a parenthesized expression, including any nested
+    /// parentheses and strings.
+    pub(crate) fn skip_parens(&mut self) -> Result<()> {
+        self.punct(b'(')?;
+        let mut depth = 1;
+        while depth > 0 {
+            match self.peek() {
+                Some(b'(') => {
+                    self.pos += 1;
+                    depth += 1;
+                }
+                Some(b')') => {
+                    self.pos += 1;
+                    depth -= 1;
+                }
+                Some(b'\'') | Some(b'"') => {
+                    self.string()?;
+                }
+                Some(b'`') => {
+                    self.ident()?;
+                }
+                Some(_) => self.pos += 1,
+                None => return Err(self.error("')'")),
+            }
+        }
+        Ok(())
+    }
+
+    /// Consume a literal value, as found in `INSERT` statements and `DEFAULT`
+    /// clauses.
+    pub(crate) fn value(&mut self) -> Result<MySqlValue> {
+        match self.peek() {
+            Some(b'\'') | Some(b'"') => Ok(MySqlValue::Str(self.string()?)),
+            Some(b'`') => Err(self.error("value")),
+            Some(_) if self.try_keyword("NULL") => Ok(MySqlValue::Null),
+            Some(_) => {
+                let word = self.word()?;
+                let lower = word.to_ascii_lowercase();
+                if lower.starts_with("0x") {
+                    Ok(MySqlValue::Hex(decode_hex(&word[2..])?))
+                } else if lower == "x" && self.peek() == Some(b'\'') {
+                    let hex = self.string()?;
+                    Ok(MySqlValue::Hex(decode_hex(&String::from_utf8_lossy(&hex))?))
+                } else if lower == "b" && self.peek() == Some(b'\'') {
+                    let bits = String::from_utf8_lossy(&self.string()?).into_owned();
+                    Ok(MySqlValue::Bits(bits))
+                } else if lower.starts_with('_') && self.peek() == Some(b'\'') {
+                    // A character set introducer, like `_binary '...'`.
+                    Ok(MySqlValue::Str(self.string()?))
+                } else if lower == "true" {
+                    Ok(MySqlValue::Number("1".to_owned()))
+                } else if lower == "false" {
+                    Ok(MySqlValue::Number("0".to_owned()))
+                } else if word.parse::<f64>().is_ok() {
+                    Ok(MySqlValue::Number(word))
+                } else {
+                    Err(format_err!("unexpected {:?} in MySQL dump", word))
+                }
+            }
+            None => Err(self.error("value")),
+        }
+    }
+
+    /// Build an error saying we expected `expected`.
+    fn error(&self, expected: &str) -> Error {
+        let context_end = (self.pos + 40).min(self.input.len());
+        format_err!(
+            "expected {} in MySQL dump at {:?}",
+            expected,
+            String::from_utf8_lossy(
+                &self.input[self.pos.min(context_end)..context_end]
+            ),
+        )
+    }
+}
+
+/// Can `b` appear in a bare word?
+fn is_word_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'$'
+}
+
+/// Decode a string of hexadecimal digits.
+fn decode_hex(digits: &str) -> Result<Vec<u8>> {
+    Ok(hex::decode(digits)
+        .with_context(|_| format!("invalid hex digits in {:?}", digits))?)
+}
+
+#[test]
+fn parses_values() {
+    let mut p = Parser::new(
+        br#"(1,-2.5e3,NULL,'it\'s ''ok''\n',0x4142,X'43',b'101',_binary 'x',"dq")"#,
+    );
+    p.punct(b'(').unwrap();
+    let mut values = vec![];
+    loop {
+        values.push(p.value().unwrap());
+        if !p.try_punct(b',') {
+            break;
+        }
+    }
+    p.punct(b')').unwrap();
+    assert!(p.is_at_end());
+    assert_eq!(
+        values,
+        vec![
+            MySqlValue::Number("1".to_owned()),
+            MySqlValue::Number("-2.5e3".to_owned()),
+            MySqlValue::Null,
+            MySqlValue::Str(b"it's 'ok'\n".to_vec()),
+            MySqlValue::Hex(b"AB".to_vec()),
+            MySqlValue::Hex(b"C".to_vec()),
+            MySqlValue::Bits("101".to_owned()),
+            MySqlValue::Str(b"x".to_vec()),
+            MySqlValue::Str(b"dq".to_vec()),
+        ],
+    );
+}
@@ -0,0 +1,198 @@
+//! Literal values in `mysqldump` output.
+
+use crate::common::*;
+use crate::schema::DataType;
+
+/// A literal value, as it appears in an `INSERT` statement or a `DEFAULT`
+/// clause.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum MySqlValue {
+    /// `NULL`.
+    Null,
+    /// A number, exactly as written.
+    Number(String),
+    /// A quoted string, which may contain binary data.
+    Str(Vec<u8>),
+    /// A hexadecimal literal, as written by `mysqldump --hex-blob`.
+    Hex(Vec<u8>),
+    /// A bit-value literal like `b'101'`, as written for `BIT` columns.
+    Bits(String),
+}
+
+impl MySqlValue {
+    /// Convert this value into a cell in our CSV interchange format, assuming
+    /// it belongs to a column of type `data_type`. Returns `None` for `NULL`.
+    ///
+    /// MySQL allows "zero" dates like `0000-00-00`, which have no equivalent
+    /// anywhere else, so we treat them as `NULL`.
+    pub(crate) fn to_csv_cell(&self, data_type: &DataType) -> Result<Option<String>> {
+        match (self, data_type) {
+            (MySqlValue::Null, _) => Ok(None),
+
+            // Booleans are usually stored as `TINYINT(1)` or `BIT(1)`.
+            (MySqlValue::Number(n), DataType::Bool) => {
+                Ok(Some(bool_cell(n.parse::<f64>()? != 0.0)))
+            }
+            (MySqlValue::Bits(bits), DataType::Bool) => {
+                Ok(Some(bool_cell(bits.contains('1'))))
+            }
+            (MySqlValue::Hex(bytes), DataType::Bool) => {
+                Ok(Some(bool_cell(bytes.iter().any(|&b| b != 0))))
+            }
+            (MySqlValue::Str(s), DataType::Bool) => match &s[..] {
+                b"0" | b"\0" => Ok(Some(bool_cell(false))),
+                b"1" | b"\x01" => Ok(Some(bool_cell(true))),
+                _ => Err(format_err!("cannot convert {:?} to a boolean", self)),
+            },
+
+            // Binary data is written as base64.
+            (MySqlValue::Str(bytes), DataType::Bytes)
+            | (MySqlValue::Hex(bytes), DataType::Bytes) => {
+                Ok(Some(base64::encode(bytes)))
+            }
+
+            // Dates and times need a bit of cleanup.
+            (MySqlValue::Str(s), DataType::Date)
+            | (MySqlValue::Str(s), DataType::TimestampWithoutTimeZone)
+            | (MySqlValue::Str(s), DataType::TimestampWithTimeZone) => {
+                let s = utf8(s)?;
+                if s.starts_with("0000-00-00") {
+                    Ok(None)
+                } else if *data_type == DataType::Date {
+                    Ok(Some(s))
+                } else {
+                    // `mysqldump` writes `TIMESTAMP` values in UTC by default.
+                    let mut ts = s.replacen(' ', "T", 1);
+                    if *data_type == DataType::TimestampWithTimeZone {
+                        ts.push('Z');
+                    }
+                    Ok(Some(ts))
+                }
+            }
+
+            // `BIT(n)` columns are stored as integers.
+            (MySqlValue::Bits(bits), _) => {
+                let value = u64::from_str_radix(bits, 2)
+                    .with_context(|_| format!("invalid bit value {:?}", bits))?;
+                Ok(Some(value.to_string()))
+            }
+
+            (MySqlValue::Number(n), _) => Ok(Some(n.to_owned())),
+            (MySqlValue::Str(s), _) | (MySqlValue::Hex(s), _) => Ok(Some(utf8(s)?)),
+        }
+    }
+}
+
+/// Make sure that an integer `cell` fits in `data_type`. We map unsigned MySQL
+/// integers to portable types that are wide enough to hold them, but if the
+/// user asks for a narrower type using `--schema` or `--cast`, we want to fail
+/// loudly instead of loading values that will overflow or wrap. Cells that
+/// aren't plain integers are left for the destination to check.
+pub(crate) fn check_integer_range(cell: &str, data_type: &DataType) -> Result<()> {
+    let value = match cell.parse::<i128>() {
+        Ok(value) => value,
+        Err(_) => return Ok(()),
+    };
+    let (name, min, max) = match data_type {
+        DataType::Int16 => ("int16", i128::from(i16::MIN), i128::from(i16::MAX)),
+        DataType::Int32 => ("int32", i128::from(i32::MIN), i128::from(i32::MAX)),
+        DataType::Int64 => ("int64", i128::from(i64::MIN), i128::from(i64::MAX)),
+        _ => return Ok(()),
+    };
+    if value < min || value > max {
+        return Err(format_err!(
+            "value {} is out of range for {} (use a wider type, like numeric(20,0))",
+            value,
+            name,
+        ));
+    }
+    Ok(())
+}
+
+/// Format a boolean for our CSV interchange format.
+fn bool_cell(value: bool) -> String {
+    if value { "t" } else { "f" }.to_owned()
+}
+
+/// Convert `bytes` to a UTF-8 string.
+fn utf8(bytes: &[u8]) -> Result<String> {
+    Ok(String::from_utf8(bytes.to_owned()).context("MySQL text was not UTF-8")?)
+}
+
+#[test]
+fn converts_values_to_csv_cells() {
+    let examples = &[
+        (MySqlValue::Null, DataType::Text, None),
+        (
+            MySqlValue::Number("1".to_owned()),
+            DataType::Bool,
+            Some("t"),
+        ),
+        (MySqlValue::Bits("0".to_owned()), DataType::Bool, Some("f")),
+        (
+            MySqlValue::Bits("101".to_owned()),
+            DataType::Int64,
+            Some("5"),
+        ),
+        (
+            MySqlValue::Hex(b"hi".to_vec()),
+            DataType::Bytes,
+            Some("aGk="),
+        ),
+        (
+            MySqlValue::Str(b"2020-01-02 03:04:05".to_vec()),
+            DataType::TimestampWithTimeZone,
+            Some("2020-01-02T03:04:05Z"),
+        ),
+        (
+            MySqlValue::Str(b"2020-01-02 03:04:05.5".to_vec()),
+            DataType::TimestampWithoutTimeZone,
+            Some("2020-01-02T03:04:05.5"),
+        ),
+        (
+            MySqlValue::Str(b"0000-00-00".to_vec()),
+            DataType::Date,
+            None,
+        ),
+        (
+            MySqlValue::Str(b"hello".to_vec()),
+            DataType::Text,
+            Some("hello"),
+        ),
+    ];
+    for (value, data_type, expected) in examples {
+        assert_eq!(
+            value.to_csv_cell(data_type).unwrap().as_deref(),
+            *expected,
+            "converting {:?} to {:?}",
+            value,
+            data_type,
+        );
+    }
+}
+
+#[test]
+fn integer_ranges_are_checked() {
+    let ok = &[
+        ("32767", DataType::Int16),
+        ("-32768", DataType::Int16),
+        ("2147483647", DataType::Int32),
+        ("9223372036854775807", DataType::Int64),
+        ("-9223372036854775808", DataType::Int64),
+        ("18446744073709551615", DataType::Text),
+        ("t", DataType::Int64),
+    ];
+    for (cell, data_type) in ok {
+        check_integer_range(cell, data_type).unwrap();
+    }
+    let too_big = &[
+        ("32768", DataType::Int16),
+        ("2147483648", DataType::Int32),
+        ("9223372036854775808", DataType::Int64),
+        ("18446744073709551615", DataType::Int64),
+    ];
+    for (cell, data_type) in too_big {
+        let err = check_integer_range(cell, data_type).unwrap_err();
+        assert!(err.to_string().contains("out of range"), "{}", err);
+    }
+}
@@ -0,0 +1,421 @@
+//! Code shared between drivers which read GIS files using GDAL's `ogrinfo`
+//! and `ogr2ogr` tools.
+//!
+//! We shell out to these tools for the same reason we shell out to `bq` and
+//! `aws`: GDAL supports dozens of obscure file formats, and it's already
+//! installed on the machines of anybody who works with GIS data.
+
+use futures::future;
+use serde::Deserialize;
+use std::{convert::TryFrom, path::Path, process::Stdio};
+use tokio::{io::BufReader, process::Command};
+
+use crate::common::*;
+use crate::schema::{Column, DataType, Srid};
+
+/// The output of `ogrinfo -json`.
+#[derive(Debug, Deserialize)]
+struct OgrInfo {
+    /// The layers in our data source.
+    layers: Vec<OgrLayer>,
+}
+
+/// A layer in an OGR data source.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OgrLayer {
+    /// The name of this layer.
+    name: String,
+    /// The geometry fields in this layer. We only support the first.
+    #[serde(default)]
+    geometry_fields: Vec<OgrGeometryField>,
+    /// The attribute fields of this layer.
+    #[serde(default)]
+    fields: Vec<OgrField>,
+}
+
+/// A geometry field in an OGR layer.
+#[derive(Debug, Deserialize)]
+struct OgrGeometryField {
+    /// The name of this field. Empty for shapefiles.
+    #[serde(default)]
+    name: String,
+    /// Can this field be null?
+    #[serde(default = "default_nullable")]
+    nullable: bool,
+    /// The coordinate system of this field, if known.
+    #[serde(default, rename = "coordinateSystem")]
+    coordinate_system: Option<OgrCoordinateSystem>,
+}
+
+/// A coordinate system, as reported by `ogrinfo -json`.
+#[derive(Debug, Deserialize)]
+struct OgrCoordinateSystem {
+    /// The coordinate system in PROJJSON format. We only look at the `id`.
+    projjson: Option<OgrProjJson>,
+}
+
+/// The parts of a PROJJSON coordinate system that we care about.
+#[derive(Debug, Deserialize)]
+struct OgrProjJson {
+    /// The authority and code identifying this coordinate system.
+    id: Option<OgrProjId>,
+}
+
+/// An identifier like `EPSG:27700`.
+#[derive(Debug, Deserialize)]
+struct OgrProjId {
+    /// The authority, such as `"EPSG"`.
+    authority: String,
+    /// The code assigned by the authority.
+    code: serde_json::Value,
+}
+
+/// An attribute field in an OGR layer.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OgrField {
+    /// The name of this field.
+    name: String,
+    /// The OGR type of this field, such as `"Integer64"` or `"String"`.
+    #[serde(rename = "type")]
+    ty: String,
+    /// The OGR subtype of this field, such as `"Boolean"` or `"JSON"`.
+    sub_type: Option<String>,
+    /// Can this field be null?
+    #[serde(default = "default_nullable")]
+    nullable: bool,
+}
+
+/// OGR fields are nullable unless marked otherwise.
+fn default_nullable() -> bool {
+    true
+}
+
+/// Convert an OGR field type and subtype to a portable `DataType`.
+fn ogr_data_type(ty: &str, sub_type: Option<&str>) -> Result<DataType> {
+    match (ty, sub_type) {
+        ("Integer", Some("Boolean")) => Ok(DataType::Bool),
+        ("Integer", Some("Int16")) => Ok(DataType::Int16),
+        ("Integer", _) => Ok(DataType::Int32),
+        ("Integer64", _) => Ok(DataType::Int64),
+        ("Real", Some("Float32")) => Ok(DataType::Float32),
+        ("Real", _) => Ok(DataType::Float64),
+        ("String", Some("JSON")) => Ok(DataType::Json),
+        ("String", Some("UUID")) => Ok(DataType::Uuid),
+        ("String", _) => Ok(DataType::Text),
+        ("Date", _) => Ok(DataType::Date),
+        ("DateTime", _) => Ok(DataType::TimestampWithoutTimeZone),
+        ("IntegerList", Some("Boolean")) => {
+            Ok(DataType::Array(Box::new(DataType::Bool)))
+        }
+        ("IntegerList", _) => Ok(DataType::Array(Box::new(DataType::Int32))),
+        ("Integer64List", _) => Ok(DataType::Array(Box::new(DataType::Int64))),
+        ("RealList", _) => Ok(DataType::Array(Box::new(DataType::Float64))),
+        ("StringList", _) => Ok(DataType::Array(Box::new(DataType::Text))),
+        (other, _) => Ok(DataType::Other(other.to_owned())),
+    }
+}
+
+#[test]
+fn ogr_data_type_examples() {
+    let examples = &[
+        ("Integer", None, DataType::Int32),
+        ("Integer", Some("Boolean"), DataType::Bool),
+        ("Integer", Some("Int16"), DataType::Int16),
+        ("Integer64", None, DataType::Int64),
+        ("Real", None, DataType::Float64),
+        ("Real", Some("Float32"), DataType::Float32),
+        ("String", None, DataType::Text),
+        ("String", Some("JSON"), DataType::Json),
+        ("String", Some("UUID"), DataType::Uuid),
+        ("Date", None, DataType::Date),
+        ("DateTime", None, DataType::TimestampWithoutTimeZone),
+        (
+            "StringList",
+            None,
+            DataType::Array(Box::new(DataType::Text)),
+        ),
+        ("Binary", None, DataType::Other("Binary".to_owned())),
+    ];
+    for (ty, sub_type, expected) in examples {
+        assert_eq!(&ogr_data_type(ty, *sub_type).unwrap(), expected);
+    }
+}
+
+impl OgrLayer {
+    /// The name we use for our geometry column, if we have one.
+    fn geometry_column_name(&self) -> Option<&str> {
+        self.geometry_fields.first().map(|f| {
+            if f.name.is_empty() {
+                "geometry"
+            } else {
+                &f.name[..]
+            }
+        })
+    }
+
+    /// The SRID of our geometry column, if we can output it without
+    /// reprojecting. This is only possible for coordinate systems with an EPSG
+    /// code, because that's how PostGIS identifies them. Layers with no
+    /// coordinate system are assumed to use WGS84.
+    fn native_srid(&self) -> Option<Srid> {
+        let geometry_field = self.geometry_fields.first()?;
+        let coordinate_system = match &geometry_field.coordinate_system {
+            Some(coordinate_system) => coordinate_system,
+            None => return Some(Srid::wgs84()),
+        };
+        let id = coordinate_system.projjson.as_ref()?.id.as_ref()?;
+        if id.authority != "EPSG" {
+            return None;
+        }
+        match &id.code {
+            serde_json::Value::Number(n) => n.as_u64(),
+            serde_json::Value::String(s) => s.parse().ok(),
+            _ => None,
+        }
+        .and_then(|code| u32::try_from(code).ok())
+        .map(Srid::new)
+    }
+
+    /// Convert this layer to a portable table schema.
+    fn to_table(&self) -> Result<Table> {
+        let mut columns = vec![];
+        for field in &self.fields {
+            columns.push(Column {
+                name: field.name.clone(),
+                is_nullable: field.nullable,
+                data_type: ogr_data_type(&field.ty, field.sub_type.as_deref())
+                    .with_context(|_| format!("error in column {}", field.name))?,
+                comment: None,
+                default: None,
+                collation: None,
+            });
+        }
+        if let Some(geometry_field) = self.geometry_fields.first() {
+            // We keep the layer's own SRID when it has an EPSG code. Otherwise
+            // `ogr2ogr -f GeoJSONSeq` reprojects to WGS84, as required by RFC
+            // 7946.
+            columns.push(Column {
+                name: self
+                    .geometry_column_name()
+                    .expect("should have geometry column")
+                    .to_owned(),
+                is_nullable: geometry_field.nullable,
+                data_type: DataType::GeoJson(
+                    self.native_srid().unwrap_or_else(Srid::wgs84),
+                ),
+                comment: None,
+                default: None,
+                collation: None,
+            });
+        }
+        Ok(Table {
+            name: self.name.clone(),
+            columns,
+            primary_key: vec![],
+            indexes: vec![],
+        })
+    }
+}
+
+/// Look up information about a layer in the GIS file at `path`. If `layer` is
+/// `None`, the file must contain exactly one layer.
+async fn layer_info(
+    ctx: &Context,
+    path: &Path,
+    layer: Option<&str>,
+) -> Result<OgrLayer> {
+    debug!(ctx.log(), "running `ogrinfo` on {}", path.display());
+    let mut command = Command::new("ogrinfo");
+    command.args(["-json", "-so", "-ro"]).arg(path);
+    if let Some(layer) = layer {
+        command.arg(layer);
+    }
+    let output = command
+        .stderr(Stdio::inherit())
+        .output()
+        .await
+        .context("error running `ogrinfo`")?;
+    if !output.status.success() {
+        return Err(format_err!("`ogrinfo` failed with {}", output.status));
+    }
+    let mut info: OgrInfo = serde_json::from_slice(&output.stdout)
+        .context("error parsing `ogrinfo -json` output")?;
+    match (layer, info.layers.len()) {
+        (_, 1) => Ok(info.layers.remove(0)),
+        (Some(layer), _) => Err(format_err!(
+            "could not find layer {:?} in {}",
+            layer,
+            path.display(),
+        )),
+        (None, 0) => Err(format_err!("no layers found in {}", path.display())),
+        (None, _) => Err(format_err!(
+            "{} contains multiple layers, please specify one of: {}",
+            path.display(),
+            info.layers
+                .iter()
+                .map(|l| &l.name[..])
+                .collect::<Vec<_>>()
+                .join(", "),
+        )),
+    }
+}
+
+/// Read the schema of a layer in the GIS file at `path`.
+pub(crate) async fn schema(
+    ctx: &Context,
+    path: &Path,
+    layer: Option<&str>,
+) -> Result<Table> {
+    layer_info(ctx, path, layer).await?.to_table()
+}
+
+/// Read the data in a layer of the GIS file at `path`, converting it to a
+/// single CSV stream with geometry encoded as GeoJSON.
+pub(crate) async fn local_data(
+    ctx: Context,
+    path: &Path,
+    layer: Option<&str>,
+    where_clause: Option<&str>,
+) -> Result<BoxStream<CsvStream>> {
+    let info = layer_info(&ctx, path, layer).await?;
+    let field_names = info
+        .fields
+        .iter()
+        .map(|f| f.name.clone())
+        .collect::<Vec<_>>();
+    let geometry_column_name = info.geometry_column_name().map(|n| n.to_owned());
+
+    // Start `ogr2ogr`, writing newline-separated GeoJSON features to stdout.
+    debug!(ctx.log(), "running `ogr2ogr` on {}", path.display());
+    let mut command = Command::new("ogr2ogr");
+    command
+        .args(["-f", "GeoJSONSeq", "/vsistdout/"])
+        .arg(path)
+        .arg(&info.name);
+    if let Some(where_clause) = where_clause {
+        command.args(["-where", where_clause]);
+    }
+    // `GeoJSONSeq` reprojects anything which isn't WGS84. If we know the
+    // layer's SRID, we claim the output is already WGS84 so that we get the
+    // original coordinates, and we report the real SRID in our schema.
+    match info.native_srid() {
+        Some(srid) if srid != Srid::wgs84() => {
+            command.args(["-a_srs", "EPSG:4326"]);
+        }
+        _ => {}
+    }
+    let mut child = command
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("error running `ogr2ogr`")?;
+    let child_stdout = child.stdout.take().expect("child should have stdout");
+    ctx.spawn_process(format!("ogr2ogr {}", path.display()), child);
+
+    // Build our CSV header.
+    let mut header = field_names.clone();
+    if let Some(geometry_column_name) = &geometry_column_name {
+        header.push(geometry_column_name.clone());
+    }
+    let header = csv_row(&header)?;
+
+    // Convert each GeoJSON feature into a CSV row.
+    let include_geometry = geometry_column_name.is_some();
+    let rows = BufReader::with_capacity(BUFFER_SIZE, child_stdout)
+        .lines()
+        .map_err(|e| format_err!("error reading `ogr2ogr` output: {}", e))
+        .try_filter(|line| future::ready(!line.trim().is_empty()))
+        .and_then(move |line| {
+            future::ready(feature_to_csv_row(&line, &field_names, include_geometry))
+        });
+    let data = stream::once(async { Ok(header) }).chain(rows);
+
+    let csv_stream = CsvStream {
+        name: info.name.clone(),
+        data: data.boxed(),
+    };
+    Ok(box_stream_once(Ok(csv_stream)))
+}
+
+/// Format a list of cells as a CSV row.
+fn csv_row<S: AsRef<str>>(cells: &[S]) -> Result<BytesMut> {
+    let mut wtr = csv::Writer::from_writer(vec![]);
+    wtr.write_record(cells.iter().map(|c| c.as_ref()))?;
+    Ok(BytesMut::from(&wtr.into_inner()?[..]))
+}
+
+/// Convert a GeoJSON feature into a CSV row in our interchange format.
+fn feature_to_csv_row(
+    line: &str,
+    field_names: &[String],
+    include_geometry: bool,
+) -> Result<BytesMut> {
+    // `ogr2ogr` may prefix each record with an ASCII record separator.
+    let line = line.trim_start_matches('\x1e');
+    let feature: serde_json::Value = serde_json::from_str(line)
+        .with_context(|_| format!("error parsing GeoJSON feature {:?}", line))?;
+    let properties = &feature["properties"];
+    let mut cells = field_names
+        .iter()
+        .map(|name| json_to_csv_cell(&properties[name]))
+        .collect::<Result<Vec<_>>>()?;
+    if include_geometry {
+        cells.push(json_to_csv_cell(&feature["geometry"])?);
+    }
+    csv_row(&cells)
+}
+
+/// Convert a JSON value to a CSV cell in our interchange format.
+fn json_to_csv_cell(value: &serde_json::Value) -> Result<String> {
+    use serde_json::Value;
+    match value {
+        Value::Null => Ok(String::new()),
+        Value::Bool(true) => Ok("t".to_owned()),
+        Value::Bool(false) => Ok("f".to_owned()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::String(s) => Ok(s.clone()),
+        Value::Array(_) | Value::Object(_) => Ok(serde_json::to_string(value)?),
+    }
+}
+
+#[test]
+fn feature_to_csv_row_handles_all_value_types() {
+    let line = "\x1e{\"type\":\"Feature\",\"properties\":{\"id\":1,\"name\":\"a,b\",\"ok\":true,\"tags\":[\"x\"],\"empty\":null},\"geometry\":{\"type\":\"Point\",\"coordinates\":[-71,42]}}";
+    let names = ["id", "name", "ok", "tags", "empty"]
+        .iter()
+        .map(|&s| s.to_owned())
+        .collect::<Vec<_>>();
+    let row = feature_to_csv_row(line, &names, true).unwrap();
+    assert_eq!(
+        std::str::from_utf8(&row).unwrap(),
+        "1,\"a,b\",t,\"[\"\"x\"\"]\",,\"{\"\"coordinates\"\":[-71,42],\"\"type\"\":\"\"Point\"\"}\"\n",
+    );
+}
+
+#[test]
+fn to_table_keeps_layer_srid() {
+    let layer_with_crs = |crs: &str| -> OgrLayer {
+        let json = format!(
+            r#"{{"name":"roads","geometryFields":[{{"name":"geom","nullable":true{}}}],"fields":[]}}"#,
+            crs,
+        );
+        serde_json::from_str(&json).unwrap()
+    };
+    let geometry_type =
+        |layer: OgrLayer| layer.to_table().unwrap().columns[0].data_type.clone();
+
+    let osgb = layer_with_crs(
+        r#","coordinateSystem":{"wkt":"...","projjson":{"id":{"authority":"EPSG","code":27700}}}"#,
+    );
+    assert_eq!(geometry_type(osgb), DataType::GeoJson(Srid::new(27700)));
+
+    let unknown = layer_with_crs(
+        r#","coordinateSystem":{"wkt":"...","projjson":{"name":"custom"}}"#,
+    );
+    assert_eq!(unknown.native_srid(), None);
+    assert_eq!(geometry_type(unknown), DataType::GeoJson(Srid::wgs84()));
+
+    let none = layer_with_crs("");
+    assert_eq!(geometry_type(none), DataType::GeoJson(Srid::wgs84()));
+}
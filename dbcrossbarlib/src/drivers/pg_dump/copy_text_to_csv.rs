@@ -0,0 +1,269 @@
+//! Convert the `COPY ... FROM stdin` text data in a `pg_restore` SQL script
+//! into our CSV interchange format.
+//!
+//! For details of the text format, see the "Text Format" section of
+//! https://www.postgresql.org/docs/current/sql-copy.html.
+
+use std::io::{self, BufRead};
+
+use crate::common::*;
+use crate::drivers::postgres_shared::{PgColumn, PgDataType, PgScalarDataType};
+
+/// Read an SQL script containing a single `COPY ... FROM stdin` block, and
+/// write the data in that block as CSV. Everything outside the `COPY` block is
+/// ignored.
+///
+/// This is synchronous, and should be run using `spawn_sync_transform`.
+pub(crate) fn copy_pg_text_to_csv(
+    columns: &[PgColumn],
+    rdr: Box<dyn Read>,
+    wtr: Box<dyn Write>,
+) -> Result<()> {
+    // We can only handle types whose PostgreSQL text representation is the
+    // same as our CSV interchange format, or which we know how to convert.
+    let conversions = columns
+        .iter()
+        .map(|col| match &col.data_type {
+            PgDataType::Scalar(PgScalarDataType::Bytea) => Ok(CellConversion::Bytea),
+            PgDataType::Array { .. }
+            | PgDataType::Scalar(PgScalarDataType::Composite { .. })
+            | PgDataType::Scalar(PgScalarDataType::Geometry(_))
+            | PgDataType::Scalar(PgScalarDataType::Interval)
+            | PgDataType::Scalar(PgScalarDataType::Range { .. }) => Err(format_err!(
+                "cannot read column {} of type {} from pg_dump archives yet",
+                col.name,
+                col.data_type,
+            )),
+            PgDataType::Scalar(_) => Ok(CellConversion::None),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let rdr = io::BufReader::with_capacity(BUFFER_SIZE, rdr);
+    let mut wtr =
+        csv::Writer::from_writer(io::BufWriter::with_capacity(BUFFER_SIZE, wtr));
+    wtr.write_record(columns.iter().map(|c| &c.name))?;
+
+    let mut in_copy = false;
+    for line in rdr.lines() {
+        let line = line.context("error reading `pg_restore` output")?;
+        if !in_copy {
+            in_copy = line.starts_with("COPY ") && line.ends_with(" FROM stdin;");
+        } else if line == "\\." {
+            in_copy = false;
+        } else {
+            let cells = line.split('\t').collect::<Vec<_>>();
+            if cells.len() != columns.len() {
+                return Err(format_err!(
+                    "expected {} columns in pg_dump data, found {}",
+                    columns.len(),
+                    cells.len(),
+                ));
+            }
+            let cells = cells
+                .into_iter()
+                .zip(&conversions)
+                .map(|(cell, conversion)| conversion.convert(decode_text_cell(cell)?))
+                .collect::<Result<Vec<_>>>()?;
+            wtr.write_record(&cells)?;
+        }
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// How to convert a decoded PostgreSQL text value to our CSV interchange
+/// format.
+enum CellConversion {
+    /// Use the value as is.
+    None,
+    /// Convert a `bytea` value in PostgreSQL's hex format to base64.
+    Bytea,
+}
+
+impl CellConversion {
+    /// Convert `value`, which has already been decoded using
+    /// `decode_text_cell`.
+    fn convert(&self, value: String) -> Result<String> {
+        match self {
+            CellConversion::Bytea if !value.is_empty() => {
+                let hex_digits = value.strip_prefix("\\x").ok_or_else(|| {
+                    format_err!(
+                        "expected bytea value in hex format in pg_dump data, found {:?}",
+                        value,
+                    )
+                })?;
+                let bytes = hex::decode(hex_digits).with_context(|_| {
+                    format!("invalid bytea value in pg_dump data: {:?}", value)
+                })?;
+                Ok(base64::encode(bytes))
+            }
+            _ => Ok(value),
+        }
+    }
+}
+
+/// Decode a single cell in PostgreSQL's `COPY` text format. `NULL` values
+/// become empty strings.
+fn decode_text_cell(cell: &str) -> Result<String> {
+    if cell == "\\N" {
+        return Ok(String::new());
+    }
+    let mut bytes = Vec::with_capacity(cell.len());
+    let mut chars = cell.bytes().peekable();
+    while let Some(b) = chars.next() {
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+        match chars.next() {
+            Some(b'b') => bytes.push(b'\x08'),
+            Some(b'f') => bytes.push(b'\x0c'),
+            Some(b'n') => bytes.push(b'\n'),
+            Some(b'r') => bytes.push(b'\r'),
+            Some(b't') => bytes.push(b'\t'),
+            Some(b'v') => bytes.push(b'\x0b'),
+            Some(b'x') => {
+                let mut value = 0u8;
+                for _ in 0..2 {
+                    match chars.peek().and_then(|&d| (d as char).to_digit(16)) {
+                        Some(digit) => {
+                            value = value * 16 + digit as u8;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                bytes.push(value);
+            }
+            Some(d @ b'0'..=b'7') => {
+                let mut value = d - b'0';
+                for _ in 0..2 {
+                    match chars.peek() {
+                        Some(&d @ b'0'..=b'7') => {
+                            value = value.wrapping_mul(8) + (d - b'0');
+                            chars.next();
+                        }
+                        _ => break,
+                    }
+                }
+                bytes.push(value);
+            }
+            Some(other) => bytes.push(other),
+            None => {
+                return Err(format_err!(
+                    "unexpected backslash at end of pg_dump data: {:?}",
+                    cell,
+                ))
+            }
+        }
+    }
+    Ok(String::from_utf8(bytes).context("pg_dump data was not UTF-8")?)
+}
+
+#[test]
+fn decode_text_cell_handles_escapes() {
+    let examples = &[
+        ("\\N", ""),
+        ("plain", "plain"),
+        ("a\\tb\\nc", "a\tb\nc"),
+        ("back\\\\slash", "back\\slash"),
+        ("\\101\\x42", "AB"),
+    ];
+    for &(input, expected) in examples {
+        assert_eq!(decode_text_cell(input).unwrap(), expected);
+    }
+}
+
+#[cfg(test)]
+fn test_column(name: &str, data_type: PgScalarDataType) -> PgColumn {
+    PgColumn {
+        name: name.to_owned(),
+        data_type: PgDataType::Scalar(data_type),
+        is_nullable: true,
+        default: None,
+        comment: None,
+        collation: None,
+    }
+}
+
+#[cfg(test)]
+fn copy_pg_text_to_csv_for_test(columns: &[PgColumn], input: &str) -> Result<String> {
+    let tmp_dir = tempdir::TempDir::new("copy_text_to_csv").unwrap();
+    let output_path = tmp_dir.path().join("output.csv");
+    let output = std::fs::File::create(&output_path).unwrap();
+    copy_pg_text_to_csv(
+        columns,
+        Box::new(io::Cursor::new(input.as_bytes().to_vec())),
+        Box::new(output),
+    )?;
+    Ok(std::fs::read_to_string(&output_path).unwrap())
+}
+
+#[test]
+fn copy_pg_text_to_csv_extracts_copy_block() {
+    let columns = vec![
+        test_column("id", PgScalarDataType::Int),
+        test_column("name", PgScalarDataType::Text),
+    ];
+    let input = "SET statement_timeout = 0;\n\
+                 COPY public.users (id, name) FROM stdin;\n\
+                 1\tJane, Doe\n\
+                 2\t\\N\n\
+                 \\.\n\
+                 \n";
+    assert_eq!(
+        copy_pg_text_to_csv_for_test(&columns, input).unwrap(),
+        "id,name\n1,\"Jane, Doe\"\n2,\n",
+    );
+}
+
+#[test]
+fn copy_pg_text_to_csv_converts_bytea_to_base64() {
+    let columns = vec![
+        test_column("id", PgScalarDataType::Int),
+        test_column("data", PgScalarDataType::Bytea),
+    ];
+    let input = "COPY public.files (id, data) FROM stdin;\n\
+                 1\t\\\\x4142\n\
+                 2\t\\N\n\
+                 \\.\n";
+    assert_eq!(
+        copy_pg_text_to_csv_for_test(&columns, input).unwrap(),
+        "id,data\n1,QUI=\n2,\n",
+    );
+}
+
+#[test]
+fn copy_pg_text_to_csv_rejects_unconvertible_types() {
+    let unsupported = vec![
+        (PgScalarDataType::Interval, "1 day 02:00:00"),
+        (
+            PgScalarDataType::Composite {
+                name: "public.point2d".to_owned(),
+                fields: vec![
+                    test_column("x", PgScalarDataType::Int),
+                    test_column("y", PgScalarDataType::Int),
+                ],
+            },
+            "(1,2)",
+        ),
+        (
+            PgScalarDataType::Range {
+                name: "int4range".to_owned(),
+                subtype: Box::new(PgScalarDataType::Int),
+            },
+            "[1,10)",
+        ),
+    ];
+    for (data_type, value) in unsupported {
+        let columns = vec![
+            test_column("id", PgScalarDataType::Int),
+            test_column("value", data_type),
+        ];
+        let input = format!(
+            "COPY public.example (id, value) FROM stdin;\n1\t{}\n\\.\n",
+            value,
+        );
+        assert!(copy_pg_text_to_csv_for_test(&columns, &input).is_err());
+    }
+}
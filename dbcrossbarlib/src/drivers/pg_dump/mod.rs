@@ -0,0 +1,197 @@
+//! Driver for reading tables from `pg_dump` archives.
+//!
+//! We shell out to `pg_restore` to extract the `CREATE TABLE` statement and
+//! `COPY` data for a single table, which means we never need a running
+//! PostgreSQL server.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::{fmt, path::PathBuf, process::Stdio, str::FromStr};
+use tokio::process::Command;
+
+use crate::common::*;
+use crate::drivers::postgres_shared::{PgColumn, PgCreateTable, TableName};
+use crate::tokio_glue::copy_reader_to_stream;
+use crate::transform::spawn_sync_transform;
+
+mod copy_text_to_csv;
+
+use self::copy_text_to_csv::copy_pg_text_to_csv;
+
+/// A table in a custom-format `pg_dump` archive, created with `pg_dump -Fc`.
+#[derive(Clone, Debug)]
+pub struct PgDumpLocator {
+    path: PathBuf,
+    table_name: String,
+}
+
+impl PgDumpLocator {
+    /// Build a `pg_restore` command which extracts part of our table.
+    fn pg_restore_command(&self, section_arg: &str) -> Result<Command> {
+        let table_name = TableName(&self.table_name);
+        let (namespace, table) = table_name.split()?;
+        let mut command = Command::new("pg_restore");
+        command.args([section_arg, "--no-owner", "--no-privileges", "--file=-"]);
+        if let Some(namespace) = namespace {
+            command.arg(format!("--schema={}", namespace));
+        }
+        command.arg(format!("--table={}", table)).arg(&self.path);
+        Ok(command)
+    }
+
+    /// Read the `CREATE TABLE` statement for our table.
+    async fn pg_create_table(&self, ctx: &Context) -> Result<PgCreateTable> {
+        lazy_static! {
+            static ref CREATE_TABLE_RE: Regex =
+                Regex::new(r"(?ms)^CREATE (?:UNLOGGED )?TABLE .*?^\);")
+                    .expect("invalid regex in source");
+        }
+
+        debug!(ctx.log(), "running `pg_restore --schema-only` on {}", self);
+        let output = self
+            .pg_restore_command("--schema-only")?
+            .stderr(Stdio::inherit())
+            .output()
+            .await
+            .context("error running `pg_restore`")?;
+        if !output.status.success() {
+            return Err(format_err!("`pg_restore` failed with {}", output.status));
+        }
+        let sql = String::from_utf8(output.stdout)
+            .context("`pg_restore` output was not UTF-8")?;
+        let create_table = CREATE_TABLE_RE
+            .find(&sql)
+            .ok_or_else(|| format_err!("could not find table in {}", self))?;
+        let mut pg_create_table = create_table
+            .as_str()
+            .parse::<PgCreateTable>()
+            .with_context(|_| format!("error parsing schema from {}", self))?;
+        let table_name = TableName(&self.table_name);
+        let (_, table) = table_name.split()?;
+        pg_create_table.name = table.to_owned();
+        Ok(pg_create_table)
+    }
+}
+
+impl fmt::Display for PgDumpLocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}#{}",
+            Self::scheme(),
+            self.path.display(),
+            self.table_name,
+        )
+    }
+}
+
+impl FromStr for PgDumpLocator {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if !s.starts_with(Self::scheme()) {
+            return Err(format_err!(
+                "expected {} locator, found {}",
+                Self::scheme(),
+                s
+            ));
+        }
+        let rest = &s[Self::scheme().len()..];
+        let idx = rest.find('#').ok_or_else(|| {
+            format_err!("{} locator must end with #table: {}", Self::scheme(), s)
+        })?;
+        let (path, table_name) = (&rest[..idx], &rest[idx + 1..]);
+        if path.is_empty() || path == "-" {
+            return Err(format_err!("{} requires a file path", Self::scheme()));
+        }
+        if table_name.is_empty() {
+            return Err(format_err!("{} requires a table name", Self::scheme()));
+        }
+        Ok(PgDumpLocator {
+            path: PathBuf::from(path),
+            table_name: table_name.to_owned(),
+        })
+    }
+}
+
+impl Locator for PgDumpLocator {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self, ctx: Context) -> BoxFuture<Option<Table>> {
+        let source = self.to_owned();
+        async move {
+            let table = source.pg_create_table(&ctx).await?.to_table()?;
+            Ok(Some(table))
+        }
+        .boxed()
+    }
+
+    fn local_data(
+        &self,
+        ctx: Context,
+        shared_args: SharedArguments<Unverified>,
+        source_args: SourceArguments<Unverified>,
+    ) -> BoxFuture<Option<BoxStream<CsvStream>>> {
+        local_data_helper(ctx, self.to_owned(), shared_args, source_args).boxed()
+    }
+}
+
+impl LocatorStatic for PgDumpLocator {
+    fn scheme() -> &'static str {
+        "pg-dump:"
+    }
+
+    fn features() -> Features {
+        Features {
+            locator: LocatorFeatures::Schema | LocatorFeatures::LocalData,
+            write_schema_if_exists: EnumSet::empty(),
+            source_args: EnumSet::empty(),
+            dest_args: EnumSet::empty(),
+            dest_if_exists: EnumSet::empty(),
+            _placeholder: (),
+        }
+    }
+}
+
+/// Implementation of `local_data`, but as a real `async` function.
+async fn local_data_helper(
+    ctx: Context,
+    source: PgDumpLocator,
+    shared_args: SharedArguments<Unverified>,
+    source_args: SourceArguments<Unverified>,
+) -> Result<Option<BoxStream<CsvStream>>> {
+    let _shared_args = shared_args.verify(PgDumpLocator::features())?;
+    let _source_args = source_args.verify(PgDumpLocator::features())?;
+
+    // Use the schema stored in the archive to decode the data, not the
+    // portable schema.
+    let pg_create_table = source.pg_create_table(&ctx).await?;
+    let columns: Vec<PgColumn> = pg_create_table.columns.clone();
+
+    // Start `pg_restore`, writing `COPY` data to stdout.
+    debug!(ctx.log(), "running `pg_restore --data-only` on {}", source);
+    let mut child = source
+        .pg_restore_command("--data-only")?
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("error running `pg_restore`")?;
+    let child_stdout = child.stdout.take().expect("child should have stdout");
+    ctx.spawn_process(format!("pg_restore {}", source.path.display()), child);
+
+    // Convert the `COPY` data to CSV.
+    let copy_data = copy_reader_to_stream(ctx.clone(), child_stdout)?;
+    let csv_data = spawn_sync_transform(
+        ctx,
+        "copy_pg_text_to_csv".to_owned(),
+        copy_data.boxed(),
+        move |_ctx, rdr, wtr| copy_pg_text_to_csv(&columns, rdr, wtr),
+    )?;
+
+    let csv_stream = CsvStream {
+        name: pg_create_table.name,
+        data: csv_data,
+    };
+    Ok(Some(box_stream_once(Ok(csv_stream))))
+}
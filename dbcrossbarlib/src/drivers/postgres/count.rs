@@ -16,7 +16,7 @@ pub(crate) async fn count_helper(
 
     // Get the parts of our locator.
     let url = locator.url.clone();
-    let table_name = locator.table_name.clone();
+    let table_name = locator.table_name()?.to_owned();
 
     // Look up the arguments we'll need.
     let schema = shared_args.schema();
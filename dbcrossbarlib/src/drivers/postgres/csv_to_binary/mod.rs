@@ -9,7 +9,7 @@
 
 use byteorder::{NetworkEndian as NE, WriteBytesExt};
 use cast;
-use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use csv;
 use geo_types::Geometry;
 use hex;
@@ -26,6 +26,8 @@ use crate::drivers::postgres_shared::{
 };
 use crate::from_csv_cell::FromCsvCell;
 use crate::from_json_value::FromJsonValue;
+use crate::interval::Interval;
+use crate::network::{Cidr, Inet, MacAddr};
 
 mod to_postgis;
 mod write_binary;
@@ -200,12 +202,19 @@ fn json_to_binary<W: Write>(
 ) -> Result<()> {
     match data_type {
         PgScalarDataType::Boolean => write_json_as_binary::<bool, W>(wtr, json),
+        PgScalarDataType::Bytea => write_json_as_binary::<Vec<u8>, W>(wtr, json),
         PgScalarDataType::Date => write_json_as_binary::<NaiveDate, W>(wtr, json),
-        PgScalarDataType::Numeric => Err(format_err!(
-            "cannot use `numeric` arrays with PostgreSQL yet",
-        )),
+        PgScalarDataType::Numeric | PgScalarDataType::NumericWithPrecision(_) => Err(
+            format_err!("cannot use `numeric` arrays with PostgreSQL yet"),
+        ),
         PgScalarDataType::Real => write_json_as_binary::<f32, W>(wtr, json),
         PgScalarDataType::DoublePrecision => write_json_as_binary::<f64, W>(wtr, json),
+        PgScalarDataType::Cidr => write_json_as_binary::<Cidr, W>(wtr, json),
+        PgScalarDataType::Composite { name, .. }
+        | PgScalarDataType::Range { name, .. } => Err(format_err!(
+            "cannot write arrays of type {} to PostgreSQL yet",
+            name,
+        )),
         PgScalarDataType::Geometry(srid) => {
             let geometry = Geometry::<f64>::from_json_value(json)?;
             let value = GeometryWithSrid {
@@ -214,9 +223,11 @@ fn json_to_binary<W: Write>(
             };
             value.write_binary(wtr)
         }
+        PgScalarDataType::Inet => write_json_as_binary::<Inet, W>(wtr, json),
         PgScalarDataType::Smallint => write_json_as_binary::<i16, W>(wtr, json),
         PgScalarDataType::Int => write_json_as_binary::<i32, W>(wtr, json),
         PgScalarDataType::Bigint => write_json_as_binary::<i64, W>(wtr, json),
+        PgScalarDataType::Interval => write_json_as_binary::<Interval, W>(wtr, json),
         PgScalarDataType::Json => Err(format_err!(
             "PostgreSQL arrays with json elements not supported (try jsonb)",
         )),
@@ -224,10 +235,17 @@ fn json_to_binary<W: Write>(
             let serialized = serde_json::to_string(json)?;
             RawJsonb(&serialized).write_binary(wtr)
         }
-        PgScalarDataType::Text => match json {
+        PgScalarDataType::Macaddr => write_json_as_binary::<MacAddr, W>(wtr, json),
+        PgScalarDataType::Enum { .. }
+        | PgScalarDataType::Text
+        | PgScalarDataType::TextOneOf(_)
+        | PgScalarDataType::Varchar(_) => match json {
             Value::String(s) => s.as_str().write_binary(wtr),
             _ => Err(format_err!("expected JSON string, found {}", json)),
         },
+        PgScalarDataType::TimeWithoutTimeZone => {
+            write_json_as_binary::<NaiveTime, W>(wtr, json)
+        }
         PgScalarDataType::TimestampWithoutTimeZone => {
             write_json_as_binary::<NaiveDateTime, W>(wtr, json)
         }
@@ -258,8 +276,9 @@ fn scalar_to_binary(
 ) -> Result<()> {
     match data_type {
         PgScalarDataType::Boolean => write_cell_as_binary::<bool>(wtr, cell),
+        PgScalarDataType::Bytea => write_cell_as_binary::<Vec<u8>>(wtr, cell),
         PgScalarDataType::Date => write_cell_as_binary::<NaiveDate>(wtr, cell),
-        PgScalarDataType::Numeric => {
+        PgScalarDataType::Numeric | PgScalarDataType::NumericWithPrecision(_) => {
             // The only sensible way to make this work is to port PostgresSQL's
             // own `decimal` parser from C, because it's an unusual internal
             // format built using very complicated parsing rules (and `numeric`
@@ -270,6 +289,12 @@ fn scalar_to_binary(
         }
         PgScalarDataType::Real => write_cell_as_binary::<f32>(wtr, cell),
         PgScalarDataType::DoublePrecision => write_cell_as_binary::<f64>(wtr, cell),
+        PgScalarDataType::Cidr => write_cell_as_binary::<Cidr>(wtr, cell),
+        PgScalarDataType::Composite { name, .. }
+        | PgScalarDataType::Range { name, .. } => Err(format_err!(
+            "cannot write values of type {} to PostgreSQL yet",
+            name,
+        )),
         PgScalarDataType::Geometry(srid) => {
             if !cell.is_empty() && cell.as_bytes()[0].is_ascii_hexdigit() {
                 // We don't have valid GeoJSON, but it looks like it's hex, so
@@ -287,9 +312,11 @@ fn scalar_to_binary(
                 value.write_binary(wtr)
             }
         }
+        PgScalarDataType::Inet => write_cell_as_binary::<Inet>(wtr, cell),
         PgScalarDataType::Smallint => write_cell_as_binary::<i16>(wtr, cell),
         PgScalarDataType::Int => write_cell_as_binary::<i32>(wtr, cell),
         PgScalarDataType::Bigint => write_cell_as_binary::<i64>(wtr, cell),
+        PgScalarDataType::Interval => write_cell_as_binary::<Interval>(wtr, cell),
         PgScalarDataType::Json => {
             let value = RawJson(cell);
             value.write_binary(wtr)
@@ -298,7 +325,14 @@ fn scalar_to_binary(
             let value = RawJsonb(cell);
             value.write_binary(wtr)
         }
-        PgScalarDataType::Text => cell.write_binary(wtr),
+        PgScalarDataType::Macaddr => write_cell_as_binary::<MacAddr>(wtr, cell),
+        PgScalarDataType::Enum { .. }
+        | PgScalarDataType::Text
+        | PgScalarDataType::TextOneOf(_)
+        | PgScalarDataType::Varchar(_) => cell.write_binary(wtr),
+        PgScalarDataType::TimeWithoutTimeZone => {
+            write_cell_as_binary::<NaiveTime>(wtr, cell)
+        }
         PgScalarDataType::TimestampWithoutTimeZone => {
             write_cell_as_binary::<NaiveDateTime>(wtr, cell)
         }
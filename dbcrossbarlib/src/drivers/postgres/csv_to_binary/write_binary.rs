@@ -1,14 +1,19 @@
 //! Write data values in PostgreSQL `BINARY` format.
 
 use byteorder::{NetworkEndian as NE, WriteBytesExt};
-use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
 use geo_types::Geometry;
 use postgis::ewkb::{AsEwkbGeometry, EwkbWrite};
-use std::mem::{size_of, size_of_val};
+use std::{
+    mem::{size_of, size_of_val},
+    net::IpAddr,
+};
 use uuid::Uuid;
 
 use super::WriteExt;
 use crate::common::*;
+use crate::interval::Interval;
+use crate::network::{Cidr, Inet, MacAddr};
 use crate::schema::Srid;
 
 /// A JSON string that we want to serialize as `json`.
@@ -106,6 +111,53 @@ impl WriteBinary for i64 {
     }
 }
 
+impl WriteBinary for Interval {
+    fn write_binary<W: Write>(&self, wtr: &mut W) -> Result<()> {
+        wtr.write_len(size_of::<i64>() + 2 * size_of::<i32>())?;
+        wtr.write_i64::<NE>(self.microseconds)?;
+        wtr.write_i32::<NE>(self.days)?;
+        wtr.write_i32::<NE>(self.months)?;
+        Ok(())
+    }
+}
+
+/// Write an IP address in the format shared by `inet` and `cidr`.
+fn write_ip_binary<W: Write>(wtr: &mut W, inet: &Inet, is_cidr: bool) -> Result<()> {
+    // PostgreSQL uses its own address family numbers, which don't depend on
+    // the local OS.
+    let (family, bytes) = match inet.addr {
+        IpAddr::V4(addr) => (2, addr.octets().to_vec()),
+        IpAddr::V6(addr) => (3, addr.octets().to_vec()),
+    };
+    wtr.write_len(4 + bytes.len())?;
+    wtr.write_u8(family)?;
+    wtr.write_u8(inet.prefix_len)?;
+    wtr.write_u8(u8::from(is_cidr))?;
+    wtr.write_u8(cast::u8(bytes.len())?)?;
+    wtr.write_all(&bytes)?;
+    Ok(())
+}
+
+impl WriteBinary for Inet {
+    fn write_binary<W: Write>(&self, wtr: &mut W) -> Result<()> {
+        write_ip_binary(wtr, self, false)
+    }
+}
+
+impl WriteBinary for Cidr {
+    fn write_binary<W: Write>(&self, wtr: &mut W) -> Result<()> {
+        write_ip_binary(wtr, &self.0, true)
+    }
+}
+
+impl WriteBinary for MacAddr {
+    fn write_binary<W: Write>(&self, wtr: &mut W) -> Result<()> {
+        wtr.write_len(self.0.len())?;
+        wtr.write_all(&self.0)?;
+        Ok(())
+    }
+}
+
 impl<'a> WriteBinary for RawJson<'a> {
     fn write_binary<W: Write>(&self, wtr: &mut W) -> Result<()> {
         // Apparently we can just write these as string data and all is good?
@@ -141,6 +193,12 @@ impl<'a> WriteBinary for &'a [u8] {
     }
 }
 
+impl WriteBinary for Vec<u8> {
+    fn write_binary<W: Write>(&self, wtr: &mut W) -> Result<()> {
+        self.as_slice().write_binary(wtr)
+    }
+}
+
 impl<'a> WriteBinary for NaiveDateTime {
     fn write_binary<W: Write>(&self, wtr: &mut W) -> Result<()> {
         let epoch = NaiveDate::from_ymd(2000, 1, 1).and_hms(0, 0, 0);
@@ -154,6 +212,20 @@ impl<'a> WriteBinary for NaiveDateTime {
     }
 }
 
+impl WriteBinary for NaiveTime {
+    fn write_binary<W: Write>(&self, wtr: &mut W) -> Result<()> {
+        let midnight =
+            NaiveTime::from_hms_opt(0, 0, 0).expect("midnight should be valid");
+        let duration = *self - midnight;
+        let microseconds = duration
+            .num_microseconds()
+            .ok_or_else(|| format_err!("date math overflow"))?;
+        wtr.write_len(size_of::<i64>())?;
+        wtr.write_i64::<NE>(microseconds)?;
+        Ok(())
+    }
+}
+
 impl<'a> WriteBinary for DateTime<Utc> {
     fn write_binary<W: Write>(&self, wtr: &mut W) -> Result<()> {
         let epoch = Utc.ymd(2000, 1, 1).and_hms(0, 0, 0);
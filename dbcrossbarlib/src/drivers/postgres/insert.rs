@@ -0,0 +1,343 @@
+//! Writing data to PostgreSQL using batched `INSERT` statements.
+//!
+//! This is much slower than `COPY`, but some managed PostgreSQL services (and
+//! `pgbouncer` in transaction mode) don't allow `COPY` at all.
+//!
+//! We send each batch as a single statement using the simple query protocol,
+//! with every value quoted as a literal. If we used parameters, `tokio-postgres`
+//! would create a named prepared statement for each batch, which doesn't work
+//! with `pgbouncer` in transaction mode.
+
+use futures::executor::block_on;
+use itertools::Itertools;
+use std::{
+    cmp::max,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{sync::mpsc, time::delay_until};
+
+use super::{connect, Client};
+use crate::common::*;
+use crate::drivers::postgres_shared::{
+    pg_quote, Ident, PgColumn, PgCreateTable, PgDataType, PgScalarDataType, TableName,
+};
+use crate::tokio_glue::{run_sync_fn_in_background, SendResultExt, SyncStreamReader};
+
+/// A batch of CSV rows to insert.
+type Batch = Vec<csv::StringRecord>;
+
+/// Options controlling how we insert data.
+#[derive(Clone, Debug)]
+pub(crate) struct InsertOptions {
+    /// The maximum number of rows to insert in a single statement.
+    pub(crate) batch_size: usize,
+    /// How many `INSERT` statements should we run at once, each on its own
+    /// connection?
+    pub(crate) concurrency: usize,
+    /// If specified, the maximum number of rows to insert per second.
+    pub(crate) max_rows_per_second: Option<f64>,
+}
+
+/// Insert the CSV data in `stream` into `dest` using `INSERT` statements. If
+/// `upsert_keys` is specified, update any existing rows with matching keys,
+/// changing only `update_columns` if specified.
+pub(crate) async fn insert_from_stream(
+    ctx: &Context,
+    url: &Url,
+    dest: &PgCreateTable,
+    upsert_keys: Option<&[String]>,
+    update_columns: Option<&[String]>,
+    stream: BoxStream<BytesMut>,
+    options: &InsertOptions,
+) -> Result<()> {
+    debug!(ctx.log(), "inserting data into {:?}", dest.name);
+    for col in &dest.columns {
+        value_sql(col, "NULL")?;
+    }
+    let batch_size = max(1, options.batch_size);
+    let concurrency = max(1, options.concurrency);
+
+    // Parse our CSV data in a background thread, and send it to us in batches.
+    let (sender, receiver) = mpsc::channel::<Batch>(concurrency);
+    let rdr = SyncStreamReader::new(ctx.child(o!("mode" => "input")), stream);
+    let columns = dest.columns.clone();
+    let read_fut =
+        run_sync_fn_in_background("read_csv_for_insert".to_owned(), move || {
+            read_csv_batches(&columns, Box::new(rdr), batch_size, sender)
+        });
+    ctx.spawn_worker(read_fut.boxed());
+
+    // Open one connection for each concurrent `INSERT`.
+    let mut clients = Vec::with_capacity(concurrency);
+    for _ in 0..concurrency {
+        clients.push(connect(ctx.clone(), url.clone()).await?);
+    }
+    let clients = Arc::new(Mutex::new(clients));
+    let next_start = Arc::new(Mutex::new(Instant::now()));
+
+    receiver
+        .map(Ok::<_, Error>)
+        .try_for_each_concurrent(concurrency, |batch| {
+            let clients = clients.clone();
+            let next_start = next_start.clone();
+            async move {
+                if let Some(rows_per_second) = options.max_rows_per_second {
+                    let start =
+                        reserve_start_time(&next_start, batch.len(), rows_per_second);
+                    delay_until(start.into()).await;
+                }
+                let client = clients
+                    .lock()
+                    .expect("lock poisoned")
+                    .pop()
+                    .expect("should always have a free client");
+                insert_batch(ctx, &client, dest, upsert_keys, update_columns, &batch)
+                    .await?;
+                clients.lock().expect("lock poisoned").push(client);
+                Ok(())
+            }
+        })
+        .await
+}
+
+/// Read CSV data from `rdr`, and send it to `sender` in batches.
+///
+/// This is synchronous, and should be run in a background thread.
+fn read_csv_batches(
+    columns: &[PgColumn],
+    rdr: Box<dyn Read>,
+    batch_size: usize,
+    mut sender: mpsc::Sender<Batch>,
+) -> Result<()> {
+    let mut rdr = csv::Reader::from_reader(rdr);
+
+    // Check to make sure our CSV headers and table column names match.
+    let headers = rdr.headers()?;
+    if headers.len() != columns.len() {
+        return Err(format_err!(
+            "CSV file has {} columns, but schema has {}",
+            headers.len(),
+            columns.len(),
+        ));
+    }
+    for (idx, (hdr, col)) in headers.iter().zip(columns.iter()).enumerate() {
+        if hdr != col.name {
+            return Err(format_err!(
+                "CSV file has column {} at position {}, but schema has {}",
+                hdr,
+                idx,
+                col.name,
+            ));
+        }
+    }
+
+    for chunk in &rdr.records().chunks(batch_size) {
+        let batch = chunk.collect::<Result<Batch, _>>()?;
+        block_on(sender.send(batch)).map_send_err()?;
+    }
+    Ok(())
+}
+
+/// Figure out when we can start inserting `row_count` rows without exceeding
+/// `rows_per_second`, and reserve that time.
+fn reserve_start_time(
+    next_start: &Mutex<Instant>,
+    row_count: usize,
+    rows_per_second: f64,
+) -> Instant {
+    let mut next_start = next_start.lock().expect("lock poisoned");
+    let start = max(*next_start, Instant::now());
+    *next_start = start + Duration::from_secs_f64(row_count as f64 / rows_per_second);
+    start
+}
+
+/// Insert a single batch of rows.
+async fn insert_batch(
+    ctx: &Context,
+    client: &Client,
+    dest: &PgCreateTable,
+    upsert_keys: Option<&[String]>,
+    update_columns: Option<&[String]>,
+    batch: &[csv::StringRecord],
+) -> Result<()> {
+    trace!(
+        ctx.log(),
+        "inserting {} rows into {}",
+        batch.len(),
+        dest.name
+    );
+    let sql = insert_sql(dest, upsert_keys, update_columns, batch)?;
+    client
+        .batch_execute(sql.as_str())
+        .await
+        .with_context(|_| format!("error inserting data into {}", dest.name))?;
+    Ok(())
+}
+
+/// Generate a multi-row `INSERT` statement for `batch`. Every value is
+/// quoted as a text literal, and converted to the column type using SQL. Empty
+/// values become `NULL`.
+fn insert_sql(
+    dest: &PgCreateTable,
+    upsert_keys: Option<&[String]>,
+    update_columns: Option<&[String]>,
+    batch: &[csv::StringRecord],
+) -> Result<String> {
+    let mut rows = Vec::with_capacity(batch.len());
+    for row in batch {
+        let values = dest
+            .columns
+            .iter()
+            .zip(row.iter())
+            .map(|(col, cell)| {
+                if cell.is_empty() {
+                    value_sql(col, "NULL")
+                } else {
+                    value_sql(col, &pg_quote(cell))
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        rows.push(format!("({})", values.join(", ")));
+    }
+    let mut sql = format!(
+        "INSERT INTO {} ({}) VALUES\n{}",
+        TableName(&dest.name),
+        dest.columns.iter().map(|c| Ident(&c.name)).join(", "),
+        rows.join(",\n"),
+    );
+    if let Some(upsert_keys) = upsert_keys {
+        sql.push_str(&super::write_local_data::on_conflict_sql(
+            dest,
+            upsert_keys,
+            update_columns,
+        )?);
+    }
+    Ok(sql)
+}
+
+/// Generate SQL to convert the text literal `param` into a value for `col`.
+fn value_sql(col: &PgColumn, param: &str) -> Result<String> {
+    let cannot_insert = || {
+        format_err!(
+            "cannot write column {} of type {} using INSERT",
+            col.name,
+            col.data_type,
+        )
+    };
+    match &col.data_type {
+        // We can't parse our JSON interchange format for these types in SQL.
+        PgDataType::Scalar(PgScalarDataType::Composite { .. })
+        | PgDataType::Scalar(PgScalarDataType::Range { .. })
+        | PgDataType::Array {
+            ty: PgScalarDataType::Composite { .. },
+            ..
+        }
+        | PgDataType::Array {
+            ty: PgScalarDataType::Range { .. },
+            ..
+        } => Err(cannot_insert()),
+        PgDataType::Scalar(PgScalarDataType::Geometry(srid)) => Ok(format!(
+            "ST_SetSRID(ST_GeomFromGeoJSON({}::text), {})",
+            param,
+            srid.to_u32(),
+        )),
+        PgDataType::Scalar(PgScalarDataType::Bytea) => {
+            Ok(format!("decode({}::text, 'base64')", param))
+        }
+        PgDataType::Scalar(ty) => Ok(format!("{}::text::{}", param, ty)),
+        PgDataType::Array {
+            dimension_count: 1,
+            ty: PgScalarDataType::Bytea,
+        } => Ok(format!(
+            "CASE WHEN {param}::text IS NULL THEN NULL ELSE ARRAY(SELECT decode(elem, 'base64') FROM jsonb_array_elements_text({param}::text::jsonb) AS elem) END",
+            param = param,
+        )),
+        PgDataType::Array {
+            dimension_count: 1,
+            ty,
+        } if !matches!(ty, PgScalarDataType::Geometry(_)) => Ok(format!(
+            "CASE WHEN {param}::text IS NULL THEN NULL ELSE ARRAY(SELECT jsonb_array_elements_text({param}::text::jsonb))::{ty}[] END",
+            param = param,
+            ty = ty,
+        )),
+        PgDataType::Array { .. } => Err(cannot_insert()),
+    }
+}
+
+#[test]
+fn insert_sql_quotes_literals() {
+    let table = PgCreateTable {
+        name: "example".to_owned(),
+        columns: vec![
+            PgColumn {
+                name: "id".to_owned(),
+                data_type: PgDataType::Scalar(PgScalarDataType::Int),
+                is_nullable: false,
+                default: None,
+                comment: None,
+                collation: None,
+            },
+            PgColumn {
+                name: "tags".to_owned(),
+                data_type: PgDataType::Array {
+                    dimension_count: 1,
+                    ty: PgScalarDataType::Text,
+                },
+                is_nullable: true,
+                default: None,
+                comment: None,
+                collation: None,
+            },
+        ],
+        primary_key: vec![],
+        if_not_exists: false,
+        temporary: false,
+    };
+    let batch = vec![
+        csv::StringRecord::from(vec!["1", r#"["it's"]"#]),
+        csv::StringRecord::from(vec!["2", ""]),
+    ];
+    let sql = insert_sql(&table, None, None, &batch).unwrap();
+    assert_eq!(
+        sql,
+        r#"INSERT INTO "example" ("id", "tags") VALUES
+('1'::text::int, CASE WHEN '["it''s"]'::text IS NULL THEN NULL ELSE ARRAY(SELECT jsonb_array_elements_text('["it''s"]'::text::jsonb))::text[] END),
+('2'::text::int, CASE WHEN NULL::text IS NULL THEN NULL ELSE ARRAY(SELECT jsonb_array_elements_text(NULL::text::jsonb))::text[] END)"#,
+    );
+    assert!(!sql.contains('$'));
+}
+
+#[test]
+fn value_sql_rejects_composite_and_range_columns() {
+    let column = |data_type| PgColumn {
+        name: "value".to_owned(),
+        data_type,
+        is_nullable: true,
+        default: None,
+        comment: None,
+        collation: None,
+    };
+    let composite = PgScalarDataType::Composite {
+        name: "public.point2d".to_owned(),
+        fields: vec![],
+    };
+    let range = PgScalarDataType::Range {
+        name: "int4range".to_owned(),
+        subtype: Box::new(PgScalarDataType::Int),
+    };
+    for ty in &[composite, range] {
+        let err = value_sql(&column(PgDataType::Scalar(ty.to_owned())), "NULL")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("using INSERT"), "{}", err);
+        assert!(value_sql(
+            &column(PgDataType::Array {
+                dimension_count: 1,
+                ty: ty.to_owned(),
+            }),
+            "NULL"
+        )
+        .is_err());
+    }
+}
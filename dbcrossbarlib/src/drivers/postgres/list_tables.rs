@@ -0,0 +1,62 @@
+//! Implementation of `list_tables`, but as a real `async` function.
+
+use super::{connect, PostgresLocator};
+use crate::common::*;
+
+/// Implementation of `list_tables`, but as a real `async` function.
+pub(crate) async fn list_tables_helper(
+    ctx: Context,
+    locator: PostgresLocator,
+) -> Result<Option<Vec<(String, BoxLocator)>>> {
+    // A locator with a `#table_name` only refers to a single table.
+    if locator.table_name.is_some() {
+        return Ok(None);
+    }
+
+    // List all the ordinary tables outside of the system schemas.
+    let conn = connect(ctx.clone(), locator.url.clone()).await?;
+    let rows = conn
+        .query(
+            r#"
+SELECT table_schema, table_name
+    FROM information_schema.tables
+    WHERE table_type = 'BASE TABLE'
+        AND table_schema NOT IN ('pg_catalog', 'information_schema')
+    ORDER BY table_schema, table_name
+"#,
+            &[],
+        )
+        .await
+        .with_context(|_| format!("error listing tables in {}", locator))?;
+    let tables = rows
+        .iter()
+        .map(|row| {
+            let schema: &str = row.get("table_schema");
+            let name: &str = row.get("table_name");
+            let full_name = qualified_table_name(schema, name);
+            let locator = PostgresLocator {
+                url: locator.url.clone(),
+                table_name: Some(full_name.clone()),
+            };
+            (full_name, locator.boxed())
+        })
+        .collect::<Vec<_>>();
+    debug!(ctx.log(), "found {} tables in {}", tables.len(), locator);
+    Ok(Some(tables))
+}
+
+/// Tables in `public` are normally referred to without a schema, so leave it
+/// off.
+fn qualified_table_name(schema: &str, name: &str) -> String {
+    if schema == "public" {
+        name.to_owned()
+    } else {
+        format!("{}.{}", schema, name)
+    }
+}
+
+#[test]
+fn public_schema_is_omitted() {
+    assert_eq!(qualified_table_name("public", "users"), "users");
+    assert_eq!(qualified_table_name("audit", "events"), "audit.events");
+}
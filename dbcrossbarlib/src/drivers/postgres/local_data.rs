@@ -2,24 +2,94 @@
 
 use bytes::Bytes;
 use failure::Fail;
+use serde::Deserialize;
 
 use super::{connect, PostgresLocator};
 use crate::common::*;
-use crate::drivers::postgres_shared::{CheckCatalog, PgCreateTable};
+use crate::driver_args::deserialize_optional_from_str;
+use crate::drivers::postgres_shared::{
+    CheckCatalog, PgCreateTable, PgDataType, PgKeyRange, PgScalarDataType,
+};
 
-/// Copy the specified table from the database, returning a `CsvStream`.
+/// Parsed version of `--from-arg` values.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PostgresSourceArguments {
+    /// Split our export into this many ranges of keys, which will be exported
+    /// using separate connections.
+    #[serde(default, deserialize_with = "deserialize_optional_from_str")]
+    ranges: Option<usize>,
+
+    /// The integer column to split our export on. Defaults to the primary key.
+    #[serde(default)]
+    partition_column: Option<String>,
+}
+
+impl PostgresSourceArguments {
+    /// How many key ranges should we export? Returns `None` if we should
+    /// export everything using a single query.
+    fn range_count(&self, max_streams: usize) -> Option<usize> {
+        match (self.ranges, &self.partition_column) {
+            (Some(ranges), _) => Some(ranges),
+            (None, Some(_)) => Some(max_streams),
+            (None, None) => None,
+        }
+    }
+
+    /// Which column should we split our export on?
+    fn key_column(&self, pg_create_table: &PgCreateTable) -> Result<String> {
+        let column = match &self.partition_column {
+            Some(column) => column.to_owned(),
+            None => match &pg_create_table.primary_key[..] {
+                [column] => column.to_owned(),
+                _ => {
+                    return Err(format_err!(
+                        "{} needs a single-column primary key to export key ranges, or --from-arg=partition_column=COLUMN",
+                        pg_create_table.name,
+                    ))
+                }
+            },
+        };
+        let pg_column = pg_create_table
+            .columns
+            .iter()
+            .find(|c| c.name == column)
+            .ok_or_else(|| format_err!("no column {:?} to partition on", column))?;
+        match &pg_column.data_type {
+            PgDataType::Scalar(
+                PgScalarDataType::Smallint
+                | PgScalarDataType::Int
+                | PgScalarDataType::Bigint,
+            ) => Ok(column),
+            _ => Err(format_err!(
+                "can only split exports on integer columns, but {:?} is {}",
+                column,
+                pg_column.data_type,
+            )),
+        }
+    }
+}
+
+/// Copy the specified table from the database, returning a `CsvStream`. If
+/// `limit` is present, we only copy that many rows.
 pub(crate) async fn local_data_helper(
     ctx: Context,
     url: Url,
     table_name: String,
     shared_args: SharedArguments<Unverified>,
     source_args: SourceArguments<Unverified>,
+    limit: Option<usize>,
 ) -> Result<Option<BoxStream<CsvStream>>> {
+    let max_streams = shared_args.max_streams();
     let shared_args = shared_args.verify(PostgresLocator::features())?;
     let source_args = source_args.verify(PostgresLocator::features())?;
 
     // Look up the arguments we'll need.
     let schema = shared_args.schema();
+    let pg_source_args = source_args
+        .driver_args()
+        .deserialize::<PostgresSourceArguments>()
+        .context("could not parse --from-arg")?;
 
     // Set up our logger.
     let ctx =
@@ -35,14 +105,118 @@ pub(crate) async fn local_data_helper(
     )
     .await?;
 
-    // Generate SQL for query.
+    // Figure out whether we should split our export into key ranges. We
+    // always use a single query for previews.
+    let key_ranges = match pg_source_args.range_count(max_streams) {
+        Some(0) => return Err(format_err!("ranges must be greater than 0")),
+        Some(count) if limit.is_none() => {
+            if source_args.order_by().is_some() || source_args.limit().is_some() {
+                return Err(format_err!(
+                    "cannot use --order-by or --limit when exporting key ranges from PostgreSQL"
+                ));
+            }
+            let column = pg_source_args.key_column(&pg_create_table)?;
+            find_key_ranges(&ctx, &url, &pg_create_table, &source_args, &column, count)
+                .await?
+        }
+        _ => vec![],
+    };
+
+    if key_ranges.len() <= 1 {
+        // Copy the data out of PostgreSQL as a single CSV stream.
+        let mut sql_bytes: Vec<u8> = vec![];
+        pg_create_table.write_export_sql(
+            &mut sql_bytes,
+            &source_args,
+            schema,
+            limit,
+            None,
+        )?;
+        let sql = String::from_utf8(sql_bytes).expect("should always be UTF-8");
+        let csv_stream = copy_out(ctx, url, table_name, sql).await?;
+        let box_stream = stream::once(async { Ok(csv_stream) }).boxed();
+        return Ok(Some(box_stream));
+    }
+
+    // Build one query for each key range, and run them as our consumer asks for
+    // streams, so that `--max-streams` controls how many run at once.
+    let queries = key_ranges
+        .iter()
+        .enumerate()
+        .map(|(i, key_range)| -> Result<(String, String)> {
+            let mut sql_bytes: Vec<u8> = vec![];
+            pg_create_table.write_export_sql(
+                &mut sql_bytes,
+                &source_args,
+                schema,
+                None,
+                Some(key_range),
+            )?;
+            let sql = String::from_utf8(sql_bytes).expect("should always be UTF-8");
+            Ok((format!("{}_{:04}", table_name, i), sql))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let csv_streams = stream::iter(queries).then(move |(name, sql)| {
+        let ctx = ctx.child(o!("stream" => name.clone()));
+        copy_out(ctx, url.clone(), name, sql)
+    });
+    Ok(Some(csv_streams.boxed()))
+}
+
+/// Look up the smallest and largest values of `column`, and split them into
+/// `count` key ranges. Returns an empty list if there are no rows.
+async fn find_key_ranges(
+    ctx: &Context,
+    url: &Url,
+    pg_create_table: &PgCreateTable,
+    source_args: &SourceArguments<Verified>,
+    column: &str,
+    count: usize,
+) -> Result<Vec<PgKeyRange>> {
     let mut sql_bytes: Vec<u8> = vec![];
-    pg_create_table.write_export_sql(&mut sql_bytes, &source_args)?;
+    pg_create_table.write_min_max_sql(&mut sql_bytes, source_args, column)?;
     let sql = String::from_utf8(sql_bytes).expect("should always be UTF-8");
-    debug!(ctx.log(), "export SQL: {}", sql);
+    debug!(ctx.log(), "key range SQL: {}", sql);
+
+    let conn = connect(ctx.clone(), url.clone()).await?;
+    let row = conn
+        .query_one(sql.as_str(), &[])
+        .await
+        .context("error looking up key range")?;
+    let min: Option<i64> = row.get("min_value");
+    let max: Option<i64> = row.get("max_value");
+    match (min, max) {
+        (Some(min), Some(max)) => {
+            let key_ranges = PgKeyRange::split(column, min, max, count);
+            debug!(
+                ctx.log(),
+                "exporting {} in {} key ranges",
+                column,
+                key_ranges.len(),
+            );
+            Ok(key_ranges)
+        }
+        _ => Ok(vec![]),
+    }
+}
 
-    // Copy the data out of PostgreSQL as a CSV stream.
-    let conn = connect(ctx.clone(), url).await?;
+/// Run `sql`, which should be a `COPY ... TO STDOUT` statement, and return the
+/// output as a CSV stream named `name`.
+async fn copy_out(
+    ctx: Context,
+    url: Url,
+    name: String,
+    sql: String,
+) -> Result<CsvStream> {
+    debug!(ctx.log(), "export SQL: {}", sql);
+    // Our stream keeps using this connection after we return, so don't let
+    // anybody else borrow it from the pool.
+    let conn = connect(ctx.clone(), url).await?.detach();
+    // Output `interval` values as ISO 8601 durations, as required by our CSV
+    // interchange format.
+    conn.batch_execute("SET intervalstyle = 'iso_8601'")
+        .await
+        .context("could not set PostgreSQL intervalstyle")?;
     let stmt = conn.prepare(&sql).await?;
     let rdr = conn
         .copy_out(&stmt)
@@ -59,10 +233,8 @@ pub(crate) async fn local_data_helper(
         // Convert errors to our standard error type.
         .map_err(|err| err.context("error reading data from PostgreSQL").into());
 
-    let csv_stream = CsvStream {
-        name: table_name.clone(),
+    Ok(CsvStream {
+        name,
         data: rdr.boxed(),
-    };
-    let box_stream = stream::once(async { Ok(csv_stream) }).boxed();
-    Ok(Some(box_stream))
+    })
 }
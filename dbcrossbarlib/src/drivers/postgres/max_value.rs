@@ -0,0 +1,53 @@
+//! Implementation of `max_value`, but as a real `async` function.
+
+use super::{connect, PostgresLocator};
+use crate::common::*;
+use crate::drivers::postgres_shared::{CheckCatalog, PgCreateTable};
+
+/// Implementation of `max_value`, but as a real `async` function.
+pub(crate) async fn max_value_helper(
+    ctx: Context,
+    locator: PostgresLocator,
+    shared_args: SharedArguments<Unverified>,
+    source_args: SourceArguments<Unverified>,
+    column: String,
+) -> Result<Option<String>> {
+    let shared_args = shared_args.verify(PostgresLocator::features())?;
+    let source_args = source_args.verify(PostgresLocator::features())?;
+
+    // Get the parts of our locator.
+    let url = locator.url.clone();
+    let table_name = locator.table_name()?.to_owned();
+
+    // Convert our schema to a native PostgreSQL schema.
+    let pg_create_table = PgCreateTable::from_pg_catalog_or_default(
+        // No need to look at the catalog, since we only need one column name.
+        CheckCatalog::No,
+        &url,
+        &table_name,
+        shared_args.schema(),
+    )
+    .await?;
+
+    // Generate SQL for query.
+    let mut sql_bytes: Vec<u8> = vec![];
+    pg_create_table.write_max_sql(&mut sql_bytes, &source_args, &column)?;
+    let sql = String::from_utf8(sql_bytes).expect("should always be UTF-8");
+    debug!(ctx.log(), "max SQL: {}", sql);
+
+    // Run our query.
+    let conn = connect(ctx.clone(), url).await?;
+    let stmt = conn.prepare(&sql).await?;
+    let rows = conn
+        .query(&stmt, &[])
+        .await
+        .context("error running max query")?;
+    if rows.len() != 1 {
+        Err(format_err!(
+            "expected 1 row of max output, got {}",
+            rows.len(),
+        ))
+    } else {
+        Ok(rows[0].get("max_value"))
+    }
+}
@@ -0,0 +1,349 @@
+//! PostgreSQL declarative partitioning.
+
+use serde::Deserialize;
+use std::{fmt, str::FromStr};
+
+use super::Client;
+use crate::common::*;
+use crate::drivers::postgres_shared::{
+    pg_quote, Ident, PgCreateTable, PgDataType, PgScalarDataType, TableName,
+};
+
+/// How should we split rows between partitions?
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum PgPartitionStrategy {
+    /// Each partition holds a range of values.
+    Range,
+    /// Each partition holds a list of values.
+    List,
+}
+
+impl fmt::Display for PgPartitionStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PgPartitionStrategy::Range => write!(f, "RANGE"),
+            PgPartitionStrategy::List => write!(f, "LIST"),
+        }
+    }
+}
+
+/// A `PARTITION BY` clause, specified as `range(column)` or `list(column)`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct PgPartitionBy {
+    /// How to split rows between partitions.
+    strategy: PgPartitionStrategy,
+    /// The column to partition on.
+    column: String,
+}
+
+impl FromStr for PgPartitionBy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let err =
+            || format_err!("expected range(column) or list(column), found {:?}", s);
+        let open = s.find('(').ok_or_else(err)?;
+        if !s.ends_with(')') {
+            return Err(err());
+        }
+        let strategy = match &s[..open].trim().to_ascii_lowercase()[..] {
+            "range" => PgPartitionStrategy::Range,
+            "list" => PgPartitionStrategy::List,
+            _ => return Err(err()),
+        };
+        let column = s[open + 1..s.len() - 1].trim();
+        if column.is_empty() {
+            return Err(err());
+        }
+        Ok(PgPartitionBy {
+            strategy,
+            column: column.to_owned(),
+        })
+    }
+}
+
+/// How much time should each automatically-created range partition cover?
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum PgPartitionInterval {
+    /// One partition per day.
+    Day,
+    /// One partition per month.
+    Month,
+    /// One partition per year.
+    Year,
+}
+
+impl PgPartitionInterval {
+    /// The name of this interval, as understood by `date_trunc`.
+    fn as_str(self) -> &'static str {
+        match self {
+            PgPartitionInterval::Day => "day",
+            PgPartitionInterval::Month => "month",
+            PgPartitionInterval::Year => "year",
+        }
+    }
+
+    /// Build a partition name suffix from a start date like `2020-01-01`.
+    fn partition_suffix(self, start: &str) -> String {
+        let len = match self {
+            PgPartitionInterval::Day => 10,
+            PgPartitionInterval::Month => 7,
+            PgPartitionInterval::Year => 4,
+        };
+        start[..len.min(start.len())].replace('-', "_")
+    }
+}
+
+/// How to partition a PostgreSQL table.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct PgPartitioning {
+    /// Our `PARTITION BY` clause.
+    partition_by: PgPartitionBy,
+    /// If present, automatically create a range partition of this size for
+    /// each row we load.
+    interval: Option<PgPartitionInterval>,
+}
+
+impl PgPartitioning {
+    /// Build a `PgPartitioning` from `--to-arg` values, or return `None` if no
+    /// partitioning was requested.
+    pub(crate) fn from_dest_args(
+        partition_by: Option<PgPartitionBy>,
+        interval: Option<PgPartitionInterval>,
+    ) -> Result<Option<PgPartitioning>> {
+        match (partition_by, interval) {
+            (None, None) => Ok(None),
+            (None, Some(_)) => {
+                Err(format_err!("partition_interval requires partition_by"))
+            }
+            (Some(partition_by), Some(_))
+                if partition_by.strategy != PgPartitionStrategy::Range =>
+            {
+                Err(format_err!(
+                    "partition_interval requires partition_by=range(...)"
+                ))
+            }
+            (Some(partition_by), interval) => Ok(Some(PgPartitioning {
+                partition_by,
+                interval,
+            })),
+        }
+    }
+
+    /// Do we need to create partitions as we load data?
+    pub(crate) fn creates_partitions(&self) -> bool {
+        self.interval.is_some()
+    }
+
+    /// Make sure that `table` can be partitioned this way.
+    pub(crate) fn validate(&self, table: &PgCreateTable) -> Result<()> {
+        let column = &self.partition_by.column;
+        let col = table
+            .columns
+            .iter()
+            .find(|c| &c.name == column)
+            .ok_or_else(|| {
+                format_err!("cannot partition on unknown column {}", column)
+            })?;
+        if self.interval.is_some() {
+            match &col.data_type {
+                PgDataType::Scalar(PgScalarDataType::Date)
+                | PgDataType::Scalar(PgScalarDataType::TimestampWithoutTimeZone)
+                | PgDataType::Scalar(PgScalarDataType::TimestampWithTimeZone) => {}
+                other => {
+                    return Err(format_err!(
+                        "partition_interval requires a date or timestamp column, but {} is {}",
+                        column,
+                        other,
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Generate `CREATE TABLE` SQL for a partitioned version of `table`.
+    pub(crate) fn create_table_sql(&self, table: &PgCreateTable) -> Result<String> {
+        self.validate(table)?;
+        let sql = table.to_string();
+        let sql = sql.trim_end().trim_end_matches(';');
+        Ok(format!(
+            "{}\nPARTITION BY {} ({});\n",
+            sql,
+            self.partition_by.strategy,
+            Ident(&self.partition_by.column),
+        ))
+    }
+
+    /// Generate SQL to create a partition of `table` that holds any rows
+    /// which don't belong in another partition, including `NULL`s.
+    pub(crate) fn create_default_partition_sql(
+        &self,
+        table: &PgCreateTable,
+    ) -> Result<String> {
+        Ok(format!(
+            "CREATE TABLE IF NOT EXISTS {} PARTITION OF {} DEFAULT",
+            TableName(&partition_name(&table.name, "default")?),
+            TableName(&table.name),
+        ))
+    }
+
+    /// Generate SQL that returns the start and end of each partition needed by
+    /// the rows in `src_table`.
+    fn partition_bounds_sql(&self, src_table: &PgCreateTable) -> Option<String> {
+        let interval = self.interval?;
+        let start = format!(
+            "date_trunc({}, {})",
+            pg_quote(interval.as_str()),
+            Ident(&self.partition_by.column),
+        );
+        Some(format!(
+            "SELECT DISTINCT ({start})::date::text, ({start} + interval {interval})::date::text FROM {table} WHERE {column} IS NOT NULL",
+            start = start,
+            interval = pg_quote(&format!("1 {}", interval.as_str())),
+            table = TableName(&src_table.name),
+            column = Ident(&self.partition_by.column),
+        ))
+    }
+
+    /// Create a default partition for `dest_table`, if it doesn't already
+    /// exist, so that loading data never fails for lack of a partition.
+    pub(crate) async fn create_default_partition(
+        &self,
+        ctx: &Context,
+        client: &mut Client,
+        dest_table: &PgCreateTable,
+    ) -> Result<()> {
+        let sql = self.create_default_partition_sql(dest_table)?;
+        debug!(ctx.log(), "default partition SQL: {}", sql);
+        client.batch_execute(&sql).await.with_context(|_| {
+            format!("error creating partition of {}", dest_table.name)
+        })?;
+        Ok(())
+    }
+
+    /// Create any partitions of `dest_table` needed to hold the rows in
+    /// `src_table`.
+    pub(crate) async fn create_partitions_for(
+        &self,
+        ctx: &Context,
+        client: &mut Client,
+        src_table: &PgCreateTable,
+        dest_table: &PgCreateTable,
+    ) -> Result<()> {
+        let (interval, bounds_sql) =
+            match (self.interval, self.partition_bounds_sql(src_table)) {
+                (Some(interval), Some(sql)) => (interval, sql),
+                _ => return Ok(()),
+            };
+        debug!(ctx.log(), "partition bounds SQL: {}", bounds_sql);
+        let rows = client.query(&bounds_sql[..], &[]).await.with_context(|_| {
+            format!("error finding partitions for {}", dest_table.name)
+        })?;
+        for row in rows {
+            let start: String = row.get(0);
+            let end: String = row.get(1);
+            let name =
+                partition_name(&dest_table.name, &interval.partition_suffix(&start))?;
+            let sql = format!(
+                "CREATE TABLE IF NOT EXISTS {} PARTITION OF {} FOR VALUES FROM ({}) TO ({})",
+                TableName(&name),
+                TableName(&dest_table.name),
+                pg_quote(&start),
+                pg_quote(&end),
+            );
+            debug!(ctx.log(), "partition SQL: {}", sql);
+            client
+                .batch_execute(&sql)
+                .await
+                .with_context(|_| format!("error creating partition {}", name))?;
+        }
+        Ok(())
+    }
+}
+
+/// The name of the partition of `table_name` with `suffix`, in the same
+/// namespace as `table_name`.
+fn partition_name(table_name: &str, suffix: &str) -> Result<String> {
+    let table_name = TableName(table_name);
+    let (namespace, base_name) = table_name.split()?;
+    Ok(match namespace {
+        Some(namespace) => format!("{}.{}_{}", namespace, base_name, suffix),
+        None => format!("{}_{}", base_name, suffix),
+    })
+}
+
+#[test]
+fn partitioned_tables_are_created_with_partition_by() {
+    let table = "CREATE TABLE public.events (
+    id bigint NOT NULL,
+    region text,
+    created_at timestamp with time zone
+);"
+    .parse::<PgCreateTable>()
+    .unwrap();
+
+    let partitioning = PgPartitioning::from_dest_args(
+        Some("range(created_at)".parse().unwrap()),
+        Some(PgPartitionInterval::Month),
+    )
+    .unwrap()
+    .unwrap();
+    assert!(partitioning.creates_partitions());
+    assert_eq!(
+        partitioning.create_table_sql(&table).unwrap(),
+        r#"CREATE TABLE "public"."events" (
+    "id" bigint NOT NULL,
+    "region" text,
+    "created_at" timestamp with time zone
+)
+PARTITION BY RANGE ("created_at");
+"#,
+    );
+    assert_eq!(
+        partitioning.create_default_partition_sql(&table).unwrap(),
+        r#"CREATE TABLE IF NOT EXISTS "public"."events_default" PARTITION OF "public"."events" DEFAULT"#,
+    );
+    assert_eq!(
+        partitioning.partition_bounds_sql(&table).unwrap(),
+        r#"SELECT DISTINCT (date_trunc('month', "created_at"))::date::text, (date_trunc('month', "created_at") + interval '1 month')::date::text FROM "public"."events" WHERE "created_at" IS NOT NULL"#,
+    );
+    assert_eq!(
+        PgPartitionInterval::Month.partition_suffix("2020-03-01"),
+        "2020_03",
+    );
+
+    let list =
+        PgPartitioning::from_dest_args(Some("LIST (region)".parse().unwrap()), None)
+            .unwrap()
+            .unwrap();
+    assert!(!list.creates_partitions());
+    assert!(list
+        .create_table_sql(&table)
+        .unwrap()
+        .ends_with("\nPARTITION BY LIST (\"region\");\n"));
+
+    assert!("hash(id)".parse::<PgPartitionBy>().is_err());
+    assert!("range()".parse::<PgPartitionBy>().is_err());
+    assert!(
+        PgPartitioning::from_dest_args(None, Some(PgPartitionInterval::Day)).is_err()
+    );
+    assert!(PgPartitioning::from_dest_args(
+        Some("list(region)".parse().unwrap()),
+        Some(PgPartitionInterval::Day),
+    )
+    .is_err());
+    let by_region = PgPartitioning::from_dest_args(
+        Some("range(region)".parse().unwrap()),
+        Some(PgPartitionInterval::Day),
+    )
+    .unwrap()
+    .unwrap();
+    assert!(by_region.validate(&table).is_err());
+    let missing =
+        PgPartitioning::from_dest_args(Some("range(missing)".parse().unwrap()), None)
+            .unwrap()
+            .unwrap();
+    assert!(missing.validate(&table).is_err());
+}
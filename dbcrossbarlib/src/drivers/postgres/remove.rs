@@ -0,0 +1,23 @@
+//! Implementation of `remove`, but as a real `async` function.
+
+use super::connect;
+use crate::common::*;
+use crate::drivers::postgres_shared::TableName;
+
+/// Implementation of `remove`, but as a real `async` function. This is also
+/// used by Redshift.
+pub(crate) async fn remove_helper(
+    ctx: Context,
+    url: Url,
+    table_name: String,
+) -> Result<()> {
+    let client = connect(ctx.clone(), url).await?;
+    debug!(ctx.log(), "deleting table {} if exists", table_name);
+    let drop_sql = format!("DROP TABLE IF EXISTS {}", TableName(&table_name));
+    let drop_stmt = client.prepare(&drop_sql).await?;
+    client
+        .execute(&drop_stmt, &[])
+        .await
+        .with_context(|_| format!("error deleting {}", table_name))?;
+    Ok(())
+}
@@ -2,14 +2,156 @@
 
 use futures::pin_mut;
 use itertools::Itertools;
-use std::{collections::HashSet, io::prelude::*, iter::FromIterator, str};
+use serde::Deserialize;
+use std::{io::prelude::*, str};
 
-use super::{connect, csv_to_binary::copy_csv_to_pg_binary, Client, PostgresLocator};
+use super::{
+    connect,
+    csv_to_binary::copy_csv_to_pg_binary,
+    insert::{insert_from_stream, InsertOptions},
+    partitioning::{PgPartitionBy, PgPartitionInterval, PgPartitioning},
+    Client, PostgresLocator,
+};
 use crate::common::*;
-use crate::drivers::postgres_shared::{CheckCatalog, Ident, PgCreateTable, TableName};
+use crate::driver_args::{deserialize_from_str, deserialize_optional_from_str};
+use crate::drivers::postgres_shared::{
+    CheckCatalog, Ident, PgColumn, PgCreateIndex, PgCreateTable, TableName,
+};
+use crate::evolve_schema::EvolveSchema;
+use crate::if_exists::{upsert_update_columns, MergeDelete};
+use crate::schema::Index;
 use crate::tokio_glue::try_forward;
 use crate::transform::spawn_sync_transform;
 
+/// Parsed version of `--to-arg` values.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PostgresDestinationArguments {
+    /// How should we write our data?
+    #[serde(default)]
+    write_method: WriteMethod,
+
+    /// The maximum number of rows to write with each `INSERT`.
+    #[serde(
+        default = "PostgresDestinationArguments::default_batch_size",
+        deserialize_with = "deserialize_from_str"
+    )]
+    batch_size: usize,
+
+    /// The number of `INSERT` statements to run at once.
+    #[serde(
+        default = "PostgresDestinationArguments::default_concurrency",
+        deserialize_with = "deserialize_from_str"
+    )]
+    concurrency: usize,
+
+    /// The maximum number of rows to `INSERT` per second.
+    #[serde(default, deserialize_with = "deserialize_optional_from_str")]
+    max_rows_per_second: Option<f64>,
+
+    /// Create any secondary indexes in our schema after loading our data.
+    #[serde(default, deserialize_with = "deserialize_from_str")]
+    create_indexes: bool,
+
+    /// Create new tables with `PARTITION BY RANGE (col)` or `PARTITION BY LIST
+    /// (col)`.
+    #[serde(default, deserialize_with = "deserialize_optional_from_str")]
+    partition_by: Option<PgPartitionBy>,
+
+    /// Automatically create a range partition of this size for the rows we
+    /// load.
+    #[serde(default)]
+    partition_interval: Option<PgPartitionInterval>,
+
+    /// How should we change an existing table to match our schema?
+    #[serde(default, deserialize_with = "deserialize_optional_from_str")]
+    evolve_schema: Option<EvolveSchema>,
+}
+
+impl PostgresDestinationArguments {
+    fn default_batch_size() -> usize {
+        1000
+    }
+
+    fn default_concurrency() -> usize {
+        1
+    }
+
+    /// Get our options for `write_method=insert`.
+    fn insert_options(&self) -> Result<InsertOptions> {
+        if self.batch_size == 0 || self.concurrency == 0 {
+            return Err(format_err!(
+                "batch_size and concurrency must be greater than 0"
+            ));
+        }
+        match self.max_rows_per_second {
+            Some(rate) if rate <= 0.0 || !rate.is_finite() => {
+                Err(format_err!("max_rows_per_second must be a positive number"))
+            }
+            _ => Ok(InsertOptions {
+                batch_size: self.batch_size,
+                concurrency: self.concurrency,
+                max_rows_per_second: self.max_rows_per_second,
+            }),
+        }
+    }
+
+    /// How should we partition our destination table?
+    fn partitioning(&self) -> Result<Option<PgPartitioning>> {
+        let partitioning = PgPartitioning::from_dest_args(
+            self.partition_by.clone(),
+            self.partition_interval,
+        )?;
+        if self.write_method == WriteMethod::Insert
+            && partitioning
+                .as_ref()
+                .map(|p| p.creates_partitions())
+                .unwrap_or(false)
+        {
+            return Err(format_err!(
+                "partition_interval cannot be used with write_method=insert"
+            ));
+        }
+        Ok(partitioning)
+    }
+
+    /// Can we `COPY` several streams into our destination table at once?
+    fn copies_in_parallel(
+        &self,
+        if_exists: &IfExists,
+        creates_partitions: bool,
+        max_streams: usize,
+    ) -> bool {
+        self.write_method == WriteMethod::Copy
+            && !if_exists.changes_existing_rows()
+            && !creates_partitions
+            && max_streams > 1
+    }
+
+    /// Make sure we can write data using `if_exists`.
+    fn check_if_exists(&self, if_exists: &IfExists) -> Result<()> {
+        if self.write_method == WriteMethod::Insert && if_exists.is_merge() {
+            return Err(format_err!(
+                "--if-exists={} cannot be used with write_method=insert",
+                if_exists,
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// How should we write data to PostgreSQL?
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum WriteMethod {
+    /// Use `COPY FROM STDIN WITH BINARY`. This is much faster.
+    #[default]
+    Copy,
+    /// Use batched, multi-row `INSERT` statements. This works with servers and
+    /// proxies that don't support `COPY`.
+    Insert,
+}
+
 /// If `table_name` exists, `DROP` it.
 async fn drop_table_if_exists(
     ctx: &Context,
@@ -31,15 +173,37 @@ async fn create_table(
     ctx: &Context,
     client: &mut Client,
     table: &PgCreateTable,
+) -> Result<()> {
+    create_table_with_sql(ctx, client, table, format!("{}", table)).await
+}
+
+/// Create `table` using `create_sql`, which may contain database-specific
+/// table options.
+async fn create_table_with_sql(
+    ctx: &Context,
+    client: &mut Client,
+    table: &PgCreateTable,
+    create_sql: String,
 ) -> Result<()> {
     debug!(ctx.log(), "create table {}", table.name);
-    let create_sql = format!("{}", table);
     debug!(ctx.log(), "CREATE TABLE SQL: {}", create_sql);
     let create_stmt = client.prepare(&create_sql).await?;
     client
         .execute(&create_stmt, &[])
         .await
         .with_context(|_| format!("error creating {}", &table.name))?;
+
+    // Column comments don't matter for temporary tables.
+    if !table.temporary {
+        for comment in table.comments() {
+            let comment_sql = comment.to_string();
+            debug!(ctx.log(), "COMMENT SQL: {}", comment_sql);
+            let comment_stmt = client.prepare(&comment_sql).await?;
+            client.execute(&comment_stmt, &[]).await.with_context(|_| {
+                format!("error commenting on {}.{}", table.name, comment.column_name)
+            })?;
+        }
+    }
     Ok(())
 }
 
@@ -50,6 +214,13 @@ async fn create_temp_table_for(
     client: &mut Client,
     table: &PgCreateTable,
 ) -> Result<PgCreateTable> {
+    let temp_table = temp_table_for(table)?;
+    create_table(ctx, client, &temp_table).await?;
+    Ok(temp_table)
+}
+
+/// Describe a temporary table based on `table`, but using a different name.
+pub(crate) fn temp_table_for(table: &PgCreateTable) -> Result<PgCreateTable> {
     let mut temp_table = table.to_owned();
     let temp_name = {
         // Temporary table names aren't allowed to include namespaces.
@@ -60,10 +231,122 @@ async fn create_temp_table_for(
     temp_table.name = temp_name;
     temp_table.if_not_exists = false;
     temp_table.temporary = true;
-    create_table(ctx, client, &temp_table).await?;
+    // Our incoming data may contain duplicate keys, which we want to report
+    // when merging, not when loading the temporary table.
+    temp_table.primary_key.clear();
     Ok(temp_table)
 }
 
+/// Create `indexes` from `src_table_name` on `dest_table_name`, unless indexes
+/// with the same names already exist.
+async fn create_indexes(
+    ctx: &Context,
+    client: &mut Client,
+    src_table_name: &str,
+    dest_table_name: &str,
+    indexes: &[Index],
+) -> Result<()> {
+    for index in indexes {
+        let mut pg_create_index = PgCreateIndex::from_index(dest_table_name, index);
+        pg_create_index.name =
+            index_name_for_table(&index.name, src_table_name, dest_table_name)?;
+        pg_create_index.if_not_exists = true;
+        let create_sql = format!("{}", pg_create_index);
+        debug!(ctx.log(), "CREATE INDEX SQL: {}", create_sql);
+        let create_stmt = client.prepare(&create_sql).await?;
+        client
+            .execute(&create_stmt, &[])
+            .await
+            .with_context(|_| format!("error creating index {}", index.name))?;
+    }
+    Ok(())
+}
+
+/// Index names must be unique within a namespace, so if `index_name` follows
+/// PostgreSQL's convention of starting with the table name, replace
+/// `src_table_name` with `dest_table_name`. Otherwise, copying a table within
+/// the same database would not create any indexes.
+fn index_name_for_table(
+    index_name: &str,
+    src_table_name: &str,
+    dest_table_name: &str,
+) -> Result<String> {
+    let src_table_name = TableName(src_table_name);
+    let dest_table_name = TableName(dest_table_name);
+    let (_, src_base_name) = src_table_name.split()?;
+    let (_, dest_base_name) = dest_table_name.split()?;
+    let prefix = format!("{}_", src_base_name);
+    Ok(match index_name.strip_prefix(&prefix) {
+        Some(rest) => format!("{}_{}", dest_base_name, rest),
+        None => index_name.to_owned(),
+    })
+}
+
+#[test]
+fn index_names_follow_table_names() {
+    let examples = &[
+        (
+            "users_email_idx",
+            "public.users",
+            "users2",
+            "users2_email_idx",
+        ),
+        ("users_email_idx", "users", "other.users", "users_email_idx"),
+        ("by_email", "users", "users2", "by_email"),
+    ];
+    for &(index_name, src, dest, expected) in examples {
+        assert_eq!(
+            index_name_for_table(index_name, src, dest).unwrap(),
+            expected
+        );
+    }
+}
+
+#[test]
+fn merge_removes_rows_missing_from_source() {
+    use crate::drivers::postgres_shared::{PgDataType, PgScalarDataType};
+
+    let column = |name: &str| PgColumn {
+        name: name.to_owned(),
+        data_type: PgDataType::Scalar(PgScalarDataType::Text),
+        is_nullable: false,
+        default: None,
+        comment: None,
+        collation: None,
+    };
+    let dest_table = PgCreateTable {
+        name: "users".to_owned(),
+        columns: vec![column("id"), column("name")],
+        primary_key: vec![],
+        if_not_exists: true,
+        temporary: false,
+    };
+    let mut src_table = dest_table.clone();
+    src_table.name = "users_temp".to_owned();
+    let keys = vec!["id".to_owned()];
+
+    let sql = merge_sql(&src_table, &dest_table, &keys, &MergeDelete::Delete).unwrap();
+    assert_eq!(sql.len(), 2);
+    assert!(sql[0].contains("ON CONFLICT (\"id\")"));
+    assert_eq!(
+        sql[1],
+        r#"DELETE FROM "users" AS dest WHERE NOT EXISTS (SELECT 1 FROM "users_temp" AS src WHERE src."id" = dest."id")"#,
+    );
+
+    let soft_delete = MergeDelete::SoftDelete("deleted_at".to_owned());
+    let sql = merge_sql(&src_table, &dest_table, &keys, &soft_delete).unwrap();
+    assert_eq!(sql.len(), 3);
+    assert_eq!(
+        sql[1],
+        r#"UPDATE "users" AS dest SET "deleted_at" = NULL FROM "users_temp" AS src WHERE src."id" = dest."id" AND dest."deleted_at" IS NOT NULL"#,
+    );
+    assert_eq!(
+        sql[2],
+        r#"UPDATE "users" AS dest SET "deleted_at" = now() WHERE dest."deleted_at" IS NULL AND NOT EXISTS (SELECT 1 FROM "users_temp" AS src WHERE src."id" = dest."id")"#,
+    );
+    assert!(check_soft_delete_column(&dest_table, "name").is_err());
+}
+
 /// Run `DROP TABLE` and/or `CREATE TABLE` as needed to prepare `table` for
 /// copying in data.
 ///
@@ -72,12 +355,41 @@ async fn create_temp_table_for(
 pub(crate) async fn prepare_table(
     ctx: &Context,
     client: &mut Client,
-    mut table: PgCreateTable,
+    table: PgCreateTable,
     if_exists: &IfExists,
 ) -> Result<()> {
+    prepare_table_with_sql(ctx, client, table, if_exists, |table| {
+        Ok(format!("{}", table))
+    })
+    .await
+}
+
+/// Like `prepare_table`, but call `create_sql` to generate our `CREATE TABLE`
+/// SQL. This allows databases like Redshift to add their own table options.
+pub(crate) async fn prepare_table_with_sql<F>(
+    ctx: &Context,
+    client: &mut Client,
+    mut table: PgCreateTable,
+    if_exists: &IfExists,
+    create_sql: F,
+) -> Result<()>
+where
+    F: FnOnce(&PgCreateTable) -> Result<String>,
+{
+    if let IfExists::Overwrite = if_exists {
+        drop_table_if_exists(ctx, client, &table).await?;
+    }
+    set_if_not_exists(&mut table, if_exists);
+    let sql = create_sql(&table)?;
+    create_table_with_sql(ctx, client, &table, sql).await
+}
+
+/// Decide whether `table` should be created using `CREATE TABLE IF NOT EXISTS`,
+/// depending on `if_exists`.
+fn set_if_not_exists(table: &mut PgCreateTable, if_exists: &IfExists) {
     match if_exists {
         IfExists::Overwrite => {
-            drop_table_if_exists(ctx, client, &table).await?;
+            // We'll have dropped any existing table.
             table.if_not_exists = false;
         }
         IfExists::Append => {
@@ -92,14 +404,46 @@ pub(crate) async fn prepare_table(
             // the table already exists, we will fail with an error.
             table.if_not_exists = false;
         }
-        IfExists::Upsert(_keys) => {
+        IfExists::Upsert(..) | IfExists::Merge(..) => {
             // Here, we can only make our final destination table. Each incoming
             // data stream will create its own temp table and then upsert into
             // this.
             table.if_not_exists = true;
         }
     }
-    create_table(ctx, client, &table).await
+}
+
+/// If `table_name` already exists, add any columns from `schema` that it lacks,
+/// as nullable columns. We call `add_column_sql` to generate the `ALTER TABLE`
+/// SQL for each column, so that databases like Redshift can use their own
+/// syntax.
+pub(crate) async fn add_missing_columns<F>(
+    ctx: &Context,
+    client: &mut Client,
+    url: &Url,
+    table_name: &str,
+    schema: &Table,
+    add_column_sql: F,
+) -> Result<()>
+where
+    F: Fn(&PgCreateTable, &PgColumn) -> Result<String>,
+{
+    let existing = match PgCreateTable::from_pg_catalog(url, table_name).await? {
+        Some(existing) => existing,
+        // We'll create the table with all our columns.
+        None => return Ok(()),
+    };
+    let wanted = PgCreateTable::from_name_and_table(table_name.to_owned(), schema)?;
+    for col in existing.missing_columns(&wanted) {
+        info!(ctx.log(), "adding column {} to {}", col.name, table_name);
+        let sql = add_column_sql(&existing, &col)?;
+        debug!(ctx.log(), "ALTER TABLE SQL: {}", sql);
+        let stmt = client.prepare(&sql).await?;
+        client.execute(&stmt, &[]).await.with_context(|_| {
+            format!("error adding column {} to {}", col.name, table_name)
+        })?;
+    }
+    Ok(())
 }
 
 /// Generate the `COPY ... FROM ...` SQL we'll pass to `copy_in`. `data_format`
@@ -127,6 +471,21 @@ fn copy_from_sql(table: &PgCreateTable, data_format: &str) -> Result<String> {
 /// Given `stream` containing CSV data, plus a the URL and table_name for a
 /// destination, as well as `"COPY FROM"` SQL, copy the data into the specified
 /// destination.
+/// Convert a CSV stream into a PostgreSQL `BINARY` stream for `dest`.
+fn csv_to_binary_stream(
+    ctx: &Context,
+    dest: &PgCreateTable,
+    data: BoxStream<BytesMut>,
+) -> Result<BoxStream<BytesMut>> {
+    let transform_table = dest.clone();
+    spawn_sync_transform(
+        ctx.clone(),
+        "copy_csv_to_pg_binary".to_owned(),
+        data,
+        move |_ctx, rdr, wtr| copy_csv_to_pg_binary(&transform_table, rdr, wtr),
+    )
+}
+
 async fn copy_from_stream<'a>(
     ctx: &'a Context,
     client: &'a mut Client,
@@ -148,43 +507,31 @@ async fn copy_from_stream<'a>(
     Ok(())
 }
 
-/// Generate SQL to perform an UPSERT from `src_table_name` into `dest_table`
-/// using `upsert_keys`.
-fn upsert_sql(
-    src_table: &PgCreateTable,
+/// Generate an `ON CONFLICT` clause which updates existing rows in
+/// `dest_table` that match `upsert_keys`. If `update_columns` is specified, we
+/// only update those columns.
+pub(super) fn on_conflict_sql(
     dest_table: &PgCreateTable,
     upsert_keys: &[String],
+    update_columns: Option<&[String]>,
 ) -> Result<String> {
-    // Figure out which of our columns are "value" (non-key) columns.
-    let upsert_keys_set: HashSet<&str> =
-        HashSet::from_iter(upsert_keys.iter().map(|k| &k[..]));
-    let value_keys = dest_table
+    // Figure out which of our columns are "value" columns that we update.
+    let columns = dest_table
         .columns
         .iter()
-        .filter_map(|c| {
-            if upsert_keys_set.contains(&c.name[..]) {
-                None
-            } else {
-                Some(&c.name[..])
-            }
-        })
+        .map(|c| &c.name[..])
         .collect::<Vec<_>>();
+    let value_keys = upsert_update_columns(&columns, upsert_keys, update_columns)?;
 
     // TODO: Do we need to check for NULLable key columns which might
     // produce duplicate rows on upsert, like we do for BigQuery?
 
     Ok(format!(
         r#"
-INSERT INTO {dest_table} ({all_columns}) (
-    SELECT {all_columns} FROM {src_table}
-)
 ON CONFLICT ({key_columns})
 DO UPDATE SET
     {value_updates}
 "#,
-        dest_table = Ident(&dest_table.name),
-        src_table = Ident(&src_table.name),
-        all_columns = dest_table.columns.iter().map(|c| Ident(&c.name)).join(", "),
         key_columns = upsert_keys.iter().map(|k| Ident(k)).join(", "),
         value_updates = value_keys
             .iter()
@@ -193,6 +540,58 @@ DO UPDATE SET
     ))
 }
 
+/// Generate SQL to perform an UPSERT from `src_table_name` into `dest_table`
+/// using `upsert_keys`, updating `update_columns` if specified.
+fn upsert_sql(
+    src_table: &PgCreateTable,
+    dest_table: &PgCreateTable,
+    upsert_keys: &[String],
+    update_columns: Option<&[String]>,
+) -> Result<String> {
+    Ok(format!(
+        r#"
+INSERT INTO {dest_table} ({all_columns}) (
+    SELECT {all_columns} FROM {src_table}
+){on_conflict}"#,
+        dest_table = Ident(&dest_table.name),
+        src_table = Ident(&src_table.name),
+        all_columns = dest_table.columns.iter().map(|c| Ident(&c.name)).join(", "),
+        on_conflict = on_conflict_sql(dest_table, upsert_keys, update_columns)?,
+    ))
+}
+
+/// Generate SQL to insert all rows from `src_table` into `dest_table`.
+fn insert_from_sql(src_table: &PgCreateTable, dest_table: &PgCreateTable) -> String {
+    format!(
+        "INSERT INTO {dest_table} ({all_columns}) SELECT {all_columns} FROM {src_table}",
+        dest_table = TableName(&dest_table.name),
+        src_table = TableName(&src_table.name),
+        all_columns = dest_table.columns.iter().map(|c| Ident(&c.name)).join(", "),
+    )
+}
+
+/// Insert all rows from `src` into `dest`.
+async fn insert_from(
+    ctx: &Context,
+    client: &mut Client,
+    src_table: &PgCreateTable,
+    dest_table: &PgCreateTable,
+) -> Result<()> {
+    let sql = insert_from_sql(src_table, dest_table);
+    debug!(
+        ctx.log(),
+        "inserting from {} to {} with {}", src_table.name, dest_table.name, sql,
+    );
+    let stmt = client.prepare(&sql).await?;
+    client.execute(&stmt, &[]).await.with_context(|_| {
+        format!(
+            "error inserting from {} to {}",
+            src_table.name, dest_table.name,
+        )
+    })?;
+    Ok(())
+}
+
 /// Upsert all rows from `src` into `dest`.
 pub(crate) async fn upsert_from(
     ctx: &Context,
@@ -200,8 +599,9 @@ pub(crate) async fn upsert_from(
     src_table: &PgCreateTable,
     dest_table: &PgCreateTable,
     upsert_keys: &[String],
+    update_columns: Option<&[String]>,
 ) -> Result<()> {
-    let sql = upsert_sql(src_table, dest_table, upsert_keys)?;
+    let sql = upsert_sql(src_table, dest_table, upsert_keys, update_columns)?;
     debug!(
         ctx.log(),
         "upserting from {} to {} with {}", src_table.name, dest_table.name, sql,
@@ -216,6 +616,102 @@ pub(crate) async fn upsert_from(
     Ok(())
 }
 
+/// Make sure that our soft-delete `column` isn't part of the data we're
+/// loading, because we set it ourselves.
+fn check_soft_delete_column(dest_table: &PgCreateTable, column: &str) -> Result<()> {
+    if dest_table.columns.iter().any(|c| c.name == column) {
+        return Err(format_err!(
+            "soft-delete column {} must not be in the input data",
+            column,
+        ));
+    }
+    Ok(())
+}
+
+/// Generate SQL to add the column used to record soft deletes by
+/// `--if-exists=merge-on:...`, if it doesn't already exist.
+fn add_soft_delete_column_sql(dest_table: &PgCreateTable, column: &str) -> String {
+    format!(
+        "ALTER TABLE {} ADD COLUMN IF NOT EXISTS {} timestamp with time zone",
+        TableName(&dest_table.name),
+        Ident(column),
+    )
+}
+
+/// Generate SQL to merge `src_table` into `dest_table`. We upsert every row in
+/// `src_table` using `merge_keys`, and then handle any rows of `dest_table`
+/// which don't appear in `src_table` as specified by `delete`.
+fn merge_sql(
+    src_table: &PgCreateTable,
+    dest_table: &PgCreateTable,
+    merge_keys: &[String],
+    delete: &MergeDelete,
+) -> Result<Vec<String>> {
+    let keys_match = merge_keys
+        .iter()
+        .map(|k| format!("src.{key} = dest.{key}", key = Ident(k)))
+        .join(" AND ");
+    let missing_from_src = format!(
+        "NOT EXISTS (SELECT 1 FROM {src_table} AS src WHERE {keys_match})",
+        src_table = TableName(&src_table.name),
+        keys_match = keys_match,
+    );
+    let mut statements = vec![upsert_sql(src_table, dest_table, merge_keys, None)?
+        .trim()
+        .to_owned()];
+    match delete {
+        MergeDelete::Delete => {
+            statements.push(format!(
+                "DELETE FROM {dest_table} AS dest WHERE {missing_from_src}",
+                dest_table = TableName(&dest_table.name),
+                missing_from_src = missing_from_src,
+            ));
+        }
+        MergeDelete::SoftDelete(column) => {
+            // Rows which reappear are no longer deleted.
+            statements.push(format!(
+                "UPDATE {dest_table} AS dest SET {column} = NULL FROM {src_table} AS src WHERE {keys_match} AND dest.{column} IS NOT NULL",
+                dest_table = TableName(&dest_table.name),
+                src_table = TableName(&src_table.name),
+                column = Ident(column),
+                keys_match = keys_match,
+            ));
+            statements.push(format!(
+                "UPDATE {dest_table} AS dest SET {column} = now() WHERE dest.{column} IS NULL AND {missing_from_src}",
+                dest_table = TableName(&dest_table.name),
+                column = Ident(column),
+                missing_from_src = missing_from_src,
+            ));
+        }
+    }
+    Ok(statements)
+}
+
+/// Merge all rows from `src` into `dest`.
+async fn merge_from(
+    ctx: &Context,
+    client: &mut Client,
+    src_table: &PgCreateTable,
+    dest_table: &PgCreateTable,
+    merge_keys: &[String],
+    delete: &MergeDelete,
+) -> Result<()> {
+    // PostgreSQL runs all the statements in a single `batch_execute` as one
+    // transaction, so readers never see rows that are only half-merged.
+    let sql = merge_sql(src_table, dest_table, merge_keys, delete)?.join(";\n");
+    debug!(
+        ctx.log(),
+        "merging from {} to {} with {}", src_table.name, dest_table.name, sql,
+    );
+    client.batch_execute(sql.as_str()).await.with_context(|_| {
+        format!(
+            "error merging from {} to {}",
+            src_table.name, dest_table.name,
+        )
+    })?;
+    Ok(())
+}
+
 /// The actual implementation of `write_local_data`, in a separate function so we
 /// can use `async`.
 pub(crate) async fn write_local_data_helper(
@@ -230,32 +726,124 @@ pub(crate) async fn write_local_data_helper(
 
     // Look up our arguments.
     let schema = shared_args.schema();
+    let max_streams = shared_args.max_streams();
     let if_exists = dest_args.if_exists().to_owned();
+    let pg_dest_args = dest_args
+        .driver_args()
+        .deserialize::<PostgresDestinationArguments>()
+        .context("could not parse --to-arg")?;
+    let insert_options = pg_dest_args.insert_options()?;
+    let partitioning = pg_dest_args.partitioning()?;
+    pg_dest_args.check_if_exists(&if_exists)?;
+    let src_table_name = schema.name.clone();
+    let indexes = if pg_dest_args.create_indexes {
+        schema.indexes.clone()
+    } else {
+        vec![]
+    };
 
     let url = dest.url.clone();
-    let table_name = dest.table_name.clone();
+    let table_name = dest.table_name()?.to_owned();
     let ctx = ctx.child(o!("table" => table_name.clone()));
     debug!(
         ctx.log(),
         "writing data streams to {} table {}", url, table_name,
     );
 
+    // Connect to PostgreSQL, and add any missing columns if asked.
+    let mut client = connect(ctx.clone(), url.clone()).await?;
+    if EvolveSchema::adds_columns(pg_dest_args.evolve_schema, &if_exists) {
+        add_missing_columns(
+            &ctx,
+            &mut client,
+            dest.url(),
+            &table_name,
+            schema,
+            |table, col| {
+                Ok(format!(
+                    "ALTER TABLE {} ADD COLUMN IF NOT EXISTS {}",
+                    TableName(&table.name),
+                    col,
+                ))
+            },
+        )
+        .await?;
+    }
+
     // Try to look up our destination table schema in the database.
     let dest_table = PgCreateTable::from_pg_catalog_or_default(
         CheckCatalog::from(&if_exists),
         dest.url(),
-        dest.table_name(),
+        &table_name,
         schema,
     )
     .await?;
 
-    // Connect to PostgreSQL and prepare our destination table.
-    let mut client = connect(ctx.clone(), url.clone()).await?;
-    prepare_table(&ctx, &mut client, dest_table.clone(), &if_exists).await?;
+    // Prepare our destination table.
+    if let Some(partitioning) = &partitioning {
+        prepare_table_with_sql(
+            &ctx,
+            &mut client,
+            dest_table.clone(),
+            &if_exists,
+            |table| partitioning.create_table_sql(table),
+        )
+        .await?;
+        partitioning
+            .create_default_partition(&ctx, &mut client, &dest_table)
+            .await?;
+    } else {
+        prepare_table(&ctx, &mut client, dest_table.clone(), &if_exists).await?;
+    }
+    let creates_partitions = partitioning
+        .as_ref()
+        .map(|p| p.creates_partitions())
+        .unwrap_or(false);
+
+    // When merging, we need to load all our streams before we can tell which
+    // rows are missing, so we collect them in a single temporary table.
+    let merge_table = if let IfExists::Merge(_, delete) = &if_exists {
+        if let MergeDelete::SoftDelete(column) = delete {
+            check_soft_delete_column(&dest_table, column)?;
+            let sql = add_soft_delete_column_sql(&dest_table, column);
+            debug!(ctx.log(), "adding soft-delete column: {}", sql);
+            client.batch_execute(sql.as_str()).await.with_context(|_| {
+                format!("error adding {} to {}", column, dest_table.name)
+            })?;
+        }
+        Some(create_temp_table_for(&ctx, &mut client, &dest_table).await?)
+    } else {
+        None
+    };
+
+    // If we're copying straight into our destination table, we can load
+    // several streams at once, each using its own connection. Upserts, merges
+    // and new partitions all need to run one stream at a time.
+    let copies_in_parallel =
+        pg_dest_args.copies_in_parallel(&if_exists, creates_partitions, max_streams);
 
-    // Insert data streams one at a time, because parallel insertion _probably_
-    // won't gain much with Postgres (but we haven't measured).
     let fut = async move {
+        if copies_in_parallel {
+            debug!(ctx.log(), "copying up to {} streams at once", max_streams);
+            data.by_ref()
+                .map_ok(|csv_stream| {
+                    let ctx = ctx.child(o!("stream" => csv_stream.name.clone()));
+                    let url = url.clone();
+                    let dest_table = dest_table.clone();
+                    async move {
+                        let mut client = connect(ctx.clone(), url).await?;
+                        let binary_stream =
+                            csv_to_binary_stream(&ctx, &dest_table, csv_stream.data)?;
+                        copy_from_stream(&ctx, &mut client, &dest_table, binary_stream)
+                            .await
+                    }
+                })
+                .try_buffer_unordered(max_streams)
+                .try_collect::<Vec<()>>()
+                .await?;
+        }
+
+        // Otherwise, insert data streams one at a time.
         while let Some(result) = data.next().await {
             match result {
                 Err(err) => {
@@ -265,19 +853,44 @@ pub(crate) async fn write_local_data_helper(
                 Ok(csv_stream) => {
                     let ctx = ctx.child(o!("stream" => csv_stream.name.clone()));
 
+                    // If we can't use `COPY`, fall back to `INSERT`.
+                    if pg_dest_args.write_method == WriteMethod::Insert {
+                        let (upsert_keys, update_columns) = match &if_exists {
+                            IfExists::Upsert(cols, update) => {
+                                (Some(&cols[..]), update.as_deref())
+                            }
+                            _ => (None, None),
+                        };
+                        insert_from_stream(
+                            &ctx,
+                            &url,
+                            &dest_table,
+                            upsert_keys,
+                            update_columns,
+                            csv_stream.data,
+                            &insert_options,
+                        )
+                        .await?;
+                        continue;
+                    }
+
                     // Convert our CSV stream into a PostgreSQL `BINARY` stream.
-                    let transform_table = dest_table.clone();
-                    let binary_stream = spawn_sync_transform(
-                        ctx.clone(),
-                        "copy_csv_to_pg_binary".to_owned(),
-                        csv_stream.data,
-                        move |_ctx, rdr, wtr| {
-                            copy_csv_to_pg_binary(&transform_table, rdr, wtr)
-                        },
-                    )?;
-
-                    // Decide whether to do an upsert or regular insert.
-                    if let IfExists::Upsert(cols) = &if_exists {
+                    let binary_stream =
+                        csv_to_binary_stream(&ctx, &dest_table, csv_stream.data)?;
+
+                    // Decide whether we need to go through a temporary table,
+                    // either to merge, upsert or to create partitions.
+                    if let Some(merge_table) = &merge_table {
+                        // We'll merge these rows once we've seen all our
+                        // streams.
+                        copy_from_stream(
+                            &ctx,
+                            &mut client,
+                            merge_table,
+                            binary_stream,
+                        )
+                        .await?;
+                    } else if if_exists.is_upsert() || creates_partitions {
                         // Create temp table.
                         let temp_table =
                             create_temp_table_for(&ctx, &mut client, &dest_table)
@@ -292,15 +905,33 @@ pub(crate) async fn write_local_data_helper(
                         )
                         .await?;
 
-                        // Upsert from temp table into dest.
-                        upsert_from(
-                            &ctx,
-                            &mut client,
-                            &temp_table,
-                            &dest_table,
-                            &cols,
-                        )
-                        .await?;
+                        // Make sure we have partitions for all our rows.
+                        if let Some(partitioning) = &partitioning {
+                            partitioning
+                                .create_partitions_for(
+                                    &ctx,
+                                    &mut client,
+                                    &temp_table,
+                                    &dest_table,
+                                )
+                                .await?;
+                        }
+
+                        // Upsert or insert from temp table into dest.
+                        if let IfExists::Upsert(cols, update) = &if_exists {
+                            upsert_from(
+                                &ctx,
+                                &mut client,
+                                &temp_table,
+                                &dest_table,
+                                cols,
+                                update.as_deref(),
+                            )
+                            .await?;
+                        } else {
+                            insert_from(&ctx, &mut client, &temp_table, &dest_table)
+                                .await?;
+                        }
 
                         // Delete temp table (which always exists, but we can
                         // re-use this function).
@@ -318,7 +949,169 @@ pub(crate) async fn write_local_data_helper(
                 }
             }
         }
+
+        // Merge everything we loaded.
+        if let (Some(merge_table), IfExists::Merge(keys, delete)) =
+            (&merge_table, &if_exists)
+        {
+            if let Some(partitioning) = &partitioning {
+                partitioning
+                    .create_partitions_for(&ctx, &mut client, merge_table, &dest_table)
+                    .await?;
+            }
+            merge_from(&ctx, &mut client, merge_table, &dest_table, keys, delete)
+                .await?;
+            drop_table_if_exists(&ctx, &mut client, merge_table).await?;
+        }
+
+        // Create indexes after loading our data, because it's faster to build
+        // them all at once.
+        create_indexes(
+            &ctx,
+            &mut client,
+            &src_table_name,
+            &dest_table.name,
+            &indexes,
+        )
+        .await?;
         Ok(dest.boxed())
     };
     Ok(box_stream_once(Ok(fut.boxed())))
 }
+
+/// Describe what `write_local_data_helper` would do, without connecting to the
+/// database except to look up any existing destination table.
+pub(crate) async fn plan_write_helper(
+    dest: PostgresLocator,
+    shared_args: SharedArguments<Unverified>,
+    dest_args: DestinationArguments<Unverified>,
+) -> Result<Option<Vec<String>>> {
+    let shared_args = shared_args.verify(PostgresLocator::features())?;
+    let dest_args = dest_args.verify(PostgresLocator::features())?;
+
+    // Look up our arguments.
+    let schema = shared_args.schema();
+    let if_exists = dest_args.if_exists().to_owned();
+    let pg_dest_args = dest_args
+        .driver_args()
+        .deserialize::<PostgresDestinationArguments>()
+        .context("could not parse --to-arg")?;
+    let insert_options = pg_dest_args.insert_options()?;
+    let partitioning = pg_dest_args.partitioning()?;
+    pg_dest_args.check_if_exists(&if_exists)?;
+    let table_name = dest.table_name()?.to_owned();
+
+    let mut plan = vec![];
+    if EvolveSchema::adds_columns(pg_dest_args.evolve_schema, &if_exists) {
+        plan.push(format!(
+            "add any missing columns to {}",
+            TableName(&table_name),
+        ));
+    }
+
+    // Prepare our destination table.
+    let mut dest_table = PgCreateTable::from_pg_catalog_or_default(
+        CheckCatalog::from(&if_exists),
+        dest.url(),
+        &table_name,
+        schema,
+    )
+    .await?;
+    if let IfExists::Overwrite = if_exists {
+        plan.push(format!("DROP TABLE IF EXISTS {}", TableName(&table_name)));
+    }
+    set_if_not_exists(&mut dest_table, &if_exists);
+    match &partitioning {
+        Some(partitioning) => {
+            plan.push(partitioning.create_table_sql(&dest_table)?);
+            plan.push(partitioning.create_default_partition_sql(&dest_table)?);
+        }
+        None => plan.push(dest_table.to_string()),
+    }
+    let creates_partitions = partitioning
+        .as_ref()
+        .map(|p| p.creates_partitions())
+        .unwrap_or(false);
+
+    // Describe how we'll load each stream.
+    if pg_dest_args.write_method == WriteMethod::Insert {
+        plan.push(format!(
+            "for each stream: INSERT INTO {} in batches of {} rows, {} at a time",
+            TableName(&table_name),
+            insert_options.batch_size,
+            insert_options.concurrency,
+        ));
+    } else if let IfExists::Merge(keys, delete) = &if_exists {
+        let temp_table = temp_table_for(&dest_table)?;
+        if let MergeDelete::SoftDelete(column) = delete {
+            check_soft_delete_column(&dest_table, column)?;
+            plan.push(add_soft_delete_column_sql(&dest_table, column));
+        }
+        plan.push(temp_table.to_string());
+        plan.push(format!(
+            "for each stream: {}",
+            copy_from_sql(&temp_table, "BINARY")?,
+        ));
+        if creates_partitions {
+            plan.push(format!(
+                "create any partitions of {} needed for the merged rows",
+                TableName(&table_name),
+            ));
+        }
+        plan.extend(merge_sql(&temp_table, &dest_table, keys, delete)?);
+        plan.push(format!(
+            "DROP TABLE IF EXISTS {}",
+            TableName(&temp_table.name),
+        ));
+    } else if if_exists.is_upsert() || creates_partitions {
+        let temp_table = temp_table_for(&dest_table)?;
+        plan.push(format!("for each stream: {}", temp_table));
+        plan.push(format!(
+            "for each stream: {}",
+            copy_from_sql(&temp_table, "BINARY")?,
+        ));
+        if creates_partitions {
+            plan.push(format!(
+                "for each stream: create any partitions of {} needed for its rows",
+                TableName(&table_name),
+            ));
+        }
+        if let IfExists::Upsert(cols, update) = &if_exists {
+            plan.push(format!(
+                "for each stream: {}",
+                upsert_sql(&temp_table, &dest_table, cols, update.as_deref())?.trim(),
+            ));
+        } else {
+            plan.push(format!(
+                "for each stream: {}",
+                insert_from_sql(&temp_table, &dest_table),
+            ));
+        }
+        plan.push(format!(
+            "for each stream: DROP TABLE IF EXISTS {}",
+            TableName(&temp_table.name),
+        ));
+    } else if pg_dest_args.copies_in_parallel(
+        &if_exists,
+        creates_partitions,
+        shared_args.max_streams(),
+    ) {
+        plan.push(format!(
+            "for each stream, {} at a time using separate connections: {}",
+            shared_args.max_streams(),
+            copy_from_sql(&dest_table, "BINARY")?,
+        ));
+    } else {
+        plan.push(format!(
+            "for each stream: {}",
+            copy_from_sql(&dest_table, "BINARY")?,
+        ));
+    }
+
+    if pg_dest_args.create_indexes {
+        for index in &schema.indexes {
+            plan.push(PgCreateIndex::from_index(&table_name, index).to_string());
+        }
+    }
+    Ok(Some(plan))
+}
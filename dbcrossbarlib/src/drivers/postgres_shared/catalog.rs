@@ -9,14 +9,17 @@ use diesel::{
     dsl::count_star,
     pg::PgConnection,
     prelude::*,
-    sql_function,
-    sql_types::{Integer, Text},
+    sql_function, sql_query,
+    sql_types::{BigInt, Bool, Integer, Text},
+    QueryableByName,
 };
 use std::collections::HashMap;
 
-use super::{PgColumn, PgCreateTable, PgDataType, PgScalarDataType};
+use super::{
+    table::parse_column_default, PgColumn, PgCreateTable, PgDataType, PgScalarDataType,
+};
 use crate::common::*;
-use crate::schema::Srid;
+use crate::schema::{Collation, DecimalPrecision, Index, Srid};
 
 sql_function! {
     /// Given the PostgreSQL schema name, table name and column name of a
@@ -44,8 +47,30 @@ table! {
         ordinal_position -> Integer,
         is_nullable -> VarChar,
         data_type -> VarChar,
+        character_maximum_length -> Nullable<Integer>,
+        numeric_precision -> Nullable<Integer>,
+        numeric_scale -> Nullable<Integer>,
+        udt_schema -> VarChar,
+        udt_name -> VarChar,
+        column_default -> Nullable<VarChar>,
+        collation_name -> Nullable<VarChar>,
+    }
+}
+
+table! {
+    // https://www.postgresql.org/docs/10/infoschema-attributes.html
+    information_schema.attributes (udt_schema, udt_name, attribute_name) {
         udt_schema -> VarChar,
         udt_name -> VarChar,
+        attribute_name -> VarChar,
+        ordinal_position -> Integer,
+        is_nullable -> VarChar,
+        data_type -> VarChar,
+        character_maximum_length -> Nullable<Integer>,
+        numeric_precision -> Nullable<Integer>,
+        numeric_scale -> Nullable<Integer>,
+        attribute_udt_schema -> VarChar,
+        attribute_udt_name -> VarChar,
     }
 }
 
@@ -59,15 +84,239 @@ struct PgColumnSchema {
     ordinal_position: i32,
     is_nullable: String,
     data_type: String,
+    character_maximum_length: Option<i32>,
+    numeric_precision: Option<i32>,
+    numeric_scale: Option<i32>,
     udt_schema: String,
     udt_name: String,
+    column_default: Option<String>,
+    collation_name: Option<String>,
 }
 
 impl PgColumnSchema {
     /// Get the data type for a column.
     fn data_type(&self) -> Result<PgDataType> {
+        // `numeric` columns declared with an explicit precision keep it.
+        if let ("numeric", Some(precision)) =
+            (self.data_type.as_str(), self.numeric_precision)
+        {
+            let scale = self.numeric_scale.unwrap_or(0);
+            let precision =
+                DecimalPrecision::new(cast::u32(precision)?, cast::u32(scale)?)?;
+            return Ok(PgDataType::Scalar(PgScalarDataType::NumericWithPrecision(
+                precision,
+            )));
+        }
+        // So do `character varying(n)` and `character(n)` columns.
+        if let ("character varying", Some(len)) | ("character", Some(len)) =
+            (self.data_type.as_str(), self.character_maximum_length)
+        {
+            return Ok(PgDataType::Scalar(PgScalarDataType::Varchar(cast::u32(
+                len,
+            )?)));
+        }
         pg_data_type(&self.data_type, &self.udt_schema, &self.udt_name)
     }
+
+    /// Get the collation for a column, if it isn't the default. We treat
+    /// `citext` columns as case-insensitive text.
+    fn collation(&self) -> Option<Collation> {
+        match (self.udt_name.as_str(), &self.collation_name) {
+            ("citext", _) | ("_citext", _) => Some(Collation::CaseInsensitive),
+            (_, Some(name)) if name != "default" => {
+                Some(Collation::Named(name.to_owned()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// An attribute of a PostgreSQL composite type.
+#[derive(Queryable)]
+struct PgAttributeSchema {
+    udt_schema: String,
+    udt_name: String,
+    attribute_name: String,
+    ordinal_position: i32,
+    is_nullable: String,
+    data_type: String,
+    character_maximum_length: Option<i32>,
+    numeric_precision: Option<i32>,
+    numeric_scale: Option<i32>,
+    attribute_udt_schema: String,
+    attribute_udt_name: String,
+}
+
+impl PgAttributeSchema {
+    /// Describe this attribute as though it were a column, so that we can
+    /// look up its type the same way.
+    fn into_column_schema(self) -> PgColumnSchema {
+        PgColumnSchema {
+            table_catalog: String::new(),
+            table_schema: self.udt_schema,
+            table_name: self.udt_name,
+            column_name: self.attribute_name,
+            ordinal_position: self.ordinal_position,
+            is_nullable: self.is_nullable,
+            data_type: self.data_type,
+            character_maximum_length: self.character_maximum_length,
+            numeric_precision: self.numeric_precision,
+            numeric_scale: self.numeric_scale,
+            udt_schema: self.attribute_udt_schema,
+            udt_name: self.attribute_udt_name,
+            column_default: None,
+            collation_name: None,
+        }
+    }
+}
+
+/// If `pg_col` has a user-defined type (or an array of one), return whether
+/// it's an array, and the name of the type. Built-in types live in
+/// `pg_catalog` and are never user-defined.
+fn user_defined_type_name(pg_col: &PgColumnSchema) -> Option<(bool, &str)> {
+    // Array element types have the same name as the array type, minus a
+    // leading "_".
+    match (pg_col.data_type.as_str(), pg_col.udt_name.as_str()) {
+        ("USER-DEFINED", "citext") | ("USER-DEFINED", "geometry") => None,
+        ("USER-DEFINED", name) => Some((false, name)),
+        ("ARRAY", "_citext") => None,
+        ("ARRAY", name)
+            if pg_col.udt_schema != "pg_catalog" && name.starts_with('_') =>
+        {
+            Some((true, &name[1..]))
+        }
+        _ => None,
+    }
+}
+
+/// Wrap `ty` in an array type if `is_array` is true.
+fn scalar_or_array(is_array: bool, ty: PgScalarDataType) -> PgDataType {
+    if is_array {
+        PgDataType::Array {
+            dimension_count: 1,
+            ty,
+        }
+    } else {
+        PgDataType::Scalar(ty)
+    }
+}
+
+/// A single label of a PostgreSQL `ENUM` type.
+///
+/// We use a raw SQL query for these, because the system catalog tables aren't
+/// part of `information_schema`.
+#[derive(QueryableByName)]
+struct PgEnumLabel {
+    #[sql_type = "BigInt"]
+    type_oid: i64,
+
+    #[sql_type = "Text"]
+    label: String,
+}
+
+/// SQL used to look up the labels of an `ENUM` type, in sort order.
+const ENUM_LABELS_SQL: &str = r#"
+SELECT t.oid::bigint AS type_oid, e.enumlabel::text AS label
+FROM pg_catalog.pg_enum e
+JOIN pg_catalog.pg_type t ON e.enumtypid = t.oid
+JOIN pg_catalog.pg_namespace n ON t.typnamespace = n.oid
+WHERE n.nspname = $1 AND t.typname = $2
+ORDER BY e.enumsortorder
+"#;
+
+/// If `pg_col` is an `ENUM` type or an array of one, look up the labels of the
+/// type and return the corresponding data type.
+fn enum_data_type(
+    conn: &PgConnection,
+    pg_col: &PgColumnSchema,
+) -> Result<Option<PgDataType>> {
+    let (is_array, type_name) = match user_defined_type_name(pg_col) {
+        Some(found) => found,
+        None => return Ok(None),
+    };
+
+    let rows = sql_query(ENUM_LABELS_SQL)
+        .bind::<Text, _>(&pg_col.udt_schema)
+        .bind::<Text, _>(type_name)
+        .load::<PgEnumLabel>(conn)
+        .with_context(|_| format!("error looking up enum type {}", type_name))?;
+    let oid = match rows.first() {
+        Some(row) => cast::u32(row.type_oid)?,
+        None => return Ok(None),
+    };
+    let ty = PgScalarDataType::Enum {
+        name: format!("{}.{}", pg_col.udt_schema, type_name),
+        oid,
+        labels: rows.into_iter().map(|row| row.label).collect(),
+    };
+    Ok(Some(scalar_or_array(is_array, ty)))
+}
+
+/// If `pg_col` is a composite type or an array of one, look up the attributes
+/// of the type and return the corresponding data type.
+fn composite_data_type(
+    conn: &PgConnection,
+    pg_col: &PgColumnSchema,
+) -> Result<Option<PgDataType>> {
+    let (is_array, type_name) = match user_defined_type_name(pg_col) {
+        Some(found) => found,
+        None => return Ok(None),
+    };
+
+    let pg_attributes = attributes::table
+        .filter(attributes::udt_schema.eq(&pg_col.udt_schema))
+        .filter(attributes::udt_name.eq(type_name))
+        .order(attributes::ordinal_position)
+        .load::<PgAttributeSchema>(conn)
+        .with_context(|_| format!("error looking up composite type {}", type_name))?;
+    if pg_attributes.is_empty() {
+        return Ok(None);
+    }
+    let fields = pg_attributes
+        .into_iter()
+        .map(|attr| {
+            let attr = attr.into_column_schema();
+            Ok(PgColumn {
+                data_type: column_data_type(conn, &attr).with_context(|_| {
+                    format!("error in composite type {}", type_name)
+                })?,
+                is_nullable: parse_is_nullable(&attr.is_nullable)?,
+                name: attr.column_name,
+                default: None,
+                comment: None,
+                collation: None,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let ty = PgScalarDataType::Composite {
+        name: format!("{}.{}", pg_col.udt_schema, type_name),
+        fields,
+    };
+    Ok(Some(scalar_or_array(is_array, ty)))
+}
+
+/// Get the data type of `pg_col`, looking up any user-defined types we
+/// understand.
+fn column_data_type(
+    conn: &PgConnection,
+    pg_col: &PgColumnSchema,
+) -> Result<PgDataType> {
+    if let Some(data_type) = enum_data_type(conn, pg_col)? {
+        Ok(data_type)
+    } else if let Some(data_type) = composite_data_type(conn, pg_col)? {
+        Ok(data_type)
+    } else {
+        pg_col.data_type()
+    }
+}
+
+/// Parse an `is_nullable` value from `information_schema`.
+fn parse_is_nullable(is_nullable: &str) -> Result<bool> {
+    match is_nullable {
+        "YES" => Ok(true),
+        "NO" => Ok(false),
+        value => Err(format_err!("Unexpected is_nullable value: {:?}", value)),
+    }
 }
 
 /// Fetch information about a table from the database.
@@ -92,6 +341,7 @@ pub(crate) fn fetch_from_url(
     }
 
     // Look up column information.
+    let mut comments = fetch_column_comments(&conn, table_schema, table_name)?;
     let pg_columns = columns::table
         .filter(columns::table_schema.eq(table_schema))
         .filter(columns::table_name.eq(table_name))
@@ -138,34 +388,186 @@ pub(crate) fn fetch_from_url(
         let data_type = if let Some(srid) = srid_map.get(&pg_col.column_name) {
             PgDataType::Scalar(PgScalarDataType::Geometry(*srid))
         } else {
-            pg_col.data_type()?
+            column_data_type(&conn, &pg_col)?
         };
 
         // Build our column.
         columns.push(PgColumn {
+            is_nullable: parse_is_nullable(&pg_col.is_nullable)?,
+            default: pg_col
+                .column_default
+                .as_ref()
+                .and_then(|expr| parse_column_default(expr)),
+            comment: comments.remove(&pg_col.column_name),
+            collation: pg_col.collation(),
             name: pg_col.column_name,
             data_type,
-            is_nullable: match pg_col.is_nullable.as_str() {
-                "YES" => true,
-                "NO" => false,
-                value => {
-                    return Err(format_err!(
-                        "Unexpected is_nullable value: {:?}",
-                        value,
-                    ));
-                }
-            },
         })
     }
 
     Ok(Some(PgCreateTable {
         name: full_table_name.to_owned(),
         columns,
+        primary_key: fetch_primary_key(&conn, table_schema, table_name)?,
         temporary: false,
         if_not_exists: false,
     }))
 }
 
+/// A column in a table's primary key.
+#[derive(QueryableByName)]
+struct PgKeyColumn {
+    #[sql_type = "Text"]
+    column_name: String,
+}
+
+/// SQL used to look up the primary key columns of a table, in order.
+const PRIMARY_KEY_SQL: &str = r#"
+SELECT kcu.column_name::text AS column_name
+FROM information_schema.table_constraints tc
+JOIN information_schema.key_column_usage kcu
+  ON kcu.constraint_schema = tc.constraint_schema
+  AND kcu.constraint_name = tc.constraint_name
+  AND kcu.table_schema = tc.table_schema
+  AND kcu.table_name = tc.table_name
+WHERE tc.constraint_type = 'PRIMARY KEY'
+  AND tc.table_schema = $1
+  AND tc.table_name = $2
+ORDER BY kcu.ordinal_position
+"#;
+
+/// Look up the names of the primary key columns of a table, or an empty list
+/// if it has no primary key.
+fn fetch_primary_key(
+    conn: &PgConnection,
+    table_schema: &str,
+    table_name: &str,
+) -> Result<Vec<String>> {
+    let rows = sql_query(PRIMARY_KEY_SQL)
+        .bind::<Text, _>(table_schema)
+        .bind::<Text, _>(table_name)
+        .load::<PgKeyColumn>(conn)
+        .with_context(|_| format!("error looking up primary key of {}", table_name))?;
+    Ok(rows.into_iter().map(|row| row.column_name).collect())
+}
+
+/// A comment on a column.
+#[derive(QueryableByName)]
+struct PgColumnComment {
+    #[sql_type = "Text"]
+    column_name: String,
+
+    #[sql_type = "Text"]
+    description: String,
+}
+
+/// SQL used to look up the comments on a table's columns. We query
+/// `pg_description` directly, because Redshift doesn't have `col_description`.
+const COLUMN_COMMENTS_SQL: &str = r#"
+SELECT
+    a.attname::text AS column_name,
+    d.description::text AS description
+FROM pg_catalog.pg_attribute a
+JOIN pg_catalog.pg_class c ON c.oid = a.attrelid
+JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace
+JOIN pg_catalog.pg_description d
+  ON d.objoid = c.oid
+  AND d.objsubid = a.attnum
+WHERE n.nspname = $1
+  AND c.relname = $2
+  AND a.attnum > 0
+"#;
+
+/// Look up the comments on a table's columns, indexed by column name.
+fn fetch_column_comments(
+    conn: &PgConnection,
+    table_schema: &str,
+    table_name: &str,
+) -> Result<HashMap<String, String>> {
+    let rows = sql_query(COLUMN_COMMENTS_SQL)
+        .bind::<Text, _>(table_schema)
+        .bind::<Text, _>(table_name)
+        .load::<PgColumnComment>(conn)
+        .with_context(|_| {
+            format!("error looking up column comments of {}", table_name)
+        })?;
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.column_name, row.description))
+        .collect())
+}
+
+/// A column in a secondary index.
+#[derive(QueryableByName)]
+struct PgIndexColumn {
+    #[sql_type = "Text"]
+    index_name: String,
+
+    #[sql_type = "Bool"]
+    is_unique: bool,
+
+    #[sql_type = "Text"]
+    column_name: String,
+}
+
+/// SQL used to look up the columns of a table's secondary indexes, in order.
+/// We skip the primary key, because we handle that separately, and we skip
+/// anything that we can't represent portably: indexes on expressions, partial
+/// indexes, and anything other than a plain `btree` index.
+const INDEX_COLUMNS_SQL: &str = r#"
+SELECT
+    i.indexname::text AS index_name,
+    ix.indisunique AS is_unique,
+    a.attname::text AS column_name
+FROM pg_catalog.pg_indexes i
+JOIN pg_catalog.pg_namespace n ON n.nspname = i.schemaname
+JOIN pg_catalog.pg_class c ON c.relname = i.indexname AND c.relnamespace = n.oid
+JOIN pg_catalog.pg_am am ON am.oid = c.relam
+JOIN pg_catalog.pg_index ix ON ix.indexrelid = c.oid
+JOIN LATERAL unnest(ix.indkey::int2[]) WITH ORDINALITY AS k(attnum, position)
+  ON k.position <= ix.indnkeyatts
+JOIN pg_catalog.pg_attribute a
+  ON a.attrelid = ix.indrelid AND a.attnum = k.attnum
+WHERE i.schemaname = $1
+  AND i.tablename = $2
+  AND am.amname = 'btree'
+  AND NOT ix.indisprimary
+  AND ix.indexprs IS NULL
+  AND ix.indpred IS NULL
+ORDER BY i.indexname, k.position
+"#;
+
+/// Fetch the secondary indexes of a table from the database.
+pub(crate) fn fetch_indexes_from_url(
+    database_url: &Url,
+    full_table_name: &str,
+) -> Result<Vec<Index>> {
+    let conn = PgConnection::establish(database_url.as_str())
+        .context("error connecting to PostgreSQL")?;
+    let (table_schema, table_name) = parse_full_table_name(full_table_name);
+    let rows = sql_query(INDEX_COLUMNS_SQL)
+        .bind::<Text, _>(table_schema)
+        .bind::<Text, _>(table_name)
+        .load::<PgIndexColumn>(&conn)
+        .with_context(|_| format!("error looking up indexes of {}", table_name))?;
+
+    // Group our columns by index. Our rows are sorted by index name.
+    let mut indexes: Vec<Index> = vec![];
+    for row in rows {
+        match indexes.last_mut() {
+            Some(index) if index.name == row.index_name => {
+                index.columns.push(row.column_name)
+            }
+            _ => indexes.push(Index {
+                name: row.index_name,
+                columns: vec![row.column_name],
+                is_unique: row.is_unique,
+            }),
+        }
+    }
+    Ok(indexes)
+}
+
 /// Given a name of the form `mytable` or `myschema.mytable`, split it into
 /// a `table_schema` and `table_name`.
 fn parse_full_table_name(full_table_name: &str) -> (&str, &str) {
@@ -194,13 +596,19 @@ fn pg_data_type(
         // base types.
         let element_type = match udt_name {
             "_bool" => PgScalarDataType::Boolean,
+            "_bytea" => PgScalarDataType::Bytea,
+            "_cidr" => PgScalarDataType::Cidr,
             "_date" => PgScalarDataType::Date,
             "_float4" => PgScalarDataType::Real,
             "_float8" => PgScalarDataType::DoublePrecision,
+            "_inet" => PgScalarDataType::Inet,
             "_int2" => PgScalarDataType::Smallint,
             "_int4" => PgScalarDataType::Int,
             "_int8" => PgScalarDataType::Bigint,
-            "_text" => PgScalarDataType::Text,
+            "_interval" => PgScalarDataType::Interval,
+            "_macaddr" => PgScalarDataType::Macaddr,
+            "_text" | "_citext" => PgScalarDataType::Text,
+            "_time" => PgScalarDataType::TimeWithoutTimeZone,
             "_timestamp" => PgScalarDataType::TimestampWithoutTimeZone,
             "_timestamptz" => PgScalarDataType::TimestampWithTimeZone,
             "_uuid" => PgScalarDataType::Uuid,
@@ -223,17 +631,33 @@ fn pg_data_type(
         let ty = match data_type {
             "bigint" => Ok(PgScalarDataType::Bigint),
             "boolean" => Ok(PgScalarDataType::Boolean),
+            "bytea" => Ok(PgScalarDataType::Bytea),
             "character" => Ok(PgScalarDataType::Text),
+            "cidr" => Ok(PgScalarDataType::Cidr),
             "character varying" => Ok(PgScalarDataType::Text),
             "date" => Ok(PgScalarDataType::Date),
             "double precision" => Ok(PgScalarDataType::DoublePrecision),
+            "inet" => Ok(PgScalarDataType::Inet),
             "integer" => Ok(PgScalarDataType::Int),
+            "interval" => Ok(PgScalarDataType::Interval),
             "json" => Ok(PgScalarDataType::Json),
             "jsonb" => Ok(PgScalarDataType::Jsonb),
+            "macaddr" => Ok(PgScalarDataType::Macaddr),
             "numeric" => Ok(PgScalarDataType::Numeric),
             "real" => Ok(PgScalarDataType::Real),
+            "daterange" => Ok(range("daterange", PgScalarDataType::Date)),
+            "int4range" => Ok(range("int4range", PgScalarDataType::Int)),
+            "int8range" => Ok(range("int8range", PgScalarDataType::Bigint)),
+            "numrange" => Ok(range("numrange", PgScalarDataType::Numeric)),
+            "tsrange" => {
+                Ok(range("tsrange", PgScalarDataType::TimestampWithoutTimeZone))
+            }
+            "tstzrange" => {
+                Ok(range("tstzrange", PgScalarDataType::TimestampWithTimeZone))
+            }
             "smallint" => Ok(PgScalarDataType::Smallint),
             "text" => Ok(PgScalarDataType::Text),
+            "time without time zone" => Ok(PgScalarDataType::TimeWithoutTimeZone),
             "timestamp with time zone" => Ok(PgScalarDataType::TimestampWithTimeZone),
             "timestamp without time zone" => {
                 Ok(PgScalarDataType::TimestampWithoutTimeZone)
@@ -245,6 +669,14 @@ fn pg_data_type(
     }
 }
 
+/// Build a range type named `name` over `subtype`.
+fn range(name: &str, subtype: PgScalarDataType) -> PgScalarDataType {
+    PgScalarDataType::Range {
+        name: name.to_owned(),
+        subtype: Box::new(subtype),
+    }
+}
+
 #[test]
 fn parsing_pg_data_type() {
     let array = |ty| PgDataType::Array {
@@ -273,10 +705,26 @@ fn parsing_pg_data_type() {
             ("double precision", "pg_catalog", "float8"),
             PgDataType::Scalar(PgScalarDataType::DoublePrecision),
         ),
+        (
+            ("cidr", "pg_catalog", "cidr"),
+            PgDataType::Scalar(PgScalarDataType::Cidr),
+        ),
+        (
+            ("inet", "pg_catalog", "inet"),
+            PgDataType::Scalar(PgScalarDataType::Inet),
+        ),
+        (
+            ("macaddr", "pg_catalog", "macaddr"),
+            PgDataType::Scalar(PgScalarDataType::Macaddr),
+        ),
         (
             ("integer", "pg_catalog", "int4"),
             PgDataType::Scalar(PgScalarDataType::Int),
         ),
+        (
+            ("interval", "pg_catalog", "interval"),
+            PgDataType::Scalar(PgScalarDataType::Interval),
+        ),
         (
             ("json", "pg_catalog", "json"),
             PgDataType::Scalar(PgScalarDataType::Json),
@@ -285,6 +733,17 @@ fn parsing_pg_data_type() {
             ("jsonb", "pg_catalog", "jsonb"),
             PgDataType::Scalar(PgScalarDataType::Jsonb),
         ),
+        (
+            ("int4range", "pg_catalog", "int4range"),
+            PgDataType::Scalar(range("int4range", PgScalarDataType::Int)),
+        ),
+        (
+            ("tstzrange", "pg_catalog", "tstzrange"),
+            PgDataType::Scalar(range(
+                "tstzrange",
+                PgScalarDataType::TimestampWithTimeZone,
+            )),
+        ),
         (
             ("real", "pg_catalog", "float4"),
             PgDataType::Scalar(PgScalarDataType::Real),
@@ -330,6 +789,14 @@ fn parsing_pg_data_type() {
             ("ARRAY", "pg_catalog", "_int8"),
             array(PgScalarDataType::Bigint),
         ),
+        (
+            ("ARRAY", "pg_catalog", "_inet"),
+            array(PgScalarDataType::Inet),
+        ),
+        (
+            ("ARRAY", "pg_catalog", "_interval"),
+            array(PgScalarDataType::Interval),
+        ),
         (
             ("ARRAY", "pg_catalog", "_text"),
             array(PgScalarDataType::Text),
@@ -342,6 +809,14 @@ fn parsing_pg_data_type() {
             ("ARRAY", "pg_catalog", "_timestamptz"),
             array(PgScalarDataType::TimestampWithTimeZone),
         ),
+        (
+            ("time without time zone", "pg_catalog", "time"),
+            PgDataType::Scalar(PgScalarDataType::TimeWithoutTimeZone),
+        ),
+        (
+            ("ARRAY", "pg_catalog", "_time"),
+            array(PgScalarDataType::TimeWithoutTimeZone),
+        ),
         (
             ("ARRAY", "pg_catalog", "_uuid"),
             array(PgScalarDataType::Uuid),
@@ -2,9 +2,9 @@
 
 use std::fmt;
 
-use super::{Ident, PgDataType, PgScalarDataType};
+use super::{pg_quote, Ident, PgDataType, PgScalarDataType};
 use crate::common::*;
-use crate::schema::Column;
+use crate::schema::{Collation, Column, ColumnDefault, DataType, Srid};
 
 /// A column in a PostgreSQL table.
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -15,6 +15,14 @@ pub(crate) struct PgColumn {
     pub(crate) data_type: PgDataType,
     /// Can this column be `NULL`?
     pub(crate) is_nullable: bool,
+    /// The default value of this column, if any.
+    pub(crate) default: Option<ColumnDefault>,
+    /// A comment describing this column. This isn't part of the column
+    /// declaration, so we write it out using a separate `COMMENT ON COLUMN`.
+    pub(crate) comment: Option<String>,
+    /// The collation of this column, if it isn't the default. We represent
+    /// case-insensitive text using the `citext` extension.
+    pub(crate) collation: Option<Collation>,
 }
 
 impl PgColumn {
@@ -23,8 +31,11 @@ impl PgColumn {
         let data_type = PgDataType::from_data_type(&col.data_type)?;
         Ok(PgColumn {
             name: col.name.clone(),
+            default: scalar_default(&data_type, &col.default),
+            collation: collatable_collation(&data_type, &col.collation),
             data_type,
             is_nullable: col.is_nullable,
+            comment: col.comment.clone(),
         })
     }
 
@@ -34,10 +45,47 @@ impl PgColumn {
             name: self.name.clone(),
             data_type: self.data_type.to_data_type()?,
             is_nullable: self.is_nullable,
-            comment: None,
+            comment: self.comment.clone(),
+            default: scalar_default(&self.data_type, &self.default),
+            collation: self.collation.clone(),
         })
     }
 
+    /// Write a `SELECT` expression for this column, as it should appear in
+    /// `requested`, a column from the schema we were asked to export. If this
+    /// is a geometry column and `requested` uses a different SRID, we
+    /// reproject it using `ST_Transform`.
+    pub(crate) fn write_export_select_expr_as(
+        &self,
+        f: &mut dyn Write,
+        requested: Option<&Column>,
+    ) -> Result<()> {
+        if let Some(srid) = self.reprojected_srid(requested) {
+            write!(
+                f,
+                "ST_AsGeoJSON(ST_Transform({name}, {srid})) AS {name}",
+                name = Ident(&self.name),
+                srid = srid,
+            )?;
+            Ok(())
+        } else {
+            self.write_export_select_expr(f)
+        }
+    }
+
+    /// If we need to reproject this column to match `requested`, return the
+    /// new SRID. We can't reproject geometry columns with SRID 0, because we
+    /// don't know what their coordinates mean.
+    fn reprojected_srid(&self, requested: Option<&Column>) -> Option<Srid> {
+        match (&self.data_type, requested.map(|c| &c.data_type)) {
+            (
+                PgDataType::Scalar(PgScalarDataType::Geometry(current)),
+                Some(DataType::GeoJson(srid)),
+            ) if current != srid && current.to_u32() != 0 => Some(*srid),
+            _ => None,
+        }
+    }
+
     /// Write a `SELECT` expression for this column.
     pub(crate) fn write_export_select_expr(&self, f: &mut dyn Write) -> Result<()> {
         let name = Ident(&self.name);
@@ -90,10 +138,45 @@ impl PgColumn {
                     name = name,
                 )?;
             }
+            // `bytea` values are exported as base64, which PostgreSQL splits
+            // into lines unless we remove the newlines.
+            PgDataType::Array {
+                dimension_count,
+                ty: PgScalarDataType::Bytea,
+            } => {
+                check_dimension(*dimension_count)?;
+                write!(
+                    f,
+                    r#"(SELECT array_to_json(array_agg((elem))) FROM (SELECT translate(encode(elem, 'base64'), E'\n', '') AS elem FROM unnest({name}) AS elem) AS elems) AS {name}"#,
+                    name = name,
+                )?;
+            }
             // Regular arrays can be dumped directly.
             PgDataType::Array { .. } => {
                 write!(f, "array_to_json({name}) AS {name}", name = name)?;
             }
+            PgDataType::Scalar(PgScalarDataType::Bytea) => {
+                write!(
+                    f,
+                    r#"translate(encode({name}, 'base64'), E'\n', '') AS {name}"#,
+                    name = name,
+                )?;
+            }
+            // Composite values are exported as JSON objects.
+            PgDataType::Scalar(PgScalarDataType::Composite { .. }) => {
+                write!(f, "to_json({name}) AS {name}", name = name)?;
+            }
+            // Ranges are exported as JSON objects with the same fields as the
+            // struct we use to represent them.
+            PgDataType::Scalar(PgScalarDataType::Range { subtype, .. }) => {
+                write!(
+                    f,
+                    r#"CASE WHEN {name} IS NULL THEN NULL ELSE json_build_object('lower', {lower}, 'upper', {upper}, 'lower_inc', lower_inc({name}), 'upper_inc', upper_inc({name}), 'empty', isempty({name})) END AS {name}"#,
+                    name = name,
+                    lower = json_bound_expr(subtype, &format!("lower({})", name)),
+                    upper = json_bound_expr(subtype, &format!("upper({})", name)),
+                )?;
+            }
             PgDataType::Scalar(PgScalarDataType::Geometry(_srid)) => {
                 // TODO: This will preserve the current SRID of the column, so
                 // let's hope `_srid` matches the database's if we make it this far.
@@ -121,12 +204,100 @@ impl PgColumn {
     }
 }
 
+/// Format `expr`, a range bound of type `ty`, the way it should appear in a JSON
+/// value in our CSV interchange format.
+fn json_bound_expr(ty: &PgScalarDataType, expr: &str) -> String {
+    match ty {
+        // Like other `bigint` values in JSON, we export these as strings.
+        PgScalarDataType::Bigint => format!("{}::text", expr),
+        PgScalarDataType::TimestampWithoutTimeZone => format!(
+            r#"TRIM(TRAILING '.' FROM TRIM(TRAILING '0' FROM to_char({}, 'YYYY-MM-DD"T"HH24:MI:SS.US')))"#,
+            expr,
+        ),
+        PgScalarDataType::TimestampWithTimeZone => format!(
+            r#"TRIM(TRAILING '.' FROM TRIM(TRAILING '0' FROM to_char({} AT TIME ZONE 'UTC', 'YYYY-MM-DD"T"HH24:MI:SS.US'))) || 'Z'"#,
+            expr,
+        ),
+        _ => expr.to_owned(),
+    }
+}
+
+/// Return `default` if we can use it with `data_type`. Array literals are
+/// written differently by PostgreSQL and our CSV interchange format, so we only
+/// carry defaults for scalar columns.
+fn scalar_default(
+    data_type: &PgDataType,
+    default: &Option<ColumnDefault>,
+) -> Option<ColumnDefault> {
+    match data_type {
+        PgDataType::Scalar(_) => default.clone(),
+        PgDataType::Array { .. } => None,
+    }
+}
+
+/// Return `collation` if we can use it with `data_type`. Only text columns
+/// have collations.
+fn collatable_collation(
+    data_type: &PgDataType,
+    collation: &Option<Collation>,
+) -> Option<Collation> {
+    match data_type {
+        PgDataType::Scalar(PgScalarDataType::Text)
+        | PgDataType::Scalar(PgScalarDataType::TextOneOf(_))
+        | PgDataType::Scalar(PgScalarDataType::Varchar(_))
+        | PgDataType::Array {
+            ty: PgScalarDataType::Text,
+            ..
+        }
+        | PgDataType::Array {
+            ty: PgScalarDataType::Varchar(_),
+            ..
+        } => collation.clone(),
+        _ => None,
+    }
+}
+
 impl fmt::Display for PgColumn {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} {}", Ident(&self.name), self.data_type)?;
+        write!(f, "{} ", Ident(&self.name))?;
+        match (&self.collation, &self.data_type) {
+            (Some(Collation::CaseInsensitive), PgDataType::Array { .. }) => {
+                write!(f, "citext[]")?
+            }
+            (Some(Collation::CaseInsensitive), PgDataType::Scalar(_)) => {
+                write!(f, "citext")?
+            }
+            (Some(Collation::Named(name)), data_type) => {
+                write!(f, "{} COLLATE {}", data_type, Ident(name))?
+            }
+            (None, data_type) => write!(f, "{}", data_type)?,
+        }
+        match &self.default {
+            None => {}
+            Some(ColumnDefault::Literal(value)) => {
+                write!(f, " DEFAULT {}", pg_quote(value))?
+            }
+            Some(ColumnDefault::CurrentTimestamp) => {
+                write!(f, " DEFAULT CURRENT_TIMESTAMP")?
+            }
+        }
         if !self.is_nullable {
             write!(f, " NOT NULL")?;
         }
+        // We only restrict scalar `one_of` columns. Arrays are left as `text[]`,
+        // because there's no simple constraint that handles `NULL` elements.
+        if let PgDataType::Scalar(PgScalarDataType::TextOneOf(labels)) =
+            &self.data_type
+        {
+            write!(f, " CHECK ({} IN (", Ident(&self.name))?;
+            for (idx, label) in labels.iter().enumerate() {
+                if idx > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", pg_quote(label))?;
+            }
+            write!(f, "))")?;
+        }
         Ok(())
     }
 }
@@ -0,0 +1,31 @@
+//! PostgreSQL column comments.
+
+use std::fmt;
+
+use super::{pg_quote, Ident, TableName};
+
+/// A PostgreSQL `COMMENT ON COLUMN` statement.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct PgCommentOnColumn {
+    /// The table containing the column.
+    pub(crate) table_name: String,
+    /// The column to comment on.
+    pub(crate) column_name: String,
+    /// The comment, or `None` to remove an existing comment.
+    pub(crate) comment: Option<String>,
+}
+
+impl fmt::Display for PgCommentOnColumn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "COMMENT ON COLUMN {}.{} IS ",
+            TableName(&self.table_name),
+            Ident(&self.column_name),
+        )?;
+        match &self.comment {
+            Some(comment) => writeln!(f, "{};", pg_quote(comment)),
+            None => writeln!(f, "NULL;"),
+        }
+    }
+}
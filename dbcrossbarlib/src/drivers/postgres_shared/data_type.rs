@@ -2,8 +2,9 @@
 
 use std::fmt;
 
+use super::{PgColumn, TableName};
 use crate::common::*;
-use crate::schema::{DataType, Srid};
+use crate::schema::{DataType, DecimalPrecision, Srid, StructField};
 
 /// A native PostgreSQL data type.
 ///
@@ -114,20 +115,56 @@ impl fmt::Display for PgDataType {
 #[allow(missing_docs)]
 pub(crate) enum PgScalarDataType {
     Boolean,
+    Bytea,
     Date,
     Numeric,
+    NumericWithPrecision(DecimalPrecision),
     Real,
     DoublePrecision,
+    Cidr,
+    /// A named composite type which already exists in the database.
+    Composite {
+        /// The type name, including the namespace.
+        name: String,
+        /// The attributes of the type, in order.
+        fields: Vec<PgColumn>,
+    },
+    /// A named `ENUM` type which already exists in the database.
+    Enum {
+        /// The type name, including the namespace.
+        name: String,
+        /// The OID of the type, which we need when writing binary arrays.
+        oid: u32,
+        /// The allowed labels, in sort order.
+        labels: Vec<String>,
+    },
     Geometry(Srid),
+    Inet,
     Smallint,
     Int,
     Bigint,
+    Interval,
     Json,
     Jsonb,
+    Macaddr,
+    /// A built-in range type, like `int4range` or `tstzrange`.
+    Range {
+        /// The name of the range type.
+        name: String,
+        /// The type of the range's bounds.
+        subtype: Box<PgScalarDataType>,
+    },
     Text,
+    /// A `text` column restricted to the specified labels using a `CHECK`
+    /// constraint. We use this instead of `Enum` when creating tables, so that
+    /// we don't need to create any named types.
+    TextOneOf(Vec<String>),
+    TimeWithoutTimeZone,
     TimestampWithoutTimeZone,
     TimestampWithTimeZone,
     Uuid,
+    /// A `character varying(n)` column, with a maximum length in characters.
+    Varchar(u32),
 }
 
 impl PgScalarDataType {
@@ -139,17 +176,34 @@ impl PgScalarDataType {
                 unreachable!("should have been handled by PgDataType::from_data_type")
             }
             DataType::Bool => Ok(PgScalarDataType::Boolean),
+            DataType::Bytes => Ok(PgScalarDataType::Bytea),
+            DataType::Cidr => Ok(PgScalarDataType::Cidr),
             DataType::Date => Ok(PgScalarDataType::Date),
             DataType::Decimal => Ok(PgScalarDataType::Numeric),
+            DataType::DecimalWithPrecision(p) => {
+                Ok(PgScalarDataType::NumericWithPrecision(*p))
+            }
             DataType::Float32 => Ok(PgScalarDataType::Real),
             DataType::Float64 => Ok(PgScalarDataType::DoublePrecision),
             DataType::GeoJson(srid) => Ok(PgScalarDataType::Geometry(*srid)),
+            DataType::Inet => Ok(PgScalarDataType::Inet),
             DataType::Int16 => Ok(PgScalarDataType::Smallint),
             DataType::Int32 => Ok(PgScalarDataType::Int),
             DataType::Int64 => Ok(PgScalarDataType::Bigint),
+            DataType::Interval => Ok(PgScalarDataType::Interval),
             DataType::Json => Ok(PgScalarDataType::Jsonb),
+            DataType::MacAddr => Ok(PgScalarDataType::Macaddr),
+            DataType::OneOf(labels) if labels.is_empty() => {
+                Err(format_err!("one_of types must have at least one label"))
+            }
+            DataType::OneOf(labels) => Ok(PgScalarDataType::TextOneOf(labels.clone())),
             DataType::Other(_) => Ok(PgScalarDataType::Text),
+            // We don't create composite types, so store structs as JSON
+            // objects.
+            DataType::Struct(_) => Ok(PgScalarDataType::Jsonb),
             DataType::Text => Ok(PgScalarDataType::Text),
+            DataType::TextWithMaxLength(len) => Ok(PgScalarDataType::Varchar(*len)),
+            DataType::Time => Ok(PgScalarDataType::TimeWithoutTimeZone),
             DataType::TimestampWithoutTimeZone => {
                 Ok(PgScalarDataType::TimestampWithoutTimeZone)
             }
@@ -164,16 +218,58 @@ impl PgScalarDataType {
     pub(crate) fn to_data_type(&self) -> Result<DataType> {
         match self {
             PgScalarDataType::Boolean => Ok(DataType::Bool),
+            PgScalarDataType::Bytea => Ok(DataType::Bytes),
             PgScalarDataType::Date => Ok(DataType::Date),
             PgScalarDataType::Numeric => Ok(DataType::Decimal),
+            PgScalarDataType::NumericWithPrecision(p) => {
+                Ok(DataType::DecimalWithPrecision(*p))
+            }
             PgScalarDataType::Real => Ok(DataType::Float32),
             PgScalarDataType::DoublePrecision => Ok(DataType::Float64),
+            PgScalarDataType::Cidr => Ok(DataType::Cidr),
+            PgScalarDataType::Composite { fields, .. } => Ok(DataType::Struct(
+                fields
+                    .iter()
+                    .map(|f| {
+                        Ok(StructField {
+                            name: f.name.clone(),
+                            is_nullable: f.is_nullable,
+                            data_type: f.data_type.to_data_type()?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?,
+            )),
+            PgScalarDataType::Enum { labels, .. }
+            | PgScalarDataType::TextOneOf(labels) => {
+                Ok(DataType::OneOf(labels.clone()))
+            }
             PgScalarDataType::Geometry(srid) => Ok(DataType::GeoJson(*srid)),
+            PgScalarDataType::Inet => Ok(DataType::Inet),
             PgScalarDataType::Smallint => Ok(DataType::Int16),
             PgScalarDataType::Int => Ok(DataType::Int32),
             PgScalarDataType::Bigint => Ok(DataType::Int64),
+            PgScalarDataType::Interval => Ok(DataType::Interval),
             PgScalarDataType::Jsonb | PgScalarDataType::Json => Ok(DataType::Json),
+            PgScalarDataType::Macaddr => Ok(DataType::MacAddr),
+            // We don't have a portable range type, so we represent ranges as
+            // structs, with `NULL` bounds for unbounded or empty ranges.
+            PgScalarDataType::Range { subtype, .. } => {
+                let bound_type = subtype.to_data_type()?;
+                let field = |name: &str, data_type: &DataType| StructField {
+                    name: name.to_owned(),
+                    is_nullable: true,
+                    data_type: data_type.to_owned(),
+                };
+                Ok(DataType::Struct(vec![
+                    field("lower", &bound_type),
+                    field("upper", &bound_type),
+                    field("lower_inc", &DataType::Bool),
+                    field("upper_inc", &DataType::Bool),
+                    field("empty", &DataType::Bool),
+                ]))
+            }
             PgScalarDataType::Text => Ok(DataType::Text),
+            PgScalarDataType::TimeWithoutTimeZone => Ok(DataType::Time),
             PgScalarDataType::TimestampWithoutTimeZone => {
                 Ok(DataType::TimestampWithoutTimeZone)
             }
@@ -181,6 +277,7 @@ impl PgScalarDataType {
                 Ok(DataType::TimestampWithTimeZone)
             }
             PgScalarDataType::Uuid => Ok(DataType::Uuid),
+            PgScalarDataType::Varchar(len) => Ok(DataType::TextWithMaxLength(*len)),
         }
     }
 
@@ -190,22 +287,42 @@ impl PgScalarDataType {
     pub(crate) fn oid(&self) -> Result<i32> {
         match self {
             PgScalarDataType::Boolean => Ok(16),
+            PgScalarDataType::Bytea => Ok(17),
             PgScalarDataType::Date => Ok(1082),
-            PgScalarDataType::Numeric => Ok(1700),
+            PgScalarDataType::Numeric | PgScalarDataType::NumericWithPrecision(_) => {
+                Ok(1700)
+            }
             PgScalarDataType::Real => Ok(700),
             PgScalarDataType::DoublePrecision => Ok(701),
+            PgScalarDataType::Cidr => Ok(650),
+            PgScalarDataType::Composite { name, .. } => Err(format_err!(
+                "cannot write composite type {} in binary format",
+                name,
+            )),
+            // OIDs are unsigned, but the binary format stores them as 32-bit
+            // integers, so we just want the same bits.
+            PgScalarDataType::Enum { oid, .. } => Ok(*oid as i32),
             PgScalarDataType::Geometry(_) => Err(format_err!(
                 "don't know the PostgreSQL OID for type `geometry`"
             )),
+            PgScalarDataType::Inet => Ok(869),
             PgScalarDataType::Smallint => Ok(21),
             PgScalarDataType::Int => Ok(23),
             PgScalarDataType::Bigint => Ok(20),
+            PgScalarDataType::Interval => Ok(1186),
             PgScalarDataType::Json => Ok(114),
             PgScalarDataType::Jsonb => Ok(3802),
-            PgScalarDataType::Text => Ok(25),
+            PgScalarDataType::Macaddr => Ok(829),
+            PgScalarDataType::Range { name, .. } => Err(format_err!(
+                "cannot write range type {} in binary format",
+                name,
+            )),
+            PgScalarDataType::Text | PgScalarDataType::TextOneOf(_) => Ok(25),
+            PgScalarDataType::TimeWithoutTimeZone => Ok(1083),
             PgScalarDataType::TimestampWithoutTimeZone => Ok(1114),
             PgScalarDataType::TimestampWithTimeZone => Ok(1184),
             PgScalarDataType::Uuid => Ok(2950),
+            PgScalarDataType::Varchar(_) => Ok(1043),
         }
     }
 }
@@ -214,19 +331,33 @@ impl fmt::Display for PgScalarDataType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             PgScalarDataType::Boolean => write!(f, "boolean")?,
+            PgScalarDataType::Bytea => write!(f, "bytea")?,
             PgScalarDataType::Date => write!(f, "date")?,
             PgScalarDataType::Numeric => write!(f, "numeric")?,
+            PgScalarDataType::NumericWithPrecision(p) => write!(f, "numeric{}", p)?,
             PgScalarDataType::Real => write!(f, "real")?,
             PgScalarDataType::DoublePrecision => write!(f, "double precision")?,
+            PgScalarDataType::Cidr => write!(f, "cidr")?,
+            PgScalarDataType::Composite { name, .. }
+            | PgScalarDataType::Enum { name, .. } => write!(f, "{}", TableName(name))?,
             PgScalarDataType::Geometry(srid) => {
                 write!(f, "public.geometry(Geometry, {})", srid)?
             }
+            PgScalarDataType::Inet => write!(f, "inet")?,
             PgScalarDataType::Smallint => write!(f, "smallint")?,
             PgScalarDataType::Int => write!(f, "int")?,
             PgScalarDataType::Bigint => write!(f, "bigint")?,
+            PgScalarDataType::Interval => write!(f, "interval")?,
             PgScalarDataType::Json => write!(f, "json")?,
             PgScalarDataType::Jsonb => write!(f, "jsonb")?,
-            PgScalarDataType::Text => write!(f, "text")?,
+            PgScalarDataType::Macaddr => write!(f, "macaddr")?,
+            PgScalarDataType::Range { name, .. } => write!(f, "{}", name)?,
+            PgScalarDataType::Text | PgScalarDataType::TextOneOf(_) => {
+                write!(f, "text")?
+            }
+            PgScalarDataType::TimeWithoutTimeZone => {
+                write!(f, "time without time zone")?
+            }
             PgScalarDataType::TimestampWithoutTimeZone => {
                 write!(f, "timestamp without time zone")?
             }
@@ -234,6 +365,7 @@ impl fmt::Display for PgScalarDataType {
                 write!(f, "timestamp with time zone")?
             }
             PgScalarDataType::Uuid => write!(f, "uuid")?,
+            PgScalarDataType::Varchar(len) => write!(f, "character varying({})", len)?,
         }
         Ok(())
     }
@@ -0,0 +1,84 @@
+//! PostgreSQL secondary indexes.
+
+use std::fmt;
+
+use super::{catalog, Ident, TableName};
+use crate::common::*;
+use crate::schema::Index;
+use crate::separator::Separator;
+
+/// A PostgreSQL `CREATE INDEX` statement.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct PgCreateIndex {
+    /// The name of the index. PostgreSQL always creates indexes in the same
+    /// namespace as their table, so this never includes a namespace.
+    pub(crate) name: String,
+    /// The table to index.
+    pub(crate) table_name: String,
+    /// The columns to index.
+    pub(crate) columns: Vec<String>,
+    /// Should this be a `UNIQUE` index?
+    pub(crate) is_unique: bool,
+    /// Should we add an `IF NOT EXISTS` clause?
+    pub(crate) if_not_exists: bool,
+}
+
+impl PgCreateIndex {
+    /// Create a `PgCreateIndex` for a portable `Index` on `table_name`.
+    pub(crate) fn from_index(table_name: &str, index: &Index) -> PgCreateIndex {
+        PgCreateIndex {
+            name: index.name.clone(),
+            table_name: table_name.to_owned(),
+            columns: index.columns.clone(),
+            is_unique: index.is_unique,
+            if_not_exists: false,
+        }
+    }
+
+    /// Convert this index to a portable `Index`.
+    pub(crate) fn to_index(&self) -> Index {
+        Index {
+            name: self.name.clone(),
+            columns: self.columns.clone(),
+            is_unique: self.is_unique,
+        }
+    }
+}
+
+impl fmt::Display for PgCreateIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CREATE")?;
+        if self.is_unique {
+            write!(f, " UNIQUE")?;
+        }
+        write!(f, " INDEX")?;
+        if self.if_not_exists {
+            write!(f, " IF NOT EXISTS")?;
+        }
+        write!(
+            f,
+            " {} ON {} (",
+            Ident(&self.name),
+            TableName(&self.table_name),
+        )?;
+        let mut sep = Separator::new(", ");
+        for col in &self.columns {
+            write!(f, "{}{}", sep.display(), Ident(col))?;
+        }
+        writeln!(f, ");")?;
+        Ok(())
+    }
+}
+
+/// Look up the secondary indexes on `full_table_name` in the database.
+pub(crate) async fn fetch_indexes(
+    database_url: &Url,
+    full_table_name: &str,
+) -> Result<Vec<Index>> {
+    let database_url = database_url.to_owned();
+    let full_table_name = full_table_name.to_owned();
+    run_sync_fn_in_background("fetch_indexes".to_owned(), move || {
+        catalog::fetch_indexes_from_url(&database_url, &full_table_name)
+    })
+    .await
+}
@@ -6,12 +6,18 @@ use crate::common::*;
 
 mod catalog;
 mod column;
+mod comment;
 mod data_type;
+mod index;
 mod table;
 
 pub(crate) use self::column::PgColumn;
+pub(crate) use self::comment::PgCommentOnColumn;
 pub(crate) use self::data_type::{PgDataType, PgScalarDataType};
-pub(crate) use self::table::{CheckCatalog, PgCreateTable};
+pub(crate) use self::index::{fetch_indexes, PgCreateIndex};
+pub(crate) use self::table::{
+    parse_column_type, CheckCatalog, PgCreateTable, PgKeyRange,
+};
 
 /// Escape and quote a PostgreSQL string literal. See the [docs][]. We need this
 /// because PostgreSQL doesn't accept `$1`-style escapes in certain places in
@@ -1,10 +1,16 @@
 //! A PostgreSQL `CREATE TABLE` declaration.
 
-use std::{collections::HashMap, fmt, iter::FromIterator, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    fmt,
+    iter::FromIterator,
+    str::FromStr,
+};
 
-use super::{catalog, PgColumn, TableName};
+use super::{catalog, Ident, PgColumn, PgCommentOnColumn, PgCreateIndex, TableName};
 use crate::common::*;
-use crate::schema::Column;
+use crate::schema::{Collation, Column, ColumnDefault, DataType, Table};
 use crate::separator::Separator;
 
 /// Should we check the PostgreSQL catalog for a schema, or just use the one we
@@ -23,8 +29,59 @@ impl From<&IfExists> for CheckCatalog {
     fn from(if_exists: &IfExists) -> CheckCatalog {
         match if_exists {
             IfExists::Error | IfExists::Overwrite => CheckCatalog::No,
-            IfExists::Append | IfExists::Upsert(_) => CheckCatalog::Yes,
+            IfExists::Append | IfExists::Upsert(..) | IfExists::Merge(..) => {
+                CheckCatalog::Yes
+            }
+        }
+    }
+}
+
+/// A range of integer key values, used to split an export into several
+/// queries which can run at the same time.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct PgKeyRange {
+    /// The column to filter on.
+    pub(crate) column: String,
+    /// The smallest key in this range.
+    pub(crate) min: i64,
+    /// The largest key in this range.
+    pub(crate) max: i64,
+    /// Should this range also include rows where `column` is `NULL`?
+    pub(crate) include_nulls: bool,
+}
+
+impl PgKeyRange {
+    /// Split the keys from `min` to `max` into at most `count` ranges of about
+    /// the same size. The first range also includes `NULL` keys.
+    pub(crate) fn split(column: &str, min: i64, max: i64, count: usize) -> Vec<Self> {
+        // Use `i128` so that we can't overflow, even for the full `i64` range.
+        let width = i128::from(max) - i128::from(min) + 1;
+        let count = i128::try_from(count.max(1))
+            .unwrap_or(i128::MAX)
+            .min(width.max(1));
+        (0..count)
+            .map(|i| {
+                let start = i128::from(min) + width * i / count;
+                let end = i128::from(min) + width * (i + 1) / count - 1;
+                PgKeyRange {
+                    column: column.to_owned(),
+                    min: i64::try_from(start).expect("range should fit in i64"),
+                    max: i64::try_from(end).expect("range should fit in i64"),
+                    include_nulls: i == 0,
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for PgKeyRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let column = Ident(&self.column);
+        write!(f, "({} BETWEEN {} AND {}", column, self.min, self.max)?;
+        if self.include_nulls {
+            write!(f, " OR {} IS NULL", column)?;
         }
+        write!(f, ")")
     }
 }
 
@@ -41,6 +98,8 @@ pub struct PgCreateTable {
     pub(crate) name: String,
     /// The columns in the table.
     pub(crate) columns: Vec<PgColumn>,
+    /// The names of the columns in the table's primary key, if it has one.
+    pub(crate) primary_key: Vec<String>,
     /// Only create the table if it doesn't already exist.
     pub(crate) if_not_exists: bool,
     /// Create a temporary table local to a specific client session.
@@ -69,7 +128,59 @@ impl PgCreateTable {
         Ok(PgCreateTable {
             name,
             columns: pg_columns,
+            primary_key: vec![],
+            if_not_exists: false,
+            temporary: false,
+        })
+    }
+
+    /// Given a table name and a portable `Table`, construct a corresponding
+    /// `PgCreateTable`, including any primary key. We ignore `table.name` for
+    /// the reasons explained in `from_name_and_columns`.
+    pub(crate) fn from_name_and_table(
+        name: String,
+        table: &Table,
+    ) -> Result<PgCreateTable> {
+        let mut pg_table = PgCreateTable::from_name_and_columns(name, &table.columns)?;
+        pg_table.primary_key = table.primary_key.clone();
+        Ok(pg_table)
+    }
+
+    /// Build a `PgCreateTable` from the columns and `PRIMARY KEY` constraints
+    /// found by our parser. Each item contains an optional column, and any
+    /// primary key columns declared by that item.
+    fn from_parsed_items(
+        name: String,
+        items: Vec<(Option<PgColumn>, Vec<String>)>,
+    ) -> Result<PgCreateTable, &'static str> {
+        let mut columns = vec![];
+        let mut primary_key = vec![];
+        for (column, key) in items {
+            if !key.is_empty() {
+                if !primary_key.is_empty() {
+                    return Err("at most one PRIMARY KEY");
+                }
+                primary_key = key;
+            }
+            columns.extend(column);
+        }
+
+        // Primary key columns are always `NOT NULL`.
+        for key in &primary_key {
+            let col = columns
+                .iter_mut()
+                .find(|c| &c.name == key)
+                .ok_or("PRIMARY KEY columns declared in the table")?;
+            col.is_nullable = false;
+        }
+
+        Ok(PgCreateTable {
+            name,
+            columns,
+            primary_key,
             if_not_exists: false,
+            // We don't worry about trying to parse this, which we only use
+            // internally at the moment.
             temporary: false,
         })
     }
@@ -103,10 +214,8 @@ impl PgCreateTable {
         default: &Table,
     ) -> Result<PgCreateTable> {
         // If we can't find a catalog in the database, use this one.
-        let default_dest_table = PgCreateTable::from_name_and_columns(
-            full_table_name.to_owned(),
-            &default.columns,
-        )?;
+        let default_dest_table =
+            PgCreateTable::from_name_and_table(full_table_name.to_owned(), default)?;
 
         // Should we check the catalog to see if the table schema exists?
         match check_catalog {
@@ -138,6 +247,8 @@ impl PgCreateTable {
         Ok(Table {
             name: self.name.clone(),
             columns,
+            primary_key: self.primary_key.clone(),
+            indexes: vec![],
         })
     }
 
@@ -171,28 +282,73 @@ impl PgCreateTable {
                     }
                 })
                 .collect::<Result<Vec<_>>>()?,
+            primary_key: self.primary_key.clone(),
             if_not_exists: self.if_not_exists,
             temporary: self.temporary,
         })
     }
 
+    /// Return the columns of `other_table` which don't appear in this table.
+    /// These are always nullable, because they will be added to a table which
+    /// may already contain rows.
+    pub(crate) fn missing_columns(
+        &self,
+        other_table: &PgCreateTable,
+    ) -> Vec<PgColumn> {
+        let names =
+            HashSet::<&str>::from_iter(self.columns.iter().map(|c| &c.name[..]));
+        other_table
+            .columns
+            .iter()
+            .filter(|c| !names.contains(&c.name[..]))
+            .map(|c| PgColumn {
+                is_nullable: true,
+                ..c.to_owned()
+            })
+            .collect()
+    }
+
     /// Write a `COPY (SELECT ...) TO STDOUT ...` statement for this table.
+    /// Geometry columns are reprojected if `schema` asks for a different SRID.
+    /// If `limit` is present, we only export that many rows.
     pub(crate) fn write_export_sql(
         &self,
         f: &mut dyn Write,
         source_args: &SourceArguments<Verified>,
+        schema: &Table,
+        limit: Option<usize>,
+        key_range: Option<&PgKeyRange>,
     ) -> Result<()> {
+        let limit = match (limit, source_args.limit()) {
+            (Some(l1), Some(l2)) => Some(l1.min(l2)),
+            (l1, l2) => l1.or(l2),
+        };
         write!(f, "COPY (")?;
-        self.write_export_select_sql(f, source_args)?;
+        self.write_select_sql(f, source_args, schema, limit, key_range)?;
         write!(f, ") TO STDOUT WITH CSV HEADER")?;
         Ok(())
     }
 
-    /// Write a `SELECT ...` statement for this table.
+    /// Write a `SELECT ...` statement for this table. Geometry columns are
+    /// reprojected if `schema` asks for a different SRID.
     pub(crate) fn write_export_select_sql(
         &self,
         f: &mut dyn Write,
         source_args: &SourceArguments<Verified>,
+        schema: &Table,
+    ) -> Result<()> {
+        self.write_select_sql(f, source_args, schema, source_args.limit(), None)
+    }
+
+    /// Write a `SELECT ...` statement for this table, returning at most
+    /// `limit` rows, and only the rows in `key_range` if we have one.
+    fn write_select_sql(
+        &self,
+        f: &mut dyn Write,
+        source_args: &SourceArguments<Verified>,
+        schema: &Table,
+        limit: Option<usize>,
+        key_range: Option<&PgKeyRange>,
     ) -> Result<()> {
         write!(f, "SELECT ")?;
         if self.columns.is_empty() {
@@ -201,15 +357,40 @@ impl PgCreateTable {
         let mut sep = Separator::new(",");
         for col in &self.columns {
             write!(f, "{}", sep.display())?;
-            col.write_export_select_expr(f)?;
+            let requested = schema.columns.iter().find(|c| c.name == col.name);
+            col.write_export_select_expr_as(f, requested)?;
         }
         write!(f, " FROM {}", TableName(&self.name))?;
-        if let Some(where_clause) = source_args.where_clause() {
-            write!(f, " WHERE ({})", where_clause)?;
+        match (source_args.where_clause(), key_range) {
+            (Some(where_clause), Some(key_range)) => {
+                write!(f, " WHERE ({}) AND {}", where_clause, key_range)?
+            }
+            (Some(where_clause), None) => write!(f, " WHERE ({})", where_clause)?,
+            (None, Some(key_range)) => write!(f, " WHERE {}", key_range)?,
+            (None, None) => {}
+        }
+        if let Some(order_by) = source_args.order_by() {
+            write!(f, " ORDER BY {}", order_by)?;
+        }
+        if let Some(limit) = limit {
+            write!(f, " LIMIT {}", limit)?;
         }
         Ok(())
     }
 
+    /// Return `COMMENT ON COLUMN` statements for any columns with comments.
+    pub(crate) fn comments(&self) -> Vec<PgCommentOnColumn> {
+        self.columns
+            .iter()
+            .filter(|col| col.comment.is_some())
+            .map(|col| PgCommentOnColumn {
+                table_name: self.name.clone(),
+                column_name: col.name.clone(),
+                comment: col.comment.clone(),
+            })
+            .collect()
+    }
+
     /// Write a `SELECT COUNT(*) ...` statement for this table.
     pub(crate) fn write_count_sql(
         &self,
@@ -223,6 +404,42 @@ impl PgCreateTable {
         }
         Ok(())
     }
+
+    /// Write a `SELECT MAX(column) ...` statement for this table, returning
+    /// the largest value as text.
+    /// Write SQL returning the smallest and largest values of the integer
+    /// `column`, as `min_value` and `max_value`.
+    pub(crate) fn write_min_max_sql(
+        &self,
+        f: &mut dyn Write,
+        source_args: &SourceArguments<Verified>,
+        column: &str,
+    ) -> Result<()> {
+        writeln!(
+            f,
+            "SELECT MIN({col})::bigint AS min_value, MAX({col})::bigint AS max_value",
+            col = Ident(column),
+        )?;
+        writeln!(f, " FROM {}", TableName(&self.name))?;
+        if let Some(where_clause) = source_args.where_clause() {
+            writeln!(f, " WHERE ({})", where_clause)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn write_max_sql(
+        &self,
+        f: &mut dyn Write,
+        source_args: &SourceArguments<Verified>,
+        column: &str,
+    ) -> Result<()> {
+        writeln!(f, "SELECT MAX({})::text AS max_value", Ident(column))?;
+        writeln!(f, " FROM {}", TableName(&self.name))?;
+        if let Some(where_clause) = source_args.where_clause() {
+            writeln!(f, " WHERE ({})", where_clause)?;
+        }
+        Ok(())
+    }
 }
 
 impl fmt::Display for PgCreateTable {
@@ -236,13 +453,20 @@ impl fmt::Display for PgCreateTable {
             write!(f, " IF NOT EXISTS")?;
         }
         writeln!(f, " {} (", TableName(&self.name))?;
-        for (idx, col) in self.columns.iter().enumerate() {
-            write!(f, "    {}", col)?;
-            if idx + 1 == self.columns.len() {
-                writeln!(f)?;
-            } else {
-                writeln!(f, ",")?;
+        let mut sep = Separator::new(",\n");
+        for col in &self.columns {
+            write!(f, "{}    {}", sep.display(), col)?;
+        }
+        if !self.primary_key.is_empty() {
+            write!(f, "{}    PRIMARY KEY (", sep.display())?;
+            let mut key_sep = Separator::new(", ");
+            for name in &self.primary_key {
+                write!(f, "{}{}", key_sep.display(), Ident(name))?;
             }
+            write!(f, ")")?;
+        }
+        if !self.columns.is_empty() {
+            writeln!(f)?;
         }
         writeln!(f, ");")?;
         Ok(())
@@ -257,6 +481,96 @@ mod grammar {
     include!(concat!(env!("OUT_DIR"), "/create_table_sql.rs"));
 }
 
+/// Parse a column `DEFAULT` expression from PostgreSQL's catalog, returning
+/// `None` if it's too complicated to include in a portable schema.
+pub(crate) fn parse_column_default(expr: &str) -> Option<ColumnDefault> {
+    grammar::simple_default(expr).ok()
+}
+
+/// Parse a column type like `numeric(12,2)` or `citext`, and convert it to a
+/// portable data type and collation.
+pub(crate) fn parse_column_type(s: &str) -> Result<(DataType, Option<Collation>)> {
+    let (data_type, collation) = grammar::standalone_column_type(s)
+        .with_context(|_| format!("error parsing column type {:?}", s))?;
+    Ok((data_type.to_data_type()?, collation))
+}
+
+/// A statement that we can parse from an SQL schema file.
+enum PgStatement {
+    CreateTable(PgCreateTable),
+    CreateIndex(PgCreateIndex),
+    CommentOnColumn(PgCommentOnColumn),
+}
+
+impl PgCreateTable {
+    /// Parse a string containing one or more `CREATE TABLE` statements, each
+    /// optionally followed by `CREATE INDEX` and `COMMENT ON COLUMN`
+    /// statements for that table.
+    pub(crate) fn parse_many(
+        s: &str,
+    ) -> Result<Vec<(PgCreateTable, Vec<PgCreateIndex>)>> {
+        let statements = grammar::create_statements(s)
+            .context("error parsing Postgres `CREATE TABLE` statements")?;
+        let mut tables: Vec<(PgCreateTable, Vec<PgCreateIndex>)> = vec![];
+        for statement in statements {
+            match statement {
+                PgStatement::CreateTable(table) => tables.push((table, vec![])),
+                PgStatement::CreateIndex(index) => {
+                    let table = tables
+                        .iter_mut()
+                        .find(|(table, _)| {
+                            same_table_name(&table.name, &index.table_name)
+                        })
+                        .ok_or_else(|| {
+                            format_err!(
+                                "CREATE INDEX {} must come after CREATE TABLE {}",
+                                index.name,
+                                index.table_name,
+                            )
+                        })?;
+                    table.1.push(index);
+                }
+                PgStatement::CommentOnColumn(comment) => {
+                    let column = tables
+                        .iter_mut()
+                        .find(|(table, _)| {
+                            same_table_name(&table.name, &comment.table_name)
+                        })
+                        .and_then(|(table, _)| {
+                            table
+                                .columns
+                                .iter_mut()
+                                .find(|c| c.name == comment.column_name)
+                        })
+                        .ok_or_else(|| {
+                            format_err!(
+                                "COMMENT ON COLUMN {}.{} must come after CREATE TABLE {}",
+                                comment.table_name,
+                                comment.column_name,
+                                comment.table_name,
+                            )
+                        })?;
+                    column.comment = comment.comment;
+                }
+            }
+        }
+        Ok(tables)
+    }
+}
+
+/// Do `a` and `b` refer to the same table, assuming that unqualified names are
+/// in the `public` namespace?
+fn same_table_name(a: &str, b: &str) -> bool {
+    let qualify = |name: &str| {
+        if name.contains('.') {
+            name.to_owned()
+        } else {
+            format!("public.{}", name)
+        }
+    };
+    qualify(a) == qualify(b)
+}
+
 impl FromStr for PgCreateTable {
     type Err = Error;
 
@@ -269,7 +583,9 @@ impl FromStr for PgCreateTable {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::schema::{Column, DataType, Srid};
+    use crate::drivers::postgres::PostgresLocator;
+    use crate::drivers::postgres_shared::{PgDataType, PgScalarDataType};
+    use crate::schema::{Collation, Column, DataType, DecimalPrecision, Index, Srid};
 
     use std::str;
 
@@ -286,68 +602,92 @@ mod test {
                     is_nullable: true,
                     data_type: DataType::Text,
                     comment: None,
+                    default: None,
+                    collation: None,
                 },
                 Column {
                     name: "b".to_string(),
                     is_nullable: true,
                     data_type: DataType::Int32,
                     comment: None,
+                    default: None,
+                    collation: None,
                 },
                 Column {
                     name: "c".to_string(),
                     is_nullable: false,
                     data_type: DataType::Uuid,
                     comment: None,
+                    default: None,
+                    collation: None,
                 },
                 Column {
                     name: "d".to_string(),
                     is_nullable: true,
                     data_type: DataType::Date,
                     comment: None,
+                    default: None,
+                    collation: None,
                 },
                 Column {
                     name: "e".to_string(),
                     is_nullable: true,
                     data_type: DataType::Float64,
                     comment: None,
+                    default: None,
+                    collation: None,
                 },
                 Column {
                     name: "f".to_string(),
                     is_nullable: true,
                     data_type: DataType::Array(Box::new(DataType::Text)),
                     comment: None,
+                    default: None,
+                    collation: None,
                 },
                 Column {
                     name: "g".to_string(),
                     is_nullable: true,
                     data_type: DataType::Array(Box::new(DataType::Int32)),
                     comment: None,
+                    default: None,
+                    collation: None,
                 },
                 Column {
                     name: "h".to_string(),
                     is_nullable: true,
                     data_type: DataType::GeoJson(Srid::wgs84()),
                     comment: None,
+                    default: None,
+                    collation: None,
                 },
                 Column {
                     name: "i".to_string(),
                     is_nullable: true,
                     data_type: DataType::GeoJson(Srid::new(3857)),
                     comment: None,
+                    default: None,
+                    collation: None,
                 },
                 Column {
                     name: "j".to_string(),
                     is_nullable: true,
                     data_type: DataType::Int16,
                     comment: None,
+                    default: None,
+                    collation: None,
                 },
                 Column {
                     name: "k".to_string(),
                     is_nullable: true,
                     data_type: DataType::TimestampWithoutTimeZone,
                     comment: None,
+                    default: None,
+                    collation: None,
                 },
             ],
+            primary_key: vec![],
+            indexes: vec![],
         };
         assert_eq!(table, expected);
 
@@ -361,4 +701,545 @@ mod test {
         let parsed_again = pg_parsed_again.to_table().unwrap();
         assert_eq!(parsed_again, expected);
     }
+
+    #[test]
+    fn pg_dump_table() {
+        let input = r#"
+CREATE TABLE public.users (
+    id integer DEFAULT nextval('public.users_id_seq'::regclass) NOT NULL,
+    name character varying(255) DEFAULT 'a, b'::character varying,
+    balance numeric(10,2) DEFAULT 0.0 NOT NULL,
+    created_at timestamp with time zone DEFAULT now()
+);
+"#;
+        let pg_table: PgCreateTable = input.parse().unwrap();
+        let table = pg_table.to_table().unwrap();
+        assert_eq!(table.name, "public.users");
+        let columns = table
+            .columns
+            .iter()
+            .map(|c| (&c.name[..], c.is_nullable, c.data_type.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            columns,
+            vec![
+                ("id", false, DataType::Int32),
+                ("name", true, DataType::TextWithMaxLength(255)),
+                (
+                    "balance",
+                    false,
+                    DataType::DecimalWithPrecision(
+                        DecimalPrecision::new(10, 2).unwrap()
+                    ),
+                ),
+                ("created_at", true, DataType::TimestampWithTimeZone),
+            ],
+        );
+    }
+
+    #[test]
+    fn multiple_tables() {
+        let input = r#"
+-- Our tables.
+CREATE TABLE a (id int);
+CREATE TABLE b (id int, name text)
+;
+"#;
+        let tables = PgCreateTable::parse_many(input).unwrap();
+        let names = tables.iter().map(|(t, _)| &t.name[..]).collect::<Vec<_>>();
+        assert_eq!(names, &["a", "b"]);
+        assert!(input.parse::<PgCreateTable>().is_err());
+    }
+
+    #[test]
+    fn indexes() {
+        let input = r#"
+CREATE TABLE public.users (id int PRIMARY KEY, name text, email text);
+CREATE INDEX users_name_idx ON public.users USING btree (name);
+CREATE UNIQUE INDEX IF NOT EXISTS "users_email_name" ON users (email, "name");
+"#;
+        let tables = PgCreateTable::parse_many(input).unwrap();
+        assert_eq!(tables.len(), 1);
+        let (table, indexes) = &tables[0];
+        assert_eq!(table.name, "public.users");
+        let indexes = indexes.iter().map(|i| i.to_index()).collect::<Vec<_>>();
+        assert_eq!(
+            indexes,
+            vec![
+                Index {
+                    name: "users_name_idx".to_owned(),
+                    columns: vec!["name".to_owned()],
+                    is_unique: false,
+                },
+                Index {
+                    name: "users_email_name".to_owned(),
+                    columns: vec!["email".to_owned(), "name".to_owned()],
+                    is_unique: true,
+                },
+            ],
+        );
+
+        // Make sure we can write our indexes and read them back.
+        let sql = format!(
+            "{}{}",
+            table,
+            PgCreateIndex::from_index(&table.name, &indexes[1]),
+        );
+        assert!(sql.contains(
+            "CREATE UNIQUE INDEX \"users_email_name\" ON \"public\".\"users\" (\"email\", \"name\");"
+        ));
+        let parsed_again = PgCreateTable::parse_many(&sql).unwrap();
+        assert_eq!(parsed_again[0].1[0].to_index(), indexes[1]);
+
+        // Indexes must refer to a table declared earlier in the file.
+        assert!(PgCreateTable::parse_many("CREATE INDEX i ON t (a)").is_err());
+    }
+
+    #[test]
+    fn column_comments() {
+        let input = r#"
+CREATE TABLE public.users (id int, name text);
+COMMENT ON COLUMN public.users.id IS 'The user''s ID.';
+COMMENT ON COLUMN users."name" IS NULL;
+"#;
+        let tables = PgCreateTable::parse_many(input).unwrap();
+        let (table, _) = &tables[0];
+        let comments = table
+            .to_table()
+            .unwrap()
+            .columns
+            .into_iter()
+            .map(|c| c.comment)
+            .collect::<Vec<_>>();
+        assert_eq!(comments, vec![Some("The user's ID.".to_owned()), None]);
+
+        // Make sure we can write our comments and read them back.
+        let sql = format!("{}{}", table, table.comments()[0]);
+        assert!(sql.contains(
+            r#"COMMENT ON COLUMN "public"."users"."id" IS 'The user''s ID.';"#
+        ));
+        let parsed_again = PgCreateTable::parse_many(&sql).unwrap();
+        assert_eq!(&parsed_again[0].0, table);
+
+        // Comments must refer to a column declared earlier in the file.
+        assert!(PgCreateTable::parse_many(
+            "CREATE TABLE t (a int); COMMENT ON COLUMN t.b IS 'b'"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn primary_keys() {
+        let examples = &[
+            ("CREATE TABLE t (id int PRIMARY KEY, name text)", vec!["id"]),
+            (
+                "CREATE TABLE t (a int, b text, PRIMARY KEY (a, b))",
+                vec!["a", "b"],
+            ),
+            (
+                "CREATE TABLE t (a int, CONSTRAINT t_pkey PRIMARY KEY (a))",
+                vec!["a"],
+            ),
+            ("CREATE TABLE t (a int)", vec![]),
+        ];
+        for (input, expected) in examples {
+            let pg_table: PgCreateTable = input.parse().unwrap();
+            let table = pg_table.to_table().unwrap();
+            assert_eq!(&table.primary_key, expected, "parsing {}", input);
+            for col in &table.columns {
+                if expected.contains(&&col.name[..]) {
+                    assert!(!col.is_nullable, "{} should be NOT NULL", col.name);
+                }
+            }
+
+            // Make sure we can write out our primary key and read it back.
+            let parsed_again: PgCreateTable =
+                pg_table.to_string().parse().expect("error parsing table");
+            assert_eq!(parsed_again, pg_table);
+        }
+
+        for input in &[
+            "CREATE TABLE t (a int PRIMARY KEY, b int PRIMARY KEY)",
+            "CREATE TABLE t (a int, PRIMARY KEY (b))",
+        ] {
+            assert!(input.parse::<PgCreateTable>().is_err(), "parsed {}", input);
+        }
+    }
+
+    #[test]
+    fn column_defaults() {
+        let input = r#"CREATE TABLE t (
+    a int DEFAULT 0 NOT NULL,
+    b text DEFAULT 'it''s'::character varying,
+    c boolean DEFAULT TRUE,
+    d timestamp with time zone DEFAULT now() NOT NULL,
+    e bigint DEFAULT nextval('t_e_seq'::regclass),
+    f text[] DEFAULT '{}'::text[],
+    g text DEFAULT NULL
+)"#;
+        let pg_table: PgCreateTable = input.parse().unwrap();
+        let table = pg_table.to_table().unwrap();
+        let defaults = table
+            .columns
+            .iter()
+            .map(|c| c.default.clone())
+            .collect::<Vec<_>>();
+        let literal = |s: &str| Some(ColumnDefault::Literal(s.to_owned()));
+        assert_eq!(
+            defaults,
+            vec![
+                literal("0"),
+                literal("it's"),
+                literal("true"),
+                Some(ColumnDefault::CurrentTimestamp),
+                None,
+                None,
+                None,
+            ],
+        );
+        assert!(!table.columns[0].is_nullable);
+        assert!(!table.columns[3].is_nullable);
+
+        // Make sure we can write out our defaults and read them back.
+        let pg_table =
+            PgCreateTable::from_name_and_columns("t".to_owned(), &table.columns)
+                .unwrap();
+        let sql = pg_table.to_string();
+        assert!(sql.contains(r#""b" text DEFAULT 'it''s'"#), "{}", sql);
+        let parsed_again: PgCreateTable = sql.parse().expect("error parsing table");
+        assert_eq!(parsed_again, pg_table);
+    }
+
+    #[test]
+    fn numeric_precision_round_trip() {
+        let input = "CREATE TABLE t (a numeric(10, 2), b numeric(5), c numeric)";
+        let pg_table: PgCreateTable = input.parse().unwrap();
+        let types = pg_table
+            .columns
+            .iter()
+            .map(|c| c.data_type.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(types, vec!["numeric(10,2)", "numeric(5,0)", "numeric"]);
+        assert!("CREATE TABLE t (a numeric(2,3))"
+            .parse::<PgCreateTable>()
+            .is_err());
+    }
+
+    #[test]
+    fn varchar_length_round_trip() {
+        let input =
+            "CREATE TABLE t (a varchar(10), b character varying (20), c character(2), d varchar)";
+        let pg_table: PgCreateTable = input.parse().unwrap();
+        let types = pg_table
+            .columns
+            .iter()
+            .map(|c| c.data_type.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                "character varying(10)",
+                "character varying(20)",
+                "character varying(2)",
+                "text",
+            ],
+        );
+        let table = pg_table.to_table().unwrap();
+        assert_eq!(table.columns[0].data_type, DataType::TextWithMaxLength(10));
+        let round_trip =
+            PgCreateTable::from_name_and_columns(table.name.clone(), &table.columns)
+                .unwrap();
+        assert_eq!(round_trip.columns, pg_table.columns);
+    }
+
+    #[test]
+    fn column_collations() {
+        let input = r#"CREATE TABLE t (
+    a citext NOT NULL,
+    b citext[],
+    c text COLLATE pg_catalog."C",
+    d text
+)"#;
+        let pg_table: PgCreateTable = input.parse().unwrap();
+        let table = pg_table.to_table().unwrap();
+        let collations = table
+            .columns
+            .iter()
+            .map(|c| c.collation.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            collations,
+            vec![
+                Some(Collation::CaseInsensitive),
+                Some(Collation::CaseInsensitive),
+                Some(Collation::Named("C".to_owned())),
+                None,
+            ],
+        );
+        assert_eq!(table.columns[0].data_type, DataType::Text);
+
+        let pg_again =
+            PgCreateTable::from_name_and_columns("t".to_owned(), &table.columns)
+                .unwrap();
+        let sql = pg_again.to_string();
+        assert!(sql.contains(r#""a" citext NOT NULL"#), "{}", sql);
+        assert!(sql.contains(r#""b" citext[]"#), "{}", sql);
+        assert!(sql.contains(r#""c" text COLLATE "C""#), "{}", sql);
+        let parsed_again: PgCreateTable = sql.parse().expect("error parsing table");
+        assert_eq!(parsed_again, pg_again);
+    }
+
+    #[test]
+    fn missing_columns() {
+        let existing: PgCreateTable = "CREATE TABLE t (id int NOT NULL, name text)"
+            .parse()
+            .unwrap();
+        let wanted: PgCreateTable =
+            "CREATE TABLE t (id int NOT NULL, email text NOT NULL, name text, age int)"
+                .parse()
+                .unwrap();
+        let missing = existing
+            .missing_columns(&wanted)
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(missing, vec![r#""email" text"#, r#""age" int"#]);
+        assert!(wanted.missing_columns(&existing).is_empty());
+    }
+
+    #[test]
+    fn time_columns() {
+        let input = "CREATE TABLE t (a time, b time without time zone, c time[])";
+        let pg_table: PgCreateTable = input.parse().unwrap();
+        let table = pg_table.to_table().unwrap();
+        let types = table
+            .columns
+            .iter()
+            .map(|c| c.data_type.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                DataType::Time,
+                DataType::Time,
+                DataType::Array(Box::new(DataType::Time)),
+            ],
+        );
+        let pg_again =
+            PgCreateTable::from_name_and_columns("t".to_owned(), &table.columns)
+                .unwrap();
+        assert_eq!(pg_again.to_string(), pg_table.to_string());
+    }
+
+    #[test]
+    fn one_of_columns() {
+        let labels = vec!["happy".to_owned(), "it's ok".to_owned()];
+        let columns = vec![Column {
+            name: "mood".to_owned(),
+            is_nullable: false,
+            data_type: DataType::OneOf(labels.clone()),
+            comment: None,
+            default: None,
+            collation: None,
+        }];
+        let pg_table =
+            PgCreateTable::from_name_and_columns("t".to_owned(), &columns).unwrap();
+        let sql = pg_table.to_string();
+        assert_eq!(
+            sql,
+            "CREATE TABLE \"t\" (\n    \"mood\" text NOT NULL CHECK (\"mood\" IN ('happy', 'it''s ok'))\n);\n",
+        );
+        let parsed = sql.parse::<PgCreateTable>().unwrap().to_table().unwrap();
+        assert_eq!(parsed.columns, columns);
+
+        // Named enum types from the catalog also map to `one_of`.
+        let pg_col = PgColumn {
+            name: "mood".to_owned(),
+            data_type: PgDataType::Scalar(PgScalarDataType::Enum {
+                name: "public.mood".to_owned(),
+                oid: 16_385,
+                labels: labels.clone(),
+            }),
+            is_nullable: false,
+            default: None,
+            comment: None,
+            collation: None,
+        };
+        assert_eq!(pg_col.to_string(), "\"mood\" \"public\".\"mood\" NOT NULL");
+        assert_eq!(pg_col.to_column().unwrap(), columns[0]);
+    }
+
+    #[test]
+    fn composite_columns() {
+        use crate::schema::StructField;
+
+        // Composite types from the catalog map to `struct`.
+        let pg_col = PgColumn {
+            name: "point".to_owned(),
+            data_type: PgDataType::Scalar(PgScalarDataType::Composite {
+                name: "public.point2d".to_owned(),
+                fields: vec![
+                    PgColumn {
+                        name: "x".to_owned(),
+                        data_type: PgDataType::Scalar(
+                            PgScalarDataType::DoublePrecision,
+                        ),
+                        is_nullable: true,
+                        default: None,
+                        comment: None,
+                        collation: None,
+                    },
+                    PgColumn {
+                        name: "tags".to_owned(),
+                        data_type: PgDataType::Array {
+                            dimension_count: 1,
+                            ty: PgScalarDataType::Text,
+                        },
+                        is_nullable: true,
+                        default: None,
+                        comment: None,
+                        collation: None,
+                    },
+                ],
+            }),
+            is_nullable: true,
+            default: None,
+            comment: None,
+            collation: None,
+        };
+        assert_eq!(pg_col.to_string(), "\"point\" \"public\".\"point2d\"");
+        let mut sql = vec![];
+        pg_col.write_export_select_expr(&mut sql).unwrap();
+        assert_eq!(
+            String::from_utf8(sql).unwrap(),
+            "to_json(\"point\") AS \"point\"",
+        );
+        let data_type = DataType::Struct(vec![
+            StructField {
+                name: "x".to_owned(),
+                is_nullable: true,
+                data_type: DataType::Float64,
+            },
+            StructField {
+                name: "tags".to_owned(),
+                is_nullable: true,
+                data_type: DataType::Array(Box::new(DataType::Text)),
+            },
+        ]);
+        assert_eq!(pg_col.to_column().unwrap().data_type, data_type);
+
+        // Ranges are also represented as structs.
+        let pg_col = PgColumn {
+            name: "period".to_owned(),
+            data_type: PgDataType::Scalar(PgScalarDataType::Range {
+                name: "tstzrange".to_owned(),
+                subtype: Box::new(PgScalarDataType::TimestampWithTimeZone),
+            }),
+            is_nullable: true,
+            default: None,
+            comment: None,
+            collation: None,
+        };
+        assert_eq!(pg_col.to_string(), "\"period\" tstzrange");
+        let column = pg_col.to_column().unwrap();
+        match column.data_type {
+            DataType::Struct(fields) => {
+                let names = fields.iter().map(|f| &f.name[..]).collect::<Vec<_>>();
+                assert_eq!(
+                    names,
+                    &["lower", "upper", "lower_inc", "upper_inc", "empty"],
+                );
+                assert_eq!(fields[0].data_type, DataType::TimestampWithTimeZone);
+            }
+            other => panic!("expected struct, got {:?}", other),
+        }
+        let mut sql = vec![];
+        pg_col.write_export_select_expr(&mut sql).unwrap();
+        let sql = String::from_utf8(sql).unwrap();
+        assert!(sql.starts_with(
+            "CASE WHEN \"period\" IS NULL THEN NULL ELSE json_build_object('lower', "
+        ));
+        assert!(sql.contains("'empty', isempty(\"period\")) END AS \"period\""));
+
+        // But we create `jsonb` columns for structs.
+        assert_eq!(
+            PgDataType::from_data_type(&data_type).unwrap(),
+            PgDataType::Scalar(PgScalarDataType::Jsonb),
+        );
+    }
+
+    #[test]
+    fn export_reprojects_geometry() {
+        let input = "CREATE TABLE places (
+    id integer,
+    mercator public.geometry(Geometry, 3857),
+    unknown public.geometry(Geometry, 0),
+    wgs84 public.geometry(Geometry, 4326)
+);";
+        let pg_table: PgCreateTable = input.parse().unwrap();
+        let mut schema = pg_table.to_table().unwrap();
+        for col in &mut schema.columns {
+            if let DataType::GeoJson(_) = col.data_type {
+                col.data_type = DataType::GeoJson(Srid::wgs84());
+            }
+        }
+        let source_args = SourceArguments::for_temporary()
+            .verify(Features::empty())
+            .unwrap();
+        let mut sql = vec![];
+        pg_table
+            .write_export_select_sql(&mut sql, &source_args, &schema)
+            .unwrap();
+        assert_eq!(
+            str::from_utf8(&sql).unwrap(),
+            "SELECT \"id\",ST_AsGeoJSON(ST_Transform(\"mercator\", 4326)) AS \"mercator\",ST_AsGeoJSON(\"unknown\") AS \"unknown\",ST_AsGeoJSON(\"wgs84\") AS \"wgs84\" FROM \"places\"",
+        );
+    }
+
+    #[test]
+    fn export_uses_order_by_and_smallest_limit() {
+        let pg_table: PgCreateTable = "CREATE TABLE t (id integer);".parse().unwrap();
+        let schema = pg_table.to_table().unwrap();
+        let source_args = SourceArguments::for_temporary()
+            .with_limit(Some(10))
+            .with_order_by(Some("id DESC".to_owned()))
+            .verify(PostgresLocator::features())
+            .unwrap();
+        let mut sql = vec![];
+        pg_table
+            .write_export_sql(&mut sql, &source_args, &schema, Some(5), None)
+            .unwrap();
+        assert_eq!(
+            str::from_utf8(&sql).unwrap(),
+            "COPY (SELECT \"id\" FROM \"t\" ORDER BY id DESC LIMIT 5) TO STDOUT WITH CSV HEADER",
+        );
+    }
+
+    #[test]
+    fn export_splits_keys_into_ranges() {
+        let ranges = PgKeyRange::split("id", 1, 10, 3);
+        let bounds = ranges.iter().map(|r| (r.min, r.max)).collect::<Vec<_>>();
+        assert_eq!(bounds, vec![(1, 3), (4, 6), (7, 10)]);
+        assert!(ranges[0].include_nulls && !ranges[1].include_nulls);
+        assert_eq!(PgKeyRange::split("id", 5, 6, 4).len(), 2);
+        let full = PgKeyRange::split("id", i64::MIN, i64::MAX, 2);
+        assert_eq!(
+            full.iter().map(|r| (r.min, r.max)).collect::<Vec<_>>(),
+            vec![(i64::MIN, -1), (0, i64::MAX)],
+        );
+
+        let pg_table: PgCreateTable = "CREATE TABLE t (id integer);".parse().unwrap();
+        let schema = pg_table.to_table().unwrap();
+        let source_args = SourceArguments::for_temporary()
+            .verify(PostgresLocator::features())
+            .unwrap();
+        let mut sql = vec![];
+        pg_table
+            .write_export_sql(&mut sql, &source_args, &schema, None, Some(&ranges[0]))
+            .unwrap();
+        assert_eq!(
+            str::from_utf8(&sql).unwrap(),
+            "COPY (SELECT \"id\" FROM \"t\" WHERE (\"id\" BETWEEN 1 AND 3 OR \"id\" IS NULL)) TO STDOUT WITH CSV HEADER",
+        );
+    }
 }
@@ -6,9 +6,10 @@ use std::{
 };
 
 use crate::common::*;
-use crate::drivers::postgres_shared::PgCreateTable;
+use crate::drivers::postgres_shared::{PgCreateIndex, PgCreateTable};
 
-/// An SQL file containing a `CREATE TABLE` statement using Postgres syntax.
+/// An SQL file containing one or more `CREATE TABLE` statements using Postgres
+/// syntax.
 #[derive(Clone, Debug)]
 pub struct PostgresSqlLocator {
     path: PathOrStdio,
@@ -34,6 +35,10 @@ impl Locator for PostgresSqlLocator {
         self
     }
 
+    fn is_stdio(&self) -> bool {
+        matches!(self.path, PathOrStdio::Stdio)
+    }
+
     fn schema(&self, ctx: Context) -> BoxFuture<Option<Table>> {
         schema_helper(ctx, self.to_owned()).boxed()
     }
@@ -44,7 +49,20 @@ impl Locator for PostgresSqlLocator {
         table: Table,
         if_exists: IfExists,
     ) -> BoxFuture<()> {
-        write_schema_helper(ctx, self.to_owned(), table, if_exists).boxed()
+        write_schemas_helper(ctx, self.to_owned(), vec![table], if_exists).boxed()
+    }
+
+    fn schemas(&self, ctx: Context) -> BoxFuture<Option<Vec<Table>>> {
+        schemas_helper(ctx, self.to_owned()).boxed()
+    }
+
+    fn write_schemas(
+        &self,
+        ctx: Context,
+        tables: Vec<Table>,
+        if_exists: IfExists,
+    ) -> BoxFuture<()> {
+        write_schemas_helper(ctx, self.to_owned(), tables, if_exists).boxed()
     }
 }
 
@@ -67,9 +85,34 @@ impl LocatorStatic for PostgresSqlLocator {
 
 /// Implementation of `schema`, but as a real `async` function.
 async fn schema_helper(
-    _ctx: Context,
+    ctx: Context,
     source: PostgresSqlLocator,
 ) -> Result<Option<Table>> {
+    let mut tables = read_tables(ctx, &source).await?;
+    if tables.len() == 1 {
+        Ok(Some(tables.remove(0)))
+    } else {
+        Err(format_err!(
+            "expected 1 `CREATE TABLE` statement in {}, found {}",
+            source.path,
+            tables.len(),
+        ))
+    }
+}
+
+/// Implementation of `schemas`, but as a real `async` function.
+async fn schemas_helper(
+    ctx: Context,
+    source: PostgresSqlLocator,
+) -> Result<Option<Vec<Table>>> {
+    Ok(Some(read_tables(ctx, &source).await?))
+}
+
+/// Read all the `CREATE TABLE` statements in `source`.
+async fn read_tables(
+    _ctx: Context,
+    source: &PostgresSqlLocator,
+) -> Result<Vec<Table>> {
     let input = source
         .path
         .open_async()
@@ -78,31 +121,60 @@ async fn schema_helper(
     let sql = async_read_to_string(input)
         .await
         .with_context(|_| format!("error reading {}", source.path))?;
-    let pg_create_table: PgCreateTable = sql
-        .parse::<PgCreateTable>()
+    let tables = parse_tables(&sql)
         .with_context(|_| format!("error parsing {}", source.path))?;
-    let table = pg_create_table.to_table()?;
-    Ok(Some(table))
+    Ok(tables)
+}
+
+/// Parse one or more `CREATE TABLE` statements, each optionally followed by
+/// `CREATE INDEX` statements.
+pub(crate) fn parse_tables(sql: &str) -> Result<Vec<Table>> {
+    let pg_create_tables = PgCreateTable::parse_many(sql)?;
+    pg_create_tables
+        .iter()
+        .map(|(pg_create_table, pg_create_indexes)| {
+            let mut table = pg_create_table.to_table()?;
+            table.indexes = pg_create_indexes.iter().map(|i| i.to_index()).collect();
+            Ok(table)
+        })
+        .collect()
 }
 
-/// Implementation of `write_schema`, but as a real `async` function.
-async fn write_schema_helper(
+/// Implementation of `write_schemas`, but as a real `async` function.
+async fn write_schemas_helper(
     ctx: Context,
     dest: PostgresSqlLocator,
-    table: Table,
+    tables: Vec<Table>,
     if_exists: IfExists,
 ) -> Result<()> {
-    // TODO: We use the existing `table.name` here, but this might produce
-    // odd results if the input table comes from BigQuery or another
-    // database with a very different naming scheme.
-    let pg_create_table =
-        PgCreateTable::from_name_and_columns(table.name.clone(), &table.columns)?;
     let mut out = dest.path.create_async(ctx, if_exists).await?;
-    buffer_sync_write_and_copy_to_async(&mut out, |buff| {
-        write!(buff, "{}", pg_create_table)
-    })
-    .await
-    .with_context(|_| format!("error writing {}", dest.path))?;
+    buffer_sync_write_and_copy_to_async(&mut out, |buff| write_tables(buff, &tables))
+        .await
+        .with_context(|_| format!("error writing {}", dest.path))?;
     out.flush().await?;
     Ok(())
 }
+
+/// Write `tables` as `CREATE TABLE` and `CREATE INDEX` statements.
+pub(crate) fn write_tables(f: &mut dyn Write, tables: &[Table]) -> Result<()> {
+    for (idx, table) in tables.iter().enumerate() {
+        // TODO: We use the existing `table.name` here, but this might produce
+        // odd results if the input table comes from BigQuery or another
+        // database with a very different naming scheme.
+        let pg_create_table =
+            PgCreateTable::from_name_and_table(table.name.clone(), table)?;
+        if idx > 0 {
+            writeln!(f)?;
+        }
+        write!(f, "{}", pg_create_table)?;
+        for comment in pg_create_table.comments() {
+            write!(f, "{}", comment)?;
+        }
+        for index in &table.indexes {
+            let pg_create_index =
+                PgCreateIndex::from_index(&pg_create_table.name, index);
+            write!(f, "{}", pg_create_index)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,335 @@
+//! Schema-only driver for writing Protocol Buffer message definitions.
+
+use std::{fmt, str::FromStr};
+
+use crate::common::*;
+use crate::schema::{Column, DataType};
+
+/// A `.proto` file containing a single `message` describing each row of a
+/// table.
+#[derive(Clone, Debug)]
+pub struct ProtobufLocator {
+    path: PathOrStdio,
+}
+
+impl fmt::Display for ProtobufLocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.path.fmt_locator_helper(Self::scheme(), f)
+    }
+}
+
+impl FromStr for ProtobufLocator {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let path = PathOrStdio::from_str_locator_helper(Self::scheme(), s)?;
+        Ok(ProtobufLocator { path })
+    }
+}
+
+impl Locator for ProtobufLocator {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn is_stdio(&self) -> bool {
+        matches!(self.path, PathOrStdio::Stdio)
+    }
+
+    fn write_schema(
+        &self,
+        ctx: Context,
+        table: Table,
+        if_exists: IfExists,
+    ) -> BoxFuture<()> {
+        write_schema_helper(ctx, self.to_owned(), table, if_exists).boxed()
+    }
+}
+
+impl LocatorStatic for ProtobufLocator {
+    fn scheme() -> &'static str {
+        "protobuf:"
+    }
+
+    fn features() -> Features {
+        Features {
+            locator: LocatorFeatures::WriteSchema.into(),
+            write_schema_if_exists: IfExistsFeatures::no_append(),
+            source_args: EnumSet::empty(),
+            dest_args: EnumSet::empty(),
+            dest_if_exists: EnumSet::empty(),
+            _placeholder: (),
+        }
+    }
+}
+
+/// Implementation of `write_schema`, but as a real `async` function.
+async fn write_schema_helper(
+    ctx: Context,
+    dest: ProtobufLocator,
+    table: Table,
+    if_exists: IfExists,
+) -> Result<()> {
+    let mut out = dest.path.create_async(ctx, if_exists).await?;
+    buffer_sync_write_and_copy_to_async(&mut out, |buff| write_proto(buff, &table))
+        .await
+        .with_context(|_| format!("error writing {}", dest.path))?;
+    out.flush().await?;
+    Ok(())
+}
+
+/// Write a `proto3` file containing a message definition for `table`.
+pub(crate) fn write_proto(f: &mut dyn Write, table: &Table) -> Result<()> {
+    let fields = table
+        .columns
+        .iter()
+        .map(|col| {
+            let field_type = ProtobufFieldType::for_data_type(&col.data_type)
+                .with_context(|_| format!("cannot convert column {}", col.name))?;
+            Ok((col, field_type))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    writeln!(f, "syntax = \"proto3\";")?;
+    writeln!(f)?;
+    if fields.iter().any(|(_, ty)| ty.needs_timestamp_import()) {
+        writeln!(f, "import \"google/protobuf/timestamp.proto\";")?;
+        writeln!(f)?;
+    }
+    writeln!(f, "message {} {{", message_name(&table.name))?;
+    for (idx, (col, field_type)) in fields.iter().enumerate() {
+        write_field(f, idx + 1, col, field_type)?;
+    }
+    writeln!(f, "}}")?;
+    Ok(())
+}
+
+/// Write a single field declaration.
+fn write_field(
+    f: &mut dyn Write,
+    number: usize,
+    col: &Column,
+    field_type: &ProtobufFieldType,
+) -> Result<()> {
+    if let Some(comment) = &col.comment {
+        for line in comment.lines() {
+            writeln!(f, "  // {}", line)?;
+        }
+    }
+    let label = match field_type {
+        ProtobufFieldType::Repeated(_) => "repeated ",
+        // Use explicit presence for nullable scalars, so that `NULL` can be
+        // distinguished from a default value. Messages already have presence.
+        ProtobufFieldType::Scalar(name)
+            if col.is_nullable && *name != TIMESTAMP_TYPE =>
+        {
+            "optional "
+        }
+        ProtobufFieldType::Scalar(_) => "",
+    };
+    writeln!(
+        f,
+        "  {}{} {} = {};",
+        label,
+        field_type.type_name(),
+        field_name(&col.name),
+        number,
+    )?;
+    Ok(())
+}
+
+/// How to represent a column in Protocol Buffers.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum ProtobufFieldType {
+    /// A single value with the specified type.
+    Scalar(&'static str),
+    /// Zero or more values with the specified type.
+    Repeated(&'static str),
+}
+
+impl ProtobufFieldType {
+    /// Choose a Protocol Buffer type for `data_type`.
+    fn for_data_type(data_type: &DataType) -> Result<ProtobufFieldType> {
+        match data_type {
+            DataType::Array(elem) => match Self::for_data_type(elem)? {
+                ProtobufFieldType::Scalar(name) => {
+                    Ok(ProtobufFieldType::Repeated(name))
+                }
+                ProtobufFieldType::Repeated(_) => Err(format_err!(
+                    "Protocol Buffers does not support nested arrays"
+                )),
+            },
+            other => Ok(ProtobufFieldType::Scalar(scalar_type_name(other))),
+        }
+    }
+
+    /// The name of the underlying type.
+    fn type_name(&self) -> &'static str {
+        match self {
+            ProtobufFieldType::Scalar(name) | ProtobufFieldType::Repeated(name) => {
+                name
+            }
+        }
+    }
+
+    /// Do we need to import `google.protobuf.Timestamp`?
+    fn needs_timestamp_import(&self) -> bool {
+        self.type_name() == TIMESTAMP_TYPE
+    }
+}
+
+/// The well-known type we use for timestamps.
+const TIMESTAMP_TYPE: &str = "google.protobuf.Timestamp";
+
+/// The Protocol Buffers type for a non-array `data_type`. Types without a
+/// natural equivalent are represented using strings in our CSV interchange
+/// format.
+fn scalar_type_name(data_type: &DataType) -> &'static str {
+    match data_type {
+        DataType::Bool => "bool",
+        DataType::Bytes => "bytes",
+        DataType::Float32 => "float",
+        DataType::Float64 => "double",
+        DataType::Int16 | DataType::Int32 => "int32",
+        DataType::Int64 => "int64",
+        DataType::TimestampWithoutTimeZone | DataType::TimestampWithTimeZone => {
+            TIMESTAMP_TYPE
+        }
+        DataType::Array(_)
+        | DataType::Cidr
+        | DataType::Date
+        | DataType::Decimal
+        | DataType::DecimalWithPrecision(_)
+        | DataType::GeoJson(_)
+        | DataType::Inet
+        | DataType::Interval
+        | DataType::Json
+        | DataType::MacAddr
+        | DataType::OneOf(_)
+        | DataType::Other(_)
+        | DataType::Struct(_)
+        | DataType::Text
+        | DataType::TextWithMaxLength(_)
+        | DataType::Time
+        | DataType::Uuid => "string",
+    }
+}
+
+/// Convert a table name like `public.my_table` into a message name like
+/// `MyTable`.
+fn message_name(table_name: &str) -> String {
+    let base_name = table_name.rsplit('.').next().unwrap_or(table_name);
+    let mut name = String::with_capacity(base_name.len());
+    let mut capitalize_next = true;
+    for c in base_name.chars() {
+        if c.is_ascii_alphanumeric() {
+            if capitalize_next {
+                name.push(c.to_ascii_uppercase());
+            } else {
+                name.push(c);
+            }
+            capitalize_next = false;
+        } else {
+            capitalize_next = true;
+        }
+    }
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name.insert(0, 'T');
+    }
+    name
+}
+
+/// Convert a column name into a valid Protocol Buffers field name.
+fn field_name(column_name: &str) -> String {
+    let mut name = column_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name.insert_str(0, "f_");
+    }
+    name
+}
+
+#[test]
+fn message_and_field_names() {
+    assert_eq!(message_name("public.my_table"), "MyTable");
+    assert_eq!(message_name("events-2019"), "Events2019");
+    assert_eq!(message_name("1st"), "T1st");
+    assert_eq!(field_name("UserId"), "userid");
+    assert_eq!(field_name("first name"), "first_name");
+    assert_eq!(field_name("2fa"), "f_2fa");
+}
+
+#[test]
+fn write_proto_for_table() {
+    let table = Table {
+        name: "public.users".to_owned(),
+        columns: vec![
+            Column {
+                name: "id".to_owned(),
+                is_nullable: false,
+                data_type: DataType::Int64,
+                comment: Some("Primary key.".to_owned()),
+                default: None,
+                collation: None,
+            },
+            Column {
+                name: "name".to_owned(),
+                is_nullable: true,
+                data_type: DataType::Text,
+                comment: None,
+                default: None,
+                collation: None,
+            },
+            Column {
+                name: "scores".to_owned(),
+                is_nullable: true,
+                data_type: DataType::Array(Box::new(DataType::Float64)),
+                comment: None,
+                default: None,
+                collation: None,
+            },
+            Column {
+                name: "created_at".to_owned(),
+                is_nullable: true,
+                data_type: DataType::TimestampWithTimeZone,
+                comment: None,
+                default: None,
+                collation: None,
+            },
+        ],
+        primary_key: vec![],
+        indexes: vec![],
+    };
+    let mut out = vec![];
+    write_proto(&mut out, &table).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        r#"syntax = "proto3";
+
+import "google/protobuf/timestamp.proto";
+
+message Users {
+  // Primary key.
+  int64 id = 1;
+  optional string name = 2;
+  repeated double scores = 3;
+  google.protobuf.Timestamp created_at = 4;
+}
+"#,
+    );
+}
+
+#[test]
+fn nested_arrays_are_rejected() {
+    let data_type =
+        DataType::Array(Box::new(DataType::Array(Box::new(DataType::Text))));
+    assert!(ProtobufFieldType::for_data_type(&data_type).is_err());
+}
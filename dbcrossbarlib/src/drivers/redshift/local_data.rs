@@ -1,7 +1,9 @@
-//! Helper for reading data from BigQuery.
+//! Helper for reading data from Redshift.
 
 use super::RedshiftLocator;
+use crate::clouds::object_tags::ObjectTags;
 use crate::common::*;
+use crate::compression::Compression;
 use crate::drivers::s3::find_s3_temp_dir;
 
 /// Implementation of `local_data`, but as a real `async` function.
@@ -14,7 +16,9 @@ pub(crate) async fn local_data_helper(
     // Build a temporary location.
     let shared_args_v = shared_args.clone().verify(RedshiftLocator::features())?;
     let s3_temp = find_s3_temp_dir(shared_args_v.temporary_storage())?;
-    let s3_dest_args = DestinationArguments::for_temporary();
+    // `UNLOAD` writes several files in parallel, and we compress them to
+    // save time and money moving them through S3.
+    let s3_dest_args = ObjectTags::temporary_dest_args(None, Some(Compression::Gzip));
     let s3_source_args = SourceArguments::for_temporary();
 
     // Extract from Redshift to s3://.
@@ -29,7 +33,7 @@ pub(crate) async fn local_data_helper(
         )
         .await?;
 
-    // Copy from a temporary gs:// location.
+    // Copy from a temporary s3:// location, decompressing as we go.
     let from_temp_ctx = ctx.child(o!("from_temp" => s3_temp.to_string()));
     s3_temp
         .local_data(from_temp_ctx, shared_args, s3_source_args)
@@ -0,0 +1,382 @@
+//! Redshift-specific table layout options, like `DISTKEY` and `SORTKEY`.
+
+use std::{fmt, str::FromStr};
+
+use crate::common::*;
+use crate::drivers::postgres_shared::{
+    pg_quote, Ident, PgColumn, PgCreateTable, TableName,
+};
+use crate::schema::{Collation, ColumnDefault};
+use crate::separator::Separator;
+
+/// The `--to-arg` keys we use for table layout. These are handled by
+/// `dbcrossbar`, and should not be passed to `COPY` as credentials.
+pub(crate) const TABLE_LAYOUT_ARGS: &[&str] = &[
+    "diststyle",
+    "distkey",
+    "sortkey",
+    "sortkey_style",
+    "column_encodings",
+];
+
+/// The column compression encodings supported by Redshift.
+const ENCODINGS: &[&str] = &[
+    "raw",
+    "az64",
+    "bytedict",
+    "delta",
+    "delta32k",
+    "lzo",
+    "mostly8",
+    "mostly16",
+    "mostly32",
+    "runlength",
+    "text255",
+    "text32k",
+    "zstd",
+];
+
+/// How should Redshift distribute rows between nodes?
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum DistStyle {
+    /// Let Redshift choose.
+    Auto,
+    /// Distribute rows round-robin.
+    Even,
+    /// Distribute rows according to `DISTKEY`.
+    Key,
+    /// Copy the entire table to every node.
+    All,
+}
+
+impl fmt::Display for DistStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DistStyle::Auto => write!(f, "AUTO"),
+            DistStyle::Even => write!(f, "EVEN"),
+            DistStyle::Key => write!(f, "KEY"),
+            DistStyle::All => write!(f, "ALL"),
+        }
+    }
+}
+
+impl FromStr for DistStyle {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match &s.to_ascii_lowercase()[..] {
+            "auto" => Ok(DistStyle::Auto),
+            "even" => Ok(DistStyle::Even),
+            "key" => Ok(DistStyle::Key),
+            "all" => Ok(DistStyle::All),
+            _ => Err(format_err!("unknown Redshift diststyle {:?}", s)),
+        }
+    }
+}
+
+/// How should Redshift combine multiple sort key columns?
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum SortKeyStyle {
+    /// Sort by the first column, then the second, and so on.
+    Compound,
+    /// Give equal weight to each column.
+    Interleaved,
+}
+
+impl fmt::Display for SortKeyStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SortKeyStyle::Compound => write!(f, "COMPOUND"),
+            SortKeyStyle::Interleaved => write!(f, "INTERLEAVED"),
+        }
+    }
+}
+
+impl FromStr for SortKeyStyle {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match &s.to_ascii_lowercase()[..] {
+            "compound" => Ok(SortKeyStyle::Compound),
+            "interleaved" => Ok(SortKeyStyle::Interleaved),
+            _ => Err(format_err!("unknown Redshift sortkey_style {:?}", s)),
+        }
+    }
+}
+
+/// Layout options to use when creating a Redshift table.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct TableLayout {
+    /// How to distribute rows between nodes.
+    diststyle: Option<DistStyle>,
+    /// The column to distribute rows by.
+    distkey: Option<String>,
+    /// The columns to sort by.
+    sortkey: Vec<String>,
+    /// How to combine multiple `sortkey` columns.
+    sortkey_style: Option<SortKeyStyle>,
+    /// Compression encodings for individual columns.
+    column_encodings: Vec<(String, String)>,
+}
+
+impl TableLayout {
+    /// Parse our layout options from `--to-arg` values.
+    pub(crate) fn from_driver_args(args: &DriverArguments) -> Result<TableLayout> {
+        let mut layout = TableLayout::default();
+        for (k, v) in args.iter() {
+            match k {
+                "diststyle" => layout.diststyle = Some(v.parse()?),
+                "distkey" => layout.distkey = Some(v.to_owned()),
+                "sortkey" => layout.sortkey = split_list(v),
+                "sortkey_style" => layout.sortkey_style = Some(v.parse()?),
+                "column_encodings" => {
+                    for item in split_list(v) {
+                        let (col, encoding) = item
+                            .rsplit_once(':')
+                            .ok_or_else(|| {
+                                format_err!(
+                                    "expected column_encodings=col:encoding,..., found {:?}",
+                                    item,
+                                )
+                            })?;
+                        let encoding = encoding.trim().to_ascii_lowercase();
+                        if !ENCODINGS.contains(&&encoding[..]) {
+                            return Err(format_err!(
+                                "unknown Redshift column encoding {:?}",
+                                encoding,
+                            ));
+                        }
+                        layout
+                            .column_encodings
+                            .push((col.trim().to_owned(), encoding));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Check for inconsistent options.
+        match (layout.diststyle, &layout.distkey) {
+            (None, Some(_)) => layout.diststyle = Some(DistStyle::Key),
+            (Some(DistStyle::Key), None) => {
+                return Err(format_err!("diststyle=key requires a distkey"));
+            }
+            (Some(style), Some(_)) if style != DistStyle::Key => {
+                return Err(format_err!(
+                    "distkey cannot be used with diststyle={}",
+                    style.to_string().to_ascii_lowercase(),
+                ));
+            }
+            _ => {}
+        }
+        if layout.sortkey_style.is_some() && layout.sortkey.is_empty() {
+            return Err(format_err!("sortkey_style requires a sortkey"));
+        }
+        Ok(layout)
+    }
+
+    /// Make sure all the columns we refer to exist in `table`.
+    fn validate(&self, table: &PgCreateTable) -> Result<()> {
+        let columns = self
+            .distkey
+            .iter()
+            .chain(self.sortkey.iter())
+            .chain(self.column_encodings.iter().map(|(col, _)| col));
+        for col in columns {
+            if !table.columns.iter().any(|c| &c.name == col) {
+                return Err(format_err!(
+                    "cannot find column {:?} in Redshift table {}",
+                    col,
+                    table.name,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Generate `CREATE TABLE` SQL for `table` using this layout.
+    pub(crate) fn create_table_sql(&self, table: &PgCreateTable) -> Result<String> {
+        self.validate(table)?;
+        let mut out = vec![];
+        write!(&mut out, "CREATE")?;
+        if table.temporary {
+            write!(&mut out, " TEMPORARY")?;
+        }
+        write!(&mut out, " TABLE")?;
+        if table.if_not_exists {
+            write!(&mut out, " IF NOT EXISTS")?;
+        }
+        writeln!(&mut out, " {} (", TableName(&table.name))?;
+        let mut sep = Separator::new(",\n");
+        for col in &table.columns {
+            write!(&mut out, "{}    ", sep.display())?;
+            self.write_column_sql(&mut out, col)?;
+        }
+        if !table.primary_key.is_empty() {
+            write!(&mut out, "{}    PRIMARY KEY (", sep.display())?;
+            let mut key_sep = Separator::new(", ");
+            for name in &table.primary_key {
+                write!(&mut out, "{}{}", key_sep.display(), Ident(name))?;
+            }
+            write!(&mut out, ")")?;
+        }
+        if !table.columns.is_empty() {
+            writeln!(&mut out)?;
+        }
+        write!(&mut out, ")")?;
+        if let Some(diststyle) = self.diststyle {
+            write!(&mut out, "\nDISTSTYLE {}", diststyle)?;
+        }
+        if let Some(distkey) = &self.distkey {
+            write!(&mut out, "\nDISTKEY ({})", Ident(distkey))?;
+        }
+        if !self.sortkey.is_empty() {
+            writeln!(&mut out)?;
+            if let Some(sortkey_style) = self.sortkey_style {
+                write!(&mut out, "{} ", sortkey_style)?;
+            }
+            write!(&mut out, "SORTKEY (")?;
+            let mut key_sep = Separator::new(", ");
+            for name in &self.sortkey {
+                write!(&mut out, "{}{}", key_sep.display(), Ident(name))?;
+            }
+            write!(&mut out, ")")?;
+        }
+        writeln!(&mut out, ";")?;
+        Ok(String::from_utf8(out).expect("generated SQL should always be UTF-8"))
+    }
+
+    /// Generate `ALTER TABLE ... ADD COLUMN` SQL for adding `col` to `table`.
+    pub(crate) fn add_column_sql(
+        &self,
+        table: &PgCreateTable,
+        col: &PgColumn,
+    ) -> Result<String> {
+        let mut out = vec![];
+        write!(
+            &mut out,
+            "ALTER TABLE {} ADD COLUMN ",
+            TableName(&table.name)
+        )?;
+        self.write_column_sql(&mut out, col)?;
+        Ok(String::from_utf8(out).expect("generated SQL should always be UTF-8"))
+    }
+
+    /// Write the declaration of `col`. Redshift wants `ENCODE` after the
+    /// default and before any constraints, so we can't just use `PgColumn`'s
+    /// `Display`.
+    fn write_column_sql(&self, out: &mut dyn Write, col: &PgColumn) -> Result<()> {
+        write!(out, "{} {}", Ident(&col.name), col.data_type)?;
+        // Redshift only supports case-insensitive collation, not named
+        // collations.
+        if let Some(Collation::CaseInsensitive) = &col.collation {
+            write!(out, " COLLATE CASE_INSENSITIVE")?;
+        }
+        if let Some(ColumnDefault::Literal(value)) = &col.default {
+            write!(out, " DEFAULT {}", pg_quote(value))?;
+        }
+        if let Some((_, encoding)) =
+            self.column_encodings.iter().find(|(c, _)| c == &col.name)
+        {
+            write!(out, " ENCODE {}", encoding)?;
+        }
+        if !col.is_nullable {
+            write!(out, " NOT NULL")?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn create_table_sql_includes_case_insensitive_collation() {
+    let table = "CREATE TABLE users (email citext NOT NULL, name text);"
+        .parse::<PgCreateTable>()
+        .unwrap();
+    let layout = TableLayout::default();
+    assert_eq!(
+        layout.create_table_sql(&table).unwrap(),
+        r#"CREATE TABLE "users" (
+    "email" text COLLATE CASE_INSENSITIVE NOT NULL,
+    "name" text
+);
+"#,
+    );
+}
+
+/// Split a comma-separated list, ignoring whitespace and empty items.
+fn split_list(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .map(|item| item.to_owned())
+        .collect()
+}
+
+#[test]
+fn create_table_sql_includes_layout() {
+    let table = "CREATE TABLE events (
+    id bigint NOT NULL,
+    account_id bigint,
+    created_at timestamp,
+    body text
+);"
+    .parse::<PgCreateTable>()
+    .unwrap();
+    let args = DriverArguments::from_cli_args(&[
+        "iam_role=arn:aws:iam::123:role/x".to_owned(),
+        "distkey=account_id".to_owned(),
+        "sortkey=created_at,id".to_owned(),
+        "sortkey_style=interleaved".to_owned(),
+        "column_encodings=body:ZSTD,created_at:az64".to_owned(),
+    ])
+    .unwrap();
+    let layout = TableLayout::from_driver_args(&args).unwrap();
+    assert_eq!(
+        layout.create_table_sql(&table).unwrap(),
+        r#"CREATE TABLE "events" (
+    "id" bigint NOT NULL,
+    "account_id" bigint,
+    "created_at" timestamp without time zone ENCODE az64,
+    "body" text ENCODE zstd
+)
+DISTSTYLE KEY
+DISTKEY ("account_id")
+INTERLEAVED SORTKEY ("created_at", "id");
+"#,
+    );
+
+    let bad_args = [
+        "diststyle=key",
+        "diststyle=even,distkey=id",
+        "sortkey_style=compound",
+        "column_encodings=body:gzip",
+        "column_encodings=body",
+    ];
+    for bad in &bad_args {
+        let args = DriverArguments::from_cli_args(
+            &bad.split(',').map(|s| s.to_owned()).collect::<Vec<_>>(),
+        )
+        .unwrap();
+        assert!(TableLayout::from_driver_args(&args).is_err(), "{}", bad);
+    }
+
+    let args =
+        DriverArguments::from_cli_args(&["sortkey=missing".to_owned()]).unwrap();
+    let layout = TableLayout::from_driver_args(&args).unwrap();
+    assert!(layout.create_table_sql(&table).is_err());
+}
+
+#[test]
+fn add_column_sql_uses_encodings() {
+    let table = "CREATE TABLE events (id bigint NOT NULL, body text);"
+        .parse::<PgCreateTable>()
+        .unwrap();
+    let args =
+        DriverArguments::from_cli_args(&["column_encodings=body:zstd".to_owned()])
+            .unwrap();
+    let layout = TableLayout::from_driver_args(&args).unwrap();
+    assert_eq!(
+        layout.add_column_sql(&table, &table.columns[1]).unwrap(),
+        r#"ALTER TABLE "events" ADD COLUMN "body" text ENCODE zstd"#,
+    );
+}
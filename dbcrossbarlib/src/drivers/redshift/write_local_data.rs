@@ -1,9 +1,10 @@
 //! Implementation of `write_local_data` for Redshift.
 
-use super::RedshiftLocator;
+use super::{temporary_compression, RedshiftLocator};
+use crate::clouds::object_tags::ObjectTags;
 use crate::common::*;
 use crate::drivers::s3::find_s3_temp_dir;
-use crate::tokio_glue::ConsumeWithParallelism;
+use crate::parallelism::BufferWithParallelism;
 
 /// Implementation of `write_local_data`, but as a real `async` function.
 pub(crate) async fn write_local_data_helper(
@@ -16,7 +17,17 @@ pub(crate) async fn write_local_data_helper(
     // Build a temporary location.
     let shared_args_v = shared_args.clone().verify(RedshiftLocator::features())?;
     let s3_temp = find_s3_temp_dir(shared_args_v.temporary_storage())?;
-    let s3_dest_args = DestinationArguments::for_temporary();
+    let dest_args_v = dest_args.clone().verify(RedshiftLocator::features())?;
+    let object_tags = dest_args_v
+        .driver_args()
+        .iter()
+        .find(|(k, _)| *k == "object_tags")
+        .map(|(_, v)| v.parse::<ObjectTags>())
+        .transpose()
+        .context("could not parse --to-arg")?;
+    let compression = temporary_compression(dest_args_v.driver_args())?;
+    let s3_dest_args =
+        ObjectTags::temporary_dest_args(object_tags.as_ref(), compression);
     let s3_source_args = SourceArguments::for_temporary();
 
     // Copy to a temporary s3:// location.
@@ -25,13 +36,11 @@ pub(crate) async fn write_local_data_helper(
         .write_local_data(to_temp_ctx, data, shared_args.clone(), s3_dest_args)
         .await?;
 
-    // Wait for all s3:// uploads to finish with controllable parallelism.
-    //
-    // TODO: This duplicates our top-level `cp` code and we need to implement
-    // the same rules for picking a good argument to `consume_with_parallelism`
-    // and not just hard code our parallelism.
+    // Wait for all s3:// uploads to finish, using the same parallelism rules
+    // as our top-level `cp` code.
     result_stream
-        .consume_with_parallelism(shared_args_v.max_streams())
+        .buffer_with_parallelism(&ctx, &shared_args_v)
+        .try_collect::<Vec<_>>()
         .await?;
 
     // Load from s3:// to Redshift.
@@ -1,13 +1,24 @@
 //! Implementation of `RedshiftLocator::write_remote_data`.
 
-use super::{credentials_sql, RedshiftLocator};
+use itertools::Itertools;
+
+use super::{
+    credentials_sql, table_layout::TableLayout, temporary_compression, RedshiftLocator,
+};
 use crate::common::*;
 use crate::drivers::{
-    postgres::{connect, prepare_table},
-    postgres_shared::{pg_quote, CheckCatalog, PgCreateTable, TableName},
+    postgres::{
+        add_missing_columns, connect, prepare_table_with_sql, temp_table_for, Client,
+    },
+    postgres_shared::{
+        pg_quote, CheckCatalog, Ident, PgColumn, PgCreateTable, PgDataType,
+        PgScalarDataType, TableName,
+    },
     s3::S3Locator,
 };
-use crate::schema::{Column, DataType};
+use crate::evolve_schema::EvolveSchema;
+use crate::if_exists::upsert_update_columns;
+use crate::schema::{Column, ColumnDefault, DataType};
 
 /// Copy `source` to `dest` using `schema`.
 ///
@@ -41,36 +52,246 @@ pub(crate) async fn write_remote_data_helper(
     let schema = shared_args.schema();
     let to_args = dest_args.driver_args();
     let if_exists = dest_args.if_exists().to_owned();
+    let table_layout = TableLayout::from_driver_args(to_args)?;
+    let evolve_schema = EvolveSchema::from_driver_args(to_args)?;
+    let compression = temporary_compression(to_args)?;
 
-    // Try to look up our table schema in the database.
+    // Connect to Redshift, and add any missing columns if asked.
     schema.verify_redshift_can_import_from_csv()?;
-    let table_name = dest.table_name();
-    let pg_create_table = PgCreateTable::from_pg_catalog_or_default(
+    let table_name = dest.table_name()?;
+    let mut client = connect(ctx.clone(), dest.url().to_owned()).await?;
+    if EvolveSchema::adds_columns(evolve_schema, &if_exists) {
+        add_missing_columns(
+            &ctx,
+            &mut client,
+            dest.url(),
+            table_name,
+            schema,
+            |table, col| {
+                let mut col = col.to_owned();
+                adjust_column_for_redshift(&mut col);
+                table_layout.add_column_sql(table, &col)
+            },
+        )
+        .await?;
+    }
+
+    // Try to look up our table schema in the database.
+    let mut pg_create_table = PgCreateTable::from_pg_catalog_or_default(
         CheckCatalog::from(&if_exists),
         dest.url(),
-        &table_name,
+        table_name,
         schema,
     )
     .await?;
 
-    // Connect to Redshift and prepare our table.
-    let mut client = connect(ctx.clone(), dest.url().to_owned()).await?;
-    prepare_table(&ctx, &mut client, pg_create_table.clone(), &if_exists).await?;
+    // Redshift doesn't support secondary indexes, so the best we can do is to
+    // let the user know.
+    let create_indexes = to_args
+        .iter()
+        .any(|(k, v)| k == "create_indexes" && v == "true");
+    if create_indexes && !schema.indexes.is_empty() {
+        warn!(
+            ctx.log(),
+            "Redshift does not support secondary indexes, so not creating {}",
+            schema.indexes.iter().map(|i| &i.name).join(", "),
+        );
+    }
+
+    for col in &mut pg_create_table.columns {
+        adjust_column_for_redshift(col);
+    }
+
+    // Prepare our table.
+    prepare_table_with_sql(
+        &ctx,
+        &mut client,
+        pg_create_table.clone(),
+        &if_exists,
+        |table| table_layout.create_table_sql(table),
+    )
+    .await?;
+
+    // Redshift has no `ON CONFLICT`, so to upsert, we load our data into a
+    // temporary table and then update and insert from there.
+    let upsert = match &if_exists {
+        IfExists::Upsert(keys, update) => {
+            let temp_table = temp_table_for(&pg_create_table)?;
+            let create_sql = table_layout.create_table_sql(&temp_table)?;
+            debug!(ctx.log(), "CREATE TABLE SQL: {}", create_sql);
+            client
+                .batch_execute(create_sql.as_str())
+                .await
+                .with_context(|_| format!("error creating {}", temp_table.name))?;
+            Some((temp_table, keys, update.as_deref()))
+        }
+        _ => None,
+    };
+    let copy_table = match &upsert {
+        Some((temp_table, _, _)) => temp_table,
+        None => &pg_create_table,
+    };
 
     // Ask RedShift to import from S3.
-    let copy_sql = format!(
+    let mut copy_sql = format!(
         "COPY {dest} FROM {source}\n{credentials}FORMAT CSV\nIGNOREHEADER 1\nDATEFORMAT 'auto'\nTIMEFORMAT 'auto'",
-        dest = TableName(table_name),
+        dest = TableName(&copy_table.name),
         source = pg_quote(source_url.as_str()), // `$1` doesn't work here.
         credentials = credentials_sql(to_args)?,
     );
+    if let Some(compression) = compression {
+        copy_sql.push_str(&format!("\n{}", compression.redshift_copy_option()));
+    }
     let copy_stmt = client.prepare(&copy_sql).await?;
     client.execute(&copy_stmt, &[]).await.with_context(|_| {
-        format!("error copying {} from {}", pg_create_table.name, source_url)
+        format!("error copying {} from {}", copy_table.name, source_url)
     })?;
+
+    if let Some((temp_table, keys, update)) = &upsert {
+        upsert_from(&ctx, &client, temp_table, &pg_create_table, keys, *update)
+            .await?;
+    }
     Ok(vec![dest.boxed()])
 }
 
+/// Generate SQL to upsert all rows from `src_table` into `dest_table` using
+/// `upsert_keys`, updating `update_columns` if specified. We update existing
+/// rows first, and then insert any rows whose keys don't match.
+fn upsert_sql(
+    src_table: &PgCreateTable,
+    dest_table: &PgCreateTable,
+    upsert_keys: &[String],
+    update_columns: Option<&[String]>,
+) -> Result<Vec<String>> {
+    let columns = dest_table
+        .columns
+        .iter()
+        .map(|c| &c.name[..])
+        .collect::<Vec<_>>();
+    for key in upsert_keys {
+        if !columns.contains(&&key[..]) {
+            return Err(format_err!(
+                "cannot upsert on column {:?} because it isn't in the table",
+                key,
+            ));
+        }
+    }
+    let value_columns = upsert_update_columns(&columns, upsert_keys, update_columns)?;
+    let dest_name = TableName(&dest_table.name);
+    let src_name = TableName(&src_table.name);
+    let keys_match = upsert_keys
+        .iter()
+        .map(|k| format!("src.{key} = {dest}.{key}", key = Ident(k), dest = dest_name))
+        .join(" AND ");
+
+    let mut statements = vec![];
+    if !value_columns.is_empty() {
+        statements.push(format!(
+            "UPDATE {dest} SET {updates} FROM {src} AS src WHERE {keys_match}",
+            dest = dest_name,
+            updates = value_columns
+                .iter()
+                .map(|c| format!("{name} = src.{name}", name = Ident(c)))
+                .join(", "),
+            src = src_name,
+            keys_match = keys_match,
+        ));
+    }
+    statements.push(format!(
+        "INSERT INTO {dest} ({all_columns}) SELECT {src_columns} FROM {src} AS src WHERE NOT EXISTS (SELECT 1 FROM {dest} WHERE {keys_match})",
+        dest = dest_name,
+        all_columns = columns.iter().map(|c| Ident(c)).join(", "),
+        src_columns = columns.iter().map(|c| format!("src.{}", Ident(c))).join(", "),
+        src = src_name,
+        keys_match = keys_match,
+    ));
+    Ok(statements)
+}
+
+/// Upsert all rows from `src_table` into `dest_table`, in a single
+/// transaction, and then drop `src_table`, because our connection may be
+/// reused.
+async fn upsert_from(
+    ctx: &Context,
+    client: &Client,
+    src_table: &PgCreateTable,
+    dest_table: &PgCreateTable,
+    upsert_keys: &[String],
+    update_columns: Option<&[String]>,
+) -> Result<()> {
+    let sql = format!(
+        "BEGIN;\n{};\nCOMMIT;\nDROP TABLE {};",
+        upsert_sql(src_table, dest_table, upsert_keys, update_columns)?.join(";\n"),
+        TableName(&src_table.name),
+    );
+    debug!(
+        ctx.log(),
+        "upserting from {} to {} with {}", src_table.name, dest_table.name, sql,
+    );
+    client.batch_execute(sql.as_str()).await.with_context(|_| {
+        format!(
+            "error upserting from {} to {}",
+            src_table.name, dest_table.name,
+        )
+    })?;
+    Ok(())
+}
+
+#[test]
+fn upsert_sql_updates_then_inserts() {
+    let dest =
+        "CREATE TABLE public.users (id bigint NOT NULL, name text, email text);"
+            .parse::<PgCreateTable>()
+            .unwrap();
+    let src = temp_table_for(&dest).unwrap();
+    let src_name = TableName(&src.name).to_string();
+    let keys = vec!["id".to_owned()];
+
+    let sql = upsert_sql(&src, &dest, &keys, None).unwrap();
+    assert_eq!(
+        sql,
+        vec![
+            format!(
+                r#"UPDATE "public"."users" SET "name" = src."name", "email" = src."email" FROM {} AS src WHERE src."id" = "public"."users"."id""#,
+                src_name,
+            ),
+            format!(
+                r#"INSERT INTO "public"."users" ("id", "name", "email") SELECT src."id", src."name", src."email" FROM {} AS src WHERE NOT EXISTS (SELECT 1 FROM "public"."users" WHERE src."id" = "public"."users"."id")"#,
+                src_name,
+            ),
+        ],
+    );
+
+    let update = vec!["email".to_owned()];
+    let sql = upsert_sql(&src, &dest, &keys, Some(&update)).unwrap();
+    assert!(sql[0].contains(r#"SET "email" = src."email" FROM"#));
+
+    assert!(upsert_sql(&src, &dest, &["missing".to_owned()], None).is_err());
+}
+
+/// The longest `VARCHAR` Redshift supports, in bytes.
+const REDSHIFT_MAX_VARCHAR_BYTES: u32 = 65535;
+
+/// Remove column features that Redshift doesn't support.
+fn adjust_column_for_redshift(col: &mut PgColumn) {
+    // Redshift doesn't support `CHECK` constraints, so store `one_of` columns
+    // as plain text.
+    if let PgDataType::Scalar(ty @ PgScalarDataType::TextOneOf(_)) = &mut col.data_type
+    {
+        *ty = PgScalarDataType::Text;
+    }
+    // Redshift measures `VARCHAR` lengths in bytes, not characters, and a
+    // UTF-8 character may take up to 4 bytes.
+    if let PgDataType::Scalar(PgScalarDataType::Varchar(len)) = &mut col.data_type {
+        *len = len.saturating_mul(4).min(REDSHIFT_MAX_VARCHAR_BYTES);
+    }
+    // Redshift spells `CURRENT_TIMESTAMP` as `GETDATE()` in defaults, so we
+    // leave it out rather than generating Redshift-specific SQL.
+    if col.default == Some(ColumnDefault::CurrentTimestamp) {
+        col.default = None;
+    }
+}
+
 /// Extension trait for verifying Redshift compatibility.
 trait VerifyRedshiftCanImportFromCsv {
     /// Can Redshift import the data described by this type from a CSV file?
@@ -105,14 +326,26 @@ impl VerifyRedshiftCanImportFromCsv for DataType {
             | DataType::Int16
             | DataType::Int32
             | DataType::Int64
+            | DataType::OneOf(_)
             | DataType::Text
+            | DataType::TextWithMaxLength(_)
+            | DataType::Time
             | DataType::TimestampWithoutTimeZone
             | DataType::TimestampWithTimeZone => Ok(()),
+            // Redshift supports `DECIMAL(p,s)`, but only up to 38 digits.
+            DataType::DecimalWithPrecision(p) if p.precision <= 38 => Ok(()),
             DataType::Array(_)
+            | DataType::Bytes
+            | DataType::Cidr
             | DataType::Decimal
+            | DataType::DecimalWithPrecision(_)
             | DataType::GeoJson(_)
+            | DataType::Inet
+            | DataType::Interval
             | DataType::Json
+            | DataType::MacAddr
             | DataType::Other(_)
+            | DataType::Struct(_)
             | DataType::Uuid => Err(format_err!(
                 "Redshift driver does not support data type {:?}",
                 self
@@ -120,3 +353,27 @@ impl VerifyRedshiftCanImportFromCsv for DataType {
         }
     }
 }
+
+#[test]
+fn varchar_lengths_are_measured_in_bytes() {
+    let mut col = PgColumn {
+        name: "name".to_owned(),
+        data_type: PgDataType::Scalar(PgScalarDataType::Varchar(255)),
+        is_nullable: true,
+        default: None,
+        comment: None,
+        collation: None,
+    };
+    adjust_column_for_redshift(&mut col);
+    assert_eq!(
+        col.data_type,
+        PgDataType::Scalar(PgScalarDataType::Varchar(1020)),
+    );
+
+    col.data_type = PgDataType::Scalar(PgScalarDataType::Varchar(20_000));
+    adjust_column_for_redshift(&mut col);
+    assert_eq!(
+        col.data_type,
+        PgDataType::Scalar(PgScalarDataType::Varchar(65535)),
+    );
+}
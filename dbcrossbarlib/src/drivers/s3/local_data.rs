@@ -7,9 +7,30 @@ use tokio::{io::BufReader, process::Command};
 
 use super::S3Locator;
 use crate::common::*;
+use crate::compression::Compression;
 use crate::csv_stream::csv_stream_name;
+use crate::limit::limit_csv_streams;
+use crate::row_filter::filter_csv_streams;
+use crate::select::select_csv_columns;
 use crate::tokio_glue::copy_reader_to_stream;
 
+/// Implementation of `list_files`, but as a real `async` function.
+pub(crate) async fn list_files_helper(
+    ctx: Context,
+    url: Url,
+) -> Result<Option<Vec<BoxLocator>>> {
+    // Only directories contain more than one file.
+    if !url.path().ends_with('/') {
+        return Ok(None);
+    }
+    let file_urls = ls(&ctx, &url).await?.try_collect::<Vec<_>>().await?;
+    let locators = file_urls
+        .into_iter()
+        .map(|file_url| Ok(file_url.as_str().parse::<S3Locator>()?.boxed()))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Some(locators))
+}
+
 /// Implementation of `local_data`, but as a real `async` function.
 pub(crate) async fn local_data_helper(
     ctx: Context,
@@ -17,45 +38,27 @@ pub(crate) async fn local_data_helper(
     shared_args: SharedArguments<Unverified>,
     source_args: SourceArguments<Unverified>,
 ) -> Result<Option<BoxStream<CsvStream>>> {
-    let _shared_args = shared_args.verify(S3Locator::features())?;
-    let _source_args = source_args.verify(S3Locator::features())?;
+    let shared_args = shared_args.verify(S3Locator::features())?;
+    let source_args = source_args.verify(S3Locator::features())?;
+    let table = shared_args.schema().clone();
+    let where_clause = source_args.where_clause().map(|w| w.to_owned());
+    let limit = source_args.limit();
+    let select_columns = source_args.select_columns();
 
     debug!(ctx.log(), "getting CSV files from {}", url);
-
-    // Start a child process to list files at that URL.
-    debug!(ctx.log(), "listing {}", url);
-    let mut child = Command::new("aws")
-        .args(&["s3", "ls", "--recursive", url.as_str()])
-        .stdout(Stdio::piped())
-        .spawn()
-        .context("error running `aws s3 ls`")?;
-    let child_stdout = child.stdout.take().expect("child should have stdout");
-    ctx.spawn_process(format!("aws s3 ls {}", url), child);
-
-    // Parse `ls` output into lines, and convert into `CsvStream` values lazily
-    // in case there are a lot of CSV files we need to read.
-    //
-    // XXX - This will fail (either silently or noisily, I'm not sure) if there
-    // are 1000+ files in the S3 directory, and we can't fix this without
-    // switching from `aws s3` to native S3 API calls from Rust.
-    let lines = BufReader::with_capacity(BUFFER_SIZE, child_stdout)
-        .lines()
-        .map_err(|e| format_err!("error reading `aws s3 ls` output: {}", e));
-    let csv_streams = lines.and_then(move |line| {
+    let file_urls = ls(&ctx, &url).await?;
+    let filter_ctx = ctx.clone();
+    let csv_streams = file_urls.and_then(move |file_url| {
         let ctx = ctx.clone();
         let url = url.clone();
         async move {
-            trace!(ctx.log(), "`aws s3 ls` line: {}", line);
-            let bucket_url = bucket_url(&url)?;
-            let path = path_from_line(&line)?;
-            let file_url = bucket_url.join(&path)?;
-
             // Stream the file from the cloud.
             let name = csv_stream_name(url.as_str(), file_url.as_str())?;
             let ctx = ctx.child(
                 o!("stream" => name.to_owned(), "url" => file_url.as_str().to_owned()),
             );
             debug!(ctx.log(), "streaming from {} using `aws s3 cp`", file_url);
+            ctx.usage().record_api_call("s3");
             let mut child = Command::new("aws")
                 .args(&["s3", "cp", file_url.as_str(), "-"])
                 .stdout(Stdio::piped())
@@ -63,23 +66,73 @@ pub(crate) async fn local_data_helper(
                 .context("error running `aws s3 cp`")?;
             let child_stdout = child.stdout.take().expect("child should have stdout");
             let child_stdout = BufReader::with_capacity(BUFFER_SIZE, child_stdout);
-            let data = copy_reader_to_stream(ctx.clone(), child_stdout)?;
+            let mut data = copy_reader_to_stream(ctx.clone(), child_stdout)?.boxed();
             ctx.spawn_process(format!("aws s3 cp {} -", file_url), child);
+            if let Some(compression) = Compression::for_file_name(file_url.path()) {
+                data = compression.decompress_stream(&ctx, data)?;
+            }
 
             // Assemble everything into a CSV stream.
             Ok(CsvStream {
                 name: name.to_owned(),
-                data: data.boxed(),
+                data,
             })
         }
         .boxed()
     });
 
-    Ok(Some(csv_streams.boxed()))
+    // We have no database to filter rows or select columns for us, so do it
+    // ourselves.
+    let columns = table.columns.clone();
+    let csv_streams = filter_csv_streams(
+        filter_ctx.clone(),
+        table,
+        where_clause,
+        csv_streams.boxed(),
+    )?;
+    let csv_streams = limit_csv_streams(filter_ctx.clone(), limit, csv_streams);
+    if select_columns {
+        Ok(Some(select_csv_columns(filter_ctx, columns, csv_streams)))
+    } else {
+        Ok(Some(csv_streams))
+    }
+}
+
+/// List all the files at the specified `s3://` URL, recursively.
+pub(crate) async fn ls(ctx: &Context, url: &Url) -> Result<BoxStream<Url>> {
+    // Start a child process to list files at that URL.
+    debug!(ctx.log(), "listing {}", url);
+    ctx.usage().record_api_call("s3");
+    let mut child = Command::new("aws")
+        .args(["s3", "ls", "--recursive", url.as_str()])
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("error running `aws s3 ls`")?;
+    let child_stdout = child.stdout.take().expect("child should have stdout");
+    ctx.spawn_process(format!("aws s3 ls {}", url), child);
+
+    // Parse `ls` output into lines, and convert them into URLs lazily in case
+    // there are a lot of files.
+    //
+    // XXX - This will fail (either silently or noisily, I'm not sure) if there
+    // are 1000+ files in the S3 directory, and we can't fix this without
+    // switching from `aws s3` to native S3 API calls from Rust.
+    let ctx = ctx.clone();
+    let bucket_url = bucket_url(url)?;
+    let file_urls = BufReader::with_capacity(BUFFER_SIZE, child_stdout)
+        .lines()
+        .map_err(|e| format_err!("error reading `aws s3 ls` output: {}", e))
+        .and_then(move |line| {
+            trace!(ctx.log(), "`aws s3 ls` line: {}", line);
+            let file_url =
+                path_from_line(&line).and_then(|path| Ok(bucket_url.join(&path)?));
+            async move { file_url }
+        });
+    Ok(file_urls.boxed())
 }
 
 /// Given an S3 URL, get the URL for just the bucket itself.
-fn bucket_url(url: &Url) -> Result<Url> {
+pub(super) fn bucket_url(url: &Url) -> Result<Url> {
     let bucket = url
         .host()
         .ok_or_else(|| format_err!("could not find bucket name in {}", url))?;
@@ -106,7 +159,7 @@ fn bucket_url_extracts_bucket() {
 }
 
 /// Given a line of `aws s3 ls` output, extract the path.
-fn path_from_line(line: &str) -> Result<String> {
+pub(super) fn path_from_line(line: &str) -> Result<String> {
     lazy_static! {
         static ref RE: Regex = Regex::new(r#"^[-0-9]+ [:0-9]+ +[0-9]+ ([^\r\n]+)"#)
             .expect("invalid regex in source");
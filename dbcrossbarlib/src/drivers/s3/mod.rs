@@ -1,9 +1,16 @@
 //! Support for Amazon's S3.
 
+use serde::Deserialize;
 use std::{fmt, str::FromStr};
 
+use crate::clouds::object_tags::ObjectTags;
 use crate::common::*;
+use crate::compression::Compression;
+use crate::count_rows::count_local_data_helper;
+use crate::driver_args::deserialize_optional_from_str;
 use crate::drivers::redshift::RedshiftLocator;
+use crate::encryption::EncryptRecipient;
+use crate::name_template::NameTemplate;
 
 mod local_data;
 mod prepare_as_destination;
@@ -11,12 +18,52 @@ mod signing;
 mod write_local_data;
 mod write_remote_data;
 
-use local_data::local_data_helper;
-pub(crate) use prepare_as_destination::prepare_as_destination_helper;
+use local_data::{list_files_helper, local_data_helper};
+pub(crate) use prepare_as_destination::{prepare_as_destination_helper, rmdir};
 pub(crate) use signing::{sign_s3_url, AwsCredentials};
 use write_local_data::write_local_data_helper;
 use write_remote_data::write_remote_data_helper;
 
+/// Parsed version of `--to-arg` values.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct S3DestinationArguments {
+    /// Tags to set on each object we write.
+    #[serde(default, deserialize_with = "deserialize_optional_from_str")]
+    object_tags: Option<ObjectTags>,
+
+    /// Compress each object we write.
+    #[serde(default, deserialize_with = "deserialize_optional_from_str")]
+    compression: Option<Compression>,
+
+    /// Encrypt each object we write for this `age` or PGP recipient.
+    #[serde(default, deserialize_with = "deserialize_optional_from_str")]
+    encrypt_recipient: Option<EncryptRecipient>,
+
+    /// A template for the names of the files we write.
+    #[serde(default, deserialize_with = "deserialize_optional_from_str")]
+    name_template: Option<NameTemplate>,
+
+    /// Write a Hive-style directory for each value of this column.
+    #[serde(default)]
+    partition_by: Option<String>,
+}
+
+impl S3DestinationArguments {
+    /// Parse our destination arguments.
+    fn from_dest_args(dest_args: &DestinationArguments<Verified>) -> Result<Self> {
+        Ok(dest_args
+            .driver_args()
+            .deserialize::<S3DestinationArguments>()
+            .context("could not parse --to-arg")?)
+    }
+
+    /// The tags to apply to each object we write.
+    fn object_tags(&self) -> ObjectTags {
+        self.object_tags.clone().unwrap_or_default()
+    }
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct S3Locator {
     url: Url,
@@ -61,6 +108,15 @@ impl Locator for S3Locator {
         self
     }
 
+    fn count(
+        &self,
+        ctx: Context,
+        shared_args: SharedArguments<Unverified>,
+        source_args: SourceArguments<Unverified>,
+    ) -> BoxFuture<usize> {
+        count_local_data_helper(ctx, self.clone(), shared_args, source_args).boxed()
+    }
+
     fn local_data(
         &self,
         ctx: Context,
@@ -70,6 +126,18 @@ impl Locator for S3Locator {
         local_data_helper(ctx, self.url.clone(), shared_args, source_args).boxed()
     }
 
+    fn rewrites_data_locally(&self) -> bool {
+        true
+    }
+
+    fn list_files(&self, ctx: Context) -> BoxFuture<Option<Vec<BoxLocator>>> {
+        list_files_helper(ctx, self.url.clone()).boxed()
+    }
+
+    fn remove(&self, ctx: Context) -> BoxFuture<()> {
+        rmdir(ctx, self.url.clone()).boxed()
+    }
+
     fn write_local_data(
         &self,
         ctx: Context,
@@ -115,10 +183,14 @@ impl LocatorStatic for S3Locator {
 
     fn features() -> Features {
         Features {
-            locator: LocatorFeatures::LocalData | LocatorFeatures::WriteLocalData,
+            locator: LocatorFeatures::LocalData
+                | LocatorFeatures::WriteLocalData
+                | LocatorFeatures::Count
+                | LocatorFeatures::Remove,
             write_schema_if_exists: EnumSet::empty(),
-            source_args: EnumSet::empty(),
-            dest_args: EnumSet::empty(),
+            source_args: SourceArgumentsFeatures::WhereClause
+                | SourceArgumentsFeatures::Limit,
+            dest_args: DestinationArgumentsFeatures::DriverArgs.into(),
             dest_if_exists: IfExistsFeatures::Overwrite.into(),
             _placeholder: (),
         }
@@ -1,6 +1,6 @@
 //! Preparing bucket directories as output destinations.
 
-use std::process::Stdio;
+use std::process::{ExitStatus, Stdio};
 use tokio::process::Command;
 
 use crate::common::*;
@@ -13,21 +13,13 @@ pub(crate) async fn prepare_as_destination_helper(
 ) -> Result<()> {
     // Delete the existing output, if it exists.
     if if_exists == IfExists::Overwrite {
-        // Delete all the files under `self.url`.
-        debug!(ctx.log(), "deleting existing {}", s3_url);
         if !s3_url.path().ends_with('/') {
             return Err(format_err!(
                 "can only write to s3:// URL ending in '/', got {}",
                 s3_url,
             ));
         }
-        let status = Command::new("aws")
-            .args(&["s3", "rm", "--recursive", s3_url.as_str()])
-            // Throw away stdout so it doesn't corrupt our output.
-            .stdout(Stdio::null())
-            .status()
-            .await
-            .context("error running `aws s3`")?;
+        let status = delete_contents(&ctx, &s3_url).await?;
         if !status.success() {
             warn!(
                 ctx.log(),
@@ -43,3 +35,36 @@ pub(crate) async fn prepare_as_destination_helper(
         ))
     }
 }
+
+/// Recursively delete an `s3://` directory without deleting the bucket.
+pub(crate) async fn rmdir(ctx: Context, s3_url: Url) -> Result<()> {
+    if !s3_url.path().ends_with('/') {
+        return Err(format_err!(
+            "can only remove s3:// URL ending in '/', got {}",
+            s3_url,
+        ));
+    }
+    let status = delete_contents(&ctx, &s3_url).await?;
+    if !status.success() {
+        return Err(format_err!(
+            "could not delete contents of {} (`aws s3 rm` failed with {})",
+            s3_url,
+            status,
+        ));
+    }
+    Ok(())
+}
+
+/// Run `aws s3 rm` to delete all the files under `s3_url`.
+async fn delete_contents(ctx: &Context, s3_url: &Url) -> Result<ExitStatus> {
+    debug!(ctx.log(), "deleting existing {}", s3_url);
+    ctx.usage().record_api_call("s3");
+    let status = Command::new("aws")
+        .args(["s3", "rm", "--recursive", s3_url.as_str()])
+        // Throw away stdout so it doesn't corrupt our output.
+        .stdout(Stdio::null())
+        .status()
+        .await
+        .context("error running `aws s3`")?;
+    Ok(status)
+}
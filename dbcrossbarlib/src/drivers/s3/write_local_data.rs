@@ -3,8 +3,11 @@
 use std::process::Stdio;
 use tokio::process::Command;
 
-use super::{prepare_as_destination_helper, S3Locator};
+use super::{prepare_as_destination_helper, S3DestinationArguments, S3Locator};
+use crate::clouds::object_tags::ObjectTags;
 use crate::common::*;
+use crate::name_template::output_file_name;
+use crate::partition::partition_csv_streams;
 use crate::tokio_glue::copy_stream_to_writer;
 
 /// Implementation of `write_local_data`, but as a real `async` function.
@@ -15,26 +18,65 @@ pub(crate) async fn write_local_data_helper(
     shared_args: SharedArguments<Unverified>,
     dest_args: DestinationArguments<Unverified>,
 ) -> Result<BoxStream<BoxFuture<BoxLocator>>> {
-    let _shared_args = shared_args.verify(S3Locator::features())?;
+    let shared_args = shared_args.verify(S3Locator::features())?;
     let dest_args = dest_args.verify(S3Locator::features())?;
 
     // Look up our arguments.
     let if_exists = dest_args.if_exists().to_owned();
+    let s3_dest_args = S3DestinationArguments::from_dest_args(&dest_args)?;
+    let compression = s3_dest_args.compression;
+    let encrypt_recipient = s3_dest_args.encrypt_recipient.clone();
+    let name_template = s3_dest_args.name_template.clone();
+    let mut object_tags = s3_dest_args.object_tags();
+    object_tags.mark_if_temporary(&dest_args)?;
+
+    // Split our data into partitions, if asked to.
+    let data = match &s3_dest_args.partition_by {
+        Some(column) => partition_csv_streams(
+            ctx.clone(),
+            shared_args.schema(),
+            column.to_owned(),
+            data,
+        )?,
+        None => data,
+    };
 
     // Delete the existing output, if it exists.
     prepare_as_destination_helper(ctx.clone(), url.clone(), if_exists).await?;
 
     // Spawn our uploader threads.
+    let mut shard = 0;
     let written = data.map_ok(move |stream| {
+        let file_name = output_file_name(name_template.as_ref(), &stream.name, shard);
+        shard += 1;
         let url = url.clone();
         let ctx = ctx.clone();
+        let object_tags = object_tags.clone();
+        let encrypt_recipient = encrypt_recipient.clone();
         async move {
-            let url = url.join(&format!("{}.csv", stream.name))?;
+            let mut file_name = file_name;
+            let mut data = stream.data;
+            if let Some(compression) = compression {
+                file_name = compression.compressed_file_name(&file_name);
+            }
+            if let Some(recipient) = &encrypt_recipient {
+                file_name = recipient.encrypted_file_name(&file_name);
+            }
+            let url = url.join(&file_name)?;
             let ctx = ctx
                 .child(o!("stream" => stream.name.clone(), "url" => url.to_string()));
+            // Compress before we encrypt, because encrypted data won't
+            // compress.
+            if let Some(compression) = compression {
+                data = compression.compress_stream(&ctx, data)?;
+            }
+            if let Some(recipient) = &encrypt_recipient {
+                data = recipient.encrypt_stream(&ctx, data)?;
+            }
 
             // Run `aws cp - $URL` as a background process.
             debug!(ctx.log(), "uploading stream to `aws s3`");
+            ctx.usage().record_api_call("s3");
             let mut child = Command::new("aws")
                 .args(&["s3", "cp", "-", url.as_str()])
                 .stdin(Stdio::piped())
@@ -45,7 +87,7 @@ pub(crate) async fn write_local_data_helper(
             let child_stdin = child.stdin.take().expect("child should have stdin");
 
             // Copy data to our child process.
-            copy_stream_to_writer(ctx.clone(), stream.data, child_stdin)
+            copy_stream_to_writer(ctx.clone(), data, child_stdin)
                 .await
                 .context("error copying data to `aws s3`")?;
 
@@ -53,14 +95,52 @@ pub(crate) async fn write_local_data_helper(
             let status = child
                 .await
                 .with_context(|_| format!("error finishing upload to {}", url))?;
-            if status.success() {
-                Ok(S3Locator { url }.boxed())
-            } else {
-                Err(format_err!("`aws s3` returned error: {}", status))
+            if !status.success() {
+                return Err(format_err!("`aws s3` returned error: {}", status));
             }
+            put_object_tagging(&ctx, &url, &object_tags).await?;
+            Ok(S3Locator { url }.boxed())
         }
         .boxed()
     });
 
     Ok(written.boxed())
 }
+
+/// Apply `object_tags` to the S3 object at `url`.
+pub(super) async fn put_object_tagging(
+    ctx: &Context,
+    url: &Url,
+    object_tags: &ObjectTags,
+) -> Result<()> {
+    if object_tags.is_empty() {
+        return Ok(());
+    }
+    let bucket = url
+        .host_str()
+        .ok_or_else(|| format_err!("could not find bucket name in {}", url))?;
+    let key = url.path().trim_start_matches('/');
+    debug!(ctx.log(), "tagging S3 object {}", url);
+    ctx.usage().record_api_call("s3");
+    let status = Command::new("aws")
+        .args([
+            "s3api",
+            "put-object-tagging",
+            "--bucket",
+            bucket,
+            "--key",
+            key,
+            "--tagging",
+            &object_tags.s3_tagging_json(),
+        ])
+        // Throw away stdout so it doesn't corrupt our output.
+        .stdout(Stdio::null())
+        .status()
+        .await
+        .context("error running `aws s3api`")?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format_err!("`aws s3api` returned error: {}", status))
+    }
+}
@@ -1,6 +1,15 @@
 //! Implementation of `GsLocator::write_remote_data`.
 
-use super::{prepare_as_destination_helper, S3Locator};
+use std::process::Stdio;
+use tokio::process::Command;
+
+use super::{
+    local_data::{bucket_url, path_from_line},
+    prepare_as_destination_helper,
+    write_local_data::put_object_tagging,
+    S3DestinationArguments, S3Locator,
+};
+use crate::clouds::object_tags::ObjectTags;
 use crate::common::*;
 use crate::drivers::{
     postgres::connect,
@@ -35,13 +44,36 @@ pub(crate) async fn write_remote_data_helper(
     let schema = shared_args.schema();
     let from_args = source_args.driver_args();
     let if_exists = dest_args.if_exists().to_owned();
+    let s3_dest_args = S3DestinationArguments::from_dest_args(&dest_args)?;
+    if s3_dest_args.object_tags.is_some() {
+        return Err(format_err!(
+            "cannot set object_tags on files unloaded by Redshift"
+        ));
+    }
+    if s3_dest_args.encrypt_recipient.is_some() {
+        return Err(format_err!(
+            "cannot set encrypt_recipient on files unloaded by Redshift"
+        ));
+    }
+    if s3_dest_args.name_template.is_some() {
+        return Err(format_err!(
+            "cannot set name_template on files unloaded by Redshift"
+        ));
+    }
+    if s3_dest_args.partition_by.is_some() {
+        return Err(format_err!(
+            "cannot set partition_by on files unloaded by Redshift"
+        ));
+    }
+    let mut object_tags = ObjectTags::default();
+    object_tags.mark_if_temporary(&dest_args)?;
 
     // Delete the existing output, if it exists.
     prepare_as_destination_helper(ctx.clone(), dest.as_url().to_owned(), if_exists)
         .await?;
 
     // Convert our schema to a native PostgreSQL schema.
-    let table_name = source.table_name();
+    let table_name = source.table_name()?;
     let pg_create_table = PgCreateTable::from_pg_catalog_or_default(
         // Always check the catalog, because `if_exists` is for our S3
         // destination, not for Redshift source.
@@ -54,22 +86,62 @@ pub(crate) async fn write_remote_data_helper(
 
     // Generate SQL for query.
     let mut sql_bytes: Vec<u8> = vec![];
-    pg_create_table.write_export_select_sql(&mut sql_bytes, &source_args)?;
-    let select_sql = String::from_utf8(sql_bytes).expect("should always be UTF-8");
+    pg_create_table.write_export_select_sql(&mut sql_bytes, &source_args, schema)?;
+    let mut select_sql = String::from_utf8(sql_bytes).expect("should always be UTF-8");
+    if source_args.limit().is_some() {
+        // `UNLOAD` doesn't allow `LIMIT` in the outermost `SELECT`.
+        select_sql = format!("SELECT * FROM ({}) AS limited", select_sql);
+    }
     debug!(ctx.log(), "export SQL: {}", select_sql);
 
     // Export as CSV.
     let client = connect(ctx.clone(), source.url().to_owned()).await?;
-    let unload_sql = format!(
+    let mut unload_sql = format!(
         "UNLOAD ({source}) TO {dest}\n{credentials}HEADER FORMAT CSV",
         source = pg_quote(&select_sql),
         dest = pg_quote(dest.as_url().as_str()),
         credentials = credentials_sql(from_args)?,
     );
+    if let Some(compression) = s3_dest_args.compression {
+        // Redshift adds the file extension itself.
+        unload_sql.push_str(&format!("\n{}", compression.redshift_copy_option()));
+    }
     let unload_stmt = client.prepare(&unload_sql).await?;
     client
         .execute(&unload_stmt, &[])
         .await
         .with_context(|_| format!("error copying {} to {}", table_name, dest))?;
+
+    // Redshift can't tag the files it unloads, so tag them ourselves.
+    if object_tags.is_temporary() {
+        tag_unloaded_files(&ctx, dest.as_url(), &object_tags).await?;
+    }
     Ok(vec![dest.boxed()])
 }
+
+/// Apply `object_tags` to every file under the `s3://` directory `url`.
+async fn tag_unloaded_files(
+    ctx: &Context,
+    url: &Url,
+    object_tags: &ObjectTags,
+) -> Result<()> {
+    debug!(ctx.log(), "listing {}", url);
+    ctx.usage().record_api_call("s3");
+    let output = Command::new("aws")
+        .args(["s3", "ls", "--recursive", url.as_str()])
+        .stderr(Stdio::inherit())
+        .output()
+        .await
+        .context("error running `aws s3 ls`")?;
+    if !output.status.success() {
+        return Err(format_err!("`aws s3 ls` returned error: {}", output.status));
+    }
+    let listing = String::from_utf8(output.stdout)
+        .context("`aws s3 ls` output was not UTF-8")?;
+    let bucket_url = bucket_url(url)?;
+    for line in listing.lines().filter(|l| !l.is_empty()) {
+        let file_url = bucket_url.join(&path_from_line(line)?)?;
+        put_object_tagging(ctx, &file_url, object_tags).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,96 @@
+//! Driver for reading ESRI shapefiles.
+
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use crate::common::*;
+use crate::drivers::ogr_shared;
+
+/// An ESRI shapefile. The `.dbf` and `.prj` files must be located next to the
+/// `.shp` file.
+#[derive(Clone, Debug)]
+pub struct ShapefileLocator {
+    path: PathBuf,
+}
+
+impl fmt::Display for ShapefileLocator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", Self::scheme(), self.path.display())
+    }
+}
+
+impl FromStr for ShapefileLocator {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if !s.starts_with(Self::scheme()) {
+            return Err(format_err!(
+                "expected {} locator, found {}",
+                Self::scheme(),
+                s
+            ));
+        }
+        let path = &s[Self::scheme().len()..];
+        if path.is_empty() || path == "-" {
+            return Err(format_err!("{} requires a file path", Self::scheme()));
+        }
+        Ok(ShapefileLocator {
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+impl Locator for ShapefileLocator {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self, ctx: Context) -> BoxFuture<Option<Table>> {
+        let source = self.to_owned();
+        async move {
+            let table = ogr_shared::schema(&ctx, &source.path, None).await?;
+            Ok(Some(table))
+        }
+        .boxed()
+    }
+
+    fn local_data(
+        &self,
+        ctx: Context,
+        shared_args: SharedArguments<Unverified>,
+        source_args: SourceArguments<Unverified>,
+    ) -> BoxFuture<Option<BoxStream<CsvStream>>> {
+        local_data_helper(ctx, self.to_owned(), shared_args, source_args).boxed()
+    }
+}
+
+impl LocatorStatic for ShapefileLocator {
+    fn scheme() -> &'static str {
+        "shapefile:"
+    }
+
+    fn features() -> Features {
+        Features {
+            locator: LocatorFeatures::Schema | LocatorFeatures::LocalData,
+            write_schema_if_exists: EnumSet::empty(),
+            source_args: SourceArgumentsFeatures::WhereClause.into(),
+            dest_args: EnumSet::empty(),
+            dest_if_exists: EnumSet::empty(),
+            _placeholder: (),
+        }
+    }
+}
+
+/// Implementation of `local_data`, but as a real `async` function.
+async fn local_data_helper(
+    ctx: Context,
+    source: ShapefileLocator,
+    shared_args: SharedArguments<Unverified>,
+    source_args: SourceArguments<Unverified>,
+) -> Result<Option<BoxStream<CsvStream>>> {
+    let _shared_args = shared_args.verify(ShapefileLocator::features())?;
+    let source_args = source_args.verify(ShapefileLocator::features())?;
+    let data =
+        ogr_shared::local_data(ctx, &source.path, None, source_args.where_clause())
+            .await?;
+    Ok(Some(data))
+}
@@ -0,0 +1,144 @@
+//! Handling sources which contain no data.
+//!
+//! An empty source might produce no CSV streams at all, or streams containing
+//! no bytes, not even a header. Many destinations can't create a table from
+//! that. So when the user passes `--allow-empty`, we replace missing or empty
+//! streams with a stream containing just the CSV header.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crate::common::*;
+use crate::sample_data::sample_csv_stream;
+
+/// Make sure that `streams` contains at least one stream, and that none of
+/// those streams are completely empty, by adding CSV headers for `table`.
+pub fn fill_empty_csv_streams(
+    ctx: Context,
+    table: Table,
+    streams: BoxStream<CsvStream>,
+) -> BoxStream<CsvStream> {
+    let saw_stream = Arc::new(AtomicBool::new(false));
+
+    // Fill in any streams which contain no data.
+    let filled = {
+        let ctx = ctx.clone();
+        let table = table.clone();
+        let saw_stream = saw_stream.clone();
+        streams.and_then(move |stream| {
+            saw_stream.store(true, Ordering::SeqCst);
+            fill_empty_csv_stream(ctx.clone(), table.clone(), stream)
+        })
+    };
+
+    // If there were no streams at all, add one. `chain` doesn't look at this
+    // until `filled` is finished.
+    let missing = stream::once(async move {
+        if saw_stream.load(Ordering::SeqCst) {
+            Ok(None)
+        } else {
+            debug!(
+                ctx.log(),
+                "source contained no data streams, so writing only a header",
+            );
+            Ok(Some(sample_csv_stream(&table, 0)?))
+        }
+    })
+    .try_filter_map(|stream| async { Ok(stream) });
+
+    filled.chain(missing).boxed()
+}
+
+/// If `stream` contains no data, replace it with a CSV header for `table`.
+async fn fill_empty_csv_stream(
+    ctx: Context,
+    table: Table,
+    stream: CsvStream,
+) -> Result<CsvStream> {
+    let name = stream.name;
+    let mut data = stream.data;
+    loop {
+        match data.next().await {
+            // Skip any empty chunks at the start of the stream.
+            Some(Ok(bytes)) if bytes.is_empty() => {}
+            // We have data, so put it back.
+            Some(first) => {
+                return Ok(CsvStream {
+                    name,
+                    data: stream::once(async { first }).chain(data).boxed(),
+                });
+            }
+            // We reached the end of the stream without seeing any data.
+            None => {
+                debug!(
+                    ctx.log(),
+                    "stream {} is empty, so writing only a header", name,
+                );
+                let header = sample_csv_stream(&table, 0)?;
+                return Ok(CsvStream {
+                    name,
+                    data: header.data,
+                });
+            }
+        }
+    }
+}
+
+#[test]
+fn empty_sources_get_headers() {
+    use crate::schema::{Column, DataType};
+
+    let table = Table {
+        name: "t".to_owned(),
+        columns: vec![Column {
+            name: "id".to_owned(),
+            is_nullable: true,
+            data_type: DataType::Int32,
+            comment: None,
+            default: None,
+            collation: None,
+        }],
+        primary_key: vec![],
+        indexes: vec![],
+    };
+
+    let (ctx, worker_fut) = Context::create_for_test("fill_empty_csv_streams");
+    let cmd_fut = async move {
+        let collect = |streams: BoxStream<CsvStream>| {
+            let ctx = ctx.clone();
+            let table = table.clone();
+            async move {
+                let streams = fill_empty_csv_streams(ctx.clone(), table, streams)
+                    .try_collect::<Vec<_>>()
+                    .await?;
+                let mut outputs = vec![];
+                for stream in streams {
+                    outputs.push(stream.into_bytes(ctx.clone()).await?);
+                }
+                Ok::<_, Error>(outputs)
+            }
+        };
+
+        // No streams at all.
+        let outputs = collect(stream::empty().boxed()).await?;
+        assert_eq!(outputs, vec![BytesMut::from(&b"id\n"[..])]);
+
+        // An empty stream and a stream with data.
+        let streams = vec![
+            Ok(CsvStream::from_bytes(&b""[..]).await),
+            Ok(CsvStream::from_bytes(&b"id\n1\n"[..]).await),
+        ];
+        let outputs = collect(stream::iter(streams).boxed()).await?;
+        assert_eq!(
+            outputs,
+            vec![
+                BytesMut::from(&b"id\n"[..]),
+                BytesMut::from(&b"id\n1\n"[..]),
+            ],
+        );
+        Ok(())
+    };
+    run_futures_with_runtime(cmd_fut.boxed(), worker_fut).unwrap();
+}
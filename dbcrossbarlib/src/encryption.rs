@@ -0,0 +1,158 @@
+//! Encrypting the files we write, using `age` or `gpg`.
+//!
+//! These are specified using `--to-arg=encrypt_recipient=...`. Recipients
+//! beginning with `age1` are treated as `age` public keys, and everything else
+//! is passed to `gpg --recipient` as a key ID, fingerprint or email address.
+//! We shell out to the corresponding tool and encrypt each stream as we write
+//! it, so no unencrypted data ever touches the destination.
+
+use std::{fmt, process::Stdio, str::FromStr};
+use tokio::process::Command;
+
+use crate::common::*;
+use crate::tokio_glue::{copy_reader_to_stream, copy_stream_to_writer};
+
+/// Who should be able to decrypt the files we write?
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum EncryptRecipient {
+    /// An `age` public key, like `age1...`.
+    Age(String),
+    /// A PGP key ID, fingerprint or email address known to `gpg`.
+    Pgp(String),
+}
+
+impl EncryptRecipient {
+    /// The extension to add to the names of encrypted files, including the
+    /// leading `.`.
+    pub(crate) fn file_extension(&self) -> &'static str {
+        match self {
+            EncryptRecipient::Age(_) => ".age",
+            EncryptRecipient::Pgp(_) => ".gpg",
+        }
+    }
+
+    /// Add our file extension to `name`.
+    pub(crate) fn encrypted_file_name(&self, name: &str) -> String {
+        format!("{}{}", name, self.file_extension())
+    }
+
+    /// Build a command which reads plaintext on standard input and writes
+    /// ciphertext on standard output.
+    fn command(&self) -> Command {
+        match self {
+            EncryptRecipient::Age(recipient) => {
+                let mut command = Command::new("age");
+                command.args(["--encrypt", "--recipient", recipient]);
+                command
+            }
+            EncryptRecipient::Pgp(recipient) => {
+                let mut command = Command::new("gpg");
+                command.args([
+                    "--batch",
+                    "--quiet",
+                    "--trust-model",
+                    "always",
+                    "--encrypt",
+                    "--recipient",
+                    recipient,
+                    "--output",
+                    "-",
+                ]);
+                command
+            }
+        }
+    }
+
+    /// The name of the tool we use to encrypt data.
+    fn tool_name(&self) -> &'static str {
+        match self {
+            EncryptRecipient::Age(_) => "age",
+            EncryptRecipient::Pgp(_) => "gpg",
+        }
+    }
+
+    /// Encrypt `data` in the background, returning a stream of ciphertext.
+    pub(crate) fn encrypt_stream(
+        &self,
+        ctx: &Context,
+        data: BoxStream<BytesMut>,
+    ) -> Result<BoxStream<BytesMut>> {
+        let tool_name = self.tool_name();
+        debug!(
+            ctx.log(),
+            "encrypting stream with `{}` for {}", tool_name, self
+        );
+        let mut child = self
+            .command()
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|_| format!("error running `{}`", tool_name))?;
+        let child_stdin = child.stdin.take().expect("child should have stdin");
+        let child_stdout = child.stdout.take().expect("child should have stdout");
+        ctx.spawn_process(format!("`{}` for {}", tool_name, self), child);
+
+        // Feed our plaintext to the child process. Dropping `child_stdin` at
+        // the end of the copy tells the child we're done.
+        let worker_ctx = ctx.clone();
+        ctx.spawn_worker(
+            async move {
+                copy_stream_to_writer(worker_ctx, data, child_stdin)
+                    .await
+                    .with_context(|_| {
+                        format!("error copying data to `{}`", tool_name)
+                    })?;
+                Ok(())
+            }
+            .boxed(),
+        );
+
+        Ok(copy_reader_to_stream(ctx.clone(), child_stdout)?.boxed())
+    }
+}
+
+impl fmt::Display for EncryptRecipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncryptRecipient::Age(recipient) | EncryptRecipient::Pgp(recipient) => {
+                recipient.fmt(f)
+            }
+        }
+    }
+}
+
+impl FromStr for EncryptRecipient {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s.is_empty() {
+            Err(format_err!("encrypt_recipient cannot be empty"))
+        } else if s.starts_with("age1") {
+            Ok(EncryptRecipient::Age(s.to_owned()))
+        } else {
+            Ok(EncryptRecipient::Pgp(s.to_owned()))
+        }
+    }
+}
+
+#[test]
+fn parses_recipients() {
+    let age = "age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p"
+        .parse::<EncryptRecipient>()
+        .unwrap();
+    assert_eq!(
+        age,
+        EncryptRecipient::Age(
+            "age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p"
+                .to_owned()
+        ),
+    );
+    assert_eq!(age.encrypted_file_name("data.csv"), "data.csv.age");
+
+    let pgp = "partner@example.com".parse::<EncryptRecipient>().unwrap();
+    assert_eq!(pgp, EncryptRecipient::Pgp("partner@example.com".to_owned()),);
+    assert_eq!(pgp.encrypted_file_name("data.csv"), "data.csv.gpg");
+
+    assert!("".parse::<EncryptRecipient>().is_err());
+}
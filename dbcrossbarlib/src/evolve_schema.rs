@@ -0,0 +1,80 @@
+//! Changing the schema of an existing destination table to match our data.
+
+use std::{fmt, str::FromStr};
+
+use crate::common::*;
+
+/// How should we change an existing destination table whose schema doesn't
+/// match ours? This is passed as `--to-arg=evolve_schema=...`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum EvolveSchema {
+    /// Add any columns which are missing from the destination table, as
+    /// nullable columns.
+    AddColumns,
+}
+
+impl EvolveSchema {
+    /// Should we add missing columns when writing with `if_exists`? We only
+    /// change tables that we're going to keep.
+    pub(crate) fn adds_columns(
+        evolve_schema: Option<EvolveSchema>,
+        if_exists: &IfExists,
+    ) -> bool {
+        evolve_schema == Some(EvolveSchema::AddColumns)
+            && matches!(
+                if_exists,
+                IfExists::Append | IfExists::Upsert(..) | IfExists::Merge(..)
+            )
+    }
+
+    /// Look up `evolve_schema` in `args`, for drivers which don't deserialize
+    /// their arguments.
+    pub(crate) fn from_driver_args(
+        args: &DriverArguments,
+    ) -> Result<Option<EvolveSchema>> {
+        args.iter()
+            .find(|(k, _)| *k == "evolve_schema")
+            .map(|(_, v)| v.parse())
+            .transpose()
+    }
+}
+
+impl fmt::Display for EvolveSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvolveSchema::AddColumns => write!(f, "add_columns"),
+        }
+    }
+}
+
+impl FromStr for EvolveSchema {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "add_columns" => Ok(EvolveSchema::AddColumns),
+            _ => Err(format_err!(
+                "unknown evolve_schema value {:?} (expected add_columns)",
+                s,
+            )),
+        }
+    }
+}
+
+#[test]
+fn adds_columns_only_to_kept_tables() {
+    let add = Some(EvolveSchema::AddColumns);
+    assert!(EvolveSchema::adds_columns(add, &IfExists::Append));
+    assert!(EvolveSchema::adds_columns(
+        add,
+        &IfExists::Upsert(vec!["id".to_owned()], None),
+    ));
+    assert!(!EvolveSchema::adds_columns(add, &IfExists::Overwrite));
+    assert!(!EvolveSchema::adds_columns(None, &IfExists::Append));
+
+    let args =
+        DriverArguments::from_cli_args(&["evolve_schema=add_columns".to_owned()])
+            .unwrap();
+    assert_eq!(EvolveSchema::from_driver_args(&args).unwrap(), add);
+    assert!("drop_columns".parse::<EvolveSchema>().is_err());
+}
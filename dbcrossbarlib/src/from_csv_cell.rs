@@ -1,6 +1,6 @@
 //! Parsing values found in CSV cells.
 
-use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use geo_types::Geometry;
 use geojson::GeoJson;
 use lazy_static::lazy_static;
@@ -10,6 +10,8 @@ use std::convert::TryInto;
 use uuid::Uuid;
 
 use crate::common::*;
+use crate::interval::Interval;
+use crate::network::{Cidr, Inet, MacAddr};
 
 /// Parse a value found in a CSV cell. This is analogous to Rust's built-in
 /// [`FromStr`] trait, but it follws the rules of our CSV interchange format.
@@ -80,6 +82,14 @@ fn parse_bool() {
     assert!(bool::from_csv_cell("10").is_err());
 }
 
+/// Binary data is base64-encoded in our CSV interchange format.
+impl FromCsvCell for Vec<u8> {
+    fn from_csv_cell(cell: &str) -> Result<Self> {
+        Ok(base64::decode(cell)
+            .with_context(|_| format!("cannot parse {:?} as base64", cell))?)
+    }
+}
+
 impl FromCsvCell for NaiveDate {
     fn from_csv_cell(cell: &str) -> Result<Self> {
         Ok(cell
@@ -88,6 +98,14 @@ impl FromCsvCell for NaiveDate {
     }
 }
 
+impl FromCsvCell for NaiveTime {
+    fn from_csv_cell(cell: &str) -> Result<Self> {
+        Ok(cell
+            .parse::<NaiveTime>()
+            .with_context(|_| format!("cannot parse {:?} as time", cell))?)
+    }
+}
+
 impl FromCsvCell for f32 {
     fn from_csv_cell(cell: &str) -> Result<Self> {
         Ok(cell
@@ -160,6 +178,30 @@ impl FromCsvCell for i64 {
     }
 }
 
+impl FromCsvCell for Interval {
+    fn from_csv_cell(cell: &str) -> Result<Self> {
+        cell.parse::<Interval>()
+    }
+}
+
+impl FromCsvCell for Inet {
+    fn from_csv_cell(cell: &str) -> Result<Self> {
+        cell.parse::<Inet>()
+    }
+}
+
+impl FromCsvCell for Cidr {
+    fn from_csv_cell(cell: &str) -> Result<Self> {
+        cell.parse::<Cidr>()
+    }
+}
+
+impl FromCsvCell for MacAddr {
+    fn from_csv_cell(cell: &str) -> Result<Self> {
+        cell.parse::<MacAddr>()
+    }
+}
+
 impl FromCsvCell for serde_json::Value {
     fn from_csv_cell(cell: &str) -> Result<Self> {
         Ok(serde_json::from_str(cell)
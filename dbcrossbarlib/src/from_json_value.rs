@@ -1,13 +1,15 @@
 //! Construct various types from parsed JSON values.
 
 use cast;
-use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use geo_types::Geometry;
 use serde_json::Value;
 use uuid::Uuid;
 
 use crate::common::*;
 use crate::from_csv_cell::FromCsvCell;
+use crate::interval::Interval;
+use crate::network::{Cidr, Inet, MacAddr};
 
 /// Construct this type from a `serde_json::Value`.
 pub(crate) trait FromJsonValue: FromCsvCell {
@@ -40,6 +42,8 @@ impl FromJsonValue for bool {
     }
 }
 
+impl FromJsonValue for Vec<u8> {}
+
 impl FromJsonValue for NaiveDate {}
 
 impl FromJsonValue for f32 {
@@ -119,6 +123,14 @@ impl FromJsonValue for i64 {
     }
 }
 
+impl FromJsonValue for Interval {}
+
+impl FromJsonValue for Inet {}
+
+impl FromJsonValue for Cidr {}
+
+impl FromJsonValue for MacAddr {}
+
 impl FromJsonValue for Value {
     fn from_json_value(json: &Value) -> Result<Self> {
         Ok(json.to_owned())
@@ -127,6 +139,8 @@ impl FromJsonValue for Value {
 
 impl FromJsonValue for NaiveDateTime {}
 
+impl FromJsonValue for NaiveTime {}
+
 impl FromJsonValue for DateTime<FixedOffset> {}
 
 impl FromJsonValue for DateTime<Utc> {}
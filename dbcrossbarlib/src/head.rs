@@ -0,0 +1,135 @@
+//! Previewing the first few rows of a data source.
+
+use std::io;
+
+use crate::common::*;
+use crate::tokio_glue::{run_sync_fn_in_background, SyncStreamReader};
+
+/// The first few rows of a data source, as returned by `Locator::head`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Preview {
+    /// The names of our columns.
+    pub columns: Vec<String>,
+    /// Our rows, with one string per column. `NULL` values are represented as
+    /// empty strings, just as in our CSV interchange format.
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Preview {
+    /// Read up to `limit` rows from `streams`, stopping as soon as we have
+    /// enough. We read the streams one at a time, and we never open any
+    /// streams we don't need.
+    pub(crate) async fn from_csv_streams(
+        ctx: Context,
+        mut streams: BoxStream<CsvStream>,
+        limit: usize,
+    ) -> Result<Preview> {
+        let mut preview = Preview::default();
+        while preview.rows.len() < limit {
+            let stream = match streams.next().await {
+                Some(stream) => stream?,
+                None => break,
+            };
+            let name = stream.name.clone();
+            debug!(ctx.log(), "reading preview rows from stream {}", name);
+            let rdr = SyncStreamReader::new(ctx.clone(), stream.data);
+            preview = run_sync_fn_in_background("head".to_owned(), move || {
+                preview.read_csv(&name, rdr, limit)?;
+                Ok(preview)
+            })
+            .await?;
+        }
+        Ok(preview)
+    }
+
+    /// Append rows from the CSV data in `rdr` until we have `limit` rows.
+    ///
+    /// This is synchronous, and should be run in a background thread.
+    fn read_csv<R: io::Read>(
+        &mut self,
+        stream: &str,
+        rdr: R,
+        limit: usize,
+    ) -> Result<()> {
+        let read_err = || format!("cannot read CSV from stream {}", stream);
+        let mut rdr = csv::Reader::from_reader(rdr);
+        if self.columns.is_empty() {
+            let headers = rdr.headers().with_context(|_| read_err())?;
+            self.columns = headers.iter().map(|h| h.to_owned()).collect();
+        }
+        let mut row = csv::StringRecord::new();
+        while self.rows.len() < limit
+            && rdr.read_record(&mut row).with_context(|_| read_err())?
+        {
+            self.rows.push(row.iter().map(|c| c.to_owned()).collect());
+        }
+        Ok(())
+    }
+
+    /// Write our rows as CSV, with a header row.
+    pub fn write_csv<W: io::Write>(&self, wtr: W) -> Result<()> {
+        let mut wtr = csv::Writer::from_writer(wtr);
+        wtr.write_record(&self.columns)?;
+        for row in &self.rows {
+            wtr.write_record(row)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// Write our rows as a table with aligned columns, for people to read.
+    pub fn write_table<W: io::Write>(&self, mut wtr: W) -> Result<()> {
+        let mut widths = self
+            .columns
+            .iter()
+            .map(|c| c.chars().count())
+            .collect::<Vec<_>>();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let write_row = |wtr: &mut W, cells: &[String]| -> Result<()> {
+            let line = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, &width)| format!("{:width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ");
+            writeln!(wtr, "{}", line.trim_end())?;
+            Ok(())
+        };
+        write_row(&mut wtr, &self.columns)?;
+        let rules = widths.iter().map(|&w| "-".repeat(w)).collect::<Vec<_>>();
+        write_row(&mut wtr, &rules)?;
+        for row in &self.rows {
+            write_row(&mut wtr, row)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn preview_stops_after_limit() {
+    use crate::tokio_glue::run_futures_with_runtime;
+
+    let (ctx, worker_fut) = Context::create_for_test("preview");
+    let cmd_fut = async move {
+        let streams = vec![
+            Ok(CsvStream::from_bytes(&b"id,name\n1,a\n"[..]).await),
+            Ok(CsvStream::from_bytes(&b"id,name\n2,\n3,c\n"[..]).await),
+        ];
+        let preview =
+            Preview::from_csv_streams(ctx.clone(), stream::iter(streams).boxed(), 2)
+                .await?;
+        assert_eq!(preview.columns, vec!["id", "name"]);
+        assert_eq!(preview.rows, vec![vec!["1", "a"], vec!["2", ""]]);
+
+        let mut table = vec![];
+        preview.write_table(&mut table)?;
+        assert_eq!(String::from_utf8(table)?, "id  name\n--  ----\n1   a\n2\n");
+        Ok(())
+    };
+    run_futures_with_runtime(cmd_fut.boxed(), worker_fut).unwrap();
+}
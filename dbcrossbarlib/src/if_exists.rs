@@ -15,6 +15,7 @@ pub enum IfExistsFeatures {
     Append,
     Overwrite,
     Upsert,
+    Merge,
 }
 
 impl IfExistsFeatures {
@@ -39,7 +40,11 @@ impl fmt::Display for DisplayEnumSet<IfExistsFeatures> {
         write_flag(IfExistsFeatures::Overwrite, IfExists::Overwrite)?;
         write_flag(
             IfExistsFeatures::Upsert,
-            IfExists::Upsert(vec!["col".to_owned()]),
+            IfExists::Upsert(vec!["col".to_owned()], None),
+        )?;
+        write_flag(
+            IfExistsFeatures::Merge,
+            IfExists::Merge(vec!["col".to_owned()], MergeDelete::Delete),
         )?;
         Ok(())
     }
@@ -56,19 +61,46 @@ pub enum IfExists {
     Overwrite,
     /// If the destination exists, either update or insert using the specified
     /// columns as the key. The list of keys must be non-empty, but we currently
-    /// only enforce that when parsing in `FromStr`.
-    Upsert(Vec<String>),
+    /// only enforce that when parsing in `FromStr`. If the second list is
+    /// present, we only update those columns in existing rows.
+    Upsert(Vec<String>, Option<Vec<String>>),
+    /// If the destination exists, upsert using the specified columns as the
+    /// key, and then remove any destination rows whose keys don't appear in
+    /// the new data.
+    Merge(Vec<String>, MergeDelete),
+}
+
+/// How should `IfExists::Merge` remove rows which are missing from the new
+/// data?
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MergeDelete {
+    /// Delete the rows.
+    Delete,
+    /// Keep the rows, but set the specified timestamp column to the time
+    /// they were removed. Rows which reappear have this column set to `NULL`.
+    SoftDelete(String),
 }
 
 impl IfExists {
     /// Are we supposed to perform an upsert?
     pub(crate) fn is_upsert(&self) -> bool {
         match self {
-            IfExists::Upsert(_) => true,
+            IfExists::Upsert(..) => true,
             _ => false,
         }
     }
 
+    /// Will we need to update or delete existing rows, and not just add new
+    /// ones?
+    pub(crate) fn changes_existing_rows(&self) -> bool {
+        self.is_upsert() || self.is_merge()
+    }
+
+    /// Are we supposed to perform a merge?
+    pub(crate) fn is_merge(&self) -> bool {
+        matches!(self, IfExists::Merge(..))
+    }
+
     /// Convert to an `tokio::OpenOptions` value, returning an error for
     /// `IfExists::Append`.
     pub(crate) fn to_async_open_options_no_append(
@@ -81,14 +113,17 @@ impl IfExists {
                 open_options.create_new(true);
             }
             IfExists::Overwrite => {
-                open_options.create(true).append(true);
+                open_options.create(true).truncate(true);
             }
             IfExists::Append => {
                 return Err(format_err!("appending not supported"));
             }
-            IfExists::Upsert(_) => {
+            IfExists::Upsert(..) => {
                 return Err(format_err!("upsert not supported"));
             }
+            IfExists::Merge(..) => {
+                return Err(format_err!("merge not supported"));
+            }
         }
         Ok(open_options)
     }
@@ -114,11 +149,14 @@ impl IfExists {
             IfExists::Append if !features.contains(IfExistsFeatures::Append) => Err(
                 format_err!("this driver does not support --if-exists=append"),
             ),
-            IfExists::Upsert(_) if !features.contains(IfExistsFeatures::Upsert) => {
+            IfExists::Upsert(..) if !features.contains(IfExistsFeatures::Upsert) => {
                 Err(format_err!(
                     "this driver does not support --if-exists=upsert-on:..."
                 ))
             }
+            IfExists::Merge(..) if !features.contains(IfExistsFeatures::Merge) => Err(
+                format_err!("this driver does not support --if-exists=merge-on:..."),
+            ),
             _ => Ok(()),
         }
     }
@@ -133,14 +171,74 @@ impl Default for IfExists {
 /// The prefix used for the serialized version of `IfExists::Upsert`.
 const UPSERT_PREFIX: &str = "upsert-on:";
 
+/// Separates upsert keys from the columns to update.
+const UPDATE_SEPARATOR: &str = ":update:";
+
+/// The prefix used for the serialized version of `IfExists::Merge`.
+const MERGE_PREFIX: &str = "merge-on:";
+
+/// Separates merge keys from a `MergeDelete::SoftDelete` column.
+const SOFT_DELETE_SEPARATOR: &str = ":soft-delete:";
+
+/// Which of `columns` should an upsert update in existing rows? By default,
+/// this is every column which isn't one of `keys`. Fails if `update` contains
+/// a column that isn't in `columns`.
+pub(crate) fn upsert_update_columns<'a>(
+    columns: &[&'a str],
+    keys: &[String],
+    update: Option<&[String]>,
+) -> Result<Vec<&'a str>> {
+    match update {
+        None => Ok(columns
+            .iter()
+            .copied()
+            .filter(|c| !keys.iter().any(|k| k == c))
+            .collect()),
+        Some(update) => update
+            .iter()
+            .map(|u| {
+                columns.iter().copied().find(|c| c == u).ok_or_else(|| {
+                    format_err!(
+                        "cannot update column {:?} because it isn't in the table",
+                        u
+                    )
+                })
+            })
+            .collect(),
+    }
+}
+
+/// Parse a comma-separated list of column names, which must not be empty.
+fn parse_column_list(s: &str, what: &str) -> Result<Vec<String>> {
+    let columns = s.split(',').map(|s| s.to_owned()).collect::<Vec<_>>();
+    if columns.len() == 1 && columns[0].is_empty() {
+        return Err(format_err!("must specify {}", what));
+    }
+    if columns.iter().any(|c| c.is_empty()) {
+        return Err(format_err!("`{}` contains an empty column name", s));
+    }
+    Ok(columns)
+}
+
 impl fmt::Display for IfExists {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             IfExists::Error => "error".fmt(f),
             IfExists::Append => "append".fmt(f),
             IfExists::Overwrite => "overwrite".fmt(f),
-            IfExists::Upsert(merge_keys) => {
-                write!(f, "{}{}", UPSERT_PREFIX, merge_keys.iter().join(","))
+            IfExists::Upsert(merge_keys, update) => {
+                write!(f, "{}{}", UPSERT_PREFIX, merge_keys.iter().join(","))?;
+                if let Some(update) = update {
+                    write!(f, "{}{}", UPDATE_SEPARATOR, update.iter().join(","))?;
+                }
+                Ok(())
+            }
+            IfExists::Merge(merge_keys, delete) => {
+                write!(f, "{}{}", MERGE_PREFIX, merge_keys.iter().join(","))?;
+                if let MergeDelete::SoftDelete(column) = delete {
+                    write!(f, "{}{}", SOFT_DELETE_SEPARATOR, column)?;
+                }
+                Ok(())
             }
         }
     }
@@ -155,19 +253,52 @@ impl FromStr for IfExists {
             "append" => Ok(IfExists::Append),
             "overwrite" => Ok(IfExists::Overwrite),
             _ if s.starts_with(UPSERT_PREFIX) => {
-                let merge_keys = s[UPSERT_PREFIX.len()..]
-                    .split(',')
-                    .map(|s| s.to_owned())
-                    .collect::<Vec<_>>();
-                if merge_keys.is_empty()
-                    || (merge_keys.len() == 1 && merge_keys[0] == "")
-                {
-                    return Err(format_err!("must specify keys after `upsert-on:`"));
+                let rest = &s[UPSERT_PREFIX.len()..];
+                let (keys, update) = match rest.find(UPDATE_SEPARATOR) {
+                    Some(idx) => {
+                        (&rest[..idx], Some(&rest[idx + UPDATE_SEPARATOR.len()..]))
+                    }
+                    None => (rest, None),
+                };
+                let merge_keys = parse_column_list(keys, "keys after `upsert-on:`")?;
+                let update = update
+                    .map(|u| parse_column_list(u, "columns after `:update:`"))
+                    .transpose()?;
+                if let Some(update) = &update {
+                    if let Some(key) = update.iter().find(|u| merge_keys.contains(u)) {
+                        return Err(format_err!(
+                            "cannot update upsert key {:?} in `{}`",
+                            key,
+                            s,
+                        ));
+                    }
                 }
-                if merge_keys.iter().any(|k| k == "") {
-                    return Err(format_err!("`{}` contains an empty merge key", s));
+                Ok(IfExists::Upsert(merge_keys, update))
+            }
+            _ if s.starts_with(MERGE_PREFIX) => {
+                let rest = &s[MERGE_PREFIX.len()..];
+                let (keys, delete) = match rest.find(SOFT_DELETE_SEPARATOR) {
+                    Some(idx) => {
+                        let column = &rest[idx + SOFT_DELETE_SEPARATOR.len()..];
+                        if column.is_empty() || column.contains(',') {
+                            return Err(format_err!(
+                                "must specify one column after `:soft-delete:`"
+                            ));
+                        }
+                        (&rest[..idx], MergeDelete::SoftDelete(column.to_owned()))
+                    }
+                    None => (rest, MergeDelete::Delete),
+                };
+                let merge_keys = parse_column_list(keys, "keys after `merge-on:`")?;
+                if let MergeDelete::SoftDelete(column) = &delete {
+                    if merge_keys.contains(column) {
+                        return Err(format_err!(
+                            "cannot use merge key {:?} as a soft-delete column",
+                            column,
+                        ));
+                    }
                 }
-                Ok(IfExists::Upsert(merge_keys))
+                Ok(IfExists::Merge(merge_keys, delete))
             }
             _ => Err(format_err!("unknown if-exists value: {}", s)),
         }
@@ -180,10 +311,31 @@ fn parse_and_display() {
         ("error", IfExists::Error),
         ("append", IfExists::Append),
         ("overwrite", IfExists::Overwrite),
-        ("upsert-on:id", IfExists::Upsert(vec!["id".to_owned()])),
+        (
+            "upsert-on:id",
+            IfExists::Upsert(vec!["id".to_owned()], None),
+        ),
         (
             "upsert-on:first,last",
-            IfExists::Upsert(vec!["first".to_owned(), "last".to_owned()]),
+            IfExists::Upsert(vec!["first".to_owned(), "last".to_owned()], None),
+        ),
+        (
+            "upsert-on:first,last:update:email",
+            IfExists::Upsert(
+                vec!["first".to_owned(), "last".to_owned()],
+                Some(vec!["email".to_owned()]),
+            ),
+        ),
+        (
+            "merge-on:id",
+            IfExists::Merge(vec!["id".to_owned()], MergeDelete::Delete),
+        ),
+        (
+            "merge-on:first,last:soft-delete:deleted_at",
+            IfExists::Merge(
+                vec!["first".to_owned(), "last".to_owned()],
+                MergeDelete::SoftDelete("deleted_at".to_owned()),
+            ),
         ),
     ];
     for (serialized, value) in &examples {
@@ -195,4 +347,32 @@ fn parse_and_display() {
 #[test]
 fn must_have_upsert_keys() {
     assert!("upsert-on:".parse::<IfExists>().is_err());
+    assert!("upsert-on:id,".parse::<IfExists>().is_err());
+    assert!("upsert-on:id:update:".parse::<IfExists>().is_err());
+    assert!("upsert-on:id:update:id,name".parse::<IfExists>().is_err());
+}
+
+#[test]
+fn must_have_merge_keys() {
+    assert!("merge-on:".parse::<IfExists>().is_err());
+    assert!("merge-on:id:soft-delete:".parse::<IfExists>().is_err());
+    assert!("merge-on:id:soft-delete:a,b".parse::<IfExists>().is_err());
+    assert!("merge-on:id:soft-delete:id".parse::<IfExists>().is_err());
+}
+
+#[test]
+fn upsert_updates_non_key_columns_by_default() {
+    let columns = ["id", "name", "email"];
+    let keys = vec!["id".to_owned()];
+    assert_eq!(
+        upsert_update_columns(&columns, &keys, None).unwrap(),
+        vec!["name", "email"],
+    );
+    let update = vec!["email".to_owned()];
+    assert_eq!(
+        upsert_update_columns(&columns, &keys, Some(&update)).unwrap(),
+        vec!["email"],
+    );
+    let missing = vec!["missing".to_owned()];
+    assert!(upsert_update_columns(&columns, &keys, Some(&missing)).is_err());
 }
@@ -0,0 +1,154 @@
+//! Time intervals, as used by our portable `interval` type.
+//!
+//! In our CSV interchange format, intervals are written as ISO 8601 durations,
+//! like `P1Y2M3DT4H5M6.5S`. Like PostgreSQL, we keep months, days and smaller
+//! units separate, because the length of a month or a day varies.
+
+use std::str::FromStr;
+
+use crate::common::*;
+
+/// A length of time, stored the same way PostgreSQL stores an `interval`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct Interval {
+    /// Months (including 12 months for each year).
+    pub(crate) months: i32,
+    /// Days (including 7 days for each week).
+    pub(crate) days: i32,
+    /// Hours, minutes and seconds, in microseconds.
+    pub(crate) microseconds: i64,
+}
+
+impl FromStr for Interval {
+    type Err = Error;
+
+    /// Parse an ISO 8601 duration. Each component may have its own sign, as
+    /// in PostgreSQL's output, and we also allow a sign for the whole duration.
+    /// Only seconds may have a fractional part, and digits beyond microseconds
+    /// are ignored.
+    fn from_str(s: &str) -> Result<Self> {
+        let err = || format_err!("cannot parse {:?} as an ISO 8601 duration", s);
+        let (negate, rest) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let mut rest = rest.strip_prefix('P').ok_or_else(err)?;
+
+        let (mut months, mut days, mut microseconds) = (0i64, 0i64, 0i64);
+        let mut in_time = false;
+        let mut found_component = false;
+        while !rest.is_empty() {
+            if !in_time {
+                if let Some(time) = rest.strip_prefix('T') {
+                    in_time = true;
+                    rest = time;
+                    continue;
+                }
+            }
+            let end = rest
+                .find(|c: char| c.is_ascii_alphabetic())
+                .ok_or_else(err)?;
+            let (number, unit) = (&rest[..end], &rest[end..=end]);
+            rest = &rest[end + 1..];
+            found_component = true;
+            let (total, scale, value) = match (in_time, unit) {
+                (false, "Y") => (&mut months, 12, parse_int(number)),
+                (false, "M") => (&mut months, 1, parse_int(number)),
+                (false, "W") => (&mut days, 7, parse_int(number)),
+                (false, "D") => (&mut days, 1, parse_int(number)),
+                (true, "H") => (&mut microseconds, 3_600_000_000, parse_int(number)),
+                (true, "M") => (&mut microseconds, 60_000_000, parse_int(number)),
+                (true, "S") => (&mut microseconds, 1, parse_seconds(number)),
+                _ => return Err(err()),
+            };
+            *total = value
+                .and_then(|v| v.checked_mul(scale))
+                .and_then(|v| total.checked_add(v))
+                .ok_or_else(err)?;
+        }
+        if !found_component {
+            return Err(err());
+        }
+        if negate {
+            months = -months;
+            days = -days;
+            microseconds = -microseconds;
+        }
+        Ok(Interval {
+            months: cast::i32(months).map_err(|_| err())?,
+            days: cast::i32(days).map_err(|_| err())?,
+            microseconds,
+        })
+    }
+}
+
+/// Parse an optionally-signed integer component.
+fn parse_int(number: &str) -> Option<i64> {
+    number.parse::<i64>().ok()
+}
+
+/// Parse an optionally-signed number of seconds, with an optional fractional
+/// part, and return microseconds.
+fn parse_seconds(number: &str) -> Option<i64> {
+    let (negative, unsigned) = match number.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, number),
+    };
+    let mut parts = unsigned.splitn(2, ['.', ',']);
+    let whole = parts.next().unwrap_or_default();
+    let fraction = parts.next().unwrap_or_default();
+    if whole.is_empty() || !whole.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let micros = fraction
+        .bytes()
+        .chain(std::iter::repeat(b'0'))
+        .take(6)
+        .fold(0, |acc, digit| acc * 10 + i64::from(digit - b'0'));
+    let total = whole
+        .parse::<i64>()
+        .ok()?
+        .checked_mul(1_000_000)?
+        .checked_add(micros)?;
+    Some(if negative { -total } else { total })
+}
+
+#[test]
+fn parse_iso8601_durations() {
+    let interval = |months, days, microseconds| Interval {
+        months,
+        days,
+        microseconds,
+    };
+    let examples = &[
+        ("P1Y2M3DT4H5M6.5S", interval(14, 3, 14_706_500_000)),
+        ("PT0S", interval(0, 0, 0)),
+        ("P2W", interval(0, 14, 0)),
+        ("PT1M", interval(0, 0, 60_000_000)),
+        ("P-1Y-2M3DT-4H", interval(-14, 3, -14_400_000_000)),
+        ("-P1DT1S", interval(0, -1, -1_000_000)),
+        ("PT-0.000001S", interval(0, 0, -1)),
+        ("PT1,25S", interval(0, 0, 1_250_000)),
+        ("PT0.1234567S", interval(0, 0, 123_456)),
+    ];
+    for (s, expected) in examples {
+        assert_eq!(&s.parse::<Interval>().unwrap(), expected, "parsing {}", s);
+    }
+    for s in &[
+        "",
+        "P",
+        "1D",
+        "PT",
+        "P1H",
+        "PT1D",
+        "P1.5D",
+        "P1",
+        "PT.5S",
+        "P99999999999Y",
+    ] {
+        assert!(s.parse::<Interval>().is_err(), "should not parse {}", s);
+    }
+}
@@ -15,23 +15,51 @@ extern crate diesel;
 use std::result;
 
 pub(crate) mod args;
+pub mod cast;
+pub mod check_headers;
 pub(crate) mod clouds;
+pub mod column_transform;
+mod compression;
 pub(crate) mod concat;
+pub(crate) mod config;
 pub(crate) mod context;
+pub(crate) mod count_rows;
 pub(crate) mod csv_stream;
+pub mod dedupe;
 mod driver_args;
 pub mod drivers;
+pub mod empty_streams;
+mod encryption;
+pub(crate) mod evolve_schema;
 pub(crate) mod from_csv_cell;
 pub(crate) mod from_json_value;
+pub mod head;
 pub(crate) mod if_exists;
+pub(crate) mod interval;
+pub(crate) mod limit;
 pub(crate) mod locator;
+pub(crate) mod name_template;
+pub(crate) mod network;
+pub(crate) mod parallelism;
+pub(crate) mod partition;
 pub(crate) mod path_or_stdio;
 pub mod rechunk;
+pub mod rename;
+pub mod row_filter;
+pub mod sample_data;
 pub mod schema;
+pub mod schema_diff;
+mod scratch;
+pub mod select;
 pub(crate) mod separator;
+pub mod sync_state;
+pub mod tee;
 mod temporary_storage;
 pub mod tokio_glue;
 pub(crate) mod transform;
+pub mod union;
+pub mod usage;
+pub mod validate;
 
 /// Standard error type for this library.
 pub use failure::Error;
@@ -49,8 +77,10 @@ pub use args::{
 pub use context::Context;
 pub use csv_stream::CsvStream;
 pub use driver_args::DriverArguments;
-pub use if_exists::IfExists;
+pub use if_exists::{IfExists, MergeDelete};
 pub use locator::{BoxLocator, DisplayOutputLocators, Locator};
+pub use parallelism::BufferWithParallelism;
+pub use scratch::ScratchDir;
 pub use temporary_storage::TemporaryStorage;
 pub use tokio_glue::{run_futures_with_runtime, ConsumeWithParallelism};
 
@@ -84,7 +114,8 @@ pub(crate) mod common {
         context::Context,
         csv_stream::CsvStream,
         driver_args::DriverArguments,
-        if_exists::{IfExists, IfExistsFeatures},
+        head::Preview,
+        if_exists::{IfExists, IfExistsFeatures, MergeDelete},
         locator::{
             BoxLocator, DisplayOutputLocators, Features, Locator, LocatorFeatures,
             LocatorStatic,
@@ -0,0 +1,115 @@
+//! Copying only the first rows of our input.
+//!
+//! Database drivers handle `--limit` by adding a `LIMIT` clause to their SQL.
+//! Drivers which read CSV files stop copying rows once they've seen enough.
+
+use futures::future;
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use crate::common::*;
+use crate::transform::spawn_sync_transform;
+
+/// Copy at most `limit` rows from `streams`, counting rows across all
+/// streams. Once we've copied enough rows, we stop reading new streams. This
+/// is used by drivers like `csv:` and `gs://`, which have no database to limit
+/// rows for them.
+pub(crate) fn limit_csv_streams(
+    ctx: Context,
+    limit: Option<usize>,
+    streams: BoxStream<CsvStream>,
+) -> BoxStream<CsvStream> {
+    let limit = match limit {
+        Some(limit) => limit,
+        None => return streams,
+    };
+    let remaining = Arc::new(AtomicUsize::new(limit));
+    let first = Arc::new(AtomicUsize::new(0));
+    let remaining_for_take = remaining.clone();
+    streams
+        // Always keep our first stream, so that we have a header to copy.
+        .take_while(move |_| {
+            let is_first = first.fetch_add(1, Ordering::SeqCst) == 0;
+            future::ready(is_first || remaining_for_take.load(Ordering::SeqCst) > 0)
+        })
+        .and_then(move |stream| {
+            let ctx = ctx.clone();
+            let remaining = remaining.clone();
+            async move {
+                let name = stream.name.clone();
+                let data = spawn_sync_transform(
+                    ctx,
+                    "limit_csv".to_owned(),
+                    stream.data,
+                    move |_ctx, rdr, wtr| {
+                        limit_csv(&remaining, rdr, wtr).with_context(|_| {
+                            format!("cannot limit rows in stream {}", name)
+                        })?;
+                        Ok(())
+                    },
+                )?;
+                Ok(CsvStream {
+                    name: stream.name,
+                    data,
+                })
+            }
+        })
+        .boxed()
+}
+
+/// Copy rows from `rdr` to `wtr` until `remaining` reaches zero, decrementing
+/// it once for each row.
+///
+/// This is synchronous, and should be run using `spawn_sync_transform`.
+fn limit_csv<R: Read, W: Write>(
+    remaining: &AtomicUsize,
+    rdr: R,
+    wtr: W,
+) -> Result<()> {
+    let mut rdr = csv::Reader::from_reader(rdr);
+    let mut wtr =
+        csv::Writer::from_writer(io::BufWriter::with_capacity(BUFFER_SIZE, wtr));
+
+    let headers = rdr.byte_headers()?.clone();
+    if headers.is_empty() {
+        // Empty streams have no header to copy.
+        return Ok(());
+    }
+    wtr.write_byte_record(&headers)?;
+    let mut row = csv::ByteRecord::new();
+    while rdr.read_byte_record(&mut row)? {
+        let took = remaining
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        if !took {
+            break;
+        }
+        wtr.write_byte_record(&row)?;
+    }
+    wtr.flush()?;
+
+    // Read the rest of our input, so that whoever is producing it doesn't see
+    // an unexpected error.
+    io::copy(&mut rdr.into_inner(), &mut io::sink())?;
+    Ok(())
+}
+
+#[test]
+fn limit_counts_rows_across_streams() {
+    let run = |remaining: &AtomicUsize, input: &str| -> Result<String> {
+        let mut out = vec![];
+        limit_csv(remaining, input.as_bytes(), &mut out)?;
+        Ok(String::from_utf8(out)?)
+    };
+    let remaining = AtomicUsize::new(3);
+    assert_eq!(run(&remaining, "id\n1\n2\n").unwrap(), "id\n1\n2\n");
+    assert_eq!(run(&remaining, "id\n3\n4\n").unwrap(), "id\n3\n");
+    assert_eq!(run(&remaining, "id\n5\n").unwrap(), "id\n");
+    assert_eq!(run(&remaining, "").unwrap(), "");
+    assert_eq!(remaining.load(Ordering::SeqCst), 0);
+}
@@ -6,6 +6,7 @@ use std::{fmt, marker::PhantomData, str::FromStr};
 
 use crate::args::EnumSetExt;
 use crate::common::*;
+use crate::config::Config;
 use crate::drivers::find_driver;
 
 /// When called from the CLI, should we display a list of individual locators
@@ -48,6 +49,13 @@ pub trait Locator: fmt::Debug + fmt::Display + Send + Sync + 'static {
     /// [so]: https://stackoverflow.com/a/33687996
     fn as_any(&self) -> &dyn Any;
 
+    /// Format this locator so that it can be parsed again, including any
+    /// passwords which `Display` hides. Use this when building new locators
+    /// from an existing one, but never for logging.
+    fn to_string_with_credentials(&self) -> String {
+        self.to_string()
+    }
+
     /// Return a table schema, if available.
     fn schema(&self, _ctx: Context) -> BoxFuture<Option<Table>> {
         async { Ok(None) }.boxed()
@@ -65,6 +73,35 @@ pub trait Locator: fmt::Debug + fmt::Display + Send + Sync + 'static {
         async move { Err(err) }.boxed()
     }
 
+    /// Return the schemas of all the tables described by this locator, if
+    /// available. Most locators only describe a single table, so by default,
+    /// we call `schema`.
+    fn schemas(&self, ctx: Context) -> BoxFuture<Option<Vec<Table>>> {
+        let schema = self.schema(ctx);
+        async move { Ok(schema.await?.map(|table| vec![table])) }.boxed()
+    }
+
+    /// Write the schemas of several tables to this locator. By default, we
+    /// only support writing a single table using `write_schema`.
+    fn write_schemas(
+        &self,
+        ctx: Context,
+        mut schemas: Vec<Table>,
+        if_exists: IfExists,
+    ) -> BoxFuture<()> {
+        if schemas.len() == 1 {
+            let schema = schemas.remove(0);
+            self.write_schema(ctx, schema, if_exists)
+        } else {
+            let err = format_err!(
+                "cannot write {} table schemas to {}",
+                schemas.len(),
+                self,
+            );
+            async move { Err(err) }.boxed()
+        }
+    }
+
     /// Count the records specified by this locator.
     fn count(
         &self,
@@ -76,6 +113,50 @@ pub trait Locator: fmt::Debug + fmt::Display + Send + Sync + 'static {
         async move { Err(err) }.boxed()
     }
 
+    /// Return the largest value of `column` in the records specified by this
+    /// locator, formatted as text, or `None` if there are no matching records.
+    /// This is used to track how far `sync` has copied.
+    fn max_value(
+        &self,
+        _ctx: Context,
+        _shared_args: SharedArguments<Unverified>,
+        _source_args: SourceArguments<Unverified>,
+        column: &str,
+    ) -> BoxFuture<Option<String>> {
+        let err = format_err!("cannot find the largest {} at {}", column, self);
+        async move { Err(err) }.boxed()
+    }
+
+    /// Return up to `limit` rows from this locator, so that people can take a
+    /// quick look at the data. By default, we read rows from `local_data` until
+    /// we have enough, but drivers which can fetch a few rows more cheaply
+    /// should override this.
+    fn head(
+        &self,
+        ctx: Context,
+        shared_args: SharedArguments<Unverified>,
+        source_args: SourceArguments<Unverified>,
+        limit: usize,
+    ) -> BoxFuture<Option<Preview>> {
+        let data = self.local_data(ctx.clone(), shared_args, source_args);
+        async move {
+            match data.await? {
+                Some(streams) => {
+                    Ok(Some(Preview::from_csv_streams(ctx, streams, limit).await?))
+                }
+                None => Ok(None),
+            }
+        }
+        .boxed()
+    }
+
+    /// Delete the table or files specified by this locator. It isn't an error
+    /// if they don't exist.
+    fn remove(&self, _ctx: Context) -> BoxFuture<()> {
+        let err = format_err!("cannot remove {}", self);
+        async move { Err(err) }.boxed()
+    }
+
     /// If this locator can be used as a local data source, return a stream of
     /// CSV streams. This function type is bit hairy:
     ///
@@ -102,11 +183,51 @@ pub trait Locator: fmt::Debug + fmt::Display + Send + Sync + 'static {
         async { Ok(None) }.boxed()
     }
 
+    /// Does `local_data` evaluate `--where` and drop columns that aren't in
+    /// the schema by reading every row itself? If so, we can only honor
+    /// `--where` and `--select` when the data passes through this machine,
+    /// and not during `write_remote_data`.
+    fn rewrites_data_locally(&self) -> bool {
+        false
+    }
+
+    /// If this locator refers to a collection of tables (such as an entire
+    /// BigQuery dataset), return the bare name of each table, along with a
+    /// locator pointing to that table. Returns `None` if this locator refers to
+    /// a single table.
+    fn list_tables(
+        &self,
+        _ctx: Context,
+    ) -> BoxFuture<Option<Vec<(String, BoxLocator)>>> {
+        async { Ok(None) }.boxed()
+    }
+
+    /// If this locator refers to a collection of tables, return a locator for
+    /// the table `name` in that collection. This allows a whole database or
+    /// dataset to be used as a destination for `list_tables`. Returns `None` if
+    /// this locator refers to a single table.
+    fn table_locator(&self, _name: &str) -> Result<Option<BoxLocator>> {
+        Ok(None)
+    }
+
+    /// If this locator refers to a directory or prefix containing data files,
+    /// return a locator for each file. Returns `None` if this locator refers to
+    /// a single file, or to something which isn't stored as files.
+    fn list_files(&self, _ctx: Context) -> BoxFuture<Option<Vec<BoxLocator>>> {
+        async { Ok(None) }.boxed()
+    }
+
     /// Should we display the individual output locations?
     fn display_output_locators(&self) -> DisplayOutputLocators {
         DisplayOutputLocators::IfRequested
     }
 
+    /// Does this locator refer to standard input or standard output? Writing
+    /// to standard output never changes any stored data.
+    fn is_stdio(&self) -> bool {
+        false
+    }
+
     /// If this locator can be used as a local data sink, write data to it.
     ///
     /// This function takes a stream `data` as input, the elements of which are
@@ -159,6 +280,21 @@ pub trait Locator: fmt::Debug + fmt::Display + Send + Sync + 'static {
         let err = format_err!("cannot write_remote_data from source {}", source);
         async move { Err(err) }.boxed()
     }
+
+    /// Describe the steps that `write_local_data` would take to write data to
+    /// this locator, such as the SQL it would run, without changing anything.
+    /// If `remote_source` is present, describe `write_remote_data` instead.
+    /// This is used by `cp --dry-run`. Returns `None` if this driver can't
+    /// describe its writes.
+    fn plan_write(
+        &self,
+        _ctx: Context,
+        _shared_args: SharedArguments<Unverified>,
+        _dest_args: DestinationArguments<Unverified>,
+        _remote_source: Option<String>,
+    ) -> BoxFuture<Option<Vec<String>>> {
+        async { Ok(None) }.boxed()
+    }
 }
 
 /// A value of an unknown type implementing `Locator`.
@@ -168,6 +304,11 @@ impl FromStr for BoxLocator {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
+        // Expand aliases from our config file.
+        if let Some(alias) = s.strip_prefix("alias:") {
+            return Config::load()?.expand_alias(alias);
+        }
+
         // Parse our locator into a URL-style scheme and the rest.
         lazy_static! {
             static ref SCHEME_RE: Regex = Regex::new("^[A-Za-z][-A-Za-z0-9+.]*:")
@@ -187,7 +328,9 @@ impl FromStr for BoxLocator {
 #[test]
 fn locator_from_str_to_string_roundtrip() {
     let locators = vec![
+        "bigquery:my_project:my_dataset",
         "bigquery:my_project:my_dataset.my_table",
+        "bigquery:my_project:my_dataset.my_table@2020-01-01T00:00:00Z",
         "bigquery-schema:dir/my_table.json",
         "bigml:dataset",
         "bigml:datasets",
@@ -197,10 +340,19 @@ fn locator_from_str_to_string_roundtrip() {
         "csv:file.csv",
         "csv:dir/",
         "dbcrossbar-schema:file.json",
+        "geopackage:file.gpkg",
+        "geopackage:file.gpkg#layer",
         "gs://example-bucket/tmp/",
+        "msgpack:-",
+        "msgpack:file.msgpack",
+        "pg-dump:nightly.dump#table",
+        "pg-dump:nightly.dump#schema.table",
         "postgres://localhost:5432/db#my_table",
         "postgres-sql:dir/my_table.sql",
+        "protobuf:-",
+        "protobuf:dir/my_table.proto",
         "s3://example/my-dir/",
+        "shapefile:dir/file.shp",
     ];
     for locator in locators.into_iter() {
         let parsed: BoxLocator = locator.parse().unwrap();
@@ -216,6 +368,7 @@ pub enum LocatorFeatures {
     LocalData,
     WriteLocalData,
     Count,
+    Remove,
 }
 
 /// A collection of all the features supported by a given driver. This is
@@ -255,10 +408,17 @@ impl fmt::Display for Features {
             writeln!(f, "  {}", self.write_schema_if_exists.display())?;
         }
         if self.locator.contains(LocatorFeatures::Count) {
+            // Drivers which can't filter rows themselves count them locally,
+            // so `count` always supports `--where`. It has no `--limit` or
+            // `--order-by`.
+            let count_args = (self.source_args | SourceArgumentsFeatures::WhereClause)
+                - SourceArgumentsFeatures::Limit
+                - SourceArgumentsFeatures::OrderBy;
             writeln!(f, "- count")?;
-            if !self.source_args.is_empty() {
-                writeln!(f, "  {}", self.source_args.display())?;
-            }
+            writeln!(f, "  {}", count_args.display())?;
+        }
+        if self.locator.contains(LocatorFeatures::Remove) {
+            writeln!(f, "- rm")?;
         }
         if self.locator.contains(LocatorFeatures::LocalData) {
             writeln!(f, "- cp FROM:")?;
@@ -0,0 +1,190 @@
+//! Templates for naming the files we write to directories and buckets.
+//!
+//! These are specified using `--to-arg=name_template=...`, and look like
+//! `events-{date}-{shard:04}.csv`.
+
+use chrono::{NaiveDate, Utc};
+use std::{fmt, str::FromStr};
+
+use crate::common::*;
+
+/// A single part of a `NameTemplate`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Part {
+    /// Literal text.
+    Text(String),
+    /// The name of the stream we're writing.
+    Name,
+    /// The position of the stream in our output, starting at 0, padded with
+    /// zeros to the specified width.
+    Shard(usize),
+    /// The UTC date when the copy started, as `YYYY-MM-DD`.
+    Date,
+}
+
+/// A template for output file names.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct NameTemplate {
+    /// The original template, for display.
+    template: String,
+    /// The parsed template.
+    parts: Vec<Part>,
+    /// The date to use for `{date}`.
+    date: NaiveDate,
+}
+
+impl NameTemplate {
+    /// Generate a file name for the stream `name` at position `shard`.
+    pub(crate) fn file_name(&self, name: &str, shard: usize) -> String {
+        let mut file_name = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => file_name.push_str(text),
+                Part::Name => file_name.push_str(name),
+                Part::Shard(width) => {
+                    file_name.push_str(&format!("{:0width$}", shard, width = width))
+                }
+                Part::Date => {
+                    file_name.push_str(&self.date.format("%Y-%m-%d").to_string())
+                }
+            }
+        }
+        file_name
+    }
+
+    /// Parse a single `{...}` placeholder.
+    fn parse_placeholder(placeholder: &str) -> Result<Part> {
+        match placeholder {
+            "name" => Ok(Part::Name),
+            "shard" => Ok(Part::Shard(0)),
+            "date" => Ok(Part::Date),
+            _ => match placeholder.strip_prefix("shard:") {
+                Some(width) => {
+                    let width = width.parse::<usize>().map_err(|_| {
+                        format_err!("expected a width in {{shard:{}}}", width)
+                    })?;
+                    Ok(Part::Shard(width))
+                }
+                None => Err(format_err!(
+                    "unknown placeholder {{{}}}, expected {{name}}, {{shard}} or {{date}}",
+                    placeholder,
+                )),
+            },
+        }
+    }
+}
+
+/// Choose a file name for the stream `name` at position `shard`, using
+/// `template` if we have one, and `name.csv` otherwise. If `name` contains a
+/// directory, like the streams produced by `partition_by`, we only apply the
+/// template to the last part.
+pub(crate) fn output_file_name(
+    template: Option<&NameTemplate>,
+    name: &str,
+    shard: usize,
+) -> String {
+    match template {
+        Some(template) => match name.rfind('/') {
+            Some(idx) => format!(
+                "{}{}",
+                &name[..=idx],
+                template.file_name(&name[idx + 1..], shard),
+            ),
+            None => template.file_name(name, shard),
+        },
+        None => format!("{}.csv", name),
+    }
+}
+
+impl fmt::Display for NameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.template.fmt(f)
+    }
+}
+
+impl FromStr for NameTemplate {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.contains('/') || s.contains('\\') {
+            return Err(format_err!("name_template cannot contain a path separator"));
+        }
+
+        let mut parts = vec![];
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            if rest[..start].contains('}') {
+                return Err(format_err!("unexpected }} in name_template {:?}", s));
+            }
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_owned()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format_err!("unclosed {{ in name_template {:?}", s))?;
+            parts.push(Self::parse_placeholder(&rest[start + 1..start + end])?);
+            rest = &rest[start + end + 1..];
+        }
+        if rest.contains('}') {
+            return Err(format_err!("unexpected }} in name_template {:?}", s));
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_owned()));
+        }
+
+        // Make sure each stream gets its own file.
+        if !parts
+            .iter()
+            .any(|p| matches!(p, Part::Name | Part::Shard(_)))
+        {
+            return Err(format_err!(
+                "name_template {:?} must contain {{name}} or {{shard}}",
+                s,
+            ));
+        }
+
+        Ok(NameTemplate {
+            template: s.to_owned(),
+            parts,
+            date: Utc::now().date_naive(),
+        })
+    }
+}
+
+#[test]
+fn name_template_generates_file_names() {
+    let mut template = "events-{date}-{shard:04}.csv"
+        .parse::<NameTemplate>()
+        .unwrap();
+    template.date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+    assert_eq!(
+        template.file_name("chunk_0001", 7),
+        "events-2023-01-02-0007.csv"
+    );
+    assert_eq!(template.to_string(), "events-{date}-{shard:04}.csv");
+
+    let template = "{name}_{shard}.csv".parse::<NameTemplate>().unwrap();
+    assert_eq!(template.file_name("part", 12), "part_12.csv");
+    assert_eq!(
+        output_file_name(Some(&template), "day=2023-01-01/part-000", 3),
+        "day=2023-01-01/part-000_3.csv",
+    );
+    assert_eq!(output_file_name(None, "chunk_0001", 0), "chunk_0001.csv");
+
+    for bad in &[
+        "events.csv",
+        "{date}.csv",
+        "dir/{name}.csv",
+        "{shard:x}.csv",
+        "{nme}.csv",
+        "{name.csv",
+        "{name}}.csv",
+        "}{name}.csv",
+    ] {
+        assert!(
+            bad.parse::<NameTemplate>().is_err(),
+            "should reject {}",
+            bad
+        );
+    }
+}
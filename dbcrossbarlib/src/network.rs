@@ -0,0 +1,198 @@
+//! Network addresses, as used by our portable `inet`, `cidr` and `mac_addr`
+//! types.
+//!
+//! In our CSV interchange format, these are written the way PostgreSQL writes
+//! them: IP addresses with an optional `/prefix`, and MAC addresses as six
+//! pairs of hexadecimal digits separated by `:`.
+
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    str::FromStr,
+};
+
+use crate::common::*;
+
+/// An IP address, with an optional network prefix length, like PostgreSQL's
+/// `inet`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct Inet {
+    /// The address itself.
+    pub(crate) addr: IpAddr,
+    /// The number of bits in the network prefix.
+    pub(crate) prefix_len: u8,
+}
+
+impl FromStr for Inet {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s, None),
+        };
+        let addr = addr
+            .parse::<IpAddr>()
+            .with_context(|_| format!("cannot parse {:?} as an IP address", s))?;
+        let max_prefix_len = max_prefix_len(addr);
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse::<u8>()
+                .ok()
+                .filter(|&len| len <= max_prefix_len)
+                .ok_or_else(|| format_err!("invalid network prefix in {:?}", s))?,
+            None => max_prefix_len,
+        };
+        Ok(Inet { addr, prefix_len })
+    }
+}
+
+impl fmt::Display for Inet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.prefix_len == max_prefix_len(self.addr) {
+            write!(f, "{}", self.addr)
+        } else {
+            write!(f, "{}/{}", self.addr, self.prefix_len)
+        }
+    }
+}
+
+/// An IP network, like PostgreSQL's `cidr`. Unlike `Inet`, all the bits after
+/// the network prefix must be zero.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct Cidr(pub(crate) Inet);
+
+impl FromStr for Cidr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let inet = s.parse::<Inet>()?;
+        let host_bits_set = match inet.addr {
+            IpAddr::V4(addr) => {
+                u32::from(addr)
+                    .checked_shl(u32::from(inet.prefix_len))
+                    .unwrap_or(0)
+                    != 0
+            }
+            IpAddr::V6(addr) => {
+                u128::from(addr)
+                    .checked_shl(u32::from(inet.prefix_len))
+                    .unwrap_or(0)
+                    != 0
+            }
+        };
+        if host_bits_set {
+            return Err(format_err!(
+                "{:?} has bits set to the right of the network prefix",
+                s,
+            ));
+        }
+        Ok(Cidr(inet))
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // PostgreSQL always includes the prefix length for `cidr` values.
+        write!(f, "{}/{}", self.0.addr, self.0.prefix_len)
+    }
+}
+
+/// The number of bits in `addr`.
+fn max_prefix_len(addr: IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(_) => 8 * std::mem::size_of::<Ipv4Addr>() as u8,
+        IpAddr::V6(_) => 8 * std::mem::size_of::<Ipv6Addr>() as u8,
+    }
+}
+
+/// A 6-byte MAC address, like PostgreSQL's `macaddr`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct MacAddr(pub(crate) [u8; 6]);
+
+impl FromStr for MacAddr {
+    type Err = Error;
+
+    /// Parse a MAC address. Like PostgreSQL, we accept 12 hexadecimal digits
+    /// optionally grouped using `:`, `-` or `.`, as in `08:00:2b:01:02:03`,
+    /// `08-00-2b-01-02-03`, `0800.2b01.0203` or `08002b010203`.
+    fn from_str(s: &str) -> Result<Self> {
+        let err = || format_err!("cannot parse {:?} as a MAC address", s);
+        let separators = s
+            .chars()
+            .filter(|c| !c.is_ascii_hexdigit())
+            .collect::<Vec<_>>();
+        if separators.iter().any(|c| ![':', '-', '.'].contains(c))
+            || separators.windows(2).any(|w| w[0] != w[1])
+        {
+            return Err(err());
+        }
+        let digits = s
+            .chars()
+            .filter(|c| c.is_ascii_hexdigit())
+            .collect::<String>();
+        let mut bytes = [0; 6];
+        hex::decode_to_slice(&digits, &mut bytes).map_err(|_| err())?;
+        Ok(MacAddr(bytes))
+    }
+}
+
+impl fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let b = &self.0;
+        write!(
+            f,
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            b[0], b[1], b[2], b[3], b[4], b[5],
+        )
+    }
+}
+
+#[test]
+fn parse_network_addresses() {
+    let inet_examples = &[
+        ("192.168.0.1", "192.168.0.1"),
+        ("192.168.0.1/24", "192.168.0.1/24"),
+        ("192.168.0.1/32", "192.168.0.1"),
+        ("::1", "::1"),
+        ("2001:db8::1/64", "2001:db8::1/64"),
+    ];
+    for &(input, expected) in inet_examples {
+        let inet = input.parse::<Inet>().unwrap();
+        assert_eq!(inet.to_string(), expected, "parsing {}", input);
+    }
+    for &input in &["", "192.168.0", "192.168.0.1/33", "::1/129", "1.2.3.4/x"] {
+        assert!(input.parse::<Inet>().is_err(), "should reject {:?}", input);
+    }
+
+    let cidr_examples = &[
+        ("10.0.0.0/8", "10.0.0.0/8"),
+        ("192.168.1.7", "192.168.1.7/32"),
+        ("0.0.0.0/0", "0.0.0.0/0"),
+        ("2001:db8::/32", "2001:db8::/32"),
+    ];
+    for &(input, expected) in cidr_examples {
+        let cidr = input.parse::<Cidr>().unwrap();
+        assert_eq!(cidr.to_string(), expected, "parsing {}", input);
+    }
+    for &input in &["10.0.0.1/8", "2001:db8::1/32"] {
+        assert!(input.parse::<Cidr>().is_err(), "should reject {:?}", input);
+    }
+
+    for &input in &[
+        "08:00:2b:01:02:03",
+        "08-00-2B-01-02-03",
+        "0800.2b01.0203",
+        "08002b010203",
+    ] {
+        let mac = input.parse::<MacAddr>().unwrap();
+        assert_eq!(mac.to_string(), "08:00:2b:01:02:03", "parsing {}", input);
+    }
+    for &input in &["08:00:2b:01:02", "08:00-2b:01:02:03", "08:00:2b:01:02:0g"] {
+        assert!(
+            input.parse::<MacAddr>().is_err(),
+            "should reject {:?}",
+            input
+        );
+    }
+}
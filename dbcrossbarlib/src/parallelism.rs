@@ -0,0 +1,208 @@
+//! Deciding how many streams to copy at once.
+//!
+//! By default, we run up to `--max-streams` copies in parallel. With
+//! `--adaptive-streams`, we start with a single copy, and adjust the number of
+//! copies every few seconds based on how quickly we're writing data, never
+//! going above `--max-streams`. This is a simple hill-climbing controller: as
+//! long as throughput doesn't drop, we keep moving in the same direction, and
+//! when it drops, we turn around.
+//!
+//! We don't currently react to error rates. Any error from a stream fails the
+//! whole copy, and drivers already retry transient errors on their own, so
+//! there's nothing for us to observe yet.
+
+use futures::{future::Fuse, select, stream::FuturesUnordered};
+use std::time::{Duration, Instant};
+
+use crate::common::*;
+use crate::usage::Direction;
+
+/// How often should we measure throughput and adjust our parallelism?
+const ADJUST_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How much must throughput drop before we decide our last change was a
+/// mistake? This keeps us from turning around because of ordinary noise.
+const DROP_TOLERANCE: f64 = 0.9;
+
+/// Extension for `BoxStream<BoxFuture<T>>`.
+pub trait BufferWithParallelism<T>: Sized {
+    /// Run futures from the stream in parallel, using the `--max-streams` and
+    /// `--adaptive-streams` settings in `shared_args`, and return their
+    /// results in the order they finish.
+    fn buffer_with_parallelism<S: ArgumentState>(
+        self,
+        ctx: &Context,
+        shared_args: &SharedArguments<S>,
+    ) -> BoxStream<T>;
+}
+
+impl<T: Send + Sized + 'static> BufferWithParallelism<T> for BoxStream<BoxFuture<T>> {
+    fn buffer_with_parallelism<S: ArgumentState>(
+        self,
+        ctx: &Context,
+        shared_args: &SharedArguments<S>,
+    ) -> BoxStream<T> {
+        if shared_args.adaptive_streams() {
+            buffer_unordered_adaptive(ctx.clone(), shared_args.max_streams(), self)
+        } else {
+            self.try_buffer_unordered(shared_args.max_streams()).boxed()
+        }
+    }
+}
+
+/// Run futures from `futures` in parallel, letting an `AdaptiveParallelism`
+/// controller decide how many to run at once.
+fn buffer_unordered_adaptive<T: Send + 'static>(
+    ctx: Context,
+    max_streams: usize,
+    futures: BoxStream<BoxFuture<T>>,
+) -> BoxStream<T> {
+    let (mut sender, receiver) = mpsc::channel::<Result<T>>(1);
+    let worker_ctx = ctx.child(o!("streams_transform" => "buffer_unordered_adaptive"));
+    let worker = async move {
+        let mut futures = futures.fuse();
+        let mut running = FuturesUnordered::new();
+        let mut controller = AdaptiveParallelism::new(max_streams, Instant::now());
+        // Don't use `interval`, whose first tick fires immediately, before
+        // we've measured anything.
+        let mut ticks = tokio::time::interval_at(
+            tokio::time::Instant::now() + ADJUST_INTERVAL,
+            ADJUST_INTERVAL,
+        );
+        loop {
+            if futures.is_done() && running.is_empty() {
+                return Ok(());
+            }
+
+            // Only ask for another future if we have room to run it.
+            let mut next_future =
+                if !futures.is_done() && running.len() < controller.limit() {
+                    futures.next().fuse()
+                } else {
+                    Fuse::terminated()
+                };
+            let mut tick = Box::pin(ticks.tick()).fuse();
+            select! {
+                fut = next_future => match fut {
+                    Some(Ok(fut)) => running.push(fut),
+                    Some(Err(err)) => {
+                        let _ = sender.send(Err(err)).await;
+                        return Ok(());
+                    }
+                    // `futures` is now done.
+                    None => {}
+                },
+                result = running.select_next_some() => {
+                    let is_err = result.is_err();
+                    if sender.send(result).await.is_err() || is_err {
+                        // Our consumer has gone away, or we failed.
+                        return Ok(());
+                    }
+                }
+                _ = tick => {
+                    let total_bytes = worker_ctx.usage().total_bytes(Direction::Up);
+                    let old_limit = controller.limit();
+                    controller.observe(Instant::now(), total_bytes);
+                    if controller.limit() != old_limit {
+                        debug!(
+                            worker_ctx.log(),
+                            "changing parallelism from {} to {}",
+                            old_limit,
+                            controller.limit(),
+                        );
+                    }
+                }
+            }
+        }
+    };
+    ctx.spawn_worker(worker.boxed());
+    receiver.boxed()
+}
+
+/// A hill-climbing controller which tries to find the number of parallel
+/// streams with the best throughput.
+#[derive(Debug)]
+struct AdaptiveParallelism {
+    /// The most streams we may run at once.
+    max: usize,
+    /// How many streams we're currently allowed to run.
+    limit: usize,
+    /// Are we currently adding streams (`true`) or removing them (`false`)?
+    increasing: bool,
+    /// When did our current measurement start?
+    window_start: Instant,
+    /// How many bytes had we written when our measurement started?
+    window_start_bytes: u64,
+    /// Our throughput during our last measurement, in bytes per second.
+    last_rate: Option<f64>,
+}
+
+impl AdaptiveParallelism {
+    /// Create a new controller which starts with one stream.
+    fn new(max: usize, now: Instant) -> Self {
+        Self {
+            max: max.max(1),
+            limit: 1,
+            increasing: true,
+            window_start: now,
+            window_start_bytes: 0,
+            last_rate: None,
+        }
+    }
+
+    /// How many streams should we run at once?
+    fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Record that we've written `total_bytes` so far, and adjust our limit
+    /// based on how our throughput has changed.
+    fn observe(&mut self, now: Instant, total_bytes: u64) {
+        let elapsed = now.duration_since(self.window_start).as_secs_f64();
+        if elapsed <= 0.0 {
+            return;
+        }
+        let rate =
+            total_bytes.saturating_sub(self.window_start_bytes) as f64 / elapsed;
+        if let Some(last_rate) = self.last_rate {
+            if rate < last_rate * DROP_TOLERANCE {
+                // Our last change made things worse, so turn around.
+                self.increasing = !self.increasing;
+            }
+        }
+        if self.increasing {
+            self.limit = (self.limit + 1).min(self.max);
+        } else {
+            self.limit = self.limit.saturating_sub(1).max(1);
+        }
+        self.window_start = now;
+        self.window_start_bytes = total_bytes;
+        self.last_rate = Some(rate);
+    }
+}
+
+#[test]
+fn adaptive_parallelism_climbs_and_backs_off() {
+    let start = Instant::now();
+    let secs = |n: u64| start + Duration::from_secs(n);
+    let mut controller = AdaptiveParallelism::new(3, start);
+    assert_eq!(controller.limit(), 1);
+
+    // Throughput keeps improving, so we keep adding streams, up to our max.
+    controller.observe(secs(1), 100);
+    assert_eq!(controller.limit(), 2);
+    controller.observe(secs(2), 300);
+    assert_eq!(controller.limit(), 3);
+    controller.observe(secs(3), 600);
+    assert_eq!(controller.limit(), 3);
+
+    // Throughput drops, so we back off.
+    controller.observe(secs(4), 700);
+    assert_eq!(controller.limit(), 2);
+
+    // Small changes don't make us turn around.
+    controller.observe(secs(5), 795);
+    assert_eq!(controller.limit(), 1);
+    controller.observe(secs(6), 795);
+    assert_eq!(controller.limit(), 2);
+}
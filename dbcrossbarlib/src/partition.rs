@@ -0,0 +1,301 @@
+//! Splitting CSV data into Hive-style partitions.
+//!
+//! These are specified using `--to-arg=partition_by=COLUMN`, and produce one
+//! output stream for each value of `COLUMN`, named like
+//! `event_date=2023-01-01/part-000`.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::{fs, io::BufReader};
+
+use crate::common::*;
+use crate::concat::concatenate_csv_streams;
+use crate::tokio_glue::{copy_reader_to_stream, SyncStreamReader};
+
+/// The directory name Hive uses for `NULL` partition values.
+const HIVE_DEFAULT_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+
+/// How many partition files should we keep open at once? If we see more
+/// distinct values than this, we close the least recently used file and
+/// reopen it later, so that we don't run out of file descriptors.
+const MAX_OPEN_PARTITIONS: usize = 256;
+
+/// Combine `streams`, and split them into one stream for each value of
+/// `column`. The partition column itself is removed from the output, because
+/// tools which read Hive-style partitions expect to find it in the directory
+/// name.
+///
+/// We need to see every row before we know which partitions we have, so rows
+/// are written to temporary files on the local machine.
+pub(crate) fn partition_csv_streams(
+    ctx: Context,
+    schema: &Table,
+    column: String,
+    streams: BoxStream<CsvStream>,
+) -> Result<BoxStream<CsvStream>> {
+    if !schema.columns.iter().any(|c| c.name == column) {
+        return Err(format_err!(
+            "cannot partition by column {:?} because it isn't in the schema",
+            column,
+        ));
+    }
+    if schema.columns.len() < 2 {
+        return Err(format_err!(
+            "cannot partition by {:?} because it is the only column",
+            column,
+        ));
+    }
+
+    let ctx = ctx.child(o!("streams_transform" => "partition_csv_streams"));
+    let combined = concatenate_csv_streams(ctx.clone(), streams)?;
+    let rdr = SyncStreamReader::new(ctx.clone(), combined.data);
+    let partitions_fut = async move {
+        let tmp_dir = tempdir::TempDir::new("partition")?;
+        let tmp_path = tmp_dir.path().to_owned();
+        let partitions =
+            run_sync_fn_in_background("partition_csv".to_owned(), move || {
+                Ok(partition_csv(&column, rdr, &tmp_path, MAX_OPEN_PARTITIONS)
+                    .context("cannot partition CSV data")?)
+            })
+            .await?;
+
+        // Keep our temporary directory around until we've opened all our
+        // partition files.
+        let tmp_dir = Arc::new(tmp_dir);
+        let partition_streams = stream::iter(partitions).then(move |(dir, path)| {
+            let ctx = ctx.clone();
+            let tmp_dir = tmp_dir.clone();
+            async move {
+                let _tmp_dir = tmp_dir;
+                let name = format!("{}/part-000", dir);
+                let file = fs::File::open(&path)
+                    .await
+                    .with_context(|_| format!("cannot open {}", path.display()))?;
+                let data = BufReader::with_capacity(BUFFER_SIZE, file);
+                let ctx = ctx.child(o!("stream" => name.clone()));
+                let data = copy_reader_to_stream(ctx, data)?;
+                Ok(CsvStream {
+                    name,
+                    data: data.boxed(),
+                })
+            }
+        });
+        Ok::<_, Error>(partition_streams.boxed())
+    };
+    Ok(stream::once(partitions_fut).try_flatten().boxed())
+}
+
+/// A temporary file containing the rows for one partition.
+struct Partition {
+    /// The Hive-style directory name for this partition.
+    dir_name: String,
+    /// The path to our temporary file.
+    path: PathBuf,
+    /// A writer for our temporary file, if it's currently open.
+    wtr: Option<csv::Writer<io::BufWriter<File>>>,
+    /// When we last wrote to this partition, counted in rows.
+    last_used: u64,
+}
+
+/// Copy rows from `rdr` into one CSV file in `dir` for each value of `column`,
+/// leaving out `column` itself. Returns a list of Hive-style directory names
+/// and the files containing the rows for each, sorted by name. At most
+/// `max_open` files will be open at any one time.
+///
+/// This is synchronous, and should be run using `run_sync_fn_in_background`.
+fn partition_csv<R: Read>(
+    column: &str,
+    rdr: R,
+    dir: &Path,
+    max_open: usize,
+) -> Result<Vec<(String, PathBuf)>> {
+    assert!(max_open > 0, "must allow at least one open partition");
+    let mut rdr = csv::Reader::from_reader(rdr);
+    let headers = rdr.byte_headers()?.clone();
+    if headers.is_empty() {
+        // Empty input has no partitions.
+        return Ok(vec![]);
+    }
+    let idx = headers
+        .iter()
+        .position(|h| h == column.as_bytes())
+        .ok_or_else(|| format_err!("cannot find partition column {:?}", column))?;
+    let without_column = |row: &csv::ByteRecord| -> csv::ByteRecord {
+        row.iter()
+            .enumerate()
+            .filter(|&(i, _)| i != idx)
+            .map(|(_, value)| value)
+            .collect()
+    };
+    let out_headers = without_column(&headers);
+
+    // All our partitions, plus the values of our open partitions, indexed by
+    // when they were last used.
+    let mut partitions = HashMap::<Vec<u8>, Partition>::new();
+    let mut open_by_last_use = BTreeMap::<u64, Vec<u8>>::new();
+    let mut row = csv::ByteRecord::new();
+    let mut row_count = 0;
+    while rdr.read_byte_record(&mut row)? {
+        row_count += 1;
+        let value = row.get(idx).unwrap_or_default();
+        let is_open = partitions
+            .get(value)
+            .map(|p| p.wtr.is_some())
+            .unwrap_or(false);
+
+        // If we need to open a file, make sure we have room.
+        if !is_open && open_by_last_use.len() >= max_open {
+            let oldest = *open_by_last_use
+                .keys()
+                .next()
+                .expect("should have an open partition");
+            let oldest_value = open_by_last_use
+                .remove(&oldest)
+                .expect("should have oldest partition");
+            let oldest_partition = partitions
+                .get_mut(&oldest_value)
+                .expect("should have partition for open file");
+            if let Some(mut wtr) = oldest_partition.wtr.take() {
+                wtr.flush()?;
+            }
+        }
+
+        // Look up our partition, creating or re-opening its file if needed.
+        let partition = match partitions.get_mut(value) {
+            Some(partition) => {
+                if partition.wtr.is_none() {
+                    let file =
+                        OpenOptions::new().append(true).open(&partition.path)?;
+                    partition.wtr =
+                        Some(csv::Writer::from_writer(io::BufWriter::new(file)));
+                } else {
+                    open_by_last_use.remove(&partition.last_used);
+                }
+                partition
+            }
+            None => {
+                let value_str = String::from_utf8(value.to_owned())
+                    .context("partition values must be UTF-8")?;
+                let dir_name = format!("{}={}", column, hive_escape(&value_str));
+                let path = dir.join(format!("partition_{}.csv", partitions.len()));
+                let mut wtr =
+                    csv::Writer::from_writer(io::BufWriter::new(File::create(&path)?));
+                wtr.write_byte_record(&out_headers)?;
+                partitions.entry(value.to_owned()).or_insert(Partition {
+                    dir_name,
+                    path,
+                    wtr: Some(wtr),
+                    last_used: row_count,
+                })
+            }
+        };
+        partition.last_used = row_count;
+        open_by_last_use.insert(row_count, value.to_owned());
+        partition
+            .wtr
+            .as_mut()
+            .expect("partition should be open")
+            .write_byte_record(&without_column(&row))?;
+    }
+
+    let mut result = vec![];
+    for (_, partition) in partitions {
+        if let Some(mut wtr) = partition.wtr {
+            wtr.flush()?;
+        }
+        result.push((partition.dir_name, partition.path));
+    }
+    result.sort();
+    Ok(result)
+}
+
+/// Escape a partition value the way Hive does, so that it can be used as part
+/// of a directory name.
+fn hive_escape(value: &str) -> String {
+    if value.is_empty() {
+        return HIVE_DEFAULT_PARTITION.to_owned();
+    }
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\u{0}'..='\u{1F}'
+            | '"'
+            | '#'
+            | '%'
+            | '\''
+            | '*'
+            | '/'
+            | ':'
+            | '='
+            | '?'
+            | '\\'
+            | '\u{7F}'
+            | '{'
+            | '['
+            | ']'
+            | '^' => escaped.push_str(&format!("%{:02X}", c as u32)),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[test]
+fn partition_csv_splits_rows_by_value() {
+    use std::fs::read_to_string;
+
+    let tmp_dir = tempdir::TempDir::new("partition_test").unwrap();
+    let input = "id,day,name\n1,2023-01-01,a\n2,2023-01-02,b\n3,2023-01-01,c\n4,,d\n";
+    let partitions =
+        partition_csv("day", input.as_bytes(), tmp_dir.path(), 10).unwrap();
+    let outputs = partitions
+        .into_iter()
+        .map(|(dir, path)| (dir, read_to_string(path).unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        outputs,
+        vec![
+            (
+                "day=2023-01-01".to_owned(),
+                "id,name\n1,a\n3,c\n".to_owned()
+            ),
+            ("day=2023-01-02".to_owned(), "id,name\n2,b\n".to_owned()),
+            (
+                "day=__HIVE_DEFAULT_PARTITION__".to_owned(),
+                "id,name\n4,d\n".to_owned()
+            ),
+        ],
+    );
+    assert!(partition_csv("missing", input.as_bytes(), tmp_dir.path(), 10).is_err());
+    assert_eq!(
+        hive_escape("2023-01-01T12:00:00Z"),
+        "2023-01-01T12%3A00%3A00Z"
+    );
+    assert_eq!(hive_escape("a/b=c"), "a%2Fb%3Dc");
+}
+
+#[test]
+fn partition_csv_reopens_files_when_too_many_are_open() {
+    use std::fs::read_to_string;
+
+    // Interleave 5 partition values, but only allow 2 open files.
+    let tmp_dir = tempdir::TempDir::new("partition_test").unwrap();
+    let mut input = "id,key\n".to_owned();
+    for id in 0..20 {
+        input.push_str(&format!("{},k{}\n", id, id % 5));
+    }
+    let partitions =
+        partition_csv("key", input.as_bytes(), tmp_dir.path(), 2).unwrap();
+    assert_eq!(partitions.len(), 5);
+    for (key, (dir, path)) in partitions.into_iter().enumerate() {
+        assert_eq!(dir, format!("key=k{}", key));
+        let expected =
+            format!("id\n{}\n{}\n{}\n{}\n", key, key + 5, key + 10, key + 15,);
+        assert_eq!(read_to_string(path).unwrap(), expected);
+    }
+}
@@ -0,0 +1,216 @@
+//! Renaming columns while copying.
+//!
+//! Source systems often use column names which aren't allowed by the
+//! destination, like `CamelCase` names with spaces in them. We rename these
+//! columns in the schema we pass to the destination, and in the header of each
+//! CSV stream.
+
+use csv;
+use serde_json::Value;
+use std::{collections::HashSet, io, str::FromStr};
+
+use crate::common::*;
+use crate::schema::Table;
+use crate::transform::spawn_sync_transform;
+
+/// A list of columns to rename, in the form `(old_name, new_name)`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ColumnRenames {
+    renames: Vec<(String, String)>,
+}
+
+impl ColumnRenames {
+    /// Parse a JSON object mapping old column names to new ones.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let map = serde_json::from_str::<serde_json::Map<String, Value>>(json)
+            .context("expected a JSON object mapping old names to new ones")?;
+        let renames = map
+            .into_iter()
+            .map(|(old, new)| match new {
+                Value::String(new) => Ok((old, new)),
+                _ => Err(format_err!("new name for {:?} must be a string", old)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ColumnRenames { renames })
+    }
+
+    /// Add all the renames in `other` to this list.
+    pub fn extend(&mut self, other: ColumnRenames) {
+        self.renames.extend(other.renames);
+    }
+
+    /// Are there any columns to rename?
+    pub fn is_empty(&self) -> bool {
+        self.renames.is_empty()
+    }
+
+    /// Look up the new name for `name`.
+    fn rename<'a>(&'a self, name: &'a str) -> &'a str {
+        self.renames
+            .iter()
+            .find(|(old, _)| old == name)
+            .map(|(_, new)| &new[..])
+            .unwrap_or(name)
+    }
+
+    /// Return a copy of `table` with our columns (and any keys and indexes
+    /// which use them) renamed. Fails if we try to rename a column that
+    /// doesn't exist, or if two columns would end up with the same name.
+    pub fn rename_table(&self, table: &Table) -> Result<Table> {
+        for (old, _) in &self.renames {
+            if !table.columns.iter().any(|c| &c.name == old) {
+                return Err(format_err!(
+                    "cannot rename column {:?} because it isn't in the schema",
+                    old,
+                ));
+            }
+        }
+        let mut table = table.clone();
+        let mut seen = HashSet::new();
+        for col in &mut table.columns {
+            col.name = self.rename(&col.name).to_owned();
+            if !seen.insert(col.name.clone()) {
+                return Err(format_err!(
+                    "renaming columns would produce two columns named {:?}",
+                    col.name,
+                ));
+            }
+        }
+        for key in &mut table.primary_key {
+            *key = self.rename(key).to_owned();
+        }
+        for index in &mut table.indexes {
+            for col in &mut index.columns {
+                *col = self.rename(col).to_owned();
+            }
+        }
+        Ok(table)
+    }
+}
+
+impl FromStr for ColumnRenames {
+    type Err = Error;
+
+    /// Parse a list of the form `old:new,old2:new2`.
+    fn from_str(s: &str) -> Result<Self> {
+        let renames = s
+            .split(',')
+            .filter(|item| !item.trim().is_empty())
+            .map(|item| {
+                let mut parts = item.splitn(2, ':');
+                match (parts.next(), parts.next()) {
+                    (Some(old), Some(new)) if !old.is_empty() && !new.is_empty() => {
+                        Ok((old.to_owned(), new.to_owned()))
+                    }
+                    _ => Err(format_err!(
+                        "expected a rename of the form old:new, found {:?}",
+                        item,
+                    )),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ColumnRenames { renames })
+    }
+}
+
+/// Rename the columns in the header of each stream in `streams`.
+pub fn rename_csv_headers(
+    ctx: Context,
+    renames: ColumnRenames,
+    streams: BoxStream<CsvStream>,
+) -> BoxStream<CsvStream> {
+    streams
+        .and_then(move |stream| {
+            let ctx = ctx.clone();
+            let renames = renames.clone();
+            async move {
+                let data = spawn_sync_transform(
+                    ctx,
+                    "rename_csv_headers".to_owned(),
+                    stream.data,
+                    move |_ctx, rdr, wtr| rename_header(&renames, rdr, wtr),
+                )?;
+                Ok(CsvStream {
+                    name: stream.name,
+                    data,
+                })
+            }
+        })
+        .boxed()
+}
+
+/// Copy CSV data from `rdr` to `wtr`, renaming the columns in the header.
+///
+/// This is synchronous, and should be run using `spawn_sync_transform`.
+fn rename_header<R: Read, W: Write>(
+    renames: &ColumnRenames,
+    rdr: R,
+    wtr: W,
+) -> Result<()> {
+    let mut rdr = csv::Reader::from_reader(rdr);
+    let mut wtr =
+        csv::Writer::from_writer(io::BufWriter::with_capacity(BUFFER_SIZE, wtr));
+    let headers = rdr.headers()?.clone();
+    wtr.write_record(headers.iter().map(|h| renames.rename(h)))?;
+    for row in rdr.byte_records() {
+        wtr.write_byte_record(&row?)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[test]
+fn renames_are_parsed_and_applied() {
+    use crate::schema::{Column, DataType};
+
+    let renames = "UserId:user_id,Full Name:full_name"
+        .parse::<ColumnRenames>()
+        .unwrap();
+    let json_renames =
+        ColumnRenames::from_json(r#"{"UserId":"user_id","Full Name":"full_name"}"#)
+            .unwrap();
+    assert!("UserId".parse::<ColumnRenames>().is_err());
+
+    for renames in &[&renames, &json_renames] {
+        let mut out = vec![];
+        rename_header(renames, "UserId,Full Name,x\n1,A,2\n".as_bytes(), &mut out)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "user_id,full_name,x\n1,A,2\n",
+        );
+    }
+
+    let table = Table {
+        name: "t".to_owned(),
+        columns: ["UserId", "Full Name", "full_name"]
+            .iter()
+            .map(|&name| Column {
+                name: name.to_owned(),
+                is_nullable: true,
+                data_type: DataType::Text,
+                comment: None,
+                default: None,
+                collation: None,
+            })
+            .collect(),
+        primary_key: vec!["UserId".to_owned()],
+        indexes: vec![],
+    };
+    let renamed = "UserId:user_id"
+        .parse::<ColumnRenames>()
+        .unwrap()
+        .rename_table(&table)
+        .unwrap();
+    assert_eq!(renamed.columns[0].name, "user_id");
+    assert_eq!(renamed.primary_key, vec!["user_id".to_owned()]);
+    let err = renames.rename_table(&table).unwrap_err().to_string();
+    assert!(err.contains("two columns named \"full_name\""), "{}", err);
+    let err = "Missing:x"
+        .parse::<ColumnRenames>()
+        .unwrap()
+        .rename_table(&table)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("isn't in the schema"), "{}", err);
+}
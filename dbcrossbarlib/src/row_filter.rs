@@ -0,0 +1,594 @@
+//! Evaluating simple SQL `WHERE` expressions against CSV rows.
+//!
+//! Databases can filter rows for us, but sources like `csv:`, `s3://` and
+//! `gs://` can't. For these, we evaluate a small subset of SQL ourselves:
+//!
+//! - Comparisons using `=`, `<>`, `!=`, `<`, `<=`, `>` and `>=`.
+//! - `IS NULL`, `IS NOT NULL`, `IN (...)` and `NOT IN (...)`.
+//! - `AND`, `OR`, `NOT` and parentheses.
+//! - Column names (optionally in `"double quotes"`), `'strings'`, numbers,
+//!   `TRUE`, `FALSE` and `NULL`.
+//!
+//! We compare values using the type of the column involved: numbers
+//! numerically, booleans as booleans, dates and timestamps chronologically,
+//! and everything else as text. A date compared to a timestamp means midnight
+//! at the start of that day. CSV files without a schema only have text
+//! columns, so if we compare a text column to an unquoted number, we compare
+//! numerically. Empty CSV cells are `NULL`, and we follow SQL's rules for
+//! `NULL`, so `x <> 1` does not match rows where `x` is `NULL`.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use std::{cmp::Ordering, io, iter, str::FromStr};
+
+use crate::common::*;
+use crate::from_csv_cell::FromCsvCell;
+use crate::schema::DataType;
+use crate::transform::spawn_sync_transform;
+
+/// Include our `rust-peg` grammar.
+///
+/// We disable lots of clippy warnings because this is machine-generated code.
+#[allow(clippy::all, rust_2018_idioms, elided_lifetimes_in_paths)]
+mod grammar {
+    include!(concat!(env!("OUT_DIR"), "/row_filter.rs"));
+}
+
+/// A parsed filter expression.
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    IsNull(Operand),
+    In(Operand, Vec<Operand>),
+    Compare(Operand, CmpOp, Operand),
+}
+
+/// A comparison operator.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    /// Does `ordering` satisfy this operator?
+    fn test(self, ordering: Ordering) -> bool {
+        match self {
+            CmpOp::Eq => ordering == Ordering::Equal,
+            CmpOp::Ne => ordering != Ordering::Equal,
+            CmpOp::Lt => ordering == Ordering::Less,
+            CmpOp::Le => ordering != Ordering::Greater,
+            CmpOp::Gt => ordering == Ordering::Greater,
+            CmpOp::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+/// A value in a filter expression.
+#[derive(Clone, Debug, PartialEq)]
+enum Operand {
+    Null,
+    /// A literal value, in the same format we'd expect in a CSV cell.
+    Literal(String),
+    /// An unquoted numeric literal.
+    Number(String),
+    Column(String),
+}
+
+/// A `WHERE` expression that we can evaluate against CSV rows.
+#[derive(Clone, Debug)]
+pub struct RowFilter {
+    expr: Expr,
+}
+
+impl FromStr for RowFilter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let expr = grammar::filter(s)
+            .map_err(|err| format_err!("cannot evaluate {:?} locally: {}", s, err))?;
+        Ok(RowFilter { expr })
+    }
+}
+
+impl RowFilter {
+    /// Prepare to evaluate this filter against CSV rows with the specified
+    /// `headers`, using the column types in `table`.
+    pub fn bind(
+        &self,
+        table: &Table,
+        headers: &csv::StringRecord,
+    ) -> Result<BoundRowFilter> {
+        Ok(BoundRowFilter {
+            expr: bind_expr(&self.expr, table, headers)?,
+        })
+    }
+}
+
+/// A `RowFilter` which has been matched up with a specific CSV header.
+#[derive(Clone, Debug)]
+pub struct BoundRowFilter {
+    expr: BoundExpr,
+}
+
+impl BoundRowFilter {
+    /// Should we keep `row`?
+    pub fn matches(&self, row: &csv::StringRecord) -> Result<bool> {
+        Ok(self.expr.eval(row)? == Some(true))
+    }
+}
+
+/// Drop any rows in `streams` which don't match `where_clause`, using the
+/// column types in `table`. This is used by drivers like `csv:` and `gs://`,
+/// which have no database to filter rows for them.
+pub(crate) fn filter_csv_streams(
+    ctx: Context,
+    table: Table,
+    where_clause: Option<String>,
+    streams: BoxStream<CsvStream>,
+) -> Result<BoxStream<CsvStream>> {
+    let filter = match where_clause {
+        Some(where_clause) => where_clause.parse::<RowFilter>()?,
+        None => return Ok(streams),
+    };
+    Ok(streams
+        .and_then(move |stream| {
+            let ctx = ctx.clone();
+            let table = table.clone();
+            let filter = filter.clone();
+            async move {
+                let name = stream.name.clone();
+                let data = spawn_sync_transform(
+                    ctx,
+                    "filter_csv".to_owned(),
+                    stream.data,
+                    move |_ctx, rdr, wtr| {
+                        filter_csv(&table, &filter, rdr, wtr).with_context(|_| {
+                            format!("cannot filter stream {}", name)
+                        })?;
+                        Ok(())
+                    },
+                )?;
+                Ok(CsvStream {
+                    name: stream.name,
+                    data,
+                })
+            }
+        })
+        .boxed())
+}
+
+/// Copy the rows in `rdr` which match `filter` to `wtr`.
+///
+/// This is synchronous, and should be run using `spawn_sync_transform`.
+fn filter_csv<R: Read, W: Write>(
+    table: &Table,
+    filter: &RowFilter,
+    rdr: R,
+    wtr: W,
+) -> Result<()> {
+    let mut rdr = csv::Reader::from_reader(rdr);
+    let mut wtr =
+        csv::Writer::from_writer(io::BufWriter::with_capacity(BUFFER_SIZE, wtr));
+
+    let headers = rdr.headers()?.clone();
+    let filter = filter.bind(table, &headers)?;
+    wtr.write_record(&headers)?;
+    let mut row = csv::StringRecord::new();
+    while rdr.read_record(&mut row)? {
+        if filter.matches(&row)? {
+            wtr.write_record(&row)?;
+        }
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// An `Expr` which refers to columns by index.
+#[derive(Clone, Debug)]
+enum BoundExpr {
+    And(Box<BoundExpr>, Box<BoundExpr>),
+    Or(Box<BoundExpr>, Box<BoundExpr>),
+    Not(Box<BoundExpr>),
+    IsNull(BoundOperand),
+    In(BoundOperand, Vec<BoundOperand>, CompareAs),
+    Compare(BoundOperand, CmpOp, BoundOperand, CompareAs),
+}
+
+impl BoundExpr {
+    /// Evaluate this expression using SQL's three-valued logic, where `None`
+    /// means "unknown".
+    fn eval(&self, row: &csv::StringRecord) -> Result<Option<bool>> {
+        match self {
+            BoundExpr::And(l, r) => {
+                let l = l.eval(row)?;
+                if l == Some(false) {
+                    return Ok(Some(false));
+                }
+                Ok(match (l, r.eval(row)?) {
+                    (_, Some(false)) => Some(false),
+                    (Some(true), Some(true)) => Some(true),
+                    _ => None,
+                })
+            }
+            BoundExpr::Or(l, r) => {
+                let l = l.eval(row)?;
+                if l == Some(true) {
+                    return Ok(Some(true));
+                }
+                Ok(match (l, r.eval(row)?) {
+                    (_, Some(true)) => Some(true),
+                    (Some(false), Some(false)) => Some(false),
+                    _ => None,
+                })
+            }
+            BoundExpr::Not(expr) => Ok(expr.eval(row)?.map(|b| !b)),
+            BoundExpr::IsNull(operand) => Ok(Some(operand.value(row).is_none())),
+            BoundExpr::In(operand, items, compare_as) => {
+                let value = match operand.value(row) {
+                    Some(value) => value,
+                    None => return Ok(None),
+                };
+                let mut saw_null = false;
+                for item in items {
+                    match item.value(row) {
+                        Some(item) => {
+                            if compare_as.compare(value, item)? == Ordering::Equal {
+                                return Ok(Some(true));
+                            }
+                        }
+                        None => saw_null = true,
+                    }
+                }
+                Ok(if saw_null { None } else { Some(false) })
+            }
+            BoundExpr::Compare(l, op, r, compare_as) => {
+                match (l.value(row), r.value(row)) {
+                    (Some(l), Some(r)) => Ok(Some(op.test(compare_as.compare(l, r)?))),
+                    _ => Ok(None),
+                }
+            }
+        }
+    }
+}
+
+/// An `Operand` which refers to columns by index.
+#[derive(Clone, Debug)]
+enum BoundOperand {
+    Null,
+    Literal(String),
+    Column(usize),
+}
+
+impl BoundOperand {
+    /// Get our value from `row`, or `None` if it's `NULL`.
+    fn value<'a>(&'a self, row: &'a csv::StringRecord) -> Option<&'a str> {
+        match self {
+            BoundOperand::Null => None,
+            BoundOperand::Literal(value) => Some(value),
+            BoundOperand::Column(idx) => row.get(*idx).filter(|cell| !cell.is_empty()),
+        }
+    }
+}
+
+/// How should we compare two values?
+#[derive(Clone, Copy, Debug)]
+enum CompareAs {
+    Bool,
+    Number,
+    Date,
+    Timestamp,
+    TimestampWithTimeZone,
+    Text,
+}
+
+impl CompareAs {
+    /// Decide how to compare values in a column of type `data_type`.
+    fn for_data_type(data_type: &DataType) -> CompareAs {
+        match data_type {
+            DataType::Bool => CompareAs::Bool,
+            DataType::Decimal
+            | DataType::DecimalWithPrecision(_)
+            | DataType::Float32
+            | DataType::Float64
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64 => CompareAs::Number,
+            DataType::Date => CompareAs::Date,
+            DataType::TimestampWithoutTimeZone => CompareAs::Timestamp,
+            DataType::TimestampWithTimeZone => CompareAs::TimestampWithTimeZone,
+            _ => CompareAs::Text,
+        }
+    }
+
+    /// Compare two non-`NULL` values.
+    fn compare(self, l: &str, r: &str) -> Result<Ordering> {
+        match self {
+            CompareAs::Bool => compare_parsed::<bool>(l, r),
+            CompareAs::Number => compare_parsed::<f64>(l, r),
+            CompareAs::Date => compare_parsed::<NaiveDate>(l, r),
+            CompareAs::Timestamp => Ok(parse_timestamp(l)?.cmp(&parse_timestamp(r)?)),
+            CompareAs::TimestampWithTimeZone => Ok(parse_timestamp_with_time_zone(l)?
+                .cmp(&parse_timestamp_with_time_zone(r)?)),
+            CompareAs::Text => Ok(l.cmp(r)),
+        }
+    }
+}
+
+/// Parse `l` and `r` as `T` and compare them.
+fn compare_parsed<T: FromCsvCell + PartialOrd>(l: &str, r: &str) -> Result<Ordering> {
+    T::from_csv_cell(l)?
+        .partial_cmp(&T::from_csv_cell(r)?)
+        .ok_or_else(|| format_err!("cannot compare {:?} and {:?}", l, r))
+}
+
+/// Parse a timestamp. Like SQL, we treat a date as midnight at the start of
+/// that day.
+fn parse_timestamp(value: &str) -> Result<NaiveDateTime> {
+    NaiveDateTime::from_csv_cell(value).or_else(|err| {
+        match NaiveDate::from_csv_cell(value) {
+            Ok(date) => {
+                Ok(date.and_hms_opt(0, 0, 0).expect("midnight should be valid"))
+            }
+            Err(_) => Err(err),
+        }
+    })
+}
+
+/// Parse a timestamp with a time zone. We treat a date as midnight UTC.
+fn parse_timestamp_with_time_zone(value: &str) -> Result<DateTime<Utc>> {
+    DateTime::<Utc>::from_csv_cell(value).or_else(
+        |err| match NaiveDate::from_csv_cell(value) {
+            Ok(date) => Ok(Utc.from_utc_datetime(
+                &date.and_hms_opt(0, 0, 0).expect("midnight should be valid"),
+            )),
+            Err(_) => Err(err),
+        },
+    )
+}
+
+/// Look up column names in `expr`, and decide how to compare values.
+fn bind_expr(
+    expr: &Expr,
+    table: &Table,
+    headers: &csv::StringRecord,
+) -> Result<BoundExpr> {
+    let bind = |expr: &Expr| -> Result<Box<BoundExpr>> {
+        Ok(Box::new(bind_expr(expr, table, headers)?))
+    };
+    Ok(match expr {
+        Expr::And(l, r) => BoundExpr::And(bind(l)?, bind(r)?),
+        Expr::Or(l, r) => BoundExpr::Or(bind(l)?, bind(r)?),
+        Expr::Not(expr) => BoundExpr::Not(bind(expr)?),
+        Expr::IsNull(operand) => BoundExpr::IsNull(bind_operand(operand, headers)?),
+        Expr::In(operand, items) => BoundExpr::In(
+            bind_operand(operand, headers)?,
+            items
+                .iter()
+                .map(|item| bind_operand(item, headers))
+                .collect::<Result<Vec<_>>>()?,
+            compare_as(iter::once(operand).chain(items), table),
+        ),
+        Expr::Compare(l, op, r) => BoundExpr::Compare(
+            bind_operand(l, headers)?,
+            *op,
+            bind_operand(r, headers)?,
+            compare_as([l, r].iter().copied(), table),
+        ),
+    })
+}
+
+/// Find the index of a column in `headers`.
+fn bind_operand(
+    operand: &Operand,
+    headers: &csv::StringRecord,
+) -> Result<BoundOperand> {
+    Ok(match operand {
+        Operand::Null => BoundOperand::Null,
+        Operand::Literal(value) | Operand::Number(value) => {
+            BoundOperand::Literal(value.to_owned())
+        }
+        Operand::Column(name) => BoundOperand::Column(
+            headers
+                .iter()
+                .position(|header| header == name)
+                .ok_or_else(|| {
+                    format_err!("cannot find column {:?} in CSV header", name)
+                })?,
+        ),
+    })
+}
+
+/// Decide how to compare `operands`, based on the type of the first column we
+/// find. If there are no columns, or only text columns, we compare numerically
+/// if there's an unquoted number, and as text otherwise.
+fn compare_as<'a>(
+    operands: impl IntoIterator<Item = &'a Operand>,
+    table: &Table,
+) -> CompareAs {
+    let operands = operands.into_iter().collect::<Vec<_>>();
+    let column_compare_as = operands
+        .iter()
+        .find_map(|operand| match operand {
+            Operand::Column(name) => table.columns.iter().find(|c| &c.name == name),
+            _ => None,
+        })
+        .map(|column| CompareAs::for_data_type(&column.data_type));
+    let has_number = operands.iter().any(|o| matches!(o, Operand::Number(_)));
+    match column_compare_as {
+        Some(CompareAs::Text) | None if has_number => CompareAs::Number,
+        Some(compare_as) => compare_as,
+        None => CompareAs::Text,
+    }
+}
+
+#[test]
+fn row_filter_follows_sql_rules() {
+    use crate::schema::Column;
+
+    let column = |name: &str, data_type: DataType| Column {
+        name: name.to_owned(),
+        is_nullable: true,
+        data_type,
+        comment: None,
+        default: None,
+        collation: None,
+    };
+    let table = Table {
+        name: "t".to_owned(),
+        columns: vec![
+            column("id", DataType::Int64),
+            column("name", DataType::Text),
+            column("active", DataType::Bool),
+            column("Created At", DataType::Date),
+        ],
+        primary_key: vec![],
+        indexes: vec![],
+    };
+    let headers = csv::StringRecord::from(vec!["id", "name", "active", "Created At"]);
+    let rows = [
+        csv::StringRecord::from(vec!["1", "ann", "t", "2020-01-15"]),
+        csv::StringRecord::from(vec!["2", "bob", "f", "2020-02-15"]),
+        csv::StringRecord::from(vec!["10", "", "", ""]),
+    ];
+    let matching_ids = |filter: &str| {
+        let filter = filter
+            .parse::<RowFilter>()
+            .unwrap()
+            .bind(&table, &headers)
+            .unwrap();
+        rows.iter()
+            .filter(|row| filter.matches(row).unwrap())
+            .map(|row| row[0].to_owned())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(matching_ids("id > 2"), vec!["10"]);
+    assert_eq!(matching_ids("id >= 2 and id <= 9"), vec!["2"]);
+    assert_eq!(matching_ids("name = 'bob' OR id = 1"), vec!["1", "2"]);
+    assert_eq!(matching_ids("name <> 'bob'"), vec!["1"]);
+    assert_eq!(matching_ids("NOT (name = 'bob')"), vec!["1"]);
+    assert_eq!(matching_ids("name IS NULL"), vec!["10"]);
+    assert_eq!(matching_ids("name is not null"), vec!["1", "2"]);
+    assert_eq!(matching_ids("active = TRUE"), vec!["1"]);
+    assert_eq!(matching_ids("name IN ('ann', 'bob')"), vec!["1", "2"]);
+    assert_eq!(
+        matching_ids("name NOT IN ('ann', NULL)"),
+        Vec::<String>::new()
+    );
+    assert_eq!(matching_ids(r#""Created At" < '2020-02-01'"#), vec!["1"]);
+    assert_eq!(matching_ids("name = 'it''s'"), Vec::<String>::new());
+}
+
+#[test]
+fn row_filter_compares_dates_to_timestamps_and_text_to_numbers() {
+    use crate::schema::Column;
+
+    let column = |name: &str, data_type: DataType| Column {
+        name: name.to_owned(),
+        is_nullable: true,
+        data_type,
+        comment: None,
+        default: None,
+        collation: None,
+    };
+    let table = Table {
+        name: "t".to_owned(),
+        columns: vec![
+            column("id", DataType::Text),
+            column("ts", DataType::TimestampWithoutTimeZone),
+            column("tstz", DataType::TimestampWithTimeZone),
+        ],
+        primary_key: vec![],
+        indexes: vec![],
+    };
+    let headers = csv::StringRecord::from(vec!["id", "ts", "tstz"]);
+    let rows = [
+        csv::StringRecord::from(vec![
+            "9",
+            "2020-05-31 23:59:59",
+            "2020-05-31 23:59:59+00",
+        ]),
+        csv::StringRecord::from(vec![
+            "10",
+            "2020-06-01 00:00:00",
+            "2020-06-01 00:00:00+00",
+        ]),
+    ];
+    let matching_ids = |table: &Table, filter: &str| {
+        let filter = filter
+            .parse::<RowFilter>()
+            .unwrap()
+            .bind(table, &headers)
+            .unwrap();
+        rows.iter()
+            .filter(|row| filter.matches(row).unwrap())
+            .map(|row| row[0].to_owned())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(matching_ids(&table, "ts >= '2020-06-01'"), vec!["10"]);
+    assert_eq!(matching_ids(&table, "tstz < '2020-06-01'"), vec!["9"]);
+    assert_eq!(matching_ids(&table, "id > 9"), vec!["10"]);
+    assert_eq!(matching_ids(&table, "id > '9'"), Vec::<String>::new());
+
+    // Without a schema, we still compare numbers numerically.
+    let no_schema = Table {
+        name: "t".to_owned(),
+        columns: vec![],
+        primary_key: vec![],
+        indexes: vec![],
+    };
+    assert_eq!(matching_ids(&no_schema, "id > 9"), vec!["10"]);
+    assert_eq!(matching_ids(&no_schema, "id IN (10, 11)"), vec!["10"]);
+}
+
+#[test]
+fn filter_csv_keeps_header_and_matching_rows() {
+    use crate::schema::Column;
+
+    let table = Table {
+        name: "t".to_owned(),
+        columns: vec![Column {
+            name: "id".to_owned(),
+            is_nullable: true,
+            data_type: DataType::Int32,
+            comment: None,
+            default: None,
+            collation: None,
+        }],
+        primary_key: vec![],
+        indexes: vec![],
+    };
+    let run = |filter: &str, input: &str| -> Result<String> {
+        let filter = filter.parse::<RowFilter>()?;
+        let mut out = vec![];
+        filter_csv(&table, &filter, input.as_bytes(), &mut out)?;
+        Ok(String::from_utf8(out)?)
+    };
+    assert_eq!(run("id > 1", "id\n1\n2\n10\n").unwrap(), "id\n2\n10\n");
+    assert_eq!(run("id > 100", "id\n1\n").unwrap(), "id\n");
+    assert!(run("missing = 1", "id\n1\n").is_err());
+}
+
+#[test]
+fn row_filter_reports_errors() {
+    assert!("id >".parse::<RowFilter>().is_err());
+    assert!("id = 1 AND".parse::<RowFilter>().is_err());
+    assert!("lower(name) = 'ann'".parse::<RowFilter>().is_err());
+
+    let table = Table {
+        name: "t".to_owned(),
+        columns: vec![],
+        primary_key: vec![],
+        indexes: vec![],
+    };
+    let headers = csv::StringRecord::from(vec!["id"]);
+    let filter = "missing = 1".parse::<RowFilter>().unwrap();
+    assert!(filter.bind(&table, &headers).is_err());
+}
@@ -0,0 +1,197 @@
+//! Generating sample rows for a table schema.
+//!
+//! This is used by `dbcrossbar conv --with-sample-rows`, so that people can
+//! hand both a schema and some example data to downstream users. The values
+//! are synthetic, but they follow our CSV interchange format, so they can be
+//! written to any destination driver.
+
+use chrono::{Duration, NaiveDate, NaiveTime};
+use serde_json::{json, Value};
+use std::io;
+
+use crate::common::*;
+use crate::schema::{Column, DataType};
+
+/// Every fifth row will contain `NULL` in nullable columns.
+const NULL_EVERY: usize = 5;
+
+/// Generate a CSV stream containing `rows` sample rows for `table`.
+pub fn sample_csv_stream(table: &Table, rows: usize) -> Result<CsvStream> {
+    let mut buf = vec![];
+    write_sample_csv(&table.columns, rows, &mut buf)?;
+    Ok(CsvStream {
+        name: table.name.clone(),
+        data: box_stream_once(Ok(BytesMut::from(&buf[..]))),
+    })
+}
+
+/// Write `rows` sample rows for `columns` to `wtr` as CSV.
+fn write_sample_csv<W: io::Write>(
+    columns: &[Column],
+    rows: usize,
+    wtr: W,
+) -> Result<()> {
+    let mut wtr = csv::Writer::from_writer(wtr);
+    wtr.write_record(columns.iter().map(|c| &c.name))?;
+    for idx in 0..rows {
+        let row = columns
+            .iter()
+            .map(|col| {
+                if col.is_nullable && idx % NULL_EVERY == NULL_EVERY - 1 {
+                    Ok(String::new())
+                } else {
+                    Ok(sample_cell(&col.data_type, idx).with_context(|_| {
+                        format!("could not generate sample for {}", col.name)
+                    })?)
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        wtr.write_record(&row)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Generate a sample CSV cell of type `data_type` for row `idx`.
+fn sample_cell(data_type: &DataType, idx: usize) -> Result<String> {
+    match sample_value(data_type, idx)? {
+        Value::String(s) => Ok(s),
+        Value::Bool(true) => Ok("t".to_owned()),
+        Value::Bool(false) => Ok("f".to_owned()),
+        value => Ok(serde_json::to_string(&value)?),
+    }
+}
+
+/// Generate a sample value of type `data_type` for row `idx`, represented the
+/// way it would appear inside a JSON array in our CSV interchange format.
+fn sample_value(data_type: &DataType, idx: usize) -> Result<Value> {
+    // Use `n` for values that should start at 1.
+    let n = idx + 1;
+    Ok(match data_type {
+        DataType::Array(elem) => {
+            json!([sample_value(elem, idx)?, sample_value(elem, idx + 1)?])
+        }
+        DataType::Bool => json!(idx.is_multiple_of(2)),
+        DataType::Bytes => json!(base64::encode(format!("sample {}", n))),
+        DataType::Cidr => json!(format!("10.{}.0.0/16", n % 256)),
+        DataType::Date => {
+            let date = NaiveDate::from_ymd_opt(2000, 1, 1)
+                .expect("valid date")
+                .checked_add_signed(Duration::days(idx as i64))
+                .ok_or_else(|| format_err!("sample date out of range"))?;
+            json!(date.format("%Y-%m-%d").to_string())
+        }
+        DataType::Decimal => json!(format!("{}.25", n)),
+        DataType::DecimalWithPrecision(p) => {
+            // Keep within the allowed number of digits on each side of the
+            // decimal point.
+            let integer_digits = p.integer_digits().min(9);
+            let integer = if integer_digits == 0 {
+                0
+            } else {
+                n % 10usize.pow(integer_digits)
+            };
+            if p.scale == 0 {
+                json!(integer.to_string())
+            } else {
+                json!(format!("{}.5", integer))
+            }
+        }
+        DataType::Float32 | DataType::Float64 => json!(idx as f64 + 0.5),
+        DataType::GeoJson(_) => json!({
+            "type": "Point",
+            "coordinates": [-71.0 + idx as f64 / 100.0, 42.0],
+        }),
+        DataType::Inet => json!(format!("192.168.{}.{}/24", n / 256 % 256, n % 256)),
+        DataType::Int16 | DataType::Int32 => json!(n % 32_768),
+        // Our interchange format puts `int64` values in arrays in strings, so
+        // that JSON parsers don't lose precision.
+        DataType::Int64 => json!(n.to_string()),
+        DataType::Interval => json!(format!("P{}DT1H", n)),
+        DataType::Json => json!({ "id": n }),
+        DataType::MacAddr => {
+            json!(format!("08:00:2b:00:{:02x}:{:02x}", n / 256 % 256, n % 256))
+        }
+        DataType::OneOf(labels) if labels.is_empty() => {
+            return Err(format_err!("one_of type has no labels"));
+        }
+        DataType::OneOf(labels) => json!(labels[idx % labels.len()]),
+        DataType::Other(_) | DataType::Text => json!(format!("sample {}", n)),
+        DataType::TextWithMaxLength(max_len) => {
+            let text = format!("sample {}", n);
+            json!(text.chars().take(*max_len as usize).collect::<String>())
+        }
+        DataType::Struct(fields) => {
+            let mut obj = serde_json::Map::new();
+            for field in fields {
+                obj.insert(field.name.clone(), sample_value(&field.data_type, idx)?);
+            }
+            Value::Object(obj)
+        }
+        DataType::Time => json!(sample_time(idx).format("%H:%M:%S").to_string()),
+        DataType::TimestampWithoutTimeZone => json!(sample_timestamp(idx)?),
+        DataType::TimestampWithTimeZone => {
+            json!(format!("{}Z", sample_timestamp(idx)?))
+        }
+        DataType::Uuid => json!(format!("00000000-0000-4000-8000-{:012x}", n)),
+    })
+}
+
+/// A sample time of day for row `idx`, starting at noon.
+fn sample_time(idx: usize) -> NaiveTime {
+    let secs = (12 * 60 * 60 + idx * 61) % (24 * 60 * 60);
+    NaiveTime::from_num_seconds_from_midnight_opt(secs as u32, 0)
+        .expect("seconds should always be in range")
+}
+
+/// A sample timestamp (without a time zone) for row `idx`.
+fn sample_timestamp(idx: usize) -> Result<String> {
+    let timestamp = NaiveDate::from_ymd_opt(2000, 1, 1)
+        .expect("valid date")
+        .and_time(sample_time(idx))
+        .checked_add_signed(Duration::days(idx as i64))
+        .ok_or_else(|| format_err!("sample timestamp out of range"))?;
+    Ok(timestamp.format("%Y-%m-%dT%H:%M:%S").to_string())
+}
+
+#[test]
+fn sample_rows_match_schema() {
+    use crate::schema::DecimalPrecision;
+
+    let column = |name: &str, is_nullable, data_type| Column {
+        name: name.to_owned(),
+        is_nullable,
+        data_type,
+        comment: None,
+        default: None,
+        collation: None,
+    };
+    let columns = vec![
+        column("id", false, DataType::Int64),
+        column("ok", true, DataType::Bool),
+        column(
+            "color",
+            false,
+            DataType::OneOf(vec!["red".to_owned(), "green".to_owned()]),
+        ),
+        column(
+            "price",
+            false,
+            DataType::DecimalWithPrecision(DecimalPrecision::new(2, 2).unwrap()),
+        ),
+        column("seen_at", false, DataType::TimestampWithTimeZone),
+        column("tags", false, DataType::Array(Box::new(DataType::Int64))),
+    ];
+    let mut buf = vec![];
+    write_sample_csv(&columns, 5, &mut buf).unwrap();
+    assert_eq!(
+        String::from_utf8(buf).unwrap(),
+        "id,ok,color,price,seen_at,tags
+1,t,red,0.5,2000-01-01T12:00:00Z,\"[\"\"1\"\",\"\"2\"\"]\"
+2,f,green,0.5,2000-01-02T12:01:01Z,\"[\"\"2\"\",\"\"3\"\"]\"
+3,t,red,0.5,2000-01-03T12:02:02Z,\"[\"\"3\"\",\"\"4\"\"]\"
+4,f,green,0.5,2000-01-04T12:03:03Z,\"[\"\"4\"\",\"\"5\"\"]\"
+5,,red,0.5,2000-01-05T12:04:04Z,\"[\"\"5\"\",\"\"6\"\"]\"
+",
+    );
+}
@@ -46,6 +46,14 @@ use serde_derive::{Deserialize, Serialize};
 use serde_json::json;
 use std::fmt;
 
+use crate::common::*;
+
+mod convert;
+mod named_types;
+
+pub use self::convert::{convert, SchemaFormat};
+pub(crate) use self::named_types::NamedTypes;
+
 /// Information about a table.
 ///
 /// This is the "top level" of our JSON schema format.
@@ -56,6 +64,32 @@ pub struct Table {
 
     /// Information about the table's columns.
     pub columns: Vec<Column>,
+
+    /// The names of the columns in the table's primary key, if it has one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub primary_key: Vec<String>,
+
+    /// Secondary indexes on this table, not including the primary key.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub indexes: Vec<Index>,
+}
+
+/// Information about a secondary index on a table.
+///
+/// We only represent simple indexes on a list of columns. Indexes on
+/// expressions and partial indexes are specific to a particular database, so
+/// we don't include them in our portable schema.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Index {
+    /// The name of the index.
+    pub name: String,
+
+    /// The columns included in the index, in order.
+    pub columns: Vec<String>,
+
+    /// Does this index require each row to have a unique value?
+    #[serde(default)]
+    pub is_unique: bool,
 }
 
 /// Information about a column.
@@ -70,9 +104,61 @@ pub struct Column {
     /// The data type of this column.
     pub data_type: DataType,
 
-    /// An optional comment associated with this column.
-    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// An optional comment associated with this column. BigQuery calls this a
+    /// `description`, so we also accept that name when parsing.
+    #[serde(
+        default,
+        alias = "description",
+        skip_serializing_if = "Option::is_none"
+    )]
     pub comment: Option<String>,
+
+    /// The default value of this column, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<ColumnDefault>,
+
+    /// How values in this column are compared and sorted, if this differs
+    /// from the database's default. This is mostly useful for text columns.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collation: Option<Collation>,
+}
+
+/// A default value for a column.
+///
+/// We only represent simple defaults which most databases can handle. More
+/// complicated expressions are specific to a particular database, so they're
+/// dropped when we read a schema.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnDefault {
+    /// A literal value, written the same way it would appear in our CSV
+    /// interchange format.
+    Literal(String),
+    /// The time at which a row is inserted.
+    CurrentTimestamp,
+}
+
+/// How a column compares and sorts values.
+///
+/// Collations vary a lot between databases, so we only try to preserve
+/// whether comparisons are case-sensitive, plus the original collation name
+/// for databases which understand it.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Collation {
+    /// Compare values without regard to case, like PostgreSQL's `citext` or
+    /// BigQuery's `'und:ci'` collation.
+    CaseInsensitive,
+    /// A database-specific collation, like PostgreSQL's `"C"` or
+    /// `"en-US-x-icu"`. These are assumed to be case-sensitive.
+    Named(String),
+}
+
+impl Collation {
+    /// Does this collation ignore case when comparing values?
+    pub fn is_case_insensitive(&self) -> bool {
+        matches!(self, Collation::CaseInsensitive)
+    }
 }
 
 /// The data type of a column.
@@ -109,30 +195,63 @@ pub enum DataType {
     Array(Box<DataType>),
     /// A boolean value.
     Bool,
+    /// Binary data. In CSV data, this is written using standard base64
+    /// encoding.
+    Bytes,
+    /// An IP network, like PostgreSQL's `cidr`. In CSV data, this is written
+    /// as `10.0.0.0/8`, and bits after the prefix must be zero.
+    Cidr,
     /// A date, with no associated time value.
     Date,
     /// A decimal integer (can represent currency, etc., without rounding
     /// errors).
     Decimal,
+    /// A decimal number with a fixed precision and scale, like SQL's
+    /// `NUMERIC(10,2)`.
+    DecimalWithPrecision(DecimalPrecision),
     /// 4-byte float.
     Float32,
     /// 8-byte float.
     Float64,
     /// Geodata in GeoJSON format, using the specified SRID.
     GeoJson(Srid),
+    /// An IP address, with an optional network prefix length, like
+    /// PostgreSQL's `inet`. In CSV data, this is written as `192.168.0.1` or
+    /// `192.168.0.1/24`.
+    Inet,
     /// 2-byte int.
     Int16,
     /// 4-byte integer.
     Int32,
     /// 8-byte integer.
     Int64,
+    /// A length of time, with separate months, days and microseconds, like a
+    /// PostgreSQL `interval`. In CSV data, this is written as an ISO 8601
+    /// duration, like `P1Y2M3DT4H5M6.5S`.
+    Interval,
     /// JSON data. This includes both Postgres `json` and `jsonb` types, the
     /// differences between which don't usually matter when converting schemas.
     Json,
+    /// A MAC address, like PostgreSQL's `macaddr`. In CSV data, this is
+    /// written as `08:00:2b:01:02:03`.
+    MacAddr,
+    /// A text value which must be one of the specified labels, like a
+    /// PostgreSQL `ENUM` type. The labels are listed in their sort order.
+    OneOf(Vec<String>),
     /// A data type which isn't in this list.
     Other(String),
+    /// A record with named fields, like a PostgreSQL composite type or a
+    /// BigQuery `STRUCT`. In CSV data, this is written as a JSON object.
+    Struct(Vec<StructField>),
     /// A text type.
     Text,
+    /// A text type with a maximum length in characters, like SQL's
+    /// `VARCHAR(255)`. Drivers which can't enforce a length treat this as
+    /// `Text`.
+    TextWithMaxLength(u32),
+    /// A time of day, with no associated date or timezone. In CSV data, this
+    /// is written as `HH:MM:SS`, optionally followed by fractional seconds.
+    Time,
     /// A timestamp with no timezone. Ideally, this will would be in UTC, and
     /// some systems like BigQuery may automatically assume that.
     TimestampWithoutTimeZone,
@@ -142,6 +261,15 @@ pub enum DataType {
     Uuid,
 }
 
+impl fmt::Display for DataType {
+    /// Display a `DataType` using the same JSON we use in portable schemas, but
+    /// leave simple type names unquoted.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let json = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        write!(f, "{}", json.trim_matches('"'))
+    }
+}
+
 #[test]
 fn data_type_serialization_examples() {
     // Our serialization format is an external format, so let's write some tests
@@ -152,19 +280,45 @@ fn data_type_serialization_examples() {
             json!({"array":"text"}),
         ),
         (DataType::Bool, json!("bool")),
+        (DataType::Bytes, json!("bytes")),
+        (DataType::Cidr, json!("cidr")),
         (DataType::Date, json!("date")),
         (DataType::Decimal, json!("decimal")),
+        (
+            DataType::DecimalWithPrecision(DecimalPrecision::new(10, 2).unwrap()),
+            json!({"decimal_with_precision":{"precision":10,"scale":2}}),
+        ),
         (DataType::Float32, json!("float32")),
         (DataType::Float64, json!("float64")),
+        (DataType::Inet, json!("inet")),
         (DataType::Int16, json!("int16")),
         (DataType::Int32, json!("int32")),
         (DataType::Int64, json!("int64")),
+        (DataType::Interval, json!("interval")),
         (DataType::Json, json!("json")),
+        (DataType::MacAddr, json!("mac_addr")),
+        (
+            DataType::OneOf(vec!["red".to_owned(), "green".to_owned()]),
+            json!({"one_of":["red","green"]}),
+        ),
         (
             DataType::Other("custom".to_owned()),
             json!({"other":"custom"}),
         ),
+        (
+            DataType::Struct(vec![StructField {
+                name: "x".to_owned(),
+                is_nullable: true,
+                data_type: DataType::Float64,
+            }]),
+            json!({"struct":[{"name":"x","is_nullable":true,"data_type":"float64"}]}),
+        ),
         (DataType::Text, json!("text")),
+        (
+            DataType::TextWithMaxLength(255),
+            json!({"text_with_max_length":255}),
+        ),
+        (DataType::Time, json!("time")),
         (
             DataType::TimestampWithoutTimeZone,
             json!("timestamp_without_time_zone"),
@@ -187,16 +341,29 @@ fn data_type_roundtrip() {
     let data_types = vec![
         DataType::Array(Box::new(DataType::Text)),
         DataType::Bool,
+        DataType::Bytes,
+        DataType::Cidr,
         DataType::Date,
         DataType::Decimal,
+        DataType::DecimalWithPrecision(DecimalPrecision::new(38, 9).unwrap()),
         DataType::Float32,
         DataType::Float64,
+        DataType::Inet,
         DataType::Int16,
         DataType::Int32,
         DataType::Int64,
+        DataType::Interval,
         DataType::Json,
+        DataType::MacAddr,
+        DataType::OneOf(vec!["red".to_owned(), "green".to_owned()]),
         DataType::Other("custom".to_owned()),
+        DataType::Struct(vec![StructField {
+            name: "tags".to_owned(),
+            is_nullable: false,
+            data_type: DataType::Array(Box::new(DataType::Text)),
+        }]),
         DataType::Text,
+        DataType::Time,
         DataType::TimestampWithoutTimeZone,
         DataType::TimestampWithTimeZone,
         DataType::Uuid,
@@ -209,6 +376,57 @@ fn data_type_roundtrip() {
     }
 }
 
+/// A named field of a `DataType::Struct`.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct StructField {
+    /// The name of the field.
+    pub name: String,
+
+    /// Can this field be `NULL`?
+    pub is_nullable: bool,
+
+    /// The data type of this field.
+    pub data_type: DataType,
+}
+
+/// The precision and scale of a fixed-point decimal number. For example,
+/// `NUMERIC(10,2)` has 10 digits in total, 2 of which are after the decimal
+/// point.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct DecimalPrecision {
+    /// The total number of significant digits.
+    pub precision: u32,
+    /// The number of digits after the decimal point.
+    #[serde(default)]
+    pub scale: u32,
+}
+
+impl DecimalPrecision {
+    /// Create a new `DecimalPrecision`, making sure that `scale` is no larger
+    /// than `precision`.
+    pub fn new(precision: u32, scale: u32) -> Result<DecimalPrecision> {
+        if precision == 0 || scale > precision {
+            return Err(format_err!(
+                "invalid decimal precision and scale ({},{})",
+                precision,
+                scale,
+            ));
+        }
+        Ok(DecimalPrecision { precision, scale })
+    }
+
+    /// The maximum number of digits before the decimal point.
+    pub fn integer_digits(self) -> u32 {
+        self.precision - self.scale
+    }
+}
+
+impl fmt::Display for DecimalPrecision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({},{})", self.precision, self.scale)
+    }
+}
+
 /// An SRID number specifying how to intepret geographical coordinates.
 #[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 #[serde(transparent)]
@@ -243,3 +461,30 @@ impl fmt::Display for Srid {
         self.0.fmt(f)
     }
 }
+
+#[test]
+fn table_primary_key_serialization() {
+    use serde_json::{self, json};
+
+    // Tables without a primary key should serialize the way they always did.
+    let old_style = json!({
+        "name": "example",
+        "columns": [
+            { "name": "id", "is_nullable": false, "data_type": "int64" },
+        ],
+    });
+    let table: Table = serde_json::from_value(old_style.clone()).unwrap();
+    assert!(table.primary_key.is_empty());
+    assert_eq!(serde_json::to_value(&table).unwrap(), old_style);
+
+    let with_key = json!({
+        "name": "example",
+        "columns": [
+            { "name": "id", "is_nullable": false, "data_type": "int64" },
+        ],
+        "primary_key": ["id"],
+    });
+    let table: Table = serde_json::from_value(with_key.clone()).unwrap();
+    assert_eq!(table.primary_key, vec!["id".to_owned()]);
+    assert_eq!(serde_json::to_value(&table).unwrap(), with_key);
+}
@@ -0,0 +1,185 @@
+//! Converting between schema formats without any I/O.
+//!
+//! This is useful for programs which link against `dbcrossbarlib` and want to
+//! convert schemas they already have in memory, without needing files,
+//! credentials or a `tokio` runtime.
+
+use std::{fmt, str::FromStr};
+
+use super::Table;
+use crate::common::*;
+use crate::drivers::{
+    bigquery_schema::{self, BigQuerySchemaLocator},
+    dbcrossbar_schema::{self, DbcrossbarSchemaLocator},
+    postgres_sql::{self, PostgresSqlLocator},
+    protobuf::{self, ProtobufLocator},
+};
+
+/// A schema format which can be used with `convert`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SchemaFormat {
+    /// A BigQuery JSON schema, as used by `bigquery-schema:`.
+    BigQuerySchema,
+    /// Our portable JSON schema, as used by `dbcrossbar-schema:`. This may
+    /// contain more than one table.
+    DbcrossbarSchema,
+    /// PostgreSQL `CREATE TABLE` statements, as used by `postgres-sql:`. This
+    /// may contain more than one table.
+    PostgresSql,
+    /// A Protocol Buffers message definition, as used by `protobuf:`. This can
+    /// only be used as an output format.
+    Protobuf,
+}
+
+impl SchemaFormat {
+    /// The locator scheme corresponding to this format, including the trailing
+    /// `:`.
+    pub fn scheme(self) -> &'static str {
+        match self {
+            SchemaFormat::BigQuerySchema => BigQuerySchemaLocator::scheme(),
+            SchemaFormat::DbcrossbarSchema => DbcrossbarSchemaLocator::scheme(),
+            SchemaFormat::PostgresSql => PostgresSqlLocator::scheme(),
+            SchemaFormat::Protobuf => ProtobufLocator::scheme(),
+        }
+    }
+
+    /// Parse `input` using this format.
+    fn parse(self, input: &[u8]) -> Result<Vec<Table>> {
+        match self {
+            SchemaFormat::BigQuerySchema => {
+                Ok(vec![bigquery_schema::parse_table(input)?])
+            }
+            SchemaFormat::DbcrossbarSchema => dbcrossbar_schema::parse_tables(input),
+            SchemaFormat::PostgresSql => {
+                let sql = str::from_utf8(input)?;
+                postgres_sql::parse_tables(sql)
+            }
+            SchemaFormat::Protobuf => {
+                Err(format_err!("cannot read schemas from {}", self))
+            }
+        }
+    }
+
+    /// Write `tables` to `out` using this format.
+    fn write(self, out: &mut dyn Write, tables: Vec<Table>) -> Result<()> {
+        match self {
+            SchemaFormat::BigQuerySchema => {
+                bigquery_schema::write_table(out, self.single_table(&tables)?)
+            }
+            SchemaFormat::DbcrossbarSchema => {
+                dbcrossbar_schema::write_tables(out, tables)
+            }
+            SchemaFormat::PostgresSql => postgres_sql::write_tables(out, &tables),
+            SchemaFormat::Protobuf => {
+                protobuf::write_proto(out, self.single_table(&tables)?)
+            }
+        }
+    }
+
+    /// Return the only table in `tables`, or an error if this format can't
+    /// hold that many tables.
+    fn single_table(self, tables: &[Table]) -> Result<&Table> {
+        match tables {
+            [table] => Ok(table),
+            _ => Err(format_err!(
+                "cannot write {} table schemas to {}",
+                tables.len(),
+                self,
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SchemaFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.scheme().fmt(f)
+    }
+}
+
+impl FromStr for SchemaFormat {
+    type Err = Error;
+
+    /// Parse a schema format, with or without a trailing `:`.
+    fn from_str(s: &str) -> Result<Self> {
+        let formats = [
+            SchemaFormat::BigQuerySchema,
+            SchemaFormat::DbcrossbarSchema,
+            SchemaFormat::PostgresSql,
+            SchemaFormat::Protobuf,
+        ];
+        let name = s.strip_suffix(':').unwrap_or(s);
+        formats
+            .iter()
+            .find(|format| format.scheme().strip_suffix(':') == Some(name))
+            .copied()
+            .ok_or_else(|| format_err!("unknown schema format {:?}", s))
+    }
+}
+
+/// Convert `input` from `from_format` to `to_format`. This works entirely in
+/// memory, without reading files or contacting any databases.
+///
+/// ```
+/// use dbcrossbarlib::schema::{convert, SchemaFormat};
+///
+/// let sql = "CREATE TABLE users (id bigint NOT NULL, name text);";
+/// let json = convert(
+///     SchemaFormat::PostgresSql,
+///     SchemaFormat::BigQuerySchema,
+///     sql.as_bytes(),
+/// )
+/// .expect("could not convert schema");
+/// assert!(String::from_utf8(json).unwrap().contains("REQUIRED"));
+/// ```
+pub fn convert(
+    from_format: SchemaFormat,
+    to_format: SchemaFormat,
+    input: &[u8],
+) -> Result<Vec<u8>> {
+    let tables = from_format
+        .parse(input)
+        .with_context(|_| format!("error parsing {}", from_format))?;
+    let mut out = vec![];
+    to_format
+        .write(&mut out, tables)
+        .with_context(|_| format!("error writing {}", to_format))?;
+    Ok(out)
+}
+
+#[test]
+fn convert_between_formats() {
+    let sql = "CREATE TABLE a (id bigint PRIMARY KEY); CREATE TABLE b (x text);";
+    let json = convert(
+        SchemaFormat::PostgresSql,
+        SchemaFormat::DbcrossbarSchema,
+        sql.as_bytes(),
+    )
+    .unwrap();
+    let sql_again = convert(
+        SchemaFormat::DbcrossbarSchema,
+        SchemaFormat::PostgresSql,
+        &json,
+    )
+    .unwrap();
+    let sql_again = String::from_utf8(sql_again).unwrap();
+    assert_eq!(sql_again.matches("CREATE TABLE").count(), 2);
+
+    // Single-table formats reject multiple tables.
+    assert!(convert(
+        SchemaFormat::PostgresSql,
+        SchemaFormat::BigQuerySchema,
+        sql.as_bytes()
+    )
+    .is_err());
+    assert!(convert(SchemaFormat::Protobuf, SchemaFormat::PostgresSql, b"").is_err());
+
+    assert_eq!(
+        "postgres-sql".parse::<SchemaFormat>().unwrap(),
+        SchemaFormat::PostgresSql,
+    );
+    assert_eq!(
+        "bigquery-schema:".parse::<SchemaFormat>().unwrap(),
+        SchemaFormat::BigQuerySchema,
+    );
+    assert!("postgres".parse::<SchemaFormat>().is_err());
+}
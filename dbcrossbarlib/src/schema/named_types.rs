@@ -0,0 +1,157 @@
+//! Named data types, which can be defined once in a schema file and used by
+//! many columns.
+//!
+//! A schema file may contain a `named_types` object mapping names to data
+//! types. Anywhere a data type is expected, `{ "named": "address" }` refers to
+//! one of these definitions. Named types may refer to other named types. We
+//! replace all references with the corresponding definitions when parsing, so
+//! the rest of `dbcrossbar` only ever sees ordinary `DataType` values.
+
+use serde_json::{Map, Value};
+
+use crate::common::*;
+
+/// The key used to refer to a named type.
+const NAMED: &str = "named";
+
+/// Named data type definitions, in their serialized form.
+#[derive(Debug, Default)]
+pub(crate) struct NamedTypes {
+    /// Our definitions, indexed by name.
+    definitions: Map<String, Value>,
+}
+
+impl NamedTypes {
+    /// Remove the `named_types` key from a serialized schema file, if present,
+    /// and return the definitions it contains.
+    pub(crate) fn take_from_schema(schema: &mut Value) -> Result<NamedTypes> {
+        let definitions = match schema
+            .as_object_mut()
+            .and_then(|obj| obj.remove("named_types"))
+        {
+            None => Map::new(),
+            Some(Value::Object(definitions)) => definitions,
+            Some(_) => {
+                return Err(format_err!(
+                    "expected named_types to be an object mapping names to types"
+                ))
+            }
+        };
+        Ok(NamedTypes { definitions })
+    }
+
+    /// Replace all named type references in the columns of `table`, a
+    /// serialized `Table`.
+    pub(crate) fn resolve_table(&self, table: &mut Value) -> Result<()> {
+        if let Some(Value::Array(columns)) = table.get_mut("columns") {
+            for column in columns {
+                let name = column
+                    .get("name")
+                    .and_then(|name| name.as_str())
+                    .unwrap_or("")
+                    .to_owned();
+                if let Some(data_type) = column.get_mut("data_type") {
+                    self.resolve_data_type(data_type, &mut vec![])
+                        .with_context(|_| format!("error in column {}", name))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace all named type references in `data_type`, a serialized
+    /// `DataType`. `resolving` contains the names of the types we're currently
+    /// expanding, so that we can detect cycles.
+    fn resolve_data_type(
+        &self,
+        data_type: &mut Value,
+        resolving: &mut Vec<String>,
+    ) -> Result<()> {
+        let obj = match data_type {
+            Value::Object(obj) if obj.len() == 1 => obj,
+            _ => return Ok(()),
+        };
+        if let Some(name) = obj.get(NAMED) {
+            let name = name
+                .as_str()
+                .ok_or_else(|| format_err!("expected a type name, found {}", name))?
+                .to_owned();
+            if resolving.contains(&name) {
+                return Err(format_err!("named type {} refers to itself", name));
+            }
+            let mut definition = self
+                .definitions
+                .get(&name)
+                .ok_or_else(|| format_err!("unknown named type {}", name))?
+                .clone();
+            resolving.push(name);
+            self.resolve_data_type(&mut definition, resolving)?;
+            resolving.pop();
+            *data_type = definition;
+        } else if let Some(element_type) = obj.get_mut("array") {
+            self.resolve_data_type(element_type, resolving)?;
+        } else if let Some(Value::Array(fields)) = obj.get_mut("struct") {
+            for field in fields {
+                if let Some(field_type) = field.get_mut("data_type") {
+                    self.resolve_data_type(field_type, resolving)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn resolves_named_types() {
+    use super::{DataType, StructField, Table};
+    use serde_json::json;
+
+    let mut schema = json!({
+        "named_types": {
+            "address": { "struct": [
+                { "name": "street", "is_nullable": true, "data_type": "text" },
+                { "name": "geo", "is_nullable": true, "data_type": { "named": "point" } },
+            ] },
+            "point": { "geo_json": 4326 },
+        },
+        "name": "people",
+        "columns": [
+            { "name": "home", "is_nullable": true, "data_type": { "named": "address" } },
+            { "name": "others", "is_nullable": true, "data_type": { "array": { "named": "address" } } },
+        ],
+    });
+    let named_types = NamedTypes::take_from_schema(&mut schema).unwrap();
+    named_types.resolve_table(&mut schema).unwrap();
+    let table: Table = serde_json::from_value(schema).unwrap();
+    let address = DataType::Struct(vec![
+        StructField {
+            name: "street".to_owned(),
+            is_nullable: true,
+            data_type: DataType::Text,
+        },
+        StructField {
+            name: "geo".to_owned(),
+            is_nullable: true,
+            data_type: DataType::GeoJson(Default::default()),
+        },
+    ]);
+    assert_eq!(table.columns[0].data_type, address);
+    assert_eq!(
+        table.columns[1].data_type,
+        DataType::Array(Box::new(address)),
+    );
+
+    let mut unknown = json!({
+        "name": "t",
+        "columns": [{ "name": "a", "is_nullable": true, "data_type": { "named": "nope" } }],
+    });
+    assert!(NamedTypes::default().resolve_table(&mut unknown).is_err());
+
+    let mut cyclic = json!({
+        "named_types": { "a": { "array": { "named": "a" } } },
+        "name": "t",
+        "columns": [{ "name": "a", "is_nullable": true, "data_type": { "named": "a" } }],
+    });
+    let named_types = NamedTypes::take_from_schema(&mut cyclic).unwrap();
+    assert!(named_types.resolve_table(&mut cyclic).is_err());
+}
@@ -0,0 +1,202 @@
+//! Comparing two portable schemas.
+//!
+//! This is used by `dbcrossbar schema diff` to check that two tables (often in
+//! different databases) still have compatible columns, for example in CI.
+
+use serde::Serialize;
+use std::fmt;
+
+use crate::schema::{Column, DataType, Table};
+
+/// A change to a single column.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "change")]
+pub enum ColumnChange {
+    /// The column only exists in the new schema.
+    Added {
+        /// The name of the column.
+        column: String,
+        /// The type of the column.
+        data_type: DataType,
+        /// Can the column be `NULL`?
+        is_nullable: bool,
+    },
+    /// The column only exists in the old schema.
+    Removed {
+        /// The name of the column.
+        column: String,
+    },
+    /// The column has a different type in the new schema.
+    Retyped {
+        /// The name of the column.
+        column: String,
+        /// The old type.
+        from: DataType,
+        /// The new type.
+        to: DataType,
+    },
+    /// The column allows `NULL` in one schema but not the other.
+    NullabilityChanged {
+        /// The name of the column.
+        column: String,
+        /// Could the column be `NULL` in the old schema?
+        from: bool,
+        /// Can the column be `NULL` in the new schema?
+        to: bool,
+    },
+}
+
+impl fmt::Display for ColumnChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColumnChange::Added {
+                column,
+                data_type,
+                is_nullable,
+            } => write!(
+                f,
+                "+ {}: {}{}",
+                column,
+                data_type,
+                if *is_nullable { "" } else { " NOT NULL" },
+            ),
+            ColumnChange::Removed { column } => write!(f, "- {}", column),
+            ColumnChange::Retyped { column, from, to } => {
+                write!(f, "~ {}: {} -> {}", column, from, to,)
+            }
+            ColumnChange::NullabilityChanged { column, from, to } => write!(
+                f,
+                "~ {}: {} -> {}",
+                column,
+                nullability(*from),
+                nullability(*to),
+            ),
+        }
+    }
+}
+
+/// Describe whether a column may be `NULL`.
+fn nullability(is_nullable: bool) -> &'static str {
+    if is_nullable {
+        "NULL"
+    } else {
+        "NOT NULL"
+    }
+}
+
+/// The differences between two portable schemas, compared column by column.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct SchemaDiff {
+    /// Our column changes, in the order the columns appear in the old schema,
+    /// followed by any added columns.
+    pub changes: Vec<ColumnChange>,
+}
+
+impl SchemaDiff {
+    /// Compare `old` to `new`. Columns are matched by name, and table names and
+    /// column order are ignored.
+    pub fn new(old: &Table, new: &Table) -> SchemaDiff {
+        let find = |table: &'_ Table, name: &str| -> Option<Column> {
+            table.columns.iter().find(|c| c.name == name).cloned()
+        };
+
+        let mut changes = vec![];
+        for old_col in &old.columns {
+            match find(new, &old_col.name) {
+                None => changes.push(ColumnChange::Removed {
+                    column: old_col.name.clone(),
+                }),
+                Some(new_col) => {
+                    if old_col.data_type != new_col.data_type {
+                        changes.push(ColumnChange::Retyped {
+                            column: old_col.name.clone(),
+                            from: old_col.data_type.clone(),
+                            to: new_col.data_type.clone(),
+                        });
+                    }
+                    if old_col.is_nullable != new_col.is_nullable {
+                        changes.push(ColumnChange::NullabilityChanged {
+                            column: old_col.name.clone(),
+                            from: old_col.is_nullable,
+                            to: new_col.is_nullable,
+                        });
+                    }
+                }
+            }
+        }
+        for new_col in &new.columns {
+            if find(old, &new_col.name).is_none() {
+                changes.push(ColumnChange::Added {
+                    column: new_col.name.clone(),
+                    data_type: new_col.data_type.clone(),
+                    is_nullable: new_col.is_nullable,
+                });
+            }
+        }
+        SchemaDiff { changes }
+    }
+
+    /// Are the two schemas the same?
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for SchemaDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn schemas_are_compared_by_column_name() {
+    let column = |name: &str, is_nullable: bool, data_type: DataType| Column {
+        name: name.to_owned(),
+        is_nullable,
+        data_type,
+        comment: None,
+        default: None,
+        collation: None,
+    };
+    let old = Table {
+        name: "old".to_owned(),
+        columns: vec![
+            column("id", false, DataType::Int32),
+            column("name", true, DataType::Text),
+            column("email", true, DataType::Text),
+            column("legacy", true, DataType::Bool),
+        ],
+        primary_key: vec![],
+        indexes: vec![],
+    };
+    let new = Table {
+        name: "new".to_owned(),
+        columns: vec![
+            column("name", true, DataType::Text),
+            column("id", false, DataType::Int64),
+            column("email", false, DataType::TextWithMaxLength(100)),
+            column("created_at", true, DataType::TimestampWithTimeZone),
+        ],
+        primary_key: vec![],
+        indexes: vec![],
+    };
+
+    assert!(SchemaDiff::new(&old, &old).is_empty());
+    let diff = SchemaDiff::new(&old, &new);
+    assert_eq!(
+        diff.to_string(),
+        "~ id: int32 -> int64
+~ email: text -> {\"text_with_max_length\":100}
+~ email: NULL -> NOT NULL
+- legacy
++ created_at: timestamp_with_time_zone
+",
+    );
+    assert_eq!(
+        serde_json::to_value(&diff.changes[3]).unwrap(),
+        serde_json::json!({"change": "removed", "column": "legacy"}),
+    );
+}
@@ -0,0 +1,213 @@
+//! A local scratch directory for temporary files.
+//!
+//! By default, we (and the tools we run, like `gsutil` and `bq`) put
+//! temporary files in the system temporary directory, which is often on a
+//! small root volume. A `ScratchDir` creates a private directory somewhere
+//! else, points `TMPDIR` at it, keeps track of how much space it uses, and
+//! removes it when we're done.
+
+use fs2::FileExt;
+use std::{
+    env,
+    fs::{self, File},
+    path::Path,
+    sync::{mpsc, Arc},
+    thread,
+    time::Duration,
+};
+use tempdir::TempDir;
+use walkdir::WalkDir;
+
+use crate::common::*;
+use crate::usage::UsageTracker;
+
+/// The prefix we use for our scratch directories.
+const SCRATCH_PREFIX: &str = "dbcrossbar-";
+
+/// The file inside each scratch directory which its owner keeps locked for as
+/// long as it's using the directory.
+const LOCK_FILE_NAME: &str = ".lock";
+
+/// How many times should we try to create a scratch directory, if another
+/// process removes it before we can lock it?
+const CREATE_ATTEMPTS: usize = 3;
+
+/// How often should we measure our scratch directory?
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A private scratch directory, which is deleted when dropped.
+#[derive(Debug)]
+pub struct ScratchDir {
+    /// Our directory.
+    dir: TempDir,
+    /// Our lock file, which we hold an exclusive lock on so that other
+    /// processes know we're still using this directory.
+    _lock: File,
+    /// Send a message (or drop) to stop our sampler thread.
+    stop: Option<mpsc::Sender<()>>,
+    /// Our sampler thread, which records how much space we use.
+    sampler: Option<thread::JoinHandle<()>>,
+}
+
+impl ScratchDir {
+    /// Create a new scratch directory inside `parent`, and use it for all
+    /// temporary files created by this process and its children.
+    ///
+    /// This must be called before we start any other threads (including our
+    /// logger), because it modifies our environment variables.
+    pub fn new(parent: &Path) -> Result<ScratchDir> {
+        let scratch = Self::create(parent)?;
+
+        // Point the standard temporary directory variables at our directory.
+        // These are used by `std::env::temp_dir` and most child processes.
+        for var in &["TMPDIR", "TMP", "TEMP"] {
+            env::set_var(var, scratch.path());
+        }
+        Ok(scratch)
+    }
+
+    /// Create a new scratch directory inside `parent`, without changing our
+    /// environment.
+    fn create(parent: &Path) -> Result<ScratchDir> {
+        fs::create_dir_all(parent).with_context(|_| {
+            format!("cannot create scratch directory {}", parent.display())
+        })?;
+        remove_stale_scratch_dirs(parent);
+        let prefix = format!("{}{}", SCRATCH_PREFIX, std::process::id());
+        for _ in 0..CREATE_ATTEMPTS {
+            let dir = TempDir::new_in(parent, &prefix).with_context(|_| {
+                format!("cannot create scratch directory in {}", parent.display())
+            })?;
+            let lock_path = dir.path().join(LOCK_FILE_NAME);
+            let lock = File::create(&lock_path)
+                .with_context(|_| format!("cannot create {}", lock_path.display()))?;
+            lock.lock_exclusive()
+                .with_context(|_| format!("cannot lock {}", lock_path.display()))?;
+
+            // Another process may have removed our directory as stale after we
+            // created our lock file, but before we locked it. If so, try again.
+            if lock_path.exists() {
+                return Ok(ScratchDir {
+                    dir,
+                    _lock: lock,
+                    stop: None,
+                    sampler: None,
+                });
+            }
+        }
+        Err(format_err!(
+            "cannot create scratch directory in {}",
+            parent.display()
+        ))
+    }
+
+    /// Record the peak size of our directory in `usage`. This starts a
+    /// background thread, so it should be called after `ScratchDir::new`.
+    pub fn record_usage(&mut self, usage: Arc<UsageTracker>) -> Result<()> {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let path = self.path().to_owned();
+        let sampler = thread::Builder::new()
+            .name("scratch-dir-sampler".to_owned())
+            .spawn(move || loop {
+                usage.record_scratch_bytes(disk_usage(&path));
+                match stopped.recv_timeout(SAMPLE_INTERVAL) {
+                    Err(mpsc::RecvTimeoutError::Timeout) => {}
+                    _ => return,
+                }
+            })
+            .context("cannot start scratch directory sampler")?;
+        self.stop = Some(stop);
+        self.sampler = Some(sampler);
+        Ok(())
+    }
+
+    /// The path to our scratch directory.
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        // Stop our sampler before `self.dir` deletes our directory.
+        drop(self.stop.take());
+        if let Some(sampler) = self.sampler.take() {
+            let _ = sampler.join();
+        }
+    }
+}
+
+/// The total size of the files in `path`.
+fn disk_usage(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Remove any scratch directories in `parent` left behind by `dbcrossbar`
+/// processes which are no longer running, for example because they were
+/// killed.
+///
+/// We only remove directories whose lock file we can lock. This works even if
+/// `parent` is shared with processes on other hosts or in other containers,
+/// whose process IDs we can't check. Directories without a lock file may
+/// still be being created, so we leave them alone.
+fn remove_stale_scratch_dirs(parent: &Path) {
+    let entries = match fs::read_dir(parent) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        if !entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with(SCRATCH_PREFIX)
+        {
+            continue;
+        }
+        let lock = match File::open(entry.path().join(LOCK_FILE_NAME)) {
+            Ok(lock) => lock,
+            Err(_) => continue,
+        };
+        if lock.try_lock_exclusive().is_ok() {
+            let _ = fs::remove_dir_all(entry.path());
+        }
+    }
+}
+
+#[test]
+fn scratch_dir_is_measured_and_removed() {
+    let parent = TempDir::new("scratch_parent").unwrap();
+
+    // Leave behind a directory from a process that has exited.
+    let stale = parent.path().join("dbcrossbar-123.stale");
+    fs::create_dir(&stale).unwrap();
+    File::create(stale.join(LOCK_FILE_NAME)).unwrap();
+
+    // And a directory which doesn't have a lock file yet.
+    let new = parent.path().join("dbcrossbar-456.new");
+    fs::create_dir(&new).unwrap();
+
+    let mut scratch = ScratchDir::create(parent.path()).unwrap();
+    let path = scratch.path().to_owned();
+    assert!(path.starts_with(parent.path()));
+    assert!(!stale.exists());
+    assert!(new.exists());
+
+    // A second scratch directory must not remove ours, which is still locked.
+    let other = ScratchDir::create(parent.path()).unwrap();
+    assert!(path.exists());
+    drop(other);
+
+    let usage = Arc::new(UsageTracker::default());
+    scratch.record_usage(usage.clone()).unwrap();
+    fs::write(path.join("data.csv"), vec![0u8; 1000]).unwrap();
+    assert_eq!(disk_usage(&path), 1000);
+    drop(scratch);
+    assert!(!path.exists());
+    assert!(usage.report().peak_scratch_bytes.is_some());
+}
@@ -0,0 +1,258 @@
+//! Copying only some of the columns in a table.
+//!
+//! Wide tables are slow to copy, and we often only need a few of their
+//! columns. We narrow the schema we pass to the source and destination, which
+//! allows database drivers to select only those columns in their SQL. Drivers
+//! which read CSV files drop the extra columns from each stream.
+
+use csv;
+use std::{collections::HashSet, fmt, io, str::FromStr};
+
+use crate::common::*;
+use crate::schema::{Column, Table};
+use crate::transform::spawn_sync_transform;
+
+/// A list of columns to copy, in the order they should appear.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ColumnSelection {
+    columns: Vec<String>,
+}
+
+impl ColumnSelection {
+    /// Return a copy of `table` containing only our columns. Any primary key
+    /// or index which uses a column we drop is also dropped. Fails if we try
+    /// to select a column that doesn't exist.
+    pub fn select_table(&self, table: &Table) -> Result<Table> {
+        let columns = self
+            .columns
+            .iter()
+            .map(|name| {
+                table
+                    .columns
+                    .iter()
+                    .find(|c| &c.name == name)
+                    .cloned()
+                    .ok_or_else(|| {
+                        format_err!(
+                            "cannot select column {:?} because it isn't in the schema",
+                            name,
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let selected = |name: &String| self.columns.contains(name);
+        let primary_key = if table.primary_key.iter().all(selected) {
+            table.primary_key.clone()
+        } else {
+            vec![]
+        };
+        let indexes = table
+            .indexes
+            .iter()
+            .filter(|index| index.columns.iter().all(selected))
+            .cloned()
+            .collect();
+        Ok(Table {
+            name: table.name.clone(),
+            columns,
+            primary_key,
+            indexes,
+        })
+    }
+}
+
+impl fmt::Display for ColumnSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.columns.join(","))
+    }
+}
+
+impl FromStr for ColumnSelection {
+    type Err = Error;
+
+    /// Parse a list of the form `col1,col2,col3`.
+    fn from_str(s: &str) -> Result<Self> {
+        let columns = s
+            .split(',')
+            .filter(|name| !name.trim().is_empty())
+            .map(|name| name.to_owned())
+            .collect::<Vec<_>>();
+        if columns.is_empty() {
+            return Err(format_err!("must select at least one column"));
+        }
+        let mut seen = HashSet::new();
+        for name in &columns {
+            if !seen.insert(name) {
+                return Err(format_err!("column {:?} is selected twice", name));
+            }
+        }
+        Ok(ColumnSelection { columns })
+    }
+}
+
+/// Make sure that each stream in `streams` contains only `columns`, in schema
+/// order. This is used by drivers which read CSV files, which may contain more
+/// columns than the schema we were asked to copy.
+///
+/// If a stream has the same number of columns as the schema, we leave it
+/// alone, because we normally match CSV columns to the schema by position.
+pub(crate) fn select_csv_columns(
+    ctx: Context,
+    columns: Vec<Column>,
+    streams: BoxStream<CsvStream>,
+) -> BoxStream<CsvStream> {
+    streams
+        .and_then(move |stream| {
+            select_csv_stream_columns(ctx.clone(), columns.clone(), stream)
+        })
+        .boxed()
+}
+
+/// Make sure that `stream` contains only `columns`.
+async fn select_csv_stream_columns(
+    ctx: Context,
+    columns: Vec<Column>,
+    stream: CsvStream,
+) -> Result<CsvStream> {
+    // Read enough of the stream to see the header, and then put it back. We
+    // avoid running a transform unless we have to, because transforms buffer
+    // their output, which would break `csv:` sources that use `follow=true`.
+    let name = stream.name;
+    let mut data = stream.data;
+    let mut header = BytesMut::new();
+    while !header.contains(&b'\n') {
+        match data.next().await {
+            Some(bytes) => header.extend_from_slice(&bytes?),
+            None => break,
+        }
+    }
+    if header.is_empty() {
+        // Empty streams have no columns to drop, and downstream readers
+        // don't allow empty chunks.
+        return Ok(CsvStream { name, data });
+    }
+    let header_len = csv::Reader::from_reader(&header[..])
+        .headers()
+        .map(|headers| headers.len())
+        .ok();
+    let data = stream::once(async { Ok(header) }).chain(data).boxed();
+    if header_len == Some(columns.len()) {
+        return Ok(CsvStream { name, data });
+    }
+
+    let stream_name = name.clone();
+    let data = spawn_sync_transform(
+        ctx,
+        "select_csv_columns".to_owned(),
+        data,
+        move |_ctx, rdr, wtr| {
+            select_columns(&columns, rdr, wtr).with_context(|_| {
+                format!("cannot select columns from stream {}", stream_name)
+            })?;
+            Ok(())
+        },
+    )?;
+    Ok(CsvStream { name, data })
+}
+
+/// Copy CSV data from `rdr` to `wtr`, keeping only `columns`.
+///
+/// This is synchronous, and should be run using `spawn_sync_transform`.
+fn select_columns<R: Read, W: Write>(
+    columns: &[Column],
+    rdr: R,
+    wtr: W,
+) -> Result<()> {
+    let mut rdr = csv::Reader::from_reader(rdr);
+    let mut wtr =
+        csv::Writer::from_writer(io::BufWriter::with_capacity(BUFFER_SIZE, wtr));
+
+    let headers = rdr.headers()?.clone();
+    if headers.len() == columns.len() {
+        // Nothing to drop, so just copy our data.
+        wtr.write_record(&headers)?;
+        for row in rdr.byte_records() {
+            wtr.write_byte_record(&row?)?;
+        }
+    } else {
+        let order = columns
+            .iter()
+            .map(|c| {
+                headers.iter().position(|h| h == c.name).ok_or_else(|| {
+                    format_err!("cannot find column {:?} in CSV header", c.name)
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        wtr.write_record(columns.iter().map(|c| &c.name))?;
+        let mut out = csv::ByteRecord::new();
+        for row in rdr.byte_records() {
+            let row = row?;
+            out.clear();
+            for &idx in &order {
+                out.push_field(&row[idx]);
+            }
+            wtr.write_byte_record(&out)?;
+        }
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+#[test]
+fn selections_are_parsed_and_applied() {
+    use crate::schema::{DataType, Index};
+
+    assert!("".parse::<ColumnSelection>().is_err());
+    assert!("a,a".parse::<ColumnSelection>().is_err());
+
+    let column = |name: &str| Column {
+        name: name.to_owned(),
+        is_nullable: true,
+        data_type: DataType::Text,
+        comment: None,
+        default: None,
+        collation: None,
+    };
+    let table = Table {
+        name: "t".to_owned(),
+        columns: vec![column("id"), column("name"), column("email")],
+        primary_key: vec!["id".to_owned()],
+        indexes: vec![Index {
+            name: "by_email".to_owned(),
+            columns: vec!["email".to_owned()],
+            is_unique: false,
+        }],
+    };
+    let selected = "name,id"
+        .parse::<ColumnSelection>()
+        .unwrap()
+        .select_table(&table)
+        .unwrap();
+    let names = selected
+        .columns
+        .iter()
+        .map(|c| &c.name[..])
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["name", "id"]);
+    assert_eq!(selected.primary_key, vec!["id".to_owned()]);
+    assert!(selected.indexes.is_empty());
+    let err = "missing"
+        .parse::<ColumnSelection>()
+        .unwrap()
+        .select_table(&table)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("isn't in the schema"), "{}", err);
+
+    let run = |input: &str| -> Result<String> {
+        let mut out = vec![];
+        select_columns(&selected.columns, input.as_bytes(), &mut out)?;
+        Ok(String::from_utf8(out)?)
+    };
+    assert_eq!(
+        run("id,name,email\n1,Ann,a@x\n").unwrap(),
+        "name,id\nAnn,1\n"
+    );
+    assert_eq!(run("a,b\n1,2\n").unwrap(), "a,b\n1,2\n");
+    assert!(run("id,email,x\n1,a@x,2\n").is_err());
+}
@@ -0,0 +1,290 @@
+//! Remembering how far `dbcrossbar sync` has copied each table.
+//!
+//! Each sync has a name, and we store the largest value of its incremental key
+//! that we've copied so far, known as its "high-water mark". The next sync
+//! only copies rows with a larger key.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, fs, path::PathBuf, str::FromStr};
+
+use crate::clouds::{gcloud::storage, object_tags::ObjectTags};
+use crate::common::*;
+use crate::drivers::postgres::connect;
+use crate::drivers::postgres_shared::TableName;
+
+/// The stored state of a single sync.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SyncState {
+    /// The largest value of the incremental key that we've copied.
+    pub high_water_mark: String,
+    /// When we last finished a sync.
+    pub synced_at: DateTime<Utc>,
+}
+
+/// Every sync stored in a JSON file, indexed by name.
+type SyncStates = BTreeMap<String, SyncState>;
+
+/// Where we store the state of our syncs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SyncStateStore {
+    /// A local JSON file, written as `file:path/to/state.json`.
+    File(PathBuf),
+    /// A JSON file in Google Cloud Storage, in the same format as a local
+    /// file.
+    Gs(Url),
+    /// A PostgreSQL table, written as `postgres://host/db#table`. We'll create
+    /// it if it doesn't exist.
+    Postgres {
+        /// The database containing our table.
+        url: Url,
+        /// The table to use.
+        table_name: String,
+    },
+}
+
+impl SyncStateStore {
+    /// Look up the state of the sync called `name`, if it has ever run.
+    pub async fn load(&self, ctx: &Context, name: &str) -> Result<Option<SyncState>> {
+        match self {
+            SyncStateStore::File(path) => {
+                if !path.exists() {
+                    return Ok(None);
+                }
+                let data = fs::read(path)
+                    .with_context(|_| format!("could not read {}", path.display()))?;
+                Ok(self.parse_states(&data)?.remove(name))
+            }
+            SyncStateStore::Gs(url) => match storage::read_small_file(ctx, url).await?
+            {
+                Some(data) => Ok(self.parse_states(&data)?.remove(name)),
+                None => Ok(None),
+            },
+            SyncStateStore::Postgres { url, table_name } => {
+                let conn = connect(ctx.clone(), url.clone()).await?;
+                self.create_table_if_not_exists(&conn, table_name).await?;
+                // Convert `synced_at` to RFC 3339 text, so that we can parse it.
+                let sql = format!(
+                    "SELECT high_water_mark, to_json(synced_at) #>> '{{}}' AS synced_at
+FROM {} WHERE name = $1",
+                    TableName(table_name),
+                );
+                let rows =
+                    conn.query(sql.as_str(), &[&name]).await.with_context(|_| {
+                        format!("could not read sync state from {}", self)
+                    })?;
+                rows.first()
+                    .map(|row| -> Result<SyncState> {
+                        let synced_at: String = row.get("synced_at");
+                        Ok(SyncState {
+                            high_water_mark: row.get("high_water_mark"),
+                            synced_at: synced_at
+                                .parse::<DateTime<Utc>>()
+                                .with_context(|_| {
+                                    format!(
+                                        "could not parse sync time {:?}",
+                                        synced_at
+                                    )
+                                })?,
+                        })
+                    })
+                    .transpose()
+            }
+        }
+    }
+
+    /// Record `state` as the current state of the sync called `name`.
+    pub async fn save(
+        &self,
+        ctx: &Context,
+        name: &str,
+        state: &SyncState,
+    ) -> Result<()> {
+        match self {
+            SyncStateStore::File(path) => {
+                let mut states = if path.exists() {
+                    let data = fs::read(path).with_context(|_| {
+                        format!("could not read {}", path.display())
+                    })?;
+                    self.parse_states(&data)?
+                } else {
+                    SyncStates::new()
+                };
+                states.insert(name.to_owned(), state.to_owned());
+
+                // Write to a temporary file first, so that we never leave a
+                // half-written state file behind.
+                let tmp_path = path.with_extension("json.tmp");
+                fs::write(&tmp_path, serde_json::to_vec_pretty(&states)?)
+                    .with_context(|_| {
+                        format!("could not write {}", tmp_path.display())
+                    })?;
+                fs::rename(&tmp_path, path)
+                    .with_context(|_| format!("could not write {}", path.display()))?;
+                Ok(())
+            }
+            SyncStateStore::Gs(url) => {
+                let mut states = match storage::read_small_file(ctx, url).await? {
+                    Some(data) => self.parse_states(&data)?,
+                    None => SyncStates::new(),
+                };
+                states.insert(name.to_owned(), state.to_owned());
+                let data = serde_json::to_vec_pretty(&states)?;
+                storage::upload_file(
+                    ctx.clone(),
+                    box_stream_once(Ok(BytesMut::from(&data[..]))),
+                    url,
+                    &ObjectTags::default(),
+                )
+                .await
+                .with_context(|_| format!("could not write sync state to {}", self))?;
+                Ok(())
+            }
+            SyncStateStore::Postgres { url, table_name } => {
+                let conn = connect(ctx.clone(), url.clone()).await?;
+                self.create_table_if_not_exists(&conn, table_name).await?;
+                let sql = format!(
+                    "INSERT INTO {} (name, high_water_mark, synced_at)
+VALUES ($1, $2, $3::text::timestamptz)
+ON CONFLICT (name) DO UPDATE
+SET high_water_mark = EXCLUDED.high_water_mark, synced_at = EXCLUDED.synced_at",
+                    TableName(table_name),
+                );
+                conn.execute(
+                    sql.as_str(),
+                    &[&name, &state.high_water_mark, &state.synced_at.to_rfc3339()],
+                )
+                .await
+                .with_context(|_| format!("could not write sync state to {}", self))?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Parse the contents of a JSON state file.
+    fn parse_states(&self, data: &[u8]) -> Result<SyncStates> {
+        Ok(serde_json::from_slice(data)
+            .with_context(|_| format!("could not parse sync state in {}", self))?)
+    }
+
+    /// Create our PostgreSQL state table, if needed.
+    async fn create_table_if_not_exists(
+        &self,
+        conn: &tokio_postgres::Client,
+        table_name: &str,
+    ) -> Result<()> {
+        let sql = format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+    name text PRIMARY KEY,
+    high_water_mark text NOT NULL,
+    synced_at timestamptz NOT NULL
+)",
+            TableName(table_name),
+        );
+        conn.batch_execute(&sql)
+            .await
+            .with_context(|_| format!("could not create sync state table {}", self))?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for SyncStateStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncStateStore::File(path) => write!(f, "file:{}", path.display()),
+            SyncStateStore::Gs(url) => write!(f, "{}", url),
+            SyncStateStore::Postgres { url, .. } => {
+                // Don't display any password in the URL.
+                let mut url = url.clone();
+                if url.password().is_some() {
+                    url.set_password(Some("XXXXXX"))
+                        .expect("should be able to hide password");
+                }
+                write!(f, "{}", url)
+            }
+        }
+    }
+}
+
+impl FromStr for SyncStateStore {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(path) = s.strip_prefix("file:") {
+            Ok(SyncStateStore::File(PathBuf::from(path)))
+        } else if s.starts_with("gs://") {
+            let url = s
+                .parse::<Url>()
+                .with_context(|_| format!("cannot parse {}", s))?;
+            Ok(SyncStateStore::Gs(url))
+        } else if s.starts_with("postgres:") {
+            let url = s
+                .parse::<Url>()
+                .with_context(|_| format!("cannot parse {}", s))?;
+            let table_name = url
+                .fragment()
+                .filter(|f| !f.is_empty())
+                .ok_or_else(|| {
+                    format_err!(
+                        "{} needs a table name, as in postgres://host/db#table",
+                        s
+                    )
+                })?
+                .to_owned();
+            Ok(SyncStateStore::Postgres { url, table_name })
+        } else {
+            Err(format_err!(
+                "sync state must be file:PATH, gs://BUCKET/PATH or postgres://HOST/DB#TABLE, not {:?}",
+                s,
+            ))
+        }
+    }
+}
+
+#[test]
+fn parse_sync_state_stores() {
+    assert_eq!(
+        "file:state.json".parse::<SyncStateStore>().unwrap(),
+        SyncStateStore::File(PathBuf::from("state.json")),
+    );
+    assert_eq!(
+        "gs://example/sync/state.json"
+            .parse::<SyncStateStore>()
+            .unwrap(),
+        SyncStateStore::Gs("gs://example/sync/state.json".parse().unwrap()),
+    );
+    assert_eq!(
+        "postgres://localhost/db#sync_state"
+            .parse::<SyncStateStore>()
+            .unwrap(),
+        SyncStateStore::Postgres {
+            url: "postgres://localhost/db#sync_state".parse().unwrap(),
+            table_name: "sync_state".to_owned(),
+        },
+    );
+    assert!("postgres://localhost/db".parse::<SyncStateStore>().is_err());
+    assert!("state.json".parse::<SyncStateStore>().is_err());
+}
+
+#[test]
+fn file_sync_state_store_round_trips() {
+    use crate::tokio_glue::run_futures_with_runtime;
+    use tempdir::TempDir;
+
+    let dir = TempDir::new("sync_state").unwrap();
+    let store = SyncStateStore::File(dir.path().join("state.json"));
+    let (ctx, worker_fut) = Context::create_for_test("sync_state");
+    let cmd_fut = async move {
+        assert_eq!(store.load(&ctx, "a").await?, None);
+        let state = SyncState {
+            high_water_mark: "2020-01-01 00:00:00+00".to_owned(),
+            synced_at: Utc::now(),
+        };
+        store.save(&ctx, "a", &state).await?;
+        store.save(&ctx, "b", &state).await?;
+        assert_eq!(store.load(&ctx, "a").await?, Some(state.clone()));
+        assert_eq!(store.load(&ctx, "b").await?, Some(state));
+        Ok(())
+    };
+    run_futures_with_runtime(cmd_fut.boxed(), worker_fut).unwrap();
+}
@@ -0,0 +1,124 @@
+//! Sending the same CSV streams to more than one destination.
+
+use tokio::sync::mpsc;
+
+use crate::common::*;
+
+/// Split `streams` into `count` identical copies, so that we can write the same
+/// data to several destinations while only reading our source once.
+///
+/// We send each chunk of data to every copy before reading the next chunk, so
+/// the slowest destination sets the pace and we only buffer a few chunks in
+/// memory. This means that destinations must read each `CsvStream` as they
+/// receive it, and that any `CsvStream` which one destination fails to read
+/// will only stop that destination.
+pub fn tee_csv_streams(
+    ctx: Context,
+    count: usize,
+    streams: BoxStream<CsvStream>,
+) -> Vec<BoxStream<CsvStream>> {
+    let ctx = ctx.child(o!("streams_transform" => "tee_csv_streams"));
+    let (mut stream_senders, stream_receivers): (Vec<_>, Vec<_>) = (0..count)
+        .map(|_| mpsc::channel::<Result<CsvStream>>(1))
+        .unzip();
+
+    let worker_ctx = ctx.clone();
+    let worker = async move {
+        let mut streams = streams;
+        while let Some(stream) = streams.next().await {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    send_error_to_all(&mut stream_senders, &err).await;
+                    return Ok(());
+                }
+            };
+            trace!(worker_ctx.log(), "copying stream {}", stream.name);
+
+            // Send a copy of this stream to each destination. If a destination
+            // has gone away, we just stop sending it data. It will report its
+            // own error.
+            let (mut data_senders, data_receivers): (Vec<_>, Vec<_>) = (0..count)
+                .map(|_| mpsc::channel::<Result<BytesMut>>(1))
+                .unzip();
+            for (sender, data) in stream_senders.iter_mut().zip(data_receivers) {
+                let copy = CsvStream {
+                    name: stream.name.clone(),
+                    data: data.boxed(),
+                };
+                if sender.send(Ok(copy)).await.is_err() {
+                    trace!(worker_ctx.log(), "destination closed, skipping");
+                }
+            }
+
+            // Send each chunk of data to every destination.
+            let mut data = stream.data;
+            while let Some(chunk) = data.next().await {
+                match chunk {
+                    Ok(bytes) => {
+                        for sender in &mut data_senders {
+                            // Ignore closed destinations, as above.
+                            let _ = sender.send(Ok(bytes.clone())).await;
+                        }
+                    }
+                    Err(err) => {
+                        send_error_to_all(&mut data_senders, &err).await;
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        trace!(worker_ctx.log(), "finished copying streams");
+        Ok(())
+    };
+    ctx.spawn_worker(worker.boxed());
+
+    stream_receivers.into_iter().map(|r| r.boxed()).collect()
+}
+
+/// Report `err` to every destination, since we can't clone it.
+async fn send_error_to_all<T>(senders: &mut [mpsc::Sender<Result<T>>], err: &Error) {
+    for sender in senders {
+        let _ = sender.send(Err(format_err!("{}", err))).await;
+    }
+}
+
+#[test]
+fn tee_copies_every_stream() {
+    use crate::tokio_glue::run_futures_with_runtime;
+
+    let (ctx, worker_fut) = Context::create_for_test("tee_csv_streams");
+    let cmd_fut = async move {
+        let named = |name: &str, stream: CsvStream| CsvStream {
+            name: name.to_owned(),
+            data: stream.data,
+        };
+        let streams = vec![
+            Ok(named("a", CsvStream::from_bytes(&b"id\n1\n"[..]).await)),
+            Ok(named("b", CsvStream::from_bytes(&b"id\n2\n"[..]).await)),
+        ];
+        let copies = tee_csv_streams(ctx.clone(), 2, stream::iter(streams).boxed());
+
+        // Read both copies at the same time, as real destinations would.
+        let collect_copies = copies.into_iter().map(|mut copy| {
+            let ctx = ctx.clone();
+            async move {
+                let mut outputs = vec![];
+                while let Some(stream) = copy.next().await {
+                    let stream = stream?;
+                    let name = stream.name.clone();
+                    outputs.push((name, stream.into_bytes(ctx.clone()).await?));
+                }
+                Ok::<_, Error>(outputs)
+            }
+        });
+        let outputs = futures::future::try_join_all(collect_copies).await?;
+        let expected = vec![
+            ("a".to_owned(), BytesMut::from(&b"id\n1\n"[..])),
+            ("b".to_owned(), BytesMut::from(&b"id\n2\n"[..])),
+        ];
+        assert_eq!(outputs, vec![expected.clone(), expected]);
+        Ok(())
+    };
+    run_futures_with_runtime(cmd_fut.boxed(), worker_fut).unwrap();
+}
@@ -199,7 +199,9 @@ where
                             trace!(ctx.log(), "sent bytes to stream");
                         }
                         Err(_err) => {
-                            error!(
+                            // Our consumer may have stopped reading on purpose,
+                            // as `head` does once it has enough rows.
+                            debug!(
                                 ctx.log(),
                                 "broken pipe forwarding async data to stream"
                             );
@@ -0,0 +1,84 @@
+//! Appending several sources into a single destination.
+
+use std::collections::HashSet;
+
+use crate::common::*;
+
+/// Combine the CSV streams from several `sources` into a single stream of CSV
+/// streams, reading each source in turn.
+///
+/// Sources often use the same stream names (for example, `data.csv` in one
+/// directory per month), and many destinations use stream names to build
+/// temporary file names. So if we see a name twice, we add a numeric suffix to
+/// it.
+pub fn union_csv_streams(
+    ctx: Context,
+    sources: Vec<BoxStream<CsvStream>>,
+) -> BoxStream<CsvStream> {
+    let ctx = ctx.child(o!("streams_transform" => "union_csv_streams"));
+    let mut seen = HashSet::new();
+    stream::iter(sources)
+        .flatten()
+        .map_ok(move |mut stream| {
+            if !seen.insert(stream.name.clone()) {
+                let name = (2..)
+                    .map(|i| format!("{}_{}", stream.name, i))
+                    .find(|name| !seen.contains(name))
+                    .expect("should always find an unused stream name");
+                debug!(
+                    ctx.log(),
+                    "renaming duplicate stream {} to {}", stream.name, name
+                );
+                seen.insert(name.clone());
+                stream.name = name;
+            }
+            stream
+        })
+        .boxed()
+}
+
+#[test]
+fn union_renames_duplicate_streams() {
+    use crate::tokio_glue::run_futures_with_runtime;
+
+    let (ctx, worker_fut) = Context::create_for_test("union_csv_streams");
+    let cmd_fut = async move {
+        let named = |name: &str, stream: CsvStream| CsvStream {
+            name: name.to_owned(),
+            data: stream.data,
+        };
+        let source_1 = vec![
+            Ok(named("data", CsvStream::from_bytes(&b"id\n1\n"[..]).await)),
+            Ok(named(
+                "data_2",
+                CsvStream::from_bytes(&b"id\n2\n"[..]).await,
+            )),
+        ];
+        let source_2 = vec![Ok(named(
+            "data",
+            CsvStream::from_bytes(&b"id\n3\n"[..]).await,
+        ))];
+        let sources = vec![
+            stream::iter(source_1).boxed(),
+            stream::iter(source_2).boxed(),
+        ];
+
+        let mut outputs = vec![];
+        let mut streams = union_csv_streams(ctx.clone(), sources);
+        while let Some(stream) = streams.next().await {
+            let stream = stream?;
+            let name = stream.name.clone();
+            outputs.push((name, stream.into_bytes(ctx.clone()).await?));
+        }
+        assert_eq!(
+            outputs,
+            vec![
+                ("data".to_owned(), BytesMut::from(&b"id\n1\n"[..])),
+                ("data_2".to_owned(), BytesMut::from(&b"id\n2\n"[..])),
+                ("data_3".to_owned(), BytesMut::from(&b"id\n3\n"[..])),
+            ],
+        );
+        Ok(())
+    };
+    run_futures_with_runtime(cmd_fut.boxed(), worker_fut).unwrap();
+}
@@ -0,0 +1,219 @@
+//! Tracking the resources used by a job, so that we can report on them once
+//! the job finishes.
+
+use serde_derive::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    sync::{Arc, Mutex},
+};
+
+use crate::common::*;
+
+/// Which way data is moving, from the point of view of the local machine.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Direction {
+    /// Data read from a source.
+    Down,
+    /// Data written to a destination.
+    Up,
+}
+
+/// Bytes transferred by a single driver.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct ByteCounts {
+    /// Bytes read from this driver.
+    pub down: u64,
+    /// Bytes written to this driver.
+    pub up: u64,
+}
+
+/// A summary of the resources used by a job.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct UsageReport {
+    /// The peak resident memory of this process, if we know how to measure it
+    /// on this platform.
+    pub peak_memory_bytes: Option<u64>,
+    /// The peak size of our local scratch directory, if we were given one
+    /// using `--scratch-dir`. This is sampled once per second.
+    pub peak_scratch_bytes: Option<u64>,
+    /// Bytes transferred through the local machine, by driver.
+    pub bytes: BTreeMap<String, ByteCounts>,
+    /// Calls made to each cloud service. For services which we access using
+    /// command-line tools, this counts invocations of the tool.
+    pub api_calls: BTreeMap<String, u64>,
+}
+
+impl fmt::Display for UsageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Resource usage:")?;
+        match self.peak_memory_bytes {
+            Some(peak) => writeln!(f, "  peak memory: {}", HumanBytes(peak))?,
+            None => writeln!(f, "  peak memory: unknown")?,
+        }
+        if let Some(peak) = self.peak_scratch_bytes {
+            writeln!(f, "  peak scratch disk: {}", HumanBytes(peak))?;
+        }
+        for (driver, counts) in &self.bytes {
+            writeln!(
+                f,
+                "  {}: {} down, {} up",
+                driver,
+                HumanBytes(counts.down),
+                HumanBytes(counts.up),
+            )?;
+        }
+        for (service, count) in &self.api_calls {
+            writeln!(f, "  {} API calls: {}", service, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Format a byte count for humans.
+struct HumanBytes(u64);
+
+impl fmt::Display for HumanBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+        if self.0 < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+        let mut value = self.0 as f64;
+        let mut unit = "B";
+        for u in UNITS {
+            if value < 1024.0 {
+                break;
+            }
+            value /= 1024.0;
+            unit = u;
+        }
+        write!(f, "{:.1} {}", value, unit)
+    }
+}
+
+/// Shared counters updated by drivers as a job runs. Use
+/// `Context::usage` to get access to this.
+#[derive(Debug, Default)]
+pub struct UsageTracker {
+    report: Mutex<UsageReport>,
+}
+
+impl UsageTracker {
+    /// Record `count` bytes moving in `direction` through `driver`.
+    pub fn record_bytes(&self, driver: &str, direction: Direction, count: u64) {
+        let mut report = self.report.lock().expect("lock poisoned");
+        let counts = report.bytes.entry(driver.to_owned()).or_default();
+        match direction {
+            Direction::Down => counts.down += count,
+            Direction::Up => counts.up += count,
+        }
+    }
+
+    /// How many bytes have moved in `direction` through all drivers?
+    pub fn total_bytes(&self, direction: Direction) -> u64 {
+        let report = self.report.lock().expect("lock poisoned");
+        report
+            .bytes
+            .values()
+            .map(|counts| match direction {
+                Direction::Down => counts.down,
+                Direction::Up => counts.up,
+            })
+            .sum()
+    }
+
+    /// Record a call to `service`.
+    pub fn record_api_call(&self, service: &str) {
+        let mut report = self.report.lock().expect("lock poisoned");
+        *report.api_calls.entry(service.to_owned()).or_default() += 1;
+    }
+
+    /// Record the current size of our scratch directory.
+    pub fn record_scratch_bytes(&self, count: u64) {
+        let mut report = self.report.lock().expect("lock poisoned");
+        let peak = report.peak_scratch_bytes.get_or_insert(0);
+        *peak = (*peak).max(count);
+    }
+
+    /// Summarize the resources used so far.
+    pub fn report(&self) -> UsageReport {
+        let mut report = self.report.lock().expect("lock poisoned").clone();
+        report.peak_memory_bytes = peak_memory_bytes();
+        report
+    }
+}
+
+/// Wrap each stream in `data` so that we count the bytes passing through it.
+/// `driver` should normally be the locator's scheme, without the trailing
+/// colon.
+pub fn count_csv_streams(
+    usage: Arc<UsageTracker>,
+    driver: String,
+    direction: Direction,
+    data: BoxStream<CsvStream>,
+) -> BoxStream<CsvStream> {
+    data.map_ok(move |stream| {
+        let usage = usage.clone();
+        let driver = driver.clone();
+        CsvStream {
+            name: stream.name,
+            data: stream
+                .data
+                .inspect_ok(move |bytes| {
+                    usage.record_bytes(&driver, direction, bytes.len() as u64)
+                })
+                .boxed(),
+        }
+    })
+    .boxed()
+}
+
+/// Get the name of the driver used by `locator`, for use in usage reports.
+pub fn driver_name(locator: &dyn Locator) -> String {
+    let locator = locator.to_string();
+    locator.split(':').next().unwrap_or(&locator).to_owned()
+}
+
+/// Look up the peak resident memory of this process. We only know how to do
+/// this on Linux.
+fn peak_memory_bytes() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    parse_peak_memory(&status)
+}
+
+/// Extract `VmHWM` (the resident memory "high water mark") from the contents
+/// of `/proc/self/status`.
+fn parse_peak_memory(status: &str) -> Option<u64> {
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    let mut fields = line["VmHWM:".len()..].split_whitespace();
+    let value = fields.next()?.parse::<u64>().ok()?;
+    match fields.next() {
+        Some("kB") => Some(value * 1024),
+        _ => None,
+    }
+}
+
+#[test]
+fn parses_peak_memory() {
+    let status = "Name:\tdbcrossbar\nVmPeak:\t  200 kB\nVmHWM:\t    1234 kB\n";
+    assert_eq!(parse_peak_memory(status), Some(1234 * 1024));
+    assert_eq!(parse_peak_memory("Name:\tdbcrossbar\n"), None);
+}
+
+#[test]
+fn report_counts_usage() {
+    let usage = UsageTracker::default();
+    usage.record_bytes("csv", Direction::Down, 10);
+    usage.record_bytes("csv", Direction::Down, 5);
+    usage.record_bytes("bigquery", Direction::Up, 7);
+    usage.record_api_call("bigquery");
+    usage.record_api_call("bigquery");
+    let report = usage.report();
+    assert_eq!(report.bytes["csv"], ByteCounts { down: 15, up: 0 });
+    assert_eq!(report.bytes["bigquery"], ByteCounts { down: 0, up: 7 });
+    assert_eq!(usage.total_bytes(Direction::Down), 15);
+    assert_eq!(usage.total_bytes(Direction::Up), 7);
+    assert_eq!(report.api_calls["bigquery"], 2);
+    assert_eq!(format!("{}", HumanBytes(3 * 1024 * 1024)), "3.0 MiB");
+}
@@ -0,0 +1,313 @@
+//! Checking CSV data against a schema without loading it anywhere.
+//!
+//! Vendor files often contain values which don't match the schema we were
+//! promised, and we'd rather find out before a load fails halfway through (or,
+//! worse, succeeds with mangled data). This module checks that every cell is
+//! valid UTF-8, can be parsed as the column's type, and isn't `NULL` in a
+//! `NOT NULL` column.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use csv;
+use geo_types::Geometry;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use std::{fmt, str};
+use uuid::Uuid;
+
+use crate::check_headers::column_order;
+use crate::common::*;
+use crate::from_csv_cell::FromCsvCell;
+use crate::interval::Interval;
+use crate::network::{Cidr, Inet, MacAddr};
+use crate::schema::{Column, DataType};
+use crate::tokio_glue::{run_sync_fn_in_background, SyncStreamReader};
+
+/// A single invalid cell.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct ValidationFailure {
+    /// The name of the stream containing the cell.
+    pub stream: String,
+    /// The row containing the cell, counting the first row after the header
+    /// as 1.
+    pub row: u64,
+    /// The column containing the cell.
+    pub column: String,
+    /// What's wrong with the cell.
+    pub message: String,
+}
+
+impl fmt::Display for ValidationFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: row {}, column {}: {}",
+            self.stream, self.row, self.column, self.message,
+        )
+    }
+}
+
+/// The result of validating some CSV data.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct ValidationReport {
+    /// The number of rows we checked.
+    pub row_count: u64,
+    /// The total number of invalid cells.
+    pub failure_count: u64,
+    /// Details of the first few invalid cells.
+    pub failures: Vec<ValidationFailure>,
+}
+
+impl ValidationReport {
+    /// Did we find any invalid cells?
+    pub fn is_valid(&self) -> bool {
+        self.failure_count == 0
+    }
+}
+
+/// Check every cell in `streams` against `table`, keeping details of the first
+/// `max_failures` problems. We always read all the data, so that the counts
+/// in our report are accurate.
+pub async fn validate_csv_streams(
+    ctx: Context,
+    table: Table,
+    streams: BoxStream<CsvStream>,
+    max_failures: usize,
+) -> Result<ValidationReport> {
+    let mut report = ValidationReport::default();
+    let mut streams = streams;
+    while let Some(stream) = streams.next().await {
+        let stream = stream?;
+        let name = stream.name.clone();
+        debug!(ctx.log(), "validating stream {}", name);
+        let rdr = SyncStreamReader::new(ctx.clone(), stream.data);
+        let columns = table.columns.clone();
+        report = run_sync_fn_in_background("validate".to_owned(), move || {
+            validate_csv(&name, &columns, max_failures, rdr, report)
+        })
+        .await?;
+    }
+    Ok(report)
+}
+
+/// Check the CSV data in `rdr` against `columns`, adding any problems to
+/// `report`.
+///
+/// This is synchronous, and should be run in a background thread.
+fn validate_csv<R: Read>(
+    stream: &str,
+    columns: &[Column],
+    max_failures: usize,
+    rdr: R,
+    mut report: ValidationReport,
+) -> Result<ValidationReport> {
+    let mut rdr = csv::Reader::from_reader(rdr);
+    let headers = rdr.headers()?.clone();
+    let order = column_order(columns, &headers, true)
+        .with_context(|_| format!("bad CSV header in stream {}", stream))?
+        .unwrap_or_else(|| (0..columns.len()).collect());
+
+    for (row_idx, row) in rdr.byte_records().enumerate() {
+        let row =
+            row.with_context(|_| format!("cannot read CSV from stream {}", stream))?;
+        let row_number = row_idx as u64 + 1;
+        report.row_count += 1;
+        for (col, &idx) in columns.iter().zip(&order) {
+            let cell = row.get(idx).unwrap_or_default();
+            if let Err(err) = validate_cell(col, cell) {
+                report.failure_count += 1;
+                if report.failures.len() < max_failures {
+                    report.failures.push(ValidationFailure {
+                        stream: stream.to_owned(),
+                        row: row_number,
+                        column: col.name.clone(),
+                        message: err.to_string(),
+                    });
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Check a single raw CSV cell against `col`.
+fn validate_cell(col: &Column, cell: &[u8]) -> Result<()> {
+    let cell = str::from_utf8(cell).map_err(|_| format_err!("invalid UTF-8"))?;
+    if cell.is_empty() {
+        if col.is_nullable {
+            Ok(())
+        } else {
+            Err(format_err!("NULL value in NOT NULL column"))
+        }
+    } else {
+        validate_value(&col.data_type, cell)
+    }
+}
+
+/// Check that `cell` can be parsed as `data_type`.
+fn validate_value(data_type: &DataType, cell: &str) -> Result<()> {
+    match data_type {
+        DataType::Array(elem_type) => match Value::from_csv_cell(cell)? {
+            Value::Array(elems) => {
+                for elem in elems {
+                    match elem {
+                        Value::Null => {}
+                        Value::String(s) => validate_value(elem_type, &s)?,
+                        other => validate_value(elem_type, &other.to_string())?,
+                    }
+                }
+                Ok(())
+            }
+            _ => Err(format_err!("expected a JSON array, found {:?}", cell)),
+        },
+        DataType::Bool => parse::<bool>(cell),
+        DataType::Bytes => parse::<Vec<u8>>(cell),
+        DataType::Cidr => parse::<Cidr>(cell),
+        DataType::Date => parse::<NaiveDate>(cell),
+        DataType::Decimal => validate_decimal(cell, None),
+        DataType::DecimalWithPrecision(p) => {
+            validate_decimal(cell, Some(p.precision - p.scale))
+        }
+        DataType::Float32 => parse::<f32>(cell),
+        DataType::Float64 => parse::<f64>(cell),
+        DataType::GeoJson(_) => parse::<Geometry<f64>>(cell),
+        DataType::Inet => parse::<Inet>(cell),
+        DataType::Int16 => parse::<i16>(cell),
+        DataType::Int32 => parse::<i32>(cell),
+        DataType::Int64 => parse::<i64>(cell),
+        DataType::Interval => parse::<Interval>(cell),
+        DataType::Json => parse::<Value>(cell),
+        DataType::MacAddr => parse::<MacAddr>(cell),
+        DataType::OneOf(labels) => {
+            if labels.iter().any(|l| l == cell) {
+                Ok(())
+            } else {
+                Err(format_err!("{:?} is not one of the allowed values", cell))
+            }
+        }
+        DataType::Other(_) | DataType::Text => Ok(()),
+        DataType::Struct(_) => match Value::from_csv_cell(cell)? {
+            Value::Object(_) => Ok(()),
+            _ => Err(format_err!("expected a JSON object, found {:?}", cell)),
+        },
+        DataType::TextWithMaxLength(max_len) => {
+            let len = cell.chars().count();
+            if len <= *max_len as usize {
+                Ok(())
+            } else {
+                Err(format_err!(
+                    "value is {} characters long, but the limit is {}",
+                    len,
+                    max_len,
+                ))
+            }
+        }
+        DataType::Time => parse::<NaiveTime>(cell),
+        DataType::TimestampWithoutTimeZone => parse::<NaiveDateTime>(cell),
+        DataType::TimestampWithTimeZone => parse::<DateTime<Utc>>(cell),
+        DataType::Uuid => parse::<Uuid>(cell),
+    }
+}
+
+/// Check that `cell` can be parsed as `T`.
+fn parse<T: FromCsvCell>(cell: &str) -> Result<()> {
+    T::from_csv_cell(cell)?;
+    Ok(())
+}
+
+/// Check that `cell` is a decimal number with no more than `max_int_digits`
+/// digits before the decimal point.
+fn validate_decimal(cell: &str, max_int_digits: Option<u32>) -> Result<()> {
+    lazy_static! {
+        static ref DECIMAL_RE: Regex =
+            Regex::new(r"^[-+]?([0-9]*)(?:\.[0-9]*)?$").expect("invalid DECIMAL_RE");
+    }
+    let int_digits = DECIMAL_RE
+        .captures(cell)
+        .filter(|_| cell.bytes().any(|b| b.is_ascii_digit()))
+        .map(|caps| caps[1].trim_start_matches('0').len())
+        .ok_or_else(|| format_err!("cannot parse {:?} as decimal", cell))?;
+    match max_int_digits {
+        Some(max) if int_digits > max as usize => Err(format_err!(
+            "{:?} has too many digits before the decimal point",
+            cell,
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[test]
+fn cells_are_validated() {
+    use crate::schema::DecimalPrecision;
+
+    let column = |name: &str, is_nullable: bool, data_type: DataType| Column {
+        name: name.to_owned(),
+        is_nullable,
+        data_type,
+        comment: None,
+        default: None,
+        collation: None,
+    };
+    let columns = vec![
+        column("id", false, DataType::Int32),
+        column("code", true, DataType::TextWithMaxLength(2)),
+        column(
+            "price",
+            true,
+            DataType::DecimalWithPrecision(DecimalPrecision::new(5, 2).unwrap()),
+        ),
+        column("tags", true, DataType::Array(Box::new(DataType::Int16))),
+        column("seen_at", true, DataType::TimestampWithTimeZone),
+    ];
+
+    // Columns may appear in any order.
+    let data = &b"code,id,price,tags,seen_at
+ab,1,123.45,\"[1,2]\",2020-01-01 00:00:00Z
+abc,x,1234.5,\"[1,\"\"a\"\"]\",yesterday
+,,,,
+\xff,2,,,
+"[..];
+    let report =
+        validate_csv("data.csv", &columns, 3, data, ValidationReport::default())
+            .unwrap();
+    assert_eq!(report.row_count, 4);
+    assert_eq!(report.failure_count, 7);
+    assert!(!report.is_valid());
+    let failures = report
+        .failures
+        .iter()
+        .map(|f| (f.row, &f.column[..]))
+        .collect::<Vec<_>>();
+    assert_eq!(failures, vec![(2, "id"), (2, "code"), (2, "price")]);
+    assert_eq!(
+        report.failures[1].to_string(),
+        "data.csv: row 2, column code: value is 3 characters long, but the limit is 2",
+    );
+
+    let report =
+        validate_csv("data.csv", &columns, 10, data, ValidationReport::default())
+            .unwrap();
+    let messages = report
+        .failures
+        .iter()
+        .skip(5)
+        .map(|f| f.message.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        messages,
+        vec![
+            "NULL value in NOT NULL column".to_owned(),
+            "invalid UTF-8".to_owned(),
+        ],
+    );
+
+    assert!(validate_csv(
+        "data.csv",
+        &columns,
+        10,
+        &b"id,other\n1,2\n"[..],
+        ValidationReport::default(),
+    )
+    .is_err());
+}
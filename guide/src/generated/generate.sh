@@ -10,12 +10,13 @@ function dbxb {
     ../../../target/debug/dbcrossbar "$@" 2>&1
 }
 
-for c in cp count conv; do
+for c in cp count conv head ls rm rollback run sync validate; do
     dbxb $c --help > ${c}_help.txt
 done
+dbxb schema diff --help > schema_diff_help.txt
 
 dbxb features > features.txt
 
-for d in bigml bigquery csv gs postgres redshift s3; do
-    dbxb features $d > features_$d.txt
+for d in adbc bigml bigquery csv geopackage gs msgpack mysqldump pg-dump postgres redshift s3 shapefile; do
+    dbxb features $d > features_${d//-/_}.txt
 done
\ No newline at end of file